@@ -1,7 +1,8 @@
-//! Alice PC Client - Polls server for commands and executes them locally
+//! Alice PC Client - Receives commands from the server and executes them locally
 //!
-//! This client connects to the bgalin.ru server, polls for commands from
-//! the Alice Smart Home system, and executes them on the local PC.
+//! This client keeps a WebSocket open to the bgalin.ru server, receives commands
+//! from the Alice Smart Home system as soon as they are queued, and executes them
+//! on the local PC. While the socket is down it falls back to polling.
 //!
 //! Commands supported:
 //! - Shutdown: Shuts down the PC
@@ -10,15 +11,33 @@
 //! - Notification: Shows a desktop notification
 //! - OpenUrl: Opens a URL in the default browser
 //! - Volume: Control system volume (Windows only)
-//! - Screenshot: Takes a screenshot
+//! - Screenshot: Takes a screenshot and uploads it to the server
 //! - Custom: Execute custom commands
+//!
+//! Message types come from the shared `alice-pc-protocol` crate; the heartbeat
+//! announces which commands this build can execute.
+//!
+//! Commands only run when signed by the pinned server key, not expired, not
+//! replayed and allowed by the `[allowlist]` section of the config file.
+
+mod screenshot;
+mod security;
+mod socket;
 
-use chrono::{DateTime, Utc};
+use alice_pc_protocol::{
+    parse_verifying_key, Capability, Command as PcCommand, CommandEnvelope, CommandReport, CommandResult,
+    Handshake, HeartbeatResponse, VolumeAction, PROTOCOL_VERSION,
+};
 use clap::Parser;
+use screenshot::{ScreenshotConfig, Uploader};
+use security::{Allowlist, CommandGuard};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::process::Command;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// Longest wait between WebSocket reconnection attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
 
 /// Alice PC Client - Receives commands from Alice Smart Home
 #[derive(Parser, Debug)]
@@ -32,6 +51,10 @@ struct Args {
     #[arg(short, long, env = "ALICE_PC_API_KEY")]
     api_key: Option<String>,
 
+    /// Server command signing public key (hex), printed by `server pc-keygen`
+    #[arg(long, env = "ALICE_PC_SERVER_PUBLIC_KEY")]
+    server_public_key: Option<String>,
+
     /// Config file path
     #[arg(short, long)]
     config: Option<PathBuf>,
@@ -44,9 +67,17 @@ struct Args {
     #[arg(long)]
     once: bool,
 
+    /// Only poll, never open the WebSocket channel
+    #[arg(long)]
+    no_websocket: bool,
+
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
+
+    /// Capture one screenshot into FILE with the `[screenshot]` settings and exit
+    #[arg(long, value_name = "FILE")]
+    screenshot_to: Option<PathBuf>,
 }
 
 /// Configuration file format
@@ -55,32 +86,11 @@ struct Config {
     server: Option<String>,
     api_key: Option<String>,
     interval: Option<u64>,
-}
-
-/// Command from server
-#[derive(Debug, Deserialize)]
-struct QueuedCommand {
-    id: i64,
-    command_type: String,
-    command_data: serde_json::Value,
-    priority: i32,
-}
-
-/// Result to send back to server
-#[derive(Debug, Serialize)]
-struct CommandResult {
-    command_id: i64,
-    success: bool,
-    result: Option<String>,
-    error: Option<String>,
-}
-
-/// Heartbeat response
-#[derive(Debug, Deserialize)]
-struct HeartbeatResponse {
-    status: String,
-    client_id: String,
-    server_time: String,
+    server_public_key: Option<String>,
+    #[serde(default)]
+    allowlist: Allowlist,
+    #[serde(default)]
+    screenshot: ScreenshotConfig,
 }
 
 #[tokio::main]
@@ -112,11 +122,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    // Capture-only mode, e.g. to check the setup under `xvfb-run`
+    if let Some(path) = &args.screenshot_to {
+        let shot = screenshot::take(&config.screenshot)?;
+        std::fs::write(path, &shot.data)?;
+        println!("Screenshot saved: {} ({})", path.display(), shot.summary());
+        return Ok(());
+    }
+
     // Merge config with args (args take precedence)
     let server = args.server.clone();
     let api_key = args.api_key
         .or(config.api_key)
         .ok_or("API key is required. Set via --api-key or ALICE_PC_API_KEY env var")?;
+    let server_key = args.server_public_key
+        .or(config.server_public_key)
+        .ok_or("Server public key is required. Set server_public_key in the config or ALICE_PC_SERVER_PUBLIC_KEY")?;
+    let server_key = parse_verifying_key(&server_key).map_err(|e| format!("Invalid server public key: {}", e))?;
+    let mut guard = CommandGuard::new(server_key, config.allowlist);
     let interval = args.interval;
 
     println!("=== Alice PC Client ===");
@@ -127,36 +150,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client = reqwest::Client::builder()
         .timeout(Duration::from_secs(30))
         .build()?;
+    let uploader = Uploader {
+        client: client.clone(),
+        server: server.clone(),
+        api_key: api_key.clone(),
+        config: config.screenshot,
+    };
 
-    // Initial heartbeat
-    if let Err(e) = send_heartbeat(&client, &server, &api_key).await {
-        eprintln!("Warning: Initial heartbeat failed: {}", e);
-    }
+    // The server only hands out commands after the capability handshake
+    let mut handshake_done = false;
+    let mut backoff = Duration::from_secs(1);
 
     loop {
-        // Poll for commands
-        match poll_commands(&client, &server, &api_key, args.verbose).await {
-            Ok(commands) => {
-                for cmd in commands {
-                    if args.verbose {
-                        println!("Processing command {} (type: {})", cmd.id, cmd.command_type);
-                    }
-
-                    let result = execute_command(&cmd).await;
-
-                    // Report result
-                    if let Err(e) = report_result(&client, &server, &api_key, result).await {
-                        eprintln!("Failed to report result: {}", e);
-                    }
+        if !args.once && !args.no_websocket {
+            match socket::run(&server, &api_key, &mut guard, &uploader, args.verbose).await {
+                Ok(()) => {
+                    handshake_done = true;
+                    backoff = Duration::from_secs(1);
                 }
+                Err(e) => eprintln!("WebSocket unavailable: {}", e),
             }
-            Err(e) => {
-                if args.verbose {
-                    eprintln!("Poll failed: {}", e);
-                }
+
+            // Keep polling until it is time to reconnect
+            let reconnect_at = Instant::now() + backoff;
+            println!("Reconnecting in {}s, polling meanwhile", backoff.as_secs());
+            while Instant::now() < reconnect_at {
+                poll_cycle(&client, &server, &api_key, &mut guard, &uploader, &mut handshake_done, args.verbose).await;
+                let remaining = reconnect_at.saturating_duration_since(Instant::now());
+                tokio::time::sleep(remaining.min(Duration::from_secs(interval))).await;
             }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            continue;
         }
 
+        poll_cycle(&client, &server, &api_key, &mut guard, &uploader, &mut handshake_done, args.verbose).await;
+
         if args.once {
             break;
         }
@@ -167,22 +195,98 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-/// Send heartbeat to server
+/// One polling round: handshake if still needed, then fetch, execute and report commands
+async fn poll_cycle(
+    client: &reqwest::Client,
+    server: &str,
+    api_key: &str,
+    guard: &mut CommandGuard,
+    uploader: &Uploader,
+    handshake_done: &mut bool,
+    verbose: bool,
+) {
+    if !*handshake_done {
+        match send_heartbeat(client, server, api_key, guard.capabilities()).await {
+            Ok(_) => *handshake_done = true,
+            Err(e) => eprintln!("Warning: Heartbeat failed: {}", e),
+        }
+    }
+
+    // Poll for commands
+    match poll_commands(client, server, api_key, verbose).await {
+        Ok(commands) => {
+            for cmd in commands {
+                let result = match serde_json::from_value::<CommandEnvelope>(cmd.clone()) {
+                    Ok(envelope) => {
+                        if verbose {
+                            println!("Processing command {}", envelope.id);
+                        }
+                        execute_command(&envelope, guard, uploader).await
+                    }
+                    // Sent by a newer server; tell it instead of leaving the command stuck
+                    Err(e) => match cmd.get("id").and_then(|v| v.as_i64()) {
+                        Some(command_id) => CommandReport {
+                            command_id,
+                            result: CommandResult::Unsupported {
+                                reason: format!("Cannot decode command: {}", e),
+                            },
+                        },
+                        None => {
+                            eprintln!("Skipping malformed command: {}", e);
+                            continue;
+                        }
+                    },
+                };
+
+                // Report result
+                if let Err(e) = report_result(client, server, api_key, result).await {
+                    eprintln!("Failed to report result: {}", e);
+                }
+            }
+        }
+        Err(e) => {
+            if verbose {
+                eprintln!("Poll failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Handshake sent in the HTTP heartbeat and as the first WebSocket frame
+pub(crate) fn handshake(capabilities: Vec<Capability>) -> Handshake {
+    Handshake {
+        protocol_version: PROTOCOL_VERSION,
+        client_version: env!("CARGO_PKG_VERSION").to_string(),
+        capabilities,
+    }
+}
+
+/// Send heartbeat with the capability handshake to server
 async fn send_heartbeat(
     client: &reqwest::Client,
     server: &str,
     api_key: &str,
+    capabilities: Vec<Capability>,
 ) -> Result<HeartbeatResponse, Box<dyn std::error::Error>> {
     let url = format!("{}/api/alice/pc/heartbeat", server);
+    let handshake = handshake(capabilities);
 
     let response = client
         .post(&url)
         .header("X-API-Key", api_key)
+        .json(&handshake)
         .send()
         .await?;
 
     if response.status().is_success() {
         let heartbeat: HeartbeatResponse = response.json().await?;
+        if heartbeat.status == "unsupported_protocol" {
+            return Err(format!(
+                "Server speaks protocol v{}, this client v{}; update the client",
+                heartbeat.protocol_version, PROTOCOL_VERSION
+            )
+            .into());
+        }
         println!("Connected to server (client: {})", heartbeat.client_id);
         Ok(heartbeat)
     } else {
@@ -190,13 +294,13 @@ async fn send_heartbeat(
     }
 }
 
-/// Poll server for pending commands
+/// Poll server for pending commands, decoded one by one by the caller
 async fn poll_commands(
     client: &reqwest::Client,
     server: &str,
     api_key: &str,
     verbose: bool,
-) -> Result<Vec<QueuedCommand>, Box<dyn std::error::Error>> {
+) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
     let url = format!("{}/api/alice/pc/poll", server);
 
     let response = client
@@ -206,7 +310,7 @@ async fn poll_commands(
         .await?;
 
     if response.status().is_success() {
-        let commands: Vec<QueuedCommand> = response.json().await?;
+        let commands: Vec<serde_json::Value> = response.json().await?;
         if !commands.is_empty() {
             println!("Received {} command(s)", commands.len());
         } else if verbose {
@@ -225,91 +329,78 @@ async fn report_result(
     client: &reqwest::Client,
     server: &str,
     api_key: &str,
-    result: CommandResult,
+    report: CommandReport,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let url = format!("{}/api/alice/pc/result", server);
 
     let response = client
         .post(&url)
         .header("X-API-Key", api_key)
-        .json(&result)
+        .json(&report)
         .send()
         .await?;
 
     if response.status().is_success() {
-        if result.success {
-            println!("Command {} completed successfully", result.command_id);
-        } else {
-            println!("Command {} failed: {}", result.command_id, result.error.unwrap_or_default());
-        }
+        print_report(&report);
         Ok(())
     } else {
         Err(format!("Report failed: {}", response.status()).into())
     }
 }
 
-/// Execute a command locally
-async fn execute_command(cmd: &QueuedCommand) -> CommandResult {
-    let result = match cmd.command_type.as_str() {
-        "pc_shutdown" | "Shutdown" => execute_shutdown().await,
-        "pc_restart" | "Restart" => execute_restart().await,
-        "pc_lock" | "Lock" => execute_lock().await,
-        "Notification" => {
-            let title = cmd.command_data.get("title")
-                .and_then(|v| v.as_str())
-                .unwrap_or("Alice");
-            let message = cmd.command_data.get("message")
-                .and_then(|v| v.as_str())
-                .unwrap_or("Notification from Alice");
-            execute_notification(title, message).await
-        }
-        "OpenUrl" => {
-            let url = cmd.command_data.get("url")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            execute_open_url(url).await
-        }
-        "Screenshot" => execute_screenshot().await,
-        "Volume" => {
-            let action = cmd.command_data.get("action")
-                .and_then(|v| v.as_str())
-                .unwrap_or("mute");
-            let value = cmd.command_data.get("value")
-                .and_then(|v| v.as_i64())
-                .map(|v| v as i32);
-            execute_volume(action, value).await
-        }
-        "RunCommand" => {
-            let command = cmd.command_data.get("command")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            let args: Vec<String> = cmd.command_data.get("args")
-                .and_then(|v| v.as_array())
-                .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
-                .unwrap_or_default();
-            execute_run_command(command, &args).await
-        }
-        "Custom" => {
-            let name = cmd.command_data.get("name")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            execute_custom(name, &cmd.command_data).await
+/// Print the outcome of a reported command
+pub(crate) fn print_report(report: &CommandReport) {
+    match &report.result {
+        CommandResult::Completed { .. } => println!("Command {} completed successfully", report.command_id),
+        CommandResult::Failed { error } => println!("Command {} failed: {}", report.command_id, error),
+        CommandResult::Unsupported { reason } => println!("Command {} unsupported: {}", report.command_id, reason),
+        CommandResult::Rejected { reason } => println!("Command {} rejected: {}", report.command_id, reason),
+    }
+}
+
+/// Verify and execute a command locally
+pub(crate) async fn execute_command(
+    cmd: &CommandEnvelope,
+    guard: &mut CommandGuard,
+    uploader: &Uploader,
+) -> CommandReport {
+    if cmd.version != PROTOCOL_VERSION {
+        return CommandReport {
+            command_id: cmd.id,
+            result: CommandResult::Unsupported {
+                reason: format!("Protocol v{} is not supported (client speaks v{})", cmd.version, PROTOCOL_VERSION),
+            },
+        };
+    }
+
+    let command = match guard.check(cmd) {
+        Ok(command) => command,
+        Err(reason) => {
+            eprintln!("Rejected command {}: {}", cmd.id, reason);
+            return CommandReport {
+                command_id: cmd.id,
+                result: CommandResult::Rejected { reason },
+            };
         }
-        _ => Err(format!("Unknown command type: {}", cmd.command_type)),
     };
 
-    match result {
-        Ok(msg) => CommandResult {
-            command_id: cmd.id,
-            success: true,
-            result: Some(msg),
-            error: None,
-        },
-        Err(e) => CommandResult {
-            command_id: cmd.id,
-            success: false,
-            result: None,
-            error: Some(e),
+    let result = match &command {
+        PcCommand::Shutdown => execute_shutdown().await,
+        PcCommand::Restart => execute_restart().await,
+        PcCommand::Lock => execute_lock().await,
+        PcCommand::Notification { title, message } => execute_notification(title, message).await,
+        PcCommand::OpenUrl { url } => execute_open_url(url).await,
+        PcCommand::Screenshot => screenshot::execute(cmd.id, uploader).await,
+        PcCommand::Volume { action, value } => execute_volume(*action, *value).await,
+        PcCommand::RunCommand { command, args } => execute_run_command(command, args).await,
+        PcCommand::Custom { name, payload } => execute_custom(name, payload).await,
+    };
+
+    CommandReport {
+        command_id: cmd.id,
+        result: match result {
+            Ok(message) => CommandResult::Completed { message },
+            Err(error) => CommandResult::Failed { error },
         },
     }
 }
@@ -494,58 +585,11 @@ async fn execute_open_url(url: &str) -> Result<String, String> {
     Ok(format!("Opened URL: {}", url))
 }
 
-async fn execute_screenshot() -> Result<String, String> {
-    println!("Executing: SCREENSHOT");
-
-    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
-    let filename = format!("screenshot_{}.png", timestamp);
-
-    #[cfg(target_os = "windows")]
-    {
-        // Use PowerShell to take screenshot
-        let script = format!(
-            r#"Add-Type -AssemblyName System.Windows.Forms
-$screen = [System.Windows.Forms.Screen]::PrimaryScreen.Bounds
-$bitmap = New-Object System.Drawing.Bitmap($screen.Width, $screen.Height)
-$graphics = [System.Drawing.Graphics]::FromImage($bitmap)
-$graphics.CopyFromScreen($screen.Location, [System.Drawing.Point]::Empty, $screen.Size)
-$bitmap.Save('{}')
-$graphics.Dispose()
-$bitmap.Dispose()"#,
-            filename
-        );
-
-        Command::new("powershell")
-            .args(["-Command", &script])
-            .output()
-            .map_err(|e| format!("Failed to take screenshot: {}", e))?;
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        Command::new("gnome-screenshot")
-            .args(["-f", &filename])
-            .output()
-            .or_else(|_| Command::new("scrot").arg(&filename).output())
-            .map_err(|e| format!("Failed to take screenshot: {}", e))?;
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        Command::new("screencapture")
-            .arg(&filename)
-            .output()
-            .map_err(|e| format!("Failed to take screenshot: {}", e))?;
-    }
-
-    Ok(format!("Screenshot saved: {}", filename))
-}
-
-async fn execute_volume(action: &str, value: Option<i32>) -> Result<String, String> {
-    println!("Executing: VOLUME - {} {:?}", action, value);
+async fn execute_volume(action: VolumeAction, value: Option<i32>) -> Result<String, String> {
+    println!("Executing: VOLUME - {:?} {:?}", action, value);
 
     match action {
-        "mute" | "toggle_mute" => {
+        VolumeAction::Mute | VolumeAction::ToggleMute => {
             #[cfg(target_os = "windows")]
             {
                 // Use nircmd or PowerShell
@@ -573,7 +617,7 @@ async fn execute_volume(action: &str, value: Option<i32>) -> Result<String, Stri
 
             Ok("Volume muted/unmuted".to_string())
         }
-        "set" => {
+        VolumeAction::Set => {
             let vol = value.unwrap_or(50).clamp(0, 100);
 
             #[cfg(target_os = "windows")]
@@ -607,7 +651,6 @@ async fn execute_volume(action: &str, value: Option<i32>) -> Result<String, Stri
 
             Ok(format!("Volume set to {}%", vol))
         }
-        _ => Err(format!("Unknown volume action: {}", action)),
     }
 }
 
@@ -638,16 +681,14 @@ async fn execute_run_command(command: &str, args: &[String]) -> Result<String, S
     }
 }
 
-async fn execute_custom(name: &str, data: &serde_json::Value) -> Result<String, String> {
-    println!("Executing: CUSTOM - {} : {:?}", name, data);
+async fn execute_custom(name: &str, payload: &serde_json::Value) -> Result<String, String> {
+    println!("Executing: CUSTOM - {} : {:?}", name, payload);
 
     // Custom commands can be extended here
     match name {
         "ping" => Ok("pong".to_string()),
         "echo" => {
-            let msg = data.get("payload")
-                .and_then(|v| v.as_str())
-                .unwrap_or("echo");
+            let msg = payload.as_str().unwrap_or("echo");
             Ok(msg.to_string())
         }
         _ => Err(format!("Unknown custom command: {}", name)),
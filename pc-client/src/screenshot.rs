@@ -0,0 +1,435 @@
+//! Screenshot capture: grab the screen with the platform's tools, optionally
+//! downscale and recompress it, and upload it to the server as the artifact of
+//! the command that asked for it.
+
+use alice_pc_protocol::ARTIFACTS_UNAVAILABLE;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// `[screenshot]` section of the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScreenshotConfig {
+    /// Downscale wider screenshots to this width, keeping the aspect ratio
+    pub max_width: Option<u32>,
+    pub format: ScreenshotFormat,
+    /// JPEG quality, 1-100
+    pub quality: u8,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        ScreenshotConfig {
+            max_width: None,
+            format: ScreenshotFormat::Png,
+            quality: 80,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreenshotFormat {
+    Png,
+    Jpeg,
+}
+
+impl ScreenshotFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "image/png",
+            ScreenshotFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "png",
+            ScreenshotFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// Encoded screenshot ready to upload or save
+pub struct Screenshot {
+    pub data: Vec<u8>,
+    pub format: ScreenshotFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Screenshot {
+    /// Short description used in command results, e.g. "1920x1080 PNG, 412 KB"
+    pub fn summary(&self) -> String {
+        format!(
+            "{}x{} {}, {} KB",
+            self.width,
+            self.height,
+            format!("{:?}", self.format).to_uppercase(),
+            self.data.len().div_ceil(1024)
+        )
+    }
+}
+
+/// Capture the screen and apply the configured scaling and format
+pub fn take(config: &ScreenshotConfig) -> Result<Screenshot, String> {
+    let png = capture()?;
+    process(png, config)
+}
+
+/// Capture the whole screen as PNG bytes
+fn capture() -> Result<Vec<u8>, String> {
+    let path = std::env::temp_dir().join(format!(
+        "alice-screenshot-{}-{}.png",
+        std::process::id(),
+        chrono::Local::now().format("%Y%m%d%H%M%S%3f")
+    ));
+    let result = capture_to(&path).and_then(|()| {
+        std::fs::read(&path).map_err(|e| format!("Failed to read screenshot: {}", e))
+    });
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+#[cfg(target_os = "windows")]
+fn capture_to(path: &Path) -> Result<(), String> {
+    let script = format!(
+        r#"Add-Type -AssemblyName System.Windows.Forms
+Add-Type -AssemblyName System.Drawing
+$screen = [System.Windows.Forms.Screen]::PrimaryScreen.Bounds
+$bitmap = New-Object System.Drawing.Bitmap($screen.Width, $screen.Height)
+$graphics = [System.Drawing.Graphics]::FromImage($bitmap)
+$graphics.CopyFromScreen($screen.Location, [System.Drawing.Point]::Empty, $screen.Size)
+$bitmap.Save('{}', [System.Drawing.Imaging.ImageFormat]::Png)
+$graphics.Dispose()
+$bitmap.Dispose()"#,
+        path.display().to_string().replace('\'', "''")
+    );
+    run_first(&[("powershell", vec!["-NoProfile".into(), "-Command".into(), script])], path)
+}
+
+#[cfg(target_os = "macos")]
+fn capture_to(path: &Path) -> Result<(), String> {
+    run_first(&[("screencapture", vec!["-x".into(), path_arg(path)])], path)
+}
+
+/// Wayland sessions use grim, X11 displays (including a virtual framebuffer
+/// such as `Xvfb :99` with `DISPLAY=:99`) the first installed X11 tool
+#[cfg(target_os = "linux")]
+fn capture_to(path: &Path) -> Result<(), String> {
+    let file = path_arg(path);
+    let mut tools: Vec<(&str, Vec<String>)> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.push(("grim", vec![file.clone()]));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        tools.push(("maim", vec![file.clone()]));
+        tools.push(("scrot", vec![file.clone()]));
+        tools.push(("import", vec!["-window".into(), "root".into(), file.clone()]));
+    }
+    if tools.is_empty() {
+        return Err("No display to capture: set DISPLAY (X11, Xvfb) or WAYLAND_DISPLAY".to_string());
+    }
+    tools.push(("gnome-screenshot", vec!["-f".into(), file]));
+    run_first(&tools, path)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn capture_to(_path: &Path) -> Result<(), String> {
+    Err("Screenshots are not supported on this platform".to_string())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn path_arg(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Run the tools in order until one writes a non-empty `path`
+fn run_first(tools: &[(&str, Vec<String>)], path: &Path) -> Result<(), String> {
+    let mut errors = Vec::new();
+    for (program, args) in tools {
+        match Command::new(program).args(args).output() {
+            Ok(output) if output.status.success() && written(path) => return Ok(()),
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let reason = stderr.trim().lines().last().unwrap_or_default();
+                errors.push(format!("{}: {} {}", program, output.status, reason).trim_end().to_string());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                errors.push(format!("{}: not installed", program));
+            }
+            Err(e) => errors.push(format!("{}: {}", program, e)),
+        }
+    }
+    Err(format!("Failed to take screenshot ({})", errors.join("; ")))
+}
+
+fn written(path: &Path) -> bool {
+    std::fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false)
+}
+
+/// Downscale to `max_width` and re-encode to the configured format; a PNG
+/// that needs neither is passed through untouched
+fn process(png: Vec<u8>, config: &ScreenshotConfig) -> Result<Screenshot, String> {
+    let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+        .map_err(|e| format!("Captured file is not a PNG image: {}", e))?;
+    let resize_to = config.max_width.filter(|w| *w > 0 && image.width() > *w);
+
+    if resize_to.is_none() && config.format == ScreenshotFormat::Png {
+        return Ok(Screenshot {
+            width: image.width(),
+            height: image.height(),
+            data: png,
+            format: ScreenshotFormat::Png,
+        });
+    }
+
+    let image = match resize_to {
+        Some(width) => image.resize(width, u32::MAX, FilterType::Triangle),
+        None => image,
+    };
+
+    let mut data = Vec::new();
+    match config.format {
+        ScreenshotFormat::Png => image
+            .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode PNG: {}", e))?,
+        ScreenshotFormat::Jpeg => JpegEncoder::new_with_quality(&mut data, config.quality.clamp(1, 100))
+            .encode_image(&image.to_rgb8())
+            .map_err(|e| format!("Failed to encode JPEG: {}", e))?,
+    }
+
+    Ok(Screenshot {
+        width: image.width(),
+        height: image.height(),
+        data,
+        format: config.format,
+    })
+}
+
+/// Server connection used to upload command artifacts
+pub struct Uploader {
+    pub client: reqwest::Client,
+    pub server: String,
+    pub api_key: String,
+    pub config: ScreenshotConfig,
+}
+
+impl Uploader {
+    /// Upload `shot` as the artifact of `command_id`; `None` if the server is
+    /// too old to accept artifacts (a 404 without the route's `error` message)
+    /// or cannot store them (a 501 with `ARTIFACTS_UNAVAILABLE`)
+    async fn upload(&self, command_id: i64, shot: &Screenshot) -> Result<Option<String>, String> {
+        let url = format!("{}/api/alice/pc/commands/{}/artifact", self.server, command_id);
+        let response = self
+            .client
+            .post(&url)
+            .header("X-API-Key", &self.api_key)
+            .header(reqwest::header::CONTENT_TYPE, shot.format.mime_type())
+            .body(shot.data.clone())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let status = response.status();
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        if status == reqwest::StatusCode::NOT_FOUND && !body["error"].is_string() {
+            return Ok(None);
+        }
+        if status == reqwest::StatusCode::NOT_IMPLEMENTED && body["code"] == ARTIFACTS_UNAVAILABLE {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let error = body["error"].as_str().unwrap_or_default();
+            return Err(format!("{} {}", status, error).trim_end().to_string());
+        }
+        Ok(Some(body["file_id"].as_str().unwrap_or_default().to_string()))
+    }
+}
+
+/// `Screenshot` command: capture, then upload as the command's artifact. Falls
+/// back to saving into the working directory if the server cannot take it.
+pub async fn execute(command_id: i64, uploader: &Uploader) -> Result<String, String> {
+    println!("Executing: SCREENSHOT");
+
+    let config = uploader.config.clone();
+    let shot = tokio::task::spawn_blocking(move || take(&config))
+        .await
+        .map_err(|e| format!("Screenshot task failed: {}", e))??;
+
+    match uploader.upload(command_id, &shot).await {
+        Ok(Some(_)) => Ok(format!("Screenshot uploaded: {}", shot.summary())),
+        Ok(None) => {
+            let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+            let filename = PathBuf::from(format!("screenshot_{}.{}", timestamp, shot.format.extension()));
+            std::fs::write(&filename, &shot.data).map_err(|e| format!("Failed to save screenshot: {}", e))?;
+            Ok(format!(
+                "Screenshot saved: {} ({}; server does not accept uploads)",
+                filename.display(),
+                shot.summary()
+            ))
+        }
+        Err(e) => Err(format!("Failed to upload screenshot: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba, RgbaImage};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_fn(width, height, |x, y| Rgba([(x % 256) as u8, (y % 256) as u8, 128, 255]));
+        let mut data = Vec::new();
+        image.write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png).unwrap();
+        data
+    }
+
+    fn config(max_width: Option<u32>, format: ScreenshotFormat) -> ScreenshotConfig {
+        ScreenshotConfig { max_width, format, quality: 70 }
+    }
+
+    #[test]
+    fn png_within_limits_passes_through_untouched() {
+        let original = png(64, 48);
+        for max_width in [None, Some(0), Some(64), Some(100)] {
+            let shot = process(original.clone(), &config(max_width, ScreenshotFormat::Png)).unwrap();
+            assert_eq!(shot.data, original);
+            assert_eq!((shot.width, shot.height), (64, 48));
+        }
+    }
+
+    #[test]
+    fn wide_screenshots_are_downscaled_keeping_aspect_ratio() {
+        let shot = process(png(400, 300), &config(Some(200), ScreenshotFormat::Png)).unwrap();
+        assert_eq!((shot.width, shot.height), (200, 150));
+        let decoded = image::load_from_memory_with_format(&shot.data, image::ImageFormat::Png).unwrap();
+        assert_eq!(decoded.dimensions(), (200, 150));
+    }
+
+    #[test]
+    fn jpeg_output_is_recompressed() {
+        let original = png(320, 240);
+        let shot = process(original.clone(), &config(Some(160), ScreenshotFormat::Jpeg)).unwrap();
+        assert_eq!(shot.format, ScreenshotFormat::Jpeg);
+        assert_eq!(image::guess_format(&shot.data).unwrap(), image::ImageFormat::Jpeg);
+        assert_eq!(image::load_from_memory(&shot.data).unwrap().dimensions(), (160, 120));
+        assert!(shot.summary().starts_with("160x120 JPEG, "));
+
+        // Full size too: the format alone asks for a re-encode
+        let full = process(original, &config(None, ScreenshotFormat::Jpeg)).unwrap();
+        assert_eq!((full.width, full.height), (320, 240));
+        assert_eq!(image::guess_format(&full.data).unwrap(), image::ImageFormat::Jpeg);
+    }
+
+    #[test]
+    fn captured_file_must_be_a_png() {
+        assert!(process(b"not an image".to_vec(), &config(None, ScreenshotFormat::Png)).is_err());
+    }
+
+    /// Capture on a virtual framebuffer: `Xvfb :99 & DISPLAY=:99 cargo test -- --ignored`
+    /// (needs maim, scrot or ImageMagick's import)
+    #[test]
+    #[ignore]
+    fn capture_works_on_a_virtual_framebuffer() {
+        let shot = take(&config(Some(320), ScreenshotFormat::Jpeg)).unwrap();
+        assert!(shot.width > 0 && shot.width <= 320);
+        assert_eq!(image::guess_format(&shot.data).unwrap(), image::ImageFormat::Jpeg);
+    }
+
+    /// Server answering every request with `status` and `body`; keeps the request heads
+    async fn stub(status: u16, body: String) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let heads = Arc::new(Mutex::new(Vec::new()));
+        let recorded = heads.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut chunk = [0u8; 4096];
+                // Read the head, then the body announced by Content-Length
+                let (head, length) = loop {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    request.extend_from_slice(&chunk[..n]);
+                    if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        let head = String::from_utf8_lossy(&request[..end]).to_lowercase();
+                        let length = head
+                            .lines()
+                            .find_map(|l| l.strip_prefix("content-length:"))
+                            .and_then(|v| v.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        break (head, end + 4 + length);
+                    }
+                };
+                while request.len() < length {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    request.extend_from_slice(&chunk[..n]);
+                }
+                recorded.lock().unwrap().push(head);
+                let response = format!(
+                    "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, heads)
+    }
+
+    async fn upload_to(status: u16, body: &str) -> Result<Option<String>, String> {
+        let (server, heads) = stub(status, body.to_string()).await;
+        let uploader = Uploader {
+            client: reqwest::Client::new(),
+            server,
+            api_key: "key".to_string(),
+            config: ScreenshotConfig::default(),
+        };
+        let shot = Screenshot { data: png(8, 8), format: ScreenshotFormat::Png, width: 8, height: 8 };
+        let result = uploader.upload(7, &shot).await;
+
+        let heads = heads.lock().unwrap();
+        assert_eq!(heads.len(), 1);
+        assert!(heads[0].starts_with("post /api/alice/pc/commands/7/artifact "));
+        assert!(heads[0].contains("x-api-key: key"));
+        assert!(heads[0].contains("content-type: image/png"));
+        result
+    }
+
+    #[tokio::test]
+    async fn accepted_upload_returns_the_file_id() {
+        assert_eq!(upload_to(200, r#"{"file_id": "f1"}"#).await, Ok(Some("f1".to_string())));
+    }
+
+    #[tokio::test]
+    async fn missing_route_falls_back_to_saving_locally() {
+        assert_eq!(upload_to(404, "").await, Ok(None));
+        assert_eq!(upload_to(404, r#"{"status": 404}"#).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn server_without_artifact_storage_falls_back_to_saving_locally() {
+        let body = format!(r#"{{"error": "not on PostgreSQL", "code": "{}"}}"#, ARTIFACTS_UNAVAILABLE);
+        assert_eq!(upload_to(501, &body).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn other_failures_are_reported() {
+        assert_eq!(
+            upload_to(404, r#"{"error": "Command not found"}"#).await,
+            Err("404 Not Found Command not found".to_string())
+        );
+        assert_eq!(upload_to(501, r#"{"error": "Not implemented"}"#).await, Err("501 Not Implemented Not implemented".to_string()));
+        assert_eq!(upload_to(403, r#"{"error": "Forbidden"}"#).await, Err("403 Forbidden Forbidden".to_string()));
+        assert_eq!(upload_to(500, "").await, Err("500 Internal Server Error".to_string()));
+        assert_eq!(upload_to(413, "too big").await, Err("413 Payload Too Large".to_string()));
+    }
+}
@@ -0,0 +1,126 @@
+//! Checks every command must pass before it runs: server signature, expiry,
+//! single use of its nonce and the local allowlist.
+
+use alice_pc_protocol::{Capability, Command as PcCommand, CommandEnvelope, VerifyingKey, MAX_COMMAND_TTL_SECS};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// `[allowlist]` section of the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Allowlist {
+    /// Command types that may run
+    #[serde(default = "default_commands")]
+    pub commands: Vec<Capability>,
+    /// Programs `RunCommand` may start, matched exactly against the command
+    #[serde(default)]
+    pub executables: Vec<String>,
+}
+
+impl Default for Allowlist {
+    fn default() -> Self {
+        Allowlist {
+            commands: default_commands(),
+            executables: Vec::new(),
+        }
+    }
+}
+
+/// Everything except `RunCommand`, which has to be enabled explicitly
+fn default_commands() -> Vec<Capability> {
+    Capability::ALL
+        .into_iter()
+        .filter(|c| *c != Capability::RunCommand)
+        .collect()
+}
+
+/// Nonces of executed commands, kept on disk until they expire so a restart
+/// does not reopen the replay window
+struct NonceStore {
+    path: Option<PathBuf>,
+    seen: HashMap<String, i64>,
+}
+
+impl NonceStore {
+    fn load() -> Self {
+        let path = dirs::data_local_dir().map(|p| p.join("alice-pc-client").join("nonces.json"));
+        let seen = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        NonceStore { path, seen }
+    }
+
+    /// Record `nonce`; fails if it was already used
+    fn claim(&mut self, nonce: &str, expires_at: i64, now: i64) -> Result<(), String> {
+        self.seen.retain(|_, expiry| *expiry >= now);
+        if self.seen.contains_key(nonce) {
+            return Err("Replayed command (nonce already used)".to_string());
+        }
+        self.seen.insert(nonce.to_string(), expires_at);
+        self.save();
+        Ok(())
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else { return };
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, serde_json::to_string(&self.seen).unwrap_or_default()));
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to save nonce store {}: {}", path.display(), e);
+        }
+    }
+}
+
+pub struct CommandGuard {
+    server_key: VerifyingKey,
+    allowlist: Allowlist,
+    nonces: NonceStore,
+}
+
+impl CommandGuard {
+    pub fn new(server_key: VerifyingKey, allowlist: Allowlist) -> Self {
+        CommandGuard {
+            server_key,
+            allowlist,
+            nonces: NonceStore::load(),
+        }
+    }
+
+    /// Capabilities to announce in the handshake: only what the allowlist permits
+    pub fn capabilities(&self) -> Vec<Capability> {
+        Capability::ALL
+            .into_iter()
+            .filter(|c| self.allowlist.commands.contains(c))
+            .collect()
+    }
+
+    /// Verify an envelope and return its command, or the reason to reject it
+    pub fn check(&mut self, envelope: &CommandEnvelope) -> Result<PcCommand, String> {
+        let signed = envelope.open(&self.server_key)?;
+
+        let now = chrono::Utc::now().timestamp();
+        if signed.expires_at < now {
+            return Err("Command expired".to_string());
+        }
+        if signed.expires_at > now + MAX_COMMAND_TTL_SECS {
+            return Err("Command expiry too far in the future".to_string());
+        }
+
+        let capability = signed.command.capability();
+        if !self.allowlist.commands.contains(&capability) {
+            return Err(format!("{} is not allowlisted", capability));
+        }
+        if let PcCommand::RunCommand { command, .. } = &signed.command {
+            if !self.allowlist.executables.iter().any(|e| e == command) {
+                return Err(format!("Executable {} is not allowlisted", command));
+            }
+        }
+
+        self.nonces.claim(&signed.nonce, signed.expires_at, now)?;
+        Ok(signed.command)
+    }
+}
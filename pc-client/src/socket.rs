@@ -0,0 +1,107 @@
+//! Persistent WebSocket channel: the server pushes commands as soon as they are
+//! queued, the client acks each one and sends the result back on the same socket.
+
+use crate::screenshot::Uploader;
+use crate::security::CommandGuard;
+use crate::{execute_command, handshake, print_report};
+use alice_pc_protocol::{ClientMessage, ServerMessage, PROTOCOL_VERSION};
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Seconds between heartbeat frames keeping the client marked online
+const HEARTBEAT_SECS: u64 = 30;
+
+/// Connect and serve commands until the connection drops. Fails only when the
+/// connection or the handshake could not be established.
+pub async fn run(
+    server: &str,
+    api_key: &str,
+    guard: &mut CommandGuard,
+    uploader: &Uploader,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // https:// -> wss://, http:// -> ws://
+    let url = format!("{}/api/alice/pc/ws", server.replacen("http", "ws", 1));
+    let mut request = url.as_str().into_client_request()?;
+    request.headers_mut().insert("X-API-Key", api_key.parse()?);
+    let (mut ws, _) = tokio_tungstenite::connect_async(request).await?;
+
+    let hello = ClientMessage::Hello(handshake(guard.capabilities()));
+    ws.send(Message::Text(serde_json::to_string(&hello)?)).await?;
+
+    let welcome = loop {
+        match ws.next().await {
+            Some(Ok(Message::Text(text))) => break serde_json::from_str::<ServerMessage>(&text)?,
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err("connection closed during handshake".into()),
+        }
+    };
+    match welcome {
+        ServerMessage::Welcome(w) if w.status == "ok" => {
+            println!("Connected over WebSocket (client: {})", w.client_id);
+        }
+        ServerMessage::Welcome(w) => {
+            return Err(format!(
+                "Server speaks protocol v{}, this client v{}; update the client",
+                w.protocol_version, PROTOCOL_VERSION
+            )
+            .into());
+        }
+        ServerMessage::Command(_) => return Err("server sent a command before the handshake".into()),
+    }
+
+    let mut heartbeat = tokio::time::interval(Duration::from_secs(HEARTBEAT_SECS));
+    heartbeat.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = heartbeat.tick() => {
+                if let Err(e) = ws.send(Message::Text(serde_json::to_string(&ClientMessage::Heartbeat)?)).await {
+                    eprintln!("WebSocket heartbeat failed: {}", e);
+                    break;
+                }
+            }
+            frame = ws.next() => match frame {
+                Some(Ok(Message::Text(text))) => {
+                    let envelope = match serde_json::from_str::<ServerMessage>(&text) {
+                        Ok(ServerMessage::Command(envelope)) => envelope,
+                        Ok(ServerMessage::Welcome(_)) => continue,
+                        Err(e) => {
+                            eprintln!("Ignoring malformed frame: {}", e);
+                            continue;
+                        }
+                    };
+                    if verbose {
+                        println!("Received command {}", envelope.id);
+                    }
+
+                    let ack = ClientMessage::Ack { command_id: envelope.id };
+                    if let Err(e) = ws.send(Message::Text(serde_json::to_string(&ack)?)).await {
+                        eprintln!("Failed to ack command {}: {}", envelope.id, e);
+                        break;
+                    }
+
+                    let report = execute_command(&envelope, guard, uploader).await;
+                    let frame = Message::Text(serde_json::to_string(&ClientMessage::Result(report.clone()))?);
+                    if let Err(e) = ws.send(frame).await {
+                        eprintln!("Failed to report result: {}", e);
+                        break;
+                    }
+                    print_report(&report);
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Err(e)) => {
+                    eprintln!("WebSocket error: {}", e);
+                    break;
+                }
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+
+    println!("WebSocket connection closed");
+    Ok(())
+}
@@ -0,0 +1,423 @@
+//! Alice PC Protocol - Messages exchanged between the server and the PC client
+//!
+//! The server queues [`Command`]s, the client polls them as [`CommandEnvelope`]s
+//! and answers with a [`CommandReport`]. On every heartbeat the client sends a
+//! [`Handshake`] listing the [`Capability`]s it can execute; the server refuses
+//! to queue commands that no registered client supports. The same messages also
+//! travel over a WebSocket as [`ServerMessage`] / [`ClientMessage`] frames, with
+//! polling as the fallback.
+//!
+//! Commands are signed by the server with an Ed25519 key whose public half the
+//! client pins; each carries a nonce and an expiry so it can run only once.
+//!
+//! Bump [`PROTOCOL_VERSION`] on any change that an older peer could misread.
+
+use ed25519_dalek::{Signature, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+/// Current wire protocol version
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Longest lifetime a signed command may claim; bounds how long clients remember nonces
+pub const MAX_COMMAND_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// `code` of the 501 answer to an artifact upload when the server cannot store files;
+/// the client keeps the artifact locally instead
+pub const ARTIFACTS_UNAVAILABLE: &str = "artifacts_unavailable";
+
+/// Kind of command a client is able to execute; one per [`Command`] variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Capability {
+    Shutdown,
+    Restart,
+    Lock,
+    OpenUrl,
+    RunCommand,
+    Notification,
+    Volume,
+    Screenshot,
+    Custom,
+    /// Capability announced by a newer client that this build does not know
+    #[serde(other)]
+    Unknown,
+}
+
+impl Capability {
+    /// Every capability this build of the protocol defines
+    pub const ALL: [Capability; 9] = [
+        Capability::Shutdown,
+        Capability::Restart,
+        Capability::Lock,
+        Capability::OpenUrl,
+        Capability::RunCommand,
+        Capability::Notification,
+        Capability::Volume,
+        Capability::Screenshot,
+        Capability::Custom,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::Shutdown => "Shutdown",
+            Capability::Restart => "Restart",
+            Capability::Lock => "Lock",
+            Capability::OpenUrl => "OpenUrl",
+            Capability::RunCommand => "RunCommand",
+            Capability::Notification => "Notification",
+            Capability::Volume => "Volume",
+            Capability::Screenshot => "Screenshot",
+            Capability::Custom => "Custom",
+            Capability::Unknown => "Unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Volume control action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeAction {
+    Mute,
+    ToggleMute,
+    /// Set the level to `value` percent
+    Set,
+}
+
+/// Command executed by the PC client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum Command {
+    /// Shutdown the PC
+    Shutdown,
+    /// Restart the PC
+    Restart,
+    /// Lock the PC
+    Lock,
+    /// Open a URL in browser
+    OpenUrl { url: String },
+    /// Run a program
+    RunCommand {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Show a desktop notification
+    Notification { title: String, message: String },
+    /// Control volume
+    Volume { action: VolumeAction, value: Option<i32> },
+    /// Take screenshot
+    Screenshot,
+    /// Custom command handled by name on the client
+    Custom {
+        name: String,
+        #[serde(default)]
+        payload: serde_json::Value,
+    },
+}
+
+impl Command {
+    /// Capability a client must announce to receive this command
+    pub fn capability(&self) -> Capability {
+        match self {
+            Command::Shutdown => Capability::Shutdown,
+            Command::Restart => Capability::Restart,
+            Command::Lock => Capability::Lock,
+            Command::OpenUrl { .. } => Capability::OpenUrl,
+            Command::RunCommand { .. } => Capability::RunCommand,
+            Command::Notification { .. } => Capability::Notification,
+            Command::Volume { .. } => Capability::Volume,
+            Command::Screenshot => Capability::Screenshot,
+            Command::Custom { .. } => Capability::Custom,
+        }
+    }
+}
+
+/// What the server signs: the command plus its replay protection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedCommand {
+    /// Random, unique per queued command
+    pub nonce: String,
+    /// Unix timestamp after which the client must refuse the command
+    pub expires_at: i64,
+    pub command: Command,
+}
+
+impl SignedCommand {
+    /// Serialize and sign; returns the exact payload text and its hex signature
+    pub fn sign(&self, key: &SigningKey) -> Result<(String, String), String> {
+        let payload = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        let signature = key.sign(payload.as_bytes());
+        Ok((payload, hex::encode(signature.to_bytes())))
+    }
+}
+
+/// Queued command as returned by the poll endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandEnvelope {
+    pub id: i64,
+    /// Protocol version the command was queued with
+    pub version: u32,
+    pub priority: i32,
+    /// [`SignedCommand`] as JSON; verified byte for byte, so never re-serialized
+    pub payload: String,
+    /// Hex Ed25519 signature of `payload`
+    pub signature: String,
+}
+
+impl CommandEnvelope {
+    /// Check the signature against the pinned server key and decode the payload.
+    /// Expiry and nonce reuse are left to the caller, which owns the clock and the nonce store.
+    pub fn open(&self, key: &VerifyingKey) -> Result<SignedCommand, String> {
+        let bytes: [u8; 64] = hex::decode(&self.signature)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or("malformed signature")?;
+        key.verify(self.payload.as_bytes(), &Signature::from_bytes(&bytes))
+            .map_err(|_| "invalid signature".to_string())?;
+        serde_json::from_str(&self.payload).map_err(|e| format!("cannot decode command: {}", e))
+    }
+}
+
+/// Parse a 32-byte Ed25519 secret key given as 64 hex characters
+pub fn parse_signing_key(hex_key: &str) -> Result<SigningKey, String> {
+    Ok(SigningKey::from_bytes(&parse_key_bytes(hex_key)?))
+}
+
+/// Parse a 32-byte Ed25519 public key given as 64 hex characters
+pub fn parse_verifying_key(hex_key: &str) -> Result<VerifyingKey, String> {
+    VerifyingKey::from_bytes(&parse_key_bytes(hex_key)?).map_err(|_| "not a valid Ed25519 public key".to_string())
+}
+
+fn parse_key_bytes(hex_key: &str) -> Result<[u8; 32], String> {
+    let bytes = hex::decode(hex_key.trim()).map_err(|_| "key must be hex encoded".to_string())?;
+    bytes
+        .try_into()
+        .map_err(|_| "key must be 32 bytes (64 hex characters)".to_string())
+}
+
+/// Outcome of a command, tagged by `status`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CommandResult {
+    Completed { message: String },
+    Failed { error: String },
+    /// The client could not decode the command or does not implement it
+    Unsupported { reason: String },
+    /// Refused before execution: bad signature, expired, replayed or not allowlisted
+    Rejected { reason: String },
+}
+
+/// Result sent back by the client for one command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandReport {
+    pub command_id: i64,
+    #[serde(flatten)]
+    pub result: CommandResult,
+}
+
+/// Body of the client heartbeat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Handshake {
+    pub protocol_version: u32,
+    pub client_version: String,
+    pub capabilities: Vec<Capability>,
+}
+
+/// Server answer to a heartbeat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatResponse {
+    /// `ok`, or `unsupported_protocol` when the server rejected the handshake
+    pub status: String,
+    pub client_id: String,
+    pub server_time: String,
+    pub protocol_version: u32,
+}
+
+/// Text frame sent by the server over the PC client WebSocket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Answer to `hello`, same content as the HTTP heartbeat response
+    Welcome(HeartbeatResponse),
+    /// Pushed as soon as it is queued; stays pending until the client acks it
+    Command(CommandEnvelope),
+}
+
+/// Text frame sent by the client over the PC client WebSocket; the first one must be `hello`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello(Handshake),
+    /// Keeps the client marked online between commands
+    Heartbeat,
+    /// Command received and accepted for execution
+    Ack { command_id: i64 },
+    Result(CommandReport),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn all_commands() -> Vec<Command> {
+        vec![
+            Command::Shutdown,
+            Command::Restart,
+            Command::Lock,
+            Command::OpenUrl { url: "https://example.com/?q=1".into() },
+            Command::RunCommand { command: "notepad".into(), args: vec!["a b.txt".into()] },
+            Command::Notification { title: "Hi".into(), message: "«привет»".into() },
+            Command::Volume { action: VolumeAction::Set, value: Some(40) },
+            Command::Volume { action: VolumeAction::ToggleMute, value: None },
+            Command::Screenshot,
+            Command::Custom { name: "lights".into(), payload: json!({"on": true}) },
+        ]
+    }
+
+    fn envelope(command: Command, key: &SigningKey) -> CommandEnvelope {
+        let signed = SignedCommand { nonce: "n-1".into(), expires_at: 1_700_000_000, command };
+        let (payload, signature) = signed.sign(key).unwrap();
+        CommandEnvelope { id: 1, version: PROTOCOL_VERSION, priority: 0, payload, signature }
+    }
+
+    #[test]
+    fn signed_commands_open_with_the_matching_key() {
+        let key = key(1);
+        for command in all_commands() {
+            let opened = envelope(command.clone(), &key).open(&key.verifying_key()).unwrap();
+            assert_eq!(opened.command, command);
+            assert_eq!(opened.nonce, "n-1");
+            assert_eq!(opened.expires_at, 1_700_000_000);
+        }
+    }
+
+    #[test]
+    fn open_rejects_wrong_key_and_tampering() {
+        let key = key(1);
+        let sealed = envelope(Command::OpenUrl { url: "https://example.com".into() }, &key);
+        assert_eq!(sealed.open(&self::key(2).verifying_key()).unwrap_err(), "invalid signature");
+
+        let mut tampered = sealed.clone();
+        tampered.payload = tampered.payload.replace("example.com", "evil.example");
+        assert_eq!(tampered.open(&key.verifying_key()).unwrap_err(), "invalid signature");
+
+        // Whitespace-only changes still break the byte-for-byte check
+        let mut reformatted = sealed.clone();
+        let value: serde_json::Value = serde_json::from_str(&sealed.payload).unwrap();
+        reformatted.payload = serde_json::to_string_pretty(&value).unwrap();
+        assert!(reformatted.open(&key.verifying_key()).is_err());
+
+        let mut flipped = sealed.clone();
+        flipped.signature.replace_range(0..2, if &sealed.signature[0..2] == "00" { "01" } else { "00" });
+        assert_eq!(flipped.open(&key.verifying_key()).unwrap_err(), "invalid signature");
+
+        for signature in ["", "zz", &sealed.signature[2..]] {
+            let malformed = CommandEnvelope { signature: signature.to_string(), ..sealed.clone() };
+            assert_eq!(malformed.open(&key.verifying_key()).unwrap_err(), "malformed signature");
+        }
+    }
+
+    #[test]
+    fn commands_roundtrip_through_json() {
+        for command in all_commands() {
+            let text = serde_json::to_string(&command).unwrap();
+            assert_eq!(serde_json::from_str::<Command>(&text).unwrap(), command, "{}", text);
+            assert!(Capability::ALL.contains(&command.capability()));
+        }
+        assert_eq!(serde_json::to_value(Command::Lock).unwrap(), json!({"type": "Lock"}));
+        assert_eq!(
+            serde_json::from_value::<Command>(json!({"type": "RunCommand", "data": {"command": "calc"}})).unwrap(),
+            Command::RunCommand { command: "calc".into(), args: vec![] }
+        );
+    }
+
+    #[test]
+    fn results_roundtrip_through_json() {
+        for result in [
+            CommandResult::Completed { message: "done".into() },
+            CommandResult::Failed { error: "exit 1".into() },
+            CommandResult::Unsupported { reason: "unknown command".into() },
+            CommandResult::Rejected { reason: "expired".into() },
+        ] {
+            let report = CommandReport { command_id: 9, result: result.clone() };
+            let value = serde_json::to_value(&report).unwrap();
+            assert_eq!(value["command_id"], 9);
+            let decoded: CommandReport = serde_json::from_value(value).unwrap();
+            assert_eq!(decoded.command_id, 9);
+            assert_eq!(decoded.result, result);
+        }
+        assert_eq!(
+            serde_json::to_value(CommandResult::Rejected { reason: "replayed".into() }).unwrap(),
+            json!({"status": "rejected", "reason": "replayed"})
+        );
+    }
+
+    #[test]
+    fn websocket_frames_roundtrip() {
+        let handshake = Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            client_version: "1.2.3".into(),
+            capabilities: Capability::ALL.to_vec(),
+        };
+        let frame = serde_json::to_value(ClientMessage::Hello(handshake)).unwrap();
+        assert_eq!(frame["type"], "hello");
+        match serde_json::from_value(frame).unwrap() {
+            ClientMessage::Hello(h) => assert_eq!(h.capabilities, Capability::ALL.to_vec()),
+            other => panic!("unexpected {:?}", other),
+        }
+        let ack: ClientMessage = serde_json::from_value(json!({"type": "ack", "command_id": 4})).unwrap();
+        assert!(matches!(ack, ClientMessage::Ack { command_id: 4 }));
+
+        let key = key(3);
+        let sealed = envelope(Command::Screenshot, &key);
+        let text = serde_json::to_string(&ServerMessage::Command(sealed)).unwrap();
+        match serde_json::from_str(&text).unwrap() {
+            // The payload survives the extra JSON layer byte for byte
+            ServerMessage::Command(envelope) => {
+                assert_eq!(envelope.open(&key.verifying_key()).unwrap().command, Command::Screenshot)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unsupported_capabilities_and_commands_are_rejected() {
+        let handshake: Handshake = serde_json::from_value(json!({
+            "protocol_version": PROTOCOL_VERSION,
+            "client_version": "9.0.0",
+            "capabilities": ["Lock", "Teleport"],
+        }))
+        .unwrap();
+        assert_eq!(handshake.capabilities, vec![Capability::Lock, Capability::Unknown]);
+        assert!(!Capability::ALL.contains(&Capability::Unknown));
+
+        // A command type this build does not know is signed correctly but cannot be decoded
+        let key = key(4);
+        let payload = json!({"nonce": "n", "expires_at": 1, "command": {"type": "Teleport"}}).to_string();
+        let signature = hex::encode(key.sign(payload.as_bytes()).to_bytes());
+        let sealed = CommandEnvelope { id: 2, version: PROTOCOL_VERSION, priority: 0, payload, signature };
+        assert!(sealed.open(&key.verifying_key()).unwrap_err().starts_with("cannot decode command"));
+    }
+
+    #[test]
+    fn parses_hex_keys() {
+        let secret = hex::encode([5u8; 32]);
+        let signing = parse_signing_key(&format!("{}\n", secret)).unwrap();
+        let public = hex::encode(signing.verifying_key().to_bytes());
+        assert_eq!(parse_verifying_key(&public).unwrap(), signing.verifying_key());
+        assert!(parse_signing_key(&secret[2..]).is_err());
+        assert!(parse_verifying_key("not hex").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use crate::alice::models::*;
+use crate::alice::schedule::PcScheduleService;
+use crate::alice::service::{AliceService, AliceState};
+use crate::db::DbPool;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::env;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Outgoing Yandex Smart Home notifications: pushes device state and device list changes
+/// to the notification API so the Alice app updates without waiting for a query
+#[derive(Clone)]
+pub struct YandexCallbacks {
+    client: Client,
+    /// `.../api/v1/skills/<skill_id>`
+    skill_url: String,
+    oauth_token: String,
+    /// Changes arriving within this window go out in one request
+    debounce: Duration,
+    attempts: u32,
+}
+
+impl YandexCallbacks {
+    /// Configured from `YANDEX_SKILL_ID` and `YANDEX_SKILL_OAUTH_TOKEN`; None while either is unset
+    pub fn from_env() -> Option<Self> {
+        let skill_id = env::var("YANDEX_SKILL_ID").ok().filter(|v| !v.is_empty())?;
+        let oauth_token = env::var("YANDEX_SKILL_OAUTH_TOKEN").ok().filter(|v| !v.is_empty())?;
+        let base_url = env::var("YANDEX_CALLBACK_URL").unwrap_or_else(|_| "https://dialogs.yandex.net".to_string());
+        let debounce_ms = env::var("YANDEX_CALLBACK_DEBOUNCE_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1000);
+        let attempts = env::var("YANDEX_CALLBACK_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3u32)
+            .max(1);
+
+        Some(Self {
+            client: Client::new(),
+            skill_url: format!("{}/api/v1/skills/{}", base_url.trim_end_matches('/'), skill_id),
+            oauth_token,
+            debounce: Duration::from_millis(debounce_ms),
+            attempts,
+        })
+    }
+
+    /// Forward the changes collected in `alice_state` for as long as the server runs.
+    /// Discovery is sent once at startup since migrations may have changed the device list.
+    pub fn start(self, pool: DbPool, alice_state: AliceState, schedules: PcScheduleService) {
+        info!("Yandex callbacks go to {}", self.skill_url);
+        alice_state.mark_devices_changed();
+
+        tokio::spawn(async move {
+            loop {
+                alice_state.change_signal.notified().await;
+                tokio::time::sleep(self.debounce).await;
+
+                let (devices, discovery) = alice_state.take_changes();
+                let users = match AliceService::linked_user_ids(&pool).await {
+                    Ok(users) => users,
+                    Err(e) => {
+                        error!("Failed to load Alice users for callbacks: {}", e);
+                        continue;
+                    }
+                };
+
+                if discovery {
+                    for user_id in &users {
+                        let body = json!({"ts": timestamp(), "payload": {"user_id": user_id.to_string()}});
+                        self.send("callback/discovery", &body).await;
+                    }
+                }
+                // A discovery makes Yandex re-query every device anyway
+                if discovery || devices.is_empty() || users.is_empty() {
+                    continue;
+                }
+
+                let mut states = Vec::new();
+                for device_id in &devices {
+                    let device = AliceService::query_device(&pool, device_id, &alice_state, &schedules).await;
+                    if device.error_code.is_none() {
+                        states.push(device_state(device));
+                    }
+                }
+                if states.is_empty() {
+                    continue;
+                }
+                for user_id in &users {
+                    let body = json!({
+                        "ts": timestamp(),
+                        "payload": {"user_id": user_id.to_string(), "devices": states},
+                    });
+                    self.send("callback/state", &body).await;
+                }
+            }
+        });
+    }
+
+    /// POST with retries on network errors, 429 and 5xx; other errors are not retried
+    async fn send(&self, path: &str, body: &Value) {
+        let url = format!("{}/{}", self.skill_url, path);
+        let mut delay = Duration::from_secs(1);
+
+        for attempt in 1..=self.attempts {
+            let result = self
+                .client
+                .post(&url)
+                .header("Authorization", format!("OAuth {}", self.oauth_token))
+                .timeout(Duration::from_secs(10))
+                .json(body)
+                .send()
+                .await;
+
+            let retryable = match result {
+                Ok(response) if response.status().is_success() => return,
+                Ok(response) => {
+                    let status = response.status();
+                    let text = response.text().await.unwrap_or_default();
+                    warn!("Yandex {} returned {}: {}", path, status, text);
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                }
+                Err(e) => {
+                    warn!("Yandex {} failed: {}", path, e);
+                    true
+                }
+            };
+
+            if !retryable || attempt == self.attempts {
+                break;
+            }
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+
+        error!("Giving up on Yandex {}", path);
+    }
+}
+
+/// Query answer trimmed to what the state callback takes: types and states only
+fn device_state(device: QueryDeviceResponse) -> Value {
+    let capabilities: Vec<Value> = device
+        .capabilities
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|c| Some(json!({"type": c.capability_type, "state": c.state?})))
+        .collect();
+    let properties: Vec<Value> = device
+        .properties
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|p| Some(json!({"type": p.property_type, "state": p.state?})))
+        .collect();
+
+    let mut state = json!({"id": device.id, "capabilities": capabilities});
+    if !properties.is_empty() {
+        state["properties"] = json!(properties);
+    }
+    state
+}
+
+/// Unix time in seconds, as the notification API expects
+fn timestamp() -> f64 {
+    chrono::Utc::now().timestamp_millis() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, HttpStub};
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::sync::Arc;
+
+    /// Callbacks pointed at `stub` through the same variables production uses
+    fn callbacks(stub: &HttpStub, debounce_ms: u64, attempts: u32) -> YandexCallbacks {
+        let _env = test_support::env_lock();
+        env::set_var("YANDEX_SKILL_ID", "skill");
+        env::set_var("YANDEX_SKILL_OAUTH_TOKEN", "oauth");
+        env::set_var("YANDEX_CALLBACK_URL", &stub.url);
+        env::set_var("YANDEX_CALLBACK_DEBOUNCE_MS", debounce_ms.to_string());
+        env::set_var("YANDEX_CALLBACK_ATTEMPTS", attempts.to_string());
+        YandexCallbacks::from_env().unwrap()
+    }
+
+    async fn stub_with_status(status: u16) -> HttpStub {
+        let status = Arc::new(AtomicU16::new(status));
+        HttpStub::start(move |_| (status.load(Ordering::SeqCst), r#"{"request_id": "stub"}"#.to_string())).await
+    }
+
+    #[tokio::test]
+    async fn burst_within_debounce_is_one_state_callback() {
+        let (_dir, pool) = test_support::sqlite_pool().await;
+        let user_id = sqlx::query("INSERT INTO alice_users (username, password_hash) VALUES ('callbacks', 'x')")
+            .execute(pool.as_sqlite().unwrap())
+            .await
+            .unwrap()
+            .last_insert_rowid();
+        AliceService::create_tokens(&pool, user_id).await.unwrap();
+
+        let stub = stub_with_status(200).await;
+        let alice_state = AliceState::new();
+        let schedules = PcScheduleService::init(pool.clone(), alice_state.clone()).await.unwrap();
+        callbacks(&stub, 300, 1).start(pool, alice_state.clone(), schedules);
+
+        // Startup announces the device list first
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(stub.hits("/api/v1/skills/skill/callback/discovery").len(), 1);
+
+        alice_state.set_pc_status("pc-control".to_string(), true);
+        alice_state.mark_changed("pc-control");
+        alice_state.mark_changed("website-notify");
+        alice_state.set_pc_status("pc-control".to_string(), false);
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let states = stub.hits("/callback/state");
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].method, "POST");
+        assert_eq!(states[0].header("authorization"), Some("OAuth oauth"));
+        let body = states[0].json();
+        assert_eq!(body["payload"]["user_id"], user_id.to_string());
+        let devices = body["payload"]["devices"].as_array().unwrap();
+        assert_eq!(devices.len(), 2);
+        let pc = devices.iter().find(|d| d["id"] == "pc-control").unwrap();
+        assert_eq!(pc["capabilities"][0]["state"]["value"], false);
+    }
+
+    #[tokio::test]
+    async fn throttling_and_server_errors_are_retried_up_to_attempts() {
+        for status in [429, 503] {
+            let stub = stub_with_status(status).await;
+            callbacks(&stub, 0, 3).send("callback/state", &json!({})).await;
+            assert_eq!(stub.hits("/callback/state").len(), 3, "status {}", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        for status in [400, 401, 404] {
+            let stub = stub_with_status(status).await;
+            callbacks(&stub, 0, 3).send("callback/state", &json!({})).await;
+            assert_eq!(stub.hits("/callback/state").len(), 1, "status {}", status);
+        }
+    }
+
+    #[test]
+    fn device_state_drops_capabilities_without_state() {
+        let capability = |instance: Option<&str>| DeviceCapability {
+            capability_type: "devices.capabilities.on_off".to_string(),
+            retrievable: true,
+            reportable: Some(true),
+            parameters: Some(json!({"split": false})),
+            state: instance.map(|i| CapabilityState { instance: i.to_string(), value: json!(true) }),
+        };
+        let device = QueryDeviceResponse {
+            id: "lamp".to_string(),
+            capabilities: Some(vec![capability(Some("on")), capability(None)]),
+            properties: Some(vec![DeviceProperty {
+                property_type: "devices.properties.float".to_string(),
+                retrievable: true,
+                reportable: None,
+                parameters: Some(json!({"instance": "temperature"})),
+                state: None,
+            }]),
+            error_code: None,
+            error_message: None,
+        };
+
+        assert_eq!(
+            device_state(device),
+            json!({
+                "id": "lamp",
+                "capabilities": [{"type": "devices.capabilities.on_off", "state": {"instance": "on", "value": true}}],
+            })
+        );
+    }
+}
@@ -0,0 +1,441 @@
+use crate::alice::models::*;
+use crate::alice::schema;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Home Assistant sensor device_class -> float instance, unit and device type
+const SENSOR_CLASSES: &[(&str, &str, &str, &str)] = &[
+    ("temperature", "temperature", "unit.temperature.celsius", "devices.types.sensor.climate"),
+    ("humidity", "humidity", "unit.percent", "devices.types.sensor.climate"),
+    ("carbon_dioxide", "co2_level", "unit.ppm", "devices.types.sensor.climate"),
+    ("pm1", "pm1_density", "unit.density.mcg_m3", "devices.types.sensor.climate"),
+    ("pm25", "pm2.5_density", "unit.density.mcg_m3", "devices.types.sensor.climate"),
+    ("pm10", "pm10_density", "unit.density.mcg_m3", "devices.types.sensor.climate"),
+    ("volatile_organic_compounds", "tvoc", "unit.density.mcg_m3", "devices.types.sensor.climate"),
+    ("illuminance", "illumination", "unit.illumination.lux", "devices.types.sensor.illumination"),
+    ("battery", "battery_level", "unit.percent", "devices.types.sensor"),
+    ("power", "power", "unit.watt", "devices.types.smart_meter.electricity"),
+    ("voltage", "voltage", "unit.volt", "devices.types.smart_meter.electricity"),
+    ("current", "amperage", "unit.ampere", "devices.types.smart_meter.electricity"),
+    ("energy", "electricity_meter", "unit.kilowatt_hour", "devices.types.smart_meter.electricity"),
+];
+
+/// Pressure units Alice knows, by Home Assistant unit_of_measurement
+const PRESSURE_UNITS: &[(&str, &str)] = &[
+    ("mmHg", "unit.pressure.mmhg"),
+    ("Pa", "unit.pressure.pascal"),
+    ("bar", "unit.pressure.bar"),
+    ("atm", "unit.pressure.atm"),
+];
+
+/// Home Assistant binary_sensor device_class -> event instance, on and off events, device type
+const BINARY_SENSOR_CLASSES: &[(&str, &str, &str, Option<&str>, &str)] = &[
+    ("motion", "motion", "detected", Some("not_detected"), "devices.types.sensor.motion"),
+    ("occupancy", "motion", "detected", Some("not_detected"), "devices.types.sensor.motion"),
+    ("presence", "motion", "detected", Some("not_detected"), "devices.types.sensor.motion"),
+    ("door", "open", "opened", Some("closed"), "devices.types.sensor.open"),
+    ("window", "open", "opened", Some("closed"), "devices.types.sensor.open"),
+    ("opening", "open", "opened", Some("closed"), "devices.types.sensor.open"),
+    ("garage_door", "open", "opened", Some("closed"), "devices.types.sensor.open"),
+    ("moisture", "water_leak", "leak", Some("dry"), "devices.types.sensor.water_leak"),
+    ("smoke", "smoke", "detected", Some("not_detected"), "devices.types.sensor.smoke"),
+    ("gas", "gas", "detected", Some("not_detected"), "devices.types.sensor.gas"),
+    ("battery", "battery_level", "low", Some("normal"), "devices.types.sensor"),
+    ("vibration", "vibration", "vibration", None, "devices.types.sensor.vibration"),
+];
+
+/// Registry id for a discovery topic path (`<component>/[<node_id>/]<object_id>/config`);
+/// derived from the topic alone so an empty config can remove the device
+pub fn device_id(path: &str) -> String {
+    let path = path.strip_suffix("/config").unwrap_or(path);
+    let id: String = path
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    format!("mqtt-{}", id)
+}
+
+/// Discovery topic a registry device was created from
+pub fn source_topic(device: &DeviceDefinition) -> Option<&str> {
+    device.custom_data.as_ref()?.get("mqtt_discovery")?.as_str()
+}
+
+/// Translate a Home Assistant discovery config into a registry device.
+/// Ok(None) for components without an Alice counterpart.
+pub fn to_device(path: &str, device_id: &str, topic: &str, config: &Value) -> Result<Option<DeviceDefinition>, String> {
+    let config = Config(config);
+    let component = path.split('/').next().unwrap_or_default();
+    let mut device = DeviceDefinition {
+        id: device_id.to_string(),
+        name: config.name(path),
+        description: None,
+        room: config.device_text(&["suggested_area", "sa"]),
+        device_type: String::new(),
+        capabilities: Vec::new(),
+        properties: Vec::new(),
+        handler: DeviceHandler::SiteNotification { template: String::new() },
+        custom_data: Some(json!({ "mqtt_discovery": topic })),
+        enabled: true,
+    };
+    let mut command_topic = String::new();
+    let mut instance_topics = HashMap::new();
+    let mut scales = HashMap::new();
+    let mut state_topics = Vec::new();
+    let (mut payload_on, mut payload_off) = (None, None);
+
+    match component {
+        "switch" | "light" => {
+            if component == "light" && config.text(&["schema"]).is_some_and(|s| s != "default") {
+                return Err("only the default light schema is supported".to_string());
+            }
+            command_topic = config.topic(&["command_topic", "cmd_t"]).ok_or("command_topic is required")?;
+            let on = config.text(&["payload_on", "pl_on"]).unwrap_or_else(|| "ON".to_string());
+            let off = config.text(&["payload_off", "pl_off"]).unwrap_or_else(|| "OFF".to_string());
+            let state_topic = config.topic(&["state_topic", "stat_t"]);
+            if let Some(state_topic) = &state_topic {
+                let state_on = config.text(&["state_on", "stat_on"]).unwrap_or_else(|| on.clone());
+                let state_off = config.text(&["state_off", "stat_off"]).unwrap_or_else(|| off.clone());
+                state_topics.push(MqttStateTopic {
+                    topic: state_topic.clone(),
+                    state_type: schema::ON_OFF.to_string(),
+                    instance: "on".to_string(),
+                    value_path: config.value_path(&["value_template", "val_tpl"])?,
+                    payload_map: HashMap::from([(state_on, json!(true)), (state_off, json!(false))]),
+                });
+            }
+            device.capabilities.push(capability(schema::ON_OFF, state_topic.is_some(), None));
+            payload_on = Some(on);
+            payload_off = Some(off);
+
+            if component == "light" {
+                device.device_type = "devices.types.light".to_string();
+                if let Some(brightness_topic) = config.topic(&["brightness_command_topic", "bri_cmd_t"]) {
+                    let scale = config.number(&["brightness_scale", "bri_scl"]).unwrap_or(255.0);
+                    let state_topic = config.topic(&["brightness_state_topic", "bri_stat_t"]);
+                    if let Some(state_topic) = &state_topic {
+                        state_topics.push(MqttStateTopic {
+                            topic: state_topic.clone(),
+                            state_type: schema::RANGE.to_string(),
+                            instance: "brightness".to_string(),
+                            value_path: config.value_path(&["brightness_value_template", "bri_val_tpl"])?,
+                            payload_map: HashMap::new(),
+                        });
+                    }
+                    device.capabilities.push(capability(
+                        schema::RANGE,
+                        state_topic.is_some(),
+                        Some(json!({
+                            "instance": "brightness",
+                            "unit": "unit.percent",
+                            "range": { "min": 1, "max": 100, "precision": 1 },
+                        })),
+                    ));
+                    instance_topics.insert("brightness".to_string(), brightness_topic);
+                    scales.insert("brightness".to_string(), scale);
+                }
+            } else {
+                device.device_type = "devices.types.switch".to_string();
+            }
+        }
+        "sensor" => {
+            let state_topic = config.topic(&["state_topic", "stat_t"]).ok_or("state_topic is required")?;
+            let class = config.text(&["device_class", "dev_cla"]).unwrap_or_default();
+            let unit = config.text(&["unit_of_measurement", "unit_of_meas"]).unwrap_or_default();
+            let (instance, unit, device_type) = match class.as_str() {
+                "pressure" | "atmospheric_pressure" => {
+                    let (_, unit) = PRESSURE_UNITS
+                        .iter()
+                        .find(|(name, _)| *name == unit)
+                        .ok_or_else(|| format!("pressure unit '{}' is not supported", unit))?;
+                    ("pressure", *unit, "devices.types.sensor.climate")
+                }
+                "temperature" if unit == "°F" => return Err("temperature in °F is not supported".to_string()),
+                "temperature" if unit == "K" => ("temperature", "unit.temperature.kelvin", "devices.types.sensor.climate"),
+                _ => match SENSOR_CLASSES.iter().find(|(name, ..)| *name == class) {
+                    Some((_, instance, unit, device_type)) => (*instance, *unit, *device_type),
+                    None => return Ok(None),
+                },
+            };
+            device.device_type = device_type.to_string();
+            device.properties.push(property(schema::FLOAT, json!({ "instance": instance, "unit": unit })));
+            state_topics.push(MqttStateTopic {
+                topic: state_topic,
+                state_type: schema::FLOAT.to_string(),
+                instance: instance.to_string(),
+                value_path: config.value_path(&["value_template", "val_tpl"])?,
+                payload_map: HashMap::new(),
+            });
+        }
+        "binary_sensor" => {
+            let state_topic = config.topic(&["state_topic", "stat_t"]).ok_or("state_topic is required")?;
+            let class = config.text(&["device_class", "dev_cla"]).unwrap_or_default();
+            let Some((_, instance, on_event, off_event, device_type)) =
+                BINARY_SENSOR_CLASSES.iter().find(|(name, ..)| *name == class)
+            else {
+                return Ok(None);
+            };
+            let on = config.text(&["payload_on", "pl_on"]).unwrap_or_else(|| "ON".to_string());
+            let off = config.text(&["payload_off", "pl_off"]).unwrap_or_else(|| "OFF".to_string());
+            let mut payload_map = HashMap::from([(on, json!(on_event))]);
+            let mut events = vec![json!({ "value": on_event })];
+            if let Some(off_event) = off_event {
+                payload_map.insert(off, json!(off_event));
+                events.push(json!({ "value": off_event }));
+            }
+
+            device.device_type = device_type.to_string();
+            device.properties.push(property(schema::EVENT, json!({ "instance": instance, "events": events })));
+            state_topics.push(MqttStateTopic {
+                topic: state_topic,
+                state_type: schema::EVENT.to_string(),
+                instance: instance.to_string(),
+                value_path: config.value_path(&["value_template", "val_tpl"])?,
+                payload_map,
+            });
+        }
+        _ => return Ok(None),
+    }
+
+    device.handler = DeviceHandler::Mqtt {
+        topic: command_topic,
+        instance_topics,
+        payload: None,
+        payload_on,
+        payload_off,
+        scales,
+        qos: config.number(&["qos"]).unwrap_or(0.0) as u8,
+        retain: config.0.get("retain").and_then(Value::as_bool).unwrap_or(false),
+        state_topics,
+    };
+    Ok(Some(device))
+}
+
+fn capability(kind: &str, reportable: bool, parameters: Option<Value>) -> DeviceCapability {
+    DeviceCapability {
+        capability_type: kind.to_string(),
+        retrievable: true,
+        reportable: reportable.then_some(true),
+        parameters,
+        state: None,
+    }
+}
+
+fn property(kind: &str, parameters: Value) -> DeviceProperty {
+    DeviceProperty {
+        property_type: kind.to_string(),
+        retrievable: true,
+        reportable: Some(true),
+        parameters: Some(parameters),
+        state: None,
+    }
+}
+
+/// Discovery payload; every key may come in its full or abbreviated form
+struct Config<'a>(&'a Value);
+
+impl Config<'_> {
+    fn get(&self, keys: &[&str]) -> Option<&Value> {
+        keys.iter().find_map(|key| self.0.get(*key)).filter(|v| !v.is_null())
+    }
+
+    fn text(&self, keys: &[&str]) -> Option<String> {
+        match self.get(keys)? {
+            Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()),
+        }
+    }
+
+    fn number(&self, keys: &[&str]) -> Option<f64> {
+        self.get(keys)?.as_f64()
+    }
+
+    /// Topic with the `~` base expanded
+    fn topic(&self, keys: &[&str]) -> Option<String> {
+        let topic = self.text(keys)?;
+        let Some(base) = self.0.get("~").and_then(Value::as_str) else {
+            return Some(topic);
+        };
+        if let Some(rest) = topic.strip_prefix('~') {
+            Some(format!("{}{}", base, rest))
+        } else if let Some(rest) = topic.strip_suffix('~') {
+            Some(format!("{}{}", rest, base))
+        } else {
+            Some(topic)
+        }
+    }
+
+    /// `{{ value_json.a.b }}` becomes the path "a.b"; other templates are not supported
+    fn value_path(&self, keys: &[&str]) -> Result<Option<String>, String> {
+        let Some(template) = self.text(keys) else {
+            return Ok(None);
+        };
+        let inner = template
+            .trim()
+            .strip_prefix("{{")
+            .and_then(|t| t.strip_suffix("}}"))
+            .map(str::trim)
+            .unwrap_or_default();
+        if inner == "value" {
+            return Ok(None);
+        }
+        match inner.strip_prefix("value_json.") {
+            Some(path) if !path.is_empty() && path.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') => {
+                Ok(Some(path.to_string()))
+            }
+            _ => Err(format!("value template '{}' is not supported", template)),
+        }
+    }
+
+    fn device_text(&self, keys: &[&str]) -> Option<String> {
+        let device = self.0.get("device").or_else(|| self.0.get("dev"))?;
+        keys.iter().find_map(|key| device.get(*key)?.as_str().map(str::to_string))
+    }
+
+    /// Entity name, else the device name, else the object id
+    fn name(&self, path: &str) -> String {
+        self.text(&["name"])
+            .or_else(|| self.device_text(&["name"]))
+            .unwrap_or_else(|| path.trim_end_matches("/config").rsplit('/').next().unwrap_or(path).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(path: &str, config: Value) -> Result<Option<DeviceDefinition>, String> {
+        let topic = format!("homeassistant/{}", path);
+        to_device(path, &device_id(path), &topic, &config)
+    }
+
+    fn handler(device: &DeviceDefinition) -> (&str, &HashMap<String, String>, &HashMap<String, f64>, &[MqttStateTopic]) {
+        match &device.handler {
+            DeviceHandler::Mqtt { topic, instance_topics, scales, state_topics, .. } => {
+                (topic.as_str(), instance_topics, scales, state_topics.as_slice())
+            }
+            _ => panic!("not an MQTT handler"),
+        }
+    }
+
+    #[test]
+    fn device_id_is_derived_from_the_topic_path() {
+        assert_eq!(device_id("light/Kitchen Node/ceiling/config"), "mqtt-light-kitchen-node-ceiling");
+        assert_eq!(device_id("switch/plug_1/config"), "mqtt-switch-plug_1");
+    }
+
+    #[test]
+    fn light_with_brightness_maps_to_on_off_and_range() {
+        let device = convert(
+            "light/desk/config",
+            json!({
+                "~": "zigbee/desk",
+                "name": "Desk lamp",
+                "cmd_t": "~/set",
+                "stat_t": "~/state",
+                "val_tpl": "{{ value_json.state }}",
+                "bri_cmd_t": "~/brightness/set",
+                "bri_stat_t": "~/brightness",
+                "bri_scl": 254,
+                "dev": {"name": "Lamp", "sa": "Office"},
+            }),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(device.id, "mqtt-light-desk");
+        assert_eq!(device.name, "Desk lamp");
+        assert_eq!(device.room.as_deref(), Some("Office"));
+        assert_eq!(device.device_type, "devices.types.light");
+        assert_eq!(source_topic(&device), Some("homeassistant/light/desk/config"));
+        let kinds: Vec<_> = device.capabilities.iter().map(|c| c.capability_type.as_str()).collect();
+        assert_eq!(kinds, [schema::ON_OFF, schema::RANGE]);
+        assert!(device.capabilities.iter().all(|c| c.reportable == Some(true)));
+
+        let (topic, instance_topics, scales, state_topics) = handler(&device);
+        assert_eq!(topic, "zigbee/desk/set");
+        assert_eq!(instance_topics["brightness"], "zigbee/desk/brightness/set");
+        assert_eq!(scales["brightness"], 254.0);
+        assert_eq!(state_topics[0].topic, "zigbee/desk/state");
+        assert_eq!(state_topics[0].value_path.as_deref(), Some("state"));
+        assert_eq!(state_topics[0].payload_map["ON"], json!(true));
+        assert_eq!(state_topics[0].payload_map["OFF"], json!(false));
+        assert_eq!(state_topics[1].topic, "zigbee/desk/brightness");
+        schema::validate_device(&device).unwrap();
+    }
+
+    #[test]
+    fn switch_without_state_topic_is_not_reportable() {
+        let device = convert("switch/plug/config", json!({"command_topic": "plug/set", "payload_on": "1", "payload_off": "0"}))
+            .unwrap()
+            .unwrap();
+        assert_eq!(device.name, "plug");
+        assert_eq!(device.device_type, "devices.types.switch");
+        assert_eq!(device.capabilities[0].reportable, None);
+        assert!(handler(&device).3.is_empty());
+        match &device.handler {
+            DeviceHandler::Mqtt { payload_on, payload_off, .. } => {
+                assert_eq!(payload_on.as_deref(), Some("1"));
+                assert_eq!(payload_off.as_deref(), Some("0"));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn sensors_map_to_float_and_event_properties() {
+        let temperature = convert(
+            "sensor/room/temperature/config",
+            json!({"state_topic": "room/t", "device_class": "temperature", "unit_of_measurement": "°C"}),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(temperature.device_type, "devices.types.sensor.climate");
+        assert_eq!(temperature.properties[0].property_type, schema::FLOAT);
+        assert_eq!(
+            temperature.properties[0].parameters,
+            Some(json!({"instance": "temperature", "unit": "unit.temperature.celsius"}))
+        );
+        schema::validate_device(&temperature).unwrap();
+
+        let pressure = convert(
+            "sensor/room/pressure/config",
+            json!({"state_topic": "room/p", "device_class": "pressure", "unit_of_measurement": "mmHg"}),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(pressure.properties[0].parameters.as_ref().unwrap()["unit"], "unit.pressure.mmhg");
+
+        let door = convert(
+            "binary_sensor/door/config",
+            json!({"state_topic": "door/state", "device_class": "door", "payload_on": "open", "payload_off": "shut"}),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(door.device_type, "devices.types.sensor.open");
+        assert_eq!(door.properties[0].property_type, schema::EVENT);
+        let state = &handler(&door).3[0];
+        assert_eq!(state.payload_map["open"], json!("opened"));
+        assert_eq!(state.payload_map["shut"], json!("closed"));
+        schema::validate_device(&door).unwrap();
+    }
+
+    #[test]
+    fn unsupported_configs_are_skipped_or_rejected() {
+        assert!(convert("fan/f/config", json!({"command_topic": "f/set"})).unwrap().is_none());
+        assert!(convert("sensor/x/config", json!({"state_topic": "x", "device_class": "signal_strength"}))
+            .unwrap()
+            .is_none());
+        assert!(convert("switch/s/config", json!({"state_topic": "s"})).is_err());
+        assert!(convert("light/l/config", json!({"command_topic": "l/set", "schema": "json"})).is_err());
+        assert!(convert(
+            "sensor/t/config",
+            json!({"state_topic": "t", "device_class": "temperature", "unit_of_measurement": "°F"})
+        )
+        .is_err());
+        assert!(convert(
+            "sensor/v/config",
+            json!({"state_topic": "v", "device_class": "voltage", "value_template": "{{ value | float }}"})
+        )
+        .is_err());
+    }
+}
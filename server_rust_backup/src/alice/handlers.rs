@@ -0,0 +1,285 @@
+use crate::alice::models::*;
+use crate::alice::mqtt::MqttClient;
+use crate::alice::n8n::N8nClient;
+use crate::alice::service::{AliceState, CommandQueueService};
+use crate::db::DbPool;
+use crate::notifications::{category, NewNotification, NotificationPriority, NotificationService};
+use crate::telegram::TelegramBot;
+use reqwest::Method;
+use serde_json::Value;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+/// One capability change Alice asked a registry device for
+pub struct ActionContext<'a> {
+    pub device: &'a DeviceDefinition,
+    pub capability: &'a str,
+    pub instance: &'a str,
+    /// Absolute value, relative changes already applied
+    pub value: &'a Value,
+}
+
+impl ActionContext<'_> {
+    /// The action as sent to webhooks and n8n when no body is configured
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "device_id": self.device.id,
+            "device_name": self.device.name,
+            "capability": self.capability,
+            "instance": self.instance,
+            "value": self.value,
+        })
+    }
+
+    /// Fill the placeholders of a text template
+    pub fn render(&self, template: &str) -> String {
+        template
+            .replace("{{value}}", &plain(self.value))
+            .replace("{{instance}}", self.instance)
+            .replace("{{capability}}", self.capability)
+            .replace("{{device_id}}", &self.device.id)
+            .replace("{{device_name}}", &self.device.name)
+    }
+
+    /// Fill placeholders in every string of a JSON template
+    pub fn render_value(&self, template: &Value) -> Value {
+        match template {
+            Value::String(s) if s == "{{value}}" => self.value.clone(),
+            Value::String(s) => Value::String(self.render(s)),
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.render_value(v)).collect()),
+            Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), self.render_value(v))).collect()),
+            other => other.clone(),
+        }
+    }
+}
+
+/// Strings without quotes, everything else as JSON
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl DeviceHandler {
+    /// Check handler settings before the device is saved
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            DeviceHandler::PcCommand { command, off_command, .. } => {
+                for command in std::iter::once(command).chain(off_command) {
+                    let known = command
+                        .get("type")
+                        .cloned()
+                        .and_then(|t| serde_json::from_value::<Capability>(t).ok())
+                        .is_some_and(|c| c != Capability::Unknown);
+                    if !known {
+                        return Err("pc_command: command.type must be a PC command such as \"Shutdown\"".to_string());
+                    }
+                }
+                Ok(())
+            }
+            DeviceHandler::PcPower | DeviceHandler::SiteNotification { .. } => Ok(()),
+            DeviceHandler::Telegram { template, .. } if template.trim().is_empty() => {
+                Err("telegram: template is required".to_string())
+            }
+            DeviceHandler::Telegram { .. } => Ok(()),
+            DeviceHandler::Webhook { url, method, .. } => {
+                let parsed = url::Url::parse(url).map_err(|e| format!("webhook: invalid url: {}", e))?;
+                if !matches!(parsed.scheme(), "http" | "https") {
+                    return Err("webhook: url must be http or https".to_string());
+                }
+                Method::from_bytes(method.to_uppercase().as_bytes())
+                    .map(|_| ())
+                    .map_err(|_| format!("webhook: invalid method '{}'", method))
+            }
+            DeviceHandler::N8n { workflow, .. } if workflow.trim().is_empty() => {
+                Err("n8n: workflow is required".to_string())
+            }
+            DeviceHandler::N8n { .. } => Ok(()),
+            DeviceHandler::Mqtt { topic, instance_topics, scales, qos, state_topics, .. } => {
+                if topic.is_empty() && instance_topics.is_empty() && state_topics.is_empty() {
+                    return Err("mqtt: topic is required".to_string());
+                }
+                let topics = std::iter::once(topic)
+                    .chain(instance_topics.values())
+                    .chain(state_topics.iter().map(|s| &s.topic));
+                for topic in topics {
+                    if topic.contains(['+', '#']) {
+                        return Err(format!("mqtt: topic '{}' must not contain wildcards", topic));
+                    }
+                }
+                if *qos > 2 {
+                    return Err("mqtt: qos must be 0, 1 or 2".to_string());
+                }
+                if scales.values().any(|scale| *scale <= 0.0) {
+                    return Err("mqtt: scales must be positive".to_string());
+                }
+                if state_topics.iter().any(|s| s.topic.is_empty() || s.instance.is_empty()) {
+                    return Err("mqtt: state_topics need topic and instance".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Carry out an action
+    pub async fn run(
+        &self,
+        ctx: &ActionContext<'_>,
+        pool: &DbPool,
+        alice_state: &AliceState,
+        telegram_bot: &TelegramBot,
+        admin_telegram_id: i64,
+        mqtt: &MqttClient,
+    ) -> Result<(), String> {
+        let custom_data = ctx.device.custom_data.clone().unwrap_or_default();
+
+        match self {
+            DeviceHandler::PcCommand { command, off_command, priority, client_id, group } => {
+                let template = match off_command {
+                    Some(off) if ctx.value == &Value::Bool(false) => off,
+                    _ => command,
+                };
+                let command: PcCommandType = serde_json::from_value(ctx.render_value(template))
+                    .map_err(|e| format!("Invalid PC command: {}", e))?;
+                let target = CommandTarget {
+                    client_id: client_id.clone(),
+                    group: group.clone(),
+                };
+                CommandQueueService::queue_command(pool, &command, *priority, &target, &CommandDelivery::default())
+                    .await
+                    .map(|_| ())
+            }
+            DeviceHandler::PcPower => {
+                if ctx.value.as_bool().unwrap_or(false) {
+                    // Wake-on-LAN is sent from the server since the PC client is not running yet
+                    let mac = custom_data.get("mac").and_then(|v| v.as_str()).unwrap_or("");
+                    if mac.is_empty() {
+                        return Err("MAC address not configured".to_string());
+                    }
+                    send_wol(mac).map_err(|e| format!("WoL failed: {}", e))?;
+                    alice_state.set_pc_status(ctx.device.id.clone(), true);
+                    NotificationService::post(
+                        pool,
+                        NewNotification::new(
+                            category::ALICE,
+                            NotificationPriority::Normal,
+                            &ctx.device.name,
+                            "ПК включается по команде Алисы",
+                        ),
+                    )
+                    .await;
+                } else {
+                    let cmd_id = CommandQueueService::queue_command(
+                        pool,
+                        &PcCommandType::Shutdown,
+                        10,
+                        &CommandTarget::default(),
+                        &CommandDelivery::default(),
+                    )
+                    .await
+                    .map_err(|e| format!("Failed to queue command: {}", e))?;
+                    alice_state.set_pc_status(ctx.device.id.clone(), false);
+                    NotificationService::post(
+                        pool,
+                        NewNotification::new(
+                            category::ALICE,
+                            NotificationPriority::Normal,
+                            &ctx.device.name,
+                            format!("Команда на выключение ПК отправлена (ID: {})", cmd_id),
+                        ),
+                    )
+                    .await;
+                }
+                Ok(())
+            }
+            DeviceHandler::Telegram { chat_id, template } => {
+                let chat_id = chat_id
+                    .or_else(|| {
+                        let configured = custom_data.get("chat_id")?;
+                        configured.as_i64().or_else(|| configured.as_str()?.parse().ok())
+                    })
+                    .unwrap_or(admin_telegram_id);
+                telegram_bot
+                    .send_message(chat_id, &ctx.render(template))
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| format!("Telegram error: {}", e))
+            }
+            DeviceHandler::Webhook { url, method, headers, body } => {
+                let method = Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|e| e.to_string())?;
+                let body = body.as_ref().map(|b| ctx.render_value(b)).unwrap_or_else(|| ctx.to_json());
+                let mut request = reqwest::Client::new()
+                    .request(method, ctx.render(url))
+                    .timeout(Duration::from_secs(10))
+                    .json(&body);
+                for (name, value) in headers {
+                    request = request.header(name, ctx.render(value));
+                }
+                let response = request.send().await.map_err(|e| format!("Webhook failed: {}", e))?;
+                if !response.status().is_success() {
+                    return Err(format!("Webhook returned {}", response.status()));
+                }
+                Ok(())
+            }
+            DeviceHandler::N8n { workflow, params } => {
+                let params = params.as_ref().map(|p| ctx.render_value(p)).unwrap_or_else(|| ctx.to_json());
+                N8nClient::new().trigger_workflow(workflow, params).await.map(|_| ())
+            }
+            DeviceHandler::Mqtt { topic, instance_topics, payload, payload_on, payload_off, scales, qos, retain, .. } => {
+                let topic = instance_topics.get(ctx.instance).unwrap_or(topic);
+                if topic.is_empty() {
+                    return Err(format!("mqtt: no command topic for '{}'", ctx.instance));
+                }
+                let value = match (ctx.value, scales.get(ctx.instance)) {
+                    (Value::Number(n), Some(scale)) => serde_json::json!((n.as_f64().unwrap_or(0.0) * scale / 100.0).round() as i64),
+                    _ => ctx.value.clone(),
+                };
+                let payload = match (&value, payload_on, payload_off) {
+                    (Value::Bool(true), Some(on), _) => ctx.render(on),
+                    (Value::Bool(false), _, Some(off)) => ctx.render(off),
+                    _ => match payload {
+                        Some(template) => ctx.render(&template.replace("{{value}}", &plain(&value))),
+                        None => plain(&value),
+                    },
+                };
+                mqtt.publish(&ctx.render(topic), payload.into_bytes(), *qos, *retain).await
+            }
+            DeviceHandler::SiteNotification { template } => {
+                NotificationService::post(
+                    pool,
+                    NewNotification::new(category::ALICE, NotificationPriority::Normal, &ctx.device.name, ctx.render(template))
+                        .data(ctx.to_json()),
+                )
+                .await;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Send Wake-on-LAN magic packet
+pub fn send_wol(mac_address: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Parse MAC address
+    let mac_bytes: Vec<u8> = mac_address
+        .split([':', '-'])
+        .map(|s| u8::from_str_radix(s, 16))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if mac_bytes.len() != 6 {
+        return Err("Invalid MAC address".into());
+    }
+
+    // Build magic packet: 6 bytes of 0xFF followed by MAC address 16 times
+    let mut packet = vec![0xFFu8; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac_bytes);
+    }
+
+    // Send to broadcast address on port 9
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_broadcast(true)?;
+    socket.send_to(&packet, "255.255.255.255:9")?;
+
+    Ok(())
+}
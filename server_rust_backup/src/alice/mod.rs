@@ -1,7 +1,18 @@
 pub mod models;
 pub mod service;
+pub mod schedule;
 pub mod n8n;
+pub mod schema;
+pub mod handlers;
+pub mod mqtt;
+pub mod discovery;
+pub mod callbacks;
+pub mod rules;
 
 pub use models::*;
 pub use service::*;
+pub use schedule::PcScheduleService;
 pub use n8n::*;
+pub use mqtt::MqttClient;
+pub use callbacks::YandexCallbacks;
+pub use rules::{RuleEngine, RuleEvent};
@@ -1,4 +1,13 @@
+use crate::notifications::NotificationPriority;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Wire types shared with the PC client
+pub use alice_pc_protocol::{
+    parse_signing_key, Capability, ClientMessage, Command as PcCommandType, CommandEnvelope, CommandReport,
+    CommandResult, Handshake, HeartbeatResponse, ServerMessage, SignedCommand, SigningKey, ARTIFACTS_UNAVAILABLE,
+    MAX_COMMAND_TTL_SECS, PROTOCOL_VERSION,
+};
 
 /// Request headers from Yandex
 #[derive(Debug, Clone)]
@@ -18,6 +27,7 @@ pub struct AliceUserInfo {
 pub struct DeviceCapability {
     #[serde(rename = "type")]
     pub capability_type: String,
+    #[serde(default = "default_retrievable")]
     pub retrievable: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reportable: Option<bool>,
@@ -39,6 +49,7 @@ pub struct CapabilityState {
 pub struct DeviceProperty {
     #[serde(rename = "type")]
     pub property_type: String,
+    #[serde(default = "default_retrievable")]
     pub retrievable: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reportable: Option<bool>,
@@ -48,6 +59,10 @@ pub struct DeviceProperty {
     pub state: Option<PropertyState>,
 }
 
+fn default_retrievable() -> bool {
+    true
+}
+
 /// Property state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PropertyState {
@@ -271,6 +286,137 @@ pub struct DbAliceDevice {
     pub properties: Option<String>,
     pub custom_data: Option<String>,
     pub is_enabled: bool,
+    pub handler: Option<String>,
+}
+
+/// What a registry device does when Alice changes one of its capabilities.
+/// Strings in templates may use `{{value}}`, `{{instance}}`, `{{capability}}`,
+/// `{{device_id}}` and `{{device_name}}`; a JSON string that is exactly `"{{value}}"`
+/// is replaced by the value itself, keeping numbers and booleans typed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeviceHandler {
+    /// Queue a PC client command; `off_command`, if set, is queued instead when the device is turned off
+    PcCommand {
+        command: serde_json::Value,
+        #[serde(default)]
+        off_command: Option<serde_json::Value>,
+        #[serde(default)]
+        priority: i32,
+        #[serde(default)]
+        client_id: Option<String>,
+        #[serde(default)]
+        group: Option<String>,
+    },
+    /// Wake-on-LAN on "on" (MAC from `custom_data.mac`), queued shutdown on "off"
+    PcPower,
+    /// Telegram message to `chat_id`, `custom_data.chat_id` or the admin
+    Telegram {
+        #[serde(default)]
+        chat_id: Option<i64>,
+        template: String,
+    },
+    /// HTTP request; without `body` the action itself is sent as JSON
+    Webhook {
+        url: String,
+        #[serde(default = "default_webhook_method")]
+        method: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        #[serde(default)]
+        body: Option<serde_json::Value>,
+    },
+    /// n8n workflow via its webhook; without `params` the action itself is sent
+    N8n {
+        workflow: String,
+        #[serde(default)]
+        params: Option<serde_json::Value>,
+    },
+    /// MQTT device: commands are published (the bare value without `payload`), `state_topics` are subscribed
+    Mqtt {
+        /// Command topic; may be empty for sensors that only report state
+        #[serde(default)]
+        topic: String,
+        /// Command topic per instance, e.g. a separate brightness topic; `topic` for the rest
+        #[serde(default)]
+        instance_topics: HashMap<String, String>,
+        #[serde(default)]
+        payload: Option<String>,
+        /// Published instead of true/false, e.g. "ON"/"OFF"
+        #[serde(default)]
+        payload_on: Option<String>,
+        #[serde(default)]
+        payload_off: Option<String>,
+        /// Device value matching 100% per instance, e.g. 255 for brightness
+        #[serde(default)]
+        scales: HashMap<String, f64>,
+        #[serde(default)]
+        qos: u8,
+        #[serde(default)]
+        retain: bool,
+        /// Topics the device reports its state to
+        #[serde(default)]
+        state_topics: Vec<MqttStateTopic>,
+    },
+    /// Notification on the website
+    SiteNotification { template: String },
+}
+
+/// Subscribed topic whose messages update one capability or property of a registry device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttStateTopic {
+    pub topic: String,
+    #[serde(rename = "type")]
+    pub state_type: String,
+    pub instance: String,
+    /// Dot path into a JSON payload, e.g. "temperature" for `{"temperature": 21.5}`
+    #[serde(default)]
+    pub value_path: Option<String>,
+    /// Raw payload to value, e.g. {"ON": "detected", "OFF": "not_detected"}
+    #[serde(default)]
+    pub payload_map: HashMap<String, serde_json::Value>,
+}
+
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
+/// Registry device as admins define it: the Yandex schema plus the bound handler
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceDefinition {
+    /// Taken from the path on update
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub room: Option<String>,
+    #[serde(rename = "type")]
+    pub device_type: String,
+    #[serde(default)]
+    pub capabilities: Vec<DeviceCapability>,
+    #[serde(default)]
+    pub properties: Vec<DeviceProperty>,
+    pub handler: DeviceHandler,
+    /// Handler settings editable from the admin panel, e.g. the PC's MAC address
+    #[serde(default)]
+    pub custom_data: Option<serde_json::Value>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// State reported for a capability or property of a registry device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceStateUpdate {
+    #[serde(rename = "type")]
+    pub state_type: String,
+    pub instance: String,
+    pub value: serde_json::Value,
 }
 
 /// Database model for Alice device state
@@ -300,7 +446,7 @@ pub struct DbAliceToken {
     pub expires_at: chrono::NaiveDateTime,
 }
 
-/// Notification for website (stored in memory, broadcast via SSE)
+/// Alice notification in the shape of the public feed; stored in the notification center
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebsiteNotification {
     pub id: String,
@@ -309,6 +455,17 @@ pub struct WebsiteNotification {
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+impl From<crate::notifications::Notification> for WebsiteNotification {
+    fn from(notification: crate::notifications::Notification) -> Self {
+        Self {
+            id: notification.id.to_string(),
+            message: notification.message,
+            notification_type: notification.priority,
+            created_at: notification.created_at,
+        }
+    }
+}
+
 /// PC control command
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PcCommand {
@@ -347,6 +504,42 @@ pub struct DbQueuedCommand {
     pub processed_at: Option<chrono::DateTime<chrono::Utc>>,
     pub result: Option<String>,
     pub error: Option<String>,
+    pub protocol_version: i32,
+    /// Signed `SignedCommand` JSON handed to the client verbatim
+    pub payload: Option<String>,
+    pub signature: Option<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only this client may claim the command
+    pub target_client_id: Option<String>,
+    /// Only clients of this group may claim the command
+    pub target_group: Option<String>,
+    /// Client that claimed the command for execution
+    pub claimed_by: Option<String>,
+    /// Deliveries so far; a retry is only made while below `max_attempts`
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// When the current claim started; it lapses after the visibility timeout
+    pub claimed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Retry backoff: not claimable before this time
+    pub available_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Stored file uploaded as the result, e.g. a screenshot
+    pub artifact_file_id: Option<String>,
+}
+
+/// Which PC clients may receive a queued command; empty means any capable client
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandTarget {
+    pub client_id: Option<String>,
+    pub group: Option<String>,
+}
+
+/// Delivery limits of a queued command; unset fields use the server defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandDelivery {
+    /// Signed expiry: the command is dead-lettered if not run by then
+    pub not_after: Option<chrono::DateTime<chrono::Utc>>,
+    /// Deliveries before the command is dead-lettered
+    pub max_attempts: Option<i32>,
 }
 
 /// PC client registration
@@ -361,41 +554,69 @@ pub struct DbPcClient {
     pub mac_address: Option<String>,
     pub ip_address: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Set by the heartbeat handshake; None for clients that never sent one
+    pub protocol_version: Option<i32>,
+    /// JSON array of capability names from the last handshake
+    pub capabilities: Option<serde_json::Value>,
+    /// Group for commands targeted at several clients, e.g. `office`
+    pub client_group: Option<String>,
+}
+
+impl DbPcClient {
+    /// Capabilities announced in the last handshake, if it used the current protocol
+    pub fn supported_capabilities(&self) -> Vec<String> {
+        if self.protocol_version != Some(PROTOCOL_VERSION as i32) {
+            return Vec::new();
+        }
+        self.capabilities
+            .as_ref()
+            .and_then(|c| serde_json::from_value(c.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Scheduled or recurring PC command
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DbPcSchedule {
+    pub id: i64,
+    pub name: String,
+    pub command_data: serde_json::Value,
+    pub priority: i32,
+    pub target_client_id: Option<String>,
+    pub target_group: Option<String>,
+    pub max_attempts: Option<i32>,
+    /// Six-field cron expression (with seconds), evaluated in UTC; None for one-shot schedules
+    pub cron_expr: Option<String>,
+    /// When a one-shot schedule fires
+    pub run_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Delay a one-shot schedule is re-armed with on resume, e.g. "shutdown in 30 minutes"
+    pub delay_secs: Option<i64>,
+    pub is_paused: bool,
+    /// Exposed to Alice as an on/off device so it can be used in scenes
+    pub alice_scene: bool,
+    pub last_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_command_id: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
-/// Command types that can be sent to PC
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type", content = "data")]
-pub enum PcCommandType {
-    /// Turn on PC via Wake-on-LAN
-    WakeOnLan,
-    /// Shutdown the PC
-    Shutdown,
-    /// Restart the PC
-    Restart,
-    /// Lock the PC
-    Lock,
-    /// Open a URL in browser
-    OpenUrl { url: String },
-    /// Run a command
-    RunCommand { command: String, args: Vec<String> },
-    /// Send notification to PC
-    Notification { title: String, message: String },
-    /// Control volume
-    Volume { action: String, value: Option<i32> },
-    /// Take screenshot
-    Screenshot,
-    /// Custom command
-    Custom { name: String, payload: serde_json::Value },
-}
-
-/// Response from PC client after processing command
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CommandResult {
-    pub command_id: i64,
-    pub success: bool,
-    pub result: Option<String>,
-    pub error: Option<String>,
+/// Schedule creation request; exactly one of `cron`, `run_at` and `delay_secs` is set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateScheduleRequest {
+    pub name: String,
+    pub command: PcCommandType,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(flatten)]
+    pub target: CommandTarget,
+    pub max_attempts: Option<i32>,
+    pub cron: Option<String>,
+    pub run_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub delay_secs: Option<i64>,
+    #[serde(default)]
+    pub paused: bool,
+    #[serde(default)]
+    pub alice_scene: bool,
 }
 
 /// PC client API request to get pending commands
@@ -412,6 +633,8 @@ pub struct PollCommandsRequest {
 pub struct RegisterClientRequest {
     pub client_name: String,
     pub mac_address: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
 }
 
 /// PC client registration response
@@ -420,3 +643,159 @@ pub struct RegisterClientResponse {
     pub client_id: String,
     pub api_key: String,
 }
+
+/// What starts an automation rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleTrigger {
+    /// Alice changed a device; `instance` and `value` narrow it down
+    AliceAction {
+        device_id: String,
+        #[serde(default)]
+        instance: Option<String>,
+        #[serde(default)]
+        value: Option<serde_json::Value>,
+    },
+    /// A PC client started sending heartbeats; any client without `client_id`
+    PcOnline {
+        #[serde(default)]
+        client_id: Option<String>,
+    },
+    /// A PC client stopped sending heartbeats
+    PcOffline {
+        #[serde(default)]
+        client_id: Option<String>,
+    },
+    /// Six-field cron expression (with seconds), evaluated in UTC like PC schedules
+    Schedule { cron: String },
+    /// A competitive CS2 match reached game over; `result` is "win", "loss" or "draw"
+    Cs2MatchEnd {
+        #[serde(default)]
+        result: Option<String>,
+    },
+    /// POST to `/api/alice/rules/webhook/<key>`
+    Webhook { key: String },
+}
+
+impl RuleTrigger {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RuleTrigger::AliceAction { .. } => "alice_action",
+            RuleTrigger::PcOnline { .. } => "pc_online",
+            RuleTrigger::PcOffline { .. } => "pc_offline",
+            RuleTrigger::Schedule { .. } => "schedule",
+            RuleTrigger::Cs2MatchEnd { .. } => "cs2_match_end",
+            RuleTrigger::Webhook { .. } => "webhook",
+        }
+    }
+}
+
+/// Checked when a rule triggers; all must hold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// "HH:MM" server local time, `to` before `from` spans midnight; `days` are 1 (Monday) to 7
+    TimeWindow {
+        from: String,
+        to: String,
+        #[serde(default)]
+        days: Vec<u32>,
+    },
+    /// Compare the last known value of a device capability or property
+    DeviceState {
+        device_id: String,
+        instance: String,
+        #[serde(default)]
+        op: CompareOp,
+        value: serde_json::Value,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    #[default]
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+/// One step of a rule; steps run in order and stop at the first failure.
+/// Strings may use `{{field}}` placeholders filled from the trigger event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    PcCommand {
+        command: serde_json::Value,
+        #[serde(default)]
+        priority: i32,
+        #[serde(default)]
+        client_id: Option<String>,
+        #[serde(default)]
+        group: Option<String>,
+    },
+    /// To `chat_id`, the admin by default
+    Telegram {
+        #[serde(default)]
+        chat_id: Option<i64>,
+        text: String,
+    },
+    /// Into the notification center; `title` defaults to the rule name
+    SiteNotification {
+        text: String,
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        priority: NotificationPriority,
+    },
+    /// `params` default to the event
+    N8n {
+        workflow: String,
+        #[serde(default)]
+        params: Option<serde_json::Value>,
+    },
+}
+
+/// Rule as created and edited from the admin panel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleDefinition {
+    pub name: String,
+    pub trigger: RuleTrigger,
+    #[serde(default)]
+    pub conditions: Vec<RuleCondition>,
+    pub actions: Vec<RuleAction>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+/// Database model for an automation rule
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DbAliceRule {
+    pub id: i64,
+    pub name: String,
+    pub trigger_type: String,
+    pub trigger_data: serde_json::Value,
+    pub conditions: serde_json::Value,
+    pub actions: serde_json::Value,
+    pub is_enabled: bool,
+    pub last_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_status: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Database model for one evaluation of a rule
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DbAliceRuleRun {
+    pub id: i64,
+    pub rule_id: i64,
+    pub event: serde_json::Value,
+    pub dry_run: bool,
+    /// "done", "skipped" (a condition failed) or "failed"
+    pub status: String,
+    /// Outcome of every condition and action, in order
+    pub steps: serde_json::Value,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
@@ -0,0 +1,427 @@
+use crate::alice::discovery;
+use crate::alice::models::*;
+use crate::alice::schema;
+use crate::alice::service::{AliceService, AliceState};
+use crate::db::DbPool;
+use parking_lot::RwLock;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, Publish, QoS};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// Registry device listening on a state topic
+#[derive(Clone)]
+struct StateRoute {
+    device_id: String,
+    state: MqttStateTopic,
+    scale: Option<f64>,
+}
+
+/// MQTT bridge for registry devices: `mqtt` handlers publish commands, their state topics
+/// feed `alice_device_states`, and Home Assistant discovery can create devices.
+/// Disabled unless `MQTT_HOST` is set.
+#[derive(Clone)]
+pub struct MqttClient {
+    client: Option<AsyncClient>,
+    /// State topic -> devices listening on it
+    routes: Arc<RwLock<HashMap<String, Vec<StateRoute>>>>,
+    /// Set while `MQTT_DISCOVERY` is on
+    discovery_prefix: Option<String>,
+    /// Keeps concurrent syncs from subscribing against a stale device list
+    sync_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl MqttClient {
+    /// Connect to the broker from `MQTT_*` settings; the event loop reconnects on its own
+    /// and subscribes again after every connect
+    pub fn from_env(pool: DbPool, alice_state: AliceState) -> Self {
+        let discovery_prefix = env::var("MQTT_DISCOVERY")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false)
+            .then(|| env::var("MQTT_DISCOVERY_PREFIX").unwrap_or_else(|_| "homeassistant".to_string()));
+        let Ok(host) = env::var("MQTT_HOST") else {
+            return Self {
+                client: None,
+                routes: Arc::new(RwLock::new(HashMap::new())),
+                discovery_prefix: None,
+                sync_lock: Arc::new(tokio::sync::Mutex::new(())),
+            };
+        };
+        let port = env::var("MQTT_PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(1883);
+        let client_id = env::var("MQTT_CLIENT_ID").unwrap_or_else(|_| "bgalin-server".to_string());
+
+        let mut options = MqttOptions::new(client_id, host.clone(), port);
+        options.set_keep_alive(Duration::from_secs(30));
+        // Discovery configs are retained and may be large
+        options.set_max_packet_size(256 * 1024, 256 * 1024);
+        if let Ok(username) = env::var("MQTT_USERNAME") {
+            options.set_credentials(username, env::var("MQTT_PASSWORD").unwrap_or_default());
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, 64);
+        let bridge = Self {
+            client: Some(client),
+            routes: Arc::new(RwLock::new(HashMap::new())),
+            discovery_prefix,
+            sync_lock: Arc::new(tokio::sync::Mutex::new(())),
+        };
+
+        // Messages are handled one at a time and in order, off the event loop
+        let (messages, mut incoming) = tokio::sync::mpsc::unbounded_channel::<Publish>();
+        let (this, worker_pool) = (bridge.clone(), pool.clone());
+        tokio::spawn(async move {
+            while let Some(message) = incoming.recv().await {
+                this.handle_message(&worker_pool, &alice_state, &message.topic, &message.payload)
+                    .await;
+            }
+        });
+
+        let this = bridge.clone();
+        tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("Connected to MQTT broker {}:{}", host, port);
+                        // Clean sessions lose subscriptions; subscribing from here would block the loop
+                        let (this, pool) = (this.clone(), pool.clone());
+                        tokio::spawn(async move { this.subscribe_all(&pool).await });
+                    }
+                    Ok(Event::Incoming(Packet::Publish(message))) => {
+                        let _ = messages.send(message);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("MQTT connection error: {}", e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+
+        bridge
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn discovery_enabled(&self) -> bool {
+        self.client.is_some() && self.discovery_prefix.is_some()
+    }
+
+    /// Queue a message for the broker; QoS above 2 is rejected
+    pub async fn publish(&self, topic: &str, payload: Vec<u8>, qos: u8, retain: bool) -> Result<(), String> {
+        let client = self.client.as_ref().ok_or("MQTT is not configured (MQTT_HOST)")?;
+        let qos = match qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            _ => return Err("qos must be 0, 1 or 2".to_string()),
+        };
+        client
+            .publish(topic, qos, retain, payload)
+            .await
+            .map_err(|e| format!("MQTT publish failed: {}", e))
+    }
+
+    /// Pick up state topics of added, changed and removed registry devices
+    pub async fn refresh(&self, pool: &DbPool) {
+        if self.client.is_some() {
+            self.sync_routes(pool, false).await;
+        }
+    }
+
+    async fn subscribe_all(&self, pool: &DbPool) {
+        let Some(client) = &self.client else { return };
+        if let Some(prefix) = &self.discovery_prefix {
+            // <prefix>/<component>/[<node_id>/]<object_id>/config
+            for filter in [format!("{}/+/+/config", prefix), format!("{}/+/+/+/config", prefix)] {
+                if let Err(e) = client.subscribe(filter, QoS::AtLeastOnce).await {
+                    error!("MQTT discovery subscribe failed: {}", e);
+                }
+            }
+        }
+        self.sync_routes(pool, true).await;
+    }
+
+    /// Rebuild the topic table from the registry and (un)subscribe the difference;
+    /// `resubscribe` subscribes every topic again, as after a reconnect
+    async fn sync_routes(&self, pool: &DbPool, resubscribe: bool) {
+        let Some(client) = &self.client else { return };
+        let _guard = self.sync_lock.lock().await;
+        let devices = match AliceService::list_definitions(pool).await {
+            Ok(devices) => devices,
+            Err(e) => {
+                error!("Failed to load Alice devices for MQTT: {}", e);
+                return;
+            }
+        };
+
+        let mut routes: HashMap<String, Vec<StateRoute>> = HashMap::new();
+        for device in devices.into_iter().filter(|d| d.enabled) {
+            if let DeviceHandler::Mqtt { scales, state_topics, .. } = device.handler {
+                for state in state_topics {
+                    routes.entry(state.topic.clone()).or_default().push(StateRoute {
+                        device_id: device.id.clone(),
+                        scale: scales.get(&state.instance).copied(),
+                        state,
+                    });
+                }
+            }
+        }
+
+        let previous = std::mem::replace(&mut *self.routes.write(), routes.clone());
+        for topic in routes.keys().filter(|t| resubscribe || !previous.contains_key(*t)) {
+            if let Err(e) = client.subscribe(topic, QoS::AtLeastOnce).await {
+                error!("MQTT subscribe to {} failed: {}", topic, e);
+            }
+        }
+        for topic in previous.keys().filter(|t| !routes.contains_key(*t)) {
+            if let Err(e) = client.unsubscribe(topic).await {
+                warn!("MQTT unsubscribe from {} failed: {}", topic, e);
+            }
+        }
+    }
+
+    async fn handle_message(&self, pool: &DbPool, alice_state: &AliceState, topic: &str, payload: &[u8]) {
+        if let Some(prefix) = &self.discovery_prefix {
+            if topic.starts_with(&format!("{}/", prefix)) && topic.ends_with("/config") {
+                self.handle_discovery(pool, alice_state, &topic[prefix.len() + 1..], topic, payload)
+                    .await;
+                return;
+            }
+        }
+
+        let routes = self.routes.read().get(topic).cloned().unwrap_or_default();
+        for route in routes {
+            let Some(value) = parse_state(&route, payload) else {
+                debug!("Ignoring MQTT message on {} for {}", topic, route.device_id);
+                continue;
+            };
+            let update = DeviceStateUpdate {
+                state_type: route.state.state_type.clone(),
+                instance: route.state.instance.clone(),
+                value,
+            };
+            if let Err(e) = AliceService::report_state(pool, &route.device_id, &update, alice_state).await {
+                warn!("MQTT state for {} rejected: {}", route.device_id, e);
+            }
+        }
+    }
+
+    /// Create, update or remove the device announced by a discovery config.
+    /// Devices that were not created by discovery are never touched.
+    async fn handle_discovery(
+        &self,
+        pool: &DbPool,
+        alice_state: &AliceState,
+        path: &str,
+        topic: &str,
+        payload: &[u8],
+    ) {
+        let config: Option<Value> = if payload.is_empty() {
+            None
+        } else {
+            match serde_json::from_slice(payload) {
+                Ok(config) => Some(config),
+                Err(e) => {
+                    warn!("Invalid MQTT discovery config on {}: {}", topic, e);
+                    return;
+                }
+            }
+        };
+        let device_id = discovery::device_id(path);
+
+        let existing = match AliceService::get_definition(pool, &device_id).await {
+            Ok(existing) => existing,
+            Err(e) => {
+                error!("Failed to load Alice device {}: {}", device_id, e);
+                return;
+            }
+        };
+        if let Some(existing) = &existing {
+            if discovery::source_topic(existing) != Some(topic) {
+                warn!("MQTT discovery on {} skipped: device {} exists", topic, device_id);
+                return;
+            }
+        }
+
+        // An empty retained config removes the device
+        let Some(config) = config else {
+            if existing.is_some() {
+                match AliceService::delete_device(pool, &device_id, alice_state).await {
+                    Ok(_) => info!("MQTT discovery removed device {}", device_id),
+                    Err(e) => error!("Failed to delete Alice device {}: {}", device_id, e),
+                }
+                self.refresh(pool).await;
+            }
+            return;
+        };
+
+        let device = match discovery::to_device(path, &device_id, topic, &config) {
+            Ok(Some(device)) => device,
+            Ok(None) => {
+                debug!("MQTT discovery on {} has no Alice counterpart", topic);
+                return;
+            }
+            Err(e) => {
+                warn!("MQTT discovery on {} skipped: {}", topic, e);
+                return;
+            }
+        };
+        // Retained configs are delivered again on every reconnect
+        if existing.as_ref().map(serde_json::to_value).and_then(Result::ok) == serde_json::to_value(&device).ok() {
+            return;
+        }
+
+        match AliceService::save_device(pool, &device, existing.is_none(), alice_state).await {
+            Ok(_) => info!("MQTT discovery saved device {} ({})", device_id, device.name),
+            Err(e) => warn!("MQTT discovery for {} rejected: {}", device_id, e),
+        }
+        self.refresh(pool).await;
+    }
+}
+
+/// Turn a state message into an Alice value: optional JSON path, then the payload map,
+/// on/off words for booleans and the scale for numbers
+fn parse_state(route: &StateRoute, payload: &[u8]) -> Option<Value> {
+    let text = std::str::from_utf8(payload).ok()?.trim();
+    let mut value = serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()));
+    if let Some(path) = &route.state.value_path {
+        for key in path.split('.') {
+            value = value.get(key)?.clone();
+        }
+    }
+
+    let raw = match &value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if let Some(mapped) = route.state.payload_map.get(&raw) {
+        return Some(mapped.clone());
+    }
+    // Anything else is not a state once a map is given, e.g. "unavailable"
+    if !route.state.payload_map.is_empty() {
+        return None;
+    }
+
+    match route.state.state_type.as_str() {
+        schema::ON_OFF | schema::TOGGLE => match raw.to_lowercase().as_str() {
+            "on" | "true" | "1" => Some(Value::Bool(true)),
+            "off" | "false" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        schema::RANGE | schema::FLOAT => {
+            let number = raw.parse::<f64>().ok()?;
+            match route.scale {
+                Some(scale) => Some(Value::from((number * 100.0 / scale).round() as i64)),
+                None if value.is_number() => Some(value),
+                None => serde_json::Number::from_f64(number).map(Value::Number),
+            }
+        }
+        _ => Some(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    fn route(state_type: &str, value_path: Option<&str>, payload_map: &[(&str, Value)], scale: Option<f64>) -> StateRoute {
+        StateRoute {
+            device_id: "mqtt-test".to_string(),
+            state: MqttStateTopic {
+                topic: "home/test".to_string(),
+                state_type: state_type.to_string(),
+                instance: "on".to_string(),
+                value_path: value_path.map(str::to_string),
+                payload_map: payload_map.iter().map(|(k, v)| (k.to_string(), v.clone())).collect(),
+            },
+            scale,
+        }
+    }
+
+    #[test]
+    fn json_path_selects_nested_value() {
+        let route = route(schema::FLOAT, Some("sensor.temperature"), &[], None);
+        assert_eq!(parse_state(&route, br#"{"sensor": {"temperature": 21.5}}"#), Some(json!(21.5)));
+        assert_eq!(parse_state(&route, br#"{"sensor": {}}"#), None);
+        assert_eq!(parse_state(&route, b"21.5"), None);
+    }
+
+    #[test]
+    fn raw_on_off_words_become_booleans() {
+        let route = route(schema::ON_OFF, None, &[], None);
+        for (payload, expected) in [("ON", true), ("on", true), ("true", true), ("1", true), ("OFF", false), ("0", false)] {
+            assert_eq!(parse_state(&route, payload.as_bytes()), Some(json!(expected)), "{}", payload);
+        }
+        assert_eq!(parse_state(&route, b"unavailable"), None);
+    }
+
+    #[test]
+    fn payload_map_wins_and_rejects_unmapped_payloads() {
+        let route = route(schema::EVENT, None, &[("ON", json!("detected")), ("OFF", json!("not_detected"))], None);
+        assert_eq!(parse_state(&route, b"ON"), Some(json!("detected")));
+        assert_eq!(parse_state(&route, b" OFF\n"), Some(json!("not_detected")));
+        assert_eq!(parse_state(&route, b"unavailable"), None);
+    }
+
+    #[test]
+    fn numeric_payloads_keep_their_value_or_scale_to_percent() {
+        let plain = route(schema::FLOAT, None, &[], None);
+        assert_eq!(parse_state(&plain, b"42"), Some(json!(42)));
+        assert_eq!(parse_state(&plain, b"\"3.5\""), Some(json!(3.5)));
+        assert_eq!(parse_state(&plain, b"warm"), None);
+
+        let scaled = route(schema::RANGE, None, &[], Some(255.0));
+        assert_eq!(parse_state(&scaled, b"255"), Some(json!(100)));
+        assert_eq!(parse_state(&scaled, b"128"), Some(json!(50)));
+    }
+
+    /// Round trip through a real broker: `MQTT_URL=mqtt://localhost:1883 cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn state_published_to_broker_reaches_the_registry() {
+        let url = url::Url::parse(&env::var("MQTT_URL").expect("MQTT_URL is not set")).unwrap();
+        let (_dir, pool) = test_support::sqlite_pool().await;
+        let alice_state = AliceState::new();
+        let topic = format!("bgalin-test/{}/state", uuid::Uuid::new_v4());
+
+        let bridge = {
+            let _env = test_support::env_lock();
+            env::set_var("MQTT_HOST", url.host_str().unwrap());
+            env::set_var("MQTT_PORT", url.port().unwrap_or(1883).to_string());
+            env::set_var("MQTT_CLIENT_ID", format!("bgalin-test-{}", uuid::Uuid::new_v4()));
+            env::remove_var("MQTT_DISCOVERY");
+            MqttClient::from_env(pool.clone(), alice_state.clone())
+        };
+
+        let config = json!({"name": "Test plug", "command_topic": "bgalin-test/set", "state_topic": topic});
+        let device = discovery::to_device("switch/plug/config", "mqtt-switch-plug", "homeassistant/switch/plug/config", &config)
+            .unwrap()
+            .unwrap();
+        AliceService::save_device(&pool, &device, true, &alice_state).await.unwrap();
+        // Let the connection and its subscriptions settle before publishing
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        bridge.refresh(&pool).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        bridge.publish(&topic, b"ON".to_vec(), 1, false).await.unwrap();
+        for _ in 0..50 {
+            let (capabilities, _) = AliceService::get_device_state(&pool, "mqtt-switch-plug", &alice_state)
+                .await
+                .unwrap()
+                .unwrap();
+            if capabilities[0].state.as_ref().map(|s| &s.value) == Some(&json!(true)) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("state from {} never reached the registry", topic);
+    }
+}
@@ -0,0 +1,962 @@
+use crate::alice::models::*;
+use crate::alice::n8n::N8nClient;
+use crate::alice::service::{AliceService, AliceState, CommandQueueService, PC_ONLINE_WINDOW_SECS};
+use crate::db::DbPool;
+use crate::notifications::{category, NewNotification, NotificationService};
+use crate::telegram::TelegramBot;
+use chrono::{Datelike, NaiveTime, Timelike};
+use parking_lot::RwLock;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_cron_scheduler::{Job, JobScheduler as CronScheduler};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Webhook keys are the only secret guarding the public trigger
+const MIN_WEBHOOK_KEY_LEN: usize = 16;
+
+/// Something that happened and may start rules
+#[derive(Debug, Clone)]
+pub enum RuleEvent {
+    AliceAction { device_id: String, instance: String, value: Value },
+    PcOnline { client_id: String, client_name: String },
+    PcOffline { client_id: String, client_name: String },
+    Cs2MatchEnd { map: Option<String>, mode: Option<String>, result: String, ct_score: u32, t_score: u32 },
+    Webhook { key: String, body: Value },
+}
+
+impl RuleEvent {
+    /// Event fields available to `{{field}}` placeholders; webhook bodies are merged in
+    pub fn data(&self) -> Value {
+        match self {
+            RuleEvent::AliceAction { device_id, instance, value } => {
+                json!({"device_id": device_id, "instance": instance, "value": value})
+            }
+            RuleEvent::PcOnline { client_id, client_name } | RuleEvent::PcOffline { client_id, client_name } => {
+                json!({"client_id": client_id, "client_name": client_name})
+            }
+            RuleEvent::Cs2MatchEnd { map, mode, result, ct_score, t_score } => json!({
+                "map": map, "mode": mode, "result": result, "ct_score": ct_score, "t_score": t_score,
+            }),
+            RuleEvent::Webhook { body, .. } => {
+                let mut data = match body {
+                    Value::Object(fields) => fields.clone(),
+                    _ => serde_json::Map::new(),
+                };
+                data.insert("body".to_string(), body.clone());
+                Value::Object(data)
+            }
+        }
+    }
+
+    fn matches(&self, trigger: &RuleTrigger) -> bool {
+        match (trigger, self) {
+            (
+                RuleTrigger::AliceAction { device_id, instance, value },
+                RuleEvent::AliceAction { device_id: id, instance: i, value: v },
+            ) => device_id == id && instance.as_ref().is_none_or(|x| x == i) && value.as_ref().is_none_or(|x| x == v),
+            (RuleTrigger::PcOnline { client_id }, RuleEvent::PcOnline { client_id: id, .. })
+            | (RuleTrigger::PcOffline { client_id }, RuleEvent::PcOffline { client_id: id, .. }) => {
+                client_id.as_ref().is_none_or(|x| x == id)
+            }
+            (RuleTrigger::Cs2MatchEnd { result }, RuleEvent::Cs2MatchEnd { result: r, .. }) => {
+                result.as_ref().is_none_or(|x| x == r)
+            }
+            (RuleTrigger::Webhook { key }, RuleEvent::Webhook { key: k, .. }) => key == k,
+            _ => false,
+        }
+    }
+}
+
+/// Automation rules: a trigger, conditions and ordered actions stored in `alice_rules`,
+/// with every evaluation recorded in `alice_rule_runs`
+#[derive(Clone)]
+pub struct RuleEngine {
+    pool: DbPool,
+    scheduler: CronScheduler,
+    /// Rule ID -> cron job of an enabled schedule rule
+    jobs: Arc<RwLock<HashMap<i64, Uuid>>>,
+    /// PC clients currently sending heartbeats: client ID -> name
+    online_clients: Arc<RwLock<HashMap<String, String>>>,
+    alice_state: AliceState,
+    telegram_bot: Arc<TelegramBot>,
+    admin_telegram_id: i64,
+}
+
+impl RuleEngine {
+    /// Start the scheduler, arm schedule rules and note which PC clients are already online
+    pub async fn init(
+        pool: DbPool,
+        alice_state: AliceState,
+        telegram_bot: TelegramBot,
+        admin_telegram_id: i64,
+    ) -> Result<Self, String> {
+        let scheduler = CronScheduler::new().await.map_err(|e| e.to_string())?;
+        scheduler.start().await.map_err(|e| e.to_string())?;
+
+        let online = CommandQueueService::online_clients(&pool, PC_ONLINE_WINDOW_SECS)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !online.is_empty() {
+            alice_state.set_pc_status("pc-control".to_string(), true);
+        }
+
+        let engine = Self {
+            pool,
+            scheduler,
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+            online_clients: Arc::new(RwLock::new(online.into_iter().collect())),
+            alice_state,
+            telegram_bot: Arc::new(telegram_bot),
+            admin_telegram_id,
+        };
+
+        let rules = engine.list().await.map_err(|e| e.to_string())?;
+        for rule in rules.iter().filter(|r| r.is_enabled && r.trigger_type == "schedule") {
+            if let Err(e) = engine.arm(rule).await {
+                error!("Failed to arm rule {}: {}", rule.id, e);
+            }
+        }
+
+        info!("Alice rules: {} total, {} scheduled", rules.len(), engine.jobs.read().len());
+        Ok(engine)
+    }
+
+    pub async fn list(&self) -> Result<Vec<DbAliceRule>, sqlx::Error> {
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, DbAliceRule>("SELECT * FROM alice_rules ORDER BY id")
+                    .fetch_all(p)
+                    .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, DbAliceRule>(
+                    "SELECT id::INT8 AS id, name, trigger_type, trigger_data, conditions, actions, is_enabled, last_run_at, last_status, created_at
+                     FROM alice_rules ORDER BY id"
+                )
+                .fetch_all(p)
+                .await
+            }
+        }
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<DbAliceRule>, sqlx::Error> {
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, DbAliceRule>("SELECT * FROM alice_rules WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(p)
+                    .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, DbAliceRule>(
+                    "SELECT id::INT8 AS id, name, trigger_type, trigger_data, conditions, actions, is_enabled, last_run_at, last_status, created_at
+                     FROM alice_rules WHERE id = $1"
+                )
+                .bind(id)
+                .fetch_optional(p)
+                .await
+            }
+        }
+    }
+
+    /// Stored rule back in its editable form
+    pub fn definition(rule: &DbAliceRule) -> Result<RuleDefinition, String> {
+        Ok(RuleDefinition {
+            name: rule.name.clone(),
+            trigger: serde_json::from_value(rule.trigger_data.clone()).map_err(|e| format!("trigger: {}", e))?,
+            conditions: serde_json::from_value(rule.conditions.clone()).map_err(|e| format!("conditions: {}", e))?,
+            actions: serde_json::from_value(rule.actions.clone()).map_err(|e| format!("actions: {}", e))?,
+            enabled: rule.is_enabled,
+        })
+    }
+
+    pub async fn create(&self, definition: &RuleDefinition) -> Result<DbAliceRule, String> {
+        validate(definition)?;
+        let (trigger, conditions, actions) = encode(definition)?;
+
+        let id = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query(
+                "INSERT INTO alice_rules (name, trigger_type, trigger_data, conditions, actions, is_enabled) VALUES (?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&definition.name)
+            .bind(definition.trigger.kind())
+            .bind(&trigger)
+            .bind(&conditions)
+            .bind(&actions)
+            .bind(definition.enabled)
+            .execute(p)
+            .await
+            .map_err(|e| e.to_string())?
+            .last_insert_rowid(),
+            DbPool::Postgres(p) => {
+                let row: (i64,) = sqlx::query_as(
+                    "INSERT INTO alice_rules (name, trigger_type, trigger_data, conditions, actions, is_enabled)
+                     VALUES ($1, $2, $3, $4, $5, $6) RETURNING id::INT8"
+                )
+                .bind(&definition.name)
+                .bind(definition.trigger.kind())
+                .bind(&trigger)
+                .bind(&conditions)
+                .bind(&actions)
+                .bind(definition.enabled)
+                .fetch_one(p)
+                .await
+                .map_err(|e| e.to_string())?;
+                row.0
+            }
+        };
+
+        let rule = self.get(id).await.map_err(|e| e.to_string())?.ok_or("Rule vanished")?;
+        self.rearm(&rule).await?;
+        info!("Created Alice rule {} ({})", rule.id, rule.name);
+        Ok(rule)
+    }
+
+    /// Replace a rule; `Ok(false)` if it does not exist
+    pub async fn update(&self, id: i64, definition: &RuleDefinition) -> Result<bool, String> {
+        validate(definition)?;
+        let (trigger, conditions, actions) = encode(definition)?;
+
+        let affected = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query(
+                "UPDATE alice_rules SET name = ?, trigger_type = ?, trigger_data = ?, conditions = ?, actions = ?, is_enabled = ? WHERE id = ?"
+            )
+            .bind(&definition.name)
+            .bind(definition.trigger.kind())
+            .bind(&trigger)
+            .bind(&conditions)
+            .bind(&actions)
+            .bind(definition.enabled)
+            .bind(id)
+            .execute(p)
+            .await
+            .map(|r| r.rows_affected()),
+            DbPool::Postgres(p) => sqlx::query(
+                "UPDATE alice_rules SET name = $1, trigger_type = $2, trigger_data = $3, conditions = $4, actions = $5, is_enabled = $6 WHERE id = $7"
+            )
+            .bind(&definition.name)
+            .bind(definition.trigger.kind())
+            .bind(&trigger)
+            .bind(&conditions)
+            .bind(&actions)
+            .bind(definition.enabled)
+            .bind(id)
+            .execute(p)
+            .await
+            .map(|r| r.rows_affected()),
+        }
+        .map_err(|e| e.to_string())?;
+        if affected == 0 {
+            return Ok(false);
+        }
+
+        let rule = self.get(id).await.map_err(|e| e.to_string())?.ok_or("Rule vanished")?;
+        self.rearm(&rule).await?;
+        Ok(true)
+    }
+
+    /// Delete a rule together with its history
+    pub async fn delete(&self, id: i64) -> Result<bool, String> {
+        self.disarm(id).await;
+        let result = match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("DELETE FROM alice_rule_runs WHERE rule_id = ?")
+                    .bind(id)
+                    .execute(p)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                sqlx::query("DELETE FROM alice_rules WHERE id = ?")
+                    .bind(id)
+                    .execute(p)
+                    .await
+                    .map(|r| r.rows_affected())
+            }
+            DbPool::Postgres(p) => sqlx::query("DELETE FROM alice_rules WHERE id = $1")
+                .bind(id)
+                .execute(p)
+                .await
+                .map(|r| r.rows_affected()),
+        };
+        Ok(result.map_err(|e| e.to_string())? > 0)
+    }
+
+    /// Next time a schedule rule fires
+    pub async fn next_run(&self, id: i64) -> Option<chrono::DateTime<chrono::Utc>> {
+        let job = self.jobs.read().get(&id).copied()?;
+        self.scheduler.clone().next_tick_for_job(job).await.ok().flatten()
+    }
+
+    /// Latest evaluations, of one rule or of all
+    pub async fn history(&self, rule_id: Option<i64>, limit: i64) -> Result<Vec<DbAliceRuleRun>, sqlx::Error> {
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, DbAliceRuleRun>(
+                    "SELECT * FROM alice_rule_runs WHERE (? IS NULL OR rule_id = ?) ORDER BY id DESC LIMIT ?"
+                )
+                .bind(rule_id)
+                .bind(rule_id)
+                .bind(limit)
+                .fetch_all(p)
+                .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, DbAliceRuleRun>(
+                    "SELECT id::INT8 AS id, rule_id::INT8 AS rule_id, event, dry_run, status, steps, created_at
+                     FROM alice_rule_runs WHERE ($1::INT8 IS NULL OR rule_id = $1) ORDER BY id DESC LIMIT $2"
+                )
+                .bind(rule_id)
+                .bind(limit)
+                .fetch_all(p)
+                .await
+            }
+        }
+    }
+
+    /// Drop history older than `days`
+    pub async fn purge_history(pool: &DbPool, days: i64) -> Result<u64, sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => sqlx::query("DELETE FROM alice_rule_runs WHERE created_at < datetime('now', ?)")
+                .bind(format!("-{} days", days))
+                .execute(p)
+                .await
+                .map(|r| r.rows_affected()),
+            DbPool::Postgres(p) => {
+                sqlx::query("DELETE FROM alice_rule_runs WHERE created_at < NOW() - make_interval(days => $1)")
+                    .bind(days as i32)
+                    .execute(p)
+                    .await
+                    .map(|r| r.rows_affected())
+            }
+        }
+    }
+
+    /// Start every enabled rule whose trigger matches; returns how many were started
+    pub async fn dispatch(&self, event: RuleEvent) -> usize {
+        let rules = match self.list().await {
+            Ok(rules) => rules,
+            Err(e) => {
+                error!("Failed to load Alice rules: {}", e);
+                return 0;
+            }
+        };
+
+        let mut started = 0;
+        for rule in rules.into_iter().filter(|r| r.is_enabled) {
+            let definition = match Self::definition(&rule) {
+                Ok(definition) => definition,
+                Err(e) => {
+                    warn!("Skipping broken Alice rule {}: {}", rule.id, e);
+                    continue;
+                }
+            };
+            if !event.matches(&definition.trigger) {
+                continue;
+            }
+            started += 1;
+            let (engine, data) = (self.clone(), event.data());
+            tokio::spawn(async move {
+                engine.execute(&rule, &definition, data, false).await;
+            });
+        }
+        started
+    }
+
+    /// Evaluate a rule against a made-up event without carrying out its actions
+    pub async fn dry_run(&self, id: i64, event: Value) -> Result<Option<DbAliceRuleRun>, String> {
+        let Some(rule) = self.get(id).await.map_err(|e| e.to_string())? else {
+            return Ok(None);
+        };
+        let definition = Self::definition(&rule)?;
+        Ok(Some(self.execute(&rule, &definition, event, true).await))
+    }
+
+    /// A heartbeat arrived; the first one of a client starts its `pc_online` rules
+    pub fn client_seen(&self, client_id: &str, client_name: &str) {
+        let was_online = self
+            .online_clients
+            .write()
+            .insert(client_id.to_string(), client_name.to_string())
+            .is_some();
+        if !was_online {
+            info!("PC client {} came online", client_name);
+            let engine = self.clone();
+            let event = RuleEvent::PcOnline {
+                client_id: client_id.to_string(),
+                client_name: client_name.to_string(),
+            };
+            tokio::spawn(async move {
+                engine.dispatch(event).await;
+            });
+        }
+    }
+
+    /// Clients without a heartbeat for [`PC_ONLINE_WINDOW_SECS`] go offline; the PC device
+    /// turns off once the last one does
+    pub async fn sweep_presence(&self) {
+        let online = match CommandQueueService::online_clients(&self.pool, PC_ONLINE_WINDOW_SECS).await {
+            Ok(online) => online,
+            Err(e) => {
+                error!("Failed to load online PC clients: {}", e);
+                return;
+            }
+        };
+
+        let (gone, now_empty) = {
+            let mut clients = self.online_clients.write();
+            let gone: Vec<(String, String)> = clients
+                .iter()
+                .filter(|(id, _)| !online.iter().any(|(online_id, _)| online_id == *id))
+                .map(|(id, name)| (id.clone(), name.clone()))
+                .collect();
+            for (id, _) in &gone {
+                clients.remove(id);
+            }
+            (gone, clients.is_empty())
+        };
+        if gone.is_empty() {
+            return;
+        }
+
+        if now_empty {
+            self.alice_state.set_pc_status("pc-control".to_string(), false);
+        }
+        for (client_id, client_name) in gone {
+            info!("PC client {} went offline", client_name);
+            self.dispatch(RuleEvent::PcOffline { client_id, client_name }).await;
+        }
+    }
+
+    /// Check conditions, then run the actions in order until one fails, and record the outcome
+    async fn execute(&self, rule: &DbAliceRule, definition: &RuleDefinition, event: Value, dry_run: bool) -> DbAliceRuleRun {
+        let mut steps = Vec::new();
+        let mut status = "done";
+
+        for condition in &definition.conditions {
+            let (passed, detail) = self.check(condition).await;
+            steps.push(json!({"step": "condition", "condition": condition, "ok": passed, "detail": detail}));
+            if !passed {
+                status = "skipped";
+                break;
+            }
+        }
+
+        if status == "done" {
+            for action in &definition.actions {
+                let result = match self.prepare(action, &rule.name, &event) {
+                    Ok(prepared) if dry_run => Ok(format!("would {}", prepared.describe())),
+                    Ok(prepared) => self.perform(prepared).await,
+                    Err(e) => Err(e),
+                };
+                let ok = result.is_ok();
+                let detail = result.unwrap_or_else(|e| e);
+                steps.push(json!({"step": "action", "action": action.kind(), "ok": ok, "detail": detail}));
+                if !ok {
+                    status = "failed";
+                    break;
+                }
+            }
+        }
+
+        if !dry_run {
+            info!("Alice rule {} ({}): {}", rule.id, rule.name, status);
+        }
+        self.record(rule.id, &event, dry_run, status, Value::Array(steps)).await
+    }
+
+    async fn check(&self, condition: &RuleCondition) -> (bool, String) {
+        match condition {
+            RuleCondition::TimeWindow { from, to, days } => {
+                let now = chrono::Local::now();
+                let (Ok(from), Ok(to)) = (parse_time(from), parse_time(to)) else {
+                    return (false, "invalid time window".to_string());
+                };
+                let time = NaiveTime::from_hms_opt(now.hour(), now.minute(), 0).unwrap_or_default();
+                let weekday = now.weekday().number_from_monday();
+                let passed = in_time_window(from, to, days, time, weekday);
+                (passed, format!("now {} (day {})", now.format("%H:%M"), weekday))
+            }
+            RuleCondition::DeviceState { device_id, instance, op, value } => {
+                let current = match AliceService::get_device_state(&self.pool, device_id, &self.alice_state).await {
+                    Ok(Some((capabilities, properties))) => capabilities
+                        .into_iter()
+                        .filter_map(|c| c.state.map(|s| (s.instance, s.value)))
+                        .chain(properties.into_iter().filter_map(|p| p.state.map(|s| (s.instance, s.value))))
+                        .find(|(i, _)| i == instance)
+                        .map(|(_, v)| v),
+                    Ok(None) => return (false, format!("device {} not found", device_id)),
+                    Err(e) => return (false, e.to_string()),
+                };
+                match current {
+                    Some(current) => (compare(&current, *op, value), format!("{} is {}", instance, current)),
+                    None => (false, format!("{} has no state", instance)),
+                }
+            }
+        }
+    }
+
+    /// Fill in placeholders and check the action can be carried out
+    fn prepare(&self, action: &RuleAction, rule_name: &str, event: &Value) -> Result<PreparedAction, String> {
+        Ok(match action {
+            RuleAction::PcCommand { command, priority, client_id, group } => PreparedAction::PcCommand {
+                command: serde_json::from_value(render_value(command, event))
+                    .map_err(|e| format!("Invalid PC command: {}", e))?,
+                priority: *priority,
+                target: CommandTarget {
+                    client_id: client_id.clone(),
+                    group: group.clone(),
+                },
+            },
+            RuleAction::Telegram { chat_id, text } => PreparedAction::Telegram {
+                chat_id: chat_id.unwrap_or(self.admin_telegram_id),
+                text: render(text, event),
+            },
+            RuleAction::SiteNotification { text, title, priority } => PreparedAction::SiteNotification(
+                NewNotification::new(
+                    category::ALICE,
+                    *priority,
+                    title.as_deref().map(|t| render(t, event)).unwrap_or_else(|| rule_name.to_string()),
+                    render(text, event),
+                )
+                .data(event.clone()),
+            ),
+            RuleAction::N8n { workflow, params } => PreparedAction::N8n {
+                workflow: workflow.clone(),
+                params: params.as_ref().map(|p| render_value(p, event)).unwrap_or_else(|| event.clone()),
+            },
+        })
+    }
+
+    async fn perform(&self, action: PreparedAction) -> Result<String, String> {
+        match action {
+            PreparedAction::PcCommand { command, priority, target } => {
+                CommandQueueService::queue_command(&self.pool, &command, priority, &target, &CommandDelivery::default())
+                    .await
+                    .map(|id| format!("queued PC command {}", id))
+            }
+            PreparedAction::Telegram { chat_id, text } => self
+                .telegram_bot
+                .send_message(chat_id, &text)
+                .await
+                .map(|_| format!("sent to {}", chat_id))
+                .map_err(|e| format!("Telegram error: {}", e)),
+            PreparedAction::SiteNotification(notification) => NotificationService::notify(&self.pool, &notification)
+                .await
+                .map(|n| format!("notification {} posted", n.id)),
+            PreparedAction::N8n { workflow, params } => N8nClient::new().trigger_workflow(&workflow, params).await,
+        }
+    }
+
+    async fn record(&self, rule_id: i64, event: &Value, dry_run: bool, status: &str, steps: Value) -> DbAliceRuleRun {
+        let run = DbAliceRuleRun {
+            id: 0,
+            rule_id,
+            event: event.clone(),
+            dry_run,
+            status: status.to_string(),
+            steps,
+            created_at: chrono::Utc::now(),
+        };
+
+        let result = match &self.pool {
+            DbPool::Sqlite(p) => {
+                let inserted = sqlx::query(
+                    "INSERT INTO alice_rule_runs (rule_id, event, dry_run, status, steps) VALUES (?, ?, ?, ?, ?)"
+                )
+                .bind(rule_id)
+                .bind(&run.event)
+                .bind(dry_run)
+                .bind(status)
+                .bind(&run.steps)
+                .execute(p)
+                .await;
+                if inserted.is_ok() && !dry_run {
+                    sqlx::query("UPDATE alice_rules SET last_run_at = datetime('now'), last_status = ? WHERE id = ?")
+                        .bind(status)
+                        .bind(rule_id)
+                        .execute(p)
+                        .await
+                        .ok();
+                }
+                inserted.map(|r| r.last_insert_rowid())
+            }
+            DbPool::Postgres(p) => {
+                let inserted: Result<(i64,), _> = sqlx::query_as(
+                    "INSERT INTO alice_rule_runs (rule_id, event, dry_run, status, steps) VALUES ($1, $2, $3, $4, $5) RETURNING id::INT8"
+                )
+                .bind(rule_id)
+                .bind(&run.event)
+                .bind(dry_run)
+                .bind(status)
+                .bind(&run.steps)
+                .fetch_one(p)
+                .await;
+                if inserted.is_ok() && !dry_run {
+                    sqlx::query("UPDATE alice_rules SET last_run_at = NOW(), last_status = $1 WHERE id = $2")
+                        .bind(status)
+                        .bind(rule_id)
+                        .execute(p)
+                        .await
+                        .ok();
+                }
+                inserted.map(|r| r.0)
+            }
+        };
+
+        match result {
+            Ok(id) => DbAliceRuleRun { id, ..run },
+            Err(e) => {
+                error!("Failed to record run of Alice rule {}: {}", rule_id, e);
+                run
+            }
+        }
+    }
+
+    /// Arm or disarm the rule's cron job to match its current trigger and state
+    async fn rearm(&self, rule: &DbAliceRule) -> Result<(), String> {
+        self.disarm(rule.id).await;
+        if rule.is_enabled && rule.trigger_type == "schedule" {
+            self.arm(rule).await?;
+        }
+        Ok(())
+    }
+
+    async fn arm(&self, rule: &DbAliceRule) -> Result<(), String> {
+        let Ok(RuleTrigger::Schedule { cron }) = serde_json::from_value(rule.trigger_data.clone()) else {
+            return Err("not a schedule rule".to_string());
+        };
+        let (engine, id) = (self.clone(), rule.id);
+        let job = Job::new_async(cron.as_str(), move |_, _| {
+            let engine = engine.clone();
+            Box::pin(async move { engine.fire(id).await })
+        })
+        .map_err(|e| e.to_string())?;
+
+        let job_id = self.scheduler.add(job).await.map_err(|e| e.to_string())?;
+        self.jobs.write().insert(rule.id, job_id);
+        Ok(())
+    }
+
+    async fn disarm(&self, id: i64) {
+        let job = self.jobs.write().remove(&id);
+        if let Some(job) = job {
+            if let Err(e) = self.scheduler.remove(&job).await {
+                warn!("Failed to remove job of Alice rule {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Cron job body of a schedule rule
+    async fn fire(&self, id: i64) {
+        let rule = match self.get(id).await {
+            Ok(Some(rule)) if rule.is_enabled => rule,
+            Ok(_) => return,
+            Err(e) => {
+                error!("Failed to load Alice rule {}: {}", id, e);
+                return;
+            }
+        };
+        match Self::definition(&rule) {
+            Ok(definition) => {
+                let event = json!({"time": chrono::Utc::now().to_rfc3339()});
+                self.execute(&rule, &definition, event, false).await;
+            }
+            Err(e) => warn!("Skipping broken Alice rule {}: {}", id, e),
+        }
+    }
+}
+
+/// Action with placeholders filled in, ready to run
+enum PreparedAction {
+    PcCommand { command: PcCommandType, priority: i32, target: CommandTarget },
+    Telegram { chat_id: i64, text: String },
+    SiteNotification(NewNotification),
+    N8n { workflow: String, params: Value },
+}
+
+impl PreparedAction {
+    /// What a dry run reports instead of running the action
+    fn describe(&self) -> String {
+        match self {
+            PreparedAction::PcCommand { command, priority, target } => format!(
+                "queue {} (priority {}, client {}, group {})",
+                command.capability().as_str(),
+                priority,
+                target.client_id.as_deref().unwrap_or("any"),
+                target.group.as_deref().unwrap_or("any"),
+            ),
+            PreparedAction::Telegram { chat_id, text } => format!("send \"{}\" to {}", text, chat_id),
+            PreparedAction::SiteNotification(n) => {
+                format!("post {} notification \"{}: {}\"", n.priority.as_str(), n.title, n.message)
+            }
+            PreparedAction::N8n { workflow, params } => format!("run n8n workflow {} with {}", workflow, params),
+        }
+    }
+}
+
+impl RuleAction {
+    fn kind(&self) -> &'static str {
+        match self {
+            RuleAction::PcCommand { .. } => "pc_command",
+            RuleAction::Telegram { .. } => "telegram",
+            RuleAction::SiteNotification { .. } => "site_notification",
+            RuleAction::N8n { .. } => "n8n",
+        }
+    }
+}
+
+/// Check a rule before it is saved
+fn validate(definition: &RuleDefinition) -> Result<(), String> {
+    if definition.name.trim().is_empty() {
+        return Err("name is required".to_string());
+    }
+    match &definition.trigger {
+        RuleTrigger::Schedule { cron } => {
+            Job::new_async(cron.as_str(), |_, _| Box::pin(async {})).map_err(|_| {
+                "Invalid cron expression (six fields: sec min hour day month weekday)".to_string()
+            })?;
+        }
+        RuleTrigger::Webhook { key } if key.len() < MIN_WEBHOOK_KEY_LEN => {
+            return Err(format!("webhook key must be at least {} characters", MIN_WEBHOOK_KEY_LEN));
+        }
+        RuleTrigger::Webhook { key } if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => {
+            return Err("webhook key may contain only letters, digits, '-' and '_'".to_string());
+        }
+        RuleTrigger::Cs2MatchEnd { result: Some(result) } if !matches!(result.as_str(), "win" | "loss" | "draw") => {
+            return Err("cs2_match_end: result must be win, loss or draw".to_string());
+        }
+        _ => {}
+    }
+
+    for condition in &definition.conditions {
+        if let RuleCondition::TimeWindow { from, to, days } = condition {
+            parse_time(from)?;
+            parse_time(to)?;
+            if days.iter().any(|d| !(1..=7).contains(d)) {
+                return Err("time_window: days are 1 (Monday) to 7 (Sunday)".to_string());
+            }
+        }
+    }
+
+    if definition.actions.is_empty() {
+        return Err("at least one action is required".to_string());
+    }
+    for action in &definition.actions {
+        match action {
+            RuleAction::PcCommand { command, .. } => {
+                let known = command
+                    .get("type")
+                    .cloned()
+                    .and_then(|t| serde_json::from_value::<Capability>(t).ok())
+                    .is_some_and(|c| c != Capability::Unknown);
+                if !known {
+                    return Err("pc_command: command.type must be a PC command such as \"Shutdown\"".to_string());
+                }
+            }
+            RuleAction::Telegram { text, .. } | RuleAction::SiteNotification { text, .. } if text.trim().is_empty() => {
+                return Err(format!("{}: text is required", action.kind()));
+            }
+            RuleAction::N8n { workflow, .. } if workflow.trim().is_empty() => {
+                return Err("n8n: workflow is required".to_string());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn encode(definition: &RuleDefinition) -> Result<(Value, Value, Value), String> {
+    Ok((
+        serde_json::to_value(&definition.trigger).map_err(|e| e.to_string())?,
+        serde_json::to_value(&definition.conditions).map_err(|e| e.to_string())?,
+        serde_json::to_value(&definition.actions).map_err(|e| e.to_string())?,
+    ))
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| format!("time_window: '{}' is not HH:MM", value))
+}
+
+/// Whether `time` on `weekday` (1 = Monday) is within `from..to`; the day is that of `time`,
+/// also past midnight of a window that spans it
+fn in_time_window(from: NaiveTime, to: NaiveTime, days: &[u32], time: NaiveTime, weekday: u32) -> bool {
+    let in_window = if from <= to {
+        time >= from && time < to
+    } else {
+        time >= from || time < to
+    };
+    in_window && (days.is_empty() || days.contains(&weekday))
+}
+
+/// Numbers compare numerically, everything else only for (in)equality
+fn compare(current: &Value, op: CompareOp, expected: &Value) -> bool {
+    match (op, current.as_f64(), expected.as_f64()) {
+        (CompareOp::Eq, Some(a), Some(b)) => a == b,
+        (CompareOp::Ne, Some(a), Some(b)) => a != b,
+        (CompareOp::Eq, ..) => current == expected,
+        (CompareOp::Ne, ..) => current != expected,
+        (CompareOp::Gt, Some(a), Some(b)) => a > b,
+        (CompareOp::Gte, Some(a), Some(b)) => a >= b,
+        (CompareOp::Lt, Some(a), Some(b)) => a < b,
+        (CompareOp::Lte, Some(a), Some(b)) => a <= b,
+        _ => false,
+    }
+}
+
+/// Fill `{{field}}` placeholders from the event
+fn render(template: &str, event: &Value) -> String {
+    let mut text = template.to_string();
+    if let Value::Object(fields) = event {
+        for (key, value) in fields {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            text = text.replace(&format!("{{{{{}}}}}", key), &value);
+        }
+    }
+    text
+}
+
+/// Fill placeholders in every string of a JSON template; a string that is exactly
+/// one placeholder becomes the event value itself
+fn render_value(template: &Value, event: &Value) -> Value {
+    match template {
+        Value::String(s) => s
+            .strip_prefix("{{")
+            .and_then(|s| s.strip_suffix("}}"))
+            .and_then(|key| event.get(key))
+            .cloned()
+            .unwrap_or_else(|| Value::String(render(s, event))),
+        Value::Array(items) => Value::Array(items.iter().map(|v| render_value(v, event)).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), render_value(v, event))).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> NaiveTime {
+        parse_time(time).unwrap()
+    }
+
+    fn window(from: &str, to: &str, days: &[u32], time: &str, weekday: u32) -> bool {
+        in_time_window(at(from), at(to), days, at(time), weekday)
+    }
+
+    #[test]
+    fn time_window_within_one_day() {
+        assert!(window("09:00", "18:00", &[], "09:00", 1));
+        assert!(window("09:00", "18:00", &[], "17:59", 1));
+        assert!(!window("09:00", "18:00", &[], "18:00", 1));
+        assert!(!window("09:00", "18:00", &[], "08:59", 1));
+        // An empty window never matches
+        assert!(!window("12:00", "12:00", &[], "12:00", 1));
+    }
+
+    #[test]
+    fn time_window_across_midnight() {
+        assert!(window("22:00", "06:00", &[], "22:00", 3));
+        assert!(window("22:00", "06:00", &[], "23:59", 3));
+        assert!(window("22:00", "06:00", &[], "00:00", 3));
+        assert!(window("22:00", "06:00", &[], "05:59", 3));
+        assert!(!window("22:00", "06:00", &[], "06:00", 3));
+        assert!(!window("22:00", "06:00", &[], "12:00", 3));
+    }
+
+    #[test]
+    fn time_window_day_filter() {
+        let weekdays = [1, 2, 3, 4, 5];
+        assert!(window("09:00", "18:00", &weekdays, "10:00", 5));
+        assert!(!window("09:00", "18:00", &weekdays, "10:00", 6));
+        assert!(!window("09:00", "18:00", &weekdays, "10:00", 7));
+        // Past midnight the new day counts: Friday night's window is Saturday by 01:00
+        assert!(window("22:00", "02:00", &[5], "23:00", 5));
+        assert!(!window("22:00", "02:00", &[5], "01:00", 6));
+        assert!(window("22:00", "02:00", &[5, 6], "01:00", 6));
+        assert!(parse_time("25:00").is_err());
+        assert!(parse_time("9am").is_err());
+    }
+
+    #[test]
+    fn compare_numbers_numerically() {
+        let (n, i) = (json!(21.5), json!(21));
+        assert!(compare(&json!(21), CompareOp::Eq, &json!(21.0)));
+        assert!(compare(&n, CompareOp::Ne, &i));
+        assert!(compare(&n, CompareOp::Gt, &i));
+        assert!(compare(&n, CompareOp::Gte, &json!(21.5)));
+        assert!(!compare(&n, CompareOp::Lt, &i));
+        assert!(compare(&i, CompareOp::Lt, &n));
+        assert!(compare(&i, CompareOp::Lte, &json!(21)));
+        assert!(compare(&json!(-5), CompareOp::Lt, &json!(0)));
+    }
+
+    #[test]
+    fn compare_bools_and_strings_for_equality_only() {
+        assert!(compare(&json!(true), CompareOp::Eq, &json!(true)));
+        assert!(compare(&json!(true), CompareOp::Ne, &json!(false)));
+        assert!(!compare(&json!(true), CompareOp::Eq, &json!("true")));
+        assert!(compare(&json!("heat"), CompareOp::Eq, &json!("heat")));
+        assert!(compare(&json!("heat"), CompareOp::Ne, &json!("cool")));
+        assert!(!compare(&json!("heat"), CompareOp::Eq, &json!("Heat")));
+        // Ordering needs numbers on both sides; numeric strings do not count
+        for op in [CompareOp::Gt, CompareOp::Gte, CompareOp::Lt, CompareOp::Lte] {
+            assert!(!compare(&json!("b"), op, &json!("a")), "{:?}", op);
+            assert!(!compare(&json!(true), op, &json!(false)), "{:?}", op);
+            assert!(!compare(&json!("10"), op, &json!(5)), "{:?}", op);
+        }
+        assert!(!compare(&json!("21"), CompareOp::Eq, &json!(21)));
+    }
+
+    #[test]
+    fn render_fills_placeholders_from_the_event() {
+        let event = RuleEvent::Cs2MatchEnd {
+            map: Some("de_mirage".to_string()),
+            mode: None,
+            result: "win".to_string(),
+            ct_score: 13,
+            t_score: 9,
+        }
+        .data();
+        assert_eq!(
+            render("{{result}} on {{map}}: {{ct_score}}-{{t_score}} ({{mode}})", &event),
+            "win on de_mirage: 13-9 (null)"
+        );
+        assert_eq!(render("{{unknown}} {{ result }}", &event), "{{unknown}} {{ result }}");
+        assert_eq!(render("{{result}}", &json!("not an object")), "{{result}}");
+
+        let webhook = RuleEvent::Webhook { key: "k".to_string(), body: json!({"name": "Bob", "n": [1, 2]}) }.data();
+        assert_eq!(render("Hi {{name}}, {{n}}", &webhook), "Hi Bob, [1,2]");
+        assert_eq!(render("{{body}}", &webhook), r#"{"n":[1,2],"name":"Bob"}"#);
+    }
+
+    #[test]
+    fn render_value_keeps_whole_placeholders_typed() {
+        let event = RuleEvent::AliceAction {
+            device_id: "lamp".to_string(),
+            instance: "brightness".to_string(),
+            value: json!(40),
+        }
+        .data();
+        let template = json!({
+            "type": "notify",
+            "level": "{{value}}",
+            "text": "{{device_id}} {{instance}} = {{value}}",
+            "list": ["{{device_id}}", 1, null],
+            "missing": "{{nope}}",
+        });
+        assert_eq!(
+            render_value(&template, &event),
+            json!({
+                "type": "notify",
+                "level": 40,
+                "text": "lamp brightness = 40",
+                "list": ["lamp", 1, null],
+                "missing": "{{nope}}",
+            })
+        );
+    }
+}
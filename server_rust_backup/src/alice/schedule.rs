@@ -0,0 +1,437 @@
+use crate::alice::models::*;
+use crate::alice::service::{AliceState, CommandQueueService};
+use crate::db::DbPool;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_cron_scheduler::{Job, JobScheduler as CronScheduler};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Alice device ID prefix of schedules exposed as scenes
+pub const ALICE_DEVICE_PREFIX: &str = "pc-schedule-";
+
+/// One-shot schedules missed while the server was down still fire if this late at most
+const MISSED_GRACE_SECS: i64 = 300;
+
+/// Delayed and recurring PC commands: each active schedule is a job in the cron
+/// scheduler that queues its command through [`CommandQueueService`]
+#[derive(Clone)]
+pub struct PcScheduleService {
+    pool: DbPool,
+    scheduler: CronScheduler,
+    /// Schedule ID -> job of the active schedule
+    jobs: Arc<RwLock<HashMap<i64, Uuid>>>,
+    /// Scene switches report arming and pausing to Yandex through it
+    alice_state: AliceState,
+}
+
+impl PcScheduleService {
+    /// Start the scheduler and arm every active schedule
+    pub async fn init(pool: DbPool, alice_state: AliceState) -> Result<Self, String> {
+        let scheduler = CronScheduler::new().await.map_err(|e| e.to_string())?;
+        scheduler.start().await.map_err(|e| e.to_string())?;
+
+        let service = Self {
+            pool,
+            scheduler,
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+            alice_state,
+        };
+
+        let schedules = service.list().await.map_err(|e| e.to_string())?;
+        let mut armed = 0;
+        for schedule in schedules.into_iter().filter(|s| !s.is_paused) {
+            if schedule.cron_expr.is_none()
+                && schedule.run_at.is_some_and(|t| t < Utc::now() - chrono::Duration::seconds(MISSED_GRACE_SECS))
+            {
+                warn!("PC schedule {} was missed while the server was down", schedule.id);
+                service
+                    .record_run(&schedule, true, None, Some("Missed while the server was down"))
+                    .await;
+                continue;
+            }
+            match service.arm(&schedule).await {
+                Ok(()) => armed += 1,
+                Err(e) => error!("Failed to arm PC schedule {}: {}", schedule.id, e),
+            }
+        }
+
+        info!("PC command schedules: {} active", armed);
+        Ok(service)
+    }
+
+    /// Create a schedule and arm it unless it starts paused
+    pub async fn create(&self, request: &CreateScheduleRequest) -> Result<DbPcSchedule, String> {
+        let now = Utc::now();
+        let run_at = match (&request.cron, request.run_at, request.delay_secs) {
+            (Some(_), None, None) => None,
+            (None, Some(run_at), None) if run_at > now => Some(run_at),
+            (None, Some(_), None) => return Err("run_at is in the past".to_string()),
+            (None, None, Some(delay)) if delay > 0 => Some(now + chrono::Duration::seconds(delay)),
+            (None, None, Some(_)) => return Err("delay_secs must be positive".to_string()),
+            _ => return Err("Set exactly one of cron, run_at and delay_secs".to_string()),
+        };
+        if let Some(cron) = &request.cron {
+            // Parsed up front so a bad expression is reported instead of stored
+            Job::new_async(cron.as_str(), |_, _| Box::pin(async {})).map_err(|_| {
+                "Invalid cron expression (six fields: sec min hour day month weekday)".to_string()
+            })?;
+        }
+        let command_data = serde_json::to_value(&request.command).map_err(|e| e.to_string())?;
+
+        let id = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query(
+                "INSERT INTO alice_pc_schedules (name, command_data, priority, target_client_id, target_group, max_attempts, cron_expr, run_at, delay_secs, is_paused, alice_scene)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&request.name)
+            .bind(&command_data)
+            .bind(request.priority)
+            .bind(&request.target.client_id)
+            .bind(&request.target.group)
+            .bind(request.max_attempts)
+            .bind(&request.cron)
+            .bind(run_at)
+            .bind(request.delay_secs)
+            .bind(request.paused)
+            .bind(request.alice_scene)
+            .execute(p)
+            .await
+            .map_err(|e| e.to_string())?
+            .last_insert_rowid(),
+            DbPool::Postgres(p) => {
+                let row: (i64,) = sqlx::query_as(
+                    "INSERT INTO alice_pc_schedules (name, command_data, priority, target_client_id, target_group, max_attempts, cron_expr, run_at, delay_secs, is_paused, alice_scene)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING id::INT8"
+                )
+                .bind(&request.name)
+                .bind(&command_data)
+                .bind(request.priority)
+                .bind(&request.target.client_id)
+                .bind(&request.target.group)
+                .bind(request.max_attempts)
+                .bind(&request.cron)
+                .bind(run_at)
+                .bind(request.delay_secs)
+                .bind(request.paused)
+                .bind(request.alice_scene)
+                .fetch_one(p)
+                .await
+                .map_err(|e| e.to_string())?;
+                row.0
+            }
+        };
+
+        let schedule = self.get(id).await.map_err(|e| e.to_string())?.ok_or("Schedule vanished")?;
+        if !schedule.is_paused {
+            self.arm(&schedule).await?;
+        }
+        info!("Created PC schedule {} ({})", schedule.id, schedule.name);
+        if schedule.alice_scene {
+            self.alice_state.mark_devices_changed();
+        }
+        Ok(schedule)
+    }
+
+    pub async fn list(&self) -> Result<Vec<DbPcSchedule>, sqlx::Error> {
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, DbPcSchedule>("SELECT * FROM alice_pc_schedules ORDER BY id")
+                    .fetch_all(p)
+                    .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, DbPcSchedule>(
+                    "SELECT id::INT8 AS id, name, command_data, priority, target_client_id, target_group, max_attempts, cron_expr, run_at, delay_secs,
+                            is_paused, alice_scene, last_run_at, last_command_id, last_error, created_at
+                     FROM alice_pc_schedules ORDER BY id"
+                )
+                .fetch_all(p)
+                .await
+            }
+        }
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<DbPcSchedule>, sqlx::Error> {
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, DbPcSchedule>("SELECT * FROM alice_pc_schedules WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(p)
+                    .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, DbPcSchedule>(
+                    "SELECT id::INT8 AS id, name, command_data, priority, target_client_id, target_group, max_attempts, cron_expr, run_at, delay_secs,
+                            is_paused, alice_scene, last_run_at, last_command_id, last_error, created_at
+                     FROM alice_pc_schedules WHERE id = $1"
+                )
+                .bind(id)
+                .fetch_optional(p)
+                .await
+            }
+        }
+    }
+
+    /// Next time an active schedule fires
+    pub async fn next_run(&self, id: i64) -> Option<DateTime<Utc>> {
+        let job = self.jobs.read().get(&id).copied()?;
+        self.scheduler.clone().next_tick_for_job(job).await.ok().flatten()
+    }
+
+    /// Stop a schedule from firing; `Ok(false)` if it does not exist
+    pub async fn pause(&self, id: i64) -> Result<bool, String> {
+        let Some(schedule) = self.get(id).await.map_err(|e| e.to_string())? else {
+            return Ok(false);
+        };
+        self.disarm(id).await;
+        self.set_paused(schedule.id, true, schedule.run_at).await?;
+        self.state_changed(&schedule);
+        info!("Paused PC schedule {}", id);
+        Ok(true)
+    }
+
+    /// Re-arm a paused schedule. A one-shot schedule with `delay_secs` counts its delay from now.
+    pub async fn resume(&self, id: i64) -> Result<bool, String> {
+        let Some(mut schedule) = self.get(id).await.map_err(|e| e.to_string())? else {
+            return Ok(false);
+        };
+        if schedule.cron_expr.is_none() {
+            if let Some(delay) = schedule.delay_secs {
+                schedule.run_at = Some(Utc::now() + chrono::Duration::seconds(delay));
+            } else if schedule.run_at.is_none_or(|t| t <= Utc::now()) {
+                return Err("run_at is in the past; create a new schedule".to_string());
+            }
+        }
+
+        self.disarm(id).await;
+        self.set_paused(id, false, schedule.run_at).await?;
+        schedule.is_paused = false;
+        self.arm(&schedule).await?;
+        self.state_changed(&schedule);
+        info!("Resumed PC schedule {}", id);
+        Ok(true)
+    }
+
+    /// Queue the schedule's command right now, without changing its timing
+    pub async fn run_now(&self, id: i64) -> Result<i64, String> {
+        let schedule = self.get(id).await.map_err(|e| e.to_string())?.ok_or("Schedule not found")?;
+        let result = Self::queue(&self.pool, &schedule).await;
+        // An armed one-shot stays armed: it still fires at its own time
+        self.record_run(
+            &schedule,
+            schedule.is_paused,
+            result.as_ref().ok().copied(),
+            result.as_ref().err().map(String::as_str),
+        )
+        .await;
+        result
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<bool, String> {
+        let alice_scene = self.get(id).await.map_err(|e| e.to_string())?.is_some_and(|s| s.alice_scene);
+        self.disarm(id).await;
+        let result = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query("DELETE FROM alice_pc_schedules WHERE id = ?")
+                .bind(id)
+                .execute(p)
+                .await
+                .map(|r| r.rows_affected()),
+            DbPool::Postgres(p) => sqlx::query("DELETE FROM alice_pc_schedules WHERE id = $1")
+                .bind(id)
+                .execute(p)
+                .await
+                .map(|r| r.rows_affected()),
+        };
+        if alice_scene {
+            self.alice_state.mark_devices_changed();
+        }
+        Ok(result.map_err(|e| e.to_string())? > 0)
+    }
+
+    /// Schedules marked `alice_scene`, as on/off Alice devices: on while armed
+    pub async fn alice_devices(&self) -> Vec<AliceDevice> {
+        let schedules = match self.list().await {
+            Ok(schedules) => schedules,
+            Err(e) => {
+                error!("Failed to load PC schedules for Alice: {}", e);
+                return Vec::new();
+            }
+        };
+
+        schedules
+            .into_iter()
+            .filter(|s| s.alice_scene)
+            .map(|s| AliceDevice {
+                id: Self::device_id(s.id),
+                name: s.name,
+                description: Some(s.cron_expr.map_or_else(|| "Отложенная команда ПК".to_string(), |c| format!("Расписание ПК: {}", c))),
+                room: None,
+                device_type: "devices.types.switch".to_string(),
+                custom_data: None,
+                capabilities: vec![DeviceCapability {
+                    capability_type: "devices.capabilities.on_off".to_string(),
+                    retrievable: true,
+                    reportable: Some(true),
+                    parameters: None,
+                    state: None,
+                }],
+                properties: None,
+                device_info: None,
+            })
+            .collect()
+    }
+
+    /// Schedule ID -> Alice device ID
+    pub fn device_id(id: i64) -> String {
+        format!("{}{}", ALICE_DEVICE_PREFIX, id)
+    }
+
+    /// Alice device ID -> schedule ID, for IDs with [`ALICE_DEVICE_PREFIX`]
+    pub fn schedule_id(device_id: &str) -> Option<i64> {
+        device_id.strip_prefix(ALICE_DEVICE_PREFIX)?.parse().ok()
+    }
+
+    /// Whether the schedule is armed, reported to Alice as its on/off state
+    pub fn is_armed(&self, id: i64) -> bool {
+        self.jobs.read().contains_key(&id)
+    }
+
+    /// A scene switch flipped between armed and paused
+    fn state_changed(&self, schedule: &DbPcSchedule) {
+        if schedule.alice_scene {
+            self.alice_state.mark_changed(&Self::device_id(schedule.id));
+        }
+    }
+
+    /// Add the schedule's job to the cron scheduler
+    async fn arm(&self, schedule: &DbPcSchedule) -> Result<(), String> {
+        let id = schedule.id;
+        let service = self.clone();
+        let job = match (&schedule.cron_expr, schedule.run_at) {
+            (Some(cron), _) => Job::new_async(cron.as_str(), move |_, _| {
+                let service = service.clone();
+                Box::pin(async move { service.fire(id).await })
+            }),
+            (None, Some(run_at)) => {
+                let delay = (run_at - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+                Job::new_one_shot_async(delay, move |_, _| {
+                    let service = service.clone();
+                    Box::pin(async move { service.fire(id).await })
+                })
+            }
+            (None, None) => return Err("Schedule has neither cron nor run_at".to_string()),
+        }
+        .map_err(|e| e.to_string())?;
+
+        let job_id = self.scheduler.add(job).await.map_err(|e| e.to_string())?;
+        self.jobs.write().insert(id, job_id);
+        Ok(())
+    }
+
+    async fn disarm(&self, id: i64) {
+        let job = self.jobs.write().remove(&id);
+        if let Some(job) = job {
+            if let Err(e) = self.scheduler.remove(&job).await {
+                warn!("Failed to remove job of PC schedule {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Job body: queue the command; a one-shot schedule pauses itself afterwards
+    async fn fire(&self, id: i64) {
+        let schedule = match self.get(id).await {
+            Ok(Some(schedule)) if !schedule.is_paused => schedule,
+            Ok(_) => return,
+            Err(e) => {
+                error!("Failed to load PC schedule {}: {}", id, e);
+                return;
+            }
+        };
+
+        let result = Self::queue(&self.pool, &schedule).await;
+        match &result {
+            Ok(command_id) => info!("PC schedule {} queued command {}", id, command_id),
+            Err(e) => warn!("PC schedule {} could not queue its command: {}", id, e),
+        }
+
+        if schedule.cron_expr.is_none() {
+            self.jobs.write().remove(&id);
+            self.state_changed(&schedule);
+        }
+        // A fired one-shot ends up paused
+        self.record_run(
+            &schedule,
+            schedule.cron_expr.is_none(),
+            result.as_ref().ok().copied(),
+            result.as_ref().err().map(String::as_str),
+        )
+        .await;
+    }
+
+    async fn queue(pool: &DbPool, schedule: &DbPcSchedule) -> Result<i64, String> {
+        let command: PcCommandType = serde_json::from_value(schedule.command_data.clone())
+            .map_err(|e| format!("Cannot decode scheduled command: {}", e))?;
+        let target = CommandTarget {
+            client_id: schedule.target_client_id.clone(),
+            group: schedule.target_group.clone(),
+        };
+        let delivery = CommandDelivery {
+            not_after: None,
+            max_attempts: schedule.max_attempts,
+        };
+        CommandQueueService::queue_command(pool, &command, schedule.priority, &target, &delivery).await
+    }
+
+    /// Store the outcome of a run along with the schedule's paused flag after it
+    async fn record_run(&self, schedule: &DbPcSchedule, paused: bool, command_id: Option<i64>, error: Option<&str>) {
+        let result = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query(
+                "UPDATE alice_pc_schedules SET last_run_at = datetime('now'), last_command_id = ?, last_error = ?, is_paused = ? WHERE id = ?"
+            )
+            .bind(command_id)
+            .bind(error)
+            .bind(paused)
+            .bind(schedule.id)
+            .execute(p)
+            .await
+            .map(|_| ()),
+            DbPool::Postgres(p) => sqlx::query(
+                "UPDATE alice_pc_schedules SET last_run_at = NOW(), last_command_id = $1, last_error = $2, is_paused = $3 WHERE id = $4"
+            )
+            .bind(command_id)
+            .bind(error)
+            .bind(paused)
+            .bind(schedule.id)
+            .execute(p)
+            .await
+            .map(|_| ()),
+        };
+        if let Err(e) = result {
+            error!("Failed to record run of PC schedule {}: {}", schedule.id, e);
+        }
+    }
+
+    async fn set_paused(&self, id: i64, paused: bool, run_at: Option<DateTime<Utc>>) -> Result<(), String> {
+        let result = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query("UPDATE alice_pc_schedules SET is_paused = ?, run_at = ? WHERE id = ?")
+                .bind(paused)
+                .bind(run_at)
+                .bind(id)
+                .execute(p)
+                .await
+                .map(|_| ()),
+            DbPool::Postgres(p) => sqlx::query("UPDATE alice_pc_schedules SET is_paused = $1, run_at = $2 WHERE id = $3")
+                .bind(paused)
+                .bind(run_at)
+                .bind(id)
+                .execute(p)
+                .await
+                .map(|_| ()),
+        };
+        result.map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,609 @@
+use crate::alice::models::{DeviceCapability, DeviceDefinition, DeviceProperty};
+use crate::alice::schedule::ALICE_DEVICE_PREFIX;
+use serde_json::Value;
+use std::collections::HashSet;
+
+const DEVICE_TYPES: &[&str] = &[
+    "devices.types.light",
+    "devices.types.light.ceiling",
+    "devices.types.light.lamp",
+    "devices.types.light.strip",
+    "devices.types.socket",
+    "devices.types.switch",
+    "devices.types.switch.relay",
+    "devices.types.thermostat",
+    "devices.types.thermostat.ac",
+    "devices.types.media_device",
+    "devices.types.media_device.tv",
+    "devices.types.media_device.tv_box",
+    "devices.types.media_device.receiver",
+    "devices.types.camera",
+    "devices.types.cooking",
+    "devices.types.cooking.coffee_maker",
+    "devices.types.cooking.kettle",
+    "devices.types.cooking.multicooker",
+    "devices.types.openable",
+    "devices.types.openable.curtain",
+    "devices.types.openable.valve",
+    "devices.types.humidifier",
+    "devices.types.purifier",
+    "devices.types.vacuum_cleaner",
+    "devices.types.washing_machine",
+    "devices.types.dishwasher",
+    "devices.types.iron",
+    "devices.types.sensor",
+    "devices.types.sensor.button",
+    "devices.types.sensor.climate",
+    "devices.types.sensor.gas",
+    "devices.types.sensor.illumination",
+    "devices.types.sensor.motion",
+    "devices.types.sensor.open",
+    "devices.types.sensor.smoke",
+    "devices.types.sensor.vibration",
+    "devices.types.sensor.water_leak",
+    "devices.types.smart_meter",
+    "devices.types.smart_meter.cold_water",
+    "devices.types.smart_meter.electricity",
+    "devices.types.smart_meter.gas",
+    "devices.types.smart_meter.heat",
+    "devices.types.smart_meter.hot_water",
+    "devices.types.pet_drinking_fountain",
+    "devices.types.pet_feeder",
+    "devices.types.ventilation",
+    "devices.types.ventilation.fan",
+    "devices.types.other",
+];
+
+pub const ON_OFF: &str = "devices.capabilities.on_off";
+pub const COLOR_SETTING: &str = "devices.capabilities.color_setting";
+pub const MODE: &str = "devices.capabilities.mode";
+pub const RANGE: &str = "devices.capabilities.range";
+pub const TOGGLE: &str = "devices.capabilities.toggle";
+pub const VIDEO_STREAM: &str = "devices.capabilities.video_stream";
+pub const FLOAT: &str = "devices.properties.float";
+pub const EVENT: &str = "devices.properties.event";
+
+const MODE_INSTANCES: &[&str] = &[
+    "cleanup_mode",
+    "coffee_mode",
+    "dishwashing",
+    "fan_speed",
+    "heat",
+    "input_source",
+    "program",
+    "swing",
+    "tea_mode",
+    "thermostat",
+    "work_speed",
+];
+
+const TOGGLE_INSTANCES: &[&str] = &[
+    "backlight",
+    "controls_locked",
+    "ionization",
+    "keep_warm",
+    "mute",
+    "oscillation",
+    "pause",
+];
+
+/// Range instance and the units it accepts; an empty list means no unit
+const RANGE_INSTANCES: &[(&str, &[&str])] = &[
+    ("brightness", &["unit.percent"]),
+    ("channel", &[]),
+    ("humidity", &["unit.percent"]),
+    ("open", &["unit.percent"]),
+    ("temperature", &["unit.temperature.celsius", "unit.temperature.kelvin"]),
+    ("volume", &[]),
+];
+
+const COLOR_SCENES: &[&str] = &[
+    "alarm", "alice", "candle", "dinner", "fantasy", "garland", "jungle", "movie", "neon", "night", "ocean",
+    "party", "reading", "rest", "romance", "siren", "sunrise", "sunset",
+];
+
+const FLOAT_INSTANCES: &[(&str, &[&str])] = &[
+    ("amperage", &["unit.ampere"]),
+    ("battery_level", &["unit.percent"]),
+    ("co2_level", &["unit.ppm"]),
+    ("electricity_meter", &["unit.kilowatt_hour"]),
+    ("food_level", &["unit.percent"]),
+    ("gas_meter", &["unit.cubic_meter"]),
+    ("heat_meter", &["unit.gigacalorie"]),
+    ("humidity", &["unit.percent"]),
+    ("illumination", &["unit.illumination.lux"]),
+    ("meter", &[]),
+    ("pm1_density", &["unit.density.mcg_m3"]),
+    ("pm2.5_density", &["unit.density.mcg_m3"]),
+    ("pm10_density", &["unit.density.mcg_m3"]),
+    ("power", &["unit.watt"]),
+    (
+        "pressure",
+        &["unit.pressure.atm", "unit.pressure.pascal", "unit.pressure.bar", "unit.pressure.mmhg"],
+    ),
+    ("temperature", &["unit.temperature.celsius", "unit.temperature.kelvin"]),
+    ("tvoc", &["unit.density.mcg_m3"]),
+    ("voltage", &["unit.volt"]),
+    ("water_level", &["unit.percent"]),
+    ("water_meter", &["unit.cubic_meter"]),
+];
+
+const EVENT_INSTANCES: &[(&str, &[&str])] = &[
+    ("vibration", &["tilt", "fall", "vibration"]),
+    ("open", &["opened", "closed"]),
+    ("button", &["click", "double_click", "long_press"]),
+    ("motion", &["detected", "not_detected"]),
+    ("smoke", &["detected", "not_detected", "high"]),
+    ("gas", &["detected", "not_detected", "high"]),
+    ("battery_level", &["low", "normal"]),
+    ("food_level", &["empty", "low", "normal"]),
+    ("water_level", &["empty", "low", "normal"]),
+    ("water_leak", &["dry", "leak"]),
+];
+
+/// Check a registry device against the Yandex schema; all problems are reported at once
+pub fn validate_device(device: &DeviceDefinition) -> Result<(), String> {
+    let mut errors = Vec::new();
+
+    if device.id.is_empty()
+        || device.id.len() > 64
+        || !device.id.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+    {
+        errors.push("id must be 1-64 characters of letters, digits, '-', '_' and '.'".to_string());
+    }
+    if device.id.starts_with(ALICE_DEVICE_PREFIX) {
+        errors.push(format!("ids starting with '{}' are reserved for PC schedules", ALICE_DEVICE_PREFIX));
+    }
+    if device.name.trim().is_empty() {
+        errors.push("name is required".to_string());
+    }
+    if !DEVICE_TYPES.contains(&device.device_type.as_str()) {
+        errors.push(format!("unknown device type '{}'", device.device_type));
+    }
+    if device.capabilities.is_empty() && device.properties.is_empty() {
+        errors.push("a device needs at least one capability or property".to_string());
+    }
+
+    let mut seen = HashSet::new();
+    for capability in &device.capabilities {
+        match validate_capability(capability) {
+            Ok(instance) => {
+                if !seen.insert((capability.capability_type.as_str(), instance)) {
+                    errors.push(format!("{} declared twice", describe(&capability.capability_type, instance)));
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for property in &device.properties {
+        match validate_property(property) {
+            Ok(instance) => {
+                if !seen.insert((property.property_type.as_str(), instance)) {
+                    errors.push(format!("{} declared twice", describe(&property.property_type, instance)));
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+/// Validate one capability and return the instance it is declared under
+fn validate_capability(capability: &DeviceCapability) -> Result<&str, String> {
+    let kind = capability.capability_type.as_str();
+    let params = capability.parameters.as_ref().unwrap_or(&Value::Null);
+
+    match kind {
+        ON_OFF => {
+            if !params.is_null() && !params.get("split").is_none_or(Value::is_boolean) {
+                return Err("on_off: split must be a boolean".to_string());
+            }
+            Ok("on")
+        }
+        COLOR_SETTING => {
+            let color_model = params.get("color_model");
+            if let Some(model) = color_model {
+                if !matches!(model.as_str(), Some("rgb" | "hsv")) {
+                    return Err("color_setting: color_model must be \"rgb\" or \"hsv\"".to_string());
+                }
+            }
+            let temperature = params.get("temperature_k");
+            if let Some(temperature) = temperature {
+                let (min, max) = (number(temperature, "min"), number(temperature, "max"));
+                match (min, max) {
+                    (Some(min), Some(max)) if (1500.0..=9000.0).contains(&min) && min < max && max <= 9000.0 => {}
+                    _ => return Err("color_setting: temperature_k needs min < max within 1500-9000".to_string()),
+                }
+            }
+            let scene = params.get("color_scene");
+            if let Some(scene) = scene {
+                let scenes = scene.get("scenes").and_then(Value::as_array).filter(|s| !s.is_empty());
+                let Some(scenes) = scenes else {
+                    return Err("color_setting: color_scene.scenes must be a non-empty list".to_string());
+                };
+                for id in scenes.iter().map(|s| s.get("id").and_then(Value::as_str)) {
+                    if !id.is_some_and(|id| COLOR_SCENES.contains(&id)) {
+                        return Err(format!("color_setting: unknown scene {}", id.unwrap_or("without id")));
+                    }
+                }
+            }
+            if color_model.is_none() && temperature.is_none() && scene.is_none() {
+                return Err("color_setting needs color_model, temperature_k or color_scene".to_string());
+            }
+            Ok("color")
+        }
+        MODE => {
+            let instance = instance_in(kind, params, MODE_INSTANCES)?;
+            let modes = params.get("modes").and_then(Value::as_array).filter(|m| !m.is_empty());
+            let Some(modes) = modes else {
+                return Err(format!("{}: modes must be a non-empty list", describe(kind, instance)));
+            };
+            // Yandex keeps adding mode values, so only their shape is checked
+            let mut values = HashSet::new();
+            for value in modes.iter().map(|m| m.get("value").and_then(Value::as_str)) {
+                match value {
+                    Some(v) if is_identifier(v) && values.insert(v) => {}
+                    _ => return Err(format!("{}: mode values must be unique snake_case names", describe(kind, instance))),
+                }
+            }
+            Ok(instance)
+        }
+        RANGE => {
+            let names: Vec<&str> = RANGE_INSTANCES.iter().map(|(name, _)| *name).collect();
+            let instance = instance_in(kind, params, &names)?;
+            check_unit(kind, instance, params, RANGE_INSTANCES)?;
+            if params.get("random_access").is_some_and(|r| !r.is_boolean()) {
+                return Err(format!("{}: random_access must be a boolean", describe(kind, instance)));
+            }
+            if let Some(range) = params.get("range") {
+                let (min, max) = (number(range, "min"), number(range, "max"));
+                let precision = number(range, "precision").unwrap_or(1.0);
+                match (min, max) {
+                    (Some(min), Some(max)) if min < max && precision > 0.0 && precision <= max - min => {}
+                    _ => {
+                        return Err(format!(
+                            "{}: range needs min < max and 0 < precision <= max - min",
+                            describe(kind, instance)
+                        ))
+                    }
+                }
+            }
+            Ok(instance)
+        }
+        TOGGLE => instance_in(kind, params, TOGGLE_INSTANCES),
+        VIDEO_STREAM => {
+            let protocols = params.get("protocols").and_then(Value::as_array);
+            if !protocols.is_some_and(|p| !p.is_empty() && p.iter().all(|p| p.as_str() == Some("hls"))) {
+                return Err("video_stream: protocols must be [\"hls\"]".to_string());
+            }
+            Ok("get_stream")
+        }
+        _ => Err(format!("unknown capability type '{}'", kind)),
+    }
+}
+
+/// Validate one property and return its instance
+fn validate_property(property: &DeviceProperty) -> Result<&str, String> {
+    let kind = property.property_type.as_str();
+    let params = property.parameters.as_ref().unwrap_or(&Value::Null);
+
+    match kind {
+        FLOAT => {
+            let names: Vec<&str> = FLOAT_INSTANCES.iter().map(|(name, _)| *name).collect();
+            let instance = instance_in(kind, params, &names)?;
+            check_unit(kind, instance, params, FLOAT_INSTANCES)?;
+            Ok(instance)
+        }
+        EVENT => {
+            let names: Vec<&str> = EVENT_INSTANCES.iter().map(|(name, _)| *name).collect();
+            let instance = instance_in(kind, params, &names)?;
+            let allowed = lookup(EVENT_INSTANCES, instance);
+            let events = params.get("events").and_then(Value::as_array).filter(|e| !e.is_empty());
+            let Some(events) = events else {
+                return Err(format!("{}: events must be a non-empty list", describe(kind, instance)));
+            };
+            for value in events.iter().map(|e| e.get("value").and_then(Value::as_str)) {
+                if !value.is_some_and(|v| allowed.contains(&v)) {
+                    return Err(format!(
+                        "{}: events must be among {}",
+                        describe(kind, instance),
+                        allowed.join(", ")
+                    ));
+                }
+            }
+            Ok(instance)
+        }
+        _ => Err(format!("unknown property type '{}'", kind)),
+    }
+}
+
+/// Whether `instance` addresses this capability; color_setting has one instance per color model
+pub fn capability_accepts(capability: &DeviceCapability, instance: &str) -> bool {
+    let params = capability.parameters.as_ref().unwrap_or(&Value::Null);
+    match capability.capability_type.as_str() {
+        ON_OFF => instance == "on",
+        VIDEO_STREAM => instance == "get_stream",
+        COLOR_SETTING => match instance {
+            "rgb" | "hsv" => params.get("color_model").and_then(Value::as_str) == Some(instance),
+            "temperature_k" => params.get("temperature_k").is_some(),
+            "scene" => params.get("color_scene").is_some(),
+            _ => false,
+        },
+        _ => params.get("instance").and_then(Value::as_str) == Some(instance),
+    }
+}
+
+/// Check a value Alice sent or a client reported for a capability or property
+pub fn validate_value(kind: &str, parameters: Option<&Value>, instance: &str, value: &Value) -> Result<(), String> {
+    let params = parameters.unwrap_or(&Value::Null);
+    let valid = match kind {
+        ON_OFF | TOGGLE => value.is_boolean(),
+        MODE => params
+            .get("modes")
+            .and_then(Value::as_array)
+            .is_some_and(|modes| modes.iter().any(|m| m.get("value") == Some(value))),
+        RANGE => value.as_f64().is_some_and(|v| {
+            let range = params.get("range");
+            let min = range.and_then(|r| number(r, "min")).unwrap_or(f64::MIN);
+            let max = range.and_then(|r| number(r, "max")).unwrap_or(f64::MAX);
+            (min..=max).contains(&v)
+        }),
+        COLOR_SETTING => match instance {
+            "rgb" => value.as_u64().is_some_and(|v| v <= 0xFF_FFFF),
+            "hsv" => {
+                let channel = |name: &str, max: f64| number(value, name).is_some_and(|v| (0.0..=max).contains(&v));
+                channel("h", 360.0) && channel("s", 100.0) && channel("v", 100.0)
+            }
+            "temperature_k" => value.as_f64().is_some_and(|v| {
+                let limits = params.get("temperature_k");
+                let min = limits.and_then(|t| number(t, "min")).unwrap_or(1500.0);
+                let max = limits.and_then(|t| number(t, "max")).unwrap_or(9000.0);
+                (min..=max).contains(&v)
+            }),
+            "scene" => params
+                .pointer("/color_scene/scenes")
+                .and_then(Value::as_array)
+                .is_some_and(|scenes| scenes.iter().any(|s| s.get("id") == Some(value))),
+            _ => false,
+        },
+        FLOAT => value.is_number(),
+        EVENT => params
+            .get("events")
+            .and_then(Value::as_array)
+            .is_some_and(|events| events.iter().any(|e| e.get("value") == Some(value))),
+        _ => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid value {} for {}", value, describe(kind, instance)))
+    }
+}
+
+/// Value a capability reports before it was ever changed, if there is a sensible one
+pub fn default_state(capability: &DeviceCapability) -> Option<(String, Value)> {
+    let params = capability.parameters.as_ref().unwrap_or(&Value::Null);
+    let instance = params.get("instance").and_then(Value::as_str).unwrap_or_default().to_string();
+    match capability.capability_type.as_str() {
+        ON_OFF => Some(("on".to_string(), Value::Bool(false))),
+        TOGGLE => Some((instance, Value::Bool(false))),
+        RANGE => Some((instance, params.pointer("/range/min").cloned().unwrap_or(Value::from(0)))),
+        MODE => Some((instance, params.pointer("/modes/0/value")?.clone())),
+        _ => None,
+    }
+}
+
+/// Range capabilities apply relative changes (volume up by 10) to the last known value
+pub fn apply_relative(capability: &DeviceCapability, current: Option<&Value>, delta: &Value) -> Option<Value> {
+    let params = capability.parameters.as_ref().unwrap_or(&Value::Null);
+    let range = params.get("range");
+    let min = range.and_then(|r| number(r, "min")).unwrap_or(f64::MIN);
+    let max = range.and_then(|r| number(r, "max")).unwrap_or(f64::MAX);
+    let start = current.and_then(Value::as_f64).unwrap_or(min.max(0.0));
+    let value = (start + delta.as_f64()?).clamp(min, max);
+    Some(if value.fract() == 0.0 { Value::from(value as i64) } else { Value::from(value) })
+}
+
+fn instance_in<'a>(kind: &str, params: &'a Value, allowed: &[&str]) -> Result<&'a str, String> {
+    match params.get("instance").and_then(Value::as_str) {
+        Some(instance) if allowed.contains(&instance) => Ok(instance),
+        Some(instance) => Err(format!("{}: unknown instance '{}'", short(kind), instance)),
+        None => Err(format!("{}: parameters.instance is required", short(kind))),
+    }
+}
+
+fn check_unit(kind: &str, instance: &str, params: &Value, table: &[(&str, &[&str])]) -> Result<(), String> {
+    let allowed = lookup(table, instance);
+    match params.get("unit").and_then(Value::as_str) {
+        None if allowed.is_empty() => Ok(()),
+        Some(unit) if allowed.contains(&unit) => Ok(()),
+        _ if allowed.is_empty() => Err(format!("{} takes no unit", describe(kind, instance))),
+        _ => Err(format!("{}: unit must be one of {}", describe(kind, instance), allowed.join(", "))),
+    }
+}
+
+fn lookup<'a>(table: &[(&str, &'a [&'a str])], instance: &str) -> &'a [&'a str] {
+    table.iter().find(|(name, _)| *name == instance).map(|(_, v)| *v).unwrap_or(&[])
+}
+
+fn number(value: &Value, key: &str) -> Option<f64> {
+    value.get(key).and_then(Value::as_f64)
+}
+
+fn is_identifier(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn short(kind: &str) -> &str {
+    kind.rsplit('.').next().unwrap_or(kind)
+}
+
+fn describe(kind: &str, instance: &str) -> String {
+    format!("{} '{}'", short(kind), instance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn device(device_type: &str, capabilities: Value, properties: Value) -> DeviceDefinition {
+        serde_json::from_value(json!({
+            "id": "test-device",
+            "name": "Test device",
+            "type": device_type,
+            "capabilities": capabilities,
+            "properties": properties,
+            "handler": {"type": "site_notification", "template": "{{value}}"},
+        }))
+        .unwrap()
+    }
+
+    fn with_capability(capability: Value) -> DeviceDefinition {
+        device("devices.types.other", json!([capability]), json!([]))
+    }
+
+    fn with_property(property: Value) -> DeviceDefinition {
+        device("devices.types.sensor", json!([]), json!([property]))
+    }
+
+    #[test]
+    fn accepts_range_capabilities() {
+        let brightness = with_capability(json!({
+            "type": RANGE,
+            "parameters": {"instance": "brightness", "unit": "unit.percent", "random_access": true,
+                           "range": {"min": 1, "max": 100, "precision": 1}},
+        }));
+        validate_device(&brightness).unwrap();
+        // Unitless instances and an omitted range are fine too
+        validate_device(&with_capability(json!({"type": RANGE, "parameters": {"instance": "volume"}}))).unwrap();
+    }
+
+    #[test]
+    fn accepts_color_setting_capabilities() {
+        validate_device(&with_capability(json!({
+            "type": COLOR_SETTING,
+            "parameters": {
+                "color_model": "hsv",
+                "temperature_k": {"min": 2700, "max": 6500},
+                "color_scene": {"scenes": [{"id": "night"}, {"id": "party"}]},
+            },
+        })))
+        .unwrap();
+        validate_device(&with_capability(json!({"type": COLOR_SETTING, "parameters": {"color_model": "rgb"}}))).unwrap();
+    }
+
+    #[test]
+    fn accepts_mode_and_toggle_capabilities() {
+        validate_device(&device(
+            "devices.types.thermostat.ac",
+            json!([
+                {"type": ON_OFF, "parameters": {"split": false}},
+                {"type": MODE, "parameters": {"instance": "thermostat", "modes": [{"value": "heat"}, {"value": "cool"}]}},
+                {"type": MODE, "parameters": {"instance": "fan_speed", "modes": [{"value": "auto"}]}},
+                {"type": TOGGLE, "parameters": {"instance": "ionization"}},
+            ]),
+            json!([]),
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn accepts_float_and_event_properties() {
+        validate_device(&device(
+            "devices.types.sensor.climate",
+            json!([]),
+            json!([
+                {"type": FLOAT, "parameters": {"instance": "temperature", "unit": "unit.temperature.celsius"}},
+                {"type": FLOAT, "parameters": {"instance": "pressure", "unit": "unit.pressure.mmhg"}},
+                {"type": FLOAT, "parameters": {"instance": "meter"}},
+                {"type": EVENT, "parameters": {"instance": "open", "events": [{"value": "opened"}, {"value": "closed"}]}},
+            ]),
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_instances() {
+        for (capability, message) in [
+            (json!({"type": RANGE, "parameters": {"instance": "speed"}}), "range: unknown instance 'speed'"),
+            (json!({"type": TOGGLE, "parameters": {"instance": "turbo"}}), "toggle: unknown instance 'turbo'"),
+            (
+                json!({"type": MODE, "parameters": {"instance": "color", "modes": [{"value": "red"}]}}),
+                "mode: unknown instance 'color'",
+            ),
+            (json!({"type": TOGGLE}), "toggle: parameters.instance is required"),
+        ] {
+            assert_eq!(validate_device(&with_capability(capability)), Err(message.to_string()));
+        }
+        let property = with_property(json!({"type": FLOAT, "parameters": {"instance": "speed"}}));
+        assert_eq!(validate_device(&property), Err("float: unknown instance 'speed'".to_string()));
+        let property = with_property(json!({"type": EVENT, "parameters": {"instance": "motion", "events": [{"value": "opened"}]}}));
+        assert!(validate_device(&property).unwrap_err().starts_with("event 'motion': events must be among"));
+    }
+
+    #[test]
+    fn rejects_invalid_ranges_and_units() {
+        for range in [json!({"min": 100, "max": 1}), json!({"min": 5, "max": 5}), json!({"min": 0, "max": 10, "precision": 0}), json!({"min": 0, "max": 10, "precision": 20}), json!({"max": 10})] {
+            let capability = with_capability(json!({"type": RANGE, "parameters": {"instance": "open", "unit": "unit.percent", "range": range}}));
+            assert!(validate_device(&capability).unwrap_err().contains("range needs min < max"), "{}", range);
+        }
+        let capability = with_capability(json!({"type": RANGE, "parameters": {"instance": "brightness", "unit": "unit.watt"}}));
+        assert!(validate_device(&capability).unwrap_err().contains("unit must be one of unit.percent"));
+        let capability = with_capability(json!({"type": RANGE, "parameters": {"instance": "volume", "unit": "unit.percent"}}));
+        assert_eq!(validate_device(&capability), Err("range 'volume' takes no unit".to_string()));
+        let capability = with_capability(json!({"type": COLOR_SETTING, "parameters": {"temperature_k": {"min": 6500, "max": 2700}}}));
+        assert!(validate_device(&capability).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_devices_and_duplicates() {
+        let mut invalid = device("devices.types.toaster", json!([]), json!([]));
+        invalid.id = "bad id".to_string();
+        invalid.name = " ".to_string();
+        let errors = validate_device(&invalid).unwrap_err();
+        assert_eq!(errors.split("; ").count(), 4, "{}", errors);
+
+        let duplicate = device(
+            "devices.types.light",
+            json!([{"type": ON_OFF}, {"type": ON_OFF}]),
+            json!([]),
+        );
+        assert_eq!(validate_device(&duplicate), Err("on_off 'on' declared twice".to_string()));
+        let modes = with_capability(json!({"type": MODE, "parameters": {"instance": "program", "modes": [{"value": "eco"}, {"value": "eco"}]}}));
+        assert!(validate_device(&modes).is_err());
+    }
+
+    #[test]
+    fn values_must_fit_the_declaration() {
+        let modes = json!({"instance": "thermostat", "modes": [{"value": "heat"}, {"value": "cool"}]});
+        assert!(validate_value(MODE, Some(&modes), "thermostat", &json!("heat")).is_ok());
+        assert!(validate_value(MODE, Some(&modes), "thermostat", &json!("dry")).is_err());
+        assert!(validate_value(MODE, Some(&modes), "thermostat", &json!(1)).is_err());
+        assert!(validate_value(MODE, None, "thermostat", &json!("heat")).is_err());
+
+        let range = json!({"instance": "brightness", "range": {"min": 1, "max": 100}});
+        assert!(validate_value(RANGE, Some(&range), "brightness", &json!(100)).is_ok());
+        assert!(validate_value(RANGE, Some(&range), "brightness", &json!(0)).is_err());
+        assert!(validate_value(RANGE, Some(&range), "brightness", &json!("50")).is_err());
+
+        assert!(validate_value(TOGGLE, None, "mute", &json!(true)).is_ok());
+        assert!(validate_value(ON_OFF, None, "on", &json!("on")).is_err());
+        assert!(validate_value(COLOR_SETTING, None, "rgb", &json!(0xFF_FFFF)).is_ok());
+        assert!(validate_value(COLOR_SETTING, None, "rgb", &json!(0x100_0000)).is_err());
+        assert!(validate_value(COLOR_SETTING, None, "hsv", &json!({"h": 360, "s": 100, "v": 0})).is_ok());
+        assert!(validate_value(COLOR_SETTING, None, "hsv", &json!({"h": 361, "s": 0, "v": 0})).is_err());
+        let events = json!({"instance": "open", "events": [{"value": "opened"}]});
+        assert!(validate_value(EVENT, Some(&events), "open", &json!("opened")).is_ok());
+        assert!(validate_value(EVENT, Some(&events), "open", &json!("closed")).is_err());
+        assert!(validate_value(FLOAT, None, "temperature", &json!(21.5)).is_ok());
+    }
+}
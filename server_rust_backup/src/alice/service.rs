@@ -1,57 +1,64 @@
+use crate::alice::handlers::ActionContext;
 use crate::alice::models::*;
+use crate::alice::mqtt::MqttClient;
+use crate::alice::schedule::PcScheduleService;
+use crate::alice::schema;
 use crate::db::DbPool;
+use crate::files::{FileService, StoredFile};
 use crate::telegram::TelegramBot;
 use parking_lot::RwLock;
 use reqwest::Client;
-use std::collections::HashMap;
-use std::net::UdpSocket;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{watch, Notify};
+use tracing::{info, warn};
 
-/// Shared state for Alice notifications
+/// Shared state of Alice devices
 #[derive(Clone)]
 pub struct AliceState {
-    pub notifications: Arc<RwLock<Vec<WebsiteNotification>>>,
     pub pc_status: Arc<RwLock<HashMap<String, bool>>>,
+    /// Devices whose state changed since Yandex was last notified
+    pub changed_devices: Arc<RwLock<HashSet<String>>>,
+    /// The device list changed since Yandex was last notified
+    pub devices_changed: Arc<AtomicBool>,
+    pub change_signal: Arc<Notify>,
 }
 
 impl AliceState {
     pub fn new() -> Self {
         Self {
-            notifications: Arc::new(RwLock::new(Vec::new())),
             pc_status: Arc::new(RwLock::new(HashMap::new())),
+            changed_devices: Arc::new(RwLock::new(HashSet::new())),
+            devices_changed: Arc::new(AtomicBool::new(false)),
+            change_signal: Arc::new(Notify::new()),
         }
     }
 
-    /// Add notification
-    pub fn add_notification(&self, message: String, notification_type: String) -> WebsiteNotification {
-        let notification = WebsiteNotification {
-            id: uuid::Uuid::new_v4().to_string(),
-            message,
-            notification_type,
-            created_at: chrono::Utc::now(),
-        };
-
-        let mut notifications = self.notifications.write();
-        notifications.push(notification.clone());
-
-        // Keep only last 100 notifications
-        if notifications.len() > 100 {
-            notifications.remove(0);
-        }
+    /// Record a state change for the Yandex state callback
+    pub fn mark_changed(&self, device_id: &str) {
+        self.changed_devices.write().insert(device_id.to_string());
+        self.change_signal.notify_one();
+    }
 
-        notification
+    /// Record a device list change for the Yandex discovery callback
+    pub fn mark_devices_changed(&self) {
+        self.devices_changed.store(true, Ordering::SeqCst);
+        self.change_signal.notify_one();
     }
 
-    /// Get recent notifications
-    pub fn get_notifications(&self, limit: usize) -> Vec<WebsiteNotification> {
-        let notifications = self.notifications.read();
-        notifications.iter().rev().take(limit).cloned().collect()
+    /// Changed device IDs and whether the device list changed, resetting both
+    pub fn take_changes(&self) -> (Vec<String>, bool) {
+        let devices = std::mem::take(&mut *self.changed_devices.write());
+        (devices.into_iter().collect(), self.devices_changed.swap(false, Ordering::SeqCst))
     }
 
     /// Set PC status
     pub fn set_pc_status(&self, device_id: String, is_online: bool) {
-        let mut status = self.pc_status.write();
-        status.insert(device_id, is_online);
+        let previous = self.pc_status.write().insert(device_id.clone(), is_online);
+        if previous != Some(is_online) {
+            self.mark_changed(&device_id);
+        }
     }
 
     /// Get PC status
@@ -73,32 +80,42 @@ pub struct AliceService;
 impl AliceService {
     /// Get all enabled devices
     pub async fn get_devices(pool: &DbPool) -> Result<Vec<AliceDevice>, sqlx::Error> {
+        Ok(Self::list_definitions(pool)
+            .await?
+            .into_iter()
+            .filter(|d| d.enabled)
+            .map(Self::to_alice_device)
+            .collect())
+    }
+
+    /// All registry devices, disabled ones included
+    pub async fn list_definitions(pool: &DbPool) -> Result<Vec<DeviceDefinition>, sqlx::Error> {
         let devices = match pool {
             DbPool::Sqlite(p) => {
                 sqlx::query_as::<_, DbAliceDevice>(
-                    "SELECT id, name, description, room, device_type, capabilities, properties, custom_data, is_enabled FROM alice_devices WHERE is_enabled = TRUE"
+                    "SELECT id, name, description, room, device_type, capabilities, properties, custom_data, is_enabled, handler FROM alice_devices ORDER BY id"
                 )
                 .fetch_all(p)
                 .await?
             }
             DbPool::Postgres(p) => {
                 sqlx::query_as::<_, DbAliceDevice>(
-                    "SELECT id, name, description, room, device_type, capabilities, properties, custom_data, is_enabled FROM alice_devices WHERE is_enabled = TRUE"
+                    "SELECT id, name, description, room, device_type, capabilities, properties, custom_data, is_enabled, handler FROM alice_devices ORDER BY id"
                 )
                 .fetch_all(p)
                 .await?
             }
         };
 
-        Ok(devices.into_iter().map(|d| Self::db_to_alice_device(d)).collect())
+        Ok(devices.into_iter().filter_map(Self::db_to_definition).collect())
     }
 
-    /// Get device by ID
-    pub async fn get_device(pool: &DbPool, device_id: &str) -> Result<Option<AliceDevice>, sqlx::Error> {
+    /// Get registry device by ID
+    pub async fn get_definition(pool: &DbPool, device_id: &str) -> Result<Option<DeviceDefinition>, sqlx::Error> {
         let device = match pool {
             DbPool::Sqlite(p) => {
                 sqlx::query_as::<_, DbAliceDevice>(
-                    "SELECT id, name, description, room, device_type, capabilities, properties, custom_data, is_enabled FROM alice_devices WHERE id = ?"
+                    "SELECT id, name, description, room, device_type, capabilities, properties, custom_data, is_enabled, handler FROM alice_devices WHERE id = ?"
                 )
                 .bind(device_id)
                 .fetch_optional(p)
@@ -106,7 +123,7 @@ impl AliceService {
             }
             DbPool::Postgres(p) => {
                 sqlx::query_as::<_, DbAliceDevice>(
-                    "SELECT id, name, description, room, device_type, capabilities, properties, custom_data, is_enabled FROM alice_devices WHERE id = $1"
+                    "SELECT id, name, description, room, device_type, capabilities, properties, custom_data, is_enabled, handler FROM alice_devices WHERE id = $1"
                 )
                 .bind(device_id)
                 .fetch_optional(p)
@@ -114,71 +131,54 @@ impl AliceService {
             }
         };
 
-        Ok(device.map(|d| Self::db_to_alice_device(d)))
+        Ok(device.and_then(Self::db_to_definition))
     }
 
-    /// Convert DB device to Alice device
-    fn db_to_alice_device(db_device: DbAliceDevice) -> AliceDevice {
-        let capabilities_json: Vec<String> = serde_json::from_str(&db_device.capabilities).unwrap_or_default();
-
-        let capabilities: Vec<DeviceCapability> = capabilities_json
-            .iter()
-            .map(|cap_type| {
-                match cap_type.as_str() {
-                    "on_off" => DeviceCapability {
-                        capability_type: "devices.capabilities.on_off".to_string(),
-                        retrievable: true,
-                        reportable: Some(true),
-                        parameters: None,
-                        state: None,
-                    },
-                    "custom_button" => DeviceCapability {
-                        capability_type: "devices.capabilities.toggle".to_string(),
-                        retrievable: true,
-                        reportable: Some(false),
-                        parameters: Some(serde_json::json!({
-                            "instance": "mute"
-                        })),
-                        state: None,
-                    },
-                    _ => DeviceCapability {
-                        capability_type: format!("devices.capabilities.{}", cap_type),
-                        retrievable: true,
-                        reportable: None,
-                        parameters: None,
-                        state: None,
-                    },
-                }
-            })
-            .collect();
-
-        let properties = db_device.properties.and_then(|p| {
-            let props_json: Vec<String> = serde_json::from_str(&p).ok()?;
-            Some(
-                props_json
-                    .iter()
-                    .map(|prop_type| DeviceProperty {
-                        property_type: format!("devices.properties.{}", prop_type),
-                        retrievable: true,
-                        reportable: Some(true),
-                        parameters: None,
-                        state: None,
-                    })
-                    .collect(),
-            )
-        });
-
-        let custom_data = db_device.custom_data.and_then(|c| serde_json::from_str(&c).ok());
+    /// Parse a DB row; rows with a broken schema or handler are skipped
+    fn db_to_definition(db_device: DbAliceDevice) -> Option<DeviceDefinition> {
+        let parsed = (|| -> Result<_, serde_json::Error> {
+            let capabilities = serde_json::from_str(&db_device.capabilities)?;
+            let properties = match &db_device.properties {
+                Some(p) => serde_json::from_str(p)?,
+                None => vec![],
+            };
+            let handler = serde_json::from_str(db_device.handler.as_deref().unwrap_or("null"))?;
+            Ok((capabilities, properties, handler))
+        })();
+
+        let (capabilities, properties, handler) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                warn!("Skipping Alice device {}: {}", db_device.id, e);
+                return None;
+            }
+        };
 
-        AliceDevice {
+        Some(DeviceDefinition {
             id: db_device.id,
             name: db_device.name,
             description: db_device.description,
             room: db_device.room,
             device_type: db_device.device_type,
-            custom_data,
             capabilities,
             properties,
+            handler,
+            custom_data: db_device.custom_data.and_then(|c| serde_json::from_str(&c).ok()),
+            enabled: db_device.is_enabled,
+        })
+    }
+
+    /// Registry device as Yandex sees it
+    fn to_alice_device(device: DeviceDefinition) -> AliceDevice {
+        AliceDevice {
+            id: device.id,
+            name: device.name,
+            description: device.description,
+            room: device.room,
+            device_type: device.device_type,
+            custom_data: device.custom_data,
+            capabilities: device.capabilities,
+            properties: Some(device.properties).filter(|p| !p.is_empty()),
             device_info: Some(DeviceInfo {
                 manufacturer: Some("BGalin Smart Home".to_string()),
                 model: Some("v1.0".to_string()),
@@ -188,239 +188,378 @@ impl AliceService {
         }
     }
 
-    /// Get device state
-    pub async fn get_device_state(
+    /// Create (`create`) or replace a registry device after validating it against the Yandex schema.
+    /// Returns false when the device to replace does not exist.
+    pub async fn save_device(
         pool: &DbPool,
-        device_id: &str,
+        device: &DeviceDefinition,
+        create: bool,
         alice_state: &AliceState,
-    ) -> Result<Vec<DeviceCapability>, sqlx::Error> {
-        let device = Self::get_device(pool, device_id).await?;
+    ) -> Result<bool, String> {
+        schema::validate_device(device)?;
+        device.handler.validate()?;
 
-        if device.is_none() {
-            return Ok(vec![]);
+        if create && Self::get_definition(pool, &device.id).await.map_err(|e| e.to_string())?.is_some() {
+            return Err(format!("Device {} already exists", device.id));
+        }
+
+        let capabilities = serde_json::to_string(&device.capabilities).map_err(|e| e.to_string())?;
+        let properties = Some(&device.properties)
+            .filter(|p| !p.is_empty())
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let handler = serde_json::to_string(&device.handler).map_err(|e| e.to_string())?;
+        let custom_data = device.custom_data.as_ref().map(|c| c.to_string());
+
+        let (insert, update) = match pool {
+            DbPool::Sqlite(_) => (
+                "INSERT INTO alice_devices (name, description, room, device_type, capabilities, properties, handler, custom_data, is_enabled, id)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                "UPDATE alice_devices SET name = ?, description = ?, room = ?, device_type = ?, capabilities = ?, properties = ?,
+                 handler = ?, custom_data = ?, is_enabled = ?, updated_at = datetime('now') WHERE id = ?",
+            ),
+            DbPool::Postgres(_) => (
+                "INSERT INTO alice_devices (name, description, room, device_type, capabilities, properties, handler, custom_data, is_enabled, id)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                "UPDATE alice_devices SET name = $1, description = $2, room = $3, device_type = $4, capabilities = $5, properties = $6,
+                 handler = $7, custom_data = $8, is_enabled = $9, updated_at = NOW() WHERE id = $10",
+            ),
+        };
+        let sql = if create { insert } else { update };
+
+        let affected = match pool {
+            DbPool::Sqlite(p) => sqlx::query(sql)
+                .bind(&device.name)
+                .bind(&device.description)
+                .bind(&device.room)
+                .bind(&device.device_type)
+                .bind(&capabilities)
+                .bind(&properties)
+                .bind(&handler)
+                .bind(&custom_data)
+                .bind(device.enabled)
+                .bind(&device.id)
+                .execute(p)
+                .await
+                .map(|r| r.rows_affected()),
+            DbPool::Postgres(p) => sqlx::query(sql)
+                .bind(&device.name)
+                .bind(&device.description)
+                .bind(&device.room)
+                .bind(&device.device_type)
+                .bind(&capabilities)
+                .bind(&properties)
+                .bind(&handler)
+                .bind(&custom_data)
+                .bind(device.enabled)
+                .bind(&device.id)
+                .execute(p)
+                .await
+                .map(|r| r.rows_affected()),
         }
+        .map_err(|e| e.to_string())?;
 
-        let device = device.unwrap();
-        let mut capabilities = device.capabilities;
+        if affected > 0 {
+            info!("{} Alice device {}", if create { "Created" } else { "Updated" }, device.id);
+            alice_state.mark_devices_changed();
+        }
+        Ok(affected > 0)
+    }
 
-        // Update states based on device type
-        for cap in &mut capabilities {
-            if cap.capability_type == "devices.capabilities.on_off" {
-                let is_on = alice_state.get_pc_status(device_id);
-                cap.state = Some(CapabilityState {
-                    instance: "on".to_string(),
-                    value: serde_json::Value::Bool(is_on),
-                });
-            } else if cap.capability_type == "devices.capabilities.toggle" {
-                cap.state = Some(CapabilityState {
-                    instance: "mute".to_string(),
-                    value: serde_json::Value::Bool(false),
-                });
+    /// Delete a registry device with its states and command log
+    pub async fn delete_device(pool: &DbPool, device_id: &str, alice_state: &AliceState) -> Result<bool, sqlx::Error> {
+        let affected = match pool {
+            DbPool::Sqlite(p) => {
+                for table in ["alice_device_states", "alice_command_log"] {
+                    sqlx::query(&format!("DELETE FROM {} WHERE device_id = ?", table))
+                        .bind(device_id)
+                        .execute(p)
+                        .await?;
+                }
+                sqlx::query("DELETE FROM alice_devices WHERE id = ?")
+                    .bind(device_id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
             }
+            DbPool::Postgres(p) => sqlx::query("DELETE FROM alice_devices WHERE id = $1")
+                .bind(device_id)
+                .execute(p)
+                .await?
+                .rows_affected(),
+        };
+        if affected > 0 {
+            alice_state.mark_devices_changed();
         }
+        Ok(affected > 0)
+    }
+
+    /// Stored values of a device keyed by "type:instance", oldest first
+    async fn get_states(pool: &DbPool, device_id: &str) -> Result<Vec<(String, serde_json::Value)>, sqlx::Error> {
+        let rows: Vec<(String, String)> = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as("SELECT state_key, state_value FROM alice_device_states WHERE device_id = ? ORDER BY updated_at, id")
+                    .bind(device_id)
+                    .fetch_all(p)
+                    .await?
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as("SELECT state_key, state_value FROM alice_device_states WHERE device_id = $1 ORDER BY updated_at, id")
+                    .bind(device_id)
+                    .fetch_all(p)
+                    .await?
+            }
+        };
 
-        Ok(capabilities)
+        Ok(rows
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, serde_json::from_str(&value).ok()?)))
+            .collect())
     }
 
-    /// Execute action on device
-    pub async fn execute_action(
+    /// Remember the value of a capability or property
+    async fn set_state(
         pool: &DbPool,
         device_id: &str,
-        capability_type: &str,
+        kind: &str,
         instance: &str,
-        value: serde_json::Value,
-        alice_state: &AliceState,
-        telegram_bot: &TelegramBot,
-        admin_telegram_id: i64,
-    ) -> Result<ActionResult, Box<dyn std::error::Error + Send + Sync>> {
-        let device = Self::get_device(pool, device_id).await?;
-
-        if device.is_none() {
-            return Ok(ActionResult {
-                status: "ERROR".to_string(),
-                error_code: Some("DEVICE_NOT_FOUND".to_string()),
-                error_message: Some("Device not found".to_string()),
-            });
-        }
-
-        let device = device.unwrap();
-        let custom_data = device.custom_data.unwrap_or_default();
-
-        // Log the command
-        Self::log_command(pool, device_id, capability_type, &serde_json::to_string(&value).unwrap_or_default()).await.ok();
-
-        match device_id {
-            "pc-control" => {
-                Self::handle_pc_control(pool, &custom_data, capability_type, instance, &value, alice_state).await
-            }
-            "telegram-bot" => {
-                Self::handle_telegram_bot(&custom_data, capability_type, instance, &value, telegram_bot, admin_telegram_id).await
-            }
-            "website-notify" => {
-                Self::handle_website_notify(capability_type, instance, &value, alice_state).await
+        value: &serde_json::Value,
+    ) -> Result<(), sqlx::Error> {
+        let key = format!("{}:{}", kind, instance);
+        let value = value.to_string();
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "INSERT INTO alice_device_states (device_id, state_key, state_value) VALUES (?, ?, ?)
+                     ON CONFLICT (device_id, state_key) DO UPDATE SET state_value = excluded.state_value, updated_at = datetime('now')"
+                )
+                .bind(device_id)
+                .bind(&key)
+                .bind(&value)
+                .execute(p)
+                .await?;
             }
-            _ => {
-                Ok(ActionResult {
-                    status: "ERROR".to_string(),
-                    error_code: Some("INVALID_ACTION".to_string()),
-                    error_message: Some("Unknown device".to_string()),
-                })
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "INSERT INTO alice_device_states (device_id, state_key, state_value) VALUES ($1, $2, $3)
+                     ON CONFLICT (device_id, state_key) DO UPDATE SET state_value = EXCLUDED.state_value, updated_at = NOW()"
+                )
+                .bind(device_id)
+                .bind(&key)
+                .bind(&value)
+                .execute(p)
+                .await?;
             }
         }
+        Ok(())
     }
 
-    /// Handle PC control - queues commands for PC client
-    async fn handle_pc_control(
+    /// Store a value reported from the device side, e.g. a sensor reading or a change made
+    /// outside Alice. Returns false for unknown devices.
+    pub async fn report_state(
         pool: &DbPool,
-        custom_data: &serde_json::Value,
-        _capability_type: &str,
-        instance: &str,
-        value: &serde_json::Value,
+        device_id: &str,
+        update: &DeviceStateUpdate,
         alice_state: &AliceState,
-    ) -> Result<ActionResult, Box<dyn std::error::Error + Send + Sync>> {
-        let mac = custom_data.get("mac").and_then(|v| v.as_str()).unwrap_or("");
-
-        if instance == "on" {
-            let turn_on = value.as_bool().unwrap_or(false);
-
-            if turn_on {
-                // Wake-on-LAN - still done directly from server (doesn't need PC to be on)
-                if !mac.is_empty() {
-                    match Self::send_wol(mac) {
-                        Ok(_) => {
-                            alice_state.set_pc_status("pc-control".to_string(), true);
-                            alice_state.add_notification(
-                                "ПК включается по команде Алисы".to_string(),
-                                "info".to_string(),
-                            );
-                            return Ok(ActionResult {
-                                status: "DONE".to_string(),
-                                error_code: None,
-                                error_message: None,
-                            });
-                        }
-                        Err(e) => {
-                            return Ok(ActionResult {
-                                status: "ERROR".to_string(),
-                                error_code: Some("INTERNAL_ERROR".to_string()),
-                                error_message: Some(format!("WoL failed: {}", e)),
-                            });
-                        }
-                    }
-                } else {
-                    return Ok(ActionResult {
-                        status: "ERROR".to_string(),
-                        error_code: Some("INVALID_VALUE".to_string()),
-                        error_message: Some("MAC address not configured".to_string()),
-                    });
+    ) -> Result<bool, String> {
+        let Some(device) = Self::get_definition(pool, device_id).await.map_err(|e| e.to_string())? else {
+            return Ok(false);
+        };
+
+        let parameters = device
+            .capabilities
+            .iter()
+            .find(|c| c.capability_type == update.state_type && schema::capability_accepts(c, &update.instance))
+            .map(|c| c.parameters.as_ref())
+            .or_else(|| {
+                device
+                    .properties
+                    .iter()
+                    .find(|p| {
+                        p.property_type == update.state_type
+                            && p.parameters.as_ref().and_then(|v| v.get("instance")).and_then(|v| v.as_str())
+                                == Some(update.instance.as_str())
+                    })
+                    .map(|p| p.parameters.as_ref())
+            })
+            .ok_or_else(|| format!("Device {} has no {} '{}'", device_id, update.state_type, update.instance))?;
+
+        schema::validate_value(&update.state_type, parameters, &update.instance, &update.value)?;
+        Self::set_state(pool, device_id, &update.state_type, &update.instance, &update.value)
+            .await
+            .map_err(|e| e.to_string())?;
+        alice_state.mark_changed(device_id);
+        Ok(true)
+    }
+
+    /// Current capability and property states; None for unknown or disabled devices
+    pub async fn get_device_state(
+        pool: &DbPool,
+        device_id: &str,
+        alice_state: &AliceState,
+    ) -> Result<Option<(Vec<DeviceCapability>, Vec<DeviceProperty>)>, sqlx::Error> {
+        let Some(device) = Self::get_definition(pool, device_id).await?.filter(|d| d.enabled) else {
+            return Ok(None);
+        };
+        let states = Self::get_states(pool, device_id).await?;
+        let stored = |kind: &str, instance: &str| {
+            let key = format!("{}:{}", kind, instance);
+            states.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| v.clone())
+        };
+
+        let mut capabilities = Vec::new();
+        for cap in device.capabilities.iter().filter(|c| c.retrievable) {
+            let state = match cap.capability_type.as_str() {
+                // The PC's power state comes from its client's heartbeats
+                schema::ON_OFF if matches!(device.handler, DeviceHandler::PcPower) => {
+                    Some(("on".to_string(), serde_json::Value::Bool(alice_state.get_pc_status(device_id))))
                 }
-            } else {
-                // Shutdown - queue command for PC client
-                let command_data = serde_json::json!({
-                    "type": "Shutdown"
-                });
-
-                match CommandQueueService::queue_command(pool, "pc_shutdown", command_data, 10).await {
-                    Ok(cmd_id) => {
-                        alice_state.set_pc_status("pc-control".to_string(), false);
-                        alice_state.add_notification(
-                            format!("Команда на выключение ПК отправлена (ID: {})", cmd_id),
-                            "info".to_string(),
-                        );
-                        return Ok(ActionResult {
-                            status: "DONE".to_string(),
-                            error_code: None,
-                            error_message: None,
-                        });
-                    }
-                    Err(e) => {
-                        return Ok(ActionResult {
-                            status: "ERROR".to_string(),
-                            error_code: Some("INTERNAL_ERROR".to_string()),
-                            error_message: Some(format!("Failed to queue command: {}", e)),
-                        });
-                    }
+                // A color is reported in whichever model it was last set
+                schema::COLOR_SETTING => {
+                    let prefix = format!("{}:", cap.capability_type);
+                    states
+                        .iter()
+                        .rev()
+                        .find_map(|(k, v)| Some((k.strip_prefix(&prefix)?.to_string(), v.clone())))
                 }
+                _ => schema::default_state(cap).map(|(instance, default)| {
+                    let value = stored(&cap.capability_type, &instance).unwrap_or(default);
+                    (instance, value)
+                }),
+            };
+            if let Some((instance, value)) = state {
+                let mut cap = cap.clone();
+                cap.state = Some(CapabilityState { instance, value });
+                capabilities.push(cap);
             }
         }
 
-        Ok(ActionResult {
-            status: "ERROR".to_string(),
-            error_code: Some("INVALID_ACTION".to_string()),
-            error_message: Some("Unknown action".to_string()),
-        })
+        let mut properties = Vec::new();
+        for prop in device.properties.iter().filter(|p| p.retrievable) {
+            let instance = prop.parameters.as_ref().and_then(|p| p.get("instance")).and_then(|i| i.as_str());
+            if let Some(instance) = instance {
+                if let Some(value) = stored(&prop.property_type, instance) {
+                    let mut prop = prop.clone();
+                    prop.state = Some(PropertyState {
+                        instance: instance.to_string(),
+                        value,
+                    });
+                    properties.push(prop);
+                }
+            }
+        }
+
+        Ok(Some((capabilities, properties)))
     }
 
-    /// Send Wake-on-LAN magic packet
-    fn send_wol(mac_address: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Parse MAC address
-        let mac_bytes: Vec<u8> = mac_address
-            .split(|c| c == ':' || c == '-')
-            .map(|s| u8::from_str_radix(s, 16))
-            .collect::<Result<Vec<_>, _>>()?;
+    /// Query answer for one device, registry device or PC schedule switch
+    pub async fn query_device(
+        pool: &DbPool,
+        device_id: &str,
+        alice_state: &AliceState,
+        schedules: &PcScheduleService,
+    ) -> QueryDeviceResponse {
+        let mut response = QueryDeviceResponse {
+            id: device_id.to_string(),
+            capabilities: None,
+            properties: None,
+            error_code: None,
+            error_message: None,
+        };
 
-        if mac_bytes.len() != 6 {
-            return Err("Invalid MAC address".into());
+        if let Some(schedule_id) = PcScheduleService::schedule_id(device_id) {
+            response.capabilities = Some(vec![DeviceCapability {
+                capability_type: schema::ON_OFF.to_string(),
+                retrievable: true,
+                reportable: Some(true),
+                parameters: None,
+                state: Some(CapabilityState {
+                    instance: "on".to_string(),
+                    value: serde_json::Value::Bool(schedules.is_armed(schedule_id)),
+                }),
+            }]);
+            return response;
         }
 
-        // Build magic packet: 6 bytes of 0xFF followed by MAC address 16 times
-        let mut packet = vec![0xFFu8; 6];
-        for _ in 0..16 {
-            packet.extend_from_slice(&mac_bytes);
+        match Self::get_device_state(pool, device_id, alice_state).await {
+            Ok(Some((capabilities, properties))) => {
+                response.capabilities = Some(capabilities);
+                response.properties = Some(properties).filter(|p| !p.is_empty());
+            }
+            Ok(None) => {
+                response.error_code = Some("DEVICE_NOT_FOUND".to_string());
+                response.error_message = Some("Device not found".to_string());
+            }
+            Err(_) => {
+                response.error_code = Some("DEVICE_UNREACHABLE".to_string());
+                response.error_message = Some("Failed to query device".to_string());
+            }
         }
-
-        // Send to broadcast address on port 9
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
-        socket.set_broadcast(true)?;
-        socket.send_to(&packet, "255.255.255.255:9")?;
-
-        Ok(())
+        response
     }
 
-    /// Handle Telegram bot commands
-    async fn handle_telegram_bot(
-        custom_data: &serde_json::Value,
-        _capability_type: &str,
-        _instance: &str,
-        _value: &serde_json::Value,
+    /// Execute action on device through its handler
+    pub async fn execute_action(
+        pool: &DbPool,
+        device_id: &str,
+        action: &ActionCapability,
+        alice_state: &AliceState,
         telegram_bot: &TelegramBot,
         admin_telegram_id: i64,
+        mqtt: &MqttClient,
     ) -> Result<ActionResult, Box<dyn std::error::Error + Send + Sync>> {
-        let chat_id = custom_data
-            .get("chat_id")
-            .and_then(|v| v.as_str())
-            .and_then(|s| s.parse::<i64>().ok())
-            .unwrap_or(admin_telegram_id);
-
-        // Send notification message
-        let message = "Привет! Это сообщение от Алисы!";
-
-        match telegram_bot.send_message(chat_id, message).await {
-            Ok(_) => Ok(ActionResult {
-                status: "DONE".to_string(),
-                error_code: None,
-                error_message: None,
-            }),
-            Err(e) => Ok(ActionResult {
-                status: "ERROR".to_string(),
-                error_code: Some("INTERNAL_ERROR".to_string()),
-                error_message: Some(format!("Telegram error: {}", e)),
-            }),
+        let error = |code: &str, message: String| ActionResult {
+            status: "ERROR".to_string(),
+            error_code: Some(code.to_string()),
+            error_message: Some(message),
+        };
+
+        let Some(device) = Self::get_definition(pool, device_id).await?.filter(|d| d.enabled) else {
+            return Ok(error("DEVICE_NOT_FOUND", "Device not found".to_string()));
+        };
+        let instance = action.state.instance.as_str();
+        let Some(capability) = device
+            .capabilities
+            .iter()
+            .find(|c| c.capability_type == action.capability_type && schema::capability_accepts(c, instance))
+        else {
+            return Ok(error("INVALID_ACTION", format!("Device does not support {} '{}'", action.capability_type, instance)));
+        };
+
+        let mut value = action.state.value.clone();
+        if action.state.relative == Some(true) && capability.capability_type == schema::RANGE {
+            let states = Self::get_states(pool, device_id).await?;
+            let key = format!("{}:{}", capability.capability_type, instance);
+            let current = states.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| v);
+            match schema::apply_relative(capability, current, &value) {
+                Some(absolute) => value = absolute,
+                None => return Ok(error("INVALID_VALUE", format!("Invalid relative value {}", value))),
+            }
+        }
+        if let Err(e) = schema::validate_value(&capability.capability_type, capability.parameters.as_ref(), instance, &value) {
+            return Ok(error("INVALID_VALUE", e));
         }
-    }
 
-    /// Handle website notifications
-    async fn handle_website_notify(
-        _capability_type: &str,
-        _instance: &str,
-        _value: &serde_json::Value,
-        alice_state: &AliceState,
-    ) -> Result<ActionResult, Box<dyn std::error::Error + Send + Sync>> {
-        alice_state.add_notification(
-            "Уведомление от Алисы!".to_string(),
-            "alice".to_string(),
-        );
+        // Log the command
+        Self::log_command(pool, device_id, &action.capability_type, &value.to_string()).await.ok();
+
+        let ctx = ActionContext {
+            device: &device,
+            capability: &action.capability_type,
+            instance,
+            value: &value,
+        };
+        if let Err(e) = device
+            .handler
+            .run(&ctx, pool, alice_state, telegram_bot, admin_telegram_id, mqtt)
+            .await
+        {
+            warn!("Alice action on {} failed: {}", device_id, e);
+            return Ok(error("INTERNAL_ERROR", e));
+        }
 
+        Self::set_state(pool, device_id, &action.capability_type, instance, &value).await?;
         Ok(ActionResult {
             status: "DONE".to_string(),
             error_code: None,
@@ -460,6 +599,42 @@ impl AliceService {
         Ok(())
     }
 
+    /// Alice users that linked their account, i.e. hold tokens
+    pub async fn linked_user_ids(pool: &DbPool) -> Result<Vec<i64>, sqlx::Error> {
+        let rows: Vec<(i64,)> = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as("SELECT DISTINCT user_id FROM alice_tokens")
+                    .fetch_all(p)
+                    .await?
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as("SELECT DISTINCT user_id::INT8 FROM alice_tokens")
+                    .fetch_all(p)
+                    .await?
+            }
+        };
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// Drop all tokens of a user once they unlink the skill
+    pub async fn revoke_tokens(pool: &DbPool, user_id: i64) -> Result<(), sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("DELETE FROM alice_tokens WHERE user_id = ?")
+                    .bind(user_id)
+                    .execute(p)
+                    .await?;
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query("DELETE FROM alice_tokens WHERE user_id = $1")
+                    .bind(user_id)
+                    .execute(p)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
     /// Validate access token
     pub async fn validate_token(pool: &DbPool, access_token: &str) -> Result<Option<i64>, sqlx::Error> {
         let token = match pool {
@@ -678,56 +853,233 @@ pub async fn check_device_online(ip: &str) -> bool {
     }
 }
 
+/// PC clients that sent a heartbeat or polled within this window count as online
+pub const PC_ONLINE_WINDOW_SECS: i64 = 120;
+
+/// File manager folder receiving files uploaded as command results, e.g. screenshots
+pub const ARTIFACT_FOLDER: &str = "PC Screenshots";
+
 /// Command Queue Service for PC client
 pub struct CommandQueueService;
 
+/// Bumped on every queued command so open PC client sockets push it right away
+fn queue_signal() -> &'static watch::Sender<u64> {
+    static SIGNAL: OnceLock<watch::Sender<u64>> = OnceLock::new();
+    SIGNAL.get_or_init(|| watch::channel(0).0)
+}
+
 impl CommandQueueService {
-    /// Queue a command for PC client
+    /// Wakes whenever a command is queued
+    pub fn subscribe_queue() -> watch::Receiver<u64> {
+        queue_signal().subscribe()
+    }
+
+    /// Key signing queued commands, from `PC_COMMAND_SIGNING_KEY`
+    fn signing_key() -> Result<SigningKey, String> {
+        let hex_key = std::env::var("PC_COMMAND_SIGNING_KEY")
+            .map_err(|_| "PC_COMMAND_SIGNING_KEY is not set; generate one with `server pc-keygen`".to_string())?;
+        parse_signing_key(&hex_key).map_err(|e| format!("PC_COMMAND_SIGNING_KEY: {}", e))
+    }
+
+    /// New signing key for `server pc-keygen`: (secret, public), both hex
+    pub fn generate_signing_key() -> (String, String) {
+        let mut seed = [0u8; 32];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut seed);
+        let key = SigningKey::from_bytes(&seed);
+        (hex::encode(seed), hex::encode(key.verifying_key().to_bytes()))
+    }
+
+    /// Sign `command` under a fresh nonce: (nonce, payload, signature)
+    fn sign_command(
+        command: &PcCommandType,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(String, String, String), String> {
+        let signed = SignedCommand {
+            nonce: uuid::Uuid::new_v4().simple().to_string(),
+            expires_at: expires_at.timestamp(),
+            command: command.clone(),
+        };
+        let (payload, signature) = signed.sign(&Self::signing_key()?)?;
+        Ok((signed.nonce, payload, signature))
+    }
+
+    /// Deliveries per command unless the request sets `max_attempts`, from `PC_COMMAND_MAX_ATTEMPTS`
+    fn default_max_attempts() -> i32 {
+        std::env::var("PC_COMMAND_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3)
+    }
+
+    /// How long a claimed command may go without a result before it is retried
+    fn visibility_timeout() -> chrono::Duration {
+        let secs: i64 = std::env::var("PC_COMMAND_VISIBILITY_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+        chrono::Duration::seconds(secs.max(1))
+    }
+
+    /// Delay before retry number `attempts`: `PC_COMMAND_RETRY_BACKOFF_SECS` doubled per attempt, at most an hour
+    fn retry_backoff(attempts: i32) -> chrono::Duration {
+        let base: i64 = std::env::var("PC_COMMAND_RETRY_BACKOFF_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+        let exponent = (attempts - 1).clamp(0, 16) as u32;
+        chrono::Duration::seconds((base.max(1) << exponent).min(3600))
+    }
+
+    /// Queue a signed command for PC client; refused when no client on the current
+    /// protocol that matches `target` has announced the capability it needs
     pub async fn queue_command(
         pool: &DbPool,
-        command_type: &str,
-        command_data: serde_json::Value,
+        command: &PcCommandType,
         priority: i32,
-    ) -> Result<i64, sqlx::Error> {
+        target: &CommandTarget,
+        delivery: &CommandDelivery,
+    ) -> Result<i64, String> {
+        let capability = command.capability();
+        let command_data = serde_json::to_value(command).map_err(|e| e.to_string())?;
+
+        let now = chrono::Utc::now();
+        let max_expiry = now + chrono::Duration::seconds(MAX_COMMAND_TTL_SECS);
+        let expires_at = match delivery.not_after {
+            Some(not_after) if not_after <= now => return Err("not_after is in the past".to_string()),
+            Some(not_after) if not_after > max_expiry => {
+                return Err(format!("not_after is more than {} hours away", MAX_COMMAND_TTL_SECS / 3600))
+            }
+            Some(not_after) => not_after,
+            None => {
+                let ttl_secs: i64 = std::env::var("PC_COMMAND_TTL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(600);
+                now + chrono::Duration::seconds(ttl_secs.clamp(1, MAX_COMMAND_TTL_SECS))
+            }
+        };
+        let max_attempts = delivery.max_attempts.unwrap_or_else(Self::default_max_attempts).clamp(1, 10);
+        let (nonce, payload, signature) = Self::sign_command(command, expires_at)?;
+
+        let client_id = target.client_id.as_deref();
+        let group = target.group.as_deref();
+
         let id: i64 = match pool {
-            DbPool::Sqlite(_) => {
-                // SQLite doesn't have this feature
-                return Err(sqlx::Error::Protocol("Command queue requires PostgreSQL".to_string()));
+            DbPool::Sqlite(p) => {
+                let (capable,): (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM alice_pc_clients
+                     WHERE protocol_version = ? AND EXISTS (SELECT 1 FROM json_each(capabilities) WHERE value = ?)
+                       AND (? IS NULL OR client_id = ?) AND (? IS NULL OR client_group = ?)"
+                )
+                .bind(PROTOCOL_VERSION as i32)
+                .bind(capability.as_str())
+                .bind(client_id)
+                .bind(client_id)
+                .bind(group)
+                .bind(group)
+                .fetch_one(p)
+                .await
+                .map_err(|e| e.to_string())?;
+
+                if capable == 0 {
+                    return Err(no_capable_client(capability, target));
+                }
+
+                let result = sqlx::query(
+                    "INSERT INTO alice_command_queue (command_type, command_data, priority, protocol_version, nonce, payload, signature, expires_at, target_client_id, target_group, max_attempts)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+                )
+                .bind(capability.as_str())
+                .bind(&command_data)
+                .bind(priority)
+                .bind(PROTOCOL_VERSION as i32)
+                .bind(&nonce)
+                .bind(&payload)
+                .bind(&signature)
+                .bind(sqlite_time(expires_at))
+                .bind(client_id)
+                .bind(group)
+                .bind(max_attempts)
+                .execute(p)
+                .await
+                .map_err(|e| e.to_string())?;
+                result.last_insert_rowid()
             }
             DbPool::Postgres(p) => {
+                let (capable,): (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM alice_pc_clients
+                     WHERE protocol_version = $1 AND capabilities @> jsonb_build_array($2::text)
+                       AND ($3::text IS NULL OR client_id = $3) AND ($4::text IS NULL OR client_group = $4)"
+                )
+                .bind(PROTOCOL_VERSION as i32)
+                .bind(capability.as_str())
+                .bind(client_id)
+                .bind(group)
+                .fetch_one(p)
+                .await
+                .map_err(|e| e.to_string())?;
+
+                if capable == 0 {
+                    return Err(no_capable_client(capability, target));
+                }
+
                 let row: (i64,) = sqlx::query_as(
-                    "INSERT INTO alice_command_queue (command_type, command_data, priority) VALUES ($1, $2, $3) RETURNING id"
+                    "INSERT INTO alice_command_queue (command_type, command_data, priority, protocol_version, nonce, payload, signature, expires_at, target_client_id, target_group, max_attempts)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING id::INT8"
                 )
-                .bind(command_type)
+                .bind(capability.as_str())
                 .bind(&command_data)
                 .bind(priority)
+                .bind(PROTOCOL_VERSION as i32)
+                .bind(&nonce)
+                .bind(&payload)
+                .bind(&signature)
+                .bind(expires_at)
+                .bind(client_id)
+                .bind(group)
+                .bind(max_attempts)
                 .fetch_one(p)
-                .await?;
+                .await
+                .map_err(|e| e.to_string())?;
                 row.0
             }
         };
 
-        println!("📝 Queued command {} (type: {}, priority: {})", id, command_type, priority);
+        queue_signal().send_modify(|n| *n += 1);
+        info!("Queued command {} (type: {}, priority: {})", id, capability, priority);
         Ok(id)
     }
 
-    /// Get pending commands for PC client
-    pub async fn get_pending_commands(
+    /// Queued commands in `status` (`pending`, `processing`, `completed`, `failed`, `dead` or `cancelled`)
+    pub async fn get_commands(
         pool: &DbPool,
+        status: &str,
         limit: i64,
     ) -> Result<Vec<DbQueuedCommand>, sqlx::Error> {
         match pool {
-            DbPool::Sqlite(_) => {
-                Ok(vec![])
+            DbPool::Sqlite(p) => {
+                let commands = sqlx::query_as::<_, DbQueuedCommand>(
+                    "SELECT id, command_type, command_data, priority, status, created_at, processed_at, result, error, protocol_version, payload, signature, expires_at, target_client_id, target_group, claimed_by, attempts, max_attempts, claimed_at, available_at, artifact_file_id
+                     FROM alice_command_queue
+                     WHERE status = ?
+                     ORDER BY priority DESC, created_at ASC
+                     LIMIT ?"
+                )
+                .bind(status)
+                .bind(limit)
+                .fetch_all(p)
+                .await?;
+                Ok(commands)
             }
             DbPool::Postgres(p) => {
                 let commands = sqlx::query_as::<_, DbQueuedCommand>(
-                    "SELECT id, command_type, command_data, priority, status, created_at, processed_at, result, error
+                    "SELECT id::INT8 AS id, command_type, command_data, priority, status, created_at, processed_at, result, error, protocol_version, payload, signature, expires_at, target_client_id, target_group, claimed_by, attempts, max_attempts, claimed_at, available_at, artifact_file_id
                      FROM alice_command_queue
-                     WHERE status = 'pending'
+                     WHERE status = $1
                      ORDER BY priority DESC, created_at ASC
-                     LIMIT $1"
+                     LIMIT $2"
                 )
+                .bind(status)
                 .bind(limit)
                 .fetch_all(p)
                 .await?;
@@ -736,65 +1088,550 @@ impl CommandQueueService {
         }
     }
 
-    /// Mark commands as processing
-    pub async fn mark_as_processing(
-        pool: &DbPool,
-        command_ids: &[i64],
-    ) -> Result<(), sqlx::Error> {
-        if command_ids.is_empty() {
-            return Ok(());
-        }
-
+    /// One queued command by id, in any status
+    pub async fn get_command(pool: &DbPool, command_id: i64) -> Result<Option<DbQueuedCommand>, sqlx::Error> {
         match pool {
-            DbPool::Sqlite(_) => Ok(()),
-            DbPool::Postgres(p) => {
-                // Build query for multiple IDs
-                let ids_str: String = command_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
-                let query = format!(
-                    "UPDATE alice_command_queue SET status = 'processing' WHERE id IN ({})",
-                    ids_str
-                );
-                sqlx::query(&query).execute(p).await?;
-                Ok(())
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, DbQueuedCommand>(
+                    "SELECT id, command_type, command_data, priority, status, created_at, processed_at, result, error, protocol_version, payload, signature, expires_at, target_client_id, target_group, claimed_by, attempts, max_attempts, claimed_at, available_at, artifact_file_id
+                     FROM alice_command_queue WHERE id = ?"
+                )
+                .bind(command_id)
+                .fetch_optional(p)
+                .await
             }
-        }
-    }
-
-    /// Mark command as completed
-    pub async fn mark_as_completed(
-        pool: &DbPool,
-        command_id: i64,
-        result: Option<String>,
-    ) -> Result<(), sqlx::Error> {
-        match pool {
-            DbPool::Sqlite(_) => Ok(()),
             DbPool::Postgres(p) => {
-                sqlx::query(
-                    "UPDATE alice_command_queue SET status = 'completed', processed_at = NOW(), result = $1 WHERE id = $2"
+                sqlx::query_as::<_, DbQueuedCommand>(
+                    "SELECT id::INT8 AS id, command_type, command_data, priority, status, created_at, processed_at, result, error, protocol_version, payload, signature, expires_at, target_client_id, target_group, claimed_by, attempts, max_attempts, claimed_at, available_at, artifact_file_id
+                     FROM alice_command_queue WHERE id = $1"
                 )
-                .bind(result)
                 .bind(command_id)
-                .execute(p)
-                .await?;
-                Ok(())
+                .fetch_optional(p)
+                .await
             }
         }
     }
 
-    /// Mark command as failed
-    pub async fn mark_as_failed(
+    /// Pending commands a client could claim: current protocol version, a type in
+    /// `capabilities` and a target matching the client. Nothing is claimed.
+    pub async fn get_claimable_commands(
         pool: &DbPool,
-        command_id: i64,
-        error: String,
+        client_id: &str,
+        group: Option<&str>,
+        capabilities: &[String],
+        limit: i64,
+    ) -> Result<Vec<DbQueuedCommand>, sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                let commands = sqlx::query_as::<_, DbQueuedCommand>(
+                    "SELECT id, command_type, command_data, priority, status, created_at, processed_at, result, error, protocol_version, payload, signature, expires_at, target_client_id, target_group, claimed_by, attempts, max_attempts, claimed_at, available_at, artifact_file_id
+                     FROM alice_command_queue
+                     WHERE status = 'pending' AND protocol_version = ? AND command_type IN (SELECT value FROM json_each(?))
+                       AND (target_client_id IS NULL OR target_client_id = ?)
+                       AND (target_group IS NULL OR target_group = ?)
+                       AND (available_at IS NULL OR available_at <= datetime('now'))
+                     ORDER BY priority DESC, created_at ASC
+                     LIMIT ?"
+                )
+                .bind(PROTOCOL_VERSION as i32)
+                .bind(serde_json::json!(capabilities))
+                .bind(client_id)
+                .bind(group)
+                .bind(limit)
+                .fetch_all(p)
+                .await?;
+                Ok(commands)
+            }
+            DbPool::Postgres(p) => {
+                let commands = sqlx::query_as::<_, DbQueuedCommand>(
+                    "SELECT id::INT8 AS id, command_type, command_data, priority, status, created_at, processed_at, result, error, protocol_version, payload, signature, expires_at, target_client_id, target_group, claimed_by, attempts, max_attempts, claimed_at, available_at, artifact_file_id
+                     FROM alice_command_queue
+                     WHERE status = 'pending' AND protocol_version = $1 AND command_type = ANY($2)
+                       AND (target_client_id IS NULL OR target_client_id = $3)
+                       AND (target_group IS NULL OR target_group = $4)
+                       AND (available_at IS NULL OR available_at <= NOW())
+                     ORDER BY priority DESC, created_at ASC
+                     LIMIT $5"
+                )
+                .bind(PROTOCOL_VERSION as i32)
+                .bind(capabilities)
+                .bind(client_id)
+                .bind(group)
+                .bind(limit)
+                .fetch_all(p)
+                .await?;
+                Ok(commands)
+            }
+        }
+    }
+
+    /// Atomically move claimable commands (see [`Self::get_claimable_commands`]) to
+    /// `processing` for `client_id`; concurrent claims never return the same command
+    pub async fn claim_commands(
+        pool: &DbPool,
+        client_id: &str,
+        group: Option<&str>,
+        capabilities: &[String],
+        limit: i64,
+    ) -> Result<Vec<DbQueuedCommand>, sqlx::Error> {
+        let mut commands = match pool {
+            DbPool::Sqlite(p) => {
+                // A single UPDATE runs under SQLite's write lock, so the select and claim can't interleave
+                sqlx::query_as::<_, DbQueuedCommand>(
+                    "UPDATE alice_command_queue SET status = 'processing', claimed_by = ?, claimed_at = datetime('now'), attempts = attempts + 1
+                     WHERE id IN (
+                         SELECT id FROM alice_command_queue
+                         WHERE status = 'pending' AND protocol_version = ? AND command_type IN (SELECT value FROM json_each(?))
+                           AND (target_client_id IS NULL OR target_client_id = ?)
+                           AND (target_group IS NULL OR target_group = ?)
+                           AND (available_at IS NULL OR available_at <= datetime('now'))
+                         ORDER BY priority DESC, created_at ASC
+                         LIMIT ?
+                     )
+                     RETURNING id, command_type, command_data, priority, status, created_at, processed_at, result, error, protocol_version, payload, signature, expires_at, target_client_id, target_group, claimed_by, attempts, max_attempts, claimed_at, available_at, artifact_file_id"
+                )
+                .bind(client_id)
+                .bind(PROTOCOL_VERSION as i32)
+                .bind(serde_json::json!(capabilities))
+                .bind(client_id)
+                .bind(group)
+                .bind(limit)
+                .fetch_all(p)
+                .await?
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, DbQueuedCommand>(
+                    "UPDATE alice_command_queue SET status = 'processing', claimed_by = $1, claimed_at = NOW(), attempts = attempts + 1
+                     WHERE id IN (
+                         SELECT id FROM alice_command_queue
+                         WHERE status = 'pending' AND protocol_version = $2 AND command_type = ANY($3)
+                           AND (target_client_id IS NULL OR target_client_id = $1)
+                           AND (target_group IS NULL OR target_group = $4)
+                           AND (available_at IS NULL OR available_at <= NOW())
+                         ORDER BY priority DESC, created_at ASC
+                         LIMIT $5
+                         FOR UPDATE SKIP LOCKED
+                     )
+                     RETURNING id::INT8 AS id, command_type, command_data, priority, status, created_at, processed_at, result, error, protocol_version, payload, signature, expires_at, target_client_id, target_group, claimed_by, attempts, max_attempts, claimed_at, available_at, artifact_file_id"
+                )
+                .bind(client_id)
+                .bind(PROTOCOL_VERSION as i32)
+                .bind(capabilities)
+                .bind(group)
+                .bind(limit)
+                .fetch_all(p)
+                .await?
+            }
+        };
+
+        // RETURNING rows come back in no particular order
+        commands.sort_by(|a, b| b.priority.cmp(&a.priority).then(a.created_at.cmp(&b.created_at)));
+        Ok(commands)
+    }
+
+    /// Put commands claimed by `client_id` but never acknowledged back in the queue;
+    /// the delivery does not count as an attempt
+    pub async fn release_commands(
+        pool: &DbPool,
+        command_ids: &[i64],
+        client_id: &str,
+    ) -> Result<u64, sqlx::Error> {
+        if command_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let result = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'pending', claimed_by = NULL, claimed_at = NULL, attempts = attempts - 1
+                     WHERE id IN (SELECT value FROM json_each(?)) AND claimed_by = ? AND status = 'processing'"
+                )
+                .bind(serde_json::json!(command_ids))
+                .bind(client_id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'pending', claimed_by = NULL, claimed_at = NULL, attempts = attempts - 1
+                     WHERE id = ANY($1) AND claimed_by = $2 AND status = 'processing'"
+                )
+                .bind(command_ids)
+                .bind(client_id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+        };
+
+        if result > 0 {
+            queue_signal().send_modify(|n| *n += 1);
+        }
+        Ok(result)
+    }
+
+    /// Dead-letter pending commands whose signature has expired; the client would reject them anyway
+    pub async fn expire_pending_commands(pool: &DbPool) -> Result<u64, sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                let result = sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'dead', processed_at = datetime('now'), error = 'Expired before delivery'
+                     WHERE status = 'pending' AND expires_at < datetime('now')"
+                )
+                .execute(p)
+                .await?;
+                Ok(result.rows_affected())
+            }
+            DbPool::Postgres(p) => {
+                let result = sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'dead', processed_at = NOW(), error = 'Expired before delivery'
+                     WHERE status = 'pending' AND expires_at < NOW()"
+                )
+                .execute(p)
+                .await?;
+                Ok(result.rows_affected())
+            }
+        }
+    }
+
+    /// Retry or dead-letter claimed commands that got no result within the visibility
+    /// timeout, e.g. because the PC crashed. A retry keeps its nonce, so a client that
+    /// did run the command rejects the second delivery instead of running it again.
+    pub async fn reclaim_stuck_commands(pool: &DbPool) -> Result<u64, sqlx::Error> {
+        let cutoff = chrono::Utc::now() - Self::visibility_timeout();
+        let stuck: Vec<(i64, i32, i32, Option<chrono::DateTime<chrono::Utc>>)> = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as(
+                    "SELECT id, attempts, max_attempts, expires_at FROM alice_command_queue
+                     WHERE status = 'processing' AND claimed_at < ?"
+                )
+                .bind(sqlite_time(cutoff))
+                .fetch_all(p)
+                .await?
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as(
+                    "SELECT id::INT8 AS id, attempts, max_attempts, expires_at FROM alice_command_queue
+                     WHERE status = 'processing' AND claimed_at < $1"
+                )
+                .bind(cutoff)
+                .fetch_all(p)
+                .await?
+            }
+        };
+
+        for (id, attempts, max_attempts, expires_at) in &stuck {
+            let error = format!("No result within the visibility timeout (attempt {} of {})", attempts, max_attempts);
+            if attempts >= max_attempts || expires_at.is_some_and(|e| e <= chrono::Utc::now()) {
+                Self::dead_letter(pool, *id, &error).await?;
+            } else {
+                Self::schedule_retry(pool, *id, *attempts, &error, None).await?;
+            }
+        }
+        Ok(stuck.len() as u64)
+    }
+
+    /// Handle a `Failed` result from `client_id`: queue the command again with backoff under
+    /// a fresh nonce while attempts and its expiry allow, otherwise dead-letter it
+    pub async fn retry_failed_command(
+        pool: &DbPool,
+        command_id: i64,
+        client_id: &str,
+        error: String,
+    ) -> Result<(), sqlx::Error> {
+        let row: Option<(serde_json::Value, i32, i32, Option<chrono::DateTime<chrono::Utc>>)> = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as(
+                    "SELECT command_data, attempts, max_attempts, expires_at FROM alice_command_queue WHERE id = ? AND claimed_by = ? AND status = 'processing'"
+                )
+                .bind(command_id)
+                .bind(client_id)
+                .fetch_optional(p)
+                .await?
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as(
+                    "SELECT command_data, attempts, max_attempts, expires_at FROM alice_command_queue WHERE id = $1 AND claimed_by = $2 AND status = 'processing'"
+                )
+                .bind(command_id)
+                .bind(client_id)
+                .fetch_optional(p)
+                .await?
+            }
+        };
+        let Some((command_data, attempts, max_attempts, expires_at)) = row else {
+            return Ok(());
+        };
+
+        let error = format!("{} (attempt {} of {})", error, attempts, max_attempts);
+        let retry_signing = match expires_at {
+            Some(expires_at) if attempts < max_attempts && expires_at > chrono::Utc::now() => {
+                serde_json::from_value::<PcCommandType>(command_data)
+                    .map_err(|e| e.to_string())
+                    .and_then(|command| Self::sign_command(&command, expires_at))
+            }
+            _ => return Self::dead_letter(pool, command_id, &error).await,
+        };
+
+        match retry_signing {
+            Ok(signing) => Self::schedule_retry(pool, command_id, attempts, &error, Some(signing)).await,
+            Err(e) => Self::dead_letter(pool, command_id, &format!("{}; cannot re-sign: {}", error, e)).await,
+        }
+    }
+
+    /// Return a processing command to the queue after its backoff, optionally re-signed
+    async fn schedule_retry(
+        pool: &DbPool,
+        command_id: i64,
+        attempts: i32,
+        error: &str,
+        signing: Option<(String, String, String)>,
+    ) -> Result<(), sqlx::Error> {
+        let available_at = chrono::Utc::now() + Self::retry_backoff(attempts);
+        let (nonce, payload, signature) = signing.map_or((None, None, None), |(n, p, s)| (Some(n), Some(p), Some(s)));
+
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'pending', claimed_by = NULL, claimed_at = NULL, available_at = ?, error = ?,
+                         nonce = COALESCE(?, nonce), payload = COALESCE(?, payload), signature = COALESCE(?, signature)
+                     WHERE id = ? AND status = 'processing'"
+                )
+                .bind(sqlite_time(available_at))
+                .bind(error)
+                .bind(nonce)
+                .bind(payload)
+                .bind(signature)
+                .bind(command_id)
+                .execute(p)
+                .await?;
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'pending', claimed_by = NULL, claimed_at = NULL, available_at = $1, error = $2,
+                         nonce = COALESCE($3, nonce), payload = COALESCE($4, payload), signature = COALESCE($5, signature)
+                     WHERE id = $6 AND status = 'processing'"
+                )
+                .bind(available_at)
+                .bind(error)
+                .bind(nonce)
+                .bind(payload)
+                .bind(signature)
+                .bind(command_id)
+                .execute(p)
+                .await?;
+            }
+        }
+
+        info!("Retrying command {} at {}: {}", command_id, available_at, error);
+        Ok(())
+    }
+
+    /// Move a processing command to the dead-letter state
+    async fn dead_letter(pool: &DbPool, command_id: i64, error: &str) -> Result<(), sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'dead', processed_at = datetime('now'), error = ? WHERE id = ? AND status = 'processing'"
+                )
+                .bind(error)
+                .bind(command_id)
+                .execute(p)
+                .await?;
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'dead', processed_at = NOW(), error = $1 WHERE id = $2 AND status = 'processing'"
+                )
+                .bind(error)
+                .bind(command_id)
+                .execute(p)
+                .await?;
+            }
+        }
+
+        warn!("Dead-lettered command {}: {}", command_id, error);
+        Ok(())
+    }
+
+    /// Attach a stored file to a command `client_id` is executing; false if the
+    /// command is not claimed by that client or already finished
+    pub async fn attach_artifact(
+        pool: &DbPool,
+        command_id: i64,
+        client_id: &str,
+        file_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET artifact_file_id = ?
+                     WHERE id = ? AND claimed_by = ? AND status = 'processing'"
+                )
+                .bind(file_id)
+                .bind(command_id)
+                .bind(client_id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET artifact_file_id = $1
+                     WHERE id = $2 AND claimed_by = $3 AND status = 'processing'"
+                )
+                .bind(file_id)
+                .bind(command_id)
+                .bind(client_id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+        };
+        Ok(result > 0)
+    }
+
+    /// Save an uploaded result file in the file manager (`ARTIFACT_FOLDER`) and
+    /// attach it to the command `client_id` is executing. SQLite only, like files.
+    pub async fn store_artifact(
+        pool: &DbPool,
+        command_id: i64,
+        client_id: &str,
+        name: &str,
+        data: &[u8],
+        mime_type: &str,
+    ) -> Result<StoredFile, String> {
+        let DbPool::Sqlite(p) = pool else {
+            return Err("File storage not available (PostgreSQL mode)".to_string());
+        };
+        let folder = FileService::ensure_root_folder(p, ARTIFACT_FOLDER).await?;
+        let file = FileService::upload_file(p, name, data, mime_type, Some(&folder.id), false, None).await?;
+
+        match Self::attach_artifact(pool, command_id, client_id, &file.id).await {
+            Ok(true) => Ok(file),
+            attached => {
+                FileService::delete_file(p, &file.id).await.ok();
+                Err(match attached {
+                    Err(e) => e.to_string(),
+                    _ => "Command is not being executed by this client".to_string(),
+                })
+            }
+        }
+    }
+
+    /// The command's artifact file and its contents, if it has one
+    pub async fn load_artifact(pool: &DbPool, command_id: i64) -> Result<Option<(StoredFile, Vec<u8>)>, String> {
+        let DbPool::Sqlite(p) = pool else {
+            return Err("File storage not available (PostgreSQL mode)".to_string());
+        };
+        let command = Self::get_command(pool, command_id).await.map_err(|e| e.to_string())?;
+        let Some(file_id) = command.and_then(|c| c.artifact_file_id) else {
+            return Ok(None);
+        };
+        let Some(file) = FileService::get_file(p, &file_id).await? else {
+            return Ok(None);
+        };
+        let data = FileService::get_file_data(&file.id).await?;
+        Ok(Some((file, data)))
+    }
+
+    /// Cancel a pending or processing command; a result reported afterwards is ignored
+    pub async fn cancel_command(pool: &DbPool, command_id: i64) -> Result<bool, sqlx::Error> {
+        let result = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'cancelled', processed_at = datetime('now')
+                     WHERE id = ? AND status IN ('pending', 'processing')"
+                )
+                .bind(command_id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'cancelled', processed_at = NOW()
+                     WHERE id = $1 AND status IN ('pending', 'processing')"
+                )
+                .bind(command_id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+        };
+        Ok(result > 0)
+    }
+
+    /// Expire and reclaim before handing out commands, and from the background task
+    pub async fn sweep_queue(pool: &DbPool) {
+        if let Err(e) = Self::expire_pending_commands(pool).await {
+            warn!("Failed to expire stale PC commands: {}", e);
+        }
+        if let Err(e) = Self::reclaim_stuck_commands(pool).await {
+            warn!("Failed to reclaim stuck PC commands: {}", e);
+        }
+    }
+
+    /// Mark a command claimed by `client_id` as completed
+    pub async fn mark_as_completed(
+        pool: &DbPool,
+        command_id: i64,
+        client_id: &str,
+        result: Option<String>,
+    ) -> Result<(), sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'completed', processed_at = datetime('now'), result = ? WHERE id = ? AND claimed_by = ? AND status = 'processing'"
+                )
+                .bind(result)
+                .bind(command_id)
+                .bind(client_id)
+                .execute(p)
+                .await?;
+                Ok(())
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'completed', processed_at = NOW(), result = $1 WHERE id = $2 AND claimed_by = $3 AND status = 'processing'"
+                )
+                .bind(result)
+                .bind(command_id)
+                .bind(client_id)
+                .execute(p)
+                .await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Mark a command claimed by `client_id` as failed for good, without retries
+    pub async fn mark_as_failed(
+        pool: &DbPool,
+        command_id: i64,
+        client_id: &str,
+        error: String,
     ) -> Result<(), sqlx::Error> {
         match pool {
-            DbPool::Sqlite(_) => Ok(()),
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE alice_command_queue SET status = 'failed', processed_at = datetime('now'), error = ? WHERE id = ? AND claimed_by = ? AND status = 'processing'"
+                )
+                .bind(error)
+                .bind(command_id)
+                .bind(client_id)
+                .execute(p)
+                .await?;
+                Ok(())
+            }
             DbPool::Postgres(p) => {
                 sqlx::query(
-                    "UPDATE alice_command_queue SET status = 'failed', processed_at = NOW(), error = $1 WHERE id = $2"
+                    "UPDATE alice_command_queue SET status = 'failed', processed_at = NOW(), error = $1 WHERE id = $2 AND claimed_by = $3 AND status = 'processing'"
                 )
                 .bind(error)
                 .bind(command_id)
+                .bind(client_id)
                 .execute(p)
                 .await?;
                 Ok(())
@@ -802,13 +1639,21 @@ impl CommandQueueService {
         }
     }
 
-    /// Clear old completed/failed commands (cleanup)
+    /// Delete finished commands (completed, failed, dead-lettered or cancelled) older than `hours`
     pub async fn cleanup_old_commands(pool: &DbPool, hours: i32) -> Result<u64, sqlx::Error> {
         match pool {
-            DbPool::Sqlite(_) => Ok(0),
+            DbPool::Sqlite(p) => {
+                let result = sqlx::query(
+                    "DELETE FROM alice_command_queue WHERE status IN ('completed', 'failed', 'dead', 'cancelled') AND processed_at < datetime('now', ?)"
+                )
+                .bind(format!("-{} hours", hours))
+                .execute(p)
+                .await?;
+                Ok(result.rows_affected())
+            }
             DbPool::Postgres(p) => {
                 let result = sqlx::query(
-                    "DELETE FROM alice_command_queue WHERE status IN ('completed', 'failed') AND processed_at < NOW() - $1::interval"
+                    "DELETE FROM alice_command_queue WHERE status IN ('completed', 'failed', 'dead', 'cancelled') AND processed_at < NOW() - $1::interval"
                 )
                 .bind(format!("{} hours", hours))
                 .execute(p)
@@ -823,29 +1668,67 @@ impl CommandQueueService {
         pool: &DbPool,
         client_name: &str,
         mac_address: Option<&str>,
+        group: Option<&str>,
     ) -> Result<(String, String), sqlx::Error> {
         let client_id = uuid::Uuid::new_v4().to_string();
         let api_key = format!("pc_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
 
         match pool {
-            DbPool::Sqlite(_) => {
-                Err(sqlx::Error::Protocol("PC client registration requires PostgreSQL".to_string()))
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "INSERT INTO alice_pc_clients (client_id, client_name, api_key, mac_address, client_group) VALUES (?, ?, ?, ?, ?)"
+                )
+                .bind(&client_id)
+                .bind(client_name)
+                .bind(&api_key)
+                .bind(mac_address)
+                .bind(group)
+                .execute(p)
+                .await?;
             }
             DbPool::Postgres(p) => {
                 sqlx::query(
-                    "INSERT INTO alice_pc_clients (client_id, client_name, api_key, mac_address) VALUES ($1, $2, $3, $4)"
+                    "INSERT INTO alice_pc_clients (client_id, client_name, api_key, mac_address, client_group) VALUES ($1, $2, $3, $4, $5)"
                 )
                 .bind(&client_id)
                 .bind(client_name)
                 .bind(&api_key)
                 .bind(mac_address)
+                .bind(group)
                 .execute(p)
                 .await?;
-
-                println!("🖥️ Registered new PC client: {} ({})", client_name, client_id);
-                Ok((client_id, api_key))
             }
         }
+
+        info!("Registered new PC client: {} ({})", client_name, client_id);
+        Ok((client_id, api_key))
+    }
+
+    /// Move a PC client into `group`, or out of any group with None
+    pub async fn set_client_group(
+        pool: &DbPool,
+        client_id: &str,
+        group: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("UPDATE alice_pc_clients SET client_group = ? WHERE client_id = ?")
+                    .bind(group)
+                    .bind(client_id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query("UPDATE alice_pc_clients SET client_group = $1 WHERE client_id = $2")
+                    .bind(group)
+                    .bind(client_id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+        };
+        Ok(result > 0)
     }
 
     /// Validate PC client API key
@@ -854,10 +1737,19 @@ impl CommandQueueService {
         api_key: &str,
     ) -> Result<Option<DbPcClient>, sqlx::Error> {
         match pool {
-            DbPool::Sqlite(_) => Ok(None),
+            DbPool::Sqlite(p) => {
+                let client = sqlx::query_as::<_, DbPcClient>(
+                    "SELECT id, client_id, client_name, api_key, is_online, last_seen, mac_address, ip_address, created_at, protocol_version, capabilities, client_group
+                     FROM alice_pc_clients WHERE api_key = ?"
+                )
+                .bind(api_key)
+                .fetch_optional(p)
+                .await?;
+                Ok(client)
+            }
             DbPool::Postgres(p) => {
                 let client = sqlx::query_as::<_, DbPcClient>(
-                    "SELECT id, client_id, client_name, api_key, is_online, last_seen, mac_address, ip_address, created_at
+                    "SELECT id::INT8 AS id, client_id, client_name, api_key, is_online, last_seen, mac_address, ip_address, created_at, protocol_version, capabilities, client_group
                      FROM alice_pc_clients WHERE api_key = $1"
                 )
                 .bind(api_key)
@@ -875,7 +1767,16 @@ impl CommandQueueService {
         ip_address: Option<&str>,
     ) -> Result<(), sqlx::Error> {
         match pool {
-            DbPool::Sqlite(_) => Ok(()),
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE alice_pc_clients SET is_online = TRUE, last_seen = datetime('now'), ip_address = COALESCE(?, ip_address) WHERE api_key = ?"
+                )
+                .bind(ip_address)
+                .bind(api_key)
+                .execute(p)
+                .await?;
+                Ok(())
+            }
             DbPool::Postgres(p) => {
                 sqlx::query(
                     "UPDATE alice_pc_clients SET is_online = TRUE, last_seen = NOW(), ip_address = COALESCE($1, ip_address) WHERE api_key = $2"
@@ -889,13 +1790,104 @@ impl CommandQueueService {
         }
     }
 
+    /// Store the protocol version and capabilities from a heartbeat handshake
+    pub async fn update_client_handshake(
+        pool: &DbPool,
+        api_key: &str,
+        handshake: &Handshake,
+    ) -> Result<(), sqlx::Error> {
+        let capabilities: Vec<&str> = handshake
+            .capabilities
+            .iter()
+            .filter(|c| **c != Capability::Unknown)
+            .map(|c| c.as_str())
+            .collect();
+
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE alice_pc_clients SET protocol_version = ?, capabilities = ? WHERE api_key = ?"
+                )
+                .bind(handshake.protocol_version as i32)
+                .bind(serde_json::json!(capabilities))
+                .bind(api_key)
+                .execute(p)
+                .await?;
+                Ok(())
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "UPDATE alice_pc_clients SET protocol_version = $1, capabilities = $2 WHERE api_key = $3"
+                )
+                .bind(handshake.protocol_version as i32)
+                .bind(serde_json::json!(capabilities))
+                .bind(api_key)
+                .execute(p)
+                .await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// PC clients that polled the queue within the last `within_secs` seconds
+    pub async fn count_online_clients(pool: &DbPool, within_secs: i64) -> Result<i64, sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                let (count,): (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM alice_pc_clients WHERE last_seen > datetime('now', ?)"
+                )
+                .bind(format!("-{} seconds", within_secs))
+                .fetch_one(p)
+                .await?;
+                Ok(count)
+            }
+            DbPool::Postgres(p) => {
+                let (count,): (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM alice_pc_clients WHERE last_seen > NOW() - make_interval(secs => $1)"
+                )
+                .bind(within_secs as f64)
+                .fetch_one(p)
+                .await?;
+                Ok(count)
+            }
+        }
+    }
+
+    /// ID and name of the PC clients seen within the last `within_secs` seconds
+    pub async fn online_clients(pool: &DbPool, within_secs: i64) -> Result<Vec<(String, String)>, sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as("SELECT client_id, client_name FROM alice_pc_clients WHERE last_seen > datetime('now', ?)")
+                    .bind(format!("-{} seconds", within_secs))
+                    .fetch_all(p)
+                    .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as(
+                    "SELECT client_id, client_name FROM alice_pc_clients WHERE last_seen > NOW() - make_interval(secs => $1)"
+                )
+                .bind(within_secs as f64)
+                .fetch_all(p)
+                .await
+            }
+        }
+    }
+
     /// Get all registered PC clients
     pub async fn get_clients(pool: &DbPool) -> Result<Vec<DbPcClient>, sqlx::Error> {
         match pool {
-            DbPool::Sqlite(_) => Ok(vec![]),
+            DbPool::Sqlite(p) => {
+                let clients = sqlx::query_as::<_, DbPcClient>(
+                    "SELECT id, client_id, client_name, api_key, is_online, last_seen, mac_address, ip_address, created_at, protocol_version, capabilities, client_group
+                     FROM alice_pc_clients ORDER BY created_at DESC"
+                )
+                .fetch_all(p)
+                .await?;
+                Ok(clients)
+            }
             DbPool::Postgres(p) => {
                 let clients = sqlx::query_as::<_, DbPcClient>(
-                    "SELECT id, client_id, client_name, api_key, is_online, last_seen, mac_address, ip_address, created_at
+                    "SELECT id::INT8 AS id, client_id, client_name, api_key, is_online, last_seen, mac_address, ip_address, created_at, protocol_version, capabilities, client_group
                      FROM alice_pc_clients ORDER BY created_at DESC"
                 )
                 .fetch_all(p)
@@ -907,16 +1899,38 @@ impl CommandQueueService {
 
     /// Delete a PC client
     pub async fn delete_client(pool: &DbPool, client_id: &str) -> Result<bool, sqlx::Error> {
-        match pool {
-            DbPool::Sqlite(_) => Ok(false),
+        let result = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("DELETE FROM alice_pc_clients WHERE client_id = ?")
+                    .bind(client_id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
             DbPool::Postgres(p) => {
-                let result = sqlx::query("DELETE FROM alice_pc_clients WHERE client_id = $1")
+                sqlx::query("DELETE FROM alice_pc_clients WHERE client_id = $1")
                     .bind(client_id)
                     .execute(p)
-                    .await?;
-                Ok(result.rows_affected() > 0)
+                    .await?
+                    .rows_affected()
             }
-        }
+        };
+        Ok(result > 0)
     }
 }
 
+/// Timestamp in the format of SQLite's `datetime('now')`, so the two compare as text
+fn sqlite_time(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn no_capable_client(capability: Capability, target: &CommandTarget) -> String {
+    let mut message = format!("No PC client supports {} (protocol v{})", capability, PROTOCOL_VERSION);
+    if let Some(client_id) = &target.client_id {
+        message.push_str(&format!(" with client_id {}", client_id));
+    }
+    if let Some(group) = &target.group {
+        message.push_str(&format!(" in group {}", group));
+    }
+    message
+}
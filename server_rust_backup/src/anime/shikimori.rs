@@ -1,3 +1,4 @@
+use crate::telemetry::RequestIdExt;
 use super::models::{ShikimoriAnime, ShikimoriAnimeDetails};
 use reqwest::Client;
 
@@ -26,6 +27,7 @@ impl ShikimoriClient {
                 ("order", "popularity"),
             ])
             .header("User-Agent", "BGalin Portfolio (bgalin.ru)")
+            .with_request_id()
             .send()
             .await
             .map_err(|e| format!("Request error: {}", e))?;
@@ -49,6 +51,7 @@ impl ShikimoriClient {
             .client
             .get(&url)
             .header("User-Agent", "BGalin Portfolio (bgalin.ru)")
+            .with_request_id()
             .send()
             .await
             .map_err(|e| format!("Request error: {}", e))?;
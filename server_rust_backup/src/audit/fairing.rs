@@ -0,0 +1,170 @@
+use crate::audit::models::*;
+use crate::audit::service::{redact, AuditService, AuditTarget};
+use crate::db::DbPool;
+use rocket::data::Data;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Method};
+use rocket::{Request, Response};
+use serde_json::Value;
+use std::io::Cursor;
+use tracing::error;
+
+/// Request body prefix kept for writes without a backing table; Rocket cannot peek further
+const REQUEST_PEEK_BYTES: usize = 512;
+
+/// State captured before the route runs
+#[derive(Default)]
+struct PendingAudit {
+    target: Option<AuditTarget>,
+    request_body: Option<Value>,
+    /// The JSON body was longer than the peek and could not be kept
+    request_truncated: bool,
+}
+
+/// Row touched by the request as it was before the handler ran
+struct BeforeSnapshot(Option<Value>);
+
+/// Remember who is acting for the audit log and, now that the request is authenticated,
+/// snapshot the row the write is about to change. Called by the auth guards.
+pub async fn record_actor(req: &Request<'_>, actor: AuditActor) {
+    req.local_cache(|| Some(actor));
+    req.local_cache_async(async {
+        let pending = req.local_cache(PendingAudit::default);
+        let before = match (req.rocket().state::<DbPool>(), &pending.target) {
+            (Some(pool), Some(AuditTarget { table: Some(table), target_id, .. }))
+                if req.method() != Method::Post || target_id.is_some() =>
+            {
+                AuditService::snapshot_target(pool, table, target_id.as_deref()).await
+            }
+            _ => None,
+        };
+        BeforeSnapshot(before)
+    })
+    .await;
+}
+
+/// Records every authenticated write under `/api/` into `audit_log`.
+///
+/// The row touched by the request is snapshotted once a guard has authenticated it
+/// (see [`record_actor`]) and again after the handler runs;
+/// writes without a known table fall back to the request or response JSON.
+pub struct AuditFairing;
+
+#[rocket::async_trait]
+impl Fairing for AuditFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Audit log",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, data: &mut Data<'_>) {
+        if matches!(req.method(), Method::Get | Method::Head | Method::Options) {
+            return;
+        }
+
+        let target = match AuditService::resolve_target(req.method().as_str(), req.uri().path().as_str()) {
+            Some(target) => target,
+            None => return,
+        };
+
+        // Bodies longer than the peek cannot be parsed; only note that they were cut off
+        let (request_body, request_truncated) = if req.content_type() == Some(&ContentType::JSON) {
+            let peek = data.peek(REQUEST_PEEK_BYTES).await;
+            match serde_json::from_slice::<Value>(peek) {
+                Ok(body) => (Some(redact(body)), false),
+                Err(_) => (None, peek.len() == REQUEST_PEEK_BYTES),
+            }
+        } else {
+            (None, false)
+        };
+
+        req.local_cache(|| PendingAudit {
+            target: Some(target),
+            request_body,
+            request_truncated,
+        });
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let pending = req.local_cache(PendingAudit::default);
+        let target = match &pending.target {
+            Some(target) => target.clone(),
+            None => return,
+        };
+
+        // Only authenticated writes are audited; guards store the actor
+        let actor = match req.local_cache(|| None::<AuditActor>) {
+            Some(actor) => actor.clone(),
+            None => return,
+        };
+
+        if res.status().code >= 400 {
+            return;
+        }
+
+        let pool = match req.rocket().state::<DbPool>() {
+            Some(pool) => pool,
+            None => return,
+        };
+
+        // Buffer the JSON response to read the created ID and the `success` flag
+        let mut response_json = None;
+        if res.content_type() == Some(ContentType::JSON) {
+            if let Ok(body) = res.body_mut().to_string().await {
+                response_json = serde_json::from_str::<Value>(&body).ok();
+                res.set_sized_body(body.len(), Cursor::new(body));
+            }
+        }
+
+        if let Some(json) = &response_json {
+            if json.get("success") == Some(&Value::Bool(false)) {
+                return;
+            }
+        }
+
+        let data = response_json
+            .as_ref()
+            .map(|json| json.get("data").unwrap_or(json).clone());
+
+        let target_id = target.target_id.clone().or_else(|| {
+            data.as_ref()
+                .and_then(|d| d.get("id").or_else(|| d.get("command_id")))
+                .map(|id| match id {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+        });
+
+        let after = if req.method() == Method::Delete {
+            None
+        } else {
+            let snapshot = match target.table {
+                Some(table) => AuditService::snapshot_target(pool, table, target_id.as_deref()).await,
+                None => None,
+            };
+            snapshot
+                .or_else(|| pending.request_body.clone())
+                .or_else(|| data.map(redact))
+                .or_else(|| pending.request_truncated.then(|| serde_json::json!({ "truncated": true })))
+        };
+
+        let entry = NewAuditEntry {
+            actor,
+            module: target.module,
+            action: target.action,
+            target_id,
+            method: req.method().as_str().to_string(),
+            path: req.uri().path().to_string(),
+            before: req.local_cache(|| BeforeSnapshot(None)).0.clone(),
+            after,
+            ip_address: req.client_ip().map(|ip| ip.to_string()),
+            status: res.status().code,
+        };
+
+        if let Err(e) = AuditService::record(pool, entry).await {
+            error!("Failed to write audit log: {}", e);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod fairing;
+pub mod models;
+pub mod service;
+
+pub use fairing::*;
+pub use models::*;
+pub use service::*;
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+/// Who performed an admin write; stored in the request-local cache by auth guards
+#[derive(Debug, Clone)]
+pub struct AuditActor {
+    /// "session:<id>", "token:<id>", "studio:<steam id>" or "t2:<employee id>"
+    pub label: String,
+    pub user_id: Option<i32>,
+}
+
+/// Audit log row
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor: String,
+    pub actor_user_id: Option<i32>,
+    pub module: String,
+    pub action: String,
+    pub target_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub before_data: Option<String>,
+    pub after_data: Option<String>,
+    pub diff: Option<String>,
+    pub ip_address: Option<String>,
+    pub status: i32,
+    pub created_at: String,
+}
+
+/// Audit entry as returned by the API, with JSON columns parsed
+#[derive(Debug, Serialize)]
+pub struct AuditEntryResponse {
+    pub id: i64,
+    pub actor: String,
+    pub actor_user_id: Option<i32>,
+    pub module: String,
+    pub action: String,
+    pub target_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub diff: Option<serde_json::Value>,
+    pub ip_address: Option<String>,
+    pub status: i32,
+    pub created_at: String,
+}
+
+impl From<AuditEntry> for AuditEntryResponse {
+    fn from(entry: AuditEntry) -> Self {
+        let parse = |s: Option<String>| s.and_then(|s| serde_json::from_str(&s).ok());
+
+        Self {
+            id: entry.id,
+            actor: entry.actor,
+            actor_user_id: entry.actor_user_id,
+            module: entry.module,
+            action: entry.action,
+            target_id: entry.target_id,
+            method: entry.method,
+            path: entry.path,
+            before: parse(entry.before_data),
+            after: parse(entry.after_data),
+            diff: parse(entry.diff),
+            ip_address: entry.ip_address,
+            status: entry.status,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+/// New audit record
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub actor: AuditActor,
+    pub module: String,
+    pub action: String,
+    pub target_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub ip_address: Option<String>,
+    pub status: u16,
+}
+
+/// Filters for the audit query endpoint
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditFilter {
+    pub module: Option<String>,
+    pub action: Option<String>,
+    pub actor: Option<String>,
+    pub target_id: Option<String>,
+    /// Inclusive lower bound, e.g. "2024-01-01" or "2024-01-01T10:00:00"
+    pub from: Option<String>,
+    /// Exclusive upper bound
+    pub to: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntryResponse>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
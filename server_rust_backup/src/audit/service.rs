@@ -0,0 +1,573 @@
+use crate::audit::models::*;
+use crate::db::DbPool;
+use serde_json::{Map, Value};
+use std::env;
+
+const DEFAULT_RETENTION_DAYS: i64 = 180;
+
+/// Keys whose values are never written to the audit log
+const REDACTED_KEYS: &[&str] = &["token", "api_key", "password", "secret", "access_code", "otp"];
+
+/// Path prefix (relative to `/api/`) → audited module, backing table and fixed action.
+/// More specific prefixes come first; the first ID-like segment after the prefix is the target.
+const AUDIT_TARGETS: &[(&str, &str, Option<&str>, Option<&str>)] = &[
+    ("portfolio/about", "portfolio", Some("portfolio_about"), None),
+    ("portfolio/experience", "portfolio", Some("portfolio_experience"), None),
+    ("portfolio/skills", "portfolio", Some("portfolio_skills"), None),
+    ("portfolio/contacts", "portfolio", Some("portfolio_contacts"), None),
+    ("portfolio/cases", "portfolio", Some("portfolio_cases"), None),
+    ("links", "links", Some("short_links"), None),
+    ("files/folders", "files", Some("file_folders"), None),
+    ("files/upload", "files", Some("stored_files"), Some("upload")),
+    ("files", "files", Some("stored_files"), None),
+    ("sync/folders", "sync", Some("sync_folders"), None),
+    ("admin/menu-settings", "menu", Some("menu_settings"), None),
+    ("t2/admin/stores", "t2", Some("t2_stores"), None),
+    ("t2/admin/employees", "t2", Some("t2_employees"), None),
+    ("t2/tags", "t2", Some("t2_tags"), None),
+    ("t2/products", "t2", Some("t2_products"), None),
+    ("t2/tariffs", "t2", Some("t2_tariffs"), None),
+    ("t2/services", "t2", Some("t2_services"), None),
+    ("t2/sales", "t2", Some("t2_sales"), None),
+    ("alice/admin/registry", "alice", Some("alice_devices"), None),
+    ("alice/admin/rules", "alice", Some("alice_rules"), None),
+    ("alice/pc/queue/schedules", "alice", Some("alice_pc_schedules"), None),
+    ("alice/pc/queue", "alice", Some("alice_command_queue"), Some("queue")),
+    ("console/execute", "console", None, Some("execute")),
+];
+
+/// Keystrokes and terminal resizes of console jobs are not worth an audit row
+const UNAUDITED_VERBS: &[&str] = &["input", "resize"];
+
+/// Settings-like tables without row IDs in their routes; snapshotted whole
+const WHOLE_TABLE_SNAPSHOTS: &[&str] = &["menu_settings", "portfolio_about"];
+
+/// Where an admin write lands: module, table, target row and action
+#[derive(Debug, Clone)]
+pub struct AuditTarget {
+    pub module: String,
+    pub table: Option<&'static str>,
+    pub target_id: Option<String>,
+    pub action: String,
+}
+
+pub struct AuditService;
+
+impl AuditService {
+    /// Retention period for audit records
+    pub fn retention_days() -> i64 {
+        env::var("AUDIT_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RETENTION_DAYS)
+    }
+
+    /// Resolve the audited module/table/target for a write request path
+    pub fn resolve_target(method: &str, path: &str) -> Option<AuditTarget> {
+        let rest = path.strip_prefix("/api/")?.trim_end_matches('/');
+        let default_action = match method {
+            "POST" => "create",
+            "PUT" | "PATCH" => "update",
+            "DELETE" => "delete",
+            _ => return None,
+        };
+
+        let matched = AUDIT_TARGETS.iter().find(|(prefix, ..)| {
+            rest == *prefix || rest.starts_with(&format!("{}/", prefix))
+        });
+
+        let (module, table, prefix_len, fixed_action) = match matched {
+            Some((prefix, module, table, action)) => (module.to_string(), *table, prefix.len(), *action),
+            None => {
+                let module = rest.split('/').next().filter(|s| !s.is_empty())?;
+                (module.to_string(), None, module.len(), None)
+            }
+        };
+
+        // Remaining segments, e.g. "<id>" or "<id>/<verb>"; a verb after the ID names the action
+        let remaining: Vec<&str> = rest[prefix_len..]
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let id_pos = remaining.iter().position(|s| looks_like_id(s));
+        let target_id = id_pos.map(|i| remaining[i].to_string());
+        let verb = id_pos.and_then(|i| {
+            remaining[i + 1..]
+                .iter()
+                .rev()
+                .find(|s| !looks_like_id(s))
+                .map(|s| s.to_string())
+        });
+
+        if verb.as_deref().is_some_and(|v| UNAUDITED_VERBS.contains(&v)) {
+            return None;
+        }
+
+        let action = verb
+            .or(fixed_action.map(|a| a.to_string()))
+            .unwrap_or_else(|| default_action.to_string());
+
+        Some(AuditTarget {
+            module,
+            table,
+            target_id,
+            action,
+        })
+    }
+
+    /// Snapshot the target row, or the whole table for settings-like tables
+    pub async fn snapshot_target(
+        pool: &DbPool,
+        table: &'static str,
+        target_id: Option<&str>,
+    ) -> Option<Value> {
+        match target_id {
+            Some(id) => Self::snapshot(pool, table, Some(id)).await,
+            None if WHOLE_TABLE_SNAPSHOTS.contains(&table) => Self::snapshot(pool, table, None).await,
+            None => None,
+        }
+    }
+
+    /// Current state of a row (or of the whole table when `target_id` is None) as JSON
+    pub async fn snapshot(
+        pool: &DbPool,
+        table: &'static str,
+        target_id: Option<&str>,
+    ) -> Option<Value> {
+        let json: Option<String> = match pool {
+            DbPool::Sqlite(p) => {
+                let columns: Vec<(String,)> = sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+                    .fetch_all(p)
+                    .await
+                    .ok()?;
+
+                if columns.is_empty() {
+                    return None;
+                }
+
+                let object = format!(
+                    "json_object({})",
+                    columns
+                        .iter()
+                        .map(|(c,)| format!("'{}', \"{}\"", c, c))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+
+                match target_id {
+                    Some(id) => sqlx::query_scalar(&format!("SELECT {} FROM {} WHERE id = ?", object, table))
+                        .bind(id)
+                        .fetch_optional(p)
+                        .await
+                        .ok()?,
+                    None => sqlx::query_scalar(&format!("SELECT json_group_array({}) FROM {}", object, table))
+                        .fetch_optional(p)
+                        .await
+                        .ok()?,
+                }
+            }
+            DbPool::Postgres(p) => match target_id {
+                Some(id) => sqlx::query_scalar(&format!("SELECT row_to_json(t)::TEXT FROM {} t WHERE t.id::TEXT = $1", table))
+                    .bind(id)
+                    .fetch_optional(p)
+                    .await
+                    .ok()?,
+                None => sqlx::query_scalar(&format!("SELECT COALESCE(json_agg(t), '[]')::TEXT FROM {} t", table))
+                    .fetch_optional(p)
+                    .await
+                    .ok()?,
+            },
+        };
+
+        json.and_then(|s| serde_json::from_str(&s).ok()).map(redact)
+    }
+
+    /// Write an audit record
+    pub async fn record(pool: &DbPool, entry: NewAuditEntry) -> Result<(), sqlx::Error> {
+        let diff = match (&entry.before, &entry.after) {
+            (Some(before), Some(after)) => Some(json_diff(before, after)),
+            _ => None,
+        };
+
+        let to_text = |v: &Option<Value>| v.as_ref().map(|v| v.to_string());
+
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "INSERT INTO audit_log (actor, actor_user_id, module, action, target_id, method, path, before_data, after_data, diff, ip_address, status, created_at)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))",
+                )
+                .bind(&entry.actor.label)
+                .bind(entry.actor.user_id)
+                .bind(&entry.module)
+                .bind(&entry.action)
+                .bind(&entry.target_id)
+                .bind(&entry.method)
+                .bind(&entry.path)
+                .bind(to_text(&entry.before))
+                .bind(to_text(&entry.after))
+                .bind(to_text(&diff))
+                .bind(&entry.ip_address)
+                .bind(entry.status as i32)
+                .execute(p)
+                .await?;
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "INSERT INTO audit_log (actor, actor_user_id, module, action, target_id, method, path, before_data, after_data, diff, ip_address, status, created_at)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, NOW())",
+                )
+                .bind(&entry.actor.label)
+                .bind(entry.actor.user_id)
+                .bind(&entry.module)
+                .bind(&entry.action)
+                .bind(&entry.target_id)
+                .bind(&entry.method)
+                .bind(&entry.path)
+                .bind(to_text(&entry.before))
+                .bind(to_text(&entry.after))
+                .bind(to_text(&diff))
+                .bind(&entry.ip_address)
+                .bind(entry.status as i32)
+                .execute(p)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Query audit records, newest first
+    pub async fn query(pool: &DbPool, filter: &AuditFilter) -> Result<AuditPage, sqlx::Error> {
+        let limit = filter.limit.unwrap_or(50).clamp(1, 500);
+        let offset = filter.offset.unwrap_or(0).max(0);
+
+        // (column condition, value) pairs; all values are bound as text
+        let normalize_time = |t: &String| t.replace('T', " ");
+        let conditions: Vec<(&str, String)> = [
+            ("module = {}", filter.module.clone()),
+            ("action = {}", filter.action.clone()),
+            ("actor = {}", filter.actor.clone()),
+            ("target_id = {}", filter.target_id.clone()),
+            ("created_at >= {}", filter.from.as_ref().map(normalize_time)),
+            ("created_at < {}", filter.to.as_ref().map(normalize_time)),
+        ]
+        .into_iter()
+        .filter_map(|(cond, value)| value.map(|v| (cond, v)))
+        .collect();
+
+        let build_where = |placeholder: &dyn Fn(usize) -> String| -> String {
+            if conditions.is_empty() {
+                return String::new();
+            }
+            let clauses: Vec<String> = conditions
+                .iter()
+                .enumerate()
+                .map(|(i, (cond, _))| cond.replace("{}", &placeholder(i + 1)))
+                .collect();
+            format!("WHERE {}", clauses.join(" AND "))
+        };
+
+        let (entries, total) = match pool {
+            DbPool::Sqlite(p) => {
+                let where_sql = build_where(&|_| "?".to_string());
+
+                let count_sql = format!("SELECT COUNT(*) FROM audit_log {}", where_sql);
+                let mut count = sqlx::query_as::<_, (i64,)>(&count_sql);
+                for (_, value) in &conditions {
+                    count = count.bind(value);
+                }
+                let total = count.fetch_one(p).await?.0;
+
+                let sql = format!(
+                    "SELECT id, actor, actor_user_id, module, action, target_id, method, path, before_data, after_data, diff, ip_address, status, created_at
+                     FROM audit_log {} ORDER BY id DESC LIMIT ? OFFSET ?",
+                    where_sql
+                );
+                let mut query = sqlx::query_as::<_, AuditEntry>(&sql);
+                for (_, value) in &conditions {
+                    query = query.bind(value);
+                }
+                let entries = query.bind(limit).bind(offset).fetch_all(p).await?;
+
+                (entries, total)
+            }
+            DbPool::Postgres(p) => {
+                let where_sql = build_where(&|n| {
+                    // Time bounds compare as timestamps, everything else as text
+                    let cond = &conditions[n - 1].0;
+                    if cond.starts_with("created_at") {
+                        format!("${}::timestamptz", n)
+                    } else {
+                        format!("${}", n)
+                    }
+                });
+
+                let count_sql = format!("SELECT COUNT(*) FROM audit_log {}", where_sql);
+                let mut count = sqlx::query_as::<_, (i64,)>(&count_sql);
+                for (_, value) in &conditions {
+                    count = count.bind(value);
+                }
+                let total = count.fetch_one(p).await?.0;
+
+                let n = conditions.len();
+                let sql = format!(
+                    "SELECT id, actor, actor_user_id, module, action, target_id, method, path, before_data, after_data, diff, ip_address, status, created_at::TEXT
+                     FROM audit_log {} ORDER BY id DESC LIMIT ${} OFFSET ${}",
+                    where_sql,
+                    n + 1,
+                    n + 2
+                );
+                let mut query = sqlx::query_as::<_, AuditEntry>(&sql);
+                for (_, value) in &conditions {
+                    query = query.bind(value);
+                }
+                let entries = query.bind(limit).bind(offset).fetch_all(p).await?;
+
+                (entries, total)
+            }
+        };
+
+        Ok(AuditPage {
+            entries: entries.into_iter().map(Into::into).collect(),
+            total,
+            limit,
+            offset,
+        })
+    }
+
+    /// Delete records older than the retention period
+    pub async fn purge_expired(pool: &DbPool, days: i64) -> Result<u64, sqlx::Error> {
+        let removed = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("DELETE FROM audit_log WHERE created_at < datetime('now', ?)")
+                    .bind(format!("-{} days", days))
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query("DELETE FROM audit_log WHERE created_at < NOW() - $1::interval")
+                    .bind(format!("{} days", days))
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        Ok(removed)
+    }
+}
+
+/// Numeric IDs and UUID-like strings; anything else is treated as a verb
+fn looks_like_id(segment: &str) -> bool {
+    segment.parse::<i64>().is_ok()
+        || (segment.len() >= 16 && segment.chars().all(|c| c.is_ascii_hexdigit() || c == '-'))
+}
+
+/// Replace secret values (tokens, passwords, keys) with a placeholder
+pub fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let key = k.to_lowercase();
+                    let secret = REDACTED_KEYS
+                        .iter()
+                        .any(|r| key == *r || key.ends_with(&format!("_{}", r)) || key.ends_with("_hash"));
+                    if secret && !v.is_null() {
+                        (k, Value::String("[redacted]".to_string()))
+                    } else {
+                        (k, redact(v))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        other => other,
+    }
+}
+
+/// Field-level diff: `{ "path.to.field": { "before": .., "after": .. } }` for changed leaves
+pub fn json_diff(before: &Value, after: &Value) -> Value {
+    let mut left = Map::new();
+    let mut right = Map::new();
+    flatten("", before, &mut left);
+    flatten("", after, &mut right);
+
+    let mut diff = Map::new();
+    for (key, old) in &left {
+        match right.get(key) {
+            Some(new) if new == old => {}
+            new => {
+                diff.insert(key.clone(), serde_json::json!({ "before": old, "after": new }));
+            }
+        }
+    }
+    for (key, new) in &right {
+        if !left.contains_key(key) {
+            diff.insert(key.clone(), serde_json::json!({ "before": Value::Null, "after": new }));
+        }
+    }
+
+    Value::Object(diff)
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut Map<String, Value>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (k, v) in map {
+                flatten(&join(k), v, out);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, v) in items.iter().enumerate() {
+                flatten(&join(&i.to_string()), v, out);
+            }
+        }
+        other => {
+            out.insert(prefix.to_string(), other.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn resolve(method: &str, path: &str) -> (String, Option<&'static str>, Option<String>, String) {
+        let target = AuditService::resolve_target(method, path)
+            .unwrap_or_else(|| panic!("{} {} not audited", method, path));
+        (target.module, target.table, target.target_id, target.action)
+    }
+
+    #[test]
+    fn resolves_ids_in_templated_paths() {
+        assert_eq!(
+            resolve("PUT", "/api/portfolio/cases/42"),
+            ("portfolio".into(), Some("portfolio_cases"), Some("42".into()), "update".into())
+        );
+        assert_eq!(
+            resolve("DELETE", "/api/t2/admin/employees/7/"),
+            ("t2".into(), Some("t2_employees"), Some("7".into()), "delete".into())
+        );
+        // The more specific prefix wins over "files"
+        assert_eq!(
+            resolve("POST", "/api/files/folders"),
+            ("files".into(), Some("file_folders"), None, "create".into())
+        );
+        assert_eq!(
+            resolve("POST", "/api/files/upload"),
+            ("files".into(), Some("stored_files"), None, "upload".into())
+        );
+        let uuid = "3f2b8c1e-9a4d-4e6f-8b7a-1c2d3e4f5a6b";
+        assert_eq!(
+            resolve("DELETE", &format!("/api/files/{}", uuid)),
+            ("files".into(), Some("stored_files"), Some(uuid.into()), "delete".into())
+        );
+        // A verb after the ID names the action, also over a fixed one
+        assert_eq!(
+            resolve("POST", "/api/alice/pc/queue/15/cancel"),
+            ("alice".into(), Some("alice_command_queue"), Some("15".into()), "cancel".into())
+        );
+        assert_eq!(
+            resolve("POST", "/api/alice/pc/queue"),
+            ("alice".into(), Some("alice_command_queue"), None, "queue".into())
+        );
+        assert_eq!(
+            resolve("PATCH", "/api/alice/pc/queue/schedules/3"),
+            ("alice".into(), Some("alice_pc_schedules"), Some("3".into()), "update".into())
+        );
+        assert_eq!(
+            resolve("PUT", "/api/admin/menu-settings"),
+            ("menu".into(), Some("menu_settings"), None, "update".into())
+        );
+    }
+
+    #[test]
+    fn unmatched_paths_fall_back_to_the_first_segment() {
+        assert_eq!(resolve("POST", "/api/widgets/5"), ("widgets".into(), None, Some("5".into()), "create".into()));
+        // A prefix only matches whole segments
+        assert_eq!(resolve("POST", "/api/linksx"), ("linksx".into(), None, None, "create".into()));
+    }
+
+    #[test]
+    fn unaudited_requests_resolve_to_none() {
+        for (method, path) in [
+            ("GET", "/api/links/1"),
+            ("HEAD", "/api/links"),
+            ("POST", "/links/1"),
+            ("POST", "/api/"),
+            ("POST", "/api"),
+            ("POST", "/health"),
+            ("POST", "/api/console/jobs/12/input"),
+            ("POST", "/api/console/jobs/12/resize"),
+        ] {
+            assert!(AuditService::resolve_target(method, path).is_none(), "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn diffs_nested_added_and_removed_keys() {
+        let before = json!({
+            "title": "Old",
+            "meta": {"tags": ["a", "b"], "seo": {"description": "same"}},
+            "removed": 1,
+            "unchanged": true,
+        });
+        let after = json!({
+            "title": "New",
+            "meta": {"tags": ["a", "c", "d"], "seo": {"description": "same"}},
+            "added": {"nested": null},
+            "unchanged": true,
+        });
+        assert_eq!(
+            json_diff(&before, &after),
+            json!({
+                "title": {"before": "Old", "after": "New"},
+                "meta.tags.1": {"before": "b", "after": "c"},
+                "meta.tags.2": {"before": null, "after": "d"},
+                "removed": {"before": 1, "after": null},
+                "added.nested": {"before": null, "after": null},
+            })
+        );
+    }
+
+    #[test]
+    fn diff_of_equal_values_is_empty() {
+        let value = json!({"a": {"b": [1, {"c": 2}]}, "d": {}});
+        assert_eq!(json_diff(&value, &value), json!({}));
+        // An emptied object is a leaf change rather than no change
+        assert_eq!(
+            json_diff(&json!({"a": {"b": 1}}), &json!({"a": {}})),
+            json!({"a.b": {"before": 1, "after": null}, "a": {"before": null, "after": {}}})
+        );
+    }
+
+    #[test]
+    fn redacts_secrets_at_any_depth() {
+        let value = redact(json!({
+            "name": "x",
+            "api_key": "k",
+            "user": {"password_hash": "h", "refresh_token": null},
+        }));
+        assert_eq!(
+            value,
+            json!({
+                "name": "x",
+                "api_key": "[redacted]",
+                "user": {"password_hash": "[redacted]", "refresh_token": null},
+            })
+        );
+    }
+}
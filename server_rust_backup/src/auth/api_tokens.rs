@@ -0,0 +1,490 @@
+use crate::auth::AuthService;
+use crate::db::DbPool;
+use crate::models::{ApiToken, User};
+use std::net::IpAddr;
+
+/// Prefix that distinguishes personal access tokens from session tokens
+pub const API_TOKEN_PREFIX: &str = "pat_";
+
+/// Modules a personal token can never be scoped for (session and token management)
+const RESTRICTED_MODULES: &[&str] = &["auth"];
+
+/// Personal access tokens for scripts and n8n flows
+pub struct ApiTokenService;
+
+impl ApiTokenService {
+    /// Whether a bearer token is a personal access token
+    pub fn is_api_token(token: &str) -> bool {
+        token.starts_with(API_TOKEN_PREFIX)
+    }
+
+    /// Check a scope string: `module:action`, either part may be `*`
+    pub fn validate_scope(scope: &str) -> Result<(), String> {
+        let (module, action) = scope
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid scope '{}': expected module:action", scope))?;
+
+        let valid_part = |part: &str| {
+            part == "*"
+                || (!part.is_empty()
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-'))
+        };
+
+        if !valid_part(module) || !valid_part(action) {
+            return Err(format!("Invalid scope '{}'", scope));
+        }
+
+        if RESTRICTED_MODULES.contains(&module) {
+            return Err(format!("Scope '{}' is not allowed for API tokens", scope));
+        }
+
+        Ok(())
+    }
+
+    /// Check an IP allowlist entry: a single address or a CIDR range
+    pub fn validate_ip_entry(entry: &str) -> Result<(), String> {
+        let (addr, prefix) = match entry.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (entry, None),
+        };
+
+        let ip: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("Invalid IP address '{}'", entry))?;
+
+        if let Some(prefix) = prefix {
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            match prefix.parse::<u8>() {
+                Ok(bits) if bits <= max => {}
+                _ => return Err(format!("Invalid CIDR prefix '{}'", entry)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scope required for a request: `module:read` for GET/HEAD, `module:write` otherwise.
+    /// The module is the first path segment after `/api/`.
+    pub fn required_scope(method: &str, path: &str) -> Option<String> {
+        // Prometheus scrapes the root-level endpoint
+        if path == "/metrics" {
+            return Some("metrics:read".to_string());
+        }
+
+        let rest = path.strip_prefix("/api/")?;
+        let mut segments = rest.split('/');
+        let module = segments.next().filter(|s| !s.is_empty())?;
+
+        // Dedicated scopes for actions that deserve separate grants
+        if module == "alice" && rest.starts_with("alice/pc/queue") {
+            return Some("alice:queue".to_string());
+        }
+
+        let action = match method {
+            "GET" | "HEAD" => "read",
+            _ => "write",
+        };
+
+        Some(format!("{}:{}", module, action))
+    }
+
+    /// Whether the granted scopes cover the required one (`write` implies `read`)
+    pub fn scopes_allow(granted: &[String], required: &str) -> bool {
+        let (req_module, req_action) = match required.split_once(':') {
+            Some(parts) => parts,
+            None => return false,
+        };
+
+        if RESTRICTED_MODULES.contains(&req_module) {
+            return false;
+        }
+
+        granted.iter().any(|scope| {
+            let (module, action) = match scope.split_once(':') {
+                Some(parts) => parts,
+                None => return scope == "*",
+            };
+
+            let module_ok = module == "*" || module == req_module;
+            let action_ok = action == "*"
+                || action == req_action
+                || (action == "write" && req_action == "read");
+
+            module_ok && action_ok
+        })
+    }
+
+    /// Whether the client IP is allowed by the token's allowlist (empty list allows all)
+    pub fn ip_allowed(allowlist: &[String], client_ip: Option<IpAddr>) -> bool {
+        if allowlist.is_empty() {
+            return true;
+        }
+
+        let ip = match client_ip {
+            Some(ip) => ip,
+            None => return false,
+        };
+
+        allowlist.iter().any(|entry| ip_matches(ip, entry))
+    }
+
+    /// Create a token; returns the raw token (shown once) and its record
+    pub async fn create_token(
+        pool: &DbPool,
+        user_id: i32,
+        name: &str,
+        scopes: &[String],
+        allowed_ips: &[String],
+        expires_in_days: Option<i64>,
+    ) -> Result<(String, ApiToken), sqlx::Error> {
+        let token = format!("{}{}", API_TOKEN_PREFIX, AuthService::generate_token());
+        let token_hash = AuthService::hash_token(&token);
+        let token_prefix: String = token.chars().take(12).collect();
+        let scopes_json = serde_json::to_string(scopes).unwrap_or_else(|_| "[]".to_string());
+        let allowed_ips_json = if allowed_ips.is_empty() {
+            None
+        } else {
+            serde_json::to_string(allowed_ips).ok()
+        };
+
+        let record = match pool {
+            DbPool::Sqlite(p) => {
+                let expires = expires_in_days.map(|d| format!("+{} days", d));
+
+                sqlx::query_as::<_, ApiToken>(
+                    "INSERT INTO api_tokens (user_id, name, token_hash, token_prefix, scopes, allowed_ips, expires_at, created_at)
+                     VALUES (?, ?, ?, ?, ?, ?, CASE WHEN ? IS NULL THEN NULL ELSE datetime('now', ?) END, datetime('now'))
+                     RETURNING id, user_id, name, token_prefix, scopes, allowed_ips, expires_at, last_used_at, last_used_ip, created_at",
+                )
+                .bind(user_id)
+                .bind(name)
+                .bind(&token_hash)
+                .bind(&token_prefix)
+                .bind(&scopes_json)
+                .bind(&allowed_ips_json)
+                .bind(&expires)
+                .bind(&expires)
+                .fetch_one(p)
+                .await?
+            }
+            DbPool::Postgres(p) => {
+                let expires = expires_in_days.map(|d| format!("{} days", d));
+
+                sqlx::query_as::<_, ApiToken>(
+                    "INSERT INTO api_tokens (user_id, name, token_hash, token_prefix, scopes, allowed_ips, expires_at, created_at)
+                     VALUES ($1, $2, $3, $4, $5, $6, NOW() + $7::interval, NOW())
+                     RETURNING id, user_id, name, token_prefix, scopes, allowed_ips, expires_at::TEXT, last_used_at::TEXT, last_used_ip, created_at::TEXT",
+                )
+                .bind(user_id)
+                .bind(name)
+                .bind(&token_hash)
+                .bind(&token_prefix)
+                .bind(&scopes_json)
+                .bind(&allowed_ips_json)
+                .bind(&expires)
+                .fetch_one(p)
+                .await?
+            }
+        };
+
+        Ok((token, record))
+    }
+
+    /// List tokens that are not revoked
+    pub async fn list_tokens(pool: &DbPool, user_id: i32) -> Result<Vec<ApiToken>, sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, ApiToken>(
+                    "SELECT id, user_id, name, token_prefix, scopes, allowed_ips, expires_at, last_used_at, last_used_ip, created_at
+                     FROM api_tokens WHERE user_id = ? AND revoked_at IS NULL ORDER BY created_at DESC",
+                )
+                .bind(user_id)
+                .fetch_all(p)
+                .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, ApiToken>(
+                    "SELECT id, user_id, name, token_prefix, scopes, allowed_ips, expires_at::TEXT, last_used_at::TEXT, last_used_ip, created_at::TEXT
+                     FROM api_tokens WHERE user_id = $1 AND revoked_at IS NULL ORDER BY created_at DESC",
+                )
+                .bind(user_id)
+                .fetch_all(p)
+                .await
+            }
+        }
+    }
+
+    /// Revoke a token; returns false if it doesn't exist or belongs to another user
+    pub async fn revoke_token(pool: &DbPool, user_id: i32, token_id: i32) -> Result<bool, sqlx::Error> {
+        let affected = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("UPDATE api_tokens SET revoked_at = datetime('now') WHERE id = ? AND user_id = ? AND revoked_at IS NULL")
+                    .bind(token_id)
+                    .bind(user_id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query("UPDATE api_tokens SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL")
+                    .bind(token_id)
+                    .bind(user_id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        Ok(affected > 0)
+    }
+
+    /// Validate a personal token (hash, revocation, expiry, IP allowlist) and record its use
+    pub async fn validate_token(
+        pool: &DbPool,
+        token: &str,
+        client_ip: Option<IpAddr>,
+    ) -> Result<Option<(User, ApiToken)>, sqlx::Error> {
+        let token_hash = AuthService::hash_token(token);
+        let ip_str = client_ip.map(|ip| ip.to_string());
+
+        match pool {
+            DbPool::Sqlite(p) => {
+                let record = sqlx::query_as::<_, ApiToken>(
+                    "SELECT id, user_id, name, token_prefix, scopes, allowed_ips, expires_at, last_used_at, last_used_ip, created_at
+                     FROM api_tokens
+                     WHERE token_hash = ? AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > datetime('now'))",
+                )
+                .bind(&token_hash)
+                .fetch_optional(p)
+                .await?;
+
+                let record = match record {
+                    Some(r) if Self::ip_allowed(&r.allowed_ip_list(), client_ip) => r,
+                    _ => return Ok(None),
+                };
+
+                // Record usage at most once a minute (or when the IP changes)
+                sqlx::query(
+                    "UPDATE api_tokens SET last_used_at = datetime('now'), last_used_ip = ?
+                     WHERE id = ? AND (last_used_at IS NULL OR last_used_at < datetime('now', '-1 minute') OR last_used_ip IS NOT ?)",
+                )
+                .bind(&ip_str)
+                .bind(record.id)
+                .bind(&ip_str)
+                .execute(p)
+                .await?;
+
+                let user = sqlx::query_as::<_, User>(
+                    "SELECT id, telegram_id, username, created_at FROM users WHERE id = ?",
+                )
+                .bind(record.user_id)
+                .fetch_one(p)
+                .await?;
+
+                Ok(Some((user, record)))
+            }
+            DbPool::Postgres(p) => {
+                let record = sqlx::query_as::<_, ApiToken>(
+                    "SELECT id, user_id, name, token_prefix, scopes, allowed_ips, expires_at::TEXT, last_used_at::TEXT, last_used_ip, created_at::TEXT
+                     FROM api_tokens
+                     WHERE token_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())",
+                )
+                .bind(&token_hash)
+                .fetch_optional(p)
+                .await?;
+
+                let record = match record {
+                    Some(r) if Self::ip_allowed(&r.allowed_ip_list(), client_ip) => r,
+                    _ => return Ok(None),
+                };
+
+                // Record usage at most once a minute (or when the IP changes)
+                sqlx::query(
+                    "UPDATE api_tokens SET last_used_at = NOW(), last_used_ip = $1
+                     WHERE id = $2 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute' OR last_used_ip IS DISTINCT FROM $1)",
+                )
+                .bind(&ip_str)
+                .bind(record.id)
+                .execute(p)
+                .await?;
+
+                let user = sqlx::query_as::<_, User>(
+                    "SELECT id, telegram_id, username, created_at::TEXT FROM users WHERE id = $1",
+                )
+                .bind(record.user_id)
+                .fetch_one(p)
+                .await?;
+
+                Ok(Some((user, record)))
+            }
+        }
+    }
+}
+
+/// Match an IP against a single address or CIDR range
+fn ip_matches(ip: IpAddr, entry: &str) -> bool {
+    let (addr, prefix) = match entry.split_once('/') {
+        Some((addr, prefix)) => match prefix.trim().parse::<u32>() {
+            Ok(bits) => (addr, Some(bits)),
+            // A broken prefix must not degrade into a single-address match
+            Err(_) => return false,
+        },
+        None => (entry, None),
+    };
+
+    let net: IpAddr = match addr.trim().parse() {
+        Ok(net) => net,
+        Err(_) => return false,
+    };
+
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let bits = prefix.unwrap_or(32);
+            if bits > 32 {
+                return false;
+            }
+            let mask = if bits == 0 { 0 } else { u32::MAX << (32 - bits) };
+            (u32::from(ip) & mask) == (u32::from(net) & mask)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let bits = prefix.unwrap_or(128);
+            if bits > 128 {
+                return false;
+            }
+            let mask = if bits == 0 { 0 } else { u128::MAX << (128 - bits) };
+            (u128::from(ip) & mask) == (u128::from(net) & mask)
+        }
+        // IPv4 clients seen through an IPv6 socket
+        (IpAddr::V6(ip), IpAddr::V4(_)) => match ip.to_ipv4_mapped() {
+            Some(v4) => ip_matches(IpAddr::V4(v4), entry),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allows(granted: &[&str], required: &str) -> bool {
+        let granted: Vec<String> = granted.iter().map(|s| s.to_string()).collect();
+        ApiTokenService::scopes_allow(&granted, required)
+    }
+
+    fn ip_allowed(allowlist: &[&str], ip: &str) -> bool {
+        let allowlist: Vec<String> = allowlist.iter().map(|s| s.to_string()).collect();
+        ApiTokenService::ip_allowed(&allowlist, Some(ip.parse().unwrap()))
+    }
+
+    #[test]
+    fn required_scope_follows_method_and_module() {
+        let cases = [
+            ("GET", "/api/files/list", Some("files:read")),
+            ("HEAD", "/api/files/list", Some("files:read")),
+            ("POST", "/api/files/upload", Some("files:write")),
+            ("DELETE", "/api/links/3", Some("links:write")),
+            ("PATCH", "/api/console/jobs/1", Some("console:write")),
+            ("GET", "/metrics", Some("metrics:read")),
+            ("GET", "/api/alice/pc/queue/7", Some("alice:queue")),
+            ("POST", "/api/alice/pc/queue", Some("alice:queue")),
+            ("POST", "/api/alice/devices", Some("alice:write")),
+            ("GET", "/api/auth/sessions", Some("auth:read")),
+            ("DELETE", "/api/auth/tokens/1", Some("auth:write")),
+            ("GET", "/api/", None),
+            ("GET", "/health", None),
+            ("GET", "/apifiles", None),
+        ];
+        for (method, path, expected) in cases {
+            assert_eq!(
+                ApiTokenService::required_scope(method, path).as_deref(),
+                expected,
+                "{} {}",
+                method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn write_implies_read_but_not_the_reverse() {
+        assert!(allows(&["files:write"], "files:write"));
+        assert!(allows(&["files:write"], "files:read"));
+        assert!(allows(&["files:read"], "files:read"));
+        assert!(!allows(&["files:read"], "files:write"));
+        assert!(allows(&["files:*"], "files:write"));
+        assert!(allows(&["*:read"], "console:read"));
+        assert!(!allows(&["*:read"], "console:write"));
+        assert!(allows(&["*:*"], "console:write"));
+        assert!(!allows(&[], "files:read"));
+    }
+
+    #[test]
+    fn scopes_do_not_leak_across_modules() {
+        assert!(!allows(&["files:write"], "links:read"));
+        assert!(!allows(&["file:write"], "files:read"));
+        assert!(!allows(&["files:write", "links:read"], "links:write"));
+        assert!(allows(&["files:write", "links:read"], "links:read"));
+        // The queue scope is separate from the rest of the module
+        assert!(!allows(&["alice:write"], "alice:queue"));
+        assert!(allows(&["alice:queue"], "alice:queue"));
+        assert!(!allows(&["alice:queue"], "alice:write"));
+        assert!(allows(&["alice:*"], "alice:queue"));
+        assert!(!allows(&["files:write"], "malformed"));
+    }
+
+    #[test]
+    fn auth_scopes_are_never_granted() {
+        for granted in [&["auth:read"][..], &["auth:*"], &["*:*"], &["*"]] {
+            assert!(!allows(granted, "auth:read"), "{:?}", granted);
+            assert!(!allows(granted, "auth:write"), "{:?}", granted);
+        }
+        assert!(ApiTokenService::validate_scope("auth:read").is_err());
+        assert!(ApiTokenService::validate_scope("auth:*").is_err());
+        assert!(ApiTokenService::validate_scope("files:read").is_ok());
+        assert!(ApiTokenService::validate_scope("*:*").is_ok());
+        for invalid in ["files", "files:", ":read", "Files:read", "files:read write"] {
+            assert!(ApiTokenService::validate_scope(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn ipv4_addresses_and_ranges() {
+        assert!(ip_allowed(&[], "203.0.113.9"));
+        assert!(ip_allowed(&["203.0.113.9"], "203.0.113.9"));
+        assert!(!ip_allowed(&["203.0.113.9"], "203.0.113.10"));
+        assert!(ip_allowed(&["10.0.0.0/8"], "10.255.1.2"));
+        assert!(!ip_allowed(&["10.0.0.0/8"], "11.0.0.1"));
+        assert!(ip_allowed(&["192.168.1.77/24"], "192.168.1.1"));
+        assert!(!ip_allowed(&["192.168.1.0/24"], "192.168.2.1"));
+        assert!(ip_allowed(&["0.0.0.0/0"], "8.8.8.8"));
+        assert!(ip_allowed(&["198.51.100.1", "10.0.0.0/8"], "10.1.1.1"));
+        assert!(!ApiTokenService::ip_allowed(&["10.0.0.0/8".to_string()], None));
+    }
+
+    #[test]
+    fn ipv6_addresses_and_ranges() {
+        assert!(ip_allowed(&["2001:db8::1"], "2001:db8::1"));
+        assert!(!ip_allowed(&["2001:db8::1"], "2001:db8::2"));
+        assert!(ip_allowed(&["2001:db8::/32"], "2001:db8:ffff::1"));
+        assert!(!ip_allowed(&["2001:db8::/32"], "2001:db9::1"));
+        assert!(ip_allowed(&["::/0"], "fe80::1"));
+        // Families do not mix, except IPv4 clients behind an IPv6 socket
+        assert!(!ip_allowed(&["2001:db8::/32"], "10.0.0.1"));
+        assert!(!ip_allowed(&["::/0"], "10.0.0.1"));
+        assert!(ip_allowed(&["10.0.0.0/8"], "::ffff:10.1.2.3"));
+        assert!(!ip_allowed(&["10.0.0.0/8"], "::ffff:11.1.2.3"));
+    }
+
+    #[test]
+    fn malformed_entries_never_match() {
+        for entry in ["10.0.0.1/abc", "10.0.0.1/", "10.0.0.1/33", "2001:db8::1/129", "10.0.0", "localhost", ""] {
+            assert!(!ip_allowed(&[entry], "10.0.0.1"), "{}", entry);
+            assert!(!ip_allowed(&[entry], "2001:db8::1"), "{}", entry);
+            assert!(ApiTokenService::validate_ip_entry(entry).is_err(), "{}", entry);
+        }
+        assert!(ip_allowed(&["bogus", "10.0.0.1"], "10.0.0.1"));
+    }
+}
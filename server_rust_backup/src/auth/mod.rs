@@ -1,9 +1,15 @@
+pub mod api_tokens;
+
 use crate::db::DbPool;
-use crate::models::{OtpCode, Session, User};
+use crate::models::{CreatedSession, OtpCode, Session, SessionClient, User};
 use rand::Rng;
 use sha2::{Digest, Sha256};
+use std::env;
 use uuid::Uuid;
 
+const DEFAULT_SESSION_IDLE_DAYS: i64 = 7;
+const DEFAULT_SESSION_MAX_DAYS: i64 = 30;
+
 pub struct AuthService;
 
 impl AuthService {
@@ -141,12 +147,79 @@ impl AuthService {
         }
     }
 
+    // Hash a session token for storage and lookup
+    pub fn hash_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    // Idle timeout: a session expires after this many days without requests
+    fn session_idle_days() -> i64 {
+        env::var("SESSION_IDLE_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SESSION_IDLE_DAYS)
+    }
+
+    // Absolute lifetime: a session never lives longer than this, however active
+    fn session_max_days() -> i64 {
+        env::var("SESSION_MAX_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SESSION_MAX_DAYS)
+    }
+
+    // Short human-readable device label from a User-Agent header
+    pub fn describe_device(user_agent: Option<&str>) -> String {
+        let ua = match user_agent {
+            Some(ua) if !ua.is_empty() => ua,
+            _ => return "Unknown device".to_string(),
+        };
+
+        let browser = if ua.contains("Edg/") {
+            "Edge"
+        } else if ua.contains("OPR/") {
+            "Opera"
+        } else if ua.contains("YaBrowser/") {
+            "Yandex Browser"
+        } else if ua.contains("Firefox/") {
+            "Firefox"
+        } else if ua.contains("Chrome/") {
+            "Chrome"
+        } else if ua.contains("Safari/") {
+            "Safari"
+        } else if ua.starts_with("curl/") {
+            "curl"
+        } else {
+            "Unknown browser"
+        };
+
+        let os = if ua.contains("Windows") {
+            "Windows"
+        } else if ua.contains("Android") {
+            "Android"
+        } else if ua.contains("iPhone") || ua.contains("iPad") {
+            "iOS"
+        } else if ua.contains("Mac OS X") {
+            "macOS"
+        } else if ua.contains("Linux") {
+            "Linux"
+        } else {
+            "Unknown OS"
+        };
+
+        format!("{} on {}", browser, os)
+    }
+
     // Verify OTP and create session
     pub async fn verify_otp(
         pool: &DbPool,
         telegram_id: i64,
         code: &str,
-    ) -> Result<Option<String>, sqlx::Error> {
+        client: &SessionClient,
+    ) -> Result<Option<CreatedSession>, sqlx::Error> {
+        let idle = format!("+{} days", Self::session_idle_days());
+        let max = format!("+{} days", Self::session_max_days());
+
         match pool {
             DbPool::Sqlite(p) => {
                 // Find user
@@ -182,18 +255,36 @@ impl AuthService {
                     .execute(p)
                     .await?;
 
-                // Create session (expires in 30 days)
+                // Check whether this IP has been seen before
+                let known_ip: (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM sessions WHERE user_id = ? AND ip_address = ?",
+                )
+                .bind(user.id)
+                .bind(&client.ip_address)
+                .fetch_one(p)
+                .await?;
+
+                // Create session with sliding expiry capped by the absolute lifetime
                 let token = Self::generate_token();
 
-                sqlx::query(
-                    "INSERT INTO sessions (user_id, token, expires_at, created_at) VALUES (?, ?, datetime('now', '+30 days'), datetime('now'))",
+                let (session_id,): (i32,) = sqlx::query_as(
+                    "INSERT INTO sessions (user_id, token, ip_address, user_agent, expires_at, absolute_expires_at, last_seen_at, created_at) VALUES (?, ?, ?, ?, MIN(datetime('now', ?), datetime('now', ?)), datetime('now', ?), datetime('now'), datetime('now')) RETURNING id",
                 )
                 .bind(user.id)
-                .bind(&token)
-                .execute(p)
+                .bind(Self::hash_token(&token))
+                .bind(&client.ip_address)
+                .bind(&client.user_agent)
+                .bind(&idle)
+                .bind(&max)
+                .bind(&max)
+                .fetch_one(p)
                 .await?;
 
-                Ok(Some(token))
+                Ok(Some(CreatedSession {
+                    token,
+                    session_id,
+                    is_new_ip: client.ip_address.is_some() && known_ip.0 == 0,
+                }))
             }
             DbPool::Postgres(p) => {
                 // Find user
@@ -229,33 +320,53 @@ impl AuthService {
                     .execute(p)
                     .await?;
 
-                // Create session (expires in 30 days)
+                // Check whether this IP has been seen before
+                let known_ip: (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM sessions WHERE user_id = $1 AND ip_address = $2",
+                )
+                .bind(user.id)
+                .bind(&client.ip_address)
+                .fetch_one(p)
+                .await?;
+
+                // Create session with sliding expiry capped by the absolute lifetime
                 let token = Self::generate_token();
 
-                sqlx::query(
-                    "INSERT INTO sessions (user_id, token, expires_at, created_at) VALUES ($1, $2, NOW() + INTERVAL '30 days', NOW())",
+                let (session_id,): (i32,) = sqlx::query_as(
+                    "INSERT INTO sessions (user_id, token, ip_address, user_agent, expires_at, absolute_expires_at, last_seen_at, created_at) VALUES ($1, $2, $3, $4, LEAST(NOW() + $5::interval, NOW() + $6::interval), NOW() + $6::interval, NOW(), NOW()) RETURNING id",
                 )
                 .bind(user.id)
-                .bind(&token)
-                .execute(p)
+                .bind(Self::hash_token(&token))
+                .bind(&client.ip_address)
+                .bind(&client.user_agent)
+                .bind(idle.trim_start_matches('+'))
+                .bind(max.trim_start_matches('+'))
+                .fetch_one(p)
                 .await?;
 
-                Ok(Some(token))
+                Ok(Some(CreatedSession {
+                    token,
+                    session_id,
+                    is_new_ip: client.ip_address.is_some() && known_ip.0 == 0,
+                }))
             }
         }
     }
 
-    // Validate session token
+    // Validate session token and slide its expiry forward
     pub async fn validate_token(
         pool: &DbPool,
         token: &str,
-    ) -> Result<Option<User>, sqlx::Error> {
+    ) -> Result<Option<(User, Session)>, sqlx::Error> {
+        let token_hash = Self::hash_token(token);
+        let idle = format!("+{} days", Self::session_idle_days());
+
         match pool {
             DbPool::Sqlite(p) => {
                 let session = sqlx::query_as::<_, Session>(
-                    "SELECT id, user_id, token, expires_at, created_at FROM sessions WHERE token = ? AND expires_at > datetime('now')",
+                    "SELECT id, user_id, token, ip_address, user_agent, expires_at, absolute_expires_at, last_seen_at, created_at FROM sessions WHERE token = ? AND revoked_at IS NULL AND expires_at > datetime('now') AND absolute_expires_at > datetime('now')",
                 )
-                .bind(token)
+                .bind(&token_hash)
                 .fetch_optional(p)
                 .await?;
 
@@ -264,6 +375,15 @@ impl AuthService {
                     None => return Ok(None),
                 };
 
+                // Touch at most once a minute to avoid a write per request
+                sqlx::query(
+                    "UPDATE sessions SET last_seen_at = datetime('now'), expires_at = MIN(datetime('now', ?), absolute_expires_at) WHERE id = ? AND last_seen_at < datetime('now', '-1 minute')",
+                )
+                .bind(&idle)
+                .bind(session.id)
+                .execute(p)
+                .await?;
+
                 let user = sqlx::query_as::<_, User>(
                     "SELECT id, telegram_id, username, created_at FROM users WHERE id = ?",
                 )
@@ -271,13 +391,13 @@ impl AuthService {
                 .fetch_one(p)
                 .await?;
 
-                Ok(Some(user))
+                Ok(Some((user, session)))
             }
             DbPool::Postgres(p) => {
                 let session = sqlx::query_as::<_, Session>(
-                    "SELECT id, user_id, token, expires_at, created_at FROM sessions WHERE token = $1 AND expires_at > NOW()",
+                    "SELECT id, user_id, token, ip_address, user_agent, expires_at::TEXT, absolute_expires_at::TEXT, last_seen_at::TEXT, created_at::TEXT FROM sessions WHERE token = $1 AND revoked_at IS NULL AND expires_at > NOW() AND absolute_expires_at > NOW()",
                 )
-                .bind(token)
+                .bind(&token_hash)
                 .fetch_optional(p)
                 .await?;
 
@@ -286,6 +406,15 @@ impl AuthService {
                     None => return Ok(None),
                 };
 
+                // Touch at most once a minute to avoid a write per request
+                sqlx::query(
+                    "UPDATE sessions SET last_seen_at = NOW(), expires_at = LEAST(NOW() + $1::interval, absolute_expires_at) WHERE id = $2 AND last_seen_at < NOW() - INTERVAL '1 minute'",
+                )
+                .bind(idle.trim_start_matches('+'))
+                .bind(session.id)
+                .execute(p)
+                .await?;
+
                 let user = sqlx::query_as::<_, User>(
                     "SELECT id, telegram_id, username, created_at FROM users WHERE id = $1",
                 )
@@ -293,7 +422,98 @@ impl AuthService {
                 .fetch_one(p)
                 .await?;
 
-                Ok(Some(user))
+                Ok(Some((user, session)))
+            }
+        }
+    }
+
+    // List active (not revoked, not expired) sessions of a user
+    pub async fn list_sessions(
+        pool: &DbPool,
+        user_id: i32,
+    ) -> Result<Vec<Session>, sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, Session>(
+                    "SELECT id, user_id, token, ip_address, user_agent, expires_at, absolute_expires_at, last_seen_at, created_at FROM sessions WHERE user_id = ? AND revoked_at IS NULL AND expires_at > datetime('now') AND absolute_expires_at > datetime('now') ORDER BY last_seen_at DESC",
+                )
+                .bind(user_id)
+                .fetch_all(p)
+                .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, Session>(
+                    "SELECT id, user_id, token, ip_address, user_agent, expires_at::TEXT, absolute_expires_at::TEXT, last_seen_at::TEXT, created_at::TEXT FROM sessions WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW() AND absolute_expires_at > NOW() ORDER BY last_seen_at DESC",
+                )
+                .bind(user_id)
+                .fetch_all(p)
+                .await
+            }
+        }
+    }
+
+    // Revoke a single session; returns false if it doesn't belong to the user
+    pub async fn revoke_session(
+        pool: &DbPool,
+        user_id: i32,
+        session_id: i32,
+    ) -> Result<bool, sqlx::Error> {
+        let result = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE sessions SET revoked_at = datetime('now') WHERE id = ? AND user_id = ? AND revoked_at IS NULL",
+                )
+                .bind(session_id)
+                .bind(user_id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+                )
+                .bind(session_id)
+                .bind(user_id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+        };
+
+        Ok(result > 0)
+    }
+
+    // Revoke every session of the user except `keep_session_id`
+    pub async fn revoke_other_sessions(
+        pool: &DbPool,
+        user_id: i32,
+        keep_session_id: Option<i32>,
+    ) -> Result<u64, sqlx::Error> {
+        let keep = keep_session_id.unwrap_or(0);
+
+        match pool {
+            DbPool::Sqlite(p) => {
+                let result = sqlx::query(
+                    "UPDATE sessions SET revoked_at = datetime('now') WHERE user_id = ? AND id != ? AND revoked_at IS NULL",
+                )
+                .bind(user_id)
+                .bind(keep)
+                .execute(p)
+                .await?;
+
+                Ok(result.rows_affected())
+            }
+            DbPool::Postgres(p) => {
+                let result = sqlx::query(
+                    "UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND id != $2 AND revoked_at IS NULL",
+                )
+                .bind(user_id)
+                .bind(keep)
+                .execute(p)
+                .await?;
+
+                Ok(result.rows_affected())
             }
         }
     }
@@ -0,0 +1,216 @@
+use aes_gcm::aead::stream::{DecryptorBE32, EncryptorBE32};
+use aes_gcm::aead::KeyInit;
+use aes_gcm::Aes256Gcm;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Header of encrypted backups, followed by the STREAM nonce prefix
+const MAGIC: &[u8; 5] = b"BGBK1";
+/// AES-GCM nonce minus the 5 bytes the STREAM construction uses for its counter
+const NONCE_PREFIX_LEN: usize = 7;
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+
+/// Parse `BACKUP_ENCRYPTION_KEY`: 32 bytes as 64 hex characters
+pub fn parse_key(hex_key: &str) -> Result<[u8; 32], String> {
+    let bytes = hex::decode(hex_key.trim()).map_err(|_| "key must be hex encoded".to_string())?;
+    bytes
+        .try_into()
+        .map_err(|_| "key must be 32 bytes (64 hex characters)".to_string())
+}
+
+pub fn gzip(src: &Path, dst: &Path) -> io::Result<()> {
+    let mut input = BufReader::new(File::open(src)?);
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(dst)?), Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.flush()
+}
+
+pub fn gunzip(src: &Path, dst: &Path) -> io::Result<()> {
+    let mut decoder = GzDecoder::new(BufReader::new(File::open(src)?));
+    let mut output = BufWriter::new(File::create(dst)?);
+    io::copy(&mut decoder, &mut output)?;
+    output.flush()
+}
+
+/// AES-256-GCM in 64 KiB chunks (STREAM construction), so truncation and reordering are detected
+pub fn encrypt(src: &Path, dst: &Path, key: &[u8; 32]) -> io::Result<()> {
+    let mut nonce = [0u8; NONCE_PREFIX_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let cipher = Aes256Gcm::new(key.into());
+    let mut encryptor = EncryptorBE32::from_aead(cipher, nonce.as_slice().into());
+
+    let mut input = BufReader::new(File::open(src)?);
+    let mut output = BufWriter::new(File::create(dst)?);
+    output.write_all(MAGIC)?;
+    output.write_all(&nonce)?;
+
+    let mut current = read_chunk(&mut input, CHUNK_SIZE)?;
+    loop {
+        let next = read_chunk(&mut input, CHUNK_SIZE)?;
+        if next.is_empty() {
+            let sealed = encryptor.encrypt_last(current.as_slice()).map_err(|_| crypto_error("encryption failed"))?;
+            output.write_all(&sealed)?;
+            break;
+        }
+        let sealed = encryptor.encrypt_next(current.as_slice()).map_err(|_| crypto_error("encryption failed"))?;
+        output.write_all(&sealed)?;
+        current = next;
+    }
+    output.flush()
+}
+
+pub fn decrypt(src: &Path, dst: &Path, key: &[u8; 32]) -> io::Result<()> {
+    let mut input = BufReader::new(File::open(src)?);
+    let mut magic = [0u8; 5];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(crypto_error("not an encrypted backup"));
+    }
+    let mut nonce = [0u8; NONCE_PREFIX_LEN];
+    input.read_exact(&mut nonce)?;
+
+    let cipher = Aes256Gcm::new(key.into());
+    let mut decryptor = DecryptorBE32::from_aead(cipher, nonce.as_slice().into());
+    let mut output = BufWriter::new(File::create(dst)?);
+
+    let mut current = read_chunk(&mut input, CHUNK_SIZE + TAG_SIZE)?;
+    loop {
+        let next = read_chunk(&mut input, CHUNK_SIZE + TAG_SIZE)?;
+        if next.is_empty() {
+            let plain = decryptor
+                .decrypt_last(current.as_slice())
+                .map_err(|_| crypto_error("wrong key or corrupted backup"))?;
+            output.write_all(&plain)?;
+            break;
+        }
+        let plain = decryptor
+            .decrypt_next(current.as_slice())
+            .map_err(|_| crypto_error("wrong key or corrupted backup"))?;
+        output.write_all(&plain)?;
+        current = next;
+    }
+    output.flush()
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut input = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = input.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Read up to `size` bytes, fewer only at end of file
+fn read_chunk(input: &mut impl Read, size: usize) -> io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(size);
+    input.take(size as u64).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+fn crypto_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    /// Encrypt `plain` into a tempdir; returns the dir and the ciphertext path
+    fn encrypted(plain: &[u8]) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("plain");
+        let dst = dir.path().join("sealed");
+        fs::write(&src, plain).unwrap();
+        encrypt(&src, &dst, &KEY).unwrap();
+        (dir, dst)
+    }
+
+    fn decrypted(sealed: &Path, key: &[u8; 32]) -> io::Result<Vec<u8>> {
+        let out = sealed.with_extension("out");
+        decrypt(sealed, &out, key)?;
+        fs::read(out)
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn roundtrips_any_length() {
+        for len in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1, 3 * CHUNK_SIZE + 17] {
+            let plain = pattern(len);
+            let (_dir, sealed) = encrypted(&plain);
+            let chunks = len.div_ceil(CHUNK_SIZE).max(1);
+            let expected_size = MAGIC.len() + NONCE_PREFIX_LEN + len + chunks * TAG_SIZE;
+            assert_eq!(fs::metadata(&sealed).unwrap().len() as usize, expected_size, "length {}", len);
+            assert_eq!(decrypted(&sealed, &KEY).unwrap(), plain, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn same_input_encrypts_differently() {
+        let (_a, first) = encrypted(b"backup");
+        let (_b, second) = encrypted(b"backup");
+        assert_ne!(fs::read(first).unwrap(), fs::read(second).unwrap());
+    }
+
+    #[test]
+    fn rejects_wrong_key() {
+        let (_dir, sealed) = encrypted(&pattern(CHUNK_SIZE + 5));
+        let mut other = KEY;
+        other[0] ^= 1;
+        let err = decrypted(&sealed, &other).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_tampered_ciphertext() {
+        let (_dir, sealed) = encrypted(&pattern(2 * CHUNK_SIZE + 100));
+        let original = fs::read(&sealed).unwrap();
+        let header = MAGIC.len() + NONCE_PREFIX_LEN;
+        // A flipped bit in the first chunk, the last tag, the nonce and the magic
+        for position in [header + 10, original.len() - 1, MAGIC.len() + 1, 0] {
+            let mut bytes = original.clone();
+            bytes[position] ^= 0x01;
+            fs::write(&sealed, &bytes).unwrap();
+            assert!(decrypted(&sealed, &KEY).is_err(), "flip at {}", position);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_ciphertext() {
+        let (_dir, sealed) = encrypted(&pattern(2 * CHUNK_SIZE + 100));
+        let original = fs::read(&sealed).unwrap();
+        let header = MAGIC.len() + NONCE_PREFIX_LEN;
+        let whole_chunk = CHUNK_SIZE + TAG_SIZE;
+        // Mid-chunk, exactly at a chunk boundary (drops the final chunk), header only, inside the header
+        for len in [original.len() - 50, header + 2 * whole_chunk, header, 3] {
+            fs::write(&sealed, &original[..len]).unwrap();
+            assert!(decrypted(&sealed, &KEY).is_err(), "truncated to {}", len);
+        }
+    }
+
+    #[test]
+    fn parses_hex_keys() {
+        assert_eq!(parse_key(&format!(" {} \n", "07".repeat(32))).unwrap(), KEY);
+        assert!(parse_key(&"07".repeat(31)).is_err());
+        assert!(parse_key(&"zz".repeat(32)).is_err());
+    }
+}
@@ -0,0 +1,6 @@
+pub mod archive;
+pub mod models;
+pub mod service;
+
+pub use models::*;
+pub use service::*;
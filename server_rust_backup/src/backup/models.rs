@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// One backup file, described by the `<name>.json` sidecar next to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub name: String,
+    pub backend: String, // sqlite, postgres
+    pub reason: String,  // scheduled, manual, pre-restore
+    pub created_at: String,
+    pub size_bytes: u64,
+    pub compressed: bool,
+    pub encrypted: bool,
+    /// SHA-256 of the stored file, checked before every restore
+    pub sha256: String,
+    /// Rows per table; for Postgres counted on the live database at dump time
+    pub tables: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreRequest {
+    /// Only report what the restore would do (default)
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableRestoreCount {
+    pub table: String,
+    /// None when the table only exists in the current database
+    pub backup_rows: Option<i64>,
+    /// None when the table only exists in the backup
+    pub current_rows: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub name: String,
+    pub dry_run: bool,
+    pub tables: Vec<TableRestoreCount>,
+    /// Backup of the current database taken right before a real restore
+    pub safety_backup: Option<String>,
+}
@@ -0,0 +1,627 @@
+use crate::backup::archive;
+use crate::backup::models::*;
+use crate::db::DbPool;
+use crate::notifications::{category, NewNotification, NotificationPriority, NotificationService};
+use crate::telegram::TelegramBot;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use sqlx::postgres::PgConnection;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{Connection, PgPool, SqlitePool};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+const DEFAULT_BACKUP_DIR: &str = "./backups";
+/// Wait before the next attempt when a scheduled backup fails
+const RETRY_AFTER_FAILURE_SECS: u64 = 3600;
+
+#[derive(Clone)]
+pub struct BackupService {
+    pool: DbPool,
+    database_url: String,
+    dir: PathBuf,
+    interval_hours: u64,
+    keep: usize,
+    compress: bool,
+    key: Option<[u8; 32]>,
+    /// Backups and restores never overlap
+    lock: Arc<Mutex<()>>,
+}
+
+impl BackupService {
+    /// `BACKUP_DIR` (./backups), `BACKUP_INTERVAL_HOURS` (24, 0 disables the schedule), `BACKUP_KEEP` (14),
+    /// `BACKUP_COMPRESS` (true), `BACKUP_ENCRYPTION_KEY` (optional, 64 hex characters)
+    pub fn init(pool: DbPool, database_url: &str) -> Result<Self, String> {
+        let dir = PathBuf::from(env::var("BACKUP_DIR").unwrap_or_else(|_| DEFAULT_BACKUP_DIR.to_string()));
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        let key = match env::var("BACKUP_ENCRYPTION_KEY") {
+            Ok(key) if !key.trim().is_empty() => {
+                Some(archive::parse_key(&key).map_err(|e| format!("BACKUP_ENCRYPTION_KEY: {}", e))?)
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            pool,
+            database_url: database_url.to_string(),
+            dir,
+            interval_hours: env::var("BACKUP_INTERVAL_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(24),
+            keep: env::var("BACKUP_KEEP")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(14)
+                .max(1),
+            compress: env::var("BACKUP_COMPRESS")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(true),
+            key,
+            lock: Arc::new(Mutex::new(())),
+        })
+    }
+
+    pub fn start_background_task(self, bot: TelegramBot, chat_id: i64) {
+        if self.interval_hours == 0 {
+            info!("Scheduled backups disabled");
+            return;
+        }
+
+        info!("Scheduled backups every {} h into {}", self.interval_hours, self.dir.display());
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(self.until_next_backup()).await;
+                match self.create("scheduled").await {
+                    Ok(backup) => info!("Backup {} created ({} bytes)", backup.name, backup.size_bytes),
+                    Err(e) => {
+                        error!("Scheduled backup failed: {}", e);
+                        NotificationService::post(
+                            &self.pool,
+                            NewNotification::new(
+                                category::SYSTEM,
+                                NotificationPriority::High,
+                                "❌ Резервная копия базы не создана",
+                                e.clone(),
+                            ),
+                        )
+                        .await;
+                        let message = format!("❌ Резервная копия базы не создана: {}", e);
+                        if let Err(e) = bot.send_message(chat_id, &message).await {
+                            error!("Failed to send backup notification: {}", e);
+                        }
+                        tokio::time::sleep(Duration::from_secs(RETRY_AFTER_FAILURE_SECS)).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Time left until the newest backup is `BACKUP_INTERVAL_HOURS` old
+    fn until_next_backup(&self) -> Duration {
+        let interval = self.interval_hours as i64 * 3600;
+        let age = self
+            .list()
+            .iter()
+            .filter_map(|b| DateTime::parse_from_rfc3339(&b.created_at).ok())
+            .map(|created| Utc::now().timestamp() - created.timestamp())
+            .min();
+        match age {
+            Some(age) => Duration::from_secs((interval - age).max(0) as u64),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Backups on disk, newest first
+    pub fn list(&self) -> Vec<BackupInfo> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        let mut backups: Vec<BackupInfo> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let info: BackupInfo = serde_json::from_slice(&std::fs::read(&path).ok()?).ok()?;
+                self.dir.join(&info.name).exists().then_some(info)
+            })
+            .collect();
+        backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        backups
+    }
+
+    /// Backup metadata and the path of its file
+    pub fn find(&self, name: &str) -> Result<(BackupInfo, PathBuf), String> {
+        if name.starts_with('.') || name.contains('/') || name.contains('\\') {
+            return Err("Invalid backup name".to_string());
+        }
+        self.list()
+            .into_iter()
+            .find(|b| b.name == name)
+            .map(|b| {
+                let path = self.dir.join(&b.name);
+                (b, path)
+            })
+            .ok_or_else(|| "Backup not found".to_string())
+    }
+
+    pub async fn create(&self, reason: &str) -> Result<BackupInfo, String> {
+        let _guard = self.lock.lock().await;
+        self.create_locked(reason).await
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<(), String> {
+        let _guard = self.lock.lock().await;
+        let (_, path) = self.find(name)?;
+        remove_backup(&path);
+        Ok(())
+    }
+
+    /// Restore a backup; with `dry_run` only compare its row counts with the current database
+    pub async fn restore(&self, name: &str, dry_run: bool) -> Result<RestoreReport, String> {
+        let _guard = self.lock.lock().await;
+        let (backup, path) = self.find(name)?;
+        if backup.backend != self.backend() {
+            return Err(format!("Backup is for {}, the server runs on {}", backup.backend, self.backend()));
+        }
+
+        let plain = self.unpack(&backup, &path).await?;
+        let result = match &self.pool {
+            DbPool::Sqlite(pool) => self.restore_sqlite(pool, &plain, dry_run).await,
+            DbPool::Postgres(pool) => self.restore_postgres(pool, &plain, dry_run).await,
+        };
+        let _ = std::fs::remove_file(&plain);
+
+        let (tables, safety_backup) = result?;
+        if !dry_run {
+            warn!("Database restored from backup {}", name);
+        }
+        Ok(RestoreReport {
+            name: name.to_string(),
+            dry_run,
+            tables,
+            safety_backup,
+        })
+    }
+
+    fn backend(&self) -> &'static str {
+        match self.pool {
+            DbPool::Sqlite(_) => "sqlite",
+            DbPool::Postgres(_) => "postgres",
+        }
+    }
+
+    async fn create_locked(&self, reason: &str) -> Result<BackupInfo, String> {
+        let created_at = Utc::now();
+        let base = format!("backup-{}", created_at.format("%Y%m%d-%H%M%S-%3f"));
+        let raw = self.dir.join(format!(".{}.tmp", base));
+
+        let dumped = match &self.pool {
+            DbPool::Sqlite(pool) => dump_sqlite(pool, &raw).await.map(|tables| ("sqlite", tables)),
+            DbPool::Postgres(pool) => dump_postgres(pool, &self.database_url, &raw)
+                .await
+                .map(|tables| ("dump", tables)),
+        };
+        let (extension, tables) = match dumped {
+            Ok(dumped) => dumped,
+            Err(e) => {
+                let _ = std::fs::remove_file(&raw);
+                return Err(e);
+            }
+        };
+
+        // pg_dump's custom format is already compressed
+        let compress = self.compress && extension == "sqlite";
+        let (dir, key) = (self.dir.clone(), self.key);
+        let packed = tokio::task::spawn_blocking(move || pack(&dir, &raw, &base, extension, compress, key.as_ref()))
+            .await
+            .map_err(|e| e.to_string())?;
+        let (name, size_bytes, sha256) = packed.map_err(|e| format!("Failed to write backup: {}", e))?;
+
+        let backup = BackupInfo {
+            name,
+            backend: self.backend().to_string(),
+            reason: reason.to_string(),
+            created_at: created_at.to_rfc3339(),
+            size_bytes,
+            compressed: compress,
+            encrypted: self.key.is_some(),
+            sha256,
+            tables,
+        };
+        let sidecar = serde_json::to_vec_pretty(&backup).map_err(|e| e.to_string())?;
+        std::fs::write(sidecar_path(&self.dir.join(&backup.name)), sidecar)
+            .map_err(|e| format!("Failed to write backup metadata: {}", e))?;
+
+        for old in self.list().iter().skip(self.keep) {
+            info!("Rotating out backup {}", old.name);
+            remove_backup(&self.dir.join(&old.name));
+        }
+
+        Ok(backup)
+    }
+
+    /// Check the checksum, then decrypt and decompress into a temporary file
+    async fn unpack(&self, backup: &BackupInfo, path: &Path) -> Result<PathBuf, String> {
+        if backup.encrypted && self.key.is_none() {
+            return Err("Backup is encrypted but BACKUP_ENCRYPTION_KEY is not set".to_string());
+        }
+
+        let (backup, path, key) = (backup.clone(), path.to_path_buf(), self.key);
+        let plain = self.dir.join(format!(".restore-{}.tmp", backup.name));
+        let target = plain.clone();
+        let unpacked = tokio::task::spawn_blocking(move || -> Result<(), String> {
+            let checksum = archive::sha256_file(&path).map_err(|e| e.to_string())?;
+            if checksum != backup.sha256 {
+                return Err("Backup checksum does not match, the file is corrupted".to_string());
+            }
+
+            let decrypted = target.with_extension("dec");
+            let mut current = path.clone();
+            if let Some(key) = key.filter(|_| backup.encrypted) {
+                archive::decrypt(&current, &decrypted, &key).map_err(|e| format!("Failed to decrypt: {}", e))?;
+                current = decrypted.clone();
+            }
+            let result = if backup.compressed {
+                archive::gunzip(&current, &target).map_err(|e| format!("Failed to decompress: {}", e))
+            } else {
+                std::fs::copy(&current, &target).map(|_| ()).map_err(|e| e.to_string())
+            };
+            let _ = std::fs::remove_file(&decrypted);
+            result
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if let Err(e) = unpacked {
+            let _ = std::fs::remove_file(&plain);
+            return Err(e);
+        }
+        Ok(plain)
+    }
+
+    async fn restore_sqlite(
+        &self,
+        pool: &SqlitePool,
+        plain: &Path,
+        dry_run: bool,
+    ) -> Result<(Vec<TableRestoreCount>, Option<String>), String> {
+        let backup_counts = verify_sqlite(plain).await?;
+        let mut conn = pool.acquire().await.map_err(db_error)?;
+        let current_counts = sqlite_table_counts(&mut conn, "main").await.map_err(db_error)?;
+        let tables = compare_counts(&backup_counts, &current_counts);
+        if dry_run {
+            return Ok((tables, None));
+        }
+
+        drop(conn);
+        let safety = self.create_locked("pre-restore").await?;
+
+        // Copy table contents over one connection, in one transaction, with foreign keys off
+        let mut conn = pool.acquire().await.map_err(db_error)?;
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await.map_err(db_error)?;
+        sqlx::query("ATTACH DATABASE ? AS restore_src")
+            .bind(plain.to_string_lossy().to_string())
+            .execute(&mut *conn)
+            .await
+            .map_err(db_error)?;
+        let copied = copy_sqlite_tables(&mut conn, &backup_counts, &current_counts).await;
+        let _ = sqlx::query("DETACH DATABASE restore_src").execute(&mut *conn).await;
+        let _ = sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await;
+        copied.map_err(|e| format!("Restore failed, database left unchanged: {}", e))?;
+
+        Ok((tables, Some(safety.name)))
+    }
+
+    async fn restore_postgres(
+        &self,
+        pool: &PgPool,
+        plain: &Path,
+        dry_run: bool,
+    ) -> Result<(Vec<TableRestoreCount>, Option<String>), String> {
+        let backup_counts = count_postgres_dump(pool, &self.database_url, plain).await?;
+        let mut conn = pool.acquire().await.map_err(db_error)?;
+        let current_counts = pg_table_counts(&mut conn).await.map_err(db_error)?;
+        drop(conn);
+        let tables = compare_counts(&backup_counts, &current_counts);
+        if dry_run {
+            return Ok((tables, None));
+        }
+
+        let safety = self.create_locked("pre-restore").await?;
+        let plain = plain.to_string_lossy().to_string();
+        run_pg_tool(
+            "pg_restore",
+            &self.database_url,
+            &["--clean", "--if-exists", "--no-owner", "--no-privileges", "--single-transaction", "--exit-on-error", &plain],
+        )
+        .await?;
+
+        Ok((tables, Some(safety.name)))
+    }
+}
+
+/// Online copy via `VACUUM INTO`, then open it to verify
+async fn dump_sqlite(pool: &SqlitePool, raw: &Path) -> Result<BTreeMap<String, i64>, String> {
+    sqlx::query("VACUUM INTO ?")
+        .bind(raw.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .map_err(|e| format!("VACUUM INTO failed: {}", e))?;
+    verify_sqlite(raw).await
+}
+
+/// `pg_dump` in custom format, verified by reading its table of contents
+async fn dump_postgres(pool: &PgPool, database_url: &str, raw: &Path) -> Result<BTreeMap<String, i64>, String> {
+    let mut conn = pool.acquire().await.map_err(db_error)?;
+    let tables = pg_table_counts(&mut conn).await.map_err(db_error)?;
+    drop(conn);
+
+    let raw = raw.to_string_lossy().to_string();
+    run_pg_tool("pg_dump", database_url, &["--format=custom", "--no-owner", "--no-privileges", "--file", &raw]).await?;
+    run_tool(Command::new("pg_restore").args(["--list", &raw])).await?;
+    Ok(tables)
+}
+
+/// Open a SQLite file read-only, run an integrity check and count its rows
+async fn verify_sqlite(path: &Path) -> Result<BTreeMap<String, i64>, String> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| format!("Backup cannot be opened: {}", e))?;
+
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut conn)
+        .await
+        .map_err(|e| format!("Integrity check failed: {}", e))?;
+    if integrity != "ok" {
+        return Err(format!("Integrity check failed: {}", integrity));
+    }
+
+    let counts = sqlite_table_counts(&mut conn, "main").await.map_err(db_error);
+    let _ = conn.close().await;
+    counts
+}
+
+/// Restore a dump into a scratch database to count its rows, then drop it
+async fn count_postgres_dump(pool: &PgPool, database_url: &str, plain: &Path) -> Result<BTreeMap<String, i64>, String> {
+    let mut url = url::Url::parse(database_url).map_err(|e| e.to_string())?;
+    let scratch = format!(
+        "{}_restore_check_{:08x}",
+        url.path().trim_start_matches('/'),
+        rand::thread_rng().next_u32()
+    );
+    url.set_path(&format!("/{}", scratch));
+
+    sqlx::query(&format!("CREATE DATABASE {}", quote_ident(&scratch)))
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Cannot create scratch database for the dry run: {}", e))?;
+
+    let plain = plain.to_string_lossy().to_string();
+    let counted = async {
+        run_pg_tool("pg_restore", url.as_str(), &["--no-owner", "--no-privileges", "--exit-on-error", &plain]).await?;
+        let mut conn = PgConnection::connect(url.as_str()).await.map_err(db_error)?;
+        let counts = pg_table_counts(&mut conn).await.map_err(db_error);
+        let _ = conn.close().await;
+        counts
+    }
+    .await;
+
+    if let Err(e) = sqlx::query(&format!("DROP DATABASE IF EXISTS {}", quote_ident(&scratch)))
+        .execute(pool)
+        .await
+    {
+        warn!("Failed to drop scratch database {}: {}", scratch, e);
+    }
+    counted
+}
+
+async fn copy_sqlite_tables(
+    conn: &mut SqliteConnection,
+    backup_counts: &BTreeMap<String, i64>,
+    current_counts: &BTreeMap<String, i64>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    for table in backup_counts.keys().filter(|t| current_counts.contains_key(*t)) {
+        let source: Vec<String> = sqlx::query_scalar(&format!(
+            "SELECT name FROM pragma_table_info({}, 'restore_src')",
+            quote_literal(table)
+        ))
+        .fetch_all(&mut *tx)
+        .await?;
+        let target: Vec<String> = sqlx::query_scalar(&format!(
+            "SELECT name FROM pragma_table_info({}, 'main')",
+            quote_literal(table)
+        ))
+        .fetch_all(&mut *tx)
+        .await?;
+        let columns: Vec<String> = source
+            .iter()
+            .filter(|c| target.contains(c))
+            .map(|c| quote_ident(c))
+            .collect();
+
+        sqlx::query(&format!("DELETE FROM main.{}", quote_ident(table)))
+            .execute(&mut *tx)
+            .await?;
+        if !columns.is_empty() {
+            let columns = columns.join(", ");
+            sqlx::query(&format!(
+                "INSERT INTO main.{table} ({columns}) SELECT {columns} FROM restore_src.{table}",
+                table = quote_ident(table),
+                columns = columns
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    // AUTOINCREMENT counters follow the restored rows
+    let has_sequence: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM restore_src.sqlite_master WHERE type = 'table' AND name = 'sqlite_sequence'",
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if has_sequence > 0 {
+        sqlx::query("DELETE FROM main.sqlite_sequence").execute(&mut *tx).await?;
+        sqlx::query("INSERT INTO main.sqlite_sequence (name, seq) SELECT name, seq FROM restore_src.sqlite_sequence")
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await
+}
+
+async fn sqlite_table_counts(conn: &mut SqliteConnection, schema: &str) -> Result<BTreeMap<String, i64>, sqlx::Error> {
+    let tables: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT name FROM {}.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        schema
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut counts = BTreeMap::new();
+    for table in tables {
+        let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}.{}", schema, quote_ident(&table)))
+            .fetch_one(&mut *conn)
+            .await?;
+        counts.insert(table, count);
+    }
+    Ok(counts)
+}
+
+async fn pg_table_counts(conn: &mut PgConnection) -> Result<BTreeMap<String, i64>, sqlx::Error> {
+    let tables: Vec<String> =
+        sqlx::query_scalar("SELECT tablename::TEXT FROM pg_tables WHERE schemaname = 'public' ORDER BY tablename")
+            .fetch_all(&mut *conn)
+            .await?;
+
+    let mut counts = BTreeMap::new();
+    for table in tables {
+        let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM public.{}", quote_ident(&table)))
+            .fetch_one(&mut *conn)
+            .await?;
+        counts.insert(table, count);
+    }
+    Ok(counts)
+}
+
+fn compare_counts(backup: &BTreeMap<String, i64>, current: &BTreeMap<String, i64>) -> Vec<TableRestoreCount> {
+    let mut names: Vec<&String> = backup.keys().chain(current.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|table| TableRestoreCount {
+            table: table.clone(),
+            backup_rows: backup.get(table).copied(),
+            current_rows: current.get(table).copied(),
+        })
+        .collect()
+}
+
+/// Compress and encrypt the raw dump into its final file; returns name, size and checksum
+fn pack(
+    dir: &Path,
+    raw: &Path,
+    base: &str,
+    extension: &str,
+    compress: bool,
+    key: Option<&[u8; 32]>,
+) -> std::io::Result<(String, u64, String)> {
+    let mut name = format!("{}.{}", base, extension);
+    let mut current = raw.to_path_buf();
+
+    let result = (|| {
+        if compress {
+            let gz = dir.join(format!(".{}.gz.tmp", base));
+            archive::gzip(&current, &gz)?;
+            std::fs::remove_file(&current)?;
+            current = gz;
+            name.push_str(".gz");
+        }
+        if let Some(key) = key {
+            let enc = dir.join(format!(".{}.enc.tmp", base));
+            archive::encrypt(&current, &enc, key)?;
+            std::fs::remove_file(&current)?;
+            current = enc;
+            name.push_str(".enc");
+        }
+
+        let path = dir.join(&name);
+        std::fs::rename(&current, &path)?;
+        let size = std::fs::metadata(&path)?.len();
+        Ok((name.clone(), size, archive::sha256_file(&path)?))
+    })();
+
+    if result.is_err() {
+        for tmp in [raw.to_path_buf(), dir.join(format!(".{}.gz.tmp", base)), dir.join(format!(".{}.enc.tmp", base))] {
+            let _ = std::fs::remove_file(tmp);
+        }
+    }
+    result
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".json");
+    PathBuf::from(sidecar)
+}
+
+fn remove_backup(path: &Path) {
+    for file in [path.to_path_buf(), sidecar_path(path)] {
+        if let Err(e) = std::fs::remove_file(&file) {
+            warn!("Failed to remove {}: {}", file.display(), e);
+        }
+    }
+}
+
+/// Run a Postgres client tool against `database_url`, passing the password through the environment
+async fn run_pg_tool(program: &str, database_url: &str, args: &[&str]) -> Result<(), String> {
+    let mut url = url::Url::parse(database_url).map_err(|e| e.to_string())?;
+    let password = url
+        .password()
+        .map(|p| urlencoding::decode(p).map(|p| p.into_owned()).unwrap_or_else(|_| p.to_string()));
+    let _ = url.set_password(None);
+
+    let mut command = Command::new(program);
+    command.args(args).arg(format!("--dbname={}", url));
+    if let Some(password) = password {
+        command.env("PGPASSWORD", password);
+    }
+    run_tool(&mut command).await
+}
+
+async fn run_tool(command: &mut Command) -> Result<(), String> {
+    let program = command.as_std().get_program().to_string_lossy().to_string();
+    let output = command
+        .output()
+        .await
+        .map_err(|e| format!("{}: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn db_error(e: sqlx::Error) -> String {
+    format!("Database error: {}", e)
+}
@@ -0,0 +1,402 @@
+use crate::console::models::*;
+use crate::console::service::{hash_code, kill_process_group, ConsoleService};
+use crate::db::DbPool;
+use parking_lot::RwLock;
+use std::collections::VecDeque;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, mpsc};
+use tracing::error;
+
+/// Events kept per job for subscribers that connect late
+const JOB_REPLAY_EVENTS: usize = 2000;
+/// Finished jobs stay listed (and replayable) this long
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(600);
+/// How long to wait for output pipes after the process exits
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_PTY_ROWS: u16 = 24;
+const DEFAULT_PTY_COLS: u16 = 80;
+
+/// Replay buffer and sequence counter; guarded together so subscribers never miss or repeat an event
+struct EventLog {
+    next_seq: u64,
+    events: VecDeque<JobEvent>,
+}
+
+/// A command running in the background whose output is streamed to subscribers
+pub struct ConsoleJob {
+    pub id: String,
+    pub command: String,
+    pub mode: &'static str,
+    pub pty: bool,
+    pub actor: String,
+    pub started_at: String,
+    ticket_hash: String,
+    log: RwLock<EventLog>,
+    sender: broadcast::Sender<JobEvent>,
+    control: mpsc::UnboundedSender<JobControl>,
+    running: AtomicBool,
+}
+
+impl ConsoleJob {
+    pub fn info(&self) -> JobInfo {
+        JobInfo {
+            id: self.id.clone(),
+            command: self.command.clone(),
+            mode: self.mode.to_string(),
+            pty: self.pty,
+            actor: self.actor.clone(),
+            started_at: self.started_at.clone(),
+            running: self.is_running(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn check_ticket(&self, ticket: &str) -> bool {
+        hash_code(ticket) == self.ticket_hash
+    }
+
+    /// Buffered events plus a receiver for everything after them
+    pub fn subscribe(&self) -> (Vec<JobEvent>, broadcast::Receiver<JobEvent>) {
+        let log = self.log.read();
+        (log.events.iter().cloned().collect(), self.sender.subscribe())
+    }
+
+    /// Forward stdin, resize or kill to the runner; false once the job has finished
+    pub fn send(&self, control: JobControl) -> bool {
+        self.is_running() && self.control.send(control).is_ok()
+    }
+
+    fn emit(&self, kind: JobEventKind) {
+        let mut log = self.log.write();
+        let event = JobEvent {
+            seq: log.next_seq,
+            kind,
+        };
+        log.next_seq += 1;
+        if log.events.len() >= JOB_REPLAY_EVENTS {
+            log.events.pop_front();
+        }
+        log.events.push_back(event.clone());
+        // No subscribers is fine; the event stays in the replay buffer
+        let _ = self.sender.send(event);
+    }
+}
+
+/// What the runner needs to talk to the child
+enum JobIo {
+    Pipes,
+    Pty { master: OwnedFd },
+}
+
+impl ConsoleService {
+    pub fn list_jobs(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.jobs.read().values().map(|j| j.info()).collect();
+        jobs.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        jobs
+    }
+
+    pub fn get_job(&self, id: &str) -> Option<Arc<ConsoleJob>> {
+        self.jobs.read().get(id).cloned()
+    }
+
+    /// Spawn argv in the background; `run` describes the job for `console_runs`.
+    /// Returns the job and its stream ticket.
+    pub fn start_job(
+        &self,
+        pool: DbPool,
+        run: NewConsoleRun,
+        argv: Vec<String>,
+        timeout_secs: Option<u64>,
+        pty: Option<(u16, u16)>,
+    ) -> Result<(Arc<ConsoleJob>, String), String> {
+        let running = self.jobs.read().values().filter(|j| j.is_running()).count();
+        if running >= self.limits().max_jobs {
+            return Err(format!("Too many running jobs (limit {})", self.limits().max_jobs));
+        }
+
+        let timeout = self.effective_timeout(timeout_secs, pty.is_some());
+        let mut command = self.sandboxed_command(&argv, timeout, pty.is_some())?;
+
+        let io = match pty {
+            Some((rows, cols)) => {
+                let (master, slave) = open_pty(rows, cols).map_err(|e| format!("Failed to open PTY: {}", e))?;
+                let stdio = || slave.try_clone().map(Stdio::from);
+                command
+                    .env("TERM", "xterm-256color")
+                    .stdin(stdio().map_err(|e| e.to_string())?)
+                    .stdout(stdio().map_err(|e| e.to_string())?)
+                    .stderr(stdio().map_err(|e| e.to_string())?);
+                JobIo::Pty { master }
+            }
+            None => {
+                command
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+                JobIo::Pipes
+            }
+        };
+
+        let child = command
+            .spawn()
+            .map_err(|e| format!("Failed to spawn {}: {}", argv[0], e))?;
+        // Close the parent's copies of the PTY slave so the reader sees EIO when the child exits
+        drop(command);
+
+        let ticket = crate::auth::AuthService::generate_token();
+        let (sender, _) = broadcast::channel(1024);
+        let (control, control_rx) = mpsc::unbounded_channel();
+        let job = Arc::new(ConsoleJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            command: run.command.clone(),
+            mode: run.mode,
+            pty: pty.is_some(),
+            actor: run.actor.clone(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            ticket_hash: hash_code(&ticket),
+            log: RwLock::new(EventLog {
+                next_seq: 0,
+                events: VecDeque::new(),
+            }),
+            sender,
+            control,
+            running: AtomicBool::new(true),
+        });
+
+        self.jobs.write().insert(job.id.clone(), job.clone());
+
+        let jobs = self.jobs.clone();
+        let runner_job = job.clone();
+        tokio::spawn(async move {
+            let run = NewConsoleRun {
+                argv: Some(argv),
+                ..run
+            };
+            run_job(runner_job.clone(), child, io, control_rx, timeout, pool, run).await;
+
+            tokio::time::sleep(FINISHED_JOB_TTL).await;
+            jobs.write().remove(&runner_job.id);
+        });
+
+        Ok((job, ticket))
+    }
+}
+
+/// Default terminal size when the client does not send one
+pub fn pty_size(rows: Option<u16>, cols: Option<u16>) -> (u16, u16) {
+    (
+        rows.filter(|r| *r > 0).unwrap_or(DEFAULT_PTY_ROWS),
+        cols.filter(|c| *c > 0).unwrap_or(DEFAULT_PTY_COLS),
+    )
+}
+
+async fn run_job(
+    job: Arc<ConsoleJob>,
+    mut child: tokio::process::Child,
+    io: JobIo,
+    mut control_rx: mpsc::UnboundedReceiver<JobControl>,
+    timeout: Duration,
+    pool: DbPool,
+    mut run: NewConsoleRun,
+) {
+    let start = Instant::now();
+    let pid = child.id();
+
+    // Output readers and the stdin writer for either mode
+    let (mut stdin, readers, master): (Option<Box<dyn AsyncWrite + Send + Unpin>>, _, _) = match io {
+        JobIo::Pipes => {
+            let stdin = child
+                .stdin
+                .take()
+                .map(|s| Box::new(s) as Box<dyn AsyncWrite + Send + Unpin>);
+            let out = tokio::spawn(pump_lines(child.stdout.take(), job.clone(), false));
+            let err = tokio::spawn(pump_lines(child.stderr.take(), job.clone(), true));
+            (stdin, vec![out, err], None)
+        }
+        JobIo::Pty { master } => {
+            let reader = master.try_clone().map(|fd| tokio::fs::File::from_std(std::fs::File::from(fd)));
+            let writer = master.try_clone().map(|fd| tokio::fs::File::from_std(std::fs::File::from(fd)));
+            let stdin = writer
+                .ok()
+                .map(|w| Box::new(w) as Box<dyn AsyncWrite + Send + Unpin>);
+            let out = tokio::spawn(pump_pty(reader.ok(), job.clone()));
+            (stdin, vec![out], Some(master))
+        }
+    };
+
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    let mut timed_out = false;
+    let mut killed = false;
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => break status,
+            _ = &mut deadline, if !timed_out => {
+                timed_out = true;
+                kill_process_group(pid);
+            }
+            Some(control) = control_rx.recv() => match control {
+                JobControl::Input { data } => {
+                    if let Some(writer) = stdin.as_mut() {
+                        if writer.write_all(data.as_bytes()).await.is_err() || writer.flush().await.is_err() {
+                            stdin = None;
+                        }
+                    }
+                }
+                JobControl::Resize { rows, cols } => {
+                    if let Some(master) = &master {
+                        resize_pty(master, rows, cols);
+                    }
+                }
+                JobControl::Kill => {
+                    killed = true;
+                    kill_process_group(pid);
+                }
+            },
+        }
+    };
+
+    // Close stdin so readers see EOF, then let them flush the tail of the output
+    drop(stdin);
+    let mut output_bytes = 0;
+    for reader in readers {
+        match tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, reader).await {
+            Ok(Ok(bytes)) => output_bytes += bytes,
+            _ => kill_process_group(pid),
+        }
+    }
+    drop(master);
+
+    let exit_code = status.as_ref().ok().and_then(|s| s.code());
+    if let Err(e) = &status {
+        job.emit(JobEventKind::Error {
+            message: format!("Wait error: {}", e),
+        });
+    }
+    job.emit(JobEventKind::Exit {
+        exit_code,
+        timed_out,
+        killed,
+        duration_ms: start.elapsed().as_millis(),
+    });
+    job.running.store(false, Ordering::SeqCst);
+
+    run.status = if timed_out {
+        "timed_out"
+    } else if killed {
+        "killed"
+    } else if exit_code == Some(0) {
+        "completed"
+    } else {
+        "failed"
+    };
+    run.exit_code = exit_code;
+    run.duration_ms = start.elapsed().as_millis() as i64;
+    run.output_bytes = output_bytes as i64;
+
+    if let Err(e) = ConsoleService::record(&pool, run).await {
+        error!("Failed to record console run: {}", e);
+    }
+}
+
+/// Stream a pipe line by line; returns the number of bytes read
+async fn pump_lines<R: AsyncRead + Unpin>(reader: Option<R>, job: Arc<ConsoleJob>, stderr: bool) -> usize {
+    let Some(reader) = reader else {
+        return 0;
+    };
+
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    let mut total = 0;
+    while let Ok(n) = reader.read_until(b'\n', &mut line).await {
+        if n == 0 {
+            break;
+        }
+        total += n;
+        let data = String::from_utf8_lossy(&line)
+            .trim_end_matches(['\n', '\r'])
+            .to_string();
+        job.emit(if stderr {
+            JobEventKind::Stderr { data }
+        } else {
+            JobEventKind::Stdout { data }
+        });
+        line.clear();
+    }
+    total
+}
+
+/// Stream raw terminal output; multi-byte characters split between reads are carried over
+async fn pump_pty(reader: Option<tokio::fs::File>, job: Arc<ConsoleJob>) -> usize {
+    let Some(mut reader) = reader else {
+        return 0;
+    };
+
+    let mut buf = [0u8; 4096];
+    let mut pending: Vec<u8> = Vec::new();
+    let mut total = 0;
+    // Reading the master fails with EIO once the last process holding the terminal exits
+    while let Ok(n) = reader.read(&mut buf).await {
+        if n == 0 {
+            break;
+        }
+        total += n;
+        pending.extend_from_slice(&buf[..n]);
+
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(_) => pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => pending.len(),
+        };
+        if valid == 0 {
+            continue;
+        }
+        let data = String::from_utf8_lossy(&pending[..valid]).to_string();
+        pending.drain(..valid);
+        job.emit(JobEventKind::Output { data });
+    }
+    total
+}
+
+fn open_pty(rows: u16, cols: u16) -> std::io::Result<(OwnedFd, OwnedFd)> {
+    let mut master = 0;
+    let mut slave = 0;
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: openpty fills both descriptors on success; ownership moves into OwnedFd
+    unsafe {
+        if libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), &size) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // Keep both ends out of unrelated children; stdio gets its own duplicates
+        libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC);
+        libc::fcntl(slave, libc::F_SETFD, libc::FD_CLOEXEC);
+        Ok((OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)))
+    }
+}
+
+fn resize_pty(master: &OwnedFd, rows: u16, cols: u16) {
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCSWINSZ only reads the winsize struct
+    unsafe {
+        libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size);
+    }
+}
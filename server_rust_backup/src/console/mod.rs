@@ -0,0 +1,8 @@
+pub mod jobs;
+pub mod models;
+pub mod policy;
+pub mod service;
+
+pub use jobs::*;
+pub use models::*;
+pub use service::*;
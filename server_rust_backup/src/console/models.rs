@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Type of a template argument; values are validated before the process is spawned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ArgKind {
+    /// Whole number within optional bounds
+    Integer { min: Option<i64>, max: Option<i64> },
+    /// One of a fixed set of values
+    Choice { values: Vec<String> },
+    /// Service, host or container name: letters, digits and `._@:-`, not starting with `-`
+    Name,
+    /// Relative path that must resolve inside the console working directory
+    Path,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArgSpec {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ArgKind,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Used when the argument is omitted
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Optional arguments without a default drop every template element referencing them
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// Named command: a program and its arguments with `{name}` placeholders, run without a shell
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandTemplate {
+    pub name: String,
+    pub description: String,
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub params: Vec<ArgSpec>,
+    /// Default timeout for this command
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Resource limits applied to every run
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsoleLimits {
+    pub default_timeout_secs: u64,
+    pub max_timeout_secs: u64,
+    pub memory_limit_mb: u64,
+    pub output_limit_bytes: usize,
+    pub workdir: String,
+    pub raw_mode_enabled: bool,
+    /// Default and maximum lifetime of interactive PTY jobs
+    pub pty_timeout_secs: u64,
+    /// Streaming jobs running at the same time
+    pub max_jobs: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExecuteCommandRequest {
+    /// Template name, or a shell command line when `raw` is set
+    pub command: String,
+    #[serde(default)]
+    pub args: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub raw: bool,
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommandResult {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub execution_time_ms: u128,
+    pub success: bool,
+    pub timed_out: bool,
+    /// Output exceeded the limit and was cut
+    pub truncated: bool,
+    /// Total bytes written to stdout and stderr
+    pub output_bytes: usize,
+}
+
+impl CommandResult {
+    /// Result for a run rejected before spawning
+    pub fn rejected(message: impl Into<String>) -> Self {
+        Self {
+            stdout: String::new(),
+            stderr: message.into(),
+            exit_code: None,
+            execution_time_ms: 0,
+            success: false,
+            timed_out: false,
+            truncated: false,
+            output_bytes: 0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommandCatalog {
+    pub commands: Vec<CommandTemplate>,
+    pub limits: ConsoleLimits,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawModeConfirmRequest {
+    pub otp: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RawModeStatus {
+    pub enabled: bool,
+    /// Seconds left on the step-up grant of the current session
+    pub expires_in_secs: Option<u64>,
+}
+
+/// Console run history row
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ConsoleRun {
+    pub id: i64,
+    pub actor: String,
+    pub user_id: Option<i32>,
+    /// "template" or "raw"
+    pub mode: String,
+    pub command: String,
+    /// JSON array of the final argv
+    pub argv: Option<String>,
+    /// "completed", "failed", "timed_out", "killed" or "rejected"
+    pub status: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: i64,
+    pub output_bytes: i64,
+    pub error: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: String,
+}
+
+/// Run to be recorded in `console_runs`
+#[derive(Debug, Clone)]
+pub struct NewConsoleRun {
+    pub actor: String,
+    pub user_id: Option<i32>,
+    pub mode: &'static str,
+    pub command: String,
+    pub argv: Option<Vec<String>>,
+    pub status: &'static str,
+    pub exit_code: Option<i32>,
+    pub duration_ms: i64,
+    pub output_bytes: i64,
+    pub error: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+/// Start a streaming job; same policy as `ExecuteCommandRequest`
+#[derive(Debug, Deserialize)]
+pub struct StartJobRequest {
+    pub command: String,
+    #[serde(default)]
+    pub args: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub raw: bool,
+    pub timeout_secs: Option<u64>,
+    /// Run in a pseudo-terminal (interactive mode)
+    #[serde(default)]
+    pub pty: bool,
+    pub rows: Option<u16>,
+    pub cols: Option<u16>,
+}
+
+/// Message from the client to a running job (HTTP body or WebSocket text frame)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobControl {
+    /// Bytes for stdin; include "\n" (or "\r" for a PTY) to submit a line
+    Input { data: String },
+    /// Terminal size of a PTY job
+    Resize { rows: u16, cols: u16 },
+    /// Kill the whole process group
+    Kill,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobInputRequest {
+    pub data: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobResizeRequest {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+/// Event streamed to subscribers; `seq` grows by one per event
+#[derive(Debug, Clone, Serialize)]
+pub struct JobEvent {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub kind: JobEventKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobEventKind {
+    /// One line of stdout (pipe mode)
+    Stdout { data: String },
+    /// One line of stderr (pipe mode)
+    Stderr { data: String },
+    /// Raw terminal output (PTY mode)
+    Output { data: String },
+    Exit {
+        exit_code: Option<i32>,
+        timed_out: bool,
+        killed: bool,
+        duration_ms: u128,
+    },
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub command: String,
+    pub mode: String,
+    pub pty: bool,
+    pub actor: String,
+    pub started_at: String,
+    pub running: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StartJobResponse {
+    pub job: JobInfo,
+    /// One-job ticket for `?ticket=` on the stream endpoints (browsers cannot set headers there)
+    pub ticket: String,
+}
@@ -0,0 +1,379 @@
+use crate::console::models::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path};
+use tracing::warn;
+
+/// Longest accepted value of a `name` argument
+const MAX_NAME_LEN: usize = 128;
+
+fn param(name: &str, kind: ArgKind, default: Option<&str>) -> ArgSpec {
+    ArgSpec {
+        name: name.to_string(),
+        kind,
+        description: None,
+        default: default.map(|d| d.to_string()),
+        required: true,
+    }
+}
+
+fn template(name: &str, description: &str, program: &str, args: &[&str], params: Vec<ArgSpec>) -> CommandTemplate {
+    CommandTemplate {
+        name: name.to_string(),
+        description: description.to_string(),
+        program: program.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+        params,
+        timeout_secs: None,
+    }
+}
+
+fn lines_param() -> ArgSpec {
+    param("lines", ArgKind::Integer { min: Some(1), max: Some(5000) }, Some("100"))
+}
+
+/// Commands available out of the box; `CONSOLE_COMMANDS_FILE` can add or override them
+pub fn builtin_templates() -> Vec<CommandTemplate> {
+    vec![
+        template("uptime", "Время работы и нагрузка", "uptime", &[], vec![]),
+        template("disk_usage", "Свободное место на дисках", "df", &["-h"], vec![]),
+        template("memory", "Использование памяти", "free", &["-m"], vec![]),
+        template("processes", "Процессы по потреблению памяти", "ps", &["aux", "--sort=-%mem"], vec![]),
+        template("ports", "Открытые порты", "ss", &["-tulpn"], vec![]),
+        template(
+            "list_dir",
+            "Содержимое каталога в рабочей директории",
+            "ls",
+            &["-la", "--", "{path}"],
+            vec![param("path", ArgKind::Path, Some("."))],
+        ),
+        template(
+            "dir_size",
+            "Размер каталога в рабочей директории",
+            "du",
+            &["-sh", "--", "{path}"],
+            vec![param("path", ArgKind::Path, Some("."))],
+        ),
+        template(
+            "tail_file",
+            "Последние строки файла в рабочей директории",
+            "tail",
+            &["-n", "{lines}", "--", "{path}"],
+            vec![lines_param(), param("path", ArgKind::Path, None)],
+        ),
+        template(
+            "service_status",
+            "Статус systemd-сервиса",
+            "systemctl",
+            &["status", "--no-pager", "--", "{service}"],
+            vec![param("service", ArgKind::Name, None)],
+        ),
+        template(
+            "service_restart",
+            "Перезапуск systemd-сервиса",
+            "systemctl",
+            &["restart", "--", "{service}"],
+            vec![param("service", ArgKind::Name, None)],
+        ),
+        template(
+            "journal",
+            "Журнал systemd-сервиса",
+            "journalctl",
+            &["-u", "{service}", "-n", "{lines}", "--no-pager", "--output=short-iso"],
+            vec![param("service", ArgKind::Name, None), lines_param()],
+        ),
+        template(
+            "ping",
+            "Проверка доступности хоста",
+            "ping",
+            &["-c", "{count}", "--", "{host}"],
+            vec![
+                param("count", ArgKind::Integer { min: Some(1), max: Some(10) }, Some("4")),
+                param("host", ArgKind::Name, None),
+            ],
+        ),
+        template("docker_ps", "Запущенные контейнеры", "docker", &["ps"], vec![]),
+    ]
+}
+
+/// Load extra templates from a JSON array; entries replace built-ins with the same name
+pub fn load_templates(file: Option<&str>) -> Vec<CommandTemplate> {
+    let mut templates = builtin_templates();
+
+    let Some(path) = file else {
+        return templates;
+    };
+
+    let extra: Vec<CommandTemplate> = match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+    {
+        Ok(extra) => extra,
+        Err(e) => {
+            warn!("Failed to load console commands from {}: {}", path, e);
+            return templates;
+        }
+    };
+
+    for t in extra {
+        templates.retain(|existing| existing.name != t.name);
+        templates.push(t);
+    }
+    templates
+}
+
+/// Validate the arguments and build the final argv (program first)
+pub fn render(
+    template: &CommandTemplate,
+    args: &HashMap<String, Value>,
+    workdir: &Path,
+) -> Result<Vec<String>, String> {
+    if let Some(unknown) = args.keys().find(|k| !template.params.iter().any(|p| &p.name == *k)) {
+        return Err(format!("Unknown argument: {}", unknown));
+    }
+
+    // None marks an omitted optional argument
+    let mut values: HashMap<&str, Option<String>> = HashMap::new();
+    for spec in &template.params {
+        let raw = match args.get(&spec.name) {
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(Value::Number(n)) => Some(n.to_string()),
+            Some(Value::Null) | None => spec.default.clone(),
+            Some(_) => return Err(format!("Argument {} must be a string or number", spec.name)),
+        };
+
+        let value = match raw {
+            Some(raw) => Some(validate(spec, &raw, workdir)?),
+            None if spec.required => return Err(format!("Missing argument: {}", spec.name)),
+            None => None,
+        };
+        values.insert(spec.name.as_str(), value);
+    }
+
+    let mut argv = vec![template.program.clone()];
+    'elements: for element in &template.args {
+        let mut rendered = element.clone();
+        for (name, value) in &values {
+            let placeholder = format!("{{{}}}", name);
+            if rendered.contains(&placeholder) {
+                match value {
+                    Some(v) => rendered = rendered.replace(&placeholder, v),
+                    None => continue 'elements,
+                }
+            }
+        }
+        argv.push(rendered);
+    }
+
+    Ok(argv)
+}
+
+fn validate(spec: &ArgSpec, value: &str, workdir: &Path) -> Result<String, String> {
+    match &spec.kind {
+        ArgKind::Integer { min, max } => {
+            let n: i64 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("Argument {} must be an integer", spec.name))?;
+            if min.is_some_and(|min| n < min) || max.is_some_and(|max| n > max) {
+                return Err(format!(
+                    "Argument {} must be between {} and {}",
+                    spec.name,
+                    min.map(|v| v.to_string()).unwrap_or_else(|| "-∞".to_string()),
+                    max.map(|v| v.to_string()).unwrap_or_else(|| "∞".to_string())
+                ));
+            }
+            Ok(n.to_string())
+        }
+        ArgKind::Choice { values } => {
+            if values.iter().any(|v| v == value) {
+                Ok(value.to_string())
+            } else {
+                Err(format!("Argument {} must be one of: {}", spec.name, values.join(", ")))
+            }
+        }
+        ArgKind::Name => {
+            let valid = !value.is_empty()
+                && value.len() <= MAX_NAME_LEN
+                && !value.starts_with('-')
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '@' | ':' | '-'));
+            if valid {
+                Ok(value.to_string())
+            } else {
+                Err(format!("Argument {} contains forbidden characters", spec.name))
+            }
+        }
+        ArgKind::Path => jail_path(value, workdir)
+            .map(|p| p.to_string_lossy().to_string())
+            .ok_or_else(|| format!("Argument {} must be an existing path inside the working directory", spec.name)),
+    }
+}
+
+/// Resolve a relative path inside the jail, following symlinks
+fn jail_path(value: &str, workdir: &Path) -> Option<std::path::PathBuf> {
+    let relative = Path::new(value);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+
+    let resolved = workdir.join(relative).canonicalize().ok()?;
+    resolved.starts_with(workdir).then_some(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    /// Jail with `logs/app.log`, `my dir/` and a sibling directory outside it
+    fn jail() -> (TempDir, PathBuf) {
+        let root = tempfile::tempdir().unwrap();
+        let workdir = root.path().canonicalize().unwrap().join("jail");
+        std::fs::create_dir_all(workdir.join("logs")).unwrap();
+        std::fs::create_dir_all(workdir.join("my dir")).unwrap();
+        std::fs::write(workdir.join("logs/app.log"), "line\n").unwrap();
+        std::fs::create_dir_all(root.path().join("outside")).unwrap();
+        std::fs::write(root.path().join("outside/secret"), "secret\n").unwrap();
+        (root, workdir)
+    }
+
+    fn builtin(name: &str) -> CommandTemplate {
+        builtin_templates().into_iter().find(|t| t.name == name).unwrap()
+    }
+
+    fn args(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn jail_accepts_paths_inside_the_workdir() {
+        let (_root, workdir) = jail();
+        assert_eq!(jail_path(".", &workdir), Some(workdir.clone()));
+        assert_eq!(jail_path("logs/app.log", &workdir), Some(workdir.join("logs/app.log")));
+        assert_eq!(jail_path("./logs/./app.log", &workdir), Some(workdir.join("logs/app.log")));
+        assert_eq!(jail_path("logs/missing.log", &workdir), None);
+    }
+
+    #[test]
+    fn jail_rejects_parent_components() {
+        let (_root, workdir) = jail();
+        for value in ["..", "../outside/secret", "logs/../../outside/secret", "logs/../app.log", "logs/.."] {
+            assert_eq!(jail_path(value, &workdir), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn jail_rejects_absolute_paths() {
+        let (root, workdir) = jail();
+        let outside = root.path().join("outside/secret");
+        for value in ["/etc/passwd", "/", outside.to_str().unwrap()] {
+            assert_eq!(jail_path(value, &workdir), None, "{}", value);
+        }
+        // Even when they point inside the jail
+        assert_eq!(jail_path(workdir.join("logs/app.log").to_str().unwrap(), &workdir), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn jail_rejects_symlink_escapes() {
+        let (root, workdir) = jail();
+        std::os::unix::fs::symlink(root.path().join("outside"), workdir.join("escape")).unwrap();
+        std::os::unix::fs::symlink("/etc/passwd", workdir.join("passwd")).unwrap();
+        std::os::unix::fs::symlink(workdir.join("logs/app.log"), workdir.join("inside")).unwrap();
+
+        assert_eq!(jail_path("escape", &workdir), None);
+        assert_eq!(jail_path("escape/secret", &workdir), None);
+        assert_eq!(jail_path("passwd", &workdir), None);
+        // Links that stay inside resolve to their target
+        assert_eq!(jail_path("inside", &workdir), Some(workdir.join("logs/app.log")));
+    }
+
+    #[test]
+    fn render_builds_argv_with_defaults() {
+        let (_root, workdir) = jail();
+        let argv = render(&builtin("tail_file"), &args(json!({"path": "logs/app.log"})), &workdir).unwrap();
+        let path = workdir.join("logs/app.log").to_string_lossy().to_string();
+        assert_eq!(argv, ["tail", "-n", "100", "--", path.as_str()]);
+
+        let argv = render(&builtin("ping"), &args(json!({"host": "bgalin.ru", "count": 2})), &workdir).unwrap();
+        assert_eq!(argv, ["ping", "-c", "2", "--", "bgalin.ru"]);
+    }
+
+    #[test]
+    fn render_rejects_integers_out_of_range() {
+        let (_root, workdir) = jail();
+        let ping = builtin("ping");
+        for count in [json!(0), json!(11), json!(-1), json!("99999999999999999999"), json!("4; reboot"), json!(1.5)] {
+            let result = render(&ping, &args(json!({"host": "bgalin.ru", "count": count})), &workdir);
+            assert!(result.is_err(), "{}", count);
+        }
+        assert!(render(&ping, &args(json!({"host": "bgalin.ru", "count": " 10 "})), &workdir).is_ok());
+    }
+
+    #[test]
+    fn render_rejects_names_with_shell_metacharacters() {
+        let (_root, workdir) = jail();
+        let status = builtin("service_status");
+        for service in [
+            "nginx; reboot",
+            "nginx && reboot",
+            "nginx|sh",
+            "$(id)",
+            "`id`",
+            "nginx > /etc/passwd",
+            "nginx\nreboot",
+            "nginx reboot",
+            "-H root@host",
+            "--help",
+            "*",
+            "",
+        ] {
+            let result = render(&status, &args(json!({"service": service})), &workdir);
+            assert!(result.is_err(), "{:?}", service);
+        }
+        assert!(render(&status, &args(json!({"service": "x".repeat(MAX_NAME_LEN + 1)})), &workdir).is_err());
+        assert!(render(&status, &args(json!({"service": "getty@tty1.service"})), &workdir).is_ok());
+    }
+
+    #[test]
+    fn render_rejects_unknown_missing_and_non_scalar_arguments() {
+        let (_root, workdir) = jail();
+        let status = builtin("service_status");
+        assert!(render(&status, &args(json!({})), &workdir).is_err());
+        assert!(render(&status, &args(json!({"service": "nginx", "extra": "x"})), &workdir).is_err());
+        assert!(render(&status, &args(json!({"service": ["nginx", "sshd"]})), &workdir).is_err());
+        assert!(render(&status, &args(json!({"service": {"a": 1}})), &workdir).is_err());
+    }
+
+    #[test]
+    fn one_parameter_is_never_split_into_several_arguments() {
+        let (_root, workdir) = jail();
+        let list = builtin("list_dir");
+        let argv = render(&list, &args(json!({"path": "my dir"})), &workdir).unwrap();
+        assert_eq!(argv.len(), 4);
+        assert_eq!(argv[3], workdir.join("my dir").to_string_lossy());
+
+        // A template author packing a parameter into a larger element still gets one element
+        let custom = CommandTemplate {
+            name: "grep".to_string(),
+            description: String::new(),
+            program: "grep".to_string(),
+            args: vec!["--regexp={pattern}".to_string(), "{opt}".to_string()],
+            params: vec![
+                param("pattern", ArgKind::Choice { values: vec!["a b".to_string()] }, None),
+                ArgSpec { required: false, ..param("opt", ArgKind::Name, None) },
+            ],
+            timeout_secs: None,
+        };
+        let argv = render(&custom, &args(json!({"pattern": "a b"})), &workdir).unwrap();
+        assert_eq!(argv, ["grep", "--regexp=a b"]);
+        assert!(render(&custom, &args(json!({"pattern": "a"})), &workdir).is_err());
+    }
+}
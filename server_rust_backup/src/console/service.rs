@@ -0,0 +1,432 @@
+use crate::console::jobs::ConsoleJob;
+use crate::console::models::*;
+use crate::console::policy;
+use crate::db::DbPool;
+use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_MAX_TIMEOUT_SECS: u64 = 300;
+const DEFAULT_MEMORY_LIMIT_MB: u64 = 512;
+const DEFAULT_OUTPUT_LIMIT_KB: usize = 100;
+const DEFAULT_WORKDIR: &str = "./console";
+const DEFAULT_RAW_GRANT_MINUTES: u64 = 10;
+const DEFAULT_PTY_TIMEOUT_SECS: u64 = 3600;
+const DEFAULT_MAX_JOBS: usize = 4;
+
+/// Step-up codes are valid for 5 minutes and allow 5 attempts
+const STEP_UP_CODE_TTL: Duration = Duration::from_secs(300);
+const STEP_UP_MAX_ATTEMPTS: u8 = 5;
+
+/// Search path for template programs; the server environment is not inherited
+const SANDBOX_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// Pending or confirmed raw-mode step-up of one session
+struct StepUp {
+    code_hash: String,
+    code_expires: Instant,
+    attempts: u8,
+    granted_until: Option<Instant>,
+}
+
+/// Output of a finished (or killed) process
+struct ProcessOutput {
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    timed_out: bool,
+    truncated: bool,
+    output_bytes: usize,
+}
+
+/// Runs allowlisted command templates (and, after step-up, raw shell lines) in a jail
+#[derive(Clone)]
+pub struct ConsoleService {
+    templates: Arc<Vec<CommandTemplate>>,
+    limits: Arc<ConsoleLimits>,
+    workdir: Arc<PathBuf>,
+    raw_grant: Duration,
+    step_ups: Arc<RwLock<HashMap<i32, StepUp>>>,
+    pub(crate) jobs: Arc<RwLock<HashMap<String, Arc<ConsoleJob>>>>,
+}
+
+impl ConsoleService {
+    /// Read limits from the environment and prepare the working directory
+    pub fn init() -> std::io::Result<Self> {
+        let workdir = env::var("CONSOLE_WORKDIR").unwrap_or_else(|_| DEFAULT_WORKDIR.to_string());
+        std::fs::create_dir_all(&workdir)?;
+        let workdir = std::fs::canonicalize(&workdir)?;
+
+        let max_timeout_secs = env::var("CONSOLE_MAX_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_TIMEOUT_SECS);
+
+        let limits = ConsoleLimits {
+            default_timeout_secs: DEFAULT_TIMEOUT_SECS.min(max_timeout_secs),
+            max_timeout_secs,
+            memory_limit_mb: env::var("CONSOLE_MEMORY_LIMIT_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MEMORY_LIMIT_MB),
+            output_limit_bytes: env::var("CONSOLE_OUTPUT_LIMIT_KB")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(DEFAULT_OUTPUT_LIMIT_KB)
+                * 1024,
+            workdir: workdir.to_string_lossy().to_string(),
+            raw_mode_enabled: env::var("CONSOLE_RAW_MODE")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            pty_timeout_secs: env::var("CONSOLE_PTY_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_PTY_TIMEOUT_SECS),
+            max_jobs: env::var("CONSOLE_MAX_JOBS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_JOBS),
+        };
+
+        let raw_grant_minutes = env::var("CONSOLE_RAW_GRANT_MINUTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RAW_GRANT_MINUTES);
+
+        let templates = policy::load_templates(env::var("CONSOLE_COMMANDS_FILE").ok().as_deref());
+
+        Ok(Self {
+            templates: Arc::new(templates),
+            limits: Arc::new(limits),
+            workdir: Arc::new(workdir),
+            raw_grant: Duration::from_secs(raw_grant_minutes * 60),
+            step_ups: Arc::new(RwLock::new(HashMap::new())),
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    pub fn templates(&self) -> Vec<CommandTemplate> {
+        self.templates.to_vec()
+    }
+
+    pub fn limits(&self) -> ConsoleLimits {
+        (*self.limits).clone()
+    }
+
+    pub fn raw_mode_enabled(&self) -> bool {
+        self.limits.raw_mode_enabled
+    }
+
+    /// Build argv for a named template; errors describe the rejected argument
+    pub fn prepare(&self, name: &str, args: &HashMap<String, serde_json::Value>) -> Result<(Vec<String>, Option<u64>), String> {
+        let template = self
+            .templates
+            .iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| format!("Unknown command: {}", name))?;
+
+        let argv = policy::render(template, args, &self.workdir)?;
+        Ok((argv, template.timeout_secs))
+    }
+
+    /// Requested timeout clamped to the limit; interactive PTY sessions get a longer one
+    pub(crate) fn effective_timeout(&self, timeout_secs: Option<u64>, pty: bool) -> Duration {
+        let (default, max) = if pty {
+            (self.limits.pty_timeout_secs, self.limits.pty_timeout_secs)
+        } else {
+            (self.limits.default_timeout_secs, self.limits.max_timeout_secs)
+        };
+        Duration::from_secs(timeout_secs.unwrap_or(default).clamp(1, max.max(1)))
+    }
+
+    /// Run argv without a shell under the configured limits
+    pub async fn run(&self, argv: &[String], timeout_secs: Option<u64>) -> CommandResult {
+        let timeout = self.effective_timeout(timeout_secs, false);
+        let start = Instant::now();
+
+        match self.spawn_limited(argv, timeout).await {
+            Ok(output) => CommandResult {
+                stdout: output.stdout,
+                stderr: output.stderr,
+                exit_code: output.exit_code,
+                execution_time_ms: start.elapsed().as_millis(),
+                success: output.exit_code == Some(0) && !output.timed_out,
+                timed_out: output.timed_out,
+                truncated: output.truncated,
+                output_bytes: output.output_bytes,
+            },
+            Err(e) => CommandResult {
+                execution_time_ms: start.elapsed().as_millis(),
+                ..CommandResult::rejected(e)
+            },
+        }
+    }
+
+    /// Command for argv jailed to the working directory with rlimits and its own session.
+    /// With `pty` the child also takes its stdin as the controlling terminal.
+    pub(crate) fn sandboxed_command(&self, argv: &[String], timeout: Duration, pty: bool) -> Result<Command, String> {
+        let (program, args) = argv.split_first().ok_or("Empty command")?;
+
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .current_dir(self.workdir.as_path())
+            .env_clear()
+            .env("PATH", SANDBOX_PATH)
+            .env("HOME", self.workdir.as_os_str())
+            .env("LANG", "C.UTF-8")
+            .kill_on_drop(true);
+
+        let memory_bytes = self.limits.memory_limit_mb.saturating_mul(1024 * 1024);
+        let cpu_secs = timeout.as_secs() + 1;
+        // SAFETY: only async-signal-safe libc calls between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                // New session and process group so a timeout kills the whole tree
+                libc::setsid();
+                if pty {
+                    libc::ioctl(0, libc::TIOCSCTTY, 0);
+                }
+                let limit = |resource, value: u64| {
+                    let rlim = libc::rlimit {
+                        rlim_cur: value as libc::rlim_t,
+                        rlim_max: value as libc::rlim_t,
+                    };
+                    libc::setrlimit(resource, &rlim);
+                };
+                limit(libc::RLIMIT_AS, memory_bytes);
+                limit(libc::RLIMIT_CPU, cpu_secs);
+                limit(libc::RLIMIT_CORE, 0);
+                Ok(())
+            });
+        }
+
+        Ok(command)
+    }
+
+    async fn spawn_limited(&self, argv: &[String], timeout: Duration) -> Result<ProcessOutput, String> {
+        let mut command = self.sandboxed_command(argv, timeout, false)?;
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to spawn {}: {}", argv[0], e))?;
+        let pid = child.id();
+
+        let limit = self.limits.output_limit_bytes;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        let collect = async {
+            let (out, err) = tokio::join!(read_capped(stdout, limit), read_capped(stderr, limit));
+            let status = child.wait().await.map_err(|e| format!("Wait error: {}", e))?;
+            Ok::<_, String>((out, err, status.code()))
+        };
+
+        match tokio::time::timeout(timeout, collect).await {
+            Ok(Ok(((stdout, out_total), (stderr, err_total), exit_code))) => Ok(ProcessOutput {
+                truncated: out_total > limit || err_total > limit,
+                output_bytes: out_total + err_total,
+                stdout,
+                stderr,
+                exit_code,
+                timed_out: false,
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => {
+                kill_process_group(pid);
+                Ok(ProcessOutput {
+                    stdout: String::new(),
+                    stderr: "Command timed out".to_string(),
+                    exit_code: None,
+                    timed_out: true,
+                    truncated: false,
+                    output_bytes: 0,
+                })
+            }
+        }
+    }
+
+    /// Start a raw-mode step-up for a session; returns the code to deliver out of band
+    pub fn start_step_up(&self, session_id: i32) -> String {
+        let code = crate::auth::AuthService::generate_otp();
+        self.step_ups.write().insert(
+            session_id,
+            StepUp {
+                code_hash: hash_code(&code),
+                code_expires: Instant::now() + STEP_UP_CODE_TTL,
+                attempts: 0,
+                granted_until: None,
+            },
+        );
+        code
+    }
+
+    /// Confirm a step-up code; on success raw mode is granted to the session for a while
+    pub fn confirm_step_up(&self, session_id: i32, code: &str) -> Result<Duration, String> {
+        let mut step_ups = self.step_ups.write();
+        let step_up = step_ups
+            .get_mut(&session_id)
+            .ok_or("No pending confirmation, request a code first")?;
+
+        if step_up.code_expires < Instant::now() || step_up.attempts >= STEP_UP_MAX_ATTEMPTS {
+            step_ups.remove(&session_id);
+            return Err("Code expired, request a new one".to_string());
+        }
+
+        step_up.attempts += 1;
+        if step_up.code_hash != hash_code(code.trim()) {
+            return Err("Invalid code".to_string());
+        }
+
+        // A code is single-use
+        step_up.code_expires = Instant::now();
+        step_up.granted_until = Some(Instant::now() + self.raw_grant);
+        Ok(self.raw_grant)
+    }
+
+    /// Time left on the raw-mode grant of a session
+    pub fn raw_grant_remaining(&self, session_id: i32) -> Option<Duration> {
+        self.step_ups
+            .read()
+            .get(&session_id)
+            .and_then(|s| s.granted_until)
+            .and_then(|until| until.checked_duration_since(Instant::now()))
+    }
+
+    /// Record a run (or a rejected attempt) in `console_runs`
+    pub async fn record(pool: &DbPool, run: NewConsoleRun) -> Result<(), sqlx::Error> {
+        let argv = run
+            .argv
+            .as_ref()
+            .and_then(|argv| serde_json::to_string(argv).ok());
+
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO console_runs (actor, user_id, mode, command, argv, status, exit_code, duration_ms, output_bytes, error, ip_address)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                )
+                .bind(&run.actor)
+                .bind(run.user_id)
+                .bind(run.mode)
+                .bind(&run.command)
+                .bind(&argv)
+                .bind(run.status)
+                .bind(run.exit_code)
+                .bind(run.duration_ms)
+                .bind(run.output_bytes)
+                .bind(&run.error)
+                .bind(&run.ip_address)
+                .execute(p)
+                .await?;
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO console_runs (actor, user_id, mode, command, argv, status, exit_code, duration_ms, output_bytes, error, ip_address)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                    "#,
+                )
+                .bind(&run.actor)
+                .bind(run.user_id)
+                .bind(run.mode)
+                .bind(&run.command)
+                .bind(&argv)
+                .bind(run.status)
+                .bind(run.exit_code)
+                .bind(run.duration_ms)
+                .bind(run.output_bytes)
+                .bind(&run.error)
+                .bind(&run.ip_address)
+                .execute(p)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recent runs, newest first
+    pub async fn list_runs(pool: &DbPool, limit: i64, offset: i64) -> Result<Vec<ConsoleRun>, sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, ConsoleRun>(
+                    r#"
+                    SELECT id, actor, user_id, mode, command, argv, status, exit_code, duration_ms, output_bytes, error, ip_address, created_at
+                    FROM console_runs ORDER BY id DESC LIMIT ? OFFSET ?
+                    "#,
+                )
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(p)
+                .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, ConsoleRun>(
+                    r#"
+                    SELECT id, actor, user_id, mode, command, argv, status, exit_code, duration_ms, output_bytes, error, ip_address, created_at::TEXT
+                    FROM console_runs ORDER BY id DESC LIMIT $1 OFFSET $2
+                    "#,
+                )
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(p)
+                .await
+            }
+        }
+    }
+}
+
+/// Read a pipe to the end, keeping at most `limit` bytes; returns the kept text and the total size
+async fn read_capped<R: AsyncRead + Unpin>(reader: Option<R>, limit: usize) -> (String, usize) {
+    let Some(mut reader) = reader else {
+        return (String::new(), 0);
+    };
+
+    let mut kept = Vec::new();
+    let mut total = 0;
+    let mut buf = [0u8; 8192];
+    // Keep draining past the limit so the process never blocks on a full pipe
+    while let Ok(n) = reader.read(&mut buf).await {
+        if n == 0 {
+            break;
+        }
+        let room = limit.saturating_sub(kept.len());
+        kept.extend_from_slice(&buf[..n.min(room)]);
+        total += n;
+    }
+
+    let mut text = String::from_utf8_lossy(&kept).to_string();
+    if total > kept.len() {
+        text.push_str(&format!("... [truncated, {} more bytes]", total - kept.len()));
+    }
+    (text, total)
+}
+
+/// Kill the process group started by `sandboxed_command`
+pub(crate) fn kill_process_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        // SAFETY: signalling the process group created in pre_exec
+        unsafe {
+            libc::kill(-(pid as i32), libc::SIGKILL);
+        }
+    }
+}
+
+pub(crate) fn hash_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.as_bytes()))
+}
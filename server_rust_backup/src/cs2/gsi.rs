@@ -78,6 +78,23 @@ impl GSIPayload {
         false
     }
 
+    /// "win", "loss" or "draw" for the player's team by the map score
+    pub fn match_result(&self) -> Option<&'static str> {
+        let map = self.map.as_ref()?;
+        let ct = map.team_ct.as_ref().map(|t| t.score).unwrap_or(0);
+        let t = map.team_t.as_ref().map(|t| t.score).unwrap_or(0);
+        let (mine, theirs) = match self.player.as_ref()?.team.as_deref()? {
+            "CT" => (ct, t),
+            "T" => (t, ct),
+            _ => return None,
+        };
+        Some(match mine.cmp(&theirs) {
+            std::cmp::Ordering::Greater => "win",
+            std::cmp::Ordering::Less => "loss",
+            std::cmp::Ordering::Equal => "draw",
+        })
+    }
+
     pub fn get_all_steamids(&self) -> Vec<String> {
         let mut steamids = Vec::new();
 
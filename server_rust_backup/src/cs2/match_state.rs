@@ -12,6 +12,8 @@ pub struct MatchState {
     pub map_name: Option<String>,
     pub mode: Option<String>,
     pub round: Option<u32>,
+    /// Map phase: "warmup", "live", "intermission" or "gameover"
+    pub phase: Option<String>,
     pub ct_score: u32,
     pub t_score: u32,
     pub players: HashMap<String, PlayerStats>,
@@ -28,6 +30,7 @@ impl Default for MatchState {
             map_name: None,
             mode: None,
             round: None,
+            phase: None,
             ct_score: 0,
             t_score: 0,
             players: HashMap::new(),
@@ -49,8 +52,10 @@ impl MatchStateManager {
         }
     }
 
-    pub fn update_from_gsi(&self, gsi: &GSIPayload, my_steamid: &str) {
+    /// Apply a GSI update; true when it is the one that ended the match
+    pub fn update_from_gsi(&self, gsi: &GSIPayload, my_steamid: &str) -> bool {
         let mut state = self.state.write();
+        let mut ended = false;
 
         // Update basic match info
         state.is_active = gsi.provider.is_some();
@@ -61,6 +66,8 @@ impl MatchStateManager {
             state.map_name = map.name.clone();
             state.mode = map.mode.clone();
             state.round = map.round;
+            ended = map.phase.as_deref() == Some("gameover") && state.phase.as_deref() != Some("gameover");
+            state.phase = map.phase.clone();
             state.ct_score = map.team_ct.as_ref().map(|t| t.score).unwrap_or(0);
             state.t_score = map.team_t.as_ref().map(|t| t.score).unwrap_or(0);
         }
@@ -88,6 +95,8 @@ impl MatchStateManager {
                 }
             }
         }
+
+        ended
     }
 
     pub fn add_player_stats(&self, steamid: String, stats: PlayerStats) {
@@ -1,6 +1,27 @@
 use sqlx::{sqlite::SqlitePoolOptions, postgres::PgPoolOptions, SqlitePool, PgPool, Pool, Sqlite, Postgres};
 use std::time::Duration;
 
+/// Built-in Alice devices moved onto the device registry: (id, capabilities, handler)
+const REGISTRY_DEFAULT_DEVICES: &[(&str, &str, &str)] = &[
+    (
+        "pc-control",
+        r#"[{"type": "devices.capabilities.on_off", "retrievable": true, "reportable": true}]"#,
+        r#"{"type": "pc_power"}"#,
+    ),
+    (
+        "telegram-bot",
+        r#"[{"type": "devices.capabilities.toggle", "retrievable": true, "reportable": false, "parameters": {"instance": "mute"}}]"#,
+        r#"{"type": "telegram", "template": "Привет! Это сообщение от Алисы!"}"#,
+    ),
+    (
+        "website-notify",
+        r#"[{"type": "devices.capabilities.toggle", "retrievable": true, "reportable": false, "parameters": {"instance": "mute"}}]"#,
+        r#"{"type": "site_notification", "template": "Уведомление от Алисы!"}"#,
+    ),
+];
+
+pub mod transfer;
+
 /// Database type enum for selecting backend
 #[derive(Debug, Clone)]
 pub enum DatabaseType {
@@ -162,6 +183,198 @@ async fn run_sqlite_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    // Session metadata, sliding expiry and revocation
+    sqlx::query("ALTER TABLE sessions ADD COLUMN ip_address TEXT")
+        .execute(pool)
+        .await
+        .ok();
+    sqlx::query("ALTER TABLE sessions ADD COLUMN user_agent TEXT")
+        .execute(pool)
+        .await
+        .ok();
+    sqlx::query("ALTER TABLE sessions ADD COLUMN last_seen_at DATETIME")
+        .execute(pool)
+        .await
+        .ok();
+    sqlx::query("ALTER TABLE sessions ADD COLUMN absolute_expires_at DATETIME")
+        .execute(pool)
+        .await
+        .ok();
+    sqlx::query("ALTER TABLE sessions ADD COLUMN revoked_at DATETIME")
+        .execute(pool)
+        .await
+        .ok();
+
+    // Sessions created before token hashing stored the raw token; drop them
+    sqlx::query("DELETE FROM sessions WHERE last_seen_at IS NULL")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sessions_user_ip ON sessions(user_id, ip_address)")
+        .execute(pool)
+        .await?;
+
+    // Personal access tokens for automation
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS api_tokens (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            token_hash TEXT UNIQUE NOT NULL,
+            token_prefix TEXT NOT NULL,
+            scopes TEXT NOT NULL,
+            allowed_ips TEXT,
+            expires_at DATETIME,
+            last_used_at DATETIME,
+            last_used_ip TEXT,
+            revoked_at DATETIME,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_api_tokens_hash ON api_tokens(token_hash)")
+        .execute(pool)
+        .await?;
+
+    // Audit log of admin writes
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            actor TEXT NOT NULL,
+            actor_user_id INTEGER,
+            module TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target_id TEXT,
+            method TEXT NOT NULL,
+            path TEXT NOT NULL,
+            before_data TEXT,
+            after_data TEXT,
+            diff TEXT,
+            ip_address TEXT,
+            status INTEGER NOT NULL,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_module ON audit_log(module)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_target ON audit_log(module, target_id)")
+        .execute(pool)
+        .await?;
+
+    // Console run history
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS console_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            actor TEXT NOT NULL,
+            user_id INTEGER,
+            mode TEXT NOT NULL,
+            command TEXT NOT NULL,
+            argv TEXT,
+            status TEXT NOT NULL,
+            exit_code INTEGER,
+            duration_ms BIGINT NOT NULL DEFAULT 0,
+            output_bytes BIGINT NOT NULL DEFAULT 0,
+            error TEXT,
+            ip_address TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Host metrics history; one row per metric, resolution and bucket
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS metric_samples (
+            metric TEXT NOT NULL,
+            resolution INTEGER NOT NULL,
+            bucket_start BIGINT NOT NULL,
+            avg_value DOUBLE PRECISION NOT NULL,
+            min_value DOUBLE PRECISION NOT NULL,
+            max_value DOUBLE PRECISION NOT NULL,
+            samples INTEGER NOT NULL DEFAULT 1
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_metric_samples_bucket ON metric_samples(metric, resolution, bucket_start)",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS metric_alert_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            metric TEXT NOT NULL,
+            operator TEXT NOT NULL,
+            threshold DOUBLE PRECISION NOT NULL,
+            duration_secs BIGINT NOT NULL DEFAULT 300,
+            enabled BOOLEAN NOT NULL DEFAULT TRUE,
+            last_triggered_at DATETIME,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Database viewer: saved queries and execution history
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS db_saved_queries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            query TEXT NOT NULL,
+            description TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS db_query_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            query TEXT NOT NULL,
+            params TEXT,
+            row_count BIGINT,
+            execution_time_ms BIGINT NOT NULL DEFAULT 0,
+            error TEXT,
+            actor TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
     // Portfolio tables
     sqlx::query(
         r#"
@@ -1517,6 +1730,155 @@ async fn run_sqlite_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    // === Alice Command Queue for PC Client ===
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS alice_command_queue (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            command_type TEXT NOT NULL,
+            command_data TEXT NOT NULL,
+            priority INTEGER NOT NULL DEFAULT 0,
+            status TEXT NOT NULL DEFAULT 'pending',
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            processed_at DATETIME,
+            result TEXT,
+            error TEXT,
+            protocol_version INTEGER NOT NULL DEFAULT 1,
+            nonce TEXT,
+            payload TEXT,
+            signature TEXT,
+            expires_at DATETIME,
+            target_client_id TEXT,
+            target_group TEXT,
+            claimed_by TEXT
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_alice_queue_status ON alice_command_queue(status)")
+        .execute(pool)
+        .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_alice_queue_priority ON alice_command_queue(priority DESC, created_at ASC)")
+        .execute(pool)
+        .await?;
+
+    // Delivery attempts, visibility timeout and retry backoff
+    sqlx::query("ALTER TABLE alice_command_queue ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0")
+        .execute(pool)
+        .await
+        .ok();
+    sqlx::query("ALTER TABLE alice_command_queue ADD COLUMN max_attempts INTEGER NOT NULL DEFAULT 3")
+        .execute(pool)
+        .await
+        .ok();
+    sqlx::query("ALTER TABLE alice_command_queue ADD COLUMN claimed_at DATETIME")
+        .execute(pool)
+        .await
+        .ok();
+    sqlx::query("ALTER TABLE alice_command_queue ADD COLUMN available_at DATETIME")
+        .execute(pool)
+        .await
+        .ok();
+    // Stored file uploaded by the client as the command's result, e.g. a screenshot
+    sqlx::query("ALTER TABLE alice_command_queue ADD COLUMN artifact_file_id TEXT")
+        .execute(pool)
+        .await
+        .ok();
+
+    // === Alice PC Client Registration ===
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS alice_pc_clients (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            client_id TEXT UNIQUE NOT NULL,
+            client_name TEXT NOT NULL,
+            api_key TEXT UNIQUE NOT NULL,
+            is_online BOOLEAN NOT NULL DEFAULT FALSE,
+            last_seen DATETIME,
+            mac_address TEXT,
+            ip_address TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            protocol_version INTEGER,
+            capabilities TEXT,
+            client_group TEXT
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_alice_pc_clients_key ON alice_pc_clients(api_key)")
+        .execute(pool)
+        .await?;
+
+    // === Alice PC Command Schedules ===
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS alice_pc_schedules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            command_data TEXT NOT NULL,
+            priority INTEGER NOT NULL DEFAULT 0,
+            target_client_id TEXT,
+            target_group TEXT,
+            max_attempts INTEGER,
+            cron_expr TEXT,
+            run_at DATETIME,
+            delay_secs INTEGER,
+            is_paused BOOLEAN NOT NULL DEFAULT FALSE,
+            alice_scene BOOLEAN NOT NULL DEFAULT FALSE,
+            last_run_at DATETIME,
+            last_command_id INTEGER,
+            last_error TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // === Alice Automation Rules ===
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS alice_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            trigger_type TEXT NOT NULL,
+            trigger_data TEXT NOT NULL,
+            conditions TEXT NOT NULL,
+            actions TEXT NOT NULL,
+            is_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+            last_run_at DATETIME,
+            last_status TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS alice_rule_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            rule_id INTEGER NOT NULL,
+            event TEXT NOT NULL,
+            dry_run BOOLEAN NOT NULL DEFAULT FALSE,
+            status TEXT NOT NULL,
+            steps TEXT NOT NULL,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_alice_rule_runs_rule ON alice_rule_runs(rule_id, id)")
+        .execute(pool)
+        .await?;
+
     // Insert default Alice devices
     sqlx::query(
         r#"
@@ -1532,6 +1894,20 @@ async fn run_sqlite_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Device registry: full Yandex schemas and a handler per device
+    sqlx::query("ALTER TABLE alice_devices ADD COLUMN handler TEXT")
+        .execute(pool)
+        .await
+        .ok();
+    for (id, capabilities, handler) in REGISTRY_DEFAULT_DEVICES {
+        sqlx::query("UPDATE alice_devices SET capabilities = ?, properties = NULL, handler = ? WHERE id = ? AND handler IS NULL")
+            .bind(capabilities)
+            .bind(handler)
+            .bind(id)
+            .execute(pool)
+            .await?;
+    }
+
     // Insert default Alice user (admin:admin)
     sqlx::query(
         r#"
@@ -1542,6 +1918,29 @@ async fn run_sqlite_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // === Notification Center ===
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS notifications (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            category TEXT NOT NULL,
+            priority TEXT NOT NULL DEFAULT 'normal',
+            title TEXT NOT NULL,
+            message TEXT NOT NULL DEFAULT '',
+            link TEXT,
+            data TEXT,
+            read_at DATETIME,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_notifications_category ON notifications(category, id)")
+        .execute(pool)
+        .await?;
+
     Ok(())
 }
 
@@ -1598,6 +1997,189 @@ async fn run_postgres_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    // Session metadata, sliding expiry and revocation
+    sqlx::query(
+        r#"
+        ALTER TABLE sessions
+            ADD COLUMN IF NOT EXISTS ip_address TEXT,
+            ADD COLUMN IF NOT EXISTS user_agent TEXT,
+            ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS absolute_expires_at TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS revoked_at TIMESTAMPTZ
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Sessions created before token hashing stored the raw token; drop them
+    sqlx::query("DELETE FROM sessions WHERE last_seen_at IS NULL")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sessions_user_ip ON sessions(user_id, ip_address)")
+        .execute(pool)
+        .await?;
+
+    // Personal access tokens for automation
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS api_tokens (
+            id SERIAL PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id),
+            name TEXT NOT NULL,
+            token_hash TEXT UNIQUE NOT NULL,
+            token_prefix TEXT NOT NULL,
+            scopes TEXT NOT NULL,
+            allowed_ips TEXT,
+            expires_at TIMESTAMPTZ,
+            last_used_at TIMESTAMPTZ,
+            last_used_ip TEXT,
+            revoked_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_api_tokens_hash ON api_tokens(token_hash)")
+        .execute(pool)
+        .await?;
+
+    // Audit log of admin writes
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id SERIAL PRIMARY KEY,
+            actor TEXT NOT NULL,
+            actor_user_id INTEGER,
+            module TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target_id TEXT,
+            method TEXT NOT NULL,
+            path TEXT NOT NULL,
+            before_data TEXT,
+            after_data TEXT,
+            diff TEXT,
+            ip_address TEXT,
+            status INTEGER NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_module ON audit_log(module)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_target ON audit_log(module, target_id)")
+        .execute(pool)
+        .await?;
+
+    // Console run history
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS console_runs (
+            id SERIAL PRIMARY KEY,
+            actor TEXT NOT NULL,
+            user_id INTEGER,
+            mode TEXT NOT NULL,
+            command TEXT NOT NULL,
+            argv TEXT,
+            status TEXT NOT NULL,
+            exit_code INTEGER,
+            duration_ms BIGINT NOT NULL DEFAULT 0,
+            output_bytes BIGINT NOT NULL DEFAULT 0,
+            error TEXT,
+            ip_address TEXT,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Host metrics history; one row per metric, resolution and bucket
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS metric_samples (
+            metric TEXT NOT NULL,
+            resolution INTEGER NOT NULL,
+            bucket_start BIGINT NOT NULL,
+            avg_value DOUBLE PRECISION NOT NULL,
+            min_value DOUBLE PRECISION NOT NULL,
+            max_value DOUBLE PRECISION NOT NULL,
+            samples INTEGER NOT NULL DEFAULT 1
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_metric_samples_bucket ON metric_samples(metric, resolution, bucket_start)",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS metric_alert_rules (
+            id SERIAL PRIMARY KEY,
+            name TEXT NOT NULL,
+            metric TEXT NOT NULL,
+            operator TEXT NOT NULL,
+            threshold DOUBLE PRECISION NOT NULL,
+            duration_secs BIGINT NOT NULL DEFAULT 300,
+            enabled BOOLEAN NOT NULL DEFAULT TRUE,
+            last_triggered_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Database viewer: saved queries and execution history
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS db_saved_queries (
+            id SERIAL PRIMARY KEY,
+            name TEXT NOT NULL,
+            query TEXT NOT NULL,
+            description TEXT,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS db_query_history (
+            id SERIAL PRIMARY KEY,
+            kind TEXT NOT NULL,
+            query TEXT NOT NULL,
+            params TEXT,
+            row_count BIGINT,
+            execution_time_ms BIGINT NOT NULL DEFAULT 0,
+            error TEXT,
+            actor TEXT,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
     // Portfolio tables
     sqlx::query(
         r#"
@@ -1846,6 +2428,19 @@ async fn run_postgres_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Device registry: full Yandex schemas and a handler per device
+    sqlx::query("ALTER TABLE alice_devices ADD COLUMN IF NOT EXISTS handler TEXT")
+        .execute(pool)
+        .await?;
+    for (id, capabilities, handler) in REGISTRY_DEFAULT_DEVICES {
+        sqlx::query("UPDATE alice_devices SET capabilities = $1, properties = NULL, handler = $2 WHERE id = $3 AND handler IS NULL")
+            .bind(capabilities)
+            .bind(handler)
+            .bind(id)
+            .execute(pool)
+            .await?;
+    }
+
     // Insert default Alice user
     sqlx::query(
         r#"
@@ -1916,6 +2511,134 @@ async fn run_postgres_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    // Protocol version and capabilities announced in the PC client heartbeat
+    sqlx::query(
+        r#"
+        ALTER TABLE alice_pc_clients
+            ADD COLUMN IF NOT EXISTS protocol_version INTEGER,
+            ADD COLUMN IF NOT EXISTS capabilities JSONB
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("ALTER TABLE alice_command_queue ADD COLUMN IF NOT EXISTS protocol_version INTEGER NOT NULL DEFAULT 1")
+        .execute(pool)
+        .await?;
+
+    // Ed25519-signed command payloads with nonce and expiry
+    sqlx::query(
+        r#"
+        ALTER TABLE alice_command_queue
+            ADD COLUMN IF NOT EXISTS nonce TEXT,
+            ADD COLUMN IF NOT EXISTS payload TEXT,
+            ADD COLUMN IF NOT EXISTS signature TEXT,
+            ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Per-client / per-group command targeting and the client that claimed each command
+    sqlx::query(
+        r#"
+        ALTER TABLE alice_command_queue
+            ADD COLUMN IF NOT EXISTS target_client_id TEXT,
+            ADD COLUMN IF NOT EXISTS target_group TEXT,
+            ADD COLUMN IF NOT EXISTS claimed_by TEXT
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("ALTER TABLE alice_pc_clients ADD COLUMN IF NOT EXISTS client_group TEXT")
+        .execute(pool)
+        .await?;
+
+    // Delivery attempts, visibility timeout and retry backoff
+    sqlx::query(
+        r#"
+        ALTER TABLE alice_command_queue
+            ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS max_attempts INTEGER NOT NULL DEFAULT 3,
+            ADD COLUMN IF NOT EXISTS claimed_at TIMESTAMPTZ,
+            ADD COLUMN IF NOT EXISTS available_at TIMESTAMPTZ
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Stored file uploaded by the client as the command's result, e.g. a screenshot
+    sqlx::query("ALTER TABLE alice_command_queue ADD COLUMN IF NOT EXISTS artifact_file_id TEXT")
+        .execute(pool)
+        .await?;
+
+    // === Alice PC Command Schedules ===
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS alice_pc_schedules (
+            id SERIAL PRIMARY KEY,
+            name TEXT NOT NULL,
+            command_data JSONB NOT NULL,
+            priority INTEGER NOT NULL DEFAULT 0,
+            target_client_id TEXT,
+            target_group TEXT,
+            max_attempts INTEGER,
+            cron_expr TEXT,
+            run_at TIMESTAMPTZ,
+            delay_secs BIGINT,
+            is_paused BOOLEAN NOT NULL DEFAULT FALSE,
+            alice_scene BOOLEAN NOT NULL DEFAULT FALSE,
+            last_run_at TIMESTAMPTZ,
+            last_command_id BIGINT,
+            last_error TEXT,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // === Alice Automation Rules ===
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS alice_rules (
+            id SERIAL PRIMARY KEY,
+            name TEXT NOT NULL,
+            trigger_type TEXT NOT NULL,
+            trigger_data JSONB NOT NULL,
+            conditions JSONB NOT NULL,
+            actions JSONB NOT NULL,
+            is_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+            last_run_at TIMESTAMPTZ,
+            last_status TEXT,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS alice_rule_runs (
+            id SERIAL PRIMARY KEY,
+            rule_id INTEGER NOT NULL REFERENCES alice_rules(id) ON DELETE CASCADE,
+            event JSONB NOT NULL,
+            dry_run BOOLEAN NOT NULL DEFAULT FALSE,
+            status TEXT NOT NULL,
+            steps JSONB NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_alice_rule_runs_rule ON alice_rule_runs(rule_id, id)")
+        .execute(pool)
+        .await?;
+
     // === Job Search System (Remaining Tables) ===
     sqlx::query(
         r#"
@@ -2815,5 +3538,28 @@ async fn run_postgres_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // === Notification Center ===
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS notifications (
+            id SERIAL PRIMARY KEY,
+            category TEXT NOT NULL,
+            priority TEXT NOT NULL DEFAULT 'normal',
+            title TEXT NOT NULL,
+            message TEXT NOT NULL DEFAULT '',
+            link TEXT,
+            data JSONB,
+            read_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_notifications_category ON notifications(category, id)")
+        .execute(pool)
+        .await?;
+
     Ok(())
 }
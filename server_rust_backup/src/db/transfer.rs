@@ -0,0 +1,908 @@
+use crate::db::{create_pool, DbPool};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{Row, TypeInfo, ValueRef};
+use std::collections::{BTreeMap, HashMap};
+use tracing::{info, warn};
+
+const PROGRESS_TABLE: &str = "db_transfer_progress";
+const DEFAULT_BATCH_SIZE: i64 = 500;
+/// Bound parameters per INSERT, below the limits of both backends
+const MAX_PARAMS: usize = 30000;
+
+pub struct TransferOptions {
+    pub source_url: String,
+    pub target_url: String,
+    pub batch_size: i64,
+    /// Only compare the two databases
+    pub verify_only: bool,
+}
+
+#[derive(Debug)]
+pub struct TableCheck {
+    pub table: String,
+    pub source_rows: i64,
+    pub target_rows: i64,
+    pub checksums_match: bool,
+}
+
+/// Value read from either backend
+#[derive(Debug, Clone)]
+enum Cell {
+    Null,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+/// How a column's values are converted and compared, from its declared type
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Bool,
+    Int,
+    Float,
+    Timestamp,
+    TimestampTz,
+    Bytes,
+    Text,
+}
+
+impl Kind {
+    fn from_declared(declared: &str) -> Kind {
+        let t = declared.to_lowercase();
+        if t.contains("bool") {
+            Kind::Bool
+        } else if t.contains("int") || t.contains("serial") {
+            Kind::Int
+        } else if t.contains("real") || t.contains("double") || t.contains("float") {
+            Kind::Float
+        } else if t.contains("with time zone") || t == "timestamptz" {
+            Kind::TimestampTz
+        } else if t.contains("timestamp") || t.contains("datetime") {
+            Kind::Timestamp
+        } else if t.contains("bytea") || t.contains("blob") {
+            Kind::Bytes
+        } else {
+            Kind::Text
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ColumnDef {
+    name: String,
+    declared: String,
+    kind: Kind,
+}
+
+/// A table as both sides see it: common columns, source types for reading, target types for writing
+struct TablePlan {
+    name: String,
+    source: Vec<ColumnDef>,
+    target: Vec<ColumnDef>,
+    paging: Paging,
+}
+
+/// How the source is walked in batches; the cursor is the last key or the rows already read
+#[derive(Debug, Clone)]
+enum Paging {
+    /// SQLite rowid or a single integer primary key
+    Key(String),
+    Offset,
+}
+
+impl Paging {
+    /// Cursor of a table nothing was read from yet; keys may be zero or negative
+    fn start(&self) -> i64 {
+        match self {
+            Paging::Key(_) => i64::MIN,
+            Paging::Offset => 0,
+        }
+    }
+}
+
+/// Copy every table from the source backend into the target one and verify the result.
+///
+/// The target gets its schema from the regular migrations. Each batch is inserted together with its cursor
+/// in `db_transfer_progress`, so an interrupted run continues where it stopped; tables not started yet are
+/// cleared first. Tables are then compared by row count and an order-independent checksum of normalized rows.
+pub async fn run(options: &TransferOptions) -> Result<Vec<TableCheck>, String> {
+    let source = create_pool(&options.source_url)
+        .await
+        .map_err(|e| format!("Cannot open source: {}", e))?;
+    let target = create_pool(&options.target_url)
+        .await
+        .map_err(|e| format!("Cannot open target: {}", e))?;
+    if std::mem::discriminant(&source) == std::mem::discriminant(&target) {
+        warn!("Source and target use the same backend");
+    }
+
+    let plans = plan_tables(&source, &target).await.map_err(db_error)?;
+    let batch_size = options.batch_size.max(1);
+
+    if !options.verify_only {
+        ensure_progress_table(&target).await.map_err(db_error)?;
+        let progress = load_progress(&target).await.map_err(db_error)?;
+
+        // Tables not started yet lose the rows the migrations seeded, children first
+        for plan in plans.iter().rev().filter(|p| !progress.contains_key(&p.name)) {
+            execute(&target, &format!("DELETE FROM {}", qualified(&target, &plan.name))).await.map_err(db_error)?;
+        }
+
+        for plan in &plans {
+            let (cursor, copied, done) = progress.get(&plan.name).cloned().unwrap_or((plan.paging.start(), 0, false));
+            if done {
+                info!(table = %plan.name, rows = copied, "already transferred");
+                continue;
+            }
+            copy_table(&source, &target, plan, cursor, copied, batch_size).await?;
+        }
+        reset_sequences(&target, &plans).await.map_err(db_error)?;
+    }
+
+    let mut checks = Vec::new();
+    for plan in &plans {
+        let (source_rows, source_sum) = table_checksum(&source, plan, batch_size).await.map_err(db_error)?;
+        let (target_rows, target_sum) = table_checksum(&target, &plan.as_target(), batch_size)
+            .await
+            .map_err(db_error)?;
+        checks.push(TableCheck {
+            table: plan.name.clone(),
+            source_rows,
+            target_rows,
+            checksums_match: source_sum == target_sum,
+        });
+    }
+
+    if !options.verify_only && checks.iter().all(|c| c.source_rows == c.target_rows && c.checksums_match) {
+        execute(&target, &format!("DROP TABLE IF EXISTS {}", PROGRESS_TABLE)).await.map_err(db_error)?;
+    }
+    Ok(checks)
+}
+
+impl TablePlan {
+    /// Same table read from the target side
+    fn as_target(&self) -> TablePlan {
+        TablePlan {
+            name: self.name.clone(),
+            source: self.target.clone(),
+            target: self.target.clone(),
+            paging: Paging::Offset,
+        }
+    }
+}
+
+async fn copy_table(
+    source: &DbPool,
+    target: &DbPool,
+    plan: &TablePlan,
+    mut cursor: i64,
+    mut copied: i64,
+    batch_size: i64,
+) -> Result<(), String> {
+    let batch_size = batch_size.min((MAX_PARAMS / plan.target.len().max(1)) as i64).max(1);
+    info!(table = %plan.name, resume_from = copied, "transferring");
+
+    loop {
+        let (rows, next_cursor) = read_batch(source, plan, cursor, batch_size)
+            .await
+            .map_err(|e| format!("{}: read failed: {}", plan.name, e))?;
+        let done = (rows.len() as i64) < batch_size;
+        copied += rows.len() as i64;
+        cursor = next_cursor.unwrap_or(cursor + rows.len() as i64);
+
+        write_batch(target, plan, &rows, (cursor, copied, done))
+            .await
+            .map_err(|e| format!("{}: write failed: {}", plan.name, e))?;
+        if done {
+            info!(table = %plan.name, rows = copied, "transferred");
+            return Ok(());
+        }
+    }
+}
+
+/// Tables present on both sides in foreign key order, with their common columns
+async fn plan_tables(source: &DbPool, target: &DbPool) -> Result<Vec<TablePlan>, sqlx::Error> {
+    let source_tables = list_tables(source).await?;
+    let target_tables = list_tables(target).await?;
+
+    let mut plans = Vec::new();
+    for table in &source_tables {
+        if !target_tables.contains(table) {
+            warn!(table = %table, "missing on the target, skipped");
+            continue;
+        }
+        let source_columns = columns(source, table).await?;
+        let target_columns = columns(target, table).await?;
+        let (source_cols, target_cols): (Vec<ColumnDef>, Vec<ColumnDef>) = source_columns
+            .into_iter()
+            .filter_map(|s| {
+                let t = target_columns.iter().find(|t| t.name == s.name)?.clone();
+                Some((s, t))
+            })
+            .unzip();
+        if source_cols.is_empty() {
+            continue;
+        }
+
+        let paging = match source {
+            DbPool::Sqlite(_) => Paging::Key("rowid".to_string()),
+            DbPool::Postgres(_) => match primary_key(source, table).await?.as_slice() {
+                [pk] if source_cols.iter().any(|c| &c.name == pk && c.kind == Kind::Int) => Paging::Key(pk.clone()),
+                _ => Paging::Offset,
+            },
+        };
+        plans.push(TablePlan {
+            name: table.clone(),
+            source: source_cols,
+            target: target_cols,
+            paging,
+        });
+    }
+
+    // Parents before children; whatever is left in a cycle keeps its place at the end
+    let mut deps = HashMap::new();
+    for plan in &plans {
+        deps.insert(plan.name.clone(), references(target, &plan.name).await?);
+    }
+    let mut ordered: Vec<TablePlan> = Vec::new();
+    while !plans.is_empty() {
+        let ready = plans.iter().position(|p| {
+            deps[&p.name]
+                .iter()
+                .all(|d| d == &p.name || !plans.iter().any(|other| &other.name == d))
+        });
+        ordered.push(plans.remove(ready.unwrap_or(0)));
+    }
+    Ok(ordered)
+}
+
+async fn list_tables(pool: &DbPool) -> Result<Vec<String>, sqlx::Error> {
+    let tables: Vec<String> = match pool {
+        DbPool::Sqlite(pool) => {
+            sqlx::query_scalar(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+            )
+            .fetch_all(pool)
+            .await?
+        }
+        DbPool::Postgres(pool) => {
+            sqlx::query_scalar("SELECT tablename::TEXT FROM pg_tables WHERE schemaname = 'public' ORDER BY tablename")
+                .fetch_all(pool)
+                .await?
+        }
+    };
+    Ok(tables.into_iter().filter(|t| t != PROGRESS_TABLE).collect())
+}
+
+async fn columns(pool: &DbPool, table: &str) -> Result<Vec<ColumnDef>, sqlx::Error> {
+    let columns: Vec<(String, String)> = match pool {
+        DbPool::Sqlite(pool) => {
+            sqlx::query_as("SELECT name, type FROM pragma_table_info(?) ORDER BY cid")
+                .bind(table)
+                .fetch_all(pool)
+                .await?
+        }
+        DbPool::Postgres(pool) => {
+            sqlx::query_as(
+                r#"
+                SELECT a.attname::TEXT, format_type(a.atttypid, a.atttypmod)
+                FROM pg_attribute a
+                WHERE a.attrelid = $1::regclass AND a.attnum > 0 AND NOT a.attisdropped
+                ORDER BY a.attnum
+                "#,
+            )
+            .bind(format!("public.{}", quote_ident(table)))
+            .fetch_all(pool)
+            .await?
+        }
+    };
+    Ok(columns
+        .into_iter()
+        .map(|(name, declared)| ColumnDef {
+            name,
+            kind: Kind::from_declared(&declared),
+            declared,
+        })
+        .collect())
+}
+
+async fn primary_key(pool: &DbPool, table: &str) -> Result<Vec<String>, sqlx::Error> {
+    match pool {
+        DbPool::Sqlite(pool) => {
+            sqlx::query_scalar("SELECT name FROM pragma_table_info(?) WHERE pk > 0 ORDER BY pk")
+                .bind(table)
+                .fetch_all(pool)
+                .await
+        }
+        DbPool::Postgres(pool) => {
+            sqlx::query_scalar(
+                r#"
+                SELECT a.attname::TEXT
+                FROM pg_index i
+                JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+                WHERE i.indrelid = $1::regclass AND i.indisprimary
+                "#,
+            )
+            .bind(format!("public.{}", quote_ident(table)))
+            .fetch_all(pool)
+            .await
+        }
+    }
+}
+
+/// Tables `table` points to with foreign keys
+async fn references(pool: &DbPool, table: &str) -> Result<Vec<String>, sqlx::Error> {
+    match pool {
+        DbPool::Sqlite(pool) => {
+            sqlx::query_scalar("SELECT DISTINCT \"table\" FROM pragma_foreign_key_list(?)")
+                .bind(table)
+                .fetch_all(pool)
+                .await
+        }
+        DbPool::Postgres(pool) => {
+            sqlx::query_scalar(
+                r#"
+                SELECT DISTINCT ref.relname::TEXT
+                FROM pg_constraint c
+                JOIN pg_class ref ON ref.oid = c.confrelid
+                WHERE c.contype = 'f' AND c.conrelid = $1::regclass
+                "#,
+            )
+            .bind(format!("public.{}", quote_ident(table)))
+            .fetch_all(pool)
+            .await
+        }
+    }
+}
+
+/// Next batch after `cursor`; returns the rows and the new key cursor when paging by key
+async fn read_batch(
+    pool: &DbPool,
+    plan: &TablePlan,
+    cursor: i64,
+    limit: i64,
+) -> Result<(Vec<Vec<Cell>>, Option<i64>), sqlx::Error> {
+    let table = qualified(pool, &plan.name);
+    match pool {
+        DbPool::Sqlite(pool) => {
+            let columns: Vec<String> = plan.source.iter().map(|c| quote_ident(&c.name)).collect();
+            let (key_expr, sql) = match &plan.paging {
+                Paging::Key(key) => (
+                    true,
+                    format!(
+                        "SELECT {key}, {cols} FROM {table} WHERE {key} > ? ORDER BY {key} LIMIT ?",
+                        key = quote_ident(key),
+                        cols = columns.join(", "),
+                        table = table
+                    ),
+                ),
+                Paging::Offset => (
+                    false,
+                    format!(
+                        "SELECT 0, {cols} FROM {table} ORDER BY rowid LIMIT ? OFFSET ?",
+                        cols = columns.join(", "),
+                        table = table
+                    ),
+                ),
+            };
+            let query = if key_expr {
+                sqlx::query(&sql).bind(cursor).bind(limit)
+            } else {
+                sqlx::query(&sql).bind(limit).bind(cursor)
+            };
+            let rows = query.fetch_all(pool).await?;
+
+            let mut last_key = None;
+            let mut batch = Vec::with_capacity(rows.len());
+            for row in &rows {
+                if key_expr {
+                    last_key = Some(row.try_get::<i64, _>(0)?);
+                }
+                let mut cells = Vec::with_capacity(plan.source.len());
+                for i in 1..=plan.source.len() {
+                    cells.push(sqlite_cell(row, i)?);
+                }
+                batch.push(cells);
+            }
+            Ok((batch, last_key))
+        }
+        DbPool::Postgres(pool) => {
+            let exprs: Vec<String> = plan.source.iter().map(pg_select_expr).collect();
+            let (key_expr, sql) = match &plan.paging {
+                Paging::Key(key) => (
+                    true,
+                    format!(
+                        "SELECT {key}::INT8, {cols} FROM {table} WHERE {key} > $1 ORDER BY {key} LIMIT $2",
+                        key = quote_ident(key),
+                        cols = exprs.join(", "),
+                        table = table
+                    ),
+                ),
+                Paging::Offset => {
+                    let order: Vec<String> = (2..=exprs.len() + 1).map(|i| i.to_string()).collect();
+                    (
+                        false,
+                        format!(
+                            "SELECT 0::INT8, {cols} FROM {table} ORDER BY {order} LIMIT $2 OFFSET $1",
+                            cols = exprs.join(", "),
+                            table = table,
+                            order = order.join(", ")
+                        ),
+                    )
+                }
+            };
+            let rows = sqlx::query(&sql).bind(cursor).bind(limit).fetch_all(pool).await?;
+
+            let mut last_key = None;
+            let mut batch = Vec::with_capacity(rows.len());
+            for row in &rows {
+                if key_expr {
+                    last_key = Some(row.try_get::<i64, _>(0)?);
+                }
+                let mut cells = Vec::with_capacity(plan.source.len());
+                for (i, column) in plan.source.iter().enumerate() {
+                    cells.push(pg_cell(row, i + 1, column.kind)?);
+                }
+                batch.push(cells);
+            }
+            Ok((batch, last_key))
+        }
+    }
+}
+
+fn sqlite_cell(row: &sqlx::sqlite::SqliteRow, i: usize) -> Result<Cell, sqlx::Error> {
+    let raw = row.try_get_raw(i)?;
+    if raw.is_null() {
+        return Ok(Cell::Null);
+    }
+    // Storage class of the value itself, whatever the column was declared as
+    let cell = match raw.type_info().name() {
+        "INTEGER" | "BOOLEAN" => Cell::Int(row.try_get_unchecked::<i64, _>(i)?),
+        "REAL" => Cell::Float(row.try_get_unchecked::<f64, _>(i)?),
+        "BLOB" => Cell::Bytes(row.try_get_unchecked::<Vec<u8>, _>(i)?),
+        _ => Cell::Text(row.try_get_unchecked::<String, _>(i)?),
+    };
+    Ok(cell)
+}
+
+/// Select expression that decodes into a `Cell` of the column's kind
+fn pg_select_expr(column: &ColumnDef) -> String {
+    let name = quote_ident(&column.name);
+    match column.kind {
+        Kind::Bool | Kind::Bytes => name,
+        Kind::Int => format!("{}::INT8", name),
+        Kind::Float => format!("{}::FLOAT8", name),
+        Kind::TimestampTz => format!("to_char({} AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS.US')", name),
+        Kind::Timestamp => format!("to_char({}, 'YYYY-MM-DD HH24:MI:SS.US')", name),
+        Kind::Text => format!("{}::TEXT", name),
+    }
+}
+
+fn pg_cell(row: &sqlx::postgres::PgRow, i: usize, kind: Kind) -> Result<Cell, sqlx::Error> {
+    let cell = match kind {
+        Kind::Bool => row.try_get::<Option<bool>, _>(i)?.map(Cell::Bool),
+        Kind::Int => row.try_get::<Option<i64>, _>(i)?.map(Cell::Int),
+        Kind::Float => row.try_get::<Option<f64>, _>(i)?.map(Cell::Float),
+        Kind::Bytes => row.try_get::<Option<Vec<u8>>, _>(i)?.map(Cell::Bytes),
+        Kind::Timestamp | Kind::TimestampTz => row
+            .try_get::<Option<String>, _>(i)?
+            .map(|t| Cell::Text(t.trim_end_matches(".000000").to_string())),
+        Kind::Text => row.try_get::<Option<String>, _>(i)?.map(Cell::Text),
+    };
+    Ok(cell.unwrap_or(Cell::Null))
+}
+
+/// Insert one batch and record the cursor in the same transaction
+async fn write_batch(
+    pool: &DbPool,
+    plan: &TablePlan,
+    rows: &[Vec<Cell>],
+    (cursor, copied, done): (i64, i64, bool),
+) -> Result<(), sqlx::Error> {
+    let table = qualified(pool, &plan.name);
+    let columns: Vec<String> = plan.target.iter().map(|c| quote_ident(&c.name)).collect();
+
+    match pool {
+        DbPool::Sqlite(pool) => {
+            let mut tx = pool.begin().await?;
+            if !rows.is_empty() {
+                let row_sql = format!("({})", vec!["?"; columns.len()].join(", "));
+                let sql = format!(
+                    "INSERT INTO {} ({}) VALUES {}",
+                    table,
+                    columns.join(", "),
+                    vec![row_sql; rows.len()].join(", ")
+                );
+                let mut query = sqlx::query(&sql);
+                for cell in rows.iter().flatten() {
+                    query = match cell.clone() {
+                        Cell::Null => query.bind(None::<String>),
+                        Cell::Int(v) => query.bind(v),
+                        Cell::Float(v) => query.bind(v),
+                        Cell::Bool(v) => query.bind(v as i64),
+                        Cell::Text(v) => query.bind(v),
+                        Cell::Bytes(v) => query.bind(v),
+                    };
+                }
+                query.execute(&mut *tx).await?;
+            }
+            sqlx::query(&format!(
+                "INSERT INTO {} (table_name, cursor, rows_copied, done) VALUES (?, ?, ?, ?) \
+                 ON CONFLICT (table_name) DO UPDATE SET cursor = excluded.cursor, rows_copied = excluded.rows_copied, done = excluded.done",
+                PROGRESS_TABLE
+            ))
+            .bind(&plan.name)
+            .bind(cursor)
+            .bind(copied)
+            .bind(done)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await
+        }
+        DbPool::Postgres(pool) => {
+            let mut tx = pool.begin().await?;
+            if !rows.is_empty() {
+                let mut values = Vec::with_capacity(rows.len());
+                let mut n = 0;
+                for _ in rows {
+                    let placeholders: Vec<String> = plan
+                        .target
+                        .iter()
+                        .map(|c| {
+                            n += 1;
+                            format!("${}::{}", n, c.declared)
+                        })
+                        .collect();
+                    values.push(format!("({})", placeholders.join(", ")));
+                }
+                let sql = format!("INSERT INTO {} ({}) VALUES {}", table, columns.join(", "), values.join(", "));
+                let mut query = sqlx::query(&sql);
+                for row in rows {
+                    for (cell, column) in row.iter().zip(&plan.target) {
+                        query = match (cell, column.kind) {
+                            (Cell::Null, Kind::Bytes) => query.bind(None::<Vec<u8>>),
+                            (Cell::Null, _) => query.bind(None::<String>),
+                            (Cell::Bytes(v), Kind::Bytes) => query.bind(v.clone()),
+                            (Cell::Text(v), Kind::Bytes) => query.bind(v.as_bytes().to_vec()),
+                            (cell, kind) => query.bind(pg_text(cell, kind)),
+                        };
+                    }
+                }
+                query.execute(&mut *tx).await?;
+            }
+            sqlx::query(&format!(
+                "INSERT INTO {} (table_name, cursor, rows_copied, done) VALUES ($1, $2, $3, $4) \
+                 ON CONFLICT (table_name) DO UPDATE SET cursor = EXCLUDED.cursor, rows_copied = EXCLUDED.rows_copied, done = EXCLUDED.done",
+                PROGRESS_TABLE
+            ))
+            .bind(&plan.name)
+            .bind(cursor)
+            .bind(copied)
+            .bind(done)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await
+        }
+    }
+}
+
+/// Text form Postgres casts into the target column type
+fn pg_text(cell: &Cell, kind: Kind) -> String {
+    match (cell, kind) {
+        (Cell::Int(v), Kind::Bool) => (*v != 0).to_string(),
+        // SQLite's CURRENT_TIMESTAMP is UTC without an offset
+        (Cell::Text(v), Kind::TimestampTz) => normalize_timestamp(v)
+            .map(|t| format!("{}+00", t))
+            .unwrap_or_else(|| v.clone()),
+        _ => canonical(cell, Kind::Text),
+    }
+}
+
+/// Row count and an order-independent checksum of the normalized rows
+async fn table_checksum(pool: &DbPool, plan: &TablePlan, batch_size: i64) -> Result<(i64, u128), sqlx::Error> {
+    let kinds: Vec<Kind> = plan.target.iter().map(|c| c.kind).collect();
+    let (mut count, mut sum, mut cursor) = (0i64, 0u128, plan.paging.start());
+    loop {
+        let (rows, next_cursor) = read_batch(pool, plan, cursor, batch_size).await?;
+        let fetched = rows.len() as i64;
+        for row in rows {
+            let normalized: Vec<String> = row.iter().zip(&kinds).map(|(cell, kind)| canonical(cell, *kind)).collect();
+            let digest = Sha256::digest(normalized.join("\u{1f}").as_bytes());
+            let mut first = [0u8; 16];
+            first.copy_from_slice(&digest[..16]);
+            sum = sum.wrapping_add(u128::from_be_bytes(first));
+        }
+        count += fetched;
+        cursor = next_cursor.unwrap_or(cursor + fetched);
+        if fetched < batch_size {
+            return Ok((count, sum));
+        }
+    }
+}
+
+/// Backend-independent text of a value, as the target column type sees it
+fn canonical(cell: &Cell, kind: Kind) -> String {
+    match (cell, kind) {
+        (Cell::Null, _) => "\0NULL".to_string(),
+        (Cell::Bool(b), _) => if *b { "1" } else { "0" }.to_string(),
+        (Cell::Int(v), Kind::Bool) => if *v != 0 { "1" } else { "0" }.to_string(),
+        (Cell::Text(t), Kind::Bool) => match t.to_lowercase().as_str() {
+            "1" | "t" | "true" => "1".to_string(),
+            _ => "0".to_string(),
+        },
+        (Cell::Int(v), Kind::Float) => (*v as f64).to_string(),
+        (Cell::Float(v), Kind::Int) if v.fract() == 0.0 => (*v as i64).to_string(),
+        (Cell::Text(t), Kind::Float) => t.trim().parse::<f64>().map(|v| v.to_string()).unwrap_or_else(|_| t.clone()),
+        (Cell::Text(t), Kind::Int) => t.trim().parse::<i64>().map(|v| v.to_string()).unwrap_or_else(|_| t.clone()),
+        (Cell::Text(t), Kind::Timestamp | Kind::TimestampTz) => normalize_timestamp(t).unwrap_or_else(|| t.clone()),
+        (Cell::Int(v), _) => v.to_string(),
+        (Cell::Float(v), _) => v.to_string(),
+        (Cell::Text(t), _) => t.clone(),
+        (Cell::Bytes(b), _) => hex::encode(b),
+    }
+}
+
+/// `YYYY-MM-DD HH:MM:SS.ffffff` in UTC
+fn normalize_timestamp(text: &str) -> Option<String> {
+    let text = text.trim();
+    let parsed = DateTime::parse_from_rfc3339(text)
+        .map(|t| t.with_timezone(&Utc).naive_utc())
+        .or_else(|_| DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z").map(|t| t.with_timezone(&Utc).naive_utc()))
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f"))
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()?;
+    Some(parsed.format("%Y-%m-%d %H:%M:%S%.6f").to_string())
+}
+
+async fn ensure_progress_table(pool: &DbPool) -> Result<(), sqlx::Error> {
+    execute(
+        pool,
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} (table_name TEXT PRIMARY KEY, cursor BIGINT NOT NULL, rows_copied BIGINT NOT NULL, done BOOLEAN NOT NULL)",
+            PROGRESS_TABLE
+        ),
+    )
+    .await
+}
+
+async fn load_progress(pool: &DbPool) -> Result<BTreeMap<String, (i64, i64, bool)>, sqlx::Error> {
+    let sql = format!("SELECT table_name, cursor, rows_copied, done FROM {}", PROGRESS_TABLE);
+    let rows: Vec<(String, i64, i64, bool)> = match pool {
+        DbPool::Sqlite(pool) => sqlx::query_as(&sql).fetch_all(pool).await?,
+        DbPool::Postgres(pool) => sqlx::query_as(&sql).fetch_all(pool).await?,
+    };
+    Ok(rows.into_iter().map(|(t, cursor, copied, done)| (t, (cursor, copied, done))).collect())
+}
+
+/// Move Postgres serial sequences past the copied IDs (SQLite keeps AUTOINCREMENT in step by itself)
+async fn reset_sequences(pool: &DbPool, plans: &[TablePlan]) -> Result<(), sqlx::Error> {
+    let DbPool::Postgres(pg) = pool else {
+        return Ok(());
+    };
+    for plan in plans {
+        for column in &plan.target {
+            let sequence: Option<String> = sqlx::query_scalar("SELECT pg_get_serial_sequence($1, $2)")
+                .bind(format!("public.{}", quote_ident(&plan.name)))
+                .bind(&column.name)
+                .fetch_one(pg)
+                .await?;
+            if let Some(sequence) = sequence {
+                sqlx::query(&format!(
+                    "SELECT setval($1, COALESCE(MAX({col}), 1), MAX({col}) IS NOT NULL) FROM public.{table}",
+                    col = quote_ident(&column.name),
+                    table = quote_ident(&plan.name)
+                ))
+                .bind(sequence)
+                .execute(pg)
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn execute(pool: &DbPool, sql: &str) -> Result<(), sqlx::Error> {
+    match pool {
+        DbPool::Sqlite(pool) => sqlx::query(sql).execute(pool).await.map(|_| ()),
+        DbPool::Postgres(pool) => sqlx::query(sql).execute(pool).await.map(|_| ()),
+    }
+}
+
+fn qualified(pool: &DbPool, table: &str) -> String {
+    match pool {
+        DbPool::Sqlite(_) => quote_ident(table),
+        DbPool::Postgres(_) => format!("public.{}", quote_ident(table)),
+    }
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn db_error(e: sqlx::Error) -> String {
+    format!("Database error: {}", e)
+}
+
+/// `server db-transfer --to <url> [--from <url>] [--batch-size N] [--verify-only]`; returns the exit code
+pub async fn run_cli(args: &[String]) -> i32 {
+    let mut options = TransferOptions {
+        source_url: std::env::var("DATABASE_URL").unwrap_or_default(),
+        target_url: String::new(),
+        batch_size: DEFAULT_BATCH_SIZE,
+        verify_only: false,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => options.source_url = args.next().cloned().unwrap_or_default(),
+            "--to" => options.target_url = args.next().cloned().unwrap_or_default(),
+            "--batch-size" => {
+                options.batch_size = args.next().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_BATCH_SIZE)
+            }
+            "--verify-only" => options.verify_only = true,
+            other => {
+                eprintln!("Unknown argument: {}", other);
+                return 2;
+            }
+        }
+    }
+    if options.source_url.is_empty() || options.target_url.is_empty() {
+        eprintln!("Usage: server db-transfer --to <database_url> [--from <database_url>] [--batch-size N] [--verify-only]");
+        return 2;
+    }
+
+    match run(&options).await {
+        Ok(checks) => {
+            let mut failed = false;
+            for check in &checks {
+                let ok = check.source_rows == check.target_rows && check.checksums_match;
+                failed |= !ok;
+                println!(
+                    "{:<32} {:>10} {:>10}  {}",
+                    check.table,
+                    check.source_rows,
+                    check.target_rows,
+                    if ok { "ok" } else { "MISMATCH" }
+                );
+            }
+            if failed {
+                eprintln!("Verification failed");
+                1
+            } else {
+                println!("{} tables verified", checks.len());
+                0
+            }
+        }
+        Err(e) => {
+            eprintln!("Transfer failed: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::sqlite_pool;
+
+    fn same(left: (&Cell, Kind), right: (&Cell, Kind)) -> bool {
+        canonical(left.0, left.1) == canonical(right.0, right.1)
+    }
+
+    #[test]
+    fn sqlite_integers_match_postgres_booleans() {
+        let bool_column = Kind::from_declared("BOOLEAN");
+        assert_eq!(bool_column, Kind::Bool);
+        assert!(same((&Cell::Int(1), bool_column), (&Cell::Bool(true), bool_column)));
+        assert!(same((&Cell::Int(0), bool_column), (&Cell::Bool(false), bool_column)));
+        assert!(same((&Cell::Text("true".into()), bool_column), (&Cell::Bool(true), bool_column)));
+        assert!(!same((&Cell::Int(0), bool_column), (&Cell::Bool(true), bool_column)));
+        assert!(!same((&Cell::Null, bool_column), (&Cell::Bool(false), bool_column)));
+        assert_eq!(pg_text(&Cell::Int(1), bool_column), "true");
+        assert_eq!(pg_text(&Cell::Int(0), bool_column), "false");
+    }
+
+    #[test]
+    fn sqlite_text_timestamps_match_timestamptz() {
+        let tz = Kind::from_declared("TIMESTAMP WITH TIME ZONE");
+        assert_eq!(tz, Kind::TimestampTz);
+        assert_eq!(Kind::from_declared("DATETIME"), Kind::Timestamp);
+        // What pg_cell reads for 2024-05-01 12:00:00+00 after to_char and trimming zero micros
+        let postgres = Cell::Text("2024-05-01 12:00:00".into());
+        for sqlite in [
+            "2024-05-01 12:00:00",
+            "2024-05-01T12:00:00",
+            "2024-05-01 12:00:00.000",
+            "2024-05-01T12:00:00Z",
+            "2024-05-01T15:00:00+03:00",
+            "2024-05-01 14:00:00+02",
+        ] {
+            assert!(same((&Cell::Text(sqlite.into()), tz), (&postgres, tz)), "{}", sqlite);
+        }
+        let postgres = Cell::Text("2024-05-01 12:00:00.250000".into());
+        assert!(same((&Cell::Text("2024-05-01 12:00:00.25".into()), tz), (&postgres, tz)));
+        assert!(!same((&Cell::Text("2024-05-01 12:00:01".into()), tz), (&postgres, tz)));
+        assert_eq!(pg_text(&Cell::Text("2024-05-01 12:00:00".into()), tz), "2024-05-01 12:00:00.000000+00");
+    }
+
+    #[test]
+    fn numbers_compare_across_storage_classes() {
+        assert!(same((&Cell::Int(3), Kind::Float), (&Cell::Float(3.0), Kind::Float)));
+        assert!(same((&Cell::Float(7.0), Kind::Int), (&Cell::Int(7), Kind::Int)));
+        assert!(same((&Cell::Text(" 42 ".into()), Kind::Int), (&Cell::Int(42), Kind::Int)));
+        assert!(!same((&Cell::Null, Kind::Text), (&Cell::Text(String::new()), Kind::Text)));
+    }
+
+    #[tokio::test]
+    async fn pages_by_key_across_negative_and_zero_keys() {
+        let (_dir, pool) = sqlite_pool().await;
+        execute(&pool, "CREATE TABLE paging_test (id INTEGER PRIMARY KEY, label TEXT NOT NULL)").await.unwrap();
+        for id in [5, -7, 0, -1, 2] {
+            execute(&pool, &format!("INSERT INTO paging_test (id, label) VALUES ({}, 'row {}')", id, id)).await.unwrap();
+        }
+        let columns = columns(&pool, "paging_test").await.unwrap();
+        let plan = TablePlan {
+            name: "paging_test".into(),
+            source: columns.clone(),
+            target: columns,
+            paging: Paging::Key("rowid".into()),
+        };
+
+        let mut cursor = plan.paging.start();
+        let mut keys = Vec::new();
+        loop {
+            let (rows, next) = read_batch(&pool, &plan, cursor, 2).await.unwrap();
+            for row in &rows {
+                match &row[0] {
+                    Cell::Int(id) => keys.push(*id),
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+            if rows.len() < 2 {
+                break;
+            }
+            cursor = next.unwrap();
+        }
+        assert_eq!(keys, vec![-7, -1, 0, 2, 5]);
+        assert_eq!(table_checksum(&pool, &plan, 2).await.unwrap().0, 5);
+    }
+
+    #[tokio::test]
+    async fn transfer_keeps_negative_and_zero_keys() {
+        let (source_dir, source) = sqlite_pool().await;
+        let (target_dir, target) = sqlite_pool().await;
+        let create = "CREATE TABLE paging_test (id INTEGER PRIMARY KEY, flag BOOLEAN, at DATETIME)";
+        execute(&source, create).await.unwrap();
+        execute(&target, create).await.unwrap();
+        execute(
+            &source,
+            "INSERT INTO paging_test VALUES (-3, 1, '2024-05-01 12:00:00'), (0, 0, NULL), (4, NULL, '2024-05-02T08:30:00')",
+        )
+        .await
+        .unwrap();
+
+        let url = |dir: &tempfile::TempDir| format!("sqlite://{}?mode=rwc", dir.path().join("test.db").display());
+        let checks = run(&TransferOptions {
+            source_url: url(&source_dir),
+            target_url: url(&target_dir),
+            batch_size: 2,
+            verify_only: false,
+        })
+        .await
+        .unwrap();
+        let check = checks.iter().find(|c| c.table == "paging_test").unwrap();
+        assert_eq!((check.source_rows, check.target_rows, check.checksums_match), (3, 3, true));
+        assert!(checks.iter().all(|c| c.source_rows == c.target_rows && c.checksums_match), "{:?}", checks);
+
+        let DbPool::Sqlite(target) = target else { unreachable!() };
+        let ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM paging_test ORDER BY id")
+            .fetch_all(&target)
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![-3, 0, 4]);
+    }
+}
@@ -54,6 +54,22 @@ impl FileService {
         })
     }
 
+    /// Root folder with the given name, created on first use
+    pub async fn ensure_root_folder(pool: &SqlitePool, name: &str) -> Result<Folder, String> {
+        let folder: Option<Folder> = sqlx::query_as(
+            "SELECT * FROM file_folders WHERE parent_id IS NULL AND name = ? ORDER BY created_at LIMIT 1"
+        )
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        match folder {
+            Some(folder) => Ok(folder),
+            None => Self::create_folder(pool, name, None).await,
+        }
+    }
+
     /// Get folder by ID
     pub async fn get_folder(pool: &SqlitePool, folder_id: &str) -> Result<Option<Folder>, String> {
         let folder: Option<Folder> = sqlx::query_as(
@@ -1,14 +1,130 @@
+use crate::audit::{record_actor, AuditActor};
+use crate::auth::api_tokens::ApiTokenService;
 use crate::auth::AuthService;
 use crate::db::DbPool;
-use crate::models::User;
+use crate::models::{SessionClient, User};
 use rocket::http::Status;
 use rocket::request::{self, FromRequest, Request};
 use rocket::outcome::Outcome;
 use rocket::State;
 
+// Extract the bearer token from the Authorization header
+fn bearer_token<'r>(req: &'r Request<'_>) -> Option<&'r str> {
+    req.headers().get_one("Authorization").map(|header| {
+        // Support both "Bearer TOKEN" and just "TOKEN" formats
+        if header.starts_with("Bearer ") {
+            header.trim_start_matches("Bearer ")
+        } else {
+            header
+        }
+    })
+}
+
+// Authenticated principal: an OTP session or a scoped personal access token
+struct Principal {
+    user: User,
+    session_id: Option<i32>,
+    api_token_id: Option<i32>,
+    scopes: Vec<String>,
+}
+
+// "token:<id>" for personal tokens, "session:<id>" for OTP sessions
+fn actor_label(session_id: Option<i32>, api_token_id: Option<i32>) -> String {
+    match (session_id, api_token_id) {
+        (_, Some(token_id)) => format!("token:{}", token_id),
+        (session_id, None) => format!("session:{}", session_id.unwrap_or_default()),
+    }
+}
+
+// Authenticate the request and remember the actor for the audit log
+async fn authenticate(req: &Request<'_>) -> request::Outcome<Principal, ()> {
+    let outcome = authenticate_token(req).await;
+    if let Outcome::Success(principal) = &outcome {
+        let label = actor_label(principal.session_id, principal.api_token_id);
+        record_actor(
+            req,
+            AuditActor {
+                label,
+                user_id: Some(principal.user.id),
+            },
+        )
+        .await;
+    }
+    outcome
+}
+
+// Validate a session token, or a personal token against the scope required by the route
+async fn authenticate_token(req: &Request<'_>) -> request::Outcome<Principal, ()> {
+    let token = match bearer_token(req) {
+        Some(token) => token,
+        None => return Outcome::Error((Status::Unauthorized, ())),
+    };
+
+    // Get database pool from state
+    let pool = match req.guard::<&State<DbPool>>().await {
+        Outcome::Success(pool) => pool,
+        _ => return Outcome::Error((Status::InternalServerError, ())),
+    };
+
+    if ApiTokenService::is_api_token(token) {
+        let required = match ApiTokenService::required_scope(req.method().as_str(), req.uri().path().as_str()) {
+            Some(scope) => scope,
+            None => return Outcome::Error((Status::Forbidden, ())),
+        };
+
+        return match ApiTokenService::validate_token(pool.inner(), token, req.client_ip()).await {
+            Ok(Some((user, api_token))) => {
+                if ApiTokenService::scopes_allow(&api_token.scope_list(), &required) {
+                    Outcome::Success(Principal {
+                        user,
+                        session_id: None,
+                        api_token_id: Some(api_token.id),
+                        scopes: api_token.scope_list(),
+                    })
+                } else {
+                    Outcome::Error((Status::Forbidden, ()))
+                }
+            }
+            Ok(None) => Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => Outcome::Error((Status::InternalServerError, ())),
+        };
+    }
+
+    // Validate session token
+    match AuthService::validate_token(pool.inner(), token).await {
+        Ok(Some((user, session))) => Outcome::Success(Principal {
+            user,
+            session_id: Some(session.id),
+            api_token_id: None,
+            scopes: Vec::new(),
+        }),
+        Ok(None) => Outcome::Error((Status::Unauthorized, ())),
+        Err(_) => Outcome::Error((Status::InternalServerError, ())),
+    }
+}
+
 // Authenticated user guard for admin routes
 pub struct AuthGuard {
     pub user: User,
+    /// Set when authenticated with an OTP session
+    pub session_id: Option<i32>,
+    /// Set when authenticated with a personal access token
+    pub api_token_id: Option<i32>,
+    /// Scopes granted to the personal access token (empty for sessions)
+    pub scopes: Vec<String>,
+}
+
+impl AuthGuard {
+    /// Who is acting, in the same form as the audit log
+    pub fn actor_label(&self) -> String {
+        actor_label(self.session_id, self.api_token_id)
+    }
+
+    /// Whether the caller may act with `scope`, beyond the one the route required:
+    /// sessions may do anything, personal tokens only what they were granted
+    pub fn allows(&self, scope: &str) -> bool {
+        self.api_token_id.is_none() || ApiTokenService::scopes_allow(&self.scopes, scope)
+    }
 }
 
 #[rocket::async_trait]
@@ -16,33 +132,12 @@ impl<'r> FromRequest<'r> for AuthGuard {
     type Error = ();
 
     async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
-        // Get Authorization header
-        let auth_header = req.headers().get_one("Authorization");
-
-        let token = match auth_header {
-            Some(header) => {
-                // Support both "Bearer TOKEN" and just "TOKEN" formats
-                if header.starts_with("Bearer ") {
-                    header.trim_start_matches("Bearer ")
-                } else {
-                    header
-                }
-            }
-            None => return Outcome::Error((Status::Unauthorized, ())),
-        };
-
-        // Get database pool from state
-        let pool = match req.guard::<&State<DbPool>>().await {
-            Outcome::Success(pool) => pool,
-            _ => return Outcome::Error((Status::InternalServerError, ())),
-        };
-
-        // Validate token
-        match AuthService::validate_token(pool.inner(), token).await {
-            Ok(Some(user)) => Outcome::Success(AuthGuard { user }),
-            Ok(None) => Outcome::Error((Status::Unauthorized, ())),
-            Err(_) => Outcome::Error((Status::InternalServerError, ())),
-        }
+        authenticate(req).await.map(|p| AuthGuard {
+            user: p.user,
+            session_id: p.session_id,
+            api_token_id: p.api_token_id,
+            scopes: p.scopes,
+        })
     }
 }
 
@@ -56,31 +151,21 @@ impl<'r> FromRequest<'r> for AdminSession {
     type Error = ();
 
     async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
-        // Get Authorization header
-        let auth_header = req.headers().get_one("Authorization");
+        authenticate(req).await.map(|p| AdminSession { user: p.user })
+    }
+}
 
-        let token = match auth_header {
-            Some(header) => {
-                if header.starts_with("Bearer ") {
-                    header.trim_start_matches("Bearer ")
-                } else {
-                    header
-                }
-            }
-            None => return Outcome::Error((Status::Unauthorized, ())),
-        };
+// Client IP and User-Agent of the request (never fails)
+pub struct ClientInfo(pub SessionClient);
 
-        // Get database pool from state
-        let pool = match req.guard::<&State<DbPool>>().await {
-            Outcome::Success(pool) => pool,
-            _ => return Outcome::Error((Status::InternalServerError, ())),
-        };
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = ();
 
-        // Validate token
-        match AuthService::validate_token(pool.inner(), token).await {
-            Ok(Some(user)) => Outcome::Success(AdminSession { user }),
-            Ok(None) => Outcome::Error((Status::Unauthorized, ())),
-            Err(_) => Outcome::Error((Status::InternalServerError, ())),
-        }
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(ClientInfo(SessionClient {
+            ip_address: req.client_ip().map(|ip| ip.to_string()),
+            user_agent: req.headers().get_one("User-Agent").map(|ua| ua.to_string()),
+        }))
     }
 }
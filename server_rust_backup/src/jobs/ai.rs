@@ -1,3 +1,4 @@
+use crate::telemetry::RequestIdExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -64,6 +65,7 @@ impl AIClient {
                 ],
                 "temperature": 0.7
             }))
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
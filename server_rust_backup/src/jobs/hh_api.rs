@@ -1,3 +1,4 @@
+use crate::telemetry::RequestIdExt;
 use reqwest::Client;
 use serde_json::json;
 
@@ -43,6 +44,7 @@ impl HHClient {
                 ("code", code),
                 ("redirect_uri", redirect_uri),
             ])
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -103,6 +105,7 @@ impl HHClient {
             .query(&params)
             .header("Authorization", format!("Bearer {}", token))
             .header("HH-User-Agent", "bgalin.ru (contact@bgalin.ru)")
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -141,6 +144,7 @@ impl HHClient {
                 "resume_id": resume_id,
                 "message": cover_letter
             }))
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -190,6 +194,7 @@ impl HHClient {
             .get("https://api.hh.ru/negotiations")
             .header("Authorization", format!("Bearer {}", token))
             .header("HH-User-Agent", "bgalin.ru (contact@bgalin.ru)")
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -216,6 +221,7 @@ impl HHClient {
             .get("https://api.hh.ru/resumes/mine")
             .header("Authorization", format!("Bearer {}", token))
             .header("HH-User-Agent", "bgalin.ru (contact@bgalin.ru)")
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -243,6 +249,7 @@ impl HHClient {
             .get(&format!("https://api.hh.ru/negotiations/{}", negotiation_id))
             .header("Authorization", format!("Bearer {}", token))
             .header("HH-User-Agent", "bgalin.ru (contact@bgalin.ru)")
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -264,6 +271,7 @@ impl HHClient {
             .get(&format!("https://api.hh.ru/negotiations/{}/messages", negotiation_id))
             .header("Authorization", format!("Bearer {}", token))
             .header("HH-User-Agent", "bgalin.ru (contact@bgalin.ru)")
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -293,6 +301,7 @@ impl HHClient {
             .json(&json!({
                 "message": message
             }))
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -313,6 +322,7 @@ impl HHClient {
             .get(&format!("https://api.hh.ru/vacancies/{}", vacancy_id))
             .header("Authorization", format!("Bearer {}", token))
             .header("HH-User-Agent", "bgalin.ru (contact@bgalin.ru)")
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -340,6 +350,7 @@ impl HHClient {
                 ("client_secret", client_secret),
                 ("refresh_token", refresh_token),
             ])
+            .with_request_id()
             .send()
             .await
             .map_err(|e| e.to_string())?;
@@ -4,7 +4,34 @@ use std::time::Duration;
 use tokio::time::sleep;
 use crate::db::DbPool;
 use crate::jobs::{HHClient, AIClient};
+use crate::metrics::prometheus;
+use crate::notifications::{category, NewNotification, NotificationPriority, NotificationService};
+use crate::telemetry;
 use chrono::{Utc, Timelike, Datelike};
+use tracing::{error, info, warn, Instrument};
+
+/// `data.approval` of notifications asking to approve an application
+pub const APPROVAL_JOB_APPLICATION: &str = "job_application";
+
+/// Clients and profile data shared by every application of one search cycle
+struct ApplyContext<'a> {
+    hh_client: &'a HHClient,
+    ai_client: &'a AIClient,
+    resume_id: &'a str,
+    resume_text: &'a str,
+    telegram: &'a str,
+    email: &'a str,
+}
+
+/// Saved vacancy the scheduler applies to
+struct VacancyToApply<'a> {
+    db_id: i32,
+    hh_id: &'a str,
+    title: &'a str,
+    company: &'a str,
+    description: &'a str,
+    ai_score: Option<i32>,
+}
 
 #[derive(Clone)]
 pub struct JobScheduler {
@@ -24,14 +51,14 @@ impl JobScheduler {
         let mut is_running = self.is_running.write();
         *is_running = true;
         self.log_activity_sync("system", None, "🚀 Автопоиск работы запущен");
-        println!("✅ Job scheduler started");
+        info!("Job scheduler started");
     }
 
     pub fn stop(&self) {
         let mut is_running = self.is_running.write();
         *is_running = false;
         self.log_activity_sync("system", None, "⏸️ Автопоиск работы остановлен");
-        println!("⏸️  Job scheduler stopped");
+        info!("Job scheduler stopped");
     }
 
     pub fn is_running(&self) -> bool {
@@ -100,13 +127,13 @@ impl JobScheduler {
 
     pub fn start_background_task(self) {
         tokio::spawn(async move {
-            println!("🔄 Job scheduler background task started");
+            info!("Job scheduler background task started");
 
             // Initial job search on startup
             if self.is_running() {
-                println!("🔍 Running initial job search...");
+                info!("Running initial job search...");
                 if let Err(e) = self.run_job_search().await {
-                    eprintln!("❌ Initial job search error: {}", e);
+                    error!("Initial job search error: {}", e);
                 }
             }
 
@@ -115,53 +142,62 @@ impl JobScheduler {
                     sleep(Duration::from_secs(30)).await;
                     continue;
                 }
+                prometheus::inc("bgalin_job_scheduler_cycles_total", &[], 1.0);
 
-                // Get search interval from settings (in minutes)
-                let search_interval_minutes: i64 = self.get_search_interval().await.unwrap_or(60);
+                // Each cycle gets its own correlation ID for the HH, OpenRouter and Telegram calls it makes
+                let cycle_id = uuid::Uuid::new_v4().to_string();
+                let span = tracing::info_span!("scheduler_cycle", request_id = %cycle_id);
+                telemetry::with_request_id(cycle_id, self.run_cycle().instrument(span)).await;
+            }
+        });
+    }
 
-                // Check if it's time for daily anime sync (at 3 AM)
-                let now = chrono::Local::now();
-                if now.hour() == 3 && now.minute() < 10 {
-                    if let Err(e) = self.sync_anime().await {
-                        eprintln!("❌ Anime sync error: {}", e);
-                    }
-                }
+    /// Response checks, chat monitoring and, when due, a vacancy search
+    async fn run_cycle(&self) {
+        // Get search interval from settings (in minutes)
+        let search_interval_minutes: i64 = self.get_search_interval().await.unwrap_or(60);
 
-                // Check responses every 5 minutes
-                if let Err(e) = self.check_responses().await {
-                    eprintln!("❌ Response check error: {}", e);
-                }
+        // Check if it's time for daily anime sync (at 3 AM)
+        let now = chrono::Local::now();
+        if now.hour() == 3 && now.minute() < 10 {
+            if let Err(e) = self.sync_anime().await {
+                error!("Anime sync error: {}", e);
+            }
+        }
 
-                // Monitor chats for new messages
-                if let Err(e) = self.monitor_chats_enhanced().await {
-                    eprintln!("❌ Chat monitoring error: {}", e);
-                }
+        // Check responses every 5 minutes
+        if let Err(e) = self.check_responses().await {
+            error!("Response check error: {}", e);
+        }
 
-                // Wait 5 minutes before next check
-                for _ in 0..30 {
-                    if !self.is_running() {
-                        break;
-                    }
-                    sleep(Duration::from_secs(10)).await;
-                }
+        // Monitor chats for new messages
+        if let Err(e) = self.monitor_chats_enhanced().await {
+            error!("Chat monitoring error: {}", e);
+        }
 
-                // Check if it's time to search for new jobs
-                if let Ok(Some(last_search)) = self.get_last_search_time().await {
-                    let elapsed_minutes = (Utc::now().timestamp() - last_search) / 60;
-                    if elapsed_minutes >= search_interval_minutes {
-                        if let Err(e) = self.run_job_search().await {
-                            eprintln!("❌ Job search error: {}", e);
-                            self.log_activity("error", None, &format!("Ошибка поиска: {}", e)).await.ok();
-                        }
-                    }
-                } else {
-                    // No previous search, run now
-                    if let Err(e) = self.run_job_search().await {
-                        eprintln!("❌ Job search error: {}", e);
-                    }
+        // Wait 5 minutes before next check
+        for _ in 0..30 {
+            if !self.is_running() {
+                break;
+            }
+            sleep(Duration::from_secs(10)).await;
+        }
+
+        // Check if it's time to search for new jobs
+        if let Ok(Some(last_search)) = self.get_last_search_time().await {
+            let elapsed_minutes = (Utc::now().timestamp() - last_search) / 60;
+            if elapsed_minutes >= search_interval_minutes {
+                if let Err(e) = self.run_job_search().await {
+                    error!("Job search error: {}", e);
+                    self.log_activity("error", None, &format!("Ошибка поиска: {}", e)).await.ok();
                 }
             }
-        });
+        } else {
+            // No previous search, run now
+            if let Err(e) = self.run_job_search().await {
+                error!("Job search error: {}", e);
+            }
+        }
     }
 
     async fn get_search_interval(&self) -> Result<i64, String> {
@@ -227,7 +263,7 @@ impl JobScheduler {
     }
 
     async fn run_job_search(&self) -> Result<(), String> {
-        println!("🔍 Starting AI-powered job search cycle...");
+        info!("Starting AI-powered job search cycle...");
         self.log_activity("search", None, "🔍 Начинаю поиск вакансий").await.ok();
 
         // Get all settings including new AI fields
@@ -253,7 +289,7 @@ impl JobScheduler {
         };
 
         if settings.is_none() {
-            println!("⚠️ Job search settings not found");
+            warn!("Job search settings not found");
             return Ok(());
         }
 
@@ -264,7 +300,7 @@ impl JobScheduler {
         let access_token = match self.get_valid_token().await {
             Ok(t) => t,
             Err(e) => {
-                println!("⚠️  {}", e);
+                warn!("{}", e);
                 return Ok(());
             }
         };
@@ -276,7 +312,7 @@ impl JobScheduler {
         };
 
         if !has_resume {
-            println!("⚠️  No portfolio/resume data found. Please add resume first.");
+            warn!("No portfolio/resume data found. Please add resume first.");
             return Ok(());
         }
 
@@ -313,7 +349,7 @@ impl JobScheduler {
 
         // If no queries, generate tags from resume
         if search_queries.is_empty() {
-            println!("🤖 No search queries, generating tags from resume...");
+            info!("No search queries, generating tags from resume...");
             if let Ok(tags) = ai_client.generate_search_tags(&resume_text).await {
                 for query in tags.suggested_queries {
                     search_queries.push(query.clone());
@@ -328,7 +364,7 @@ impl JobScheduler {
         }
 
         if search_queries.is_empty() {
-            println!("⚠️  No search queries available");
+            warn!("No search queries available");
             return Ok(());
         }
 
@@ -350,6 +386,15 @@ impl JobScheduler {
             .first()
             .and_then(|r| r["id"].as_str())
             .ok_or("No resume found on HH.ru")?;
+        let apply_ctx = ApplyContext {
+            hh_client: &hh_client,
+            ai_client: &ai_client,
+            resume_id,
+            resume_text: &resume_text,
+            telegram: &telegram,
+            email: &email,
+        };
+        let require_approval = Self::approval_required();
 
         let mut total_found = 0;
         let mut total_evaluated = 0;
@@ -357,7 +402,7 @@ impl JobScheduler {
 
         // Search with each query
         for query in search_queries.iter().take(5) {
-            println!("🔎 Searching: {}", query);
+            info!("Searching: {}", query);
 
             // Update tag search count
             match &self.pool {
@@ -380,7 +425,7 @@ impl JobScheduler {
             {
                 Ok(v) => v,
                 Err(e) => {
-                    eprintln!("⚠️  Search error for '{}': {}", query, e);
+                    warn!("Search error for '{}': {}", query, e);
                     continue;
                 }
             };
@@ -421,7 +466,7 @@ impl JobScheduler {
                 let vacancy_details = match hh_client.get_vacancy(vacancy_id).await {
                     Ok(v) => v,
                     Err(e) => {
-                        eprintln!("⚠️  Failed to get vacancy details: {}", e);
+                        warn!("Failed to get vacancy details: {}", e);
                         continue;
                     }
                 };
@@ -445,7 +490,7 @@ impl JobScheduler {
                             Some(eval.salary_assessment.clone()),
                         ),
                         Err(e) => {
-                            eprintln!("⚠️  AI evaluation failed: {}", e);
+                            warn!("AI evaluation failed: {}", e);
                             (None, None, None, None, None, None)
                         }
                     };
@@ -455,7 +500,13 @@ impl JobScheduler {
                     ai_score.unwrap_or(0) >= min_ai_score &&
                     ai_recommendation.as_deref() != Some("skip");
 
-                let status = if should_apply { "found" } else { "skipped" };
+                let status = if !should_apply {
+                    "skipped"
+                } else if require_approval {
+                    "pending_approval"
+                } else {
+                    "found"
+                };
 
                 // Save to DB
                 match &self.pool {
@@ -478,101 +529,32 @@ impl JobScheduler {
                 }
 
                 if !should_apply {
-                    println!("⏭️  Skipped {} (score: {}, rec: {:?})", title, ai_score.unwrap_or(0), ai_recommendation);
+                    info!("Skipped {} (score: {}, rec: {:?})", title, ai_score.unwrap_or(0), ai_recommendation);
                     continue;
                 }
 
-                // Generate cover letter
-                let cover_letter = match ai_client
-                    .generate_cover_letter(title, description, &resume_text, &telegram, &email)
-                    .await
-                {
-                    Ok(letter) => letter,
-                    Err(e) => {
-                        eprintln!("⚠️  Failed to generate cover letter: {}", e);
-                        continue;
-                    }
-                };
-
-                // Apply to vacancy
-                let negotiation_id = match hh_client
-                    .apply_to_vacancy(vacancy_id, &cover_letter, resume_id)
-                    .await
-                {
-                    Ok(id) => id,
-                    Err(e) => {
-                        eprintln!("⚠️  Failed to apply to vacancy {}: {}", title, e);
-                        continue;
-                    }
-                };
-
-                println!("✅ Applied to: {} (AI score: {})", title, ai_score.unwrap_or(0));
-
                 // Get vacancy DB id
                 let vacancy_db_id: i32 = match &self.pool {
                     DbPool::Sqlite(p) => sqlx::query_scalar("SELECT id FROM job_vacancies WHERE hh_vacancy_id = ?").bind(vacancy_id).fetch_one(p).await.unwrap_or(0),
                     DbPool::Postgres(p) => sqlx::query_scalar("SELECT id FROM job_vacancies WHERE hh_vacancy_id = $1").bind(vacancy_id).fetch_one(p).await.unwrap_or(0),
                 };
+                let to_apply = VacancyToApply {
+                    db_id: vacancy_db_id,
+                    hh_id: vacancy_id,
+                    title,
+                    company,
+                    description,
+                    ai_score,
+                };
 
-                // Update vacancy status
-                match &self.pool {
-                    DbPool::Sqlite(p) => { sqlx::query("UPDATE job_vacancies SET status = 'applied', applied_at = datetime('now') WHERE id = ?").bind(vacancy_db_id).execute(p).await.map(|_| ()).ok(); },
-                    DbPool::Postgres(p) => { sqlx::query("UPDATE job_vacancies SET status = 'applied', applied_at = NOW() WHERE id = $1").bind(vacancy_db_id).execute(p).await.map(|_| ()).ok(); },
-                }
-
-                // Save response
-                match &self.pool {
-                    DbPool::Sqlite(p) => {
-                        sqlx::query("INSERT INTO job_responses (vacancy_id, hh_negotiation_id, cover_letter, status) VALUES (?, ?, ?, 'sent')")
-                        .bind(vacancy_db_id).bind(&negotiation_id).bind(&cover_letter).execute(p).await.ok();
-                    },
-                    DbPool::Postgres(p) => {
-                        sqlx::query("INSERT INTO job_responses (vacancy_id, hh_negotiation_id, cover_letter, status) VALUES ($1, $2, $3, 'sent')")
-                        .bind(vacancy_db_id).bind(&negotiation_id).bind(&cover_letter).execute(p).await.ok();
-                    }
-                }
-
-                // Create chat record
-                match &self.pool {
-                    DbPool::Sqlite(p) => {
-                        sqlx::query("INSERT INTO job_chats_v2 (vacancy_id, hh_chat_id, employer_name) VALUES (?, ?, ?)")
-                        .bind(vacancy_db_id).bind(&negotiation_id).bind(company).execute(p).await.ok();
-                    },
-                    DbPool::Postgres(p) => {
-                        sqlx::query("INSERT INTO job_chats_v2 (vacancy_id, hh_chat_id, employer_name) VALUES ($1, $2, $3)")
-                        .bind(vacancy_db_id).bind(&negotiation_id).bind(company).execute(p).await.ok();
-                    }
+                if require_approval {
+                    self.request_approval(&to_apply, url).await;
+                    continue;
                 }
 
-                // Log activity
-                self.log_activity(
-                    "apply",
-                    Some(vacancy_db_id),
-                    &format!("✅ Отклик на {} в {} (AI: {}%)", title, company, ai_score.unwrap_or(0))
-                ).await.ok();
-
-                // Generate and send chat intro
-                if let Ok(chat_intro) = ai_client.generate_chat_intro(&cover_letter, &telegram, &email).await {
-                    if let Err(e) = hh_client.send_message(&negotiation_id, &chat_intro).await {
-                        eprintln!("⚠️  Failed to send chat intro: {}", e);
-                    } else {
-                        // Save intro message
-                        let chat_id: Option<(i32,)> = match &self.pool {
-                            DbPool::Sqlite(p) => sqlx::query_as("SELECT id FROM job_chats_v2 WHERE hh_chat_id = ?").bind(&negotiation_id).fetch_optional(p).await.ok().flatten(),
-                            DbPool::Postgres(p) => sqlx::query_as("SELECT id FROM job_chats_v2 WHERE hh_chat_id = $1").bind(&negotiation_id).fetch_optional(p).await.ok().flatten(),
-                        };
-
-                        if let Some((cid,)) = chat_id {
-                            match &self.pool {
-                                DbPool::Sqlite(p) => {
-                                    sqlx::query("INSERT INTO job_chat_messages (chat_id, author_type, text, is_auto_response) VALUES (?, 'applicant', ?, 1)").bind(cid).bind(&chat_intro).execute(p).await.ok();
-                                },
-                                DbPool::Postgres(p) => {
-                                    sqlx::query("INSERT INTO job_chat_messages (chat_id, author_type, text, is_auto_response) VALUES ($1, 'applicant', $2, TRUE)").bind(cid).bind(&chat_intro).execute(p).await.ok();
-                                }
-                            }
-                        }
-                    }
+                if let Err(e) = self.apply_vacancy(&apply_ctx, &to_apply).await {
+                    warn!("{}", e);
+                    continue;
                 }
 
                 // Update tag applied count
@@ -624,7 +606,226 @@ impl JobScheduler {
             &format!("📊 Поиск завершен: найдено {}, оценено {}, откликов {}", total_found, total_evaluated, total_applied)
         ).await.ok();
 
-        println!("✅ Job search completed. Found: {}, Evaluated: {}, Applied: {}", total_found, total_evaluated, total_applied);
+        info!("Job search completed. Found: {}, Evaluated: {}, Applied: {}", total_found, total_evaluated, total_applied);
+        Ok(())
+    }
+
+    /// `JOBS_REQUIRE_APPROVAL`: hold matching vacancies as `pending_approval` until the admin
+    /// approves them from the Telegram bot or the admin panel
+    pub fn approval_required() -> bool {
+        std::env::var("JOBS_REQUIRE_APPROVAL")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false)
+    }
+
+    /// Post the approval request; the Telegram bot forwards it with approve/skip buttons
+    async fn request_approval(&self, vacancy: &VacancyToApply<'_>, url: &str) {
+        info!("Waiting for approval: {} (AI score: {})", vacancy.title, vacancy.ai_score.unwrap_or(0));
+        self.log_activity(
+            "approval",
+            Some(vacancy.db_id),
+            &format!("⏳ Ждёт подтверждения: {} в {} (AI: {}%)", vacancy.title, vacancy.company, vacancy.ai_score.unwrap_or(0))
+        ).await.ok();
+
+        NotificationService::post(
+            &self.pool,
+            NewNotification::new(
+                category::JOBS,
+                NotificationPriority::High,
+                "Отклик ждёт подтверждения",
+                format!("{} в {} (AI: {}%)", vacancy.title, vacancy.company, vacancy.ai_score.unwrap_or(0)),
+            )
+            .link("/admin/jobs")
+            .data(serde_json::json!({
+                "approval": APPROVAL_JOB_APPLICATION,
+                "vacancy_id": vacancy.db_id,
+                "url": url,
+            })),
+        )
+        .await;
+    }
+
+    /// Cover letter, HH application, response/chat records and the chat intro
+    async fn apply_vacancy(&self, ctx: &ApplyContext<'_>, vacancy: &VacancyToApply<'_>) -> Result<(), String> {
+        let ai_client = ctx.ai_client;
+        let hh_client = ctx.hh_client;
+        let (title, company) = (vacancy.title, vacancy.company);
+        let ai_score = vacancy.ai_score;
+        let vacancy_db_id = vacancy.db_id;
+
+        // Generate cover letter
+        let cover_letter = ai_client
+            .generate_cover_letter(title, vacancy.description, ctx.resume_text, ctx.telegram, ctx.email)
+            .await
+            .map_err(|e| format!("Failed to generate cover letter: {}", e))?;
+
+        // Apply to vacancy
+        let negotiation_id = hh_client
+            .apply_to_vacancy(vacancy.hh_id, &cover_letter, ctx.resume_id)
+            .await
+            .map_err(|e| format!("Failed to apply to vacancy {}: {}", title, e))?;
+
+        info!("Applied to: {} (AI score: {})", title, ai_score.unwrap_or(0));
+
+        // Update vacancy status
+        match &self.pool {
+            DbPool::Sqlite(p) => { sqlx::query("UPDATE job_vacancies SET status = 'applied', applied_at = datetime('now') WHERE id = ?").bind(vacancy_db_id).execute(p).await.map(|_| ()).ok(); },
+            DbPool::Postgres(p) => { sqlx::query("UPDATE job_vacancies SET status = 'applied', applied_at = NOW() WHERE id = $1").bind(vacancy_db_id).execute(p).await.map(|_| ()).ok(); },
+        }
+
+        // Save response
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("INSERT INTO job_responses (vacancy_id, hh_negotiation_id, cover_letter, status) VALUES (?, ?, ?, 'sent')")
+                .bind(vacancy_db_id).bind(&negotiation_id).bind(&cover_letter).execute(p).await.ok();
+            },
+            DbPool::Postgres(p) => {
+                sqlx::query("INSERT INTO job_responses (vacancy_id, hh_negotiation_id, cover_letter, status) VALUES ($1, $2, $3, 'sent')")
+                .bind(vacancy_db_id).bind(&negotiation_id).bind(&cover_letter).execute(p).await.ok();
+            }
+        }
+
+        // Create chat record
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("INSERT INTO job_chats_v2 (vacancy_id, hh_chat_id, employer_name) VALUES (?, ?, ?)")
+                .bind(vacancy_db_id).bind(&negotiation_id).bind(company).execute(p).await.ok();
+            },
+            DbPool::Postgres(p) => {
+                sqlx::query("INSERT INTO job_chats_v2 (vacancy_id, hh_chat_id, employer_name) VALUES ($1, $2, $3)")
+                .bind(vacancy_db_id).bind(&negotiation_id).bind(company).execute(p).await.ok();
+            }
+        }
+
+        // Log activity
+        self.log_activity(
+            "apply",
+            Some(vacancy_db_id),
+            &format!("✅ Отклик на {} в {} (AI: {}%)", title, company, ai_score.unwrap_or(0))
+        ).await.ok();
+
+        // Generate and send chat intro
+        if let Ok(chat_intro) = ai_client.generate_chat_intro(&cover_letter, ctx.telegram, ctx.email).await {
+            if let Err(e) = hh_client.send_message(&negotiation_id, &chat_intro).await {
+                warn!("Failed to send chat intro: {}", e);
+            } else {
+                // Save intro message
+                let chat_id: Option<(i32,)> = match &self.pool {
+                    DbPool::Sqlite(p) => sqlx::query_as("SELECT id FROM job_chats_v2 WHERE hh_chat_id = ?").bind(&negotiation_id).fetch_optional(p).await.ok().flatten(),
+                    DbPool::Postgres(p) => sqlx::query_as("SELECT id FROM job_chats_v2 WHERE hh_chat_id = $1").bind(&negotiation_id).fetch_optional(p).await.ok().flatten(),
+                };
+
+                if let Some((cid,)) = chat_id {
+                    match &self.pool {
+                        DbPool::Sqlite(p) => {
+                            sqlx::query("INSERT INTO job_chat_messages (chat_id, author_type, text, is_auto_response) VALUES (?, 'applicant', ?, 1)").bind(cid).bind(&chat_intro).execute(p).await.ok();
+                        },
+                        DbPool::Postgres(p) => {
+                            sqlx::query("INSERT INTO job_chat_messages (chat_id, author_type, text, is_auto_response) VALUES ($1, 'applicant', $2, TRUE)").bind(cid).bind(&chat_intro).execute(p).await.ok();
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply to a vacancy held for approval
+    pub async fn approve_application(&self, vacancy_db_id: i32) -> Result<(), String> {
+        // Claim it first so a double tap cannot apply twice
+        let claimed = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query("UPDATE job_vacancies SET status = 'applying' WHERE id = ? AND status = 'pending_approval'").bind(vacancy_db_id).execute(p).await.map(|r| r.rows_affected()),
+            DbPool::Postgres(p) => sqlx::query("UPDATE job_vacancies SET status = 'applying' WHERE id = $1 AND status = 'pending_approval'").bind(vacancy_db_id).execute(p).await.map(|r| r.rows_affected()),
+        }
+        .map_err(|e| e.to_string())?;
+        if claimed == 0 {
+            return Err("Вакансия не ждёт подтверждения".to_string());
+        }
+
+        let result = self.apply_approved(vacancy_db_id).await;
+        if result.is_err() {
+            match &self.pool {
+                DbPool::Sqlite(p) => { sqlx::query("UPDATE job_vacancies SET status = 'pending_approval' WHERE id = ? AND status = 'applying'").bind(vacancy_db_id).execute(p).await.ok(); },
+                DbPool::Postgres(p) => { sqlx::query("UPDATE job_vacancies SET status = 'pending_approval' WHERE id = $1 AND status = 'applying'").bind(vacancy_db_id).execute(p).await.ok(); },
+            }
+        }
+        result
+    }
+
+    async fn apply_approved(&self, vacancy_db_id: i32) -> Result<(), String> {
+        let vacancy: (String, String, String, Option<String>, Option<i32>) = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query_as("SELECT hh_vacancy_id, title, company, description, ai_score FROM job_vacancies WHERE id = ?").bind(vacancy_db_id).fetch_one(p).await,
+            DbPool::Postgres(p) => sqlx::query_as("SELECT hh_vacancy_id, title, company, description, ai_score FROM job_vacancies WHERE id = $1").bind(vacancy_db_id).fetch_one(p).await,
+        }
+        .map_err(|e| e.to_string())?;
+        let (hh_id, title, company, description, ai_score) = vacancy;
+
+        let access_token = self.get_valid_token().await?;
+        let api_key = std::env::var("OPENROUTER_API_KEY").unwrap_or_default();
+        let model = std::env::var("AI_MODEL").unwrap_or_else(|_| "google/gemini-2.0-flash-001".to_string());
+        let ai_client = AIClient::new(api_key, model);
+        let resume_text = self.get_resume_text().await?;
+        let (telegram, email) = self.get_contacts().await?;
+
+        let mut hh_client = HHClient::new();
+        hh_client.set_token(access_token);
+        let resumes = hh_client.get_resumes().await.map_err(|e| e.to_string())?;
+        let resume_id = resumes
+            .first()
+            .and_then(|r| r["id"].as_str())
+            .ok_or("No resume found on HH.ru")?;
+
+        let ctx = ApplyContext {
+            hh_client: &hh_client,
+            ai_client: &ai_client,
+            resume_id,
+            resume_text: &resume_text,
+            telegram: &telegram,
+            email: &email,
+        };
+        let vacancy = VacancyToApply {
+            db_id: vacancy_db_id,
+            hh_id: &hh_id,
+            title: &title,
+            company: &company,
+            description: description.as_deref().unwrap_or(""),
+            ai_score,
+        };
+        self.apply_vacancy(&ctx, &vacancy).await?;
+
+        // Count it in today's stats like applications sent by the search cycle
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "INSERT INTO job_search_stats (date, searches_count, vacancies_found, applications_sent) VALUES (?, 0, 0, 1)
+                     ON CONFLICT(date) DO UPDATE SET applications_sent = applications_sent + 1"
+                )
+                .bind(&today).execute(p).await.ok();
+            },
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "INSERT INTO job_search_stats (date, searches_count, vacancies_found, applications_sent) VALUES ($1, 0, 0, 1)
+                     ON CONFLICT(date) DO UPDATE SET applications_sent = job_search_stats.applications_sent + 1"
+                )
+                .bind(&today).execute(p).await.ok();
+            }
+        }
+        Ok(())
+    }
+
+    /// Decline a vacancy held for approval
+    pub async fn skip_application(&self, vacancy_db_id: i32) -> Result<(), String> {
+        let skipped = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query("UPDATE job_vacancies SET status = 'ignored' WHERE id = ? AND status = 'pending_approval'").bind(vacancy_db_id).execute(p).await.map(|r| r.rows_affected()),
+            DbPool::Postgres(p) => sqlx::query("UPDATE job_vacancies SET status = 'ignored' WHERE id = $1 AND status = 'pending_approval'").bind(vacancy_db_id).execute(p).await.map(|r| r.rows_affected()),
+        }
+        .map_err(|e| e.to_string())?;
+        if skipped == 0 {
+            return Err("Вакансия не ждёт подтверждения".to_string());
+        }
+        self.log_activity("approval", Some(vacancy_db_id), "⏭️ Отклик отклонён вручную").await.ok();
         Ok(())
     }
 
@@ -678,6 +879,17 @@ impl JobScheduler {
                     };
 
                     self.log_activity("response", Some(db_id), event).await.ok();
+                    let priority = match new_status {
+                        "invited" => NotificationPriority::High,
+                        "rejected" => NotificationPriority::Normal,
+                        _ => NotificationPriority::Low,
+                    };
+                    let vacancy = negotiation["vacancy"]["name"].as_str().unwrap_or(vacancy_id);
+                    NotificationService::post(
+                        &self.pool,
+                        NewNotification::new(category::JOBS, priority, event, vacancy).link("/admin/jobs"),
+                    )
+                    .await;
 
                     // Update daily stats
                     let today = chrono::Local::now().format("%Y-%m-%d").to_string();
@@ -807,11 +1019,22 @@ impl JobScheduler {
                     continue;
                 }
 
-                println!("💬 New message in chat for: {}", title);
+                info!("New message in chat for: {}", title);
+                NotificationService::post(
+                    &self.pool,
+                    NewNotification::new(
+                        category::JOBS,
+                        NotificationPriority::Normal,
+                        format!("💬 Новое сообщение: {}", title),
+                        message_text,
+                    )
+                    .link("/admin/jobs"),
+                )
+                .await;
                 let analysis = ai_client.analyze_message(message_text, &chat_history).await;
                 let (ai_sentiment, ai_intent, is_bot, should_invite_tg) = match analysis {
                     Ok(a) => (Some(a.sentiment), Some(a.intent), a.is_bot, a.should_invite_telegram && !telegram_invited),
-                    Err(e) => { eprintln!("⚠️ Message analysis failed: {}", e); (None, None, AIClient::is_bot_message(message_text), false) }
+                    Err(e) => { warn!("Message analysis failed: {}", e); (None, None, AIClient::is_bot_message(message_text), false) }
                 };
 
                 // Save message
@@ -964,7 +1187,7 @@ impl JobScheduler {
             return Ok(access_token);
         }
 
-        println!("🔄 HH token expired or expiring soon. Refreshing...");
+        info!("HH token expired or expiring soon. Refreshing...");
 
         let client_id = std::env::var("HH_CLIENT_ID").map_err(|_| "HH_CLIENT_ID not set")?;
         let client_secret = std::env::var("HH_CLIENT_SECRET").map_err(|_| "HH_CLIENT_SECRET not set")?;
@@ -1000,12 +1223,12 @@ impl JobScheduler {
             }
         }
 
-        println!("✅ HH token refreshed successfully");
+        info!("HH token refreshed successfully");
         Ok(new_access_token)
     }
 
     async fn sync_anime(&self) -> Result<(), String> {
-        println!("🎬 Starting daily anime sync...");
+        info!("Starting daily anime sync...");
 
         use crate::anime::{GoogleSheetsClient, ShikimoriClient};
 
@@ -1022,7 +1245,7 @@ impl JobScheduler {
         let rows = match sheets_client.fetch_sheet_data(current_year).await {
             Ok(r) => r,
             Err(e) => {
-                eprintln!("Error fetching sheet for year {}: {}", current_year, e);
+                error!("Error fetching sheet for year {}: {}", current_year, e);
                 return Err(e);
             }
         };
@@ -1121,7 +1344,7 @@ impl JobScheduler {
             }
         }
 
-        println!("✅ Anime sync completed. Updated {} entries", total_synced);
+        info!("Anime sync completed. Updated {} entries", total_synced);
         Ok(())
     }
 }
@@ -0,0 +1,195 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use regex::Regex;
+use serde::Serialize;
+
+/// Syslog severities as used by journald `PRIORITY`
+pub const LEVEL_NAMES: [&str; 8] = ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+
+/// Default severity for records that do not carry one
+pub const DEFAULT_PRIORITY: u8 = 6;
+
+/// One parsed log line
+#[derive(Debug, Clone, Serialize)]
+pub struct LogRecord {
+    /// Opaque position for pagination: a journald cursor or a file offset
+    pub cursor: String,
+    /// RFC 3339, UTC
+    pub timestamp: Option<String>,
+    pub priority: u8,
+    pub level: String,
+    /// systemd unit, or the tracing target for the server's own logs
+    pub unit: Option<String>,
+    pub identifier: Option<String>,
+    pub pid: Option<i64>,
+    pub hostname: Option<String>,
+    /// Correlation ID of the request or scheduler cycle that produced the record
+    pub request_id: Option<String>,
+    pub message: String,
+}
+
+impl LogRecord {
+    pub fn level_name(priority: u8) -> String {
+        LEVEL_NAMES
+            .get(priority as usize)
+            .unwrap_or(&"debug")
+            .to_string()
+    }
+
+    /// Single line in the `journalctl -o short-iso` style
+    pub fn to_line(&self) -> String {
+        let source = match (&self.unit, &self.identifier) {
+            (Some(unit), _) => unit.as_str(),
+            (None, Some(identifier)) => identifier.as_str(),
+            (None, None) => "-",
+        };
+        format!(
+            "{} {} [{}] {}",
+            self.timestamp.as_deref().unwrap_or("-"),
+            source,
+            self.level,
+            self.message
+        )
+    }
+}
+
+/// Raw filter parameters from the query string
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Minimum severity: "err", "warning", "info"... or a number 0-7
+    pub level: Option<String>,
+    pub unit: Option<String>,
+    /// RFC 3339, "YYYY-MM-DD HH:MM:SS" or "YYYY-MM-DD" (UTC)
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Regular expression matched against the message
+    pub regex: Option<String>,
+    /// Only records of one request (`X-Request-Id`)
+    pub request_id: Option<String>,
+    /// Continue after this record (older entries)
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Validated filter
+#[derive(Debug, Clone)]
+pub struct LogQuery {
+    pub max_priority: Option<u8>,
+    pub unit: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub regex: Option<Regex>,
+    pub request_id: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: usize,
+}
+
+impl LogQuery {
+    pub fn from_filter(filter: &LogFilter) -> Result<Self, String> {
+        let max_priority = filter.level.as_deref().map(parse_level).transpose()?;
+
+        let unit = match filter.unit.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+            Some(unit)
+                if unit
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '@' | ':' | '-' | '\\')) =>
+            {
+                Some(unit.to_string())
+            }
+            Some(_) => return Err("Invalid unit name".to_string()),
+            None => None,
+        };
+
+        let regex = filter
+            .regex
+            .as_deref()
+            .filter(|r| !r.is_empty())
+            .map(|r| Regex::new(r).map_err(|e| format!("Invalid regex: {}", e)))
+            .transpose()?;
+
+        Ok(Self {
+            max_priority,
+            unit,
+            since: filter.since.as_deref().map(parse_time).transpose()?,
+            until: filter.until.as_deref().map(parse_time).transpose()?,
+            regex,
+            request_id: filter.request_id.clone().filter(|r| !r.is_empty()),
+            cursor: filter.cursor.clone().filter(|c| !c.is_empty()),
+            limit: filter.limit.unwrap_or(100).clamp(1, 1000),
+        })
+    }
+
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        if self.max_priority.is_some_and(|max| record.priority > max) {
+            return false;
+        }
+        if let Some(unit) = &self.unit {
+            let matches_unit = |value: &Option<String>| {
+                value
+                    .as_deref()
+                    .is_some_and(|v| v == unit || v.trim_end_matches(".service") == unit)
+            };
+            if !matches_unit(&record.unit) && !matches_unit(&record.identifier) {
+                return false;
+            }
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let time = match record.timestamp.as_deref().and_then(|t| DateTime::parse_from_rfc3339(t).ok()) {
+                Some(time) => time.with_timezone(&Utc),
+                None => return false,
+            };
+            if self.since.is_some_and(|since| time < since) || self.until.is_some_and(|until| time >= until) {
+                return false;
+            }
+        }
+        if self.request_id.is_some() && record.request_id != self.request_id {
+            return false;
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&record.message) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Page of records, newest first
+#[derive(Debug, Serialize)]
+pub struct LogPage {
+    pub records: Vec<LogRecord>,
+    /// Pass as `cursor` to get older records; None when the log is exhausted
+    pub next_cursor: Option<String>,
+    /// "journald" or the file path
+    pub source: String,
+}
+
+fn parse_level(level: &str) -> Result<u8, String> {
+    let level = level.trim().to_lowercase();
+    if let Ok(n) = level.parse::<u8>() {
+        return if n <= 7 { Ok(n) } else { Err("Level must be 0-7".to_string()) };
+    }
+    let priority = match level.as_str() {
+        "error" | "fatal" => 3,
+        "warn" => 4,
+        "trace" => 7,
+        other => LEVEL_NAMES
+            .iter()
+            .position(|name| *name == other)
+            .ok_or_else(|| format!("Unknown level: {}", other))? as u8,
+    };
+    Ok(priority)
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Ok(time.and_utc());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+    Err(format!("Invalid time: {}", value))
+}
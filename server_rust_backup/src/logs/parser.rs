@@ -0,0 +1,247 @@
+use crate::logs::models::*;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::Value;
+
+/// Parse one line of any supported format; `cursor` is used when the line carries none
+pub fn parse_line(line: &str, cursor: String) -> Option<LogRecord> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    if line.trim().is_empty() {
+        return None;
+    }
+
+    if line.trim_start().starts_with('{') {
+        if let Ok(Value::Object(map)) = serde_json::from_str::<Value>(line) {
+            let value = Value::Object(map);
+            if value.get("MESSAGE").is_some() || value.get("__REALTIME_TIMESTAMP").is_some() {
+                return Some(parse_journal(&value, cursor));
+            }
+            return Some(parse_structured(&value, cursor));
+        }
+    }
+
+    Some(parse_text(line, cursor))
+}
+
+/// `journalctl -o json` entry
+pub fn parse_journal(value: &Value, cursor: String) -> LogRecord {
+    let field = |name: &str| value.get(name).and_then(journal_string);
+
+    let timestamp = field("__REALTIME_TIMESTAMP")
+        .and_then(|us| us.parse::<i64>().ok())
+        .and_then(DateTime::from_timestamp_micros)
+        .map(format_time);
+    let priority = field("PRIORITY")
+        .and_then(|p| p.parse::<u8>().ok())
+        .filter(|p| *p <= 7)
+        .unwrap_or(DEFAULT_PRIORITY);
+
+    LogRecord {
+        cursor: field("__CURSOR").unwrap_or(cursor),
+        timestamp,
+        priority,
+        level: LogRecord::level_name(priority),
+        unit: field("_SYSTEMD_UNIT").or_else(|| field("UNIT")),
+        identifier: field("SYSLOG_IDENTIFIER").or_else(|| field("_COMM")),
+        pid: field("_PID").and_then(|p| p.parse().ok()),
+        hostname: field("_HOSTNAME"),
+        request_id: field("REQUEST_ID"),
+        message: field("MESSAGE").unwrap_or_default(),
+    }
+}
+
+/// JSON logs of the server itself (`tracing` JSON format and similar)
+fn parse_structured(value: &Value, cursor: String) -> LogRecord {
+    let string = |names: &[&str]| {
+        names.iter().find_map(|name| {
+            value
+                .get(*name)
+                .or_else(|| value.get("fields").and_then(|f| f.get(*name)))
+                .and_then(|v| match v {
+                    Value::String(s) => Some(s.clone()),
+                    Value::Null => None,
+                    other => Some(other.to_string()),
+                })
+        })
+    };
+
+    let timestamp = string(&["timestamp", "time", "ts", "@timestamp"])
+        .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+        .map(|t| format_time(t.with_timezone(&Utc)));
+    let priority = string(&["level", "severity", "lvl"])
+        .map(|l| level_priority(&l))
+        .unwrap_or(DEFAULT_PRIORITY);
+
+    LogRecord {
+        cursor,
+        timestamp,
+        priority,
+        level: LogRecord::level_name(priority),
+        unit: string(&["target", "unit", "module"]),
+        identifier: None,
+        pid: value.get("pid").and_then(Value::as_i64),
+        hostname: string(&["hostname", "host"]),
+        request_id: string(&["request_id"]).or_else(|| span_field(value, "request_id")),
+        message: string(&["message", "msg"]).unwrap_or_else(|| value.to_string()),
+    }
+}
+
+/// Field of the current span or, failing that, the innermost span that has it (`tracing` JSON)
+fn span_field(value: &Value, name: &str) -> Option<String> {
+    let from_span = |span: &Value| span.get(name).and_then(Value::as_str).map(str::to_string);
+    value.get("span").and_then(from_span).or_else(|| {
+        value
+            .get("spans")
+            .and_then(Value::as_array)
+            .and_then(|spans| spans.iter().rev().find_map(from_span))
+    })
+}
+
+/// Plain text: an optional leading RFC 3339 timestamp and a level keyword
+fn parse_text(line: &str, cursor: String) -> LogRecord {
+    let (timestamp, rest) = match line.split_once(' ') {
+        Some((first, rest)) => match DateTime::parse_from_rfc3339(first) {
+            Ok(time) => (Some(format_time(time.with_timezone(&Utc))), rest.trim_start()),
+            Err(_) => (None, line),
+        },
+        None => (None, line),
+    };
+
+    let priority = rest
+        .split_whitespace()
+        .take(3)
+        .map(|word| word.trim_matches(|c: char| !c.is_ascii_alphabetic()))
+        .find_map(|word| match word.to_uppercase().as_str() {
+            "ERROR" | "ERR" | "FATAL" | "CRIT" => Some(3),
+            "WARN" | "WARNING" => Some(4),
+            "INFO" => Some(6),
+            "DEBUG" | "TRACE" => Some(7),
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_PRIORITY);
+
+    LogRecord {
+        cursor,
+        timestamp,
+        priority,
+        level: LogRecord::level_name(priority),
+        unit: None,
+        identifier: None,
+        pid: None,
+        hostname: None,
+        request_id: None,
+        message: rest.to_string(),
+    }
+}
+
+/// Journald fields are strings, or byte arrays for non-UTF-8 values
+fn journal_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes.iter().filter_map(|b| b.as_u64().map(|b| b as u8)).collect();
+            Some(String::from_utf8_lossy(&bytes).to_string())
+        }
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn level_priority(level: &str) -> u8 {
+    match level.trim().to_lowercase().as_str() {
+        "emerg" | "emergency" | "panic" => 0,
+        "alert" => 1,
+        "crit" | "critical" | "fatal" => 2,
+        "err" | "error" => 3,
+        "warn" | "warning" => 4,
+        "notice" => 5,
+        "info" => 6,
+        "debug" | "trace" => 7,
+        other => other.parse().ok().filter(|p| *p <= 7).unwrap_or(DEFAULT_PRIORITY),
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JOURNAL: &str = include_str!("../../fixtures/logs/journal.json");
+
+    fn journal() -> Vec<LogRecord> {
+        JOURNAL
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| parse_line(line, format!("line:{}", i)))
+            .collect()
+    }
+
+    #[test]
+    fn journal_fixture_parses_every_entry() {
+        let records = journal();
+        assert_eq!(records.len(), 15);
+        // Journald cursors win over the line cursor
+        assert!(records.iter().all(|r| r.cursor.starts_with("s=5b9f1e0c")));
+
+        let first = &records[0];
+        assert_eq!(first.timestamp.as_deref(), Some("2024-10-18T10:00:00.000000Z"));
+        assert_eq!((first.priority, first.level.as_str()), (6, "info"));
+        assert_eq!(first.unit.as_deref(), Some("bgalin-backend.service"));
+        assert_eq!(first.identifier.as_deref(), Some("server"));
+        assert_eq!(first.pid, Some(412));
+        assert_eq!(first.hostname.as_deref(), Some("bgalin"));
+        assert_eq!(first.message, "🚀 Server starting...");
+        assert_eq!(records[1].timestamp.as_deref(), Some("2024-10-18T10:00:37.000123Z"));
+    }
+
+    #[test]
+    fn journal_priorities_map_to_levels() {
+        let levels: Vec<String> = journal().into_iter().map(|r| r.level).collect();
+        assert_eq!(levels[5], "warning");
+        assert_eq!(levels[6], "notice");
+        assert_eq!(levels[7], "err");
+        assert_eq!(levels[10], "debug");
+        assert_eq!(levels[12], "crit");
+    }
+
+    #[test]
+    fn journal_units_and_messages_are_extracted() {
+        let records = journal();
+        assert_eq!(records[3].unit.as_deref(), Some("nginx.service"));
+        assert_eq!(records[4].identifier.as_deref(), Some("sshd"));
+        assert_eq!(
+            records[4].message,
+            "Accepted publickey for deploy from 203.0.113.10 port 51522 ssh2"
+        );
+        assert_eq!(records[11].identifier.as_deref(), Some("CRON"));
+        // Non-UTF-8 messages come as byte arrays
+        assert_eq!(records[13].message, "Journal stopped \u{FFFD}");
+        assert_eq!(records[13].unit.as_deref(), Some("systemd-journald.service"));
+    }
+
+    #[test]
+    fn structured_and_plain_lines_are_parsed() {
+        let structured = parse_line(
+            r#"{"timestamp":"2024-10-18T10:00:00+03:00","level":"WARN","target":"server::jobs","fields":{"message":"HH token expired"},"span":{"request_id":"req-1"}}"#,
+            "offset:0".to_string(),
+        )
+        .unwrap();
+        assert_eq!(structured.cursor, "offset:0");
+        assert_eq!(structured.timestamp.as_deref(), Some("2024-10-18T07:00:00.000000Z"));
+        assert_eq!(structured.level, "warning");
+        assert_eq!(structured.unit.as_deref(), Some("server::jobs"));
+        assert_eq!(structured.request_id.as_deref(), Some("req-1"));
+        assert_eq!(structured.message, "HH token expired");
+
+        let plain = parse_line("2024-10-18T10:00:00Z ERROR disk full\r\n", "offset:9".to_string()).unwrap();
+        assert_eq!(plain.timestamp.as_deref(), Some("2024-10-18T10:00:00.000000Z"));
+        assert_eq!(plain.level, "err");
+        assert_eq!(plain.message, "ERROR disk full");
+
+        let bare = parse_line("just text", String::new()).unwrap();
+        assert_eq!((bare.timestamp, bare.level.as_str()), (None, "info"));
+        assert!(parse_line("   \n", String::new()).is_none());
+    }
+}
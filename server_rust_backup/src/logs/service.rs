@@ -0,0 +1,588 @@
+use crate::logs::models::*;
+use crate::logs::parser::{parse_journal, parse_line};
+use crate::telemetry::subscriber::DEFAULT_LOG_FILE;
+use std::env;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tracing::error;
+
+/// Entries journalctl returns per call while filling a page
+const JOURNAL_BATCH: usize = 500;
+/// Upper bound of entries inspected for one page when the regex filters most of them out
+const MAX_SCANNED: usize = 20_000;
+/// Bytes of a log file searched for one page, read backwards from the cursor
+const MAX_FILE_WINDOW: u64 = 64 * 1024 * 1024;
+/// Size of each backwards read, so a short page costs a few reads rather than the whole window
+const READ_CHUNK: u64 = 64 * 1024;
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where records come from
+#[derive(Debug, Clone)]
+pub enum LogSource {
+    /// `journalctl -o json`
+    Journald,
+    /// A file with journald JSON export lines, structured JSON or plain text
+    File(PathBuf),
+}
+
+#[derive(Clone)]
+pub struct LogService {
+    source: LogSource,
+}
+
+impl LogService {
+    /// `LOG_SOURCE=journald|file`; by default journald when `journalctl` is available, otherwise `LOG_FILE`
+    pub async fn init() -> Self {
+        let file = || LogSource::File(PathBuf::from(env::var("LOG_FILE").unwrap_or_else(|_| DEFAULT_LOG_FILE.to_string())));
+
+        let source = match env::var("LOG_SOURCE").ok().as_deref() {
+            Some("journald") => LogSource::Journald,
+            Some("file") => file(),
+            _ => {
+                let journald = Command::new("journalctl")
+                    .arg("--version")
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .await
+                    .map(|s| s.success())
+                    .unwrap_or(false);
+                if journald {
+                    LogSource::Journald
+                } else {
+                    file()
+                }
+            }
+        };
+
+        Self { source }
+    }
+
+    pub fn source_name(&self) -> String {
+        match &self.source {
+            LogSource::Journald => "journald".to_string(),
+            LogSource::File(path) => path.to_string_lossy().to_string(),
+        }
+    }
+
+    /// Matching records, newest first, starting after `query.cursor`
+    pub async fn query(&self, query: &LogQuery) -> Result<LogPage, String> {
+        let (records, next_cursor) = match &self.source {
+            LogSource::Journald => query_journal(query).await?,
+            LogSource::File(path) => query_file(path, query).await?,
+        };
+
+        Ok(LogPage {
+            records,
+            next_cursor,
+            source: self.source_name(),
+        })
+    }
+
+    /// New matching records as they are written; the feed stops when the receiver is dropped
+    pub fn tail(&self, query: LogQuery) -> mpsc::Receiver<LogRecord> {
+        let (tx, rx) = mpsc::channel(256);
+        let source = self.source.clone();
+
+        tokio::spawn(async move {
+            let result = match source {
+                LogSource::Journald => tail_journal(&query, &tx).await,
+                LogSource::File(path) => tail_file(&path, &query, &tx).await,
+            };
+            if let Err(e) = result {
+                error!("Log tail stopped: {}", e);
+            }
+        });
+
+        rx
+    }
+}
+
+/// journalctl arguments shared by paging and tailing; the regex is applied here, not by journald
+fn journal_args(query: &LogQuery) -> Vec<String> {
+    let mut args = vec!["-o".to_string(), "json".to_string(), "--no-pager".to_string()];
+    if let Some(unit) = &query.unit {
+        args.push(format!("--unit={}", unit));
+    }
+    if let Some(priority) = query.max_priority {
+        args.push(format!("--priority={}", priority));
+    }
+    if let Some(since) = query.since {
+        args.push(format!("--since=@{}", since.timestamp()));
+    }
+    if let Some(until) = query.until {
+        args.push(format!("--until=@{}", until.timestamp()));
+    }
+    args
+}
+
+async fn query_journal(query: &LogQuery) -> Result<(Vec<LogRecord>, Option<String>), String> {
+    let mut records = Vec::new();
+    let mut cursor = query.cursor.clone();
+    let mut scanned = 0;
+
+    loop {
+        let mut args = journal_args(query);
+        args.push("--reverse".to_string());
+        args.push(format!("--lines={}", JOURNAL_BATCH));
+        if let Some(cursor) = &cursor {
+            // With --reverse this continues with older entries
+            args.push(format!("--after-cursor={}", cursor));
+        }
+
+        let output = Command::new("journalctl")
+            .args(&args)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run journalctl: {}", e))?;
+        if !output.status.success() && output.stdout.is_empty() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+
+        let batch: Vec<LogRecord> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .map(|value| parse_journal(&value, String::new()))
+            .collect();
+        let exhausted = batch.len() < JOURNAL_BATCH;
+        scanned += batch.len();
+
+        for record in batch {
+            cursor = Some(record.cursor.clone());
+            if query.matches(&record) {
+                records.push(record);
+                if records.len() == query.limit {
+                    return Ok((records, cursor));
+                }
+            }
+        }
+
+        if exhausted {
+            return Ok((records, None));
+        }
+        if scanned >= MAX_SCANNED {
+            // Let the client continue the scan from here
+            return Ok((records, cursor));
+        }
+    }
+}
+
+async fn tail_journal(query: &LogQuery, tx: &mpsc::Sender<LogRecord>) -> Result<(), String> {
+    let mut args = journal_args(query);
+    args.retain(|a| !a.starts_with("--since") && !a.starts_with("--until"));
+    args.push("--follow".to_string());
+    args.push("--lines=0".to_string());
+
+    let mut child = Command::new("journalctl")
+        .args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to run journalctl: {}", e))?;
+    let stdout = child.stdout.take().ok_or("journalctl has no stdout")?;
+    let mut lines = BufReader::new(stdout).lines();
+
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line.map_err(|e| e.to_string())?,
+            _ = tx.closed() => return Ok(()),
+        };
+        let Some(line) = line else {
+            return Ok(());
+        };
+        let Ok(value) = serde_json::from_str(&line) else {
+            continue;
+        };
+        let record = parse_journal(&value, String::new());
+        if query.matches(&record) && tx.send(record).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Log files for `base`, oldest first: `base` itself, or the rolling files the server writes
+/// next to it (`server.log` -> `server.2024-10-18.log`)
+async fn log_files(base: &Path) -> Vec<PathBuf> {
+    if tokio::fs::metadata(base).await.map(|m| m.is_file()).unwrap_or(false) {
+        return vec![base.to_path_buf()];
+    }
+
+    let directory = base.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let stem = base.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let suffix = base.extension().and_then(|s| s.to_str()).map(|e| format!(".{}", e)).unwrap_or_default();
+    let prefix = format!("{}.", stem);
+
+    let mut files = Vec::new();
+    if let Ok(mut entries) = tokio::fs::read_dir(directory).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(&prefix) && name.ends_with(&suffix) && name.len() > prefix.len() + suffix.len() {
+                files.push(entry.path());
+            }
+        }
+    }
+    // Rotation dates sort chronologically
+    files.sort();
+    files
+}
+
+/// Cursors of file records are byte offsets of their lines within a named file
+fn file_cursor(path: &Path, offset: u64) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    format!("file:{}@{}", name, offset)
+}
+
+async fn query_file(base: &Path, query: &LogQuery) -> Result<(Vec<LogRecord>, Option<String>), String> {
+    let files = log_files(base).await;
+    if files.is_empty() {
+        return Err(format!("Failed to open {}: no log files", base.display()));
+    }
+
+    // Only lines that start before the cursor are older
+    let (index, end) = match &query.cursor {
+        Some(cursor) => {
+            let (name, offset) = match cursor.strip_prefix("offset:") {
+                Some(offset) => (None, offset),
+                None => cursor
+                    .strip_prefix("file:")
+                    .and_then(|c| c.rsplit_once('@'))
+                    .map(|(name, offset)| (Some(name), offset))
+                    .ok_or("Invalid cursor")?,
+            };
+            let offset = offset.parse::<u64>().map_err(|_| "Invalid cursor")?;
+            let index = match name {
+                Some(name) => files
+                    .iter()
+                    .position(|f| f.file_name().is_some_and(|n| n.to_string_lossy() == name))
+                    .ok_or("Log file of the cursor was removed")?,
+                None => files.len() - 1,
+            };
+            (index, Some(offset))
+        }
+        None => (files.len() - 1, None),
+    };
+    let path = &files[index];
+
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let len = file.metadata().await.map_err(|e| e.to_string())?.len();
+    let end = end.unwrap_or(len).min(len);
+    let floor = end.saturating_sub(MAX_FILE_WINDOW);
+
+    let mut records = Vec::new();
+    let mut scanned = 0usize;
+    // `pending` holds the bytes from `pos` up to the newest line not inspected yet
+    let mut pos = end;
+    let mut pending: Vec<u8> = Vec::new();
+    // Start of the oldest line inspected; older lines are left for the next page
+    let mut oldest = end;
+    loop {
+        let from = pos.saturating_sub(READ_CHUNK).max(floor);
+        let mut chunk = vec![0u8; (pos - from) as usize];
+        file.seek(SeekFrom::Start(from)).await.map_err(|e| e.to_string())?;
+        file.read_exact(&mut chunk).await.map_err(|e| e.to_string())?;
+        chunk.extend_from_slice(&pending);
+        pending = chunk;
+        pos = from;
+
+        // A line is complete once the newline before it is read, the first line of the file at its start
+        loop {
+            let split = match pending.iter().rposition(|b| *b == b'\n') {
+                Some(i) => i + 1,
+                None if pos == 0 && !pending.is_empty() => 0,
+                None => break,
+            };
+            let offset = pos + split as u64;
+            if split < pending.len() {
+                scanned += 1;
+                let text = String::from_utf8_lossy(&pending[split..]);
+                if let Some(record) = parse_line(&text, file_cursor(path, offset)) {
+                    if query.matches(&record) {
+                        records.push(record);
+                    }
+                }
+                if records.len() == query.limit || scanned >= MAX_SCANNED {
+                    let more = offset > 0 || index > 0;
+                    return Ok((records, more.then(|| file_cursor(path, offset))));
+                }
+            }
+            oldest = offset;
+            pending.truncate(split.saturating_sub(1));
+        }
+
+        if pos == 0 {
+            break;
+        }
+        // A partial line at the edge of the window is read whole by the next page
+        if pos == floor {
+            return Ok((records, Some(file_cursor(path, oldest))));
+        }
+    }
+
+    // The whole file was searched; continue in the previous rotated file
+    let next = if index > 0 {
+        let previous = &files[index - 1];
+        let len = tokio::fs::metadata(previous).await.map(|m| m.len()).unwrap_or(0);
+        Some(file_cursor(previous, len))
+    } else {
+        None
+    };
+    Ok((records, next))
+}
+
+async fn tail_file(base: &Path, query: &LogQuery, tx: &mpsc::Sender<LogRecord>) -> Result<(), String> {
+    let mut path = log_files(base).await.pop().unwrap_or_else(|| base.to_path_buf());
+    let mut position = tokio::fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+    let mut partial: Vec<u8> = Vec::new();
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(FILE_POLL_INTERVAL) => {}
+            _ = tx.closed() => return Ok(()),
+        }
+
+        // A new rolling file means rotation: follow it from the start
+        if let Some(newest) = log_files(base).await.pop() {
+            if newest != path {
+                path = newest;
+                position = 0;
+                partial.clear();
+            }
+        }
+
+        let Ok(mut file) = tokio::fs::File::open(&path).await else {
+            continue;
+        };
+        let len = file.metadata().await.map(|m| m.len()).unwrap_or(0);
+        if len < position {
+            // Truncated or rotated: start over
+            position = 0;
+            partial.clear();
+        }
+        if len == position {
+            continue;
+        }
+
+        file.seek(SeekFrom::Start(position)).await.map_err(|e| e.to_string())?;
+        let mut chunk = Vec::new();
+        file.take(len - position)
+            .read_to_end(&mut chunk)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let line_offset = position - partial.len() as u64;
+        position += chunk.len() as u64;
+        partial.extend_from_slice(&chunk);
+
+        // Emit complete lines, keep the unfinished tail for the next poll
+        let Some(last_newline) = partial.iter().rposition(|b| *b == b'\n') else {
+            continue;
+        };
+        let complete: Vec<u8> = partial.drain(..=last_newline).collect();
+
+        let mut offset = line_offset;
+        for line in complete.split(|b| *b == b'\n') {
+            let cursor = file_cursor(&path, offset);
+            offset += line.len() as u64 + 1;
+            let Some(record) = parse_line(&String::from_utf8_lossy(line), cursor) else {
+                continue;
+            };
+            if query.matches(&record) && tx.send(record).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    const JOURNAL: &str = include_str!("../../fixtures/logs/journal.json");
+
+    /// Service reading `file` the way it does on hosts without journald.
+    /// The lock is held until `init` has read the variables; it never waits on anything.
+    #[allow(clippy::await_holding_lock)]
+    async fn file_service(file: &Path) -> LogService {
+        let _env = test_support::env_lock();
+        env::set_var("LOG_SOURCE", "file");
+        env::set_var("LOG_FILE", file);
+        let service = LogService::init().await;
+        assert!(matches!(&service.source, LogSource::File(path) if path == file));
+        service
+    }
+
+    fn query(filter: LogFilter) -> LogQuery {
+        LogQuery::from_filter(&filter).unwrap()
+    }
+
+    fn messages(page: &LogPage) -> Vec<&str> {
+        page.records.iter().map(|r| r.message.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn file_source_filters_and_pages_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.log");
+        std::fs::write(&path, JOURNAL).unwrap();
+        let service = file_service(&path).await;
+
+        let filter = LogFilter {
+            level: Some("warning".to_string()),
+            unit: Some("bgalin-backend".to_string()),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let first = service.query(&query(filter.clone())).await.unwrap();
+        assert_eq!(first.source, path.to_string_lossy());
+        assert_eq!(
+            messages(&first),
+            [
+                "thread 'tokio-runtime-worker' panicked at src/jobs/scheduler.rs:88:21",
+                "Failed to write audit log: database is locked",
+            ]
+        );
+        let cursor = first.next_cursor.clone().unwrap();
+        assert!(cursor.starts_with("file:journal.log@"), "{}", cursor);
+
+        let second = service
+            .query(&query(LogFilter { cursor: Some(cursor), ..filter }))
+            .await
+            .unwrap();
+        assert_eq!(second.records.len(), 1);
+        assert!(second.records[0].message.starts_with("⚠️  Failed to load console commands"));
+        assert_eq!(second.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn file_source_applies_regex_and_time_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.log");
+        std::fs::write(&path, JOURNAL).unwrap();
+        let service = file_service(&path).await;
+
+        let sshd = service
+            .query(&query(LogFilter {
+                unit: Some("sshd".to_string()),
+                regex: Some("admin from [0-9.]+".to_string()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        assert_eq!(sshd.records.len(), 2);
+        assert!(sshd.records.iter().all(|r| r.identifier.as_deref() == Some("sshd")));
+
+        let window = service
+            .query(&query(LogFilter {
+                since: Some("2024-10-18 10:00:37".to_string()),
+                until: Some("2024-10-18T10:01:15Z".to_string()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        assert_eq!(messages(&window), ["✅ All systems ready", "📊 Database: sqlite://data.db"]);
+    }
+
+    #[tokio::test]
+    async fn paging_continues_into_older_rolling_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("server.2024-10-17.log"),
+            "2024-10-17T23:59:58Z INFO old one\n2024-10-17T23:59:59Z INFO old two\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("server.2024-10-18.log"),
+            "{\"timestamp\":\"2024-10-18T00:00:01Z\",\"level\":\"INFO\",\"fields\":{\"message\":\"new one\"}}\n\
+             {\"timestamp\":\"2024-10-18T00:00:02Z\",\"level\":\"INFO\",\"fields\":{\"message\":\"new two\"}}\n",
+        )
+        .unwrap();
+        // The base name itself does not exist, only its rolling files
+        let service = file_service(&dir.path().join("server.log")).await;
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = service
+                .query(&query(LogFilter { limit: Some(3), cursor, ..Default::default() }))
+                .await
+                .unwrap();
+            seen.extend(page.records.into_iter().map(|r| r.message));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(seen, ["new two", "new one", "INFO old two", "INFO old one"]);
+
+        let invalid = query(LogFilter { cursor: Some("file:gone.log@0".to_string()), ..Default::default() });
+        assert!(service.query(&invalid).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn file_pages_read_backwards_across_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.log");
+        // Several read chunks, with one line longer than a chunk in the middle
+        let mut text = String::new();
+        for i in 0..6000 {
+            if i == 3000 {
+                let long = "x".repeat(READ_CHUNK as usize * 2);
+                text.push_str(&format!("2024-10-18T10:00:00Z INFO line {} {}\n", i, long));
+            } else {
+                text.push_str(&format!("2024-10-18T10:00:00Z INFO line {}\n", i));
+            }
+        }
+        assert!(text.len() as u64 > 4 * READ_CHUNK);
+        std::fs::write(&path, &text).unwrap();
+        let service = file_service(&path).await;
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = service
+                .query(&query(LogFilter { limit: Some(1400), cursor, ..Default::default() }))
+                .await
+                .unwrap();
+            let numbers = page.records.iter().map(|r| r.message.split(' ').nth(2).unwrap().parse::<usize>());
+            seen.extend(numbers.map(Result::unwrap));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(seen, (0..6000).rev().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn file_scan_stops_at_the_line_bound_and_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("noisy.log");
+        let mut text = String::from("2024-10-18T10:00:00Z ERROR needle\n");
+        for i in 0..MAX_SCANNED + 10 {
+            text.push_str(&format!("2024-10-18T10:00:01Z INFO hay {}\n", i));
+        }
+        std::fs::write(&path, &text).unwrap();
+        let service = file_service(&path).await;
+
+        let filter = LogFilter { regex: Some("needle".to_string()), ..Default::default() };
+        let first = service.query(&query(filter.clone())).await.unwrap();
+        assert!(first.records.is_empty());
+        let cursor = first.next_cursor.unwrap();
+
+        let second = service
+            .query(&query(LogFilter { cursor: Some(cursor), ..filter }))
+            .await
+            .unwrap();
+        assert_eq!(messages(&second), ["ERROR needle"]);
+        assert_eq!(second.next_cursor, None);
+    }
+}
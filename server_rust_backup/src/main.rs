@@ -1,12 +1,18 @@
 mod alice;
 mod anime;
+mod audit;
 mod auth;
+mod backup;
+mod console;
 mod cs2;
 mod db;
 mod files;
 mod guards;
 mod jobs;
+mod logs;
+mod metrics;
 mod models;
+mod notifications;
 mod portfolio;
 mod publish;
 mod routes;
@@ -15,16 +21,42 @@ mod studio;
 mod sync;
 mod t2;
 mod telegram;
+mod telemetry;
+#[cfg(test)]
+mod test_support;
 
-use rocket::{launch, routes};
+use rocket::routes;
 use rocket_cors::{AllowedOrigins, CorsOptions};
 use std::env;
+use tracing::{error, info};
 
-#[launch]
-async fn rocket() -> _ {
+#[rocket::main]
+async fn main() {
     // Load environment variables from .env file
     dotenv::dotenv().ok();
 
+    // Structured logging to stdout and rolling files
+    let log_guard = telemetry::init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("db-transfer") {
+        std::process::exit(db::transfer::run_cli(&args[1..]).await);
+    }
+    if args.first().map(String::as_str) == Some("pc-keygen") {
+        let (secret, public) = alice::CommandQueueService::generate_signing_key();
+        println!("PC_COMMAND_SIGNING_KEY={}", secret);
+        println!("server_public_key = \"{}\"", public);
+        return;
+    }
+
+    if let Err(e) = rocket(log_guard).await.launch().await {
+        error!("Server failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn rocket(log_guard: Option<tracing_appender::non_blocking::WorkerGuard>) -> rocket::Rocket<rocket::Build> {
+
     // Get configuration from environment
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
 
@@ -43,7 +75,7 @@ async fn rocket() -> _ {
         .expect("Failed to create database pool");
 
     // Initialize Telegram bot
-    let telegram_bot = telegram::TelegramBot::new(telegram_bot_token);
+    let telegram_bot = telegram::TelegramBot::new(telegram_bot_token.clone());
 
     // Initialize Steam client
     let steam_client = steam::SteamClient::new(steam_api_key.clone(), steam_id.clone());
@@ -71,29 +103,148 @@ async fn rocket() -> _ {
         }
     });
 
+    // Spawn daily retention purge for the audit log
+    let audit_pool = pool.clone();
+    tokio::spawn(async move {
+        let retention_days = audit::AuditService::retention_days();
+        loop {
+            match audit::AuditService::purge_expired(&audit_pool, retention_days).await {
+                Ok(removed) if removed > 0 => info!("Audit log: removed {} old entries", removed),
+                Ok(_) => {}
+                Err(e) => error!("Failed to purge audit log: {}", e),
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(86400)).await; // Every day
+        }
+    });
+
+    // Spawn daily retention purge for the notification center
+    let notifications_pool = pool.clone();
+    tokio::spawn(async move {
+        let retention_days = notifications::NotificationService::retention_days();
+        loop {
+            match notifications::NotificationService::purge_expired(&notifications_pool, retention_days).await {
+                Ok(removed) if removed > 0 => info!("Notifications: removed {} old entries", removed),
+                Ok(_) => {}
+                Err(e) => error!("Failed to purge notifications: {}", e),
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(86400)).await; // Every day
+        }
+    });
+
+    // Spawn PC command queue maintenance: expiry, visibility timeouts and retention
+    let queue_pool = pool.clone();
+    tokio::spawn(async move {
+        let retention_hours: i32 = env::var("PC_COMMAND_RETENTION_HOURS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(168);
+        loop {
+            alice::CommandQueueService::sweep_queue(&queue_pool).await;
+            match alice::CommandQueueService::cleanup_old_commands(&queue_pool, retention_hours).await {
+                Ok(removed) if removed > 0 => info!("PC command queue: removed {} finished commands", removed),
+                Ok(_) => {}
+                Err(e) => error!("Failed to clean up PC command queue: {}", e),
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await; // Every minute
+        }
+    });
+
     // Initialize file service
     files::FileService::init().await.expect("Failed to initialize file service");
 
     // Initialize sync service
     sync::SyncService::init().expect("Failed to initialize sync service");
 
+    // Initialize server console sandbox
+    let console_service = console::ConsoleService::init().expect("Failed to initialize console");
+
+    // Initialize log viewer (journald or LOG_FILE)
+    let log_service = logs::LogService::init().await;
+
+    // Initialize host metrics history and alerting
+    let metrics_service = metrics::MetricsService::init(pool.clone()).await;
+    metrics_service
+        .clone()
+        .start_background_task(telegram::TelegramBot::new(telegram_bot_token.clone()), admin_telegram_id);
+
+    // Initialize database backups
+    let backup_service = backup::BackupService::init(pool.clone(), &database_url)
+        .expect("Failed to initialize backups");
+    backup_service
+        .clone()
+        .start_background_task(telegram::TelegramBot::new(telegram_bot_token.clone()), admin_telegram_id);
+
     // Initialize Alice Smart Home state
     let alice_state = alice::AliceState::new();
+    let pc_schedules = alice::PcScheduleService::init(pool.clone(), alice_state.clone())
+        .await
+        .expect("Failed to initialize PC command schedules");
+    let mqtt_client = alice::MqttClient::from_env(pool.clone(), alice_state.clone());
+    let yandex_callbacks = alice::YandexCallbacks::from_env();
+    let yandex_callbacks_enabled = yandex_callbacks.is_some();
+    if let Some(callbacks) = yandex_callbacks {
+        callbacks.start(pool.clone(), alice_state.clone(), pc_schedules.clone());
+    }
+    let rules = alice::RuleEngine::init(
+        pool.clone(),
+        alice_state.clone(),
+        telegram::TelegramBot::new(telegram_bot_token.clone()),
+        admin_telegram_id,
+    )
+    .await
+    .expect("Failed to initialize Alice rules");
 
-    println!("🚀 Server starting...");
-    println!("📊 Database: {}", database_url);
-    println!("👤 Admin Telegram ID: {}", admin_telegram_id);
-    println!("🎮 Steam ID: {}", steam_id);
-    println!("🎯 Faceit API: {}", if faceit_api_key.is_some() { "enabled" } else { "disabled" });
-    println!("💼 Job search system: ready");
-    println!("🎨 CS2 Skin Studio: ready");
-    println!("📹 Publishing Tools: ready");
-    println!("📁 File Manager: ready");
-    println!("☁️  Cloud Sync: ready");
-    println!("🔗 Link Shortener: ready");
-    println!("📱 T2 Sales System: ready");
-    println!("🏠 Alice Smart Home: ready");
-    println!("✅ All systems ready");
+    // Spawn rule maintenance: PC presence triggers and history retention
+    let rules_task = rules.clone();
+    let rules_pool = pool.clone();
+    tokio::spawn(async move {
+        let history_days: i64 = env::var("ALICE_RULE_HISTORY_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+        loop {
+            rules_task.sweep_presence().await;
+            if let Err(e) = alice::RuleEngine::purge_history(&rules_pool, history_days).await {
+                error!("Failed to purge Alice rule history: {}", e);
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await; // Every minute
+        }
+    });
+
+    // Initialize the two-way Telegram bot
+    let telegram_service = telegram::TelegramBotService::new(
+        telegram::TelegramBot::new(telegram_bot_token.clone()),
+        admin_telegram_id,
+        pool.clone(),
+        job_scheduler.get().cloned(),
+        metrics_service.clone(),
+    );
+    let telegram_updates = telegram_service.clone().start();
+
+    info!("Server starting...");
+    info!("Database: {}", database_url);
+    info!("Admin Telegram ID: {}", admin_telegram_id);
+    info!("Steam ID: {}", steam_id);
+    info!("Faceit API: {}", if faceit_api_key.is_some() { "enabled" } else { "disabled" });
+    info!("Job search system: ready");
+    info!("CS2 Skin Studio: ready");
+    info!("Publishing Tools: ready");
+    info!("File Manager: ready");
+    info!("Cloud Sync: ready");
+    info!("Link Shortener: ready");
+    info!("T2 Sales System: ready");
+    info!("Alice Smart Home: ready");
+    info!(
+        "MQTT: {}",
+        match (mqtt_client.is_enabled(), mqtt_client.discovery_enabled()) {
+            (true, true) => "enabled (discovery)",
+            (true, false) => "enabled",
+            _ => "disabled",
+        }
+    );
+    info!("Yandex callbacks: {}", if yandex_callbacks_enabled { "enabled" } else { "disabled" });
+    info!("Telegram bot updates: {}", telegram_updates);
+    info!("All systems ready");
 
     // Configure CORS
     let cors = CorsOptions {
@@ -116,7 +267,10 @@ async fn rocket() -> _ {
     .expect("Failed to create CORS fairing");
 
     rocket::build()
+        .attach(telemetry::RequestIdFairing)
         .attach(cors)
+        .attach(audit::AuditFairing)
+        .attach(metrics::RequestMetricsFairing)
         .manage(pool)
         .manage(telegram_bot)
         .manage(steam_client)
@@ -126,56 +280,88 @@ async fn rocket() -> _ {
         .manage(admin_telegram_id)
         .manage(publish_service)
         .manage(alice_state)
+        .manage(pc_schedules)
+        .manage(mqtt_client)
+        .manage(rules)
+        .manage(telegram_service)
+        .manage(console_service)
+        .manage(log_service)
+        .manage(metrics_service)
+        .manage(backup_service)
+        .manage(log_guard)
         // Public routes
         .mount(
             "/",
-            routes![
+            telemetry::traced(routes![
                 routes::public_routes::index,
                 routes::public_routes::health,
+                routes::metrics::prometheus_metrics,
                 routes::public_routes::server_time,
                 routes::public_routes::steam_profile,
                 routes::public_routes::workshop_all,
                 routes::public_routes::workshop_by_game,
                 routes::public_routes::workshop_items,
-            ],
+            ]),
         )
         // Auth routes
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::auth::request_otp,
                 routes::auth::verify_otp,
-            ],
+                routes::auth::list_sessions,
+                routes::auth::revoke_session,
+                routes::auth::revoke_all_sessions,
+                routes::auth::create_api_token,
+                routes::auth::list_api_tokens,
+                routes::auth::revoke_api_token,
+            ]),
+        )
+        // Audit log (admin)
+        .mount("/api", telemetry::traced(routes![routes::audit::list_audit_log]))
+        // Notification center (admin)
+        .mount(
+            "/api",
+            telemetry::traced(routes![
+                routes::notifications::list_notifications,
+                routes::notifications::unread_notifications,
+                routes::notifications::create_notification,
+                routes::notifications::read_notification,
+                routes::notifications::read_all_notifications,
+                routes::notifications::delete_notification,
+                routes::notifications::notification_stream_ticket,
+                routes::notifications::stream_notifications,
+            ]),
         )
         // Public portfolio route
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::portfolio::get_portfolio,
-            ],
+            ]),
         )
         // CS2 routes
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::cs2::receive_gsi,
                 routes::cs2::get_current_match,
                 routes::cs2::clear_match,
-            ],
+            ]),
         )
         // Admin routes (protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::admin::admin_info,
                 routes::admin::admin_dashboard,
                 routes::admin::admin_stats,
-            ],
+            ]),
         )
         // Admin portfolio routes (protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 // About
                 routes::portfolio::create_about,
                 routes::portfolio::update_about,
@@ -198,12 +384,12 @@ async fn rocket() -> _ {
                 // Cases
                 routes::portfolio::create_case,
                 routes::portfolio::delete_case,
-            ],
+            ]),
         )
         // Job search routes (protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::jobs::start_job_search,
                 routes::jobs::stop_job_search,
                 routes::jobs::get_search_status,
@@ -213,6 +399,8 @@ async fn rocket() -> _ {
                 routes::jobs::get_vacancies_by_status,
                 routes::jobs::get_job_stats,
                 routes::jobs::ignore_vacancy,
+                routes::jobs::approve_vacancy,
+                routes::jobs::skip_vacancy,
                 routes::jobs::start_hh_auth,
                 // Chats
                 routes::jobs::get_chats,
@@ -225,29 +413,29 @@ async fn rocket() -> _ {
                 // Activity
                 routes::jobs::get_activity_log,
                 routes::jobs::get_daily_stats,
-            ],
+            ]),
         )
         // HH OAuth callback (public)
         .mount(
             "/",
-            routes![
+            telemetry::traced(routes![
                 routes::jobs::hh_oauth_callback,
-            ],
+            ]),
         )
         // Anime auction routes (protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::anime::get_upcoming_anime,
                 routes::anime::get_watched_anime,
                 routes::anime::sync_anime_data,
                 routes::anime::get_sync_progress,
-            ],
+            ]),
         )
         // CS2 Skin Studio routes
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::studio::steam_auth,
                 routes::studio::steam_auth_callback,
                 routes::studio::get_me,
@@ -256,23 +444,23 @@ async fn rocket() -> _ {
                 routes::studio::get_project,
                 routes::studio::update_project,
                 routes::studio::delete_project,
-            ],
+            ]),
         )
         // Publishing tools routes
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::publish::convert_video,
                 routes::publish::optimize_gif,
                 routes::publish::get_status,
                 routes::publish::get_result,
                 routes::publish::download_result,
-            ],
+            ]),
         )
         // File manager routes (admin protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::files::get_folder_contents,
                 routes::files::create_folder,
                 routes::files::rename_folder,
@@ -282,21 +470,21 @@ async fn rocket() -> _ {
                 routes::files::delete_file,
                 routes::files::get_file_info,
                 routes::files::get_admin_file,
-            ],
+            ]),
         )
         // File manager public routes
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::files::get_public_file,
                 routes::files::get_private_file,
                 routes::files::check_file,
-            ],
+            ]),
         )
         // Sync routes (admin)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::sync::list_folders,
                 routes::sync::create_folder,
                 routes::sync::get_folder,
@@ -304,24 +492,24 @@ async fn rocket() -> _ {
                 routes::sync::regenerate_key,
                 routes::sync::delete_folder,
                 routes::sync::delete_client,
-            ],
+            ]),
         )
         // Sync routes (client API)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::sync::register_client,
                 routes::sync::get_sync_status,
                 routes::sync::list_files,
                 routes::sync::upload_file,
                 routes::sync::download_file,
                 routes::sync::delete_file,
-            ],
+            ]),
         )
         // Link shortener routes (admin protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::links::list_links,
                 routes::links::create_link,
                 routes::links::update_link,
@@ -331,64 +519,104 @@ async fn rocket() -> _ {
                 routes::links::get_links_summary,
                 routes::links::resolve_link,
                 routes::links::track_click,
-            ],
+            ]),
         )
         // Link shortener redirect (public)
         .mount(
             "/",
-            routes![
+            telemetry::traced(routes![
                 routes::links::redirect_link,
-            ],
+            ]),
         )
         // Database viewer routes (admin protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::database::get_tables,
                 routes::database::get_table_schema,
                 routes::database::get_table_data,
                 routes::database::execute_query,
                 routes::database::get_database_stats,
-            ],
+                routes::database::explain_query,
+                routes::database::get_query_history,
+                routes::database::clear_query_history,
+                routes::database::list_saved_queries,
+                routes::database::create_saved_query,
+                routes::database::update_saved_query,
+                routes::database::delete_saved_query,
+                routes::database::preview_row_change,
+                routes::database::apply_row_change,
+            ]),
+        )
+        // Database backup routes (admin protected)
+        .mount(
+            "/api",
+            telemetry::traced(routes![
+                routes::backup::list_backups,
+                routes::backup::create_backup,
+                routes::backup::download_backup,
+                routes::backup::restore_backup,
+                routes::backup::delete_backup,
+            ]),
         )
         // Server console routes (admin protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::console::execute_command,
+                routes::console::list_commands,
+                routes::console::raw_mode_challenge,
+                routes::console::raw_mode_confirm,
+                routes::console::raw_mode_status,
+                routes::console::list_runs,
+                routes::console::start_job,
+                routes::console::list_jobs,
+                routes::console::stream_job,
+                routes::console::job_socket,
+                routes::console::job_input,
+                routes::console::job_resize,
+                routes::console::kill_job,
                 routes::console::get_system_info,
                 routes::console::get_processes,
                 routes::console::get_logs,
+                routes::console::get_log_entries,
+                routes::console::stream_logs,
+                routes::console::get_metrics_history,
+                routes::console::get_metrics_current,
+                routes::console::list_alerts,
+                routes::console::create_alert,
+                routes::console::update_alert,
+                routes::console::delete_alert,
                 routes::console::get_services,
-            ],
+            ]),
         )
         // Menu settings (public - for sidebar visibility)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::menu::get_menu_settings,
-            ],
+            ]),
         )
         // Menu settings (admin protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::menu::get_menu_items,
                 routes::menu::update_menu_settings,
-            ],
+            ]),
         )
         // T2 Sales System routes (public)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 t2::routes::t2_health,
                 t2::routes::t2_login,
-            ],
+            ]),
         )
         // T2 Sales System routes (protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 t2::routes::t2_logout,
                 t2::routes::t2_me,
                 t2::routes::t2_admin_info,
@@ -430,12 +658,12 @@ async fn rocket() -> _ {
                 t2::routes::t2_create_sale,
                 t2::routes::t2_search,
                 t2::routes::t2_get_stats,
-            ],
+            ]),
         )
         // English Learning System routes (protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::english::get_categories,
                 routes::english::create_category,
                 routes::english::get_words,
@@ -453,12 +681,12 @@ async fn rocket() -> _ {
                 routes::english::update_settings,
                 routes::english::get_achievements,
                 routes::english::import_words,
-            ],
+            ]),
         )
         // Alice Smart Home API routes (Yandex Alice integration)
         .mount(
             "/",
-            routes![
+            telemetry::traced(routes![
                 routes::alice::alice_health,
                 routes::alice::alice_unlink,
                 routes::alice::alice_get_devices,
@@ -467,14 +695,20 @@ async fn rocket() -> _ {
                 routes::alice::alice_auth_page,
                 routes::alice::alice_auth_login,
                 routes::alice::alice_token,
-            ],
+            ]),
         )
         // Alice Admin API routes (protected)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::alice::alice_admin_get_devices,
                 routes::alice::alice_admin_update_config,
+                routes::alice::alice_admin_list_registry,
+                routes::alice::alice_admin_validate_device,
+                routes::alice::alice_admin_create_device,
+                routes::alice::alice_admin_update_device,
+                routes::alice::alice_admin_delete_device,
+                routes::alice::alice_admin_report_state,
                 routes::alice::alice_admin_get_commands,
                 routes::alice::alice_get_notifications,
                 routes::alice::alice_test_notification,
@@ -484,17 +718,39 @@ async fn rocket() -> _ {
                 routes::alice::alice_pc_register,
                 routes::alice::alice_pc_list_clients,
                 routes::alice::alice_pc_delete_client,
+                routes::alice::alice_pc_set_client_group,
                 routes::alice::alice_pc_queue_command,
                 routes::alice::alice_pc_get_queue,
-            ],
+                routes::alice::alice_pc_cancel_command,
+                routes::alice::alice_pc_get_artifact,
+                routes::alice::alice_pc_send_artifact,
+                routes::alice::alice_pc_list_schedules,
+                routes::alice::alice_pc_create_schedule,
+                routes::alice::alice_pc_pause_schedule,
+                routes::alice::alice_pc_resume_schedule,
+                routes::alice::alice_pc_run_schedule,
+                routes::alice::alice_pc_delete_schedule,
+                routes::alice::alice_admin_list_rules,
+                routes::alice::alice_admin_create_rule,
+                routes::alice::alice_admin_update_rule,
+                routes::alice::alice_admin_delete_rule,
+                routes::alice::alice_admin_dry_run_rule,
+                routes::alice::alice_admin_rule_history,
+            ]),
         )
         // PC Client API routes (authenticated by API key)
         .mount(
             "/api",
-            routes![
+            telemetry::traced(routes![
                 routes::alice::alice_pc_poll_commands,
                 routes::alice::alice_pc_report_result,
+                routes::alice::alice_pc_upload_artifact,
                 routes::alice::alice_pc_heartbeat,
-            ],
+                routes::alice::alice_pc_socket,
+            ]),
         )
+        // Automation rule webhooks (authenticated by the key in the path)
+        .mount("/api", telemetry::traced(routes![routes::alice::alice_rules_webhook]))
+        // Telegram bot webhook (authenticated by the secret token header)
+        .mount("/api", telemetry::traced(routes![routes::telegram::telegram_webhook]))
 }
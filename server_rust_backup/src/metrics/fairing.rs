@@ -0,0 +1,45 @@
+use crate::metrics::prometheus::registry;
+use rocket::data::Data;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Request, Response};
+use std::time::Instant;
+
+/// When the request reached the server
+struct RequestStart(Option<Instant>);
+
+/// Counts requests and their latency per matched route.
+///
+/// Routes are labelled by their template (`/api/console/jobs/<id>`) so paths with IDs
+/// do not create new series; requests that matched no route share the `unmatched` label.
+pub struct RequestMetricsFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(started) = req.local_cache(|| RequestStart(None)).0 else {
+            return;
+        };
+        let route = req
+            .route()
+            .map(|r| r.uri.as_str().split('?').next().unwrap_or_default().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+
+        registry().observe_request(
+            req.method().as_str(),
+            &route,
+            res.status().code,
+            started.elapsed().as_secs_f64(),
+        );
+    }
+}
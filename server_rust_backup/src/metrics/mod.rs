@@ -0,0 +1,9 @@
+pub mod fairing;
+pub mod models;
+pub mod prometheus;
+pub mod sampler;
+pub mod service;
+
+pub use fairing::*;
+pub use models::*;
+pub use service::*;
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+/// Metric names produced by the sampler; extra disks add `disk_percent:<mount>` and `disk_used_gb:<mount>`
+pub const METRIC_NAMES: &[&str] = &[
+    "cpu_percent",
+    "load1",
+    "load5",
+    "load15",
+    "memory_percent",
+    "memory_used_mb",
+    "swap_percent",
+    "disk_percent",
+    "disk_used_gb",
+    "net_rx_kbps",
+    "net_tx_kbps",
+];
+
+/// Alert comparison operators
+pub const ALERT_OPERATORS: &[&str] = &[">", ">=", "<", "<="];
+
+/// Aggregated value of one metric over a bucket
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct MetricPoint {
+    /// Bucket start, unix seconds
+    pub t: i64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricSeries {
+    pub metric: String,
+    pub points: Vec<MetricPoint>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsHistory {
+    pub from: i64,
+    pub to: i64,
+    /// Bucket size of the returned points
+    pub resolution_secs: i64,
+    pub series: Vec<MetricSeries>,
+}
+
+/// Latest sample of every metric
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub sampled_at: i64,
+    pub values: std::collections::BTreeMap<String, f64>,
+}
+
+/// Stored resolution level: buckets of `resolution_secs` kept for `retention_secs`
+#[derive(Debug, Clone)]
+pub struct MetricsLevel {
+    pub resolution_secs: i64,
+    pub retention_secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AlertRule {
+    pub id: i32,
+    pub name: String,
+    pub metric: String,
+    pub operator: String,
+    pub threshold: f64,
+    /// The condition must hold this long before the alert fires
+    pub duration_secs: i64,
+    pub enabled: bool,
+    pub last_triggered_at: Option<String>,
+    pub created_at: String,
+}
+
+impl AlertRule {
+    pub fn is_breached(&self, value: f64) -> bool {
+        match self.operator.as_str() {
+            ">" => value > self.threshold,
+            ">=" => value >= self.threshold,
+            "<" => value < self.threshold,
+            "<=" => value <= self.threshold,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlertRuleRequest {
+    pub name: String,
+    pub metric: String,
+    pub operator: String,
+    pub threshold: f64,
+    pub duration_secs: Option<i64>,
+    pub enabled: Option<bool>,
+}
+
+/// Rule with its current evaluation state
+#[derive(Debug, Serialize)]
+pub struct AlertStatus {
+    #[serde(flatten)]
+    pub rule: AlertRule,
+    /// "ok", "pending" (breached, waiting for duration) or "firing"
+    pub state: String,
+    /// Unix seconds since the condition has held
+    pub breached_since: Option<i64>,
+    pub value: Option<f64>,
+}
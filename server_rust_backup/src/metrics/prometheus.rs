@@ -0,0 +1,139 @@
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+/// Upper bounds of the request latency histogram, seconds
+pub const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Counters that can be incremented from anywhere with `inc`
+pub const COUNTERS: &[(&str, &str)] = &[
+    ("bgalin_job_scheduler_cycles_total", "Job scheduler loop iterations"),
+    ("bgalin_sync_bytes_total", "Bytes transferred by cloud sync, by direction"),
+    ("bgalin_link_clicks_total", "Short link redirects served"),
+];
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Cumulative counts per `LATENCY_BUCKETS` entry
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// Process-wide metrics; gauges are collected by the `/metrics` route at scrape time
+#[derive(Default)]
+pub struct Registry {
+    counters: RwLock<BTreeMap<(&'static str, Labels), f64>>,
+    /// (method, route, status) -> requests
+    requests: RwLock<BTreeMap<(String, String, u16), u64>>,
+    /// (method, route) -> latency
+    latency: RwLock<BTreeMap<(String, String), Histogram>>,
+}
+
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::default)
+}
+
+/// Increase a counter from `COUNTERS`
+pub fn inc(name: &'static str, labels: &[(&str, &str)], by: f64) {
+    let labels = labels.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    *registry().counters.write().entry((name, labels)).or_insert(0.0) += by;
+}
+
+impl Registry {
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        *self
+            .requests
+            .write()
+            .entry((method.to_string(), route.to_string(), status))
+            .or_insert(0) += 1;
+
+        let mut latency = self.latency.write();
+        let histogram = latency
+            .entry((method.to_string(), route.to_string()))
+            .or_insert_with(|| Histogram {
+                buckets: vec![0; LATENCY_BUCKETS.len()],
+                ..Default::default()
+            });
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    /// Request metrics and counters in the text exposition format
+    pub fn render(&self, out: &mut String) {
+        out.push_str("# HELP http_requests_total HTTP requests by route and status\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((method, route, status), count) in self.requests.read().iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_total{} {}",
+                format_labels(&[("method", method), ("route", route), ("status", &status.to_string())]),
+                count
+            );
+        }
+
+        out.push_str("# HELP http_request_duration_seconds HTTP request latency by route\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for ((method, route), histogram) in self.latency.read().iter() {
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{} {}",
+                    format_labels(&[("method", method), ("route", route), ("le", &bound.to_string())]),
+                    count
+                );
+            }
+            let labels = format_labels(&[("method", method), ("route", route)]);
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{} {}",
+                format_labels(&[("method", method), ("route", route), ("le", "+Inf")]),
+                histogram.count
+            );
+            let _ = writeln!(out, "http_request_duration_seconds_sum{} {}", labels, histogram.sum);
+            let _ = writeln!(out, "http_request_duration_seconds_count{} {}", labels, histogram.count);
+        }
+
+        let counters = self.counters.read();
+        for (name, help) in COUNTERS {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for ((_, labels), value) in counters.range((*name, Vec::new())..).take_while(|((n, _), _)| n == name) {
+                let labels: Vec<(&str, &str)> = labels.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+                let _ = writeln!(out, "{}{} {}", name, format_labels(&labels), value);
+            }
+        }
+    }
+}
+
+/// Append a gauge family with one sample per label set
+pub fn write_gauge(out: &mut String, name: &str, help: &str, samples: &[(Vec<(&str, &str)>, f64)]) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{} {}", name, format_labels(labels), value);
+    }
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| {
+            let value = v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", k, value)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
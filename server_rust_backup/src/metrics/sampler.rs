@@ -0,0 +1,145 @@
+use std::ffi::CString;
+use std::time::Instant;
+
+/// Reads host metrics from `/proc` and `statvfs`; rates need the previous sample
+pub struct Sampler {
+    disks: Vec<String>,
+    prev_cpu: Option<(u64, u64)>,
+    prev_net: Option<(Instant, u64, u64)>,
+}
+
+impl Sampler {
+    pub fn new(disks: Vec<String>) -> Self {
+        Self {
+            disks,
+            prev_cpu: None,
+            prev_net: None,
+        }
+    }
+
+    /// Current values; CPU and network rates are missing on the first call
+    pub async fn sample(&mut self) -> Vec<(String, f64)> {
+        let mut values = Vec::new();
+
+        if let Ok(stat) = tokio::fs::read_to_string("/proc/stat").await {
+            if let Some((idle, total)) = parse_cpu_times(&stat) {
+                if let Some((prev_idle, prev_total)) = self.prev_cpu {
+                    let total_delta = total.saturating_sub(prev_total);
+                    if total_delta > 0 {
+                        let busy = total_delta.saturating_sub(idle.saturating_sub(prev_idle));
+                        values.push(("cpu_percent".to_string(), busy as f64 / total_delta as f64 * 100.0));
+                    }
+                }
+                self.prev_cpu = Some((idle, total));
+            }
+        }
+
+        if let Ok(loadavg) = tokio::fs::read_to_string("/proc/loadavg").await {
+            for (name, value) in ["load1", "load5", "load15"].iter().zip(loadavg.split_whitespace()) {
+                if let Ok(value) = value.parse::<f64>() {
+                    values.push((name.to_string(), value));
+                }
+            }
+        }
+
+        if let Ok(meminfo) = tokio::fs::read_to_string("/proc/meminfo").await {
+            let kb = |key: &str| meminfo_kb(&meminfo, key);
+            let total = kb("MemTotal");
+            if total > 0 {
+                // MemAvailable accounts for reclaimable cache
+                let used = total.saturating_sub(kb("MemAvailable"));
+                values.push(("memory_percent".to_string(), used as f64 / total as f64 * 100.0));
+                values.push(("memory_used_mb".to_string(), used as f64 / 1024.0));
+            }
+            let swap_total = kb("SwapTotal");
+            if swap_total > 0 {
+                let swap_used = swap_total.saturating_sub(kb("SwapFree"));
+                values.push(("swap_percent".to_string(), swap_used as f64 / swap_total as f64 * 100.0));
+            }
+        }
+
+        for (i, mount) in self.disks.iter().enumerate() {
+            if let Some((total, available)) = disk_usage(mount) {
+                if total == 0 {
+                    continue;
+                }
+                let used = total.saturating_sub(available);
+                // The first mount is the plain `disk_*` metric
+                let suffix = if i == 0 { String::new() } else { format!(":{}", mount) };
+                values.push((format!("disk_percent{}", suffix), used as f64 / total as f64 * 100.0));
+                values.push((format!("disk_used_gb{}", suffix), used as f64 / 1_073_741_824.0));
+            }
+        }
+
+        if let Ok(netdev) = tokio::fs::read_to_string("/proc/net/dev").await {
+            let (rx, tx) = parse_net_bytes(&netdev);
+            let now = Instant::now();
+            if let Some((prev_at, prev_rx, prev_tx)) = self.prev_net {
+                let secs = now.duration_since(prev_at).as_secs_f64();
+                if secs > 0.0 {
+                    values.push(("net_rx_kbps".to_string(), rx.saturating_sub(prev_rx) as f64 / 1024.0 / secs));
+                    values.push(("net_tx_kbps".to_string(), tx.saturating_sub(prev_tx) as f64 / 1024.0 / secs));
+                }
+            }
+            self.prev_net = Some((now, rx, tx));
+        }
+
+        values
+    }
+}
+
+/// (idle + iowait, total) jiffies of the aggregate `cpu` line
+fn parse_cpu_times(stat: &str) -> Option<(u64, u64)> {
+    let line = stat.lines().find(|l| l.starts_with("cpu "))?;
+    let fields: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    let idle = fields[3] + fields.get(4).copied().unwrap_or(0);
+    // guest time is already included in user/nice
+    let total = fields.iter().take(8).sum();
+    Some((idle, total))
+}
+
+fn meminfo_kb(meminfo: &str, key: &str) -> u64 {
+    meminfo
+        .lines()
+        .find(|l| l.split(':').next() == Some(key))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Received and transmitted bytes over all interfaces except loopback
+fn parse_net_bytes(netdev: &str) -> (u64, u64) {
+    netdev
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (iface, data) = line.split_once(':')?;
+            if iface.trim() == "lo" {
+                return None;
+            }
+            let fields: Vec<u64> = data.split_whitespace().filter_map(|v| v.parse().ok()).collect();
+            Some((*fields.first()?, *fields.get(8)?))
+        })
+        .fold((0, 0), |(rx, tx), (r, t)| (rx + r, tx + t))
+}
+
+/// (total, available to unprivileged users) bytes of the filesystem holding `mount`
+fn disk_usage(mount: &str) -> Option<(u64, u64)> {
+    let path = CString::new(mount).ok()?;
+    // SAFETY: statvfs only writes into the zeroed struct
+    unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        let block = stat.f_frsize as u64;
+        Some((stat.f_blocks as u64 * block, stat.f_bavail as u64 * block))
+    }
+}
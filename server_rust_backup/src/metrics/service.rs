@@ -0,0 +1,670 @@
+use crate::db::DbPool;
+use crate::metrics::models::*;
+use crate::metrics::sampler::Sampler;
+use crate::notifications::{category, NewNotification, NotificationPriority, NotificationService};
+use crate::telegram::TelegramBot;
+use chrono::Utc;
+use parking_lot::RwLock;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Charts get at most this many points per series
+const MAX_POINTS: i64 = 1000;
+/// Rollups and retention run this often
+const MAINTENANCE_INTERVAL_SECS: i64 = 60;
+const SELECT_RULE: &str = "SELECT id, name, metric, operator, threshold, duration_secs, enabled, last_triggered_at, created_at FROM metric_alert_rules";
+const SELECT_RULE_PG: &str = "SELECT id, name, metric, operator, threshold, duration_secs, enabled, last_triggered_at::TEXT AS last_triggered_at, created_at::TEXT AS created_at FROM metric_alert_rules";
+
+/// Evaluation state of one alert rule
+#[derive(Debug, Clone, Default)]
+struct AlertState {
+    breached_since: Option<i64>,
+    firing: bool,
+    value: Option<f64>,
+}
+
+#[derive(Clone)]
+pub struct MetricsService {
+    pool: DbPool,
+    /// Raw samples first, then coarser rollups
+    levels: Vec<MetricsLevel>,
+    disks: Vec<String>,
+    latest: Arc<RwLock<Option<MetricsSnapshot>>>,
+    alerts: Arc<RwLock<HashMap<i32, AlertState>>>,
+}
+
+impl MetricsService {
+    /// `METRICS_INTERVAL_SECS` (15), `METRICS_RAW_RETENTION_HOURS` (24),
+    /// `METRICS_ROLLUPS` as `resolution_secs:retention_days,...` ("300:30,3600:365"), `METRICS_DISKS` ("/")
+    pub async fn init(pool: DbPool) -> Self {
+        let interval = env::var("METRICS_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(15)
+            .clamp(1, 3600);
+        let raw_hours = env::var("METRICS_RAW_RETENTION_HOURS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(24)
+            .max(1);
+
+        let mut levels = vec![MetricsLevel {
+            resolution_secs: interval,
+            retention_secs: raw_hours * 3600,
+        }];
+        let rollups = env::var("METRICS_ROLLUPS").unwrap_or_else(|_| "300:30,3600:365".to_string());
+        for entry in rollups.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let parsed = entry
+                .split_once(':')
+                .and_then(|(res, days)| Some((res.trim().parse::<i64>().ok()?, days.trim().parse::<i64>().ok()?)));
+            let previous = levels.last().map(|l| l.resolution_secs).unwrap_or(interval);
+            match parsed {
+                // Each level aggregates whole buckets of the previous one
+                Some((res, days)) if res > previous && res % previous == 0 && days > 0 => levels.push(MetricsLevel {
+                    resolution_secs: res,
+                    retention_secs: days * 86400,
+                }),
+                _ => warn!("Ignoring invalid METRICS_ROLLUPS entry: {}", entry),
+            }
+        }
+
+        let disks = env::var("METRICS_DISKS")
+            .unwrap_or_else(|_| "/".to_string())
+            .split(',')
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty())
+            .collect();
+
+        let service = Self {
+            pool,
+            levels,
+            disks,
+            latest: Arc::new(RwLock::new(None)),
+            alerts: Arc::new(RwLock::new(HashMap::new())),
+        };
+        if let Err(e) = service.seed_default_rules().await {
+            error!("Failed to seed alert rules: {}", e);
+        }
+        service
+    }
+
+    pub fn latest(&self) -> Option<MetricsSnapshot> {
+        self.latest.read().clone()
+    }
+
+    /// Sample, store and evaluate alerts forever; alerts go to `chat_id`
+    pub fn start_background_task(self, bot: TelegramBot, chat_id: i64) {
+        tokio::spawn(async move {
+            let interval = self.levels[0].resolution_secs;
+            let mut sampler = Sampler::new(self.disks.clone());
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval as u64));
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut last_maintenance = 0;
+
+            loop {
+                ticker.tick().await;
+                let values = sampler.sample().await;
+                let now = Utc::now().timestamp();
+                if values.is_empty() {
+                    continue;
+                }
+
+                if let Err(e) = self.store(now, &values).await {
+                    error!("Failed to store metrics: {}", e);
+                }
+                *self.latest.write() = Some(MetricsSnapshot {
+                    sampled_at: now,
+                    values: values.iter().cloned().collect(),
+                });
+
+                self.evaluate_alerts(now, &bot, chat_id).await;
+
+                if now - last_maintenance >= MAINTENANCE_INTERVAL_SECS {
+                    last_maintenance = now;
+                    if let Err(e) = self.rollup_and_prune(now).await {
+                        error!("Failed to downsample metrics: {}", e);
+                    }
+                }
+            }
+        });
+        info!("Metrics sampler started");
+    }
+
+    async fn store(&self, now: i64, values: &[(String, f64)]) -> Result<(), sqlx::Error> {
+        let resolution = self.levels[0].resolution_secs;
+        let bucket = now / resolution * resolution;
+
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                let mut tx = p.begin().await?;
+                for (metric, value) in values {
+                    sqlx::query(
+                        "INSERT INTO metric_samples (metric, resolution, bucket_start, avg_value, min_value, max_value, samples)
+                         VALUES (?, ?, ?, ?, ?, ?, 1)
+                         ON CONFLICT (metric, resolution, bucket_start) DO NOTHING",
+                    )
+                    .bind(metric)
+                    .bind(resolution)
+                    .bind(bucket)
+                    .bind(value)
+                    .bind(value)
+                    .bind(value)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                tx.commit().await?;
+            }
+            DbPool::Postgres(p) => {
+                let mut tx = p.begin().await?;
+                for (metric, value) in values {
+                    sqlx::query(
+                        "INSERT INTO metric_samples (metric, resolution, bucket_start, avg_value, min_value, max_value, samples)
+                         VALUES ($1, $2, $3, $4, $4, $4, 1)
+                         ON CONFLICT (metric, resolution, bucket_start) DO NOTHING",
+                    )
+                    .bind(metric)
+                    .bind(resolution as i32)
+                    .bind(bucket)
+                    .bind(value)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                tx.commit().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Aggregate recently completed buckets into each coarser level and drop expired rows
+    async fn rollup_and_prune(&self, now: i64) -> Result<(), sqlx::Error> {
+        for pair in self.levels.windows(2) {
+            let (source, target) = (&pair[0], &pair[1]);
+            let res = target.resolution_secs;
+            // Only whole buckets; a few earlier ones are revisited to cover a restart
+            let until = now / res * res;
+            let since = until - res * 3;
+
+            match &self.pool {
+                DbPool::Sqlite(p) => {
+                    sqlx::query(
+                        "INSERT INTO metric_samples (metric, resolution, bucket_start, avg_value, min_value, max_value, samples)
+                         SELECT metric, ?, (bucket_start / ?) * ?, SUM(avg_value * samples) / SUM(samples), MIN(min_value), MAX(max_value), SUM(samples)
+                         FROM metric_samples
+                         WHERE resolution = ? AND bucket_start >= ? AND bucket_start < ?
+                         GROUP BY metric, (bucket_start / ?) * ?
+                         ON CONFLICT (metric, resolution, bucket_start) DO NOTHING",
+                    )
+                    .bind(res)
+                    .bind(res)
+                    .bind(res)
+                    .bind(source.resolution_secs)
+                    .bind(since)
+                    .bind(until)
+                    .bind(res)
+                    .bind(res)
+                    .execute(p)
+                    .await?;
+                }
+                DbPool::Postgres(p) => {
+                    sqlx::query(
+                        "INSERT INTO metric_samples (metric, resolution, bucket_start, avg_value, min_value, max_value, samples)
+                         SELECT metric, $1::INTEGER, (bucket_start / $1) * $1, SUM(avg_value * samples) / SUM(samples), MIN(min_value), MAX(max_value), SUM(samples)
+                         FROM metric_samples
+                         WHERE resolution = $2 AND bucket_start >= $3 AND bucket_start < $4
+                         GROUP BY metric, (bucket_start / $1) * $1
+                         ON CONFLICT (metric, resolution, bucket_start) DO NOTHING",
+                    )
+                    .bind(res)
+                    .bind(source.resolution_secs as i32)
+                    .bind(since)
+                    .bind(until)
+                    .execute(p)
+                    .await?;
+                }
+            }
+        }
+
+        for level in &self.levels {
+            let cutoff = now - level.retention_secs;
+            match &self.pool {
+                DbPool::Sqlite(p) => {
+                    sqlx::query("DELETE FROM metric_samples WHERE resolution = ? AND bucket_start < ?")
+                        .bind(level.resolution_secs)
+                        .bind(cutoff)
+                        .execute(p)
+                        .await?;
+                }
+                DbPool::Postgres(p) => {
+                    sqlx::query("DELETE FROM metric_samples WHERE resolution = $1 AND bucket_start < $2")
+                        .bind(level.resolution_secs as i32)
+                        .bind(cutoff)
+                        .execute(p)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Series for charts; without `resolution` the finest level that covers `from`
+    /// and keeps the series under `MAX_POINTS` is used
+    pub async fn history(
+        &self,
+        metrics: &[String],
+        from: i64,
+        to: i64,
+        resolution: Option<i64>,
+    ) -> Result<MetricsHistory, String> {
+        if from >= to {
+            return Err("`from` must be before `to`".to_string());
+        }
+        let now = Utc::now().timestamp();
+        let span = to - from;
+
+        let level = match resolution {
+            Some(res) if res <= 0 => return Err("Resolution must be positive".to_string()),
+            // The coarsest stored level that still fits into the requested step
+            Some(res) => self
+                .levels
+                .iter()
+                .rev()
+                .find(|l| l.resolution_secs <= res && now - l.retention_secs <= from)
+                .or_else(|| self.levels.iter().find(|l| now - l.retention_secs <= from))
+                .unwrap_or(&self.levels[self.levels.len() - 1]),
+            None => self
+                .levels
+                .iter()
+                .find(|l| now - l.retention_secs <= from && span / l.resolution_secs <= MAX_POINTS)
+                .or_else(|| self.levels.iter().find(|l| now - l.retention_secs <= from))
+                .unwrap_or(&self.levels[self.levels.len() - 1]),
+        };
+
+        // Stored buckets are merged further when the series would still be too long
+        let stored = level.resolution_secs;
+        let wanted = resolution.unwrap_or(stored).max(stored).max((span + MAX_POINTS - 1) / MAX_POINTS);
+        let step = (wanted + stored - 1) / stored * stored;
+
+        let mut series = Vec::new();
+        for metric in metrics {
+            let points = self
+                .points(metric, stored, step, from, to)
+                .await
+                .map_err(|e| format!("Database error: {}", e))?;
+            series.push(MetricSeries {
+                metric: metric.clone(),
+                points,
+            });
+        }
+
+        Ok(MetricsHistory {
+            from,
+            to,
+            resolution_secs: step,
+            series,
+        })
+    }
+
+    async fn points(&self, metric: &str, stored: i64, step: i64, from: i64, to: i64) -> Result<Vec<MetricPoint>, sqlx::Error> {
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, MetricPoint>(
+                    "SELECT (bucket_start / ?) * ? AS t, SUM(avg_value * samples) / SUM(samples) AS avg, MIN(min_value) AS min, MAX(max_value) AS max
+                     FROM metric_samples
+                     WHERE metric = ? AND resolution = ? AND bucket_start >= ? AND bucket_start <= ?
+                     GROUP BY 1 ORDER BY 1",
+                )
+                .bind(step)
+                .bind(step)
+                .bind(metric)
+                .bind(stored)
+                .bind(from)
+                .bind(to)
+                .fetch_all(p)
+                .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, MetricPoint>(
+                    "SELECT (bucket_start / $1) * $1 AS t, SUM(avg_value * samples) / SUM(samples) AS avg, MIN(min_value) AS min, MAX(max_value) AS max
+                     FROM metric_samples
+                     WHERE metric = $2 AND resolution = $3 AND bucket_start >= $4 AND bucket_start <= $5
+                     GROUP BY 1 ORDER BY 1",
+                )
+                .bind(step)
+                .bind(metric)
+                .bind(stored as i32)
+                .bind(from)
+                .bind(to)
+                .fetch_all(p)
+                .await
+            }
+        }
+    }
+
+    /// Metric names that have been sampled, including extra disks
+    pub fn known_metrics(&self) -> Vec<String> {
+        let mut names: Vec<String> = METRIC_NAMES.iter().map(|m| m.to_string()).collect();
+        for disk in self.disks.iter().skip(1) {
+            names.push(format!("disk_percent:{}", disk));
+            names.push(format!("disk_used_gb:{}", disk));
+        }
+        names
+    }
+
+    async fn evaluate_alerts(&self, now: i64, bot: &TelegramBot, chat_id: i64) {
+        let rules = match self.list_rules().await {
+            Ok(rules) => rules,
+            Err(e) => {
+                error!("Failed to load alert rules: {}", e);
+                return;
+            }
+        };
+        let values: BTreeMap<String, f64> = self.latest().map(|s| s.values).unwrap_or_default();
+
+        for rule in rules.into_iter().filter(|r| r.enabled) {
+            let Some(value) = values.get(&rule.metric).copied() else {
+                continue;
+            };
+
+            // Decide under the lock, notify after releasing it
+            let transition = {
+                let mut alerts = self.alerts.write();
+                let state = alerts.entry(rule.id).or_default();
+                state.value = Some(value);
+
+                if rule.is_breached(value) {
+                    let since = *state.breached_since.get_or_insert(now);
+                    if !state.firing && now - since >= rule.duration_secs {
+                        state.firing = true;
+                        Some(true)
+                    } else {
+                        None
+                    }
+                } else {
+                    state.breached_since = None;
+                    if state.firing {
+                        state.firing = false;
+                        Some(false)
+                    } else {
+                        None
+                    }
+                }
+            };
+
+            let message = match transition {
+                Some(true) => {
+                    if let Err(e) = self.mark_triggered(rule.id).await {
+                        error!("Failed to update alert rule {}: {}", rule.id, e);
+                    }
+                    format!(
+                        "🚨 <b>{}</b>\n\n{} = {:.1} ({} {} дольше {})",
+                        rule.name,
+                        rule.metric,
+                        value,
+                        rule.operator,
+                        rule.threshold,
+                        format_duration(rule.duration_secs)
+                    )
+                }
+                Some(false) => format!("✅ <b>{}</b> — в норме\n\n{} = {:.1}", rule.name, rule.metric, value),
+                None => continue,
+            };
+
+            info!("Alert {}: {}", rule.name, if transition == Some(true) { "firing" } else { "resolved" });
+            let notification = if transition == Some(true) {
+                NewNotification::new(
+                    category::SYSTEM,
+                    NotificationPriority::Critical,
+                    format!("🚨 {}", rule.name),
+                    format!("{} = {:.1} ({} {})", rule.metric, value, rule.operator, rule.threshold),
+                )
+            } else {
+                NewNotification::new(
+                    category::SYSTEM,
+                    NotificationPriority::Normal,
+                    format!("✅ {} — в норме", rule.name),
+                    format!("{} = {:.1}", rule.metric, value),
+                )
+            };
+            NotificationService::post(&self.pool, notification.data(serde_json::json!({ "rule_id": rule.id }))).await;
+            if let Err(e) = bot.send_message(chat_id, &message).await {
+                error!("Failed to send alert notification: {}", e);
+            }
+        }
+    }
+
+    /// Rules with their current state
+    pub async fn alert_statuses(&self) -> Result<Vec<AlertStatus>, sqlx::Error> {
+        let rules = self.list_rules().await?;
+        let alerts = self.alerts.read();
+
+        Ok(rules
+            .into_iter()
+            .map(|rule| {
+                let state = alerts.get(&rule.id).cloned().unwrap_or_default();
+                let label = if !rule.enabled {
+                    "disabled"
+                } else if state.firing {
+                    "firing"
+                } else if state.breached_since.is_some() {
+                    "pending"
+                } else {
+                    "ok"
+                };
+                AlertStatus {
+                    rule,
+                    state: label.to_string(),
+                    breached_since: state.breached_since,
+                    value: state.value,
+                }
+            })
+            .collect())
+    }
+
+    pub async fn list_rules(&self) -> Result<Vec<AlertRule>, sqlx::Error> {
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, AlertRule>(&format!("{} ORDER BY id", SELECT_RULE))
+                    .fetch_all(p)
+                    .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, AlertRule>(&format!("{} ORDER BY id", SELECT_RULE_PG))
+                    .fetch_all(p)
+                    .await
+            }
+        }
+    }
+
+    pub async fn get_rule(&self, id: i32) -> Result<Option<AlertRule>, sqlx::Error> {
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, AlertRule>(&format!("{} WHERE id = ?", SELECT_RULE))
+                    .bind(id)
+                    .fetch_optional(p)
+                    .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, AlertRule>(&format!("{} WHERE id = $1", SELECT_RULE_PG))
+                    .bind(id)
+                    .fetch_optional(p)
+                    .await
+            }
+        }
+    }
+
+    pub fn validate_rule(&self, request: &AlertRuleRequest) -> Result<(), String> {
+        if request.name.trim().is_empty() {
+            return Err("Name is required".to_string());
+        }
+        if !self.known_metrics().contains(&request.metric) {
+            return Err(format!("Unknown metric: {}", request.metric));
+        }
+        if !ALERT_OPERATORS.contains(&request.operator.as_str()) {
+            return Err(format!("Operator must be one of: {}", ALERT_OPERATORS.join(" ")));
+        }
+        if !request.threshold.is_finite() {
+            return Err("Threshold must be a number".to_string());
+        }
+        if request.duration_secs.is_some_and(|d| !(0..=86400).contains(&d)) {
+            return Err("Duration must be 0-86400 seconds".to_string());
+        }
+        Ok(())
+    }
+
+    pub async fn create_rule(&self, request: &AlertRuleRequest) -> Result<i32, sqlx::Error> {
+        let duration = request.duration_secs.unwrap_or(300);
+        let enabled = request.enabled.unwrap_or(true);
+
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                let result = sqlx::query(
+                    "INSERT INTO metric_alert_rules (name, metric, operator, threshold, duration_secs, enabled) VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(request.name.trim())
+                .bind(&request.metric)
+                .bind(&request.operator)
+                .bind(request.threshold)
+                .bind(duration)
+                .bind(enabled)
+                .execute(p)
+                .await?;
+                Ok(result.last_insert_rowid() as i32)
+            }
+            DbPool::Postgres(p) => {
+                let (id,): (i32,) = sqlx::query_as(
+                    "INSERT INTO metric_alert_rules (name, metric, operator, threshold, duration_secs, enabled) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+                )
+                .bind(request.name.trim())
+                .bind(&request.metric)
+                .bind(&request.operator)
+                .bind(request.threshold)
+                .bind(duration)
+                .bind(enabled)
+                .fetch_one(p)
+                .await?;
+                Ok(id)
+            }
+        }
+    }
+
+    /// Returns false when the rule does not exist; the evaluation state starts over
+    pub async fn update_rule(&self, id: i32, request: &AlertRuleRequest) -> Result<bool, sqlx::Error> {
+        let duration = request.duration_secs.unwrap_or(300);
+        let enabled = request.enabled.unwrap_or(true);
+
+        let updated = match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE metric_alert_rules SET name = ?, metric = ?, operator = ?, threshold = ?, duration_secs = ?, enabled = ? WHERE id = ?",
+                )
+                .bind(request.name.trim())
+                .bind(&request.metric)
+                .bind(&request.operator)
+                .bind(request.threshold)
+                .bind(duration)
+                .bind(enabled)
+                .bind(id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query(
+                    "UPDATE metric_alert_rules SET name = $1, metric = $2, operator = $3, threshold = $4, duration_secs = $5, enabled = $6 WHERE id = $7",
+                )
+                .bind(request.name.trim())
+                .bind(&request.metric)
+                .bind(&request.operator)
+                .bind(request.threshold)
+                .bind(duration)
+                .bind(enabled)
+                .bind(id)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+        };
+
+        self.alerts.write().remove(&id);
+        Ok(updated > 0)
+    }
+
+    pub async fn delete_rule(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let deleted = match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("DELETE FROM metric_alert_rules WHERE id = ?")
+                    .bind(id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query("DELETE FROM metric_alert_rules WHERE id = $1")
+                    .bind(id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        self.alerts.write().remove(&id);
+        Ok(deleted > 0)
+    }
+
+    async fn mark_triggered(&self, id: i32) -> Result<(), sqlx::Error> {
+        match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("UPDATE metric_alert_rules SET last_triggered_at = CURRENT_TIMESTAMP WHERE id = ?")
+                    .bind(id)
+                    .execute(p)
+                    .await?;
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query("UPDATE metric_alert_rules SET last_triggered_at = NOW() WHERE id = $1")
+                    .bind(id)
+                    .execute(p)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Disk and memory rules for a fresh install
+    async fn seed_default_rules(&self) -> Result<(), sqlx::Error> {
+        if !self.list_rules().await?.is_empty() {
+            return Ok(());
+        }
+
+        let defaults = [
+            ("Диск заполнен более чем на 90%", "disk_percent"),
+            ("Память занята более чем на 90%", "memory_percent"),
+        ];
+        for (name, metric) in defaults {
+            self.create_rule(&AlertRuleRequest {
+                name: name.to_string(),
+                metric: metric.to_string(),
+                operator: ">".to_string(),
+                threshold: 90.0,
+                duration_secs: Some(300),
+                enabled: Some(true),
+            })
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+fn format_duration(secs: i64) -> String {
+    if secs >= 3600 && secs % 3600 == 0 {
+        format!("{} ч", secs / 3600)
+    } else if secs >= 60 && secs % 60 == 0 {
+        format!("{} мин", secs / 60)
+    } else {
+        format!("{} с", secs)
+    }
+}
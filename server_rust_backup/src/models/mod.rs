@@ -22,11 +22,81 @@ pub struct OtpCode {
 pub struct Session {
     pub id: i32,
     pub user_id: i32,
+    /// SHA-256 hex digest of the bearer token; the raw token is never stored
     pub token: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
     pub expires_at: String,
+    pub absolute_expires_at: Option<String>,
+    pub last_seen_at: Option<String>,
     pub created_at: String,
 }
 
+/// Client metadata recorded when a session is created
+#[derive(Debug, Clone, Default)]
+pub struct SessionClient {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Result of a successful OTP verification
+#[derive(Debug, Clone)]
+pub struct CreatedSession {
+    pub token: String,
+    pub session_id: i32,
+    /// True when no earlier session of this user came from the same IP
+    pub is_new_ip: bool,
+}
+
+/// Active session as shown in the admin panel
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    pub id: i32,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub device: String,
+    pub created_at: String,
+    pub last_seen_at: Option<String>,
+    pub expires_at: String,
+    pub absolute_expires_at: Option<String>,
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevokeSessionsResponse {
+    pub revoked: u64,
+}
+
+/// Personal access token record (the token hash is never selected)
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ApiToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub token_prefix: String,
+    /// JSON array of `module:action` scopes
+    pub scopes: String,
+    /// JSON array of IPs / CIDR ranges; NULL allows any IP
+    pub allowed_ips: Option<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub last_used_ip: Option<String>,
+    pub created_at: String,
+}
+
+impl ApiToken {
+    pub fn scope_list(&self) -> Vec<String> {
+        serde_json::from_str(&self.scopes).unwrap_or_default()
+    }
+
+    pub fn allowed_ip_list(&self) -> Vec<String> {
+        self.allowed_ips
+            .as_deref()
+            .and_then(|ips| serde_json::from_str(ips).ok())
+            .unwrap_or_default()
+    }
+}
+
 // Request/Response DTOs
 #[derive(Debug, Deserialize)]
 pub struct RequestOtpRequest {
@@ -75,3 +145,48 @@ impl<T> ApiResponse<T> {
         }
     }
 }
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiTokenInfo {
+    pub id: i32,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub allowed_ips: Vec<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub last_used_ip: Option<String>,
+    pub created_at: String,
+}
+
+impl From<ApiToken> for ApiTokenInfo {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            scopes: token.scope_list(),
+            allowed_ips: token.allowed_ip_list(),
+            id: token.id,
+            name: token.name,
+            token_prefix: token.token_prefix,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            last_used_ip: token.last_used_ip,
+            created_at: token.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateApiTokenResponse {
+    /// The raw token; shown only once
+    pub token: String,
+    pub api_token: ApiTokenInfo,
+}
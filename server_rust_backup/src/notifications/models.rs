@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Categories used by the server's own modules; any other non-empty name is accepted too
+pub mod category {
+    pub const ALICE: &str = "alice";
+    pub const JOBS: &str = "jobs";
+    pub const SYNC: &str = "sync";
+    pub const PUBLISH: &str = "publish";
+    pub const SECURITY: &str = "security";
+    pub const SYSTEM: &str = "system";
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+impl NotificationPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationPriority::Low => "low",
+            NotificationPriority::Normal => "normal",
+            NotificationPriority::High => "high",
+            NotificationPriority::Critical => "critical",
+        }
+    }
+}
+
+/// Notification center row
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Notification {
+    pub id: i64,
+    pub category: String,
+    pub priority: String,
+    pub title: String,
+    pub message: String,
+    /// Admin page the notification is about, e.g. "/admin/jobs"
+    pub link: Option<String>,
+    pub data: Option<Value>,
+    pub read_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Notification to post; built by modules or sent by the admin
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewNotification {
+    pub category: String,
+    #[serde(default)]
+    pub priority: NotificationPriority,
+    pub title: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub link: Option<String>,
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+impl NewNotification {
+    pub fn new(category: &str, priority: NotificationPriority, title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            category: category.to_string(),
+            priority,
+            title: title.into(),
+            message: message.into(),
+            link: None,
+            data: None,
+        }
+    }
+
+    pub fn link(mut self, link: impl Into<String>) -> Self {
+        self.link = Some(link.into());
+        self
+    }
+
+    pub fn data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
+/// What the SSE stream sends; the event name is the `type`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationEvent {
+    Created { notification: Notification },
+    /// One notification (`id`), or all of them, optionally within a category
+    Read { id: Option<i64>, category: Option<String> },
+    Deleted { id: i64 },
+}
+
+impl NotificationEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotificationEvent::Created { .. } => "created",
+            NotificationEvent::Read { .. } => "read",
+            NotificationEvent::Deleted { .. } => "deleted",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NotificationFilter {
+    pub category: Option<String>,
+    pub priority: Option<String>,
+    pub unread: Option<bool>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NotificationPage {
+    pub notifications: Vec<Notification>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Unread counters for the bell icon
+#[derive(Debug, Serialize)]
+pub struct UnreadSummary {
+    pub total: i64,
+    pub by_category: std::collections::HashMap<String, i64>,
+}
+
+/// Short-lived ticket for `?ticket=` on the stream (browsers cannot set headers there)
+#[derive(Debug, Serialize)]
+pub struct StreamTicket {
+    pub ticket: String,
+    pub expires_in: u64,
+}
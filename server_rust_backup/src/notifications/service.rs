@@ -0,0 +1,336 @@
+use crate::db::DbPool;
+use crate::notifications::models::*;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::env;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+const DEFAULT_RETENTION_DAYS: i64 = 90;
+
+/// Stream tickets stay valid for EventSource reconnects during a working session
+pub const STREAM_TICKET_TTL: Duration = Duration::from_secs(12 * 3600);
+
+/// Live events for open SSE streams
+fn events() -> &'static broadcast::Sender<NotificationEvent> {
+    static EVENTS: OnceLock<broadcast::Sender<NotificationEvent>> = OnceLock::new();
+    EVENTS.get_or_init(|| broadcast::channel(256).0)
+}
+
+/// Stream tickets -> expiry
+fn tickets() -> &'static RwLock<HashMap<String, Instant>> {
+    static TICKETS: OnceLock<RwLock<HashMap<String, Instant>>> = OnceLock::new();
+    TICKETS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+const COLUMNS: &str = "id, category, priority, title, message, link, data, read_at, created_at";
+const PG_COLUMNS: &str = "id::INT8 AS id, category, priority, title, message, link, data, read_at, created_at";
+
+/// Persistent notification center; every module posts through [`NotificationService::post`]
+pub struct NotificationService;
+
+impl NotificationService {
+    /// Retention period for notifications
+    pub fn retention_days() -> i64 {
+        env::var("NOTIFICATION_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RETENTION_DAYS)
+    }
+
+    pub fn subscribe() -> broadcast::Receiver<NotificationEvent> {
+        events().subscribe()
+    }
+
+    fn publish(event: NotificationEvent) {
+        // No receivers just means nobody has the admin open
+        let _ = events().send(event);
+    }
+
+    /// Store a notification and push it to open streams
+    pub async fn notify(pool: &DbPool, notification: &NewNotification) -> Result<Notification, String> {
+        if notification.category.trim().is_empty() || notification.category.len() > 32 {
+            return Err("category must be 1-32 characters".to_string());
+        }
+        if notification.title.trim().is_empty() {
+            return Err("title is required".to_string());
+        }
+
+        let created = match pool {
+            DbPool::Sqlite(p) => {
+                let id = sqlx::query(
+                    "INSERT INTO notifications (category, priority, title, message, link, data) VALUES (?, ?, ?, ?, ?, ?)"
+                )
+                .bind(&notification.category)
+                .bind(notification.priority.as_str())
+                .bind(&notification.title)
+                .bind(&notification.message)
+                .bind(&notification.link)
+                .bind(&notification.data)
+                .execute(p)
+                .await
+                .map_err(|e| e.to_string())?
+                .last_insert_rowid();
+                sqlx::query_as::<_, Notification>(&format!("SELECT {} FROM notifications WHERE id = ?", COLUMNS))
+                    .bind(id)
+                    .fetch_one(p)
+                    .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, Notification>(&format!(
+                    "INSERT INTO notifications (category, priority, title, message, link, data)
+                     VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+                    PG_COLUMNS
+                ))
+                .bind(&notification.category)
+                .bind(notification.priority.as_str())
+                .bind(&notification.title)
+                .bind(&notification.message)
+                .bind(&notification.link)
+                .bind(&notification.data)
+                .fetch_one(p)
+                .await
+            }
+        }
+        .map_err(|e| e.to_string())?;
+
+        debug!("Notification {} [{}]: {}", created.id, created.category, created.title);
+        Self::publish(NotificationEvent::Created { notification: created.clone() });
+        Ok(created)
+    }
+
+    /// [`NotificationService::notify`] for callers that only log a failure
+    pub async fn post(pool: &DbPool, notification: NewNotification) {
+        if let Err(e) = Self::notify(pool, &notification).await {
+            warn!("Failed to post notification \"{}\": {}", notification.title, e);
+        }
+    }
+
+    /// Notifications, newest first
+    pub async fn list(pool: &DbPool, filter: &NotificationFilter) -> Result<NotificationPage, sqlx::Error> {
+        let limit = filter.limit.unwrap_or(50).clamp(1, 500);
+        let offset = filter.offset.unwrap_or(0).max(0);
+
+        let mut conditions: Vec<(&str, &String)> = Vec::new();
+        if let Some(category) = &filter.category {
+            conditions.push(("category = {}", category));
+        }
+        if let Some(priority) = &filter.priority {
+            conditions.push(("priority = {}", priority));
+        }
+        let build_where = |placeholder: &dyn Fn(usize) -> String| -> String {
+            let mut clauses: Vec<String> = conditions
+                .iter()
+                .enumerate()
+                .map(|(i, (cond, _))| cond.replace("{}", &placeholder(i + 1)))
+                .collect();
+            if filter.unread == Some(true) {
+                clauses.push("read_at IS NULL".to_string());
+            }
+            if clauses.is_empty() {
+                String::new()
+            } else {
+                format!("WHERE {}", clauses.join(" AND "))
+            }
+        };
+
+        let (notifications, total) = match pool {
+            DbPool::Sqlite(p) => {
+                let where_sql = build_where(&|_| "?".to_string());
+
+                let count_sql = format!("SELECT COUNT(*) FROM notifications {}", where_sql);
+                let mut count = sqlx::query_as::<_, (i64,)>(&count_sql);
+                for (_, value) in &conditions {
+                    count = count.bind(*value);
+                }
+                let total = count.fetch_one(p).await?.0;
+
+                let sql = format!(
+                    "SELECT {} FROM notifications {} ORDER BY id DESC LIMIT ? OFFSET ?",
+                    COLUMNS, where_sql
+                );
+                let mut query = sqlx::query_as::<_, Notification>(&sql);
+                for (_, value) in &conditions {
+                    query = query.bind(*value);
+                }
+                (query.bind(limit).bind(offset).fetch_all(p).await?, total)
+            }
+            DbPool::Postgres(p) => {
+                let where_sql = build_where(&|n| format!("${}", n));
+
+                let count_sql = format!("SELECT COUNT(*) FROM notifications {}", where_sql);
+                let mut count = sqlx::query_as::<_, (i64,)>(&count_sql);
+                for (_, value) in &conditions {
+                    count = count.bind(*value);
+                }
+                let total = count.fetch_one(p).await?.0;
+
+                let n = conditions.len();
+                let sql = format!(
+                    "SELECT {} FROM notifications {} ORDER BY id DESC LIMIT ${} OFFSET ${}",
+                    PG_COLUMNS,
+                    where_sql,
+                    n + 1,
+                    n + 2
+                );
+                let mut query = sqlx::query_as::<_, Notification>(&sql);
+                for (_, value) in &conditions {
+                    query = query.bind(*value);
+                }
+                (query.bind(limit).bind(offset).fetch_all(p).await?, total)
+            }
+        };
+
+        Ok(NotificationPage {
+            notifications,
+            total,
+            limit,
+            offset,
+        })
+    }
+
+    /// Notifications created after `id`, oldest first; replayed to reconnecting streams
+    pub async fn since(pool: &DbPool, id: i64) -> Result<Vec<Notification>, sqlx::Error> {
+        match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as::<_, Notification>(&format!(
+                    "SELECT {} FROM notifications WHERE id > ? ORDER BY id LIMIT 500",
+                    COLUMNS
+                ))
+                .bind(id)
+                .fetch_all(p)
+                .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as::<_, Notification>(&format!(
+                    "SELECT {} FROM notifications WHERE id > $1 ORDER BY id LIMIT 500",
+                    PG_COLUMNS
+                ))
+                .bind(id)
+                .fetch_all(p)
+                .await
+            }
+        }
+    }
+
+    pub async fn unread_summary(pool: &DbPool) -> Result<UnreadSummary, sqlx::Error> {
+        let sql = "SELECT category, COUNT(*) FROM notifications WHERE read_at IS NULL GROUP BY category";
+        let rows: Vec<(String, i64)> = match pool {
+            DbPool::Sqlite(p) => sqlx::query_as(sql).fetch_all(p).await?,
+            DbPool::Postgres(p) => sqlx::query_as(sql).fetch_all(p).await?,
+        };
+        Ok(UnreadSummary {
+            total: rows.iter().map(|(_, count)| count).sum(),
+            by_category: rows.into_iter().collect(),
+        })
+    }
+
+    /// Mark one notification read; false if it does not exist or was read already
+    pub async fn mark_read(pool: &DbPool, id: i64) -> Result<bool, sqlx::Error> {
+        let affected = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("UPDATE notifications SET read_at = datetime('now') WHERE id = ? AND read_at IS NULL")
+                    .bind(id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query("UPDATE notifications SET read_at = NOW() WHERE id = $1 AND read_at IS NULL")
+                    .bind(id)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        if affected > 0 {
+            Self::publish(NotificationEvent::Read { id: Some(id), category: None });
+        }
+        Ok(affected > 0)
+    }
+
+    /// Mark every unread notification read, optionally within one category
+    pub async fn mark_all_read(pool: &DbPool, category: Option<&str>) -> Result<u64, sqlx::Error> {
+        let affected = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query(
+                    "UPDATE notifications SET read_at = datetime('now') WHERE read_at IS NULL AND (? IS NULL OR category = ?)"
+                )
+                .bind(category)
+                .bind(category)
+                .execute(p)
+                .await?
+                .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query("UPDATE notifications SET read_at = NOW() WHERE read_at IS NULL AND ($1::TEXT IS NULL OR category = $1)")
+                    .bind(category)
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        if affected > 0 {
+            Self::publish(NotificationEvent::Read {
+                id: None,
+                category: category.map(str::to_string),
+            });
+        }
+        Ok(affected)
+    }
+
+    pub async fn delete(pool: &DbPool, id: i64) -> Result<bool, sqlx::Error> {
+        let affected = match pool {
+            DbPool::Sqlite(p) => sqlx::query("DELETE FROM notifications WHERE id = ?").bind(id).execute(p).await?.rows_affected(),
+            DbPool::Postgres(p) => sqlx::query("DELETE FROM notifications WHERE id = $1").bind(id).execute(p).await?.rows_affected(),
+        };
+
+        if affected > 0 {
+            Self::publish(NotificationEvent::Deleted { id });
+        }
+        Ok(affected > 0)
+    }
+
+    /// Delete notifications older than the retention period
+    pub async fn purge_expired(pool: &DbPool, days: i64) -> Result<u64, sqlx::Error> {
+        let removed = match pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query("DELETE FROM notifications WHERE created_at < datetime('now', ?)")
+                    .bind(format!("-{} days", days))
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query("DELETE FROM notifications WHERE created_at < NOW() - $1::interval")
+                    .bind(format!("{} days", days))
+                    .execute(p)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        Ok(removed)
+    }
+
+    /// Ticket for opening the stream without an Authorization header
+    pub fn issue_ticket() -> StreamTicket {
+        let ticket = crate::auth::AuthService::generate_token();
+        let now = Instant::now();
+        let mut tickets = tickets().write();
+        tickets.retain(|_, expires| *expires > now);
+        tickets.insert(ticket.clone(), now + STREAM_TICKET_TTL);
+        StreamTicket {
+            ticket,
+            expires_in: STREAM_TICKET_TTL.as_secs(),
+        }
+    }
+
+    pub fn check_ticket(ticket: &str) -> bool {
+        tickets().read().get(ticket).is_some_and(|expires| *expires > Instant::now())
+    }
+}
@@ -7,6 +7,7 @@ use std::process::Command;
 use std::sync::Arc;
 use tokio::fs;
 use uuid::Uuid;
+use tracing::error;
 
 #[derive(Clone)]
 pub struct PublishService {
@@ -434,11 +435,11 @@ impl PublishService {
             Ok(output) => {
                 // If text overlay fails, just continue without it
                 let stderr = String::from_utf8_lossy(&output.stderr);
-                eprintln!("Frame overlay warning: {}", stderr);
+                error!("Frame overlay warning: {}", stderr);
                 Ok(())
             }
             Err(e) => {
-                eprintln!("Frame overlay error: {}", e);
+                error!("Frame overlay error: {}", e);
                 Ok(())
             }
         }
@@ -498,6 +499,18 @@ impl PublishService {
         }
     }
 
+    /// Number of jobs in each status
+    pub fn status_counts(&self) -> Vec<(JobStatus, usize)> {
+        let jobs = self.jobs.read();
+        [JobStatus::Pending, JobStatus::Processing, JobStatus::Completed, JobStatus::Error]
+            .into_iter()
+            .map(|status| {
+                let count = jobs.values().filter(|job| job.status == status).count();
+                (status, count)
+            })
+            .collect()
+    }
+
     /// Get result file path
     pub fn get_result_path(&self, job_id: &str) -> Option<PathBuf> {
         self.jobs.read().get(job_id).and_then(|job| {
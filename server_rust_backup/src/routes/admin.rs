@@ -9,6 +9,8 @@ pub struct AdminInfo {
     pub message: String,
     pub telegram_id: i64,
     pub user_id: i32,
+    pub session_id: Option<i32>,
+    pub api_token_id: Option<i32>,
 }
 
 #[derive(Serialize)]
@@ -24,6 +26,8 @@ pub async fn admin_info(_auth: AuthGuard) -> Json<ApiResponse<AdminInfo>> {
         message: "You are authenticated as admin".to_string(),
         telegram_id: _auth.user.telegram_id,
         user_id: _auth.user.id,
+        session_id: _auth.session_id,
+        api_token_id: _auth.api_token_id,
     }))
 }
 
@@ -1,18 +1,31 @@
 use crate::alice::{
     models::*,
+    mqtt::MqttClient,
+    rules::{RuleEngine, RuleEvent},
+    schedule::PcScheduleService,
+    schema,
     service::{AliceService, AliceState, CommandQueueService},
 };
 use crate::db::DbPool;
 use crate::guards::AdminSession;
-use crate::telegram::TelegramBot;
+use crate::notifications::{category, NewNotification, NotificationFilter, NotificationPriority, NotificationService};
+use crate::telegram::{TelegramBot, TelegramBotService};
 use rocket::form::FromForm;
-use rocket::http::Status;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::{ContentType, Status};
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket::response::Redirect;
+use rocket::futures::{SinkExt, StreamExt};
 use rocket::serde::json::Json;
-use rocket::{get, post, delete, State};
+use rocket::tokio::select;
+use rocket::{get, post, put, delete, State};
+use rocket_ws::stream::DuplexStream;
+use rocket_ws::{Channel, Message, WebSocket};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 
 /// Alice authorization header guard
 pub struct AliceAuth {
@@ -50,7 +63,10 @@ pub fn alice_health() -> &'static str {
 
 /// Unlink user (revoke access)
 #[post("/alice/v1.0/user/unlink")]
-pub async fn alice_unlink(_auth: AliceAuth) -> Json<serde_json::Value> {
+pub async fn alice_unlink(auth: AliceAuth, pool: &State<DbPool>) -> Json<serde_json::Value> {
+    if let Err(e) = AliceService::revoke_tokens(pool.inner(), auth.user_id).await {
+        error!("Failed to revoke Alice tokens: {}", e);
+    }
     Json(serde_json::json!({}))
 }
 
@@ -59,17 +75,22 @@ pub async fn alice_unlink(_auth: AliceAuth) -> Json<serde_json::Value> {
 pub async fn alice_get_devices(
     auth: AliceAuth,
     pool: &State<DbPool>,
+    schedules: &State<PcScheduleService>,
 ) -> Result<Json<UserDevicesResponse>, Status> {
     let request_id = uuid::Uuid::new_v4().to_string();
 
     match AliceService::get_devices(pool.inner()).await {
-        Ok(devices) => Ok(Json(UserDevicesResponse {
-            request_id,
-            payload: UserDevicesPayload {
-                user_id: auth.user_id.to_string(),
-                devices,
-            },
-        })),
+        Ok(mut devices) => {
+            // Schedules marked as Alice scenes appear as switches
+            devices.extend(schedules.alice_devices().await);
+            Ok(Json(UserDevicesResponse {
+                request_id,
+                payload: UserDevicesPayload {
+                    user_id: auth.user_id.to_string(),
+                    devices,
+                },
+            }))
+        }
         Err(_) => Err(Status::InternalServerError),
     }
 }
@@ -80,6 +101,7 @@ pub async fn alice_query_devices(
     _auth: AliceAuth,
     pool: &State<DbPool>,
     alice_state: &State<AliceState>,
+    schedules: &State<PcScheduleService>,
     request: Json<QueryRequest>,
 ) -> Result<Json<QueryResponse>, Status> {
     let request_id = uuid::Uuid::new_v4().to_string();
@@ -87,26 +109,9 @@ pub async fn alice_query_devices(
     let mut device_responses = Vec::new();
 
     for device in &request.devices {
-        match AliceService::get_device_state(pool.inner(), &device.id, alice_state.inner()).await {
-            Ok(capabilities) => {
-                device_responses.push(QueryDeviceResponse {
-                    id: device.id.clone(),
-                    capabilities: Some(capabilities),
-                    properties: None,
-                    error_code: None,
-                    error_message: None,
-                });
-            }
-            Err(_) => {
-                device_responses.push(QueryDeviceResponse {
-                    id: device.id.clone(),
-                    capabilities: None,
-                    properties: None,
-                    error_code: Some("DEVICE_UNREACHABLE".to_string()),
-                    error_message: Some("Failed to query device".to_string()),
-                });
-            }
-        }
+        device_responses.push(
+            AliceService::query_device(pool.inner(), &device.id, alice_state.inner(), schedules.inner()).await,
+        );
     }
 
     Ok(Json(QueryResponse {
@@ -118,6 +123,7 @@ pub async fn alice_query_devices(
 }
 
 /// Execute actions on devices
+#[allow(clippy::too_many_arguments)]
 #[post("/alice/v1.0/user/devices/action", data = "<request>")]
 pub async fn alice_action(
     _auth: AliceAuth,
@@ -125,6 +131,9 @@ pub async fn alice_action(
     alice_state: &State<AliceState>,
     telegram_bot: &State<TelegramBot>,
     admin_telegram_id: &State<i64>,
+    schedules: &State<PcScheduleService>,
+    mqtt: &State<MqttClient>,
+    rules: &State<RuleEngine>,
     request: Json<ActionRequest>,
 ) -> Result<Json<ActionResponse>, Status> {
     let request_id = uuid::Uuid::new_v4().to_string();
@@ -135,15 +144,27 @@ pub async fn alice_action(
         let mut capability_responses = Vec::new();
 
         for cap in &device.capabilities {
+            if let Some(schedule_id) = PcScheduleService::schedule_id(&device.id) {
+                let result = schedule_scene_action(schedules.inner(), schedule_id, &cap.state.instance, &cap.state.value).await;
+                dispatch_action(rules.inner(), &device.id, cap, &result).await;
+                capability_responses.push(ActionCapabilityResponse {
+                    capability_type: cap.capability_type.clone(),
+                    state: ActionResultState {
+                        instance: cap.state.instance.clone(),
+                        action_result: result,
+                    },
+                });
+                continue;
+            }
+
             let result = AliceService::execute_action(
                 pool.inner(),
                 &device.id,
-                &cap.capability_type,
-                &cap.state.instance,
-                cap.state.value.clone(),
+                cap,
                 alice_state.inner(),
                 telegram_bot.inner(),
                 **admin_telegram_id,
+                mqtt.inner(),
             )
             .await
             .unwrap_or(ActionResult {
@@ -151,6 +172,7 @@ pub async fn alice_action(
                 error_code: Some("INTERNAL_ERROR".to_string()),
                 error_message: Some("Unknown error".to_string()),
             });
+            dispatch_action(rules.inner(), &device.id, cap, &result).await;
 
             capability_responses.push(ActionCapabilityResponse {
                 capability_type: cap.capability_type.clone(),
@@ -175,6 +197,50 @@ pub async fn alice_action(
     }))
 }
 
+/// Start the rules triggered by a successful Alice action
+async fn dispatch_action(rules: &RuleEngine, device_id: &str, cap: &ActionCapability, result: &ActionResult) {
+    if result.status == "DONE" {
+        let event = RuleEvent::AliceAction {
+            device_id: device_id.to_string(),
+            instance: cap.state.instance.clone(),
+            value: cap.state.value.clone(),
+        };
+        rules.dispatch(event).await;
+    }
+}
+
+/// Alice turning a schedule switch on arms (resumes) it, off pauses it
+async fn schedule_scene_action(
+    schedules: &PcScheduleService,
+    schedule_id: i64,
+    instance: &str,
+    value: &serde_json::Value,
+) -> ActionResult {
+    let result = match (instance, value.as_bool()) {
+        ("on", Some(true)) => schedules.resume(schedule_id).await,
+        ("on", Some(false)) => schedules.pause(schedule_id).await,
+        _ => Err("Unknown action".to_string()),
+    };
+
+    match result {
+        Ok(true) => ActionResult {
+            status: "DONE".to_string(),
+            error_code: None,
+            error_message: None,
+        },
+        Ok(false) => ActionResult {
+            status: "ERROR".to_string(),
+            error_code: Some("DEVICE_NOT_FOUND".to_string()),
+            error_message: Some("Schedule not found".to_string()),
+        },
+        Err(e) => ActionResult {
+            status: "ERROR".to_string(),
+            error_code: Some("INVALID_ACTION".to_string()),
+            error_message: Some(e),
+        },
+    }
+}
+
 // ================== OAuth Endpoints ==================
 
 #[derive(Debug, FromForm)]
@@ -416,6 +482,95 @@ pub async fn alice_admin_update_config(
     }
 }
 
+/// All registry devices with their schema and handler, disabled ones included (admin)
+#[get("/alice/admin/registry")]
+pub async fn alice_admin_list_registry(
+    _session: AdminSession,
+    pool: &State<DbPool>,
+) -> Result<Json<Vec<DeviceDefinition>>, Status> {
+    AliceService::list_definitions(pool.inner())
+        .await
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// Check a device definition against the Yandex schema without saving it (admin)
+#[post("/alice/admin/registry/validate", data = "<device>")]
+pub async fn alice_admin_validate_device(
+    _session: AdminSession,
+    device: Json<DeviceDefinition>,
+) -> Json<serde_json::Value> {
+    let result = schema::validate_device(&device).and_then(|_| device.handler.validate());
+    registry_result(result.map(|_| true))
+}
+
+/// Add a device to the registry (admin)
+#[post("/alice/admin/registry", data = "<device>")]
+pub async fn alice_admin_create_device(
+    _session: AdminSession,
+    pool: &State<DbPool>,
+    alice_state: &State<AliceState>,
+    mqtt: &State<MqttClient>,
+    device: Json<DeviceDefinition>,
+) -> Json<serde_json::Value> {
+    let result = AliceService::save_device(pool.inner(), &device, true, alice_state.inner()).await;
+    mqtt.refresh(pool.inner()).await;
+    registry_result(result)
+}
+
+/// Replace a registry device (admin)
+#[put("/alice/admin/registry/<id>", data = "<device>")]
+pub async fn alice_admin_update_device(
+    _session: AdminSession,
+    pool: &State<DbPool>,
+    alice_state: &State<AliceState>,
+    mqtt: &State<MqttClient>,
+    id: &str,
+    device: Json<DeviceDefinition>,
+) -> Json<serde_json::Value> {
+    let mut device = device.into_inner();
+    device.id = id.to_string();
+    let result = AliceService::save_device(pool.inner(), &device, false, alice_state.inner()).await;
+    mqtt.refresh(pool.inner()).await;
+    registry_result(result)
+}
+
+/// Remove a registry device (admin)
+#[delete("/alice/admin/registry/<id>")]
+pub async fn alice_admin_delete_device(
+    _session: AdminSession,
+    pool: &State<DbPool>,
+    alice_state: &State<AliceState>,
+    mqtt: &State<MqttClient>,
+    id: &str,
+) -> Json<serde_json::Value> {
+    let result = AliceService::delete_device(pool.inner(), id, alice_state.inner())
+        .await
+        .map_err(|e| e.to_string());
+    mqtt.refresh(pool.inner()).await;
+    registry_result(result)
+}
+
+/// Report a property or capability value from the device side, e.g. a sensor reading (admin)
+#[put("/alice/admin/registry/<id>/state", data = "<update>")]
+pub async fn alice_admin_report_state(
+    _session: AdminSession,
+    pool: &State<DbPool>,
+    alice_state: &State<AliceState>,
+    id: &str,
+    update: Json<DeviceStateUpdate>,
+) -> Json<serde_json::Value> {
+    registry_result(AliceService::report_state(pool.inner(), id, &update, alice_state.inner()).await)
+}
+
+fn registry_result(result: Result<bool, String>) -> Json<serde_json::Value> {
+    match result {
+        Ok(true) => Json(serde_json::json!({"success": true})),
+        Ok(false) => Json(serde_json::json!({"success": false, "error": "Device not found"})),
+        Err(e) => Json(serde_json::json!({"success": false, "error": e})),
+    }
+}
+
 /// Get command log (admin)
 #[get("/alice/admin/commands?<limit>")]
 pub async fn alice_admin_get_commands(
@@ -430,26 +585,36 @@ pub async fn alice_admin_get_commands(
     }
 }
 
-/// Get notifications (admin/public for SSE)
+/// Recent Alice notifications from the notification center (public feed)
 #[get("/alice/notifications?<limit>")]
 pub async fn alice_get_notifications(
-    alice_state: &State<AliceState>,
-    limit: Option<usize>,
-) -> Json<Vec<WebsiteNotification>> {
-    let limit = limit.unwrap_or(10);
-    Json(alice_state.get_notifications(limit))
+    pool: &State<DbPool>,
+    limit: Option<i64>,
+) -> Result<Json<Vec<WebsiteNotification>>, Status> {
+    let filter = NotificationFilter {
+        category: Some(category::ALICE.to_string()),
+        limit: Some(limit.unwrap_or(10)),
+        ..Default::default()
+    };
+    let page = NotificationService::list(pool.inner(), &filter)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    Ok(Json(page.notifications.into_iter().map(Into::into).collect()))
 }
 
 /// Send test notification
 #[post("/alice/admin/test-notification", data = "<message>")]
 pub async fn alice_test_notification(
     _session: AdminSession,
-    alice_state: &State<AliceState>,
+    pool: &State<DbPool>,
     message: Json<serde_json::Value>,
-) -> Json<WebsiteNotification> {
+) -> Result<Json<WebsiteNotification>, Status> {
     let msg = message.get("message").and_then(|v| v.as_str()).unwrap_or("Тестовое уведомление");
-    let notification = alice_state.inner().add_notification(msg.to_string(), "test".to_string());
-    Json(notification)
+    let notification = NewNotification::new(category::ALICE, NotificationPriority::Low, "Тест", msg);
+    NotificationService::notify(pool.inner(), &notification)
+        .await
+        .map(|n| Json(n.into()))
+        .map_err(|_| Status::InternalServerError)
 }
 
 /// Send Telegram message (admin)
@@ -472,7 +637,7 @@ pub async fn alice_admin_telegram(
     match telegram_bot.send_message(chat_id, message).await {
         Ok(_) => Ok(Json(serde_json::json!({"success": true}))),
         Err(e) => {
-            eprintln!("Telegram error: {}", e);
+            error!("Telegram error: {}", e);
             Err(Status::InternalServerError)
         }
     }
@@ -531,6 +696,10 @@ pub async fn alice_admin_wake_pc(
 pub struct PcClientAuth {
     pub client_id: String,
     pub client_name: String,
+    pub api_key: String,
+    pub group: Option<String>,
+    /// Command types the client can execute, empty until it sends a current handshake
+    pub capabilities: Vec<String>,
 }
 
 #[rocket::async_trait]
@@ -553,9 +722,13 @@ impl<'r> FromRequest<'r> for PcClientAuth {
                         request.client_ip().map(|ip| ip.to_string()).as_deref(),
                     ).await;
 
+                    let capabilities = client.supported_capabilities();
                     return Outcome::Success(PcClientAuth {
                         client_id: client.client_id,
                         client_name: client.client_name,
+                        api_key: client.api_key,
+                        group: client.client_group,
+                        capabilities,
                     });
                 }
                 _ => {}
@@ -577,13 +750,14 @@ pub async fn alice_pc_register(
         pool.inner(),
         &request.client_name,
         request.mac_address.as_deref(),
+        request.group.as_deref(),
     ).await {
         Ok((client_id, api_key)) => Ok(Json(RegisterClientResponse {
             client_id,
             api_key,
         })),
         Err(e) => {
-            eprintln!("Failed to register PC client: {}", e);
+            error!("Failed to register PC client: {}", e);
             Err(Status::InternalServerError)
         }
     }
@@ -606,6 +780,9 @@ pub async fn alice_pc_list_clients(
                     "mac_address": c.mac_address,
                     "ip_address": c.ip_address,
                     "created_at": c.created_at,
+                    "protocol_version": c.protocol_version,
+                    "capabilities": c.capabilities,
+                    "group": c.client_group,
                 })
             }).collect();
             Ok(Json(client_json))
@@ -627,13 +804,38 @@ pub async fn alice_pc_delete_client(
     }
 }
 
+/// Set or clear the group of a PC client (admin)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientGroupRequest {
+    pub group: Option<String>,
+}
+
+#[put("/alice/pc/clients/<client_id>/group", data = "<request>")]
+pub async fn alice_pc_set_client_group(
+    _session: AdminSession,
+    pool: &State<DbPool>,
+    client_id: &str,
+    request: Json<ClientGroupRequest>,
+) -> Result<Json<serde_json::Value>, Status> {
+    let group = request.group.as_deref().map(str::trim).filter(|g| !g.is_empty());
+    match CommandQueueService::set_client_group(pool.inner(), client_id, group).await {
+        Ok(updated) => Ok(Json(serde_json::json!({"success": updated}))),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
 /// Queue a command for PC client (admin)
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QueueCommandRequest {
-    pub command_type: String,
-    pub command_data: serde_json::Value,
+    pub command: PcCommandType,
     #[serde(default)]
     pub priority: i32,
+    /// Optional `client_id` and/or `group`; without them any capable client may run it
+    #[serde(flatten)]
+    pub target: CommandTarget,
+    /// Optional `not_after` and `max_attempts`
+    #[serde(flatten)]
+    pub delivery: CommandDelivery,
 }
 
 #[post("/alice/pc/queue", data = "<request>")]
@@ -642,32 +844,31 @@ pub async fn alice_pc_queue_command(
     pool: &State<DbPool>,
     request: Json<QueueCommandRequest>,
 ) -> Result<Json<serde_json::Value>, Status> {
-    match CommandQueueService::queue_command(
-        pool.inner(),
-        &request.command_type,
-        request.command_data.clone(),
-        request.priority,
-    ).await {
+    match CommandQueueService::queue_command(pool.inner(), &request.command, request.priority, &request.target, &request.delivery).await {
         Ok(id) => Ok(Json(serde_json::json!({
             "success": true,
             "command_id": id
         }))),
         Err(e) => {
-            eprintln!("Failed to queue command: {}", e);
-            Err(Status::InternalServerError)
+            warn!("Failed to queue command: {}", e);
+            Ok(Json(serde_json::json!({
+                "success": false,
+                "error": e
+            })))
         }
     }
 }
 
-/// Get pending commands in queue (admin)
-#[get("/alice/pc/queue?<limit>")]
+/// Get queued commands by status, `pending` by default; `dead` lists the dead letters (admin)
+#[get("/alice/pc/queue?<status>&<limit>")]
 pub async fn alice_pc_get_queue(
     _session: AdminSession,
     pool: &State<DbPool>,
+    status: Option<&str>,
     limit: Option<i64>,
 ) -> Result<Json<Vec<serde_json::Value>>, Status> {
     let limit = limit.unwrap_or(50);
-    match CommandQueueService::get_pending_commands(pool.inner(), limit).await {
+    match CommandQueueService::get_commands(pool.inner(), status.unwrap_or("pending"), limit).await {
         Ok(commands) => {
             let cmd_json: Vec<serde_json::Value> = commands.into_iter().map(|c| {
                 serde_json::json!({
@@ -677,6 +878,18 @@ pub async fn alice_pc_get_queue(
                     "priority": c.priority,
                     "status": c.status,
                     "created_at": c.created_at,
+                    "target_client_id": c.target_client_id,
+                    "target_group": c.target_group,
+                    "claimed_by": c.claimed_by,
+                    "claimed_at": c.claimed_at,
+                    "attempts": c.attempts,
+                    "max_attempts": c.max_attempts,
+                    "not_after": c.expires_at,
+                    "available_at": c.available_at,
+                    "processed_at": c.processed_at,
+                    "result": c.result,
+                    "error": c.error,
+                    "artifact_file_id": c.artifact_file_id,
                 })
             }).collect();
             Ok(Json(cmd_json))
@@ -685,35 +898,308 @@ pub async fn alice_pc_get_queue(
     }
 }
 
+/// Cancel a pending or processing command (admin)
+#[post("/alice/pc/queue/<id>/cancel")]
+pub async fn alice_pc_cancel_command(
+    _session: AdminSession,
+    pool: &State<DbPool>,
+    id: i64,
+) -> Result<Json<serde_json::Value>, Status> {
+    match CommandQueueService::cancel_command(pool.inner(), id).await {
+        Ok(cancelled) => Ok(Json(serde_json::json!({"success": cancelled}))),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+/// Download the file a command uploaded as its result, e.g. a screenshot (admin)
+#[get("/alice/pc/queue/<id>/artifact")]
+pub async fn alice_pc_get_artifact(
+    _session: AdminSession,
+    pool: &State<DbPool>,
+    id: i64,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let (file, data) = CommandQueueService::load_artifact(pool.inner(), id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to load artifact of command {}: {}", id, e);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+    let content_type = ContentType::parse_flexible(&file.mime_type).unwrap_or(ContentType::Binary);
+    Ok((content_type, data))
+}
+
+/// Send the artifact of a command to the admin's Telegram chat (admin)
+#[post("/alice/pc/queue/<id>/artifact/telegram")]
+pub async fn alice_pc_send_artifact(
+    _session: AdminSession,
+    telegram: &State<TelegramBotService>,
+    id: i64,
+) -> Json<serde_json::Value> {
+    match telegram.send_artifact(id).await {
+        Ok(()) => Json(serde_json::json!({"success": true})),
+        Err(e) => Json(serde_json::json!({"success": false, "error": e})),
+    }
+}
+
+/// List command schedules with their next run (admin)
+#[get("/alice/pc/queue/schedules")]
+pub async fn alice_pc_list_schedules(
+    _session: AdminSession,
+    schedules: &State<PcScheduleService>,
+) -> Result<Json<Vec<serde_json::Value>>, Status> {
+    let list = schedules.list().await.map_err(|_| Status::InternalServerError)?;
+    let mut schedule_json = Vec::with_capacity(list.len());
+    for s in list {
+        schedule_json.push(serde_json::json!({
+            "id": s.id,
+            "name": s.name,
+            "command": s.command_data,
+            "priority": s.priority,
+            "client_id": s.target_client_id,
+            "group": s.target_group,
+            "max_attempts": s.max_attempts,
+            "cron": s.cron_expr,
+            "run_at": s.run_at,
+            "delay_secs": s.delay_secs,
+            "paused": s.is_paused,
+            "alice_scene": s.alice_scene,
+            "next_run": schedules.next_run(s.id).await,
+            "last_run_at": s.last_run_at,
+            "last_command_id": s.last_command_id,
+            "last_error": s.last_error,
+            "created_at": s.created_at,
+        }));
+    }
+    Ok(Json(schedule_json))
+}
+
+/// Create a delayed or recurring command (admin)
+#[post("/alice/pc/queue/schedules", data = "<request>")]
+pub async fn alice_pc_create_schedule(
+    _session: AdminSession,
+    schedules: &State<PcScheduleService>,
+    request: Json<CreateScheduleRequest>,
+) -> Json<serde_json::Value> {
+    match schedules.create(&request).await {
+        Ok(schedule) => Json(serde_json::json!({
+            "success": true,
+            "schedule_id": schedule.id,
+            "next_run": schedules.next_run(schedule.id).await,
+        })),
+        Err(e) => {
+            warn!("Failed to create PC schedule: {}", e);
+            Json(serde_json::json!({"success": false, "error": e}))
+        }
+    }
+}
+
+/// Pause a schedule (admin)
+#[post("/alice/pc/queue/schedules/<id>/pause")]
+pub async fn alice_pc_pause_schedule(
+    _session: AdminSession,
+    schedules: &State<PcScheduleService>,
+    id: i64,
+) -> Json<serde_json::Value> {
+    schedule_result(schedules.pause(id).await)
+}
+
+/// Resume a paused schedule (admin)
+#[post("/alice/pc/queue/schedules/<id>/resume")]
+pub async fn alice_pc_resume_schedule(
+    _session: AdminSession,
+    schedules: &State<PcScheduleService>,
+    id: i64,
+) -> Json<serde_json::Value> {
+    schedule_result(schedules.resume(id).await)
+}
+
+/// Queue a schedule's command now (admin)
+#[post("/alice/pc/queue/schedules/<id>/run")]
+pub async fn alice_pc_run_schedule(
+    _session: AdminSession,
+    schedules: &State<PcScheduleService>,
+    id: i64,
+) -> Json<serde_json::Value> {
+    match schedules.run_now(id).await {
+        Ok(command_id) => Json(serde_json::json!({"success": true, "command_id": command_id})),
+        Err(e) => Json(serde_json::json!({"success": false, "error": e})),
+    }
+}
+
+/// Delete a schedule (admin)
+#[delete("/alice/pc/queue/schedules/<id>")]
+pub async fn alice_pc_delete_schedule(
+    _session: AdminSession,
+    schedules: &State<PcScheduleService>,
+    id: i64,
+) -> Json<serde_json::Value> {
+    schedule_result(schedules.delete(id).await)
+}
+
+fn schedule_result(result: Result<bool, String>) -> Json<serde_json::Value> {
+    match result {
+        Ok(found) => Json(serde_json::json!({"success": found})),
+        Err(e) => Json(serde_json::json!({"success": false, "error": e})),
+    }
+}
+
+// ================== Automation Rules ==================
+
+/// List rules with their last and next run (admin)
+#[get("/alice/admin/rules")]
+pub async fn alice_admin_list_rules(
+    _session: AdminSession,
+    rules: &State<RuleEngine>,
+) -> Result<Json<Vec<serde_json::Value>>, Status> {
+    let list = rules.list().await.map_err(|_| Status::InternalServerError)?;
+    let mut rule_json = Vec::with_capacity(list.len());
+    for r in list {
+        rule_json.push(serde_json::json!({
+            "id": r.id,
+            "name": r.name,
+            "trigger": r.trigger_data,
+            "conditions": r.conditions,
+            "actions": r.actions,
+            "enabled": r.is_enabled,
+            "next_run": rules.next_run(r.id).await,
+            "last_run_at": r.last_run_at,
+            "last_status": r.last_status,
+            "created_at": r.created_at,
+        }));
+    }
+    Ok(Json(rule_json))
+}
+
+/// Create a rule (admin)
+#[post("/alice/admin/rules", data = "<rule>")]
+pub async fn alice_admin_create_rule(
+    _session: AdminSession,
+    rules: &State<RuleEngine>,
+    rule: Json<RuleDefinition>,
+) -> Json<serde_json::Value> {
+    match rules.create(&rule).await {
+        Ok(rule) => Json(serde_json::json!({"success": true, "id": rule.id})),
+        Err(e) => Json(serde_json::json!({"success": false, "error": e})),
+    }
+}
+
+/// Replace a rule (admin)
+#[put("/alice/admin/rules/<id>", data = "<rule>")]
+pub async fn alice_admin_update_rule(
+    _session: AdminSession,
+    rules: &State<RuleEngine>,
+    id: i64,
+    rule: Json<RuleDefinition>,
+) -> Json<serde_json::Value> {
+    schedule_result(rules.update(id, &rule).await)
+}
+
+/// Delete a rule and its history (admin)
+#[delete("/alice/admin/rules/<id>")]
+pub async fn alice_admin_delete_rule(
+    _session: AdminSession,
+    rules: &State<RuleEngine>,
+    id: i64,
+) -> Json<serde_json::Value> {
+    schedule_result(rules.delete(id).await)
+}
+
+/// Evaluate a rule against the given event fields without running its actions (admin)
+#[post("/alice/admin/rules/<id>/dry-run", data = "<event>")]
+pub async fn alice_admin_dry_run_rule(
+    _session: AdminSession,
+    rules: &State<RuleEngine>,
+    id: i64,
+    event: Option<Json<serde_json::Value>>,
+) -> Json<serde_json::Value> {
+    let event = event.map(|e| e.into_inner()).unwrap_or_else(|| serde_json::json!({}));
+    match rules.dry_run(id, event).await {
+        Ok(Some(run)) => Json(serde_json::json!({"success": true, "run": run})),
+        Ok(None) => Json(serde_json::json!({"success": false, "error": "Rule not found"})),
+        Err(e) => Json(serde_json::json!({"success": false, "error": e})),
+    }
+}
+
+/// Execution history, of one rule or of all (admin)
+#[get("/alice/admin/rules/history?<rule_id>&<limit>")]
+pub async fn alice_admin_rule_history(
+    _session: AdminSession,
+    rules: &State<RuleEngine>,
+    rule_id: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Json<Vec<DbAliceRuleRun>>, Status> {
+    rules
+        .history(rule_id, limit.unwrap_or(50))
+        .await
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// Incoming webhook trigger; the key in the path is the secret, the JSON body becomes the event
+#[post("/alice/rules/webhook/<key>", data = "<body>")]
+pub async fn alice_rules_webhook(
+    rules: &State<RuleEngine>,
+    key: &str,
+    body: Option<Json<serde_json::Value>>,
+) -> Json<serde_json::Value> {
+    let event = RuleEvent::Webhook {
+        key: key.to_string(),
+        body: body.map(|b| b.into_inner()).unwrap_or(serde_json::Value::Null),
+    };
+    let started = rules.dispatch(event).await;
+    Json(serde_json::json!({"success": true, "matched": started}))
+}
+
 // ================== PC Client Polling Endpoints ==================
 
-/// Poll for pending commands (PC client)
+/// Poll for pending commands the client announced support for (PC client).
+/// By default the returned commands are claimed so no other client receives them;
+/// `mark_processing=false` only peeks.
 #[get("/alice/pc/poll?<mark_processing>")]
 pub async fn alice_pc_poll_commands(
     auth: PcClientAuth,
     pool: &State<DbPool>,
     mark_processing: Option<bool>,
-) -> Result<Json<Vec<serde_json::Value>>, Status> {
-    match CommandQueueService::get_pending_commands(pool.inner(), 10).await {
-        Ok(commands) => {
-            let cmd_ids: Vec<i64> = commands.iter().map(|c| c.id).collect();
+) -> Result<Json<Vec<CommandEnvelope>>, Status> {
+    if auth.capabilities.is_empty() {
+        return Ok(Json(vec![]));
+    }
 
-            // Mark as processing if requested
-            if mark_processing.unwrap_or(true) && !cmd_ids.is_empty() {
-                let _ = CommandQueueService::mark_as_processing(pool.inner(), &cmd_ids).await;
-            }
+    CommandQueueService::sweep_queue(pool.inner()).await;
 
-            let cmd_json: Vec<serde_json::Value> = commands.into_iter().map(|c| {
-                serde_json::json!({
-                    "id": c.id,
-                    "command_type": c.command_type,
-                    "command_data": c.command_data,
-                    "priority": c.priority,
-                })
-            }).collect();
+    let commands = if mark_processing.unwrap_or(true) {
+        CommandQueueService::claim_commands(pool.inner(), &auth.client_id, auth.group.as_deref(), &auth.capabilities, 10).await
+    } else {
+        CommandQueueService::get_claimable_commands(pool.inner(), &auth.client_id, auth.group.as_deref(), &auth.capabilities, 10).await
+    };
 
-            println!("🖥️ PC client {} polled {} commands", auth.client_name, cmd_json.len());
-            Ok(Json(cmd_json))
+    match commands {
+        Ok(commands) => {
+            let mut envelopes = Vec::with_capacity(commands.len());
+            for c in commands {
+                match (c.payload, c.signature) {
+                    (Some(payload), Some(signature)) => envelopes.push(CommandEnvelope {
+                        id: c.id,
+                        version: c.protocol_version as u32,
+                        priority: c.priority,
+                        payload,
+                        signature,
+                    }),
+                    _ => {
+                        warn!("Dropping unsigned command {}", c.id);
+                        let _ = CommandQueueService::mark_as_failed(
+                            pool.inner(),
+                            c.id,
+                            &auth.client_id,
+                            "Command is not signed".to_string(),
+                        ).await;
+                    }
+                }
+            }
+
+            info!("PC client {} polled {} commands", auth.client_name, envelopes.len());
+            Ok(Json(envelopes))
         }
         Err(_) => Err(Status::InternalServerError),
     }
@@ -724,50 +1210,335 @@ pub async fn alice_pc_poll_commands(
 pub async fn alice_pc_report_result(
     auth: PcClientAuth,
     pool: &State<DbPool>,
-    request: Json<CommandResult>,
+    request: Json<CommandReport>,
 ) -> Result<Json<serde_json::Value>, Status> {
-    if request.success {
-        match CommandQueueService::mark_as_completed(
-            pool.inner(),
-            request.command_id,
-            request.result.clone(),
-        ).await {
-            Ok(_) => {
-                println!("✅ PC client {} completed command {}", auth.client_name, request.command_id);
-                Ok(Json(serde_json::json!({"success": true})))
-            }
-            Err(_) => Err(Status::InternalServerError),
+    match record_command_report(pool.inner(), &auth, request.into_inner()).await {
+        Ok(_) => Ok(Json(serde_json::json!({"success": true}))),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+/// Largest accepted artifact upload, `ALICE_PC_ARTIFACT_MAX_MB` (default 20)
+fn artifact_max_bytes() -> u64 {
+    env::var("ALICE_PC_ARTIFACT_MAX_MB")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(20)
+        * 1024
+        * 1024
+}
+
+/// Upload the result file of a command being executed, sent before its result:
+/// the raw image is the body, `Content-Type` is `image/png` or `image/jpeg` (PC client)
+#[post("/alice/pc/commands/<id>/artifact", data = "<data>")]
+pub async fn alice_pc_upload_artifact(
+    auth: PcClientAuth,
+    pool: &State<DbPool>,
+    id: i64,
+    content_type: Option<&ContentType>,
+    data: Data<'_>,
+) -> (Status, Json<serde_json::Value>) {
+    let fail = |status: Status, error: &str| (status, Json(serde_json::json!({"success": false, "error": error})));
+
+    // Tell the client to keep the file itself rather than retrying the command
+    if let DbPool::Postgres(_) = pool.inner() {
+        return (
+            Status::NotImplemented,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "File storage not available (PostgreSQL mode)",
+                "code": ARTIFACTS_UNAVAILABLE,
+            })),
+        );
+    }
+
+    let (extension, mime_type) = match content_type {
+        Some(ct) if *ct == ContentType::PNG => ("png", "image/png"),
+        Some(ct) if *ct == ContentType::JPEG => ("jpg", "image/jpeg"),
+        _ => return fail(Status::UnsupportedMediaType, "Expected image/png or image/jpeg"),
+    };
+    let command = match CommandQueueService::get_command(pool.inner(), id).await {
+        Ok(Some(command)) => command,
+        Ok(None) => return fail(Status::NotFound, "Command not found"),
+        Err(_) => return fail(Status::InternalServerError, "Failed to load command"),
+    };
+    if command.status != "processing" || command.claimed_by.as_deref() != Some(auth.client_id.as_str()) {
+        return fail(Status::Conflict, "Command is not being executed by this client");
+    }
+
+    let body = match data.open(artifact_max_bytes().bytes()).into_bytes().await {
+        Ok(body) if body.is_complete() => body.into_inner(),
+        Ok(_) => return fail(Status::PayloadTooLarge, "Artifact exceeds ALICE_PC_ARTIFACT_MAX_MB"),
+        Err(_) => return fail(Status::BadRequest, "Failed to read upload"),
+    };
+    if body.is_empty() {
+        return fail(Status::BadRequest, "Empty upload");
+    }
+
+    let name = format!(
+        "{}-{}-{}.{}",
+        command.command_type.to_lowercase(),
+        id,
+        chrono::Local::now().format("%Y%m%d_%H%M%S"),
+        extension
+    );
+    let file = match CommandQueueService::store_artifact(pool.inner(), id, &auth.client_id, &name, &body, mime_type).await {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("Failed to store artifact of command {} from {}: {}", id, auth.client_name, e);
+            return fail(Status::InternalServerError, &e);
         }
-    } else {
-        match CommandQueueService::mark_as_failed(
-            pool.inner(),
-            request.command_id,
-            request.error.clone().unwrap_or_else(|| "Unknown error".to_string()),
-        ).await {
-            Ok(_) => {
-                println!("❌ PC client {} failed command {}: {}",
-                    auth.client_name,
-                    request.command_id,
-                    request.error.as_deref().unwrap_or("Unknown"));
-                Ok(Json(serde_json::json!({"success": true})))
-            }
-            Err(_) => Err(Status::InternalServerError),
+    };
+
+    info!("PC client {} uploaded {} for command {}", auth.client_name, file.name, id);
+    let notification = NewNotification::new(
+        category::ALICE,
+        NotificationPriority::Low,
+        "Скриншот ПК",
+        format!("{}: {} ({} КБ)", auth.client_name, file.name, file.size / 1024),
+    )
+    .link("/admin/alice")
+    .data(serde_json::json!({
+        "command_id": id,
+        "file_id": file.id,
+        "artifact_url": format!("/api/alice/pc/queue/{}/artifact", id),
+    }));
+    if let Err(e) = NotificationService::notify(pool.inner(), &notification).await {
+        warn!("Failed to post artifact notification: {}", e);
+    }
+
+    (Status::Ok, Json(serde_json::json!({"success": true, "file_id": file.id})))
+}
+
+/// Store a command result reported over HTTP or the WebSocket
+async fn record_command_report(pool: &DbPool, auth: &PcClientAuth, report: CommandReport) -> Result<(), sqlx::Error> {
+    match report.result {
+        CommandResult::Completed { message } => {
+            CommandQueueService::mark_as_completed(pool, report.command_id, &auth.client_id, Some(message)).await?;
+        }
+        CommandResult::Failed { error: e } => {
+            error!("PC client {} failed command {}: {}", auth.client_name, report.command_id, e);
+            CommandQueueService::retry_failed_command(pool, report.command_id, &auth.client_id, e).await?;
+        }
+        CommandResult::Unsupported { reason } => {
+            warn!("PC client {} does not support command {}: {}", auth.client_name, report.command_id, reason);
+            CommandQueueService::mark_as_failed(pool, report.command_id, &auth.client_id, format!("Unsupported by client: {}", reason)).await?;
+        }
+        CommandResult::Rejected { reason } => {
+            warn!("PC client {} rejected command {}: {}", auth.client_name, report.command_id, reason);
+            CommandQueueService::mark_as_failed(pool, report.command_id, &auth.client_id, format!("Rejected by client: {}", reason)).await?;
         }
     }
+
+    info!("PC client {} reported command {}", auth.client_name, report.command_id);
+    Ok(())
 }
 
-/// Heartbeat endpoint (PC client)
-#[post("/alice/pc/heartbeat")]
+/// Heartbeat endpoint (PC client); the optional body is the capability handshake
+#[post("/alice/pc/heartbeat", data = "<handshake>")]
 pub async fn alice_pc_heartbeat(
     auth: PcClientAuth,
+    pool: &State<DbPool>,
     alice_state: &State<AliceState>,
-) -> Json<serde_json::Value> {
+    rules: &State<RuleEngine>,
+    handshake: Option<Json<Handshake>>,
+) -> Json<HeartbeatResponse> {
+    Json(accept_handshake(pool.inner(), &auth, alice_state.inner(), rules.inner(), handshake.as_deref()).await)
+}
+
+/// Mark the PC online and store its handshake, shared by the heartbeat and the WebSocket
+async fn accept_handshake(
+    pool: &DbPool,
+    auth: &PcClientAuth,
+    alice_state: &AliceState,
+    rules: &RuleEngine,
+    handshake: Option<&Handshake>,
+) -> HeartbeatResponse {
     // Update PC status in Alice state
     alice_state.set_pc_status("pc-control".to_string(), true);
+    rules.client_seen(&auth.client_id, &auth.client_name);
 
-    Json(serde_json::json!({
-        "status": "ok",
-        "client_id": auth.client_id,
-        "server_time": chrono::Utc::now().to_rfc3339()
-    }))
+    let mut status = "ok";
+    if let Some(handshake) = handshake {
+        if let Err(e) = CommandQueueService::update_client_handshake(pool, &auth.api_key, handshake).await {
+            error!("Failed to store handshake of PC client {}: {}", auth.client_name, e);
+        }
+        if handshake.protocol_version != PROTOCOL_VERSION {
+            warn!(
+                "PC client {} speaks protocol v{}, server expects v{}",
+                auth.client_name, handshake.protocol_version, PROTOCOL_VERSION
+            );
+            status = "unsupported_protocol";
+        }
+    }
+
+    HeartbeatResponse {
+        status: status.to_string(),
+        client_id: auth.client_id.clone(),
+        server_time: chrono::Utc::now().to_rfc3339(),
+        protocol_version: PROTOCOL_VERSION,
+    }
+}
+
+/// Seconds between pings on the PC client socket; unacked commands are resent after this long
+const PC_SOCKET_TICK_SECS: u64 = 30;
+
+/// Persistent channel (PC client): opens with a `hello` handshake, then commands are
+/// pushed as soon as they are queued and the client answers with `ack` and `result` frames
+#[get("/alice/pc/ws")]
+pub fn alice_pc_socket(
+    ws: WebSocket,
+    auth: PcClientAuth,
+    pool: &State<DbPool>,
+    alice_state: &State<AliceState>,
+    rules: &State<RuleEngine>,
+) -> Channel<'static> {
+    let pool = pool.inner().clone();
+    let alice_state = alice_state.inner().clone();
+    let rules = rules.inner().clone();
+
+    ws.channel(move |mut stream| {
+        Box::pin(async move {
+            let handshake = match stream.next().await {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(ClientMessage::Hello(handshake)) => handshake,
+                    _ => {
+                        stream.close(None).await.ok();
+                        return Ok(());
+                    }
+                },
+                _ => return Ok(()),
+            };
+
+            let welcome = accept_handshake(&pool, &auth, &alice_state, &rules, Some(&handshake)).await;
+            let accepted = welcome.status == "ok";
+            stream.send(server_message(&ServerMessage::Welcome(welcome))).await?;
+            if !accepted {
+                stream.close(None).await.ok();
+                return Ok(());
+            }
+
+            let capabilities: Vec<String> = handshake
+                .capabilities
+                .iter()
+                .filter(|c| **c != Capability::Unknown)
+                .map(|c| c.as_str().to_string())
+                .collect();
+            info!("PC client {} connected over WebSocket", auth.client_name);
+
+            let mut queue = CommandQueueService::subscribe_queue();
+            let mut ticker = rocket::tokio::time::interval(Duration::from_secs(PC_SOCKET_TICK_SECS));
+            // Claimed and pushed but not acked yet, with the time they were sent
+            let mut in_flight: HashMap<i64, Instant> = HashMap::new();
+            let mut deliver = true;
+
+            let result = loop {
+                if deliver {
+                    if let Err(e) = push_pending_commands(&mut stream, &pool, &auth, &capabilities, &mut in_flight).await {
+                        break Err(e);
+                    }
+                    deliver = false;
+                }
+
+                select! {
+                    changed = queue.changed() => {
+                        if changed.is_err() {
+                            break Ok(());
+                        }
+                        deliver = true;
+                    }
+                    _ = ticker.tick() => {
+                        if let Err(e) = stream.send(Message::Ping(Vec::new())).await {
+                            break Err(e);
+                        }
+                        deliver = true;
+                    }
+                    message = stream.next() => match message {
+                        Some(Ok(Message::Text(text))) => {
+                            let _ = CommandQueueService::update_client_heartbeat(&pool, &auth.api_key, None).await;
+                            match serde_json::from_str::<ClientMessage>(&text) {
+                                Ok(ClientMessage::Ack { command_id }) => {
+                                    in_flight.remove(&command_id);
+                                }
+                                Ok(ClientMessage::Result(report)) => {
+                                    in_flight.remove(&report.command_id);
+                                    if let Err(e) = record_command_report(&pool, &auth, report).await {
+                                        error!("Failed to store PC command result: {}", e);
+                                    }
+                                }
+                                Ok(ClientMessage::Heartbeat) | Ok(ClientMessage::Hello(_)) => {}
+                                Err(e) => warn!("Ignoring malformed frame from PC client {}: {}", auth.client_name, e),
+                            }
+                        }
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break Ok(()),
+                        _ => {}
+                    },
+                }
+            };
+
+            // Unacked commands go back to the queue for the next client to claim
+            let unacked: Vec<i64> = in_flight.into_keys().collect();
+            if let Err(e) = CommandQueueService::release_commands(&pool, &unacked, &auth.client_id).await {
+                error!("Failed to release unacked PC commands: {}", e);
+            }
+
+            info!("PC client {} disconnected from WebSocket", auth.client_name);
+            result
+        })
+    })
+}
+
+/// Return commands whose ack is overdue to the queue, then claim and push new ones the client supports
+async fn push_pending_commands(
+    stream: &mut DuplexStream,
+    pool: &DbPool,
+    auth: &PcClientAuth,
+    capabilities: &[String],
+    in_flight: &mut HashMap<i64, Instant>,
+) -> Result<(), rocket_ws::result::Error> {
+    let ack_timeout = Duration::from_secs(PC_SOCKET_TICK_SECS);
+    let overdue: Vec<i64> = in_flight
+        .iter()
+        .filter(|(_, sent)| sent.elapsed() >= ack_timeout)
+        .map(|(id, _)| *id)
+        .collect();
+    if !overdue.is_empty() {
+        in_flight.retain(|id, _| !overdue.contains(id));
+        if let Err(e) = CommandQueueService::release_commands(pool, &overdue, &auth.client_id).await {
+            error!("Failed to release unacked PC commands: {}", e);
+        }
+    }
+
+    CommandQueueService::sweep_queue(pool).await;
+
+    let commands = match CommandQueueService::claim_commands(pool, &auth.client_id, auth.group.as_deref(), capabilities, 10).await {
+        Ok(commands) => commands,
+        Err(e) => {
+            error!("Failed to claim pending PC commands: {}", e);
+            return Ok(());
+        }
+    };
+
+    for c in commands {
+        let (Some(payload), Some(signature)) = (c.payload, c.signature) else {
+            warn!("Dropping unsigned command {}", c.id);
+            let _ = CommandQueueService::mark_as_failed(pool, c.id, &auth.client_id, "Command is not signed".to_string()).await;
+            continue;
+        };
+        let envelope = CommandEnvelope {
+            id: c.id,
+            version: c.protocol_version as u32,
+            priority: c.priority,
+            payload,
+            signature,
+        };
+        // Tracked before sending so a failed send still releases the claim on disconnect
+        in_flight.insert(c.id, Instant::now());
+        stream.send(server_message(&ServerMessage::Command(envelope))).await?;
+    }
+    Ok(())
+}
+
+fn server_message(message: &ServerMessage) -> Message {
+    Message::Text(serde_json::to_string(message).unwrap_or_default())
 }
@@ -5,6 +5,7 @@ use crate::models::ApiResponse;
 use rocket::serde::json::Json;
 use rocket::{get, post, State};
 use serde::{Deserialize, Serialize};
+use tracing::{error, info};
 
 use chrono::Datelike;
 use sqlx::SqlitePool;
@@ -25,11 +26,11 @@ pub async fn get_upcoming_anime(
     .await
     {
         Ok(a) => {
-            println!("📺 Found {} upcoming anime", a.len());
+            info!("Found {} upcoming anime", a.len());
             a
         },
         Err(e) => {
-            eprintln!("❌ Database error getting upcoming anime: {}", e);
+            error!("Database error getting upcoming anime: {}", e);
             return Json(ApiResponse::error(format!("Database error: {}", e)));
         }
     };
@@ -58,11 +59,11 @@ pub async fn get_watched_anime(
     .await
     {
         Ok(a) => {
-            println!("📺 Found {} watched anime", a.len());
+            info!("Found {} watched anime", a.len());
             a
         },
         Err(e) => {
-            eprintln!("❌ Database error getting watched anime: {}", e);
+            error!("Database error getting watched anime: {}", e);
             return Json(ApiResponse::error(format!("Database error: {}", e)));
         }
     };
@@ -149,7 +150,7 @@ pub async fn sync_anime_data(
 }
 
 async fn run_sync_task(pool: SqlitePool, progress_id: i32) {
-    println!("🎬 Starting anime sync task (progress_id: {})", progress_id);
+    info!("Starting anime sync task (progress_id: {})", progress_id);
 
     let sheets_client = GoogleSheetsClient::new(SHEET_ID.to_string());
     let shikimori_client = ShikimoriClient::new();
@@ -161,7 +162,7 @@ async fn run_sync_task(pool: SqlitePool, progress_id: i32) {
     let current_year = chrono::Utc::now().year() as i64;
     let years: Vec<i64> = (2020..=current_year + 1).collect();
 
-    println!("📅 Will sync years: {:?}", years);
+    info!("Will sync years: {:?}", years);
 
     // Update total count
     let _: Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> = sqlx::query::<sqlx::Sqlite>(
@@ -175,7 +176,7 @@ async fn run_sync_task(pool: SqlitePool, progress_id: i32) {
 
     for (idx, &year) in years.iter().enumerate() {
         // Update progress
-        println!("📊 Processing year {} ({}/{})", year, idx + 1, years.len());
+        info!("Processing year {} ({}/{})", year, idx + 1, years.len());
 
         let _: Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> = sqlx::query::<sqlx::Sqlite>(
             "UPDATE anime_sync_progress SET current = $1, message = $2 WHERE id = $3"
@@ -188,11 +189,11 @@ async fn run_sync_task(pool: SqlitePool, progress_id: i32) {
 
         let rows = match sheets_client.fetch_sheet_data(year).await {
             Ok(r) => {
-                println!("✅ Fetched {} rows for year {}", r.len(), year);
+                info!("Fetched {} rows for year {}", r.len(), year);
                 r
             },
             Err(e) => {
-                eprintln!("❌ Error fetching sheet for year {}: {}", year, e);
+                error!("Error fetching sheet for year {}: {}", year, e);
                 total_errors += 1;
                 continue;
             }
@@ -331,7 +332,7 @@ async fn run_sync_task(pool: SqlitePool, progress_id: i32) {
 
     // Mark as completed
     let final_message = format!("✅ Завершено! Синхронизировано: {}, ошибок: {}", total_synced, total_errors);
-    println!("{}", final_message);
+    info!("{}", final_message);
 
     let _: Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> = sqlx::query(
         "UPDATE anime_sync_progress SET status = 'completed', current = total, message = $1, finished_at = datetime('now') WHERE id = $2"
@@ -341,5 +342,5 @@ async fn run_sync_task(pool: SqlitePool, progress_id: i32) {
     .execute(&pool)
     .await;
 
-    println!("🎬 Anime sync task completed (progress_id: {})", progress_id);
+    info!("Anime sync task completed (progress_id: {})", progress_id);
 }
@@ -0,0 +1,38 @@
+use crate::audit::{AuditFilter, AuditPage, AuditService};
+use crate::db::DbPool;
+use crate::guards::AuthGuard;
+use crate::models::ApiResponse;
+use rocket::serde::json::Json;
+use rocket::{get, State};
+
+// Search the audit log of admin writes
+#[allow(clippy::too_many_arguments)]
+#[get("/audit?<module>&<action>&<actor>&<target_id>&<from>&<to>&<limit>&<offset>")]
+pub async fn list_audit_log(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    module: Option<String>,
+    action: Option<String>,
+    actor: Option<String>,
+    target_id: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Json<ApiResponse<AuditPage>> {
+    let filter = AuditFilter {
+        module,
+        action,
+        actor,
+        target_id,
+        from,
+        to,
+        limit,
+        offset,
+    };
+
+    match AuditService::query(pool.inner(), &filter).await {
+        Ok(page) => Json(ApiResponse::success(page)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
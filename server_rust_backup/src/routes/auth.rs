@@ -1,9 +1,16 @@
+use crate::auth::api_tokens::ApiTokenService;
 use crate::auth::AuthService;
 use crate::db::DbPool;
-use crate::models::{ApiResponse, RequestOtpRequest, RequestOtpResponse, VerifyOtpRequest, VerifyOtpResponse};
+use crate::guards::{AuthGuard, ClientInfo};
+use crate::notifications::{category, NewNotification, NotificationPriority, NotificationService};
+use crate::models::{
+    ApiResponse, ApiTokenInfo, CreateApiTokenRequest, CreateApiTokenResponse, RequestOtpRequest,
+    RequestOtpResponse, RevokeSessionsResponse, SessionInfo, VerifyOtpRequest, VerifyOtpResponse,
+};
 use crate::telegram::TelegramBot;
 use rocket::serde::json::Json;
-use rocket::{post, State};
+use rocket::{delete, get, post, State};
+use tracing::error;
 
 #[post("/auth/request-otp", data = "<_request>")]
 pub async fn request_otp(
@@ -47,17 +54,50 @@ pub async fn request_otp(
 #[post("/auth/verify-otp", data = "<request>")]
 pub async fn verify_otp(
     request: Json<VerifyOtpRequest>,
+    client: ClientInfo,
     pool: &State<DbPool>,
+    telegram_bot: &State<TelegramBot>,
     admin_telegram_id: &State<i64>,
 ) -> Json<ApiResponse<VerifyOtpResponse>> {
     let telegram_id = **admin_telegram_id;
+    let client = client.0;
 
-    match AuthService::verify_otp(pool.inner(), telegram_id, &request.code).await {
-        Ok(Some(token)) => Json(ApiResponse::success(VerifyOtpResponse {
-            success: true,
-            token: Some(token),
-            message: "Authentication successful".to_string(),
-        })),
+    match AuthService::verify_otp(pool.inner(), telegram_id, &request.code, &client).await {
+        Ok(Some(session)) => {
+            // Alert the admin about a login from an IP never seen before
+            if session.is_new_ip {
+                let message = format!(
+                    "⚠️ <b>Вход с нового IP</b>\n\nIP: <code>{}</code>\nУстройство: {}\nСессия: #{}\n\nЕсли это были не вы, завершите сессию в админке.",
+                    client.ip_address.as_deref().unwrap_or("unknown"),
+                    AuthService::describe_device(client.user_agent.as_deref()),
+                    session.session_id
+                );
+                if let Err(e) = telegram_bot.send_message(telegram_id, &message).await {
+                    error!("Failed to send new session alert: {}", e);
+                }
+                NotificationService::post(
+                    pool.inner(),
+                    NewNotification::new(
+                        category::SECURITY,
+                        NotificationPriority::High,
+                        "⚠️ Вход с нового IP",
+                        format!(
+                            "{} — {}",
+                            client.ip_address.as_deref().unwrap_or("unknown"),
+                            AuthService::describe_device(client.user_agent.as_deref())
+                        ),
+                    )
+                    .data(serde_json::json!({ "session_id": session.session_id })),
+                )
+                .await;
+            }
+
+            Json(ApiResponse::success(VerifyOtpResponse {
+                success: true,
+                token: Some(session.token),
+                message: "Authentication successful".to_string(),
+            }))
+        }
         Ok(None) => Json(ApiResponse::success(VerifyOtpResponse {
             success: false,
             token: None,
@@ -66,3 +106,139 @@ pub async fn verify_otp(
         Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
     }
 }
+
+// List active sessions of the current admin
+#[get("/auth/sessions")]
+pub async fn list_sessions(
+    auth: AuthGuard,
+    pool: &State<DbPool>,
+) -> Json<ApiResponse<Vec<SessionInfo>>> {
+    match AuthService::list_sessions(pool.inner(), auth.user.id).await {
+        Ok(sessions) => Json(ApiResponse::success(
+            sessions
+                .into_iter()
+                .map(|s| SessionInfo {
+                    id: s.id,
+                    device: AuthService::describe_device(s.user_agent.as_deref()),
+                    ip_address: s.ip_address,
+                    user_agent: s.user_agent,
+                    created_at: s.created_at,
+                    last_seen_at: s.last_seen_at,
+                    expires_at: s.expires_at,
+                    absolute_expires_at: s.absolute_expires_at,
+                    current: Some(s.id) == auth.session_id,
+                })
+                .collect(),
+        )),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+// Revoke a single session (revoking the current one logs out)
+#[delete("/auth/sessions/<id>")]
+pub async fn revoke_session(
+    auth: AuthGuard,
+    pool: &State<DbPool>,
+    id: i32,
+) -> Json<ApiResponse<RevokeSessionsResponse>> {
+    match AuthService::revoke_session(pool.inner(), auth.user.id, id).await {
+        Ok(true) => Json(ApiResponse::success(RevokeSessionsResponse { revoked: 1 })),
+        Ok(false) => Json(ApiResponse::error("Session not found".to_string())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+// Revoke all sessions except the current one
+#[delete("/auth/sessions?<include_current>")]
+pub async fn revoke_all_sessions(
+    auth: AuthGuard,
+    pool: &State<DbPool>,
+    include_current: Option<bool>,
+) -> Json<ApiResponse<RevokeSessionsResponse>> {
+    let keep = if include_current.unwrap_or(false) {
+        None
+    } else {
+        auth.session_id
+    };
+
+    match AuthService::revoke_other_sessions(pool.inner(), auth.user.id, keep).await {
+        Ok(revoked) => Json(ApiResponse::success(RevokeSessionsResponse { revoked })),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+// Create a personal access token (the raw token is returned only once)
+#[post("/auth/tokens", data = "<request>")]
+pub async fn create_api_token(
+    auth: AuthGuard,
+    pool: &State<DbPool>,
+    request: Json<CreateApiTokenRequest>,
+) -> Json<ApiResponse<CreateApiTokenResponse>> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Json(ApiResponse::error("Token name is required".to_string()));
+    }
+
+    if request.scopes.is_empty() {
+        return Json(ApiResponse::error("At least one scope is required".to_string()));
+    }
+
+    for scope in &request.scopes {
+        if let Err(e) = ApiTokenService::validate_scope(scope) {
+            return Json(ApiResponse::error(e));
+        }
+    }
+
+    for entry in &request.allowed_ips {
+        if let Err(e) = ApiTokenService::validate_ip_entry(entry) {
+            return Json(ApiResponse::error(e));
+        }
+    }
+
+    if matches!(request.expires_in_days, Some(days) if days <= 0) {
+        return Json(ApiResponse::error("expires_in_days must be positive".to_string()));
+    }
+
+    match ApiTokenService::create_token(
+        pool.inner(),
+        auth.user.id,
+        name,
+        &request.scopes,
+        &request.allowed_ips,
+        request.expires_in_days,
+    )
+    .await
+    {
+        Ok((token, record)) => Json(ApiResponse::success(CreateApiTokenResponse {
+            token,
+            api_token: record.into(),
+        })),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+// List personal access tokens
+#[get("/auth/tokens")]
+pub async fn list_api_tokens(
+    auth: AuthGuard,
+    pool: &State<DbPool>,
+) -> Json<ApiResponse<Vec<ApiTokenInfo>>> {
+    match ApiTokenService::list_tokens(pool.inner(), auth.user.id).await {
+        Ok(tokens) => Json(ApiResponse::success(tokens.into_iter().map(Into::into).collect())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+// Revoke a personal access token
+#[delete("/auth/tokens/<id>")]
+pub async fn revoke_api_token(
+    auth: AuthGuard,
+    pool: &State<DbPool>,
+    id: i32,
+) -> Json<ApiResponse<RevokeSessionsResponse>> {
+    match ApiTokenService::revoke_token(pool.inner(), auth.user.id, id).await {
+        Ok(true) => Json(ApiResponse::success(RevokeSessionsResponse { revoked: 1 })),
+        Ok(false) => Json(ApiResponse::error("Token not found".to_string())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
@@ -0,0 +1,64 @@
+use crate::backup::{BackupInfo, BackupService, RestoreReport, RestoreRequest};
+use crate::guards::AuthGuard;
+use crate::models::ApiResponse;
+use rocket::http::Header;
+use rocket::serde::json::Json;
+use rocket::tokio::fs::File;
+use rocket::{delete, get, post, Responder, State};
+
+/// Backup file sent as an attachment
+#[derive(Responder)]
+#[response(content_type = "application/octet-stream")]
+pub struct BackupDownload {
+    file: File,
+    disposition: Header<'static>,
+}
+
+// List backups, newest first
+#[get("/backups")]
+pub async fn list_backups(_auth: AuthGuard, backups: &State<BackupService>) -> Json<ApiResponse<Vec<BackupInfo>>> {
+    Json(ApiResponse::success(backups.list()))
+}
+
+// Take a backup now
+#[post("/backups")]
+pub async fn create_backup(_auth: AuthGuard, backups: &State<BackupService>) -> Json<ApiResponse<BackupInfo>> {
+    match backups.create("manual").await {
+        Ok(backup) => Json(ApiResponse::success(backup)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+// Download a backup file as stored (compressed and encrypted if configured)
+#[get("/backups/<name>/download")]
+pub async fn download_backup(_auth: AuthGuard, backups: &State<BackupService>, name: &str) -> Option<BackupDownload> {
+    let (backup, path) = backups.find(name).ok()?;
+    let file = File::open(&path).await.ok()?;
+    Some(BackupDownload {
+        file,
+        disposition: Header::new("Content-Disposition", format!("attachment; filename=\"{}\"", backup.name)),
+    })
+}
+
+// Restore a backup; without `"dry_run": false` only reports row counts
+#[post("/backups/<name>/restore", data = "<request>")]
+pub async fn restore_backup(
+    _auth: AuthGuard,
+    backups: &State<BackupService>,
+    name: &str,
+    request: Json<RestoreRequest>,
+) -> Json<ApiResponse<RestoreReport>> {
+    match backups.restore(name, request.dry_run.unwrap_or(true)).await {
+        Ok(report) => Json(ApiResponse::success(report)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+// Delete a backup
+#[delete("/backups/<name>")]
+pub async fn delete_backup(_auth: AuthGuard, backups: &State<BackupService>, name: &str) -> Json<ApiResponse<()>> {
+    match backups.delete(name).await {
+        Ok(()) => Json(ApiResponse::success(())),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
@@ -1,31 +1,30 @@
-use crate::guards::AuthGuard;
+use crate::console::{
+    pty_size, CommandCatalog, CommandResult, ConsoleJob, ConsoleRun, ConsoleService, ExecuteCommandRequest,
+    JobControl, JobEvent, JobEventKind, JobInfo, JobInputRequest, JobResizeRequest, NewConsoleRun,
+    RawModeConfirmRequest, RawModeStatus, StartJobRequest, StartJobResponse,
+};
+use crate::db::DbPool;
+use crate::guards::{AuthGuard, ClientInfo};
+use crate::logs::{LogFilter, LogPage, LogQuery, LogService};
+use crate::metrics::{AlertRule, AlertRuleRequest, AlertStatus, MetricsHistory, MetricsService, MetricsSnapshot};
+use crate::models::ApiResponse;
+use crate::telegram::TelegramBot;
+use rocket::futures::{SinkExt, StreamExt};
 use rocket::http::Status;
+use rocket::response::stream::{Event, EventStream};
 use rocket::serde::json::Json;
-use rocket::{get, post};
-use serde::{Deserialize, Serialize};
-use std::process::Stdio;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{delete, get, post, put, Shutdown, State};
+use rocket_ws::{Channel, Message, WebSocket};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
 use tokio::process::Command;
-use tokio::io::AsyncReadExt;
-use std::time::Duration;
+use tracing::error;
 
 // === Models ===
 
-#[derive(Debug, Deserialize)]
-pub struct ExecuteCommandRequest {
-    pub command: String,
-    pub timeout_secs: Option<u64>,
-}
-
-#[derive(Debug, Serialize)]
-pub struct CommandResult {
-    pub stdout: String,
-    pub stderr: String,
-    pub exit_code: Option<i32>,
-    pub execution_time_ms: u128,
-    pub success: bool,
-    pub timed_out: bool,
-}
-
 #[derive(Debug, Serialize)]
 pub struct SystemInfo {
     pub hostname: String,
@@ -71,127 +70,392 @@ pub struct ServerLogs {
 
 // === Endpoints ===
 
-/// Execute a shell command (with safety restrictions)
+/// Run an allowlisted command template, or a raw shell line after step-up confirmation
 #[post("/console/execute", data = "<request>")]
 pub async fn execute_command(
-    _auth: AuthGuard,
+    auth: AuthGuard,
+    client: ClientInfo,
     request: Json<ExecuteCommandRequest>,
+    console: &State<ConsoleService>,
+    pool: &State<DbPool>,
 ) -> Result<Json<CommandResult>, Status> {
-    let cmd = request.command.trim();
-
-    // Safety checks - block dangerous commands
-    let blocked_commands = [
-        "rm -rf /",
-        "rm -rf /*",
-        "mkfs",
-        ":(){:|:&};:",
-        "dd if=/dev/zero",
-        "chmod -R 777 /",
-        "chown -R",
-        "> /dev/sda",
-        "shutdown",
-        "reboot",
-        "halt",
-        "poweroff",
-        "init 0",
-        "init 6",
-    ];
-
-    let cmd_lower = cmd.to_lowercase();
-    for blocked in blocked_commands {
-        if cmd_lower.contains(&blocked.to_lowercase()) {
-            return Ok(Json(CommandResult {
-                stdout: String::new(),
-                stderr: format!("Command blocked for safety: {}", blocked),
-                exit_code: None,
-                execution_time_ms: 0,
-                success: false,
-                timed_out: false,
-            }));
+    let command = request.command.trim().to_string();
+    let prepared = prepare_command(console, &auth, &command, &request.args, request.raw);
+    let mut run = new_run(&auth, client, &command, request.raw);
+
+    let result = match prepared {
+        Ok((argv, template_timeout)) => {
+            let result = console
+                .run(&argv, request.timeout_secs.or(template_timeout))
+                .await;
+            run.argv = Some(argv);
+            run.status = if result.timed_out {
+                "timed_out"
+            } else if result.success {
+                "completed"
+            } else {
+                "failed"
+            };
+            run.exit_code = result.exit_code;
+            run.duration_ms = result.execution_time_ms as i64;
+            run.output_bytes = result.output_bytes as i64;
+            if result.exit_code.is_none() && !result.timed_out {
+                run.error = Some(result.stderr.clone());
+            }
+            result
+        }
+        Err(e) => {
+            run.error = Some(e.clone());
+            CommandResult::rejected(e)
         }
+    };
+
+    if let Err(e) = ConsoleService::record(pool.inner(), run).await {
+        error!("Failed to record console run: {}", e);
     }
 
-    // Block commands that try to access sensitive files
-    let sensitive_patterns = ["/etc/shadow", "/etc/passwd", "~/.ssh", ".env"];
-    for pattern in sensitive_patterns {
-        if cmd_lower.contains(pattern) {
-            return Ok(Json(CommandResult {
-                stdout: String::new(),
-                stderr: format!("Access to sensitive file blocked: {}", pattern),
-                exit_code: None,
-                execution_time_ms: 0,
-                success: false,
-                timed_out: false,
-            }));
-        }
+    Ok(Json(result))
+}
+
+// Template argv, or `sh -c` for raw mode; returns argv and the template's timeout
+fn prepare_command(
+    console: &ConsoleService,
+    auth: &AuthGuard,
+    command: &str,
+    args: &HashMap<String, serde_json::Value>,
+    raw: bool,
+) -> Result<(Vec<String>, Option<u64>), String> {
+    if raw {
+        check_raw_mode(console, auth)?;
+        Ok((vec!["sh".to_string(), "-c".to_string(), command.to_string()], None))
+    } else {
+        console.prepare(command, args)
     }
+}
 
-    let timeout = Duration::from_secs(request.timeout_secs.unwrap_or(30).min(300));
-    let start = std::time::Instant::now();
+// History row for a run; status and results are filled in once it finishes
+fn new_run(auth: &AuthGuard, client: ClientInfo, command: &str, raw: bool) -> NewConsoleRun {
+    NewConsoleRun {
+        actor: auth.actor_label(),
+        user_id: Some(auth.user.id),
+        mode: if raw { "raw" } else { "template" },
+        command: command.to_string(),
+        argv: None,
+        status: "rejected",
+        exit_code: None,
+        duration_ms: 0,
+        output_bytes: 0,
+        error: None,
+        ip_address: client.0.ip_address,
+    }
+}
 
-    // Execute command
-    let result = tokio::time::timeout(timeout, async {
-        let mut child = Command::new("sh")
-            .arg("-c")
-            .arg(cmd)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Failed to spawn: {}", e))?;
+// Raw mode must be enabled, used from an OTP session and confirmed recently
+fn check_raw_mode(console: &ConsoleService, auth: &AuthGuard) -> Result<(), String> {
+    if !console.raw_mode_enabled() {
+        return Err("Raw mode is disabled (CONSOLE_RAW_MODE)".to_string());
+    }
+    let session_id = auth
+        .session_id
+        .ok_or("Raw mode is not available for API tokens")?;
+    console
+        .raw_grant_remaining(session_id)
+        .map(|_| ())
+        .ok_or_else(|| "Raw mode requires confirmation: POST /api/console/raw/challenge".to_string())
+}
 
-        let stdout = child.stdout.take();
-        let stderr = child.stderr.take();
+/// Start a command in the background and stream its output (`pty: true` for an interactive terminal)
+#[post("/console/jobs", data = "<request>")]
+pub async fn start_job(
+    auth: AuthGuard,
+    client: ClientInfo,
+    request: Json<StartJobRequest>,
+    console: &State<ConsoleService>,
+    pool: &State<DbPool>,
+) -> Json<ApiResponse<StartJobResponse>> {
+    let command = request.command.trim().to_string();
+    let mut run = new_run(&auth, client, &command, request.raw);
+    let pty = request.pty.then(|| pty_size(request.rows, request.cols));
+
+    let started = prepare_command(console, &auth, &command, &request.args, request.raw).and_then(
+        |(argv, template_timeout)| {
+            console.start_job(
+                pool.inner().clone(),
+                run.clone(),
+                argv,
+                request.timeout_secs.or(template_timeout),
+                pty,
+            )
+        },
+    );
+
+    match started {
+        Ok((job, ticket)) => Json(ApiResponse::success(StartJobResponse {
+            job: job.info(),
+            ticket,
+        })),
+        Err(e) => {
+            run.error = Some(e.clone());
+            if let Err(e) = ConsoleService::record(pool.inner(), run).await {
+                error!("Failed to record console run: {}", e);
+            }
+            Json(ApiResponse::error(e))
+        }
+    }
+}
 
-        let mut stdout_str = String::new();
-        let mut stderr_str = String::new();
+/// Running and recently finished jobs
+#[get("/console/jobs")]
+pub async fn list_jobs(
+    _auth: AuthGuard,
+    console: &State<ConsoleService>,
+) -> Json<ApiResponse<Vec<JobInfo>>> {
+    Json(ApiResponse::success(console.list_jobs()))
+}
+
+// Stream endpoints accept either the Authorization header or the job's ticket
+fn authorized_job(
+    console: &ConsoleService,
+    auth: Option<AuthGuard>,
+    id: &str,
+    ticket: Option<&str>,
+) -> Result<Arc<ConsoleJob>, Status> {
+    let job = console.get_job(id).ok_or(Status::NotFound)?;
+    if auth.is_some() || ticket.is_some_and(|t| job.check_ticket(t)) {
+        Ok(job)
+    } else {
+        Err(Status::Unauthorized)
+    }
+}
 
-        if let Some(mut out) = stdout {
-            out.read_to_string(&mut stdout_str).await.ok();
+/// Job output as Server-Sent Events: buffered events first, then live ones until exit
+#[get("/console/jobs/<id>/stream?<ticket>")]
+pub fn stream_job(
+    auth: Option<AuthGuard>,
+    id: &str,
+    ticket: Option<&str>,
+    console: &State<ConsoleService>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    let job = authorized_job(console, auth, id, ticket)?;
+    let (replay, mut rx) = job.subscribe();
+
+    Ok(EventStream! {
+        let mut finished = false;
+        for event in replay {
+            finished |= matches!(event.kind, JobEventKind::Exit { .. });
+            yield Event::json(&event).id(event.seq.to_string());
         }
 
-        if let Some(mut err) = stderr {
-            err.read_to_string(&mut stderr_str).await.ok();
+        while !finished {
+            let event = select! {
+                event = rx.recv() => event,
+                _ = &mut shutdown => break,
+            };
+            match event {
+                Ok(event) => {
+                    finished = matches!(event.kind, JobEventKind::Exit { .. });
+                    yield Event::json(&event).id(event.seq.to_string());
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
         }
+    })
+}
 
-        let status = child.wait().await.map_err(|e| format!("Wait error: {}", e))?;
+/// Job output over WebSocket; text frames from the client are `JobControl` messages.
+/// Being a GET, a `console:read` token may watch, but only a session, a `console:write`
+/// token or the job's ticket (issued to whoever started it) may control the job.
+#[get("/console/jobs/<id>/ws?<ticket>")]
+pub fn job_socket(
+    ws: WebSocket,
+    auth: Option<AuthGuard>,
+    id: &str,
+    ticket: Option<&str>,
+    console: &State<ConsoleService>,
+) -> Result<Channel<'static>, Status> {
+    let can_control = auth.as_ref().is_some_and(|auth| auth.allows("console:write"));
+    let job = authorized_job(console, auth, id, ticket)?;
+    let can_control = can_control || ticket.is_some_and(|t| job.check_ticket(t));
+
+    Ok(ws.channel(move |mut stream| {
+        Box::pin(async move {
+            let (replay, mut rx) = job.subscribe();
+            let mut finished = false;
+            for event in replay {
+                finished |= matches!(event.kind, JobEventKind::Exit { .. });
+                stream.send(job_message(&event)).await?;
+            }
 
-        Ok::<_, String>((stdout_str, stderr_str, status.code()))
+            while !finished {
+                select! {
+                    event = rx.recv() => match event {
+                        Ok(event) => {
+                            finished = matches!(event.kind, JobEventKind::Exit { .. });
+                            stream.send(job_message(&event)).await?;
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    },
+                    message = stream.next() => match message {
+                        Some(Ok(Message::Text(text))) if can_control => {
+                            if let Ok(control) = serde_json::from_str::<JobControl>(&text) {
+                                job.send(control);
+                            }
+                        }
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        _ => {}
+                    },
+                }
+            }
+
+            stream.close(None).await.ok();
+            Ok(())
+        })
+    }))
+}
+
+fn job_message(event: &JobEvent) -> Message {
+    Message::Text(serde_json::to_string(event).unwrap_or_default())
+}
+
+/// Write to the job's stdin (or terminal)
+#[post("/console/jobs/<id>/input", data = "<request>")]
+pub async fn job_input(
+    _auth: AuthGuard,
+    id: &str,
+    request: Json<JobInputRequest>,
+    console: &State<ConsoleService>,
+) -> Json<ApiResponse<bool>> {
+    send_control(console, id, JobControl::Input {
+        data: request.into_inner().data,
     })
-    .await;
-
-    let execution_time_ms = start.elapsed().as_millis();
-
-    match result {
-        Ok(Ok((stdout, stderr, exit_code))) => {
-            Ok(Json(CommandResult {
-                stdout: truncate_output(&stdout, 100000),
-                stderr: truncate_output(&stderr, 100000),
-                exit_code,
-                execution_time_ms,
-                success: exit_code == Some(0),
-                timed_out: false,
-            }))
-        }
-        Ok(Err(e)) => {
-            Ok(Json(CommandResult {
-                stdout: String::new(),
-                stderr: e,
-                exit_code: None,
-                execution_time_ms,
-                success: false,
-                timed_out: false,
-            }))
-        }
-        Err(_) => {
-            Ok(Json(CommandResult {
-                stdout: String::new(),
-                stderr: "Command timed out".to_string(),
-                exit_code: None,
-                execution_time_ms,
-                success: false,
-                timed_out: true,
-            }))
-        }
+}
+
+/// Resize the terminal of a PTY job
+#[post("/console/jobs/<id>/resize", data = "<request>")]
+pub async fn job_resize(
+    _auth: AuthGuard,
+    id: &str,
+    request: Json<JobResizeRequest>,
+    console: &State<ConsoleService>,
+) -> Json<ApiResponse<bool>> {
+    send_control(console, id, JobControl::Resize {
+        rows: request.rows,
+        cols: request.cols,
+    })
+}
+
+/// Kill the job's whole process group
+#[post("/console/jobs/<id>/kill")]
+pub async fn kill_job(
+    _auth: AuthGuard,
+    id: &str,
+    console: &State<ConsoleService>,
+) -> Json<ApiResponse<bool>> {
+    send_control(console, id, JobControl::Kill)
+}
+
+fn send_control(console: &ConsoleService, id: &str, control: JobControl) -> Json<ApiResponse<bool>> {
+    match console.get_job(id) {
+        Some(job) if job.send(control) => Json(ApiResponse::success(true)),
+        Some(_) => Json(ApiResponse::error("Job has already finished".to_string())),
+        None => Json(ApiResponse::error("Job not found".to_string())),
+    }
+}
+
+/// Available command templates and limits
+#[get("/console/commands")]
+pub async fn list_commands(
+    _auth: AuthGuard,
+    console: &State<ConsoleService>,
+) -> Json<ApiResponse<CommandCatalog>> {
+    Json(ApiResponse::success(CommandCatalog {
+        commands: console.templates(),
+        limits: console.limits(),
+    }))
+}
+
+/// Send a raw-mode confirmation code to the admin's Telegram
+#[post("/console/raw/challenge")]
+pub async fn raw_mode_challenge(
+    auth: AuthGuard,
+    console: &State<ConsoleService>,
+    bot: &State<TelegramBot>,
+) -> Json<ApiResponse<String>> {
+    if !console.raw_mode_enabled() {
+        return Json(ApiResponse::error("Raw mode is disabled (CONSOLE_RAW_MODE)".to_string()));
+    }
+    let session_id = match auth.session_id {
+        Some(id) => id,
+        None => return Json(ApiResponse::error("Raw mode is not available for API tokens".to_string())),
+    };
+
+    let code = console.start_step_up(session_id);
+    let message = format!(
+        "🖥 <b>Подтверждение raw-режима консоли</b>\n\n<code>{}</code>\n\nКод действителен 5 минут. Если это не вы — завершите все сессии.",
+        code
+    );
+
+    match bot.send_message(auth.user.telegram_id, &message).await {
+        Ok(_) => Json(ApiResponse::success("Код отправлен в Telegram".to_string())),
+        Err(e) => Json(ApiResponse::error(format!("Failed to send code: {}", e))),
+    }
+}
+
+/// Confirm the code and unlock raw mode for the current session
+#[post("/console/raw/confirm", data = "<request>")]
+pub async fn raw_mode_confirm(
+    auth: AuthGuard,
+    request: Json<RawModeConfirmRequest>,
+    console: &State<ConsoleService>,
+) -> Json<ApiResponse<RawModeStatus>> {
+    let session_id = match auth.session_id {
+        Some(id) => id,
+        None => return Json(ApiResponse::error("Raw mode is not available for API tokens".to_string())),
+    };
+
+    match console.confirm_step_up(session_id, &request.otp) {
+        Ok(granted) => Json(ApiResponse::success(RawModeStatus {
+            enabled: true,
+            expires_in_secs: Some(granted.as_secs()),
+        })),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Whether raw mode is unlocked for the current session
+#[get("/console/raw/status")]
+pub async fn raw_mode_status(
+    auth: AuthGuard,
+    console: &State<ConsoleService>,
+) -> Json<ApiResponse<RawModeStatus>> {
+    let remaining = auth
+        .session_id
+        .filter(|_| console.raw_mode_enabled())
+        .and_then(|id| console.raw_grant_remaining(id));
+
+    Json(ApiResponse::success(RawModeStatus {
+        enabled: remaining.is_some(),
+        expires_in_secs: remaining.map(|d| d.as_secs()),
+    }))
+}
+
+/// History of console runs, newest first
+#[get("/console/runs?<limit>&<offset>")]
+pub async fn list_runs(
+    _auth: AuthGuard,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    pool: &State<DbPool>,
+) -> Json<ApiResponse<Vec<ConsoleRun>>> {
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    let offset = offset.unwrap_or(0).max(0);
+
+    match ConsoleService::list_runs(pool.inner(), limit, offset).await {
+        Ok(runs) => Json(ApiResponse::success(runs)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
     }
 }
 
@@ -332,27 +596,28 @@ pub async fn get_processes(
     Ok(Json(processes))
 }
 
-/// Get recent server logs
+/// Get recent server logs as plain lines
 #[get("/console/logs?<lines>&<service>")]
 pub async fn get_logs(
     _auth: AuthGuard,
     lines: Option<usize>,
     service: Option<&str>,
+    logs: &State<LogService>,
 ) -> Result<Json<ServerLogs>, Status> {
-    let num_lines = lines.unwrap_or(100).min(1000);
+    let query = LogQuery::from_filter(&LogFilter {
+        unit: service.map(|s| s.to_string()),
+        limit: Some(lines.unwrap_or(100).min(1000)),
+        ..Default::default()
+    })
+    .map_err(|_| Status::BadRequest)?;
 
-    let cmd = if let Some(svc) = service {
-        // Validate service name
-        if !svc.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
-            return Err(Status::BadRequest);
-        }
-        format!("journalctl -u {} -n {} --no-pager --output=short-iso 2>/dev/null || tail -n {} /var/log/syslog 2>/dev/null || echo 'No logs available'", svc, num_lines, num_lines)
-    } else {
-        format!("journalctl -n {} --no-pager --output=short-iso 2>/dev/null || tail -n {} /var/log/syslog 2>/dev/null || echo 'No logs available'", num_lines, num_lines)
-    };
+    let page = logs.query(&query).await.map_err(|e| {
+        error!("Failed to read logs: {}", e);
+        Status::InternalServerError
+    })?;
 
-    let output = run_simple_command(&cmd).await;
-    let logs: Vec<String> = output.lines().map(|s| s.to_string()).collect();
+    // Oldest first, like journalctl
+    let logs: Vec<String> = page.records.iter().rev().map(|r| r.to_line()).collect();
 
     Ok(Json(ServerLogs {
         total_lines: logs.len(),
@@ -360,6 +625,190 @@ pub async fn get_logs(
     }))
 }
 
+/// Parsed log records, newest first, with filters and cursor pagination
+#[allow(clippy::too_many_arguments)]
+#[get("/console/logs/entries?<level>&<unit>&<since>&<until>&<regex>&<request_id>&<cursor>&<limit>")]
+pub async fn get_log_entries(
+    _auth: AuthGuard,
+    level: Option<String>,
+    unit: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    regex: Option<String>,
+    request_id: Option<String>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+    logs: &State<LogService>,
+) -> Json<ApiResponse<LogPage>> {
+    let filter = LogFilter {
+        level,
+        unit,
+        since,
+        until,
+        regex,
+        request_id,
+        cursor,
+        limit,
+    };
+
+    let query = match LogQuery::from_filter(&filter) {
+        Ok(query) => query,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+
+    match logs.query(&query).await {
+        Ok(page) => Json(ApiResponse::success(page)),
+        Err(e) => Json(ApiResponse::error(format!("Failed to read logs: {}", e))),
+    }
+}
+
+/// Live tail of new log records as Server-Sent Events
+#[get("/console/logs/stream?<level>&<unit>&<regex>&<request_id>")]
+pub fn stream_logs(
+    _auth: AuthGuard,
+    level: Option<String>,
+    unit: Option<String>,
+    regex: Option<String>,
+    request_id: Option<String>,
+    logs: &State<LogService>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    let query = LogQuery::from_filter(&LogFilter {
+        level,
+        unit,
+        regex,
+        request_id,
+        ..Default::default()
+    })
+    .map_err(|_| Status::BadRequest)?;
+    let mut records = logs.tail(query);
+
+    Ok(EventStream! {
+        loop {
+            let record = select! {
+                record = records.recv() => record,
+                _ = &mut shutdown => break,
+            };
+            match record {
+                Some(record) => yield Event::json(&record),
+                None => break,
+            }
+        }
+    })
+}
+
+/// Metric time series for charts; `metrics` is comma-separated, `from`/`to` are unix seconds
+#[get("/console/metrics?<metrics>&<from>&<to>&<resolution>")]
+pub async fn get_metrics_history(
+    _auth: AuthGuard,
+    metrics: Option<String>,
+    from: Option<i64>,
+    to: Option<i64>,
+    resolution: Option<i64>,
+    service: &State<MetricsService>,
+) -> Json<ApiResponse<MetricsHistory>> {
+    let names: Vec<String> = match metrics {
+        Some(list) => list
+            .split(',')
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .collect(),
+        None => vec![
+            "cpu_percent".to_string(),
+            "memory_percent".to_string(),
+            "disk_percent".to_string(),
+        ],
+    };
+    let to = to.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let from = from.unwrap_or(to - 3600);
+
+    match service.history(&names, from, to, resolution).await {
+        Ok(history) => Json(ApiResponse::success(history)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Latest sampled values and available metric names
+#[get("/console/metrics/current")]
+pub async fn get_metrics_current(
+    _auth: AuthGuard,
+    service: &State<MetricsService>,
+) -> Json<ApiResponse<MetricsSnapshot>> {
+    match service.latest() {
+        Some(snapshot) => Json(ApiResponse::success(snapshot)),
+        None => Json(ApiResponse::error("No samples yet".to_string())),
+    }
+}
+
+/// Alert rules with their current state
+#[get("/console/alerts")]
+pub async fn list_alerts(
+    _auth: AuthGuard,
+    service: &State<MetricsService>,
+) -> Json<ApiResponse<Vec<AlertStatus>>> {
+    match service.alert_statuses().await {
+        Ok(statuses) => Json(ApiResponse::success(statuses)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+#[post("/console/alerts", data = "<request>")]
+pub async fn create_alert(
+    _auth: AuthGuard,
+    request: Json<AlertRuleRequest>,
+    service: &State<MetricsService>,
+) -> Json<ApiResponse<AlertRule>> {
+    if let Err(e) = service.validate_rule(&request) {
+        return Json(ApiResponse::error(e));
+    }
+
+    let rule = match service.create_rule(&request).await {
+        Ok(id) => service.get_rule(id).await,
+        Err(e) => Err(e),
+    };
+    match rule {
+        Ok(Some(rule)) => Json(ApiResponse::success(rule)),
+        Ok(None) => Json(ApiResponse::error("Rule not found".to_string())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+#[put("/console/alerts/<id>", data = "<request>")]
+pub async fn update_alert(
+    _auth: AuthGuard,
+    id: i32,
+    request: Json<AlertRuleRequest>,
+    service: &State<MetricsService>,
+) -> Json<ApiResponse<AlertRule>> {
+    if let Err(e) = service.validate_rule(&request) {
+        return Json(ApiResponse::error(e));
+    }
+
+    let rule = match service.update_rule(id, &request).await {
+        Ok(true) => service.get_rule(id).await,
+        Ok(false) => Ok(None),
+        Err(e) => Err(e),
+    };
+    match rule {
+        Ok(Some(rule)) => Json(ApiResponse::success(rule)),
+        Ok(None) => Json(ApiResponse::error("Rule not found".to_string())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+#[delete("/console/alerts/<id>")]
+pub async fn delete_alert(
+    _auth: AuthGuard,
+    id: i32,
+    service: &State<MetricsService>,
+) -> Json<ApiResponse<bool>> {
+    match service.delete_rule(id).await {
+        Ok(true) => Json(ApiResponse::success(true)),
+        Ok(false) => Json(ApiResponse::error("Rule not found".to_string())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
 /// Get available services
 #[get("/console/services")]
 pub async fn get_services(
@@ -399,11 +848,3 @@ fn parse_meminfo_kb(meminfo: &str, key: &str) -> u64 {
         })
         .unwrap_or(0)
 }
-
-fn truncate_output(s: &str, max_len: usize) -> String {
-    if s.len() > max_len {
-        format!("{}... [truncated, {} more bytes]", &s[..max_len], s.len() - max_len)
-    } else {
-        s.to_string()
-    }
-}
@@ -1,3 +1,4 @@
+use crate::alice::{RuleEngine, RuleEvent};
 use crate::cs2::{GSIPayload, MatchStateManager, PlayerStatsClient};
 use crate::models::ApiResponse;
 use rocket::serde::json::Json;
@@ -9,6 +10,7 @@ pub async fn receive_gsi(
     payload: Json<GSIPayload>,
     match_state: &State<MatchStateManager>,
     player_stats_client: &State<PlayerStatsClient>,
+    rules: &State<RuleEngine>,
 ) -> Json<ApiResponse<String>> {
     // Verify auth token
     let expected_token = env::var("GSI_AUTH_TOKEN").unwrap_or_else(|_| "your_secret_token_here".to_string());
@@ -30,7 +32,17 @@ pub async fn receive_gsi(
     let my_steamid = env::var("STEAM_ID").unwrap_or_default();
 
     // Update match state
-    match_state.update_from_gsi(&payload, &my_steamid);
+    if match_state.update_from_gsi(&payload, &my_steamid) {
+        let state = match_state.get_state();
+        let event = RuleEvent::Cs2MatchEnd {
+            map: state.map_name,
+            mode: state.mode,
+            result: payload.match_result().unwrap_or("unknown").to_string(),
+            ct_score: state.ct_score,
+            t_score: state.t_score,
+        };
+        rules.dispatch(event).await;
+    }
 
     // Fetch player stats for all players asynchronously
     let steamids = payload.get_all_steamids();
@@ -1,10 +1,32 @@
+use crate::db::DbPool;
 use crate::guards::AuthGuard;
+use crate::models::ApiResponse;
 use rocket::http::Status;
 use rocket::serde::json::Json;
-use rocket::{get, post, State};
+use rocket::{delete, get, post, put, State};
 use serde::{Deserialize, Serialize};
-use sqlx::SqlitePool;
-use sqlx::{Row, Column};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{SqliteArguments, SqliteConnection};
+use sqlx::{Connection, SqlitePool};
+use sqlx::{Row, Column, Sqlite, ValueRef};
+use std::env;
+use tracing::{error, warn};
+
+/// Statements `execute_query` accepts; they run on a read-only connection
+const READ_KEYWORDS: [&str; 5] = ["SELECT", "WITH", "PRAGMA", "EXPLAIN", "VALUES"];
+/// Tables edit mode never touches: the trail of changes, and credentials a scoped token
+/// could otherwise widen or reuse
+const PROTECTED_TABLES: [&str; 6] = [
+    "db_query_history",
+    "audit_log",
+    "api_tokens",
+    "sessions",
+    "studio_sessions",
+    "console_runs",
+];
+const DEFAULT_HISTORY_LIMIT: i64 = 1000;
+const POSTGRES_UNSUPPORTED: &str = "Database browser not available (PostgreSQL mode)";
 
 // === Models ===
 
@@ -78,6 +100,95 @@ pub struct DatabaseStats {
     pub total_rows: i64,
     pub database_size_bytes: i64,
     pub tables: Vec<TableInfo>,
+    /// Whether row editing is enabled (DB_EDIT_MODE)
+    pub edit_mode: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExplainRequest {
+    pub query: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueryPlanNode {
+    pub id: i64,
+    pub parent: i64,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueryPlan {
+    pub nodes: Vec<QueryPlanNode>,
+    /// Plan rendered as a tree, the way the sqlite3 shell prints it
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SavedQuery {
+    pub id: i64,
+    pub name: String,
+    pub query: String,
+    pub description: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SavedQueryRequest {
+    pub name: String,
+    pub query: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct QueryHistoryEntry {
+    pub id: i64,
+    pub kind: String, // query, insert, update, delete
+    pub query: String,
+    pub params: Option<String>,
+    pub row_count: Option<i64>,
+    pub execution_time_ms: i64,
+    pub error: Option<String>,
+    pub actor: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowChangeRequest {
+    pub table: String,
+    pub action: String, // insert, update, delete
+    /// Primary key of the row for update and delete
+    #[serde(default)]
+    pub key: Map<String, Value>,
+    /// Column values for insert and update
+    #[serde(default)]
+    pub values: Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RowChangeConfirm {
+    #[serde(flatten)]
+    pub change: RowChangeRequest,
+    /// Token from the preview; the change is refused if the row has moved on since
+    pub confirm_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowChangePreview {
+    pub sql: String,
+    pub params: Vec<Value>,
+    pub before: Option<Map<String, Value>>,
+    pub after: Option<Map<String, Value>>,
+    pub rows_affected: u64,
+    pub confirm_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowChangeResult {
+    pub before: Option<Map<String, Value>>,
+    pub after: Option<Map<String, Value>>,
+    pub rows_affected: u64,
+    pub execution_time_ms: u128,
 }
 
 // === Endpoints ===
@@ -86,19 +197,20 @@ pub struct DatabaseStats {
 #[get("/database/tables")]
 pub async fn get_tables(
     _auth: AuthGuard,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Result<Json<Vec<TableInfo>>, Status> {
+    let pool = sqlite_pool(pool).ok_or(Status::ServiceUnavailable)?;
     let tables: Vec<(String,)> = sqlx::query_as(
         "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
     )
-    .fetch_all(pool.inner())
+    .fetch_all(pool)
     .await
     .map_err(|_| Status::InternalServerError)?;
 
     let mut result = Vec::new();
     for (table_name,) in tables {
         let count: (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM \"{}\"", table_name))
-            .fetch_one(pool.inner())
+            .fetch_one(pool)
             .await
             .unwrap_or((0,));
 
@@ -115,9 +227,10 @@ pub async fn get_tables(
 #[get("/database/tables/<table_name>/schema")]
 pub async fn get_table_schema(
     _auth: AuthGuard,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
     table_name: &str,
 ) -> Result<Json<TableSchema>, Status> {
+    let pool = sqlite_pool(pool).ok_or(Status::ServiceUnavailable)?;
     // Validate table name (prevent SQL injection)
     if !is_valid_identifier(table_name) {
         return Err(Status::BadRequest);
@@ -128,7 +241,7 @@ pub async fn get_table_schema(
         "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name = ?"
     )
     .bind(table_name)
-    .fetch_one(pool.inner())
+    .fetch_one(pool)
     .await
     .map_err(|_| Status::InternalServerError)?;
 
@@ -140,7 +253,7 @@ pub async fn get_table_schema(
     let columns_raw: Vec<(i64, String, String, i64, Option<String>, i64)> = sqlx::query_as(
         &format!("PRAGMA table_info(\"{}\")", table_name)
     )
-    .fetch_all(pool.inner())
+    .fetch_all(pool)
     .await
     .map_err(|_| Status::InternalServerError)?;
 
@@ -151,13 +264,13 @@ pub async fn get_table_schema(
             column_type: col_type,
             notnull: notnull == 1,
             default_value: dflt_value,
-            pk: pk == 1,
+            pk: pk > 0,
         }
     }).collect();
 
     // Get row count
     let count: (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM \"{}\"", table_name))
-        .fetch_one(pool.inner())
+        .fetch_one(pool)
         .await
         .unwrap_or((0,));
 
@@ -172,9 +285,10 @@ pub async fn get_table_schema(
 #[post("/database/tables/data", data = "<request>")]
 pub async fn get_table_data(
     _auth: AuthGuard,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
     request: Json<TableDataRequest>,
 ) -> Result<Json<TableDataResult>, Status> {
+    let pool = sqlite_pool(pool).ok_or(Status::ServiceUnavailable)?;
     let table_name = &request.table;
 
     // Validate table name
@@ -187,7 +301,7 @@ pub async fn get_table_data(
         "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name = ?"
     )
     .bind(table_name)
-    .fetch_one(pool.inner())
+    .fetch_one(pool)
     .await
     .map_err(|_| Status::InternalServerError)?;
 
@@ -199,7 +313,7 @@ pub async fn get_table_data(
     let columns_raw: Vec<(i64, String, String, i64, Option<String>, i64)> = sqlx::query_as(
         &format!("PRAGMA table_info(\"{}\")", table_name)
     )
-    .fetch_all(pool.inner())
+    .fetch_all(pool)
     .await
     .map_err(|_| Status::InternalServerError)?;
 
@@ -210,7 +324,7 @@ pub async fn get_table_data(
             column_type: col_type,
             notnull: notnull == 1,
             default_value: dflt_value,
-            pk: pk == 1,
+            pk: pk > 0,
         }
     }).collect();
 
@@ -290,7 +404,7 @@ pub async fn get_table_data(
         count_query = count_query.bind(v);
     }
     let total_rows: i64 = count_query
-        .fetch_one(pool.inner())
+        .fetch_one(pool)
         .await
         .unwrap_or(0);
 
@@ -325,7 +439,7 @@ pub async fn get_table_data(
     }
 
     let rows_raw = query
-        .fetch_all(pool.inner())
+        .fetch_all(pool)
         .await
         .map_err(|_| Status::InternalServerError)?;
 
@@ -346,43 +460,43 @@ pub async fn get_table_data(
     }))
 }
 
-/// Execute a raw SQL query (SELECT only for safety)
+/// Execute a raw read-only SQL query (SELECT, WITH, PRAGMA, EXPLAIN, VALUES)
 #[post("/database/query", data = "<request>")]
 pub async fn execute_query(
-    _auth: AuthGuard,
-    pool: &State<SqlitePool>,
+    auth: AuthGuard,
+    pool: &State<DbPool>,
     request: Json<QueryRequest>,
 ) -> Result<Json<QueryResult>, Status> {
-    let query = request.query.trim();
-
-    // Only allow SELECT queries for safety
-    let query_upper = query.to_uppercase();
-    if !query_upper.starts_with("SELECT")
-        && !query_upper.starts_with("PRAGMA")
-        && !query_upper.starts_with("EXPLAIN") {
-        return Err(Status::Forbidden);
-    }
-
-    // Block dangerous operations even in SELECT
-    let forbidden = ["DROP", "DELETE", "INSERT", "UPDATE", "ALTER", "CREATE", "ATTACH", "DETACH"];
-    for word in forbidden {
-        if query_upper.contains(word) {
-            return Err(Status::Forbidden);
-        }
-    }
+    let pool = sqlite_pool(pool).ok_or(Status::ServiceUnavailable)?;
+    let query = read_statement(&request.query, &READ_KEYWORDS).ok_or(Status::Forbidden)?;
 
     let start = std::time::Instant::now();
 
-    // Execute query
-    let rows_raw = sqlx::query(query)
-        .fetch_all(pool.inner())
-        .await
-        .map_err(|e| {
-            eprintln!("Query error: {:?}", e);
-            Status::BadRequest
-        })?;
+    // A read-only connection makes SQLite itself reject any write
+    let mut conn = read_only_connection(pool).await.map_err(|e| {
+        error!("Failed to open read-only connection: {:?}", e);
+        Status::InternalServerError
+    })?;
+    let params = request.params.clone().unwrap_or_default();
+    let mut statement = sqlx::query(query);
+    for param in &params {
+        statement = statement.bind(param);
+    }
+    let result = statement.fetch_all(&mut conn).await;
+    let _ = conn.close().await;
 
     let execution_time_ms = start.elapsed().as_millis();
+    let (row_count, query_error) = match &result {
+        Ok(rows) => (Some(rows.len() as i64), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let params_json = (!params.is_empty()).then(|| serde_json::to_string(&params).unwrap_or_default());
+    record_history(pool, "query", query, params_json, row_count, execution_time_ms, query_error, &auth.actor_label()).await;
+
+    let rows_raw = result.map_err(|e| {
+        error!("Query error: {:?}", e);
+        Status::BadRequest
+    })?;
 
     if rows_raw.is_empty() {
         return Ok(Json(QueryResult {
@@ -431,13 +545,14 @@ pub async fn execute_query(
 #[get("/database/stats")]
 pub async fn get_database_stats(
     _auth: AuthGuard,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Result<Json<DatabaseStats>, Status> {
+    let pool = sqlite_pool(pool).ok_or(Status::ServiceUnavailable)?;
     // Get all tables
     let tables: Vec<(String,)> = sqlx::query_as(
         "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
     )
-    .fetch_all(pool.inner())
+    .fetch_all(pool)
     .await
     .map_err(|_| Status::InternalServerError)?;
 
@@ -446,7 +561,7 @@ pub async fn get_database_stats(
 
     for (table_name,) in tables {
         let count: (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM \"{}\"", table_name))
-            .fetch_one(pool.inner())
+            .fetch_one(pool)
             .await
             .unwrap_or((0,));
 
@@ -459,12 +574,12 @@ pub async fn get_database_stats(
 
     // Get database size
     let page_count: (i64,) = sqlx::query_as("PRAGMA page_count")
-        .fetch_one(pool.inner())
+        .fetch_one(pool)
         .await
         .unwrap_or((0,));
 
     let page_size: (i64,) = sqlx::query_as("PRAGMA page_size")
-        .fetch_one(pool.inner())
+        .fetch_one(pool)
         .await
         .unwrap_or((4096,));
 
@@ -475,11 +590,667 @@ pub async fn get_database_stats(
         total_rows,
         database_size_bytes,
         tables: table_infos,
+        edit_mode: edit_mode_enabled(),
     }))
 }
 
+/// EXPLAIN QUERY PLAN for a SELECT
+#[post("/database/explain", data = "<request>")]
+pub async fn explain_query(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    request: Json<ExplainRequest>,
+) -> Json<ApiResponse<QueryPlan>> {
+    let Some(pool) = sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    let Some(query) = read_statement(&request.query, &["SELECT", "WITH", "VALUES"]) else {
+        return Json(ApiResponse::error("Only a single SELECT statement can be explained".to_string()));
+    };
+
+    let mut conn = match read_only_connection(pool).await {
+        Ok(conn) => conn,
+        Err(e) => return Json(ApiResponse::error(format!("Database error: {}", e))),
+    };
+    let rows: Result<Vec<(i64, i64, i64, String)>, sqlx::Error> =
+        sqlx::query_as(&format!("EXPLAIN QUERY PLAN {}", query))
+            .fetch_all(&mut conn)
+            .await;
+    let _ = conn.close().await;
+
+    match rows {
+        Ok(rows) => {
+            let nodes: Vec<QueryPlanNode> = rows
+                .into_iter()
+                .map(|(id, parent, _notused, detail)| QueryPlanNode { id, parent, detail })
+                .collect();
+            let mut text = String::from("QUERY PLAN\n");
+            render_plan(&nodes, 0, "", &mut text);
+            Json(ApiResponse::success(QueryPlan { nodes, text }))
+        }
+        Err(e) => Json(ApiResponse::error(e.to_string())),
+    }
+}
+
+/// Executed queries and row changes, newest first
+#[get("/database/history?<limit>&<offset>&<search>")]
+pub async fn get_query_history(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    search: Option<String>,
+) -> Json<ApiResponse<Vec<QueryHistoryEntry>>> {
+    let Some(pool) = sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    let offset = offset.unwrap_or(0).max(0);
+    let pattern = format!("%{}%", search.unwrap_or_default());
+
+    let entries = sqlx::query_as::<_, QueryHistoryEntry>(
+        r#"
+        SELECT id, kind, query, params, row_count, execution_time_ms, error, actor, created_at
+        FROM db_query_history
+        WHERE query LIKE ?
+        ORDER BY id DESC
+        LIMIT ? OFFSET ?
+        "#,
+    )
+    .bind(pattern)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await;
+
+    match entries {
+        Ok(entries) => Json(ApiResponse::success(entries)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+/// Clear the query history
+#[delete("/database/history")]
+pub async fn clear_query_history(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+) -> Json<ApiResponse<u64>> {
+    let Some(pool) = sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match sqlx::query("DELETE FROM db_query_history").execute(pool).await {
+        Ok(result) => Json(ApiResponse::success(result.rows_affected())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+/// List saved queries
+#[get("/database/saved-queries")]
+pub async fn list_saved_queries(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+) -> Json<ApiResponse<Vec<SavedQuery>>> {
+    let Some(pool) = sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    let queries = sqlx::query_as::<_, SavedQuery>(
+        "SELECT id, name, query, description, created_at, updated_at FROM db_saved_queries ORDER BY name",
+    )
+    .fetch_all(pool)
+    .await;
+
+    match queries {
+        Ok(queries) => Json(ApiResponse::success(queries)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+/// Save a query
+#[post("/database/saved-queries", data = "<request>")]
+pub async fn create_saved_query(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    request: Json<SavedQueryRequest>,
+) -> Json<ApiResponse<SavedQuery>> {
+    let Some(pool) = sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    if let Err(e) = validate_saved_query(&request) {
+        return Json(ApiResponse::error(e));
+    }
+
+    let saved = sqlx::query_as::<_, SavedQuery>(
+        r#"
+        INSERT INTO db_saved_queries (name, query, description)
+        VALUES (?, ?, ?)
+        RETURNING id, name, query, description, created_at, updated_at
+        "#,
+    )
+    .bind(request.name.trim())
+    .bind(request.query.trim())
+    .bind(&request.description)
+    .fetch_one(pool)
+    .await;
+
+    match saved {
+        Ok(saved) => Json(ApiResponse::success(saved)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+/// Update a saved query
+#[put("/database/saved-queries/<id>", data = "<request>")]
+pub async fn update_saved_query(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    id: i64,
+    request: Json<SavedQueryRequest>,
+) -> Json<ApiResponse<SavedQuery>> {
+    let Some(pool) = sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    if let Err(e) = validate_saved_query(&request) {
+        return Json(ApiResponse::error(e));
+    }
+
+    let saved = sqlx::query_as::<_, SavedQuery>(
+        r#"
+        UPDATE db_saved_queries
+        SET name = ?, query = ?, description = ?, updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        RETURNING id, name, query, description, created_at, updated_at
+        "#,
+    )
+    .bind(request.name.trim())
+    .bind(request.query.trim())
+    .bind(&request.description)
+    .bind(id)
+    .fetch_optional(pool)
+    .await;
+
+    match saved {
+        Ok(Some(saved)) => Json(ApiResponse::success(saved)),
+        Ok(None) => Json(ApiResponse::error("Saved query not found".to_string())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+/// Delete a saved query
+#[delete("/database/saved-queries/<id>")]
+pub async fn delete_saved_query(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    id: i64,
+) -> Json<ApiResponse<()>> {
+    let Some(pool) = sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match sqlx::query("DELETE FROM db_saved_queries WHERE id = ?").bind(id).execute(pool).await {
+        Ok(result) if result.rows_affected() > 0 => Json(ApiResponse::success(())),
+        Ok(_) => Json(ApiResponse::error("Saved query not found".to_string())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+/// Dry-run a row change inside a transaction that is rolled back
+#[post("/database/rows/preview", data = "<request>")]
+pub async fn preview_row_change(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    request: Json<RowChangeRequest>,
+) -> Json<ApiResponse<RowChangePreview>> {
+    let Some(pool) = sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    if !edit_mode_enabled() {
+        return Json(ApiResponse::error("Edit mode is disabled (DB_EDIT_MODE)".to_string()));
+    }
+
+    match run_row_change(pool, &request, None).await {
+        Ok((applied, tx)) => {
+            if let Err(e) = tx.rollback().await {
+                return Json(ApiResponse::error(format!("Database error: {}", e)));
+            }
+            Json(ApiResponse::success(RowChangePreview {
+                sql: applied.plan.sql,
+                params: applied.plan.params,
+                before: applied.before,
+                after: applied.after,
+                rows_affected: applied.rows_affected,
+                confirm_token: applied.token,
+            }))
+        }
+        Err((_, e)) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Apply a previewed row change and commit it
+#[post("/database/rows/apply", data = "<request>")]
+pub async fn apply_row_change(
+    auth: AuthGuard,
+    pool: &State<DbPool>,
+    request: Json<RowChangeConfirm>,
+) -> Json<ApiResponse<RowChangeResult>> {
+    let Some(pool) = sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    if !edit_mode_enabled() {
+        return Json(ApiResponse::error("Edit mode is disabled (DB_EDIT_MODE)".to_string()));
+    }
+
+    let start = std::time::Instant::now();
+    let outcome = match run_row_change(pool, &request.change, Some(&request.confirm_token)).await {
+        Ok((applied, tx)) => match tx.commit().await {
+            Ok(()) => Ok(applied),
+            Err(e) => Err((Some(applied.plan), format!("Database error: {}", e))),
+        },
+        Err(failure) => Err(failure),
+    };
+    let elapsed_ms = start.elapsed().as_millis();
+
+    match outcome {
+        Ok(applied) => {
+            let params = serde_json::to_string(&applied.plan.params).ok();
+            record_history(
+                pool,
+                &request.change.action,
+                &applied.plan.sql,
+                params,
+                Some(applied.rows_affected as i64),
+                elapsed_ms,
+                None,
+                &auth.actor_label(),
+            )
+            .await;
+            Json(ApiResponse::success(RowChangeResult {
+                before: applied.before,
+                after: applied.after,
+                rows_affected: applied.rows_affected,
+                execution_time_ms: elapsed_ms,
+            }))
+        }
+        Err((plan, e)) => {
+            if let Some(plan) = plan {
+                let params = serde_json::to_string(&plan.params).ok();
+                record_history(
+                    pool,
+                    &request.change.action,
+                    &plan.sql,
+                    params,
+                    None,
+                    elapsed_ms,
+                    Some(e.clone()),
+                    &auth.actor_label(),
+                )
+                .await;
+            }
+            Json(ApiResponse::error(e))
+        }
+    }
+}
+
 // === Helper functions ===
 
+/// The browser reads SQLite's catalog and PRAGMAs, so it only works on the SQLite backend
+fn sqlite_pool(pool: &DbPool) -> Option<&SqlitePool> {
+    match pool {
+        DbPool::Sqlite(p) => Some(p),
+        DbPool::Postgres(_) => None,
+    }
+}
+
+fn edit_mode_enabled() -> bool {
+    env::var("DB_EDIT_MODE")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false)
+}
+
+/// Single statement starting with one of `keywords`, without the trailing semicolon
+fn read_statement<'a>(query: &'a str, keywords: &[&str]) -> Option<&'a str> {
+    let statement = query.trim().trim_end_matches(|c: char| c == ';' || c.is_whitespace());
+    if statement.is_empty() || has_statement_separator(statement) {
+        return None;
+    }
+
+    let first_word: String = statement
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_uppercase();
+    keywords.contains(&first_word.as_str()).then_some(statement)
+}
+
+/// Whether a `;` appears outside string literals and quoted identifiers
+fn has_statement_separator(sql: &str) -> bool {
+    let mut quote: Option<char> = None;
+    for c in sql.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if matches!(c, '\'' | '"' | '`') => quote = Some(c),
+            None if c == '[' => quote = Some(']'),
+            None if c == ';' => return true,
+            None => {}
+        }
+    }
+    false
+}
+
+/// Separate connection to the same database file opened with SQLITE_OPEN_READONLY
+async fn read_only_connection(pool: &SqlitePool) -> Result<SqliteConnection, sqlx::Error> {
+    let options = (*pool.connect_options()).clone().read_only(true);
+    SqliteConnection::connect_with(&options).await
+}
+
+/// Append prefixed plan nodes under `parent` in sqlite3 shell style
+fn render_plan(nodes: &[QueryPlanNode], parent: i64, prefix: &str, out: &mut String) {
+    let children: Vec<&QueryPlanNode> = nodes.iter().filter(|n| n.parent == parent).collect();
+    for (i, node) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        out.push_str(prefix);
+        out.push_str(if last { "`--" } else { "|--" });
+        out.push_str(&node.detail);
+        out.push('\n');
+        if node.id != parent {
+            let child_prefix = format!("{}{}", prefix, if last { "   " } else { "|  " });
+            render_plan(nodes, node.id, &child_prefix, out);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record_history(
+    pool: &SqlitePool,
+    kind: &str,
+    query: &str,
+    params: Option<String>,
+    row_count: Option<i64>,
+    execution_time_ms: u128,
+    query_error: Option<String>,
+    actor: &str,
+) {
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO db_query_history (kind, query, params, row_count, execution_time_ms, error, actor)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(kind)
+    .bind(query)
+    .bind(params)
+    .bind(row_count)
+    .bind(execution_time_ms as i64)
+    .bind(query_error)
+    .bind(actor)
+    .execute(pool)
+    .await;
+    if let Err(e) = inserted {
+        warn!("Failed to record query history: {}", e);
+        return;
+    }
+
+    let limit = env::var("DB_QUERY_HISTORY_LIMIT")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .max(1);
+    let pruned = sqlx::query(
+        "DELETE FROM db_query_history WHERE id <= (SELECT id FROM db_query_history ORDER BY id DESC LIMIT 1 OFFSET ?)",
+    )
+    .bind(limit)
+    .execute(pool)
+    .await;
+    if let Err(e) = pruned {
+        warn!("Failed to prune query history: {}", e);
+    }
+}
+
+fn validate_saved_query(request: &SavedQueryRequest) -> Result<(), String> {
+    if request.name.trim().is_empty() {
+        return Err("Name is required".to_string());
+    }
+    if request.query.trim().is_empty() {
+        return Err("Query is required".to_string());
+    }
+    Ok(())
+}
+
+/// SQL and bound values of a row change
+struct RowChangePlan {
+    sql: String,
+    params: Vec<Value>,
+    /// Where the row is before the change (update, delete)
+    key: Vec<(String, Value)>,
+    /// Where the row is after the change (insert with a full key, update)
+    after_key: Vec<(String, Value)>,
+}
+
+/// A row change executed inside a still open transaction
+struct AppliedRowChange {
+    plan: RowChangePlan,
+    before: Option<Map<String, Value>>,
+    after: Option<Map<String, Value>>,
+    rows_affected: u64,
+    token: String,
+}
+
+/// Why a row change failed; carries the plan once its statement has been run
+type RowChangeFailure = (Option<RowChangePlan>, String);
+
+/// Execute a row change in a new transaction and hand it back uncommitted.
+///
+/// With `expected_token` the row must still look exactly like it did in the preview.
+async fn run_row_change<'c>(
+    pool: &'c SqlitePool,
+    request: &RowChangeRequest,
+    expected_token: Option<&str>,
+) -> Result<(AppliedRowChange, sqlx::Transaction<'c, Sqlite>), RowChangeFailure> {
+    let table = request.table.as_str();
+    check_editable_table(table).map_err(|e| (None, e))?;
+
+    let db_err = |e: sqlx::Error| (None, format!("Database error: {}", e));
+    let mut tx = pool.begin().await.map_err(db_err)?;
+
+    let columns = table_columns(&mut tx, table).await.map_err(db_err)?;
+    if columns.is_empty() {
+        return Err((None, format!("Table {} not found", table)));
+    }
+    let plan = plan_row_change(request, &columns).map_err(|e| (None, e))?;
+
+    let before = if plan.key.is_empty() {
+        None
+    } else {
+        match fetch_row(&mut tx, table, &columns, &plan.key).await.map_err(db_err)? {
+            Some(row) => Some(row),
+            None => return Err((None, "Row not found".to_string())),
+        }
+    };
+
+    let token = confirm_token(request, &before);
+    if expected_token.is_some_and(|expected| expected != token) {
+        return Err((None, "Row has changed since the preview, preview the change again".to_string()));
+    }
+
+    let mut query = sqlx::query(&plan.sql);
+    for value in &plan.params {
+        query = bind_json(query, value);
+    }
+    let rows_affected = match query.execute(&mut *tx).await {
+        Ok(result) => result.rows_affected(),
+        Err(e) => return Err((Some(plan), format!("Database error: {}", e))),
+    };
+    if rows_affected != 1 {
+        return Err((Some(plan), format!("Change would affect {} rows instead of one", rows_affected)));
+    }
+
+    let after = match request.action.as_str() {
+        "delete" => None,
+        "insert" if plan.after_key.is_empty() => {
+            let sql = format!("SELECT * FROM \"{}\" WHERE rowid = last_insert_rowid()", table);
+            let row = sqlx::query(&sql).fetch_optional(&mut *tx).await.map_err(db_err)?;
+            row.map(|row| row_to_map(&row, &columns))
+        }
+        _ => fetch_row(&mut tx, table, &columns, &plan.after_key).await.map_err(db_err)?,
+    };
+
+    Ok((AppliedRowChange { plan, before, after, rows_affected, token }, tx))
+}
+
+/// Table names are case-insensitive in SQLite, so `AUDIT_LOG` must be refused like `audit_log`
+fn check_editable_table(table: &str) -> Result<(), String> {
+    let name = table.to_ascii_lowercase();
+    if !is_valid_identifier(table) || name.starts_with("sqlite_") {
+        return Err("Invalid table name".to_string());
+    }
+    if PROTECTED_TABLES.contains(&name.as_str()) {
+        return Err(format!("Table {} cannot be edited", table));
+    }
+    Ok(())
+}
+
+fn plan_row_change(request: &RowChangeRequest, columns: &[ColumnInfo]) -> Result<RowChangePlan, String> {
+    let table = &request.table;
+    let pk_columns: Vec<&str> = columns.iter().filter(|c| c.pk).map(|c| c.name.as_str()).collect();
+    if pk_columns.is_empty() {
+        return Err(format!("Table {} has no primary key", table));
+    }
+
+    for column in request.values.keys() {
+        if !columns.iter().any(|c| &c.name == column) {
+            return Err(format!("Unknown column: {}", column));
+        }
+    }
+
+    // update and delete address exactly one row by its full primary key
+    let key = |request: &RowChangeRequest| -> Result<Vec<(String, Value)>, String> {
+        if request.key.len() != pk_columns.len() || !pk_columns.iter().all(|c| request.key.contains_key(*c)) {
+            return Err(format!("Key must contain exactly the primary key columns: {}", pk_columns.join(", ")));
+        }
+        Ok(pk_columns.iter().map(|c| (c.to_string(), request.key[*c].clone())).collect())
+    };
+
+    match request.action.as_str() {
+        "insert" => {
+            let sql = if request.values.is_empty() {
+                format!("INSERT INTO \"{}\" DEFAULT VALUES", table)
+            } else {
+                let names: Vec<String> = request.values.keys().map(|c| format!("\"{}\"", c)).collect();
+                let placeholders = vec!["?"; names.len()].join(", ");
+                format!("INSERT INTO \"{}\" ({}) VALUES ({})", table, names.join(", "), placeholders)
+            };
+            let after_key = if pk_columns.iter().all(|c| request.values.contains_key(*c)) {
+                pk_columns.iter().map(|c| (c.to_string(), request.values[*c].clone())).collect()
+            } else {
+                Vec::new()
+            };
+            Ok(RowChangePlan {
+                sql,
+                params: request.values.values().cloned().collect(),
+                key: Vec::new(),
+                after_key,
+            })
+        }
+        "update" => {
+            let key = key(request)?;
+            if request.values.is_empty() {
+                return Err("Nothing to update".to_string());
+            }
+            let assignments: Vec<String> = request.values.keys().map(|c| format!("\"{}\" = ?", c)).collect();
+            let sql = format!("UPDATE \"{}\" SET {} WHERE {}", table, assignments.join(", "), key_clause(&key));
+            let mut params: Vec<Value> = request.values.values().cloned().collect();
+            params.extend(key.iter().map(|(_, v)| v.clone()));
+            let after_key = key
+                .iter()
+                .map(|(c, v)| (c.clone(), request.values.get(c).unwrap_or(v).clone()))
+                .collect();
+            Ok(RowChangePlan { sql, params, key, after_key })
+        }
+        "delete" => {
+            let key = key(request)?;
+            let sql = format!("DELETE FROM \"{}\" WHERE {}", table, key_clause(&key));
+            let params = key.iter().map(|(_, v)| v.clone()).collect();
+            Ok(RowChangePlan { sql, params, key, after_key: Vec::new() })
+        }
+        other => Err(format!("Unknown action: {}", other)),
+    }
+}
+
+fn key_clause(key: &[(String, Value)]) -> String {
+    key.iter()
+        .map(|(c, _)| format!("\"{}\" = ?", c))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Ties a confirmation to the exact change and the row as it looked in the preview
+fn confirm_token(request: &RowChangeRequest, before: &Option<Map<String, Value>>) -> String {
+    let payload = serde_json::json!({
+        "table": request.table,
+        "action": request.action,
+        "key": request.key,
+        "values": request.values,
+        "before": before,
+    });
+    hex::encode(Sha256::digest(payload.to_string().as_bytes()))
+}
+
+async fn table_columns(conn: &mut SqliteConnection, table: &str) -> Result<Vec<ColumnInfo>, sqlx::Error> {
+    let columns_raw: Vec<(i64, String, String, i64, Option<String>, i64)> =
+        sqlx::query_as(&format!("PRAGMA table_info(\"{}\")", table))
+            .fetch_all(conn)
+            .await?;
+
+    Ok(columns_raw
+        .into_iter()
+        .map(|(cid, name, column_type, notnull, default_value, pk)| ColumnInfo {
+            cid,
+            name,
+            column_type,
+            notnull: notnull == 1,
+            default_value,
+            pk: pk > 0,
+        })
+        .collect())
+}
+
+async fn fetch_row(
+    conn: &mut SqliteConnection,
+    table: &str,
+    columns: &[ColumnInfo],
+    key: &[(String, Value)],
+) -> Result<Option<Map<String, Value>>, sqlx::Error> {
+    let sql = format!("SELECT * FROM \"{}\" WHERE {}", table, key_clause(key));
+    let mut query = sqlx::query(&sql);
+    for (_, value) in key {
+        query = bind_json(query, value);
+    }
+    let row = query.fetch_optional(conn).await?;
+    Ok(row.map(|row| row_to_map(&row, columns)))
+}
+
+fn row_to_map(row: &sqlx::sqlite::SqliteRow, columns: &[ColumnInfo]) -> Map<String, Value> {
+    columns
+        .iter()
+        .map(|col| (col.name.clone(), sqlite_value_to_json(row, &col.name, &col.column_type)))
+        .collect()
+}
+
+fn bind_json<'q>(
+    query: sqlx::query::Query<'q, Sqlite, SqliteArguments<'q>>,
+    value: &Value,
+) -> sqlx::query::Query<'q, Sqlite, SqliteArguments<'q>> {
+    match value {
+        Value::Null => query.bind(None::<String>),
+        Value::Bool(b) => query.bind(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => query.bind(i),
+            None => query.bind(n.as_f64()),
+        },
+        Value::String(s) => query.bind(s.clone()),
+        other => query.bind(other.to_string()),
+    }
+}
+
 fn is_valid_identifier(name: &str) -> bool {
     !name.is_empty()
         && name.chars().all(|c| c.is_alphanumeric() || c == '_')
@@ -487,6 +1258,10 @@ fn is_valid_identifier(name: &str) -> bool {
 }
 
 fn sqlite_value_to_json(row: &sqlx::sqlite::SqliteRow, col_name: &str, col_type: &str) -> serde_json::Value {
+    if row.try_get_raw(col_name).map(|v| v.is_null()).unwrap_or(false) {
+        return serde_json::Value::Null;
+    }
+
     let col_type_upper = col_type.to_uppercase();
 
     if col_type_upper.contains("INT") {
@@ -523,3 +1298,85 @@ fn sqlite_value_to_json(row: &sqlx::sqlite::SqliteRow, col_name: &str, col_type:
 
     serde_json::Value::Null
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use rocket::local::asynchronous::Client;
+    use rocket::routes;
+
+    fn change(table: &str) -> RowChangeRequest {
+        RowChangeRequest {
+            table: table.to_string(),
+            action: "delete".to_string(),
+            key: serde_json::from_value(serde_json::json!({"id": 1})).unwrap(),
+            values: Map::new(),
+        }
+    }
+
+    #[test]
+    fn protected_tables_cannot_be_edited_in_any_case() {
+        for table in PROTECTED_TABLES {
+            let capitalized = format!("{}{}", &table[..1].to_uppercase(), &table[1..]);
+            for name in [table.to_string(), table.to_uppercase(), capitalized] {
+                assert_eq!(check_editable_table(&name), Err(format!("Table {} cannot be edited", name)));
+            }
+        }
+        for name in ["sqlite_master", "SQLITE_MASTER", "Sqlite_Sequence", "bad name", "x\"y"] {
+            assert_eq!(check_editable_table(name), Err("Invalid table name".to_string()), "{}", name);
+        }
+        assert!(check_editable_table("short_links").is_ok());
+        assert!(check_editable_table("Short_Links").is_ok());
+    }
+
+    #[tokio::test]
+    async fn mixed_case_names_do_not_reach_protected_rows() {
+        let (_dir, pool) = test_support::sqlite_pool().await;
+        let DbPool::Sqlite(pool) = pool else { unreachable!() };
+        sqlx::query("INSERT INTO audit_log (actor, module, action, method, path, status) VALUES ('session:1', 'links', 'create', 'POST', '/api/links', 200)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        for table in ["Audit_Log", "AUDIT_LOG", "API_TOKENS", "Sessions", "console_RUNS"] {
+            let Err((plan, e)) = run_row_change(&pool, &change(table), None).await else {
+                panic!("{} was editable", table);
+            };
+            assert!(plan.is_none());
+            assert_eq!(e, format!("Table {} cannot be edited", table));
+        }
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_log").fetch_one(&pool).await.unwrap();
+        assert_eq!(left, 1);
+    }
+
+    #[tokio::test]
+    async fn routes_launch_with_the_managed_db_pool() {
+        let (_dir, pool) = test_support::sqlite_pool().await;
+        let rocket = rocket::build()
+            .mount(
+                "/api",
+                routes![
+                    get_tables,
+                    get_table_schema,
+                    get_table_data,
+                    execute_query,
+                    get_database_stats,
+                    explain_query,
+                    get_query_history,
+                    clear_query_history,
+                    list_saved_queries,
+                    create_saved_query,
+                    update_saved_query,
+                    delete_saved_query,
+                    preview_row_change,
+                    apply_row_change,
+                ],
+            )
+            .manage(pool);
+        // Ignition fails when a route asks for state that is not managed
+        let client = Client::tracked(rocket).await.expect("database routes must launch");
+        let response = client.get("/api/database/tables").dispatch().await;
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+}
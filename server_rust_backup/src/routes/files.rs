@@ -2,6 +2,10 @@ use crate::files::{
     AccessCodeRequest, CreateFolderRequest, FileResponse, FileService,
     FolderResponse, RenameFolderRequest, UpdateFileRequest,
 };
+use crate::db::DbPool;
+use crate::audit::{record_actor, AuditActor};
+use crate::auth::api_tokens::ApiTokenService;
+use crate::guards::AuthGuard;
 use crate::models::ApiResponse;
 use rocket::form::Form;
 use rocket::fs::TempFile;
@@ -13,10 +17,41 @@ use sqlx::SqlitePool;
 use std::env;
 use uuid::Uuid;
 
-// Admin guard - checks if Steam ID matches admin
+// Helper to get SQLite pool from DbPool (files is SQLite-only feature)
+fn get_sqlite_pool(pool: &DbPool) -> Option<&SqlitePool> {
+    match pool {
+        DbPool::Sqlite(p) => Some(p),
+        DbPool::Postgres(_) => None,
+    }
+}
+
+const POSTGRES_UNSUPPORTED: &str = "File manager not available (PostgreSQL mode)";
+
+// Steam ID of the studio session behind `token`, if it is still valid
+async fn studio_session_steam_id(pool: &SqlitePool, token: &str) -> Option<String> {
+    let result: Option<(String,)> = sqlx::query_as(
+        r#"
+        SELECT u.steam_id
+        FROM studio_sessions s
+        JOIN studio_users u ON s.user_id = u.id
+        WHERE s.token = ? AND s.expires_at > datetime('now')
+        "#,
+    )
+    .bind(token)
+    .fetch_optional(pool)
+    .await
+    .ok()
+    .flatten();
+
+    result.map(|(steam_id,)| steam_id)
+}
+
+// Admin guard - the studio's Steam admin session (used by the studio file manager),
+// or anything the shared guard accepts (OTP session, `files:*` personal token)
 #[allow(dead_code)]
-pub struct AdminAuth {
-    pub steam_id: String,
+pub enum AdminAuth {
+    Studio { steam_id: String },
+    Admin(AuthGuard),
 }
 
 #[rocket::async_trait]
@@ -24,8 +59,6 @@ impl<'r> FromRequest<'r> for AdminAuth {
     type Error = &'static str;
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let admin_steam_id = env::var("ADMIN_STEAM_ID").unwrap_or_default();
-
         // Get token from Authorization header
         let token = match request.headers().get_one("Authorization") {
             Some(auth) => auth.strip_prefix("Bearer ").unwrap_or(auth),
@@ -34,34 +67,39 @@ impl<'r> FromRequest<'r> for AdminAuth {
             }
         };
 
-        // Get database pool
-        let pool = match request.guard::<&State<SqlitePool>>().await {
-            Outcome::Success(pool) => pool,
-            _ => return Outcome::Error((Status::InternalServerError, "Database error")),
-        };
-
-        // Validate session and check if admin
-        let result: Option<(String,)> = sqlx::query_as(
-            r#"
-            SELECT u.steam_id
-            FROM studio_sessions s
-            JOIN studio_users u ON s.user_id = u.id
-            WHERE s.token = ? AND s.expires_at > datetime('now')
-            "#,
-        )
-        .bind(token)
-        .fetch_optional(pool.inner())
-        .await
-        .ok()
-        .flatten();
-
-        match result {
-            Some((steam_id,)) if steam_id == admin_steam_id => {
-                Outcome::Success(AdminAuth { steam_id })
+        if !ApiTokenService::is_api_token(token) {
+            let studio_steam_id = match request.guard::<&State<DbPool>>().await {
+                Outcome::Success(pool) => match get_sqlite_pool(pool) {
+                    Some(pool) => studio_session_steam_id(pool, token).await,
+                    None => None,
+                },
+                _ => return Outcome::Error((Status::InternalServerError, "Database error")),
+            };
+
+            if let Some(steam_id) = studio_steam_id {
+                let admin_steam_id = env::var("ADMIN_STEAM_ID").unwrap_or_default();
+                if admin_steam_id.trim().is_empty() || steam_id.trim() != admin_steam_id.trim() {
+                    return Outcome::Error((Status::Forbidden, "Not an admin"));
+                }
+
+                // Remember the studio admin for the audit log
+                record_actor(
+                    request,
+                    AuditActor {
+                        label: format!("studio:{}", steam_id),
+                        user_id: None,
+                    },
+                )
+                .await;
+                return Outcome::Success(AdminAuth::Studio { steam_id });
             }
-            Some(_) => Outcome::Error((Status::Forbidden, "Not an admin")),
-            None => Outcome::Error((Status::Unauthorized, "Invalid or expired token")),
         }
+
+        request
+            .guard::<AuthGuard>()
+            .await
+            .map(AdminAuth::Admin)
+            .map_error(|(status, ())| (status, "Invalid or expired token"))
     }
 }
 
@@ -83,8 +121,11 @@ pub struct UploadForm<'r> {
 pub async fn get_folder_contents(
     _auth: AdminAuth,
     folder_id: Option<String>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     match FileService::get_folder_contents(pool, folder_id.as_deref()).await {
         Ok(contents) => Json(ApiResponse::success(serde_json::json!(contents))),
         Err(e) => Json(ApiResponse::error(e)),
@@ -96,8 +137,11 @@ pub async fn get_folder_contents(
 pub async fn create_folder(
     _auth: AdminAuth,
     request: Json<CreateFolderRequest>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     match FileService::create_folder(pool, &request.name, request.parent_id.as_deref()).await {
         Ok(folder) => Json(ApiResponse::success(serde_json::json!({
             "folder": FolderResponse::from(folder)
@@ -112,8 +156,11 @@ pub async fn rename_folder(
     _auth: AdminAuth,
     folder_id: &str,
     request: Json<RenameFolderRequest>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     match FileService::rename_folder(pool, folder_id, &request.name).await {
         Ok(Some(folder)) => Json(ApiResponse::success(serde_json::json!({
             "folder": FolderResponse::from(folder)
@@ -128,8 +175,11 @@ pub async fn rename_folder(
 pub async fn delete_folder(
     _auth: AdminAuth,
     folder_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     match FileService::delete_folder(pool, folder_id).await {
         Ok(true) => Json(ApiResponse::success(serde_json::json!({ "deleted": true }))),
         Ok(false) => Json(ApiResponse::error("Folder not found".to_string())),
@@ -142,8 +192,11 @@ pub async fn delete_folder(
 pub async fn upload_file(
     _auth: AdminAuth,
     mut form: Form<UploadForm<'_>>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     // Get file info before persisting
     let name = form
         .file
@@ -206,8 +259,11 @@ pub async fn update_file(
     _auth: AdminAuth,
     file_id: &str,
     request: Json<UpdateFileRequest>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     let folder_id = request.folder_id.as_ref().map(|f| Some(f.as_str()));
 
     match FileService::update_file(
@@ -233,8 +289,11 @@ pub async fn update_file(
 pub async fn delete_file(
     _auth: AdminAuth,
     file_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     match FileService::delete_file(pool, file_id).await {
         Ok(true) => Json(ApiResponse::success(serde_json::json!({ "deleted": true }))),
         Ok(false) => Json(ApiResponse::error("File not found".to_string())),
@@ -247,8 +306,11 @@ pub async fn delete_file(
 pub async fn get_file_info(
     _auth: AdminAuth,
     file_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     match FileService::get_file(pool, file_id).await {
         Ok(Some(file)) => Json(ApiResponse::success(serde_json::json!({
             "file": FileResponse::from(file)
@@ -264,8 +326,9 @@ pub async fn get_file_info(
 #[get("/files/public/<file_id>")]
 pub async fn get_public_file(
     file_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Result<(ContentType, Vec<u8>), Status> {
+    let pool = get_sqlite_pool(pool).ok_or(Status::ServiceUnavailable)?;
     let file = FileService::get_file(pool, file_id)
         .await
         .map_err(|_| Status::InternalServerError)?
@@ -289,8 +352,9 @@ pub async fn get_public_file(
 pub async fn get_private_file(
     file_id: &str,
     request: Json<AccessCodeRequest>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Result<(ContentType, Vec<u8>), Status> {
+    let pool = get_sqlite_pool(pool).ok_or(Status::ServiceUnavailable)?;
     let file = FileService::get_file(pool, file_id)
         .await
         .map_err(|_| Status::InternalServerError)?
@@ -329,8 +393,11 @@ pub async fn get_private_file(
 #[get("/files/check/<file_id>")]
 pub async fn check_file(
     file_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     match FileService::get_file(pool, file_id).await {
         Ok(Some(file)) => Json(ApiResponse::success(serde_json::json!({
             "exists": true,
@@ -353,8 +420,9 @@ pub async fn check_file(
 pub async fn get_admin_file(
     file_id: &str,
     token: Option<String>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Result<(ContentType, Vec<u8>), Status> {
+    let pool = get_sqlite_pool(pool).ok_or(Status::ServiceUnavailable)?;
     // Get admin steam ID from env
     let admin_steam_id = env::var("ADMIN_STEAM_ID").unwrap_or_default();
     if admin_steam_id.is_empty() {
@@ -365,22 +433,8 @@ pub async fn get_admin_file(
     let token = token.ok_or(Status::Unauthorized)?;
 
     // Validate session and check if admin
-    let result: Option<(String,)> = sqlx::query_as(
-        r#"
-        SELECT u.steam_id
-        FROM studio_sessions s
-        JOIN studio_users u ON s.user_id = u.id
-        WHERE s.token = ? AND s.expires_at > datetime('now')
-        "#,
-    )
-    .bind(&token)
-    .fetch_optional(pool.inner())
-    .await
-    .ok()
-    .flatten();
-
-    match result {
-        Some((steam_id,)) if steam_id.trim() == admin_steam_id.trim() => {}
+    match studio_session_steam_id(pool, &token).await {
+        Some(steam_id) if steam_id.trim() == admin_steam_id.trim() => {}
         _ => return Err(Status::Forbidden),
     }
 
@@ -407,6 +407,37 @@ pub async fn ignore_vacancy(
     Json(ApiResponse::success("Vacancy ignored".to_string()))
 }
 
+// Approve a vacancy held by JOBS_REQUIRE_APPROVAL and apply to it
+#[post("/jobs/vacancies/<id>/approve")]
+pub async fn approve_vacancy(
+    _auth: AuthGuard,
+    id: i32,
+    scheduler: &State<MaybeJobScheduler>,
+) -> Json<ApiResponse<String>> {
+    let Some(scheduler) = scheduler.get() else {
+        return Json(ApiResponse::error("Job search not available (PostgreSQL mode)".to_string()));
+    };
+    match scheduler.approve_application(id).await {
+        Ok(()) => Json(ApiResponse::success("Application sent".to_string())),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+#[post("/jobs/vacancies/<id>/skip")]
+pub async fn skip_vacancy(
+    _auth: AuthGuard,
+    id: i32,
+    scheduler: &State<MaybeJobScheduler>,
+) -> Json<ApiResponse<String>> {
+    let Some(scheduler) = scheduler.get() else {
+        return Json(ApiResponse::error("Job search not available (PostgreSQL mode)".to_string()));
+    };
+    match scheduler.skip_application(id).await {
+        Ok(()) => Json(ApiResponse::success("Vacancy skipped".to_string())),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
 // HH OAuth callback endpoint - redirects to frontend after processing
 #[get("/auth/hh/callback?<code>&<error>&<error_description>")]
 pub async fn hh_oauth_callback(
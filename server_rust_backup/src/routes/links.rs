@@ -1,4 +1,5 @@
 use crate::guards::AuthGuard;
+use crate::metrics::prometheus;
 use rocket::http::Status;
 use rocket::response::content::RawHtml;
 use rocket::serde::json::Json;
@@ -608,6 +609,7 @@ pub async fn redirect_link(
     .execute(pool.inner())
     .await
     .ok();
+    prometheus::inc("bgalin_link_clicks_total", &[], 1.0);
 
     // Generate HTML with tracking and redirect
     let custom_js = link.custom_js.as_deref().unwrap_or("");
@@ -0,0 +1,51 @@
+use crate::alice::{CommandQueueService, PC_ONLINE_WINDOW_SECS};
+use crate::db::DbPool;
+use crate::guards::AuthGuard;
+use crate::metrics::prometheus::{registry, write_gauge};
+use crate::metrics::MetricsService;
+use crate::publish::PublishService;
+use rocket::http::ContentType;
+use rocket::{get, State};
+use tracing::error;
+
+// Prometheus text exposition of request metrics, counters and business gauges
+#[get("/metrics")]
+pub async fn prometheus_metrics(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    publish: &State<PublishService>,
+    host: &State<MetricsService>,
+) -> (ContentType, String) {
+    let mut out = String::new();
+    registry().render(&mut out);
+
+    let publish_jobs: Vec<(Vec<(&str, &str)>, f64)> = publish
+        .status_counts()
+        .into_iter()
+        .map(|(status, count)| (vec![("status", status.as_str())], count as f64))
+        .collect();
+    write_gauge(&mut out, "bgalin_publish_jobs", "Publish jobs by status", &publish_jobs);
+
+    match CommandQueueService::count_online_clients(pool.inner(), PC_ONLINE_WINDOW_SECS).await {
+        Ok(online) => write_gauge(
+            &mut out,
+            "bgalin_pc_clients_online",
+            "PC clients seen in the last two minutes",
+            &[(vec![], online as f64)],
+        ),
+        Err(e) => error!("Failed to count online PC clients: {}", e),
+    }
+
+    if let Some(snapshot) = host.latest() {
+        let samples: Vec<(Vec<(&str, &str)>, f64)> = snapshot
+            .values
+            .iter()
+            .map(|(name, value)| (vec![("metric", name.as_str())], *value))
+            .collect();
+        write_gauge(&mut out, "bgalin_host", "Latest host metrics sample", &samples);
+    }
+
+    let content_type = ContentType::new("text", "plain")
+        .with_params([("version", "0.0.4"), ("charset", "utf-8")]);
+    (content_type, out)
+}
@@ -12,6 +12,11 @@ pub mod files;
 pub mod sync;
 pub mod links;
 pub mod database;
+pub mod backup;
 pub mod console;
 pub mod menu;
 pub mod english;
+pub mod audit;
+pub mod metrics;
+pub mod notifications;
+pub mod telegram;
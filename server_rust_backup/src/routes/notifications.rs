@@ -0,0 +1,173 @@
+use crate::db::DbPool;
+use crate::guards::AuthGuard;
+use crate::models::ApiResponse;
+use crate::notifications::{
+    NewNotification, Notification, NotificationEvent, NotificationFilter, NotificationPage, NotificationService,
+    StreamTicket, UnreadSummary,
+};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{delete, get, post, Shutdown, State};
+
+/// `Last-Event-ID` of a reconnecting EventSource
+pub struct LastEventId(Option<i64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(LastEventId(
+            req.headers().get_one("Last-Event-ID").and_then(|id| id.parse().ok()),
+        ))
+    }
+}
+
+// List notifications, newest first
+#[get("/notifications?<category>&<priority>&<unread>&<limit>&<offset>")]
+pub async fn list_notifications(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    category: Option<String>,
+    priority: Option<String>,
+    unread: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Json<ApiResponse<NotificationPage>> {
+    let filter = NotificationFilter {
+        category,
+        priority,
+        unread,
+        limit,
+        offset,
+    };
+
+    match NotificationService::list(pool.inner(), &filter).await {
+        Ok(page) => Json(ApiResponse::success(page)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+// Unread counters, total and per category
+#[get("/notifications/unread")]
+pub async fn unread_notifications(_auth: AuthGuard, pool: &State<DbPool>) -> Json<ApiResponse<UnreadSummary>> {
+    match NotificationService::unread_summary(pool.inner()).await {
+        Ok(summary) => Json(ApiResponse::success(summary)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+// Post a notification, e.g. from a script or n8n
+#[post("/notifications", data = "<notification>")]
+pub async fn create_notification(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    notification: Json<NewNotification>,
+) -> Json<ApiResponse<Notification>> {
+    match NotificationService::notify(pool.inner(), &notification).await {
+        Ok(created) => Json(ApiResponse::success(created)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+#[post("/notifications/<id>/read")]
+pub async fn read_notification(_auth: AuthGuard, pool: &State<DbPool>, id: i64) -> Json<ApiResponse<bool>> {
+    match NotificationService::mark_read(pool.inner(), id).await {
+        Ok(changed) => Json(ApiResponse::success(changed)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+// Mark everything read, or only one category
+#[post("/notifications/read-all?<category>")]
+pub async fn read_all_notifications(
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
+    category: Option<String>,
+) -> Json<ApiResponse<u64>> {
+    match NotificationService::mark_all_read(pool.inner(), category.as_deref()).await {
+        Ok(count) => Json(ApiResponse::success(count)),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+#[delete("/notifications/<id>")]
+pub async fn delete_notification(_auth: AuthGuard, pool: &State<DbPool>, id: i64) -> Json<ApiResponse<bool>> {
+    match NotificationService::delete(pool.inner(), id).await {
+        Ok(true) => Json(ApiResponse::success(true)),
+        Ok(false) => Json(ApiResponse::error("Notification not found".to_string())),
+        Err(e) => Json(ApiResponse::error(format!("Database error: {}", e))),
+    }
+}
+
+// Ticket for `?ticket=` on the stream, since EventSource cannot send the Authorization header
+#[post("/notifications/stream-ticket")]
+pub async fn notification_stream_ticket(_auth: AuthGuard) -> Json<ApiResponse<StreamTicket>> {
+    Json(ApiResponse::success(NotificationService::issue_ticket()))
+}
+
+/// Live notification events as Server-Sent Events. Notifications missed since `Last-Event-ID`
+/// (or `?since=`) are replayed first; event IDs are notification IDs.
+#[get("/notifications/stream?<ticket>&<since>")]
+pub fn stream_notifications(
+    auth: Option<AuthGuard>,
+    ticket: Option<&str>,
+    since: Option<i64>,
+    last_event_id: LastEventId,
+    pool: &State<DbPool>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    if auth.is_none() && !ticket.is_some_and(NotificationService::check_ticket) {
+        return Err(Status::Unauthorized);
+    }
+    let pool = pool.inner().clone();
+    let since = last_event_id.0.or(since);
+    // Subscribe before the replay so nothing falls between the two
+    let mut rx = NotificationService::subscribe();
+
+    Ok(EventStream! {
+        let mut last_id = since.unwrap_or(0);
+        if let Some(since) = since {
+            match NotificationService::since(&pool, since).await {
+                Ok(missed) => {
+                    for notification in missed {
+                        last_id = notification.id;
+                        yield notification_event(&NotificationEvent::Created { notification });
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to replay notifications: {}", e),
+            }
+        }
+
+        loop {
+            let event = select! {
+                event = rx.recv() => event,
+                _ = &mut shutdown => break,
+            };
+            match event {
+                Ok(NotificationEvent::Created { notification }) if notification.id <= last_id => continue,
+                Ok(event) => {
+                    if let NotificationEvent::Created { notification } = &event {
+                        last_id = notification.id;
+                    }
+                    yield notification_event(&event);
+                }
+                // The client refetches the list when told it fell behind
+                Err(RecvError::Lagged(_)) => yield Event::data("").event("lagged"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+fn notification_event(event: &NotificationEvent) -> Event {
+    let sse = Event::json(event).event(event.name());
+    match event {
+        NotificationEvent::Created { notification } => sse.id(notification.id.to_string()),
+        _ => sse,
+    }
+}
@@ -1,4 +1,6 @@
+use crate::db::DbPool;
 use crate::models::ApiResponse;
+use crate::notifications::{category, NewNotification, NotificationPriority, NotificationService};
 use crate::publish::{FrameSettings, JobStatus, PublishService, StatusResponse};
 use rocket::form::Form;
 use rocket::fs::TempFile;
@@ -7,6 +9,7 @@ use rocket::response::stream::ReaderStream;
 use rocket::serde::json::Json;
 use rocket::tokio::fs::File;
 use rocket::{get, post, FromForm, State};
+use std::path::PathBuf;
 
 #[derive(FromForm)]
 pub struct ConvertForm<'r> {
@@ -44,6 +47,7 @@ pub struct OptimizeForm<'r> {
 pub async fn convert_video(
     mut form: Form<ConvertForm<'_>>,
     service: &State<PublishService>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
     // Create job
     let job = service.create_job();
@@ -84,6 +88,7 @@ pub async fn convert_video(
     let service_clone = service.inner().clone();
     let job_id_clone = job_id.clone();
     let optimization_clone = optimization.clone();
+    let pool = pool.inner().clone();
 
     tokio::spawn(async move {
         let result = service_clone
@@ -101,12 +106,14 @@ pub async fn convert_video(
             )
             .await;
 
+        let notification = finished_notification("Конвертация в GIF", &job_id_clone, &result);
         if let Err(e) = result {
             service_clone.update_job(&job_id_clone, |job| {
                 job.status = JobStatus::Error;
                 job.error = Some(e);
             });
         }
+        NotificationService::post(&pool, notification).await;
     });
 
     Json(ApiResponse::success(serde_json::json!({
@@ -119,6 +126,7 @@ pub async fn convert_video(
 pub async fn optimize_gif(
     mut form: Form<OptimizeForm<'_>>,
     service: &State<PublishService>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
     // Create job
     let job = service.create_job();
@@ -156,6 +164,7 @@ pub async fn optimize_gif(
     let service_clone = service.inner().clone();
     let job_id_clone = job_id.clone();
     let optimization_clone = optimization.clone();
+    let pool = pool.inner().clone();
 
     tokio::spawn(async move {
         let result = service_clone
@@ -170,12 +179,14 @@ pub async fn optimize_gif(
             )
             .await;
 
+        let notification = finished_notification("Оптимизация GIF", &job_id_clone, &result);
         if let Err(e) = result {
             service_clone.update_job(&job_id_clone, |job| {
                 job.status = JobStatus::Error;
                 job.error = Some(e);
             });
         }
+        NotificationService::post(&pool, notification).await;
     });
 
     Json(ApiResponse::success(serde_json::json!({
@@ -183,6 +194,25 @@ pub async fn optimize_gif(
     })))
 }
 
+/// Conversions run in the background, so their outcome goes to the notification center
+fn finished_notification(task: &str, job_id: &str, result: &Result<PathBuf, String>) -> NewNotification {
+    match result {
+        Ok(_) => NewNotification::new(
+            category::PUBLISH,
+            NotificationPriority::Low,
+            format!("✅ {}: готово", task),
+            format!("Задача {}", job_id),
+        ),
+        Err(e) => NewNotification::new(
+            category::PUBLISH,
+            NotificationPriority::High,
+            format!("❌ {}: ошибка", task),
+            e.clone(),
+        ),
+    }
+    .data(serde_json::json!({ "job_id": job_id }))
+}
+
 /// Get job status
 #[get("/studio/publish/status/<job_id>")]
 pub async fn get_status(
@@ -9,6 +9,7 @@ use rocket::serde::json::Json;
 use rocket::{delete, get, post, put, State};
 use sqlx::SqlitePool;
 use std::env;
+use tracing::info;
 
 // Request guard for Studio authentication
 #[allow(dead_code)]
@@ -187,7 +188,7 @@ pub async fn get_me(
 
             // Debug log for troubleshooting admin detection
             if !admin_steam_id.is_empty() {
-                println!("[Studio] Admin check: user_steam_id={}, admin_steam_id={}, is_admin={}",
+                info!("[Studio] Admin check: user_steam_id={}, admin_steam_id={}, is_admin={}",
                     user_steam_id, admin_steam_id, is_admin);
             }
 
@@ -1,5 +1,8 @@
+use crate::db::DbPool;
+use crate::guards::AuthGuard;
+use crate::metrics::prometheus;
 use crate::models::ApiResponse;
-use crate::routes::files::AdminAuth;
+use crate::notifications::{category, NewNotification, NotificationPriority, NotificationService};
 use crate::sync::{
     CreateSyncFolderRequest, RegisterClientRequest, RenameSyncFolderRequest, SyncClientResponse,
     SyncDiff, SyncFileResponse, SyncService, SyncStatusRequest,
@@ -10,10 +13,22 @@ use rocket::request::{FromRequest, Outcome, Request};
 use rocket::serde::json::Json;
 use rocket::{delete, get, post, put, State};
 use sqlx::SqlitePool;
+use tracing::{error, info};
+
+// Helper to get SQLite pool from DbPool (sync is SQLite-only feature)
+fn get_sqlite_pool(pool: &DbPool) -> Option<&SqlitePool> {
+    match pool {
+        DbPool::Sqlite(p) => Some(p),
+        DbPool::Postgres(_) => None,
+    }
+}
+
+const POSTGRES_UNSUPPORTED: &str = "Sync not available (PostgreSQL mode)";
 
 // API Key auth guard for sync clients
 pub struct SyncAuth {
     pub folder_id: String,
+    pub folder_name: String,
 }
 
 #[rocket::async_trait]
@@ -30,15 +45,19 @@ impl<'r> FromRequest<'r> for SyncAuth {
         };
 
         // Get database pool
-        let pool = match request.guard::<&State<SqlitePool>>().await {
+        let pool = match request.guard::<&State<DbPool>>().await {
             Outcome::Success(pool) => pool,
             _ => return Outcome::Error((Status::InternalServerError, "Database error")),
         };
+        let Some(pool) = get_sqlite_pool(pool) else {
+            return Outcome::Error((Status::ServiceUnavailable, POSTGRES_UNSUPPORTED));
+        };
 
         // Validate API key
-        match SyncService::get_folder_by_key(pool.inner(), api_key).await {
+        match SyncService::get_folder_by_key(pool, api_key).await {
             Ok(Some(folder)) => Outcome::Success(SyncAuth {
                 folder_id: folder.id,
+                folder_name: folder.name,
             }),
             Ok(None) => Outcome::Error((Status::Unauthorized, "Invalid API key")),
             Err(_) => Outcome::Error((Status::InternalServerError, "Database error")),
@@ -51,10 +70,13 @@ impl<'r> FromRequest<'r> for SyncAuth {
 /// List all sync folders (admin)
 #[get("/sync/folders")]
 pub async fn list_folders(
-    _auth: AdminAuth,
-    pool: &State<SqlitePool>,
+    _auth: AuthGuard,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::list_folders(pool.inner()).await {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::list_folders(pool).await {
         Ok(folders) => Json(ApiResponse::success(serde_json::json!({ "folders": folders }))),
         Err(e) => Json(ApiResponse::error(e)),
     }
@@ -63,13 +85,16 @@ pub async fn list_folders(
 /// Create sync folder (admin)
 #[post("/sync/folders", data = "<request>")]
 pub async fn create_folder(
-    _auth: AdminAuth,
+    _auth: AuthGuard,
     request: Json<CreateSyncFolderRequest>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::create_folder(pool.inner(), &request.name).await {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::create_folder(pool, &request.name).await {
         Ok(folder) => {
-            let stats = SyncService::get_folder_stats(pool.inner(), &folder.id)
+            let stats = SyncService::get_folder_stats(pool, &folder.id)
                 .await
                 .unwrap_or((0, 0, 0));
             Json(ApiResponse::success(serde_json::json!({
@@ -92,19 +117,22 @@ pub async fn create_folder(
 /// Get sync folder details (admin)
 #[get("/sync/folders/<folder_id>")]
 pub async fn get_folder(
-    _auth: AdminAuth,
+    _auth: AuthGuard,
     folder_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::get_folder(pool.inner(), folder_id).await {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::get_folder(pool, folder_id).await {
         Ok(Some(folder)) => {
-            let stats = SyncService::get_folder_stats(pool.inner(), &folder.id)
+            let stats = SyncService::get_folder_stats(pool, &folder.id)
                 .await
                 .unwrap_or((0, 0, 0));
-            let clients = SyncService::list_clients(pool.inner(), folder_id)
+            let clients = SyncService::list_clients(pool, folder_id)
                 .await
                 .unwrap_or_default();
-            let files = SyncService::list_files(pool.inner(), folder_id)
+            let files = SyncService::list_files(pool, folder_id)
                 .await
                 .unwrap_or_default();
 
@@ -131,12 +159,15 @@ pub async fn get_folder(
 /// Rename sync folder (admin)
 #[put("/sync/folders/<folder_id>", data = "<request>")]
 pub async fn rename_folder(
-    _auth: AdminAuth,
+    _auth: AuthGuard,
     folder_id: &str,
     request: Json<RenameSyncFolderRequest>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::rename_folder(pool.inner(), folder_id, &request.name).await {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::rename_folder(pool, folder_id, &request.name).await {
         Ok(true) => Json(ApiResponse::success(serde_json::json!({ "renamed": true }))),
         Ok(false) => Json(ApiResponse::error("Folder not found".to_string())),
         Err(e) => Json(ApiResponse::error(e)),
@@ -146,11 +177,14 @@ pub async fn rename_folder(
 /// Regenerate API key (admin)
 #[post("/sync/folders/<folder_id>/regenerate-key")]
 pub async fn regenerate_key(
-    _auth: AdminAuth,
+    _auth: AuthGuard,
     folder_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::regenerate_api_key(pool.inner(), folder_id).await {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::regenerate_api_key(pool, folder_id).await {
         Ok(Some(new_key)) => Json(ApiResponse::success(serde_json::json!({ "apiKey": new_key }))),
         Ok(None) => Json(ApiResponse::error("Folder not found".to_string())),
         Err(e) => Json(ApiResponse::error(e)),
@@ -160,11 +194,14 @@ pub async fn regenerate_key(
 /// Delete sync folder (admin)
 #[delete("/sync/folders/<folder_id>")]
 pub async fn delete_folder(
-    _auth: AdminAuth,
+    _auth: AuthGuard,
     folder_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::delete_folder(pool.inner(), folder_id).await {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::delete_folder(pool, folder_id).await {
         Ok(true) => Json(ApiResponse::success(serde_json::json!({ "deleted": true }))),
         Ok(false) => Json(ApiResponse::error("Folder not found".to_string())),
         Err(e) => Json(ApiResponse::error(e)),
@@ -174,11 +211,14 @@ pub async fn delete_folder(
 /// Delete client (admin)
 #[delete("/sync/clients/<client_id>")]
 pub async fn delete_client(
-    _auth: AdminAuth,
+    _auth: AuthGuard,
     client_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::delete_client(pool.inner(), client_id).await {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::delete_client(pool, client_id).await {
         Ok(true) => Json(ApiResponse::success(serde_json::json!({ "deleted": true }))),
         Ok(false) => Json(ApiResponse::error("Client not found".to_string())),
         Err(e) => Json(ApiResponse::error(e)),
@@ -192,13 +232,30 @@ pub async fn delete_client(
 pub async fn register_client(
     auth: SyncAuth,
     request: Json<RegisterClientRequest>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::register_client(pool.inner(), &auth.folder_id, &request.device_name).await {
-        Ok(client) => Json(ApiResponse::success(serde_json::json!({
-            "clientId": client.id,
-            "folderId": auth.folder_id
-        }))),
+    let db = pool;
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::register_client(pool, &auth.folder_id, &request.device_name).await {
+        Ok(client) => {
+            NotificationService::post(
+                db,
+                NewNotification::new(
+                    category::SYNC,
+                    NotificationPriority::Normal,
+                    "🔗 Новое устройство синхронизации",
+                    format!("{} подключено к папке «{}»", request.device_name, auth.folder_name),
+                )
+                .data(serde_json::json!({ "client_id": client.id, "folder_id": auth.folder_id })),
+            )
+            .await;
+            Json(ApiResponse::success(serde_json::json!({
+                "clientId": client.id,
+                "folderId": auth.folder_id
+            })))
+        }
         Err(e) => Json(ApiResponse::error(e)),
     }
 }
@@ -208,14 +265,17 @@ pub async fn register_client(
 pub async fn get_sync_status(
     auth: SyncAuth,
     request: Json<SyncStatusRequest>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<SyncDiff>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     // Update client sync time
-    SyncService::update_client_sync_time(pool.inner(), &request.client_id)
+    SyncService::update_client_sync_time(pool, &request.client_id)
         .await
         .ok();
 
-    match SyncService::compute_sync_diff(pool.inner(), &auth.folder_id, &request.files).await {
+    match SyncService::compute_sync_diff(pool, &auth.folder_id, &request.files).await {
         Ok(diff) => Json(ApiResponse::success(diff)),
         Err(e) => Json(ApiResponse::error(e)),
     }
@@ -225,9 +285,12 @@ pub async fn get_sync_status(
 #[get("/sync/files")]
 pub async fn list_files(
     auth: SyncAuth,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::list_files(pool.inner(), &auth.folder_id).await {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::list_files(pool, &auth.folder_id).await {
         Ok(files) => Json(ApiResponse::success(serde_json::json!({
             "files": files.into_iter().map(SyncFileResponse::from).collect::<Vec<_>>()
         }))),
@@ -242,26 +305,30 @@ pub async fn upload_file(
     path: String,
     data: Data<'_>,
     content_type: Option<&ContentType>,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
     // URL decode the path (handles special characters like spaces, cyrillic, etc.)
     let decoded_path = urlencoding::decode(&path)
         .map(|s| s.into_owned())
         .unwrap_or(path);
 
-    println!("📤 Upload request: folder={}, path={}", auth.folder_id, decoded_path);
+    info!("Upload request: folder={}, path={}", auth.folder_id, decoded_path);
 
     // Read file data (limit to 100MB)
     let bytes = match data.open(100.mebibytes()).into_bytes().await {
         Ok(b) if b.is_complete() => b.into_inner(),
         Ok(_) => return Json(ApiResponse::error("File too large (max 100MB)".to_string())),
         Err(e) => {
-            println!("❌ Failed to read file data: {}", e);
+            error!("Failed to read file data: {}", e);
             return Json(ApiResponse::error(format!("Failed to read file: {}", e)));
         }
     };
 
-    println!("📦 Received {} bytes", bytes.len());
+    info!("Received {} bytes", bytes.len());
+    prometheus::inc("bgalin_sync_bytes_total", &[("direction", "upload")], bytes.len() as f64);
 
     // Extract filename from path
     let name = decoded_path
@@ -275,7 +342,7 @@ pub async fn upload_file(
         .unwrap_or_else(|| "application/octet-stream".to_string());
 
     match SyncService::upload_file(
-        pool.inner(),
+        pool,
         &auth.folder_id,
         &decoded_path,
         &name,
@@ -285,13 +352,13 @@ pub async fn upload_file(
     .await
     {
         Ok(file) => {
-            println!("✅ File uploaded successfully: {}", decoded_path);
+            info!("File uploaded successfully: {}", decoded_path);
             Json(ApiResponse::success(serde_json::json!({
                 "file": SyncFileResponse::from(file)
             })))
         }
         Err(e) => {
-            println!("❌ Upload failed for {}: {}", decoded_path, e);
+            error!("Upload failed for {}: {}", decoded_path, e);
             Json(ApiResponse::error(e))
         }
     }
@@ -302,9 +369,10 @@ pub async fn upload_file(
 pub async fn download_file(
     auth: SyncAuth,
     file_id: &str,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Result<(ContentType, Vec<u8>), Status> {
-    let file = SyncService::get_file_by_id(pool.inner(), file_id)
+    let pool = get_sqlite_pool(pool).ok_or(Status::ServiceUnavailable)?;
+    let file = SyncService::get_file_by_id(pool, file_id)
         .await
         .map_err(|_| Status::InternalServerError)?
         .ok_or(Status::NotFound)?;
@@ -318,6 +386,8 @@ pub async fn download_file(
         .await
         .map_err(|_| Status::InternalServerError)?;
 
+    prometheus::inc("bgalin_sync_bytes_total", &[("direction", "download")], data.len() as f64);
+
     let content_type = ContentType::parse_flexible(&file.mime_type).unwrap_or(ContentType::Binary);
 
     Ok((content_type, data))
@@ -328,9 +398,12 @@ pub async fn download_file(
 pub async fn delete_file(
     auth: SyncAuth,
     path: String,
-    pool: &State<SqlitePool>,
+    pool: &State<DbPool>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    match SyncService::delete_file(pool.inner(), &auth.folder_id, &path).await {
+    let Some(pool) = get_sqlite_pool(pool) else {
+        return Json(ApiResponse::error(POSTGRES_UNSUPPORTED.to_string()));
+    };
+    match SyncService::delete_file(pool, &auth.folder_id, &path).await {
         Ok(true) => Json(ApiResponse::success(serde_json::json!({ "deleted": true }))),
         Ok(false) => Json(ApiResponse::error("File not found".to_string())),
         Err(e) => Json(ApiResponse::error(e)),
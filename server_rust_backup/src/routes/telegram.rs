@@ -0,0 +1,107 @@
+use crate::telegram::{TelegramBotService, Update};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::{post, State};
+
+/// `X-Telegram-Bot-Api-Secret-Token` set by Telegram on webhook requests
+pub struct TelegramSecretToken(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TelegramSecretToken {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(TelegramSecretToken(
+            req.headers().get_one("X-Telegram-Bot-Api-Secret-Token").map(str::to_string),
+        ))
+    }
+}
+
+// Bot updates in TELEGRAM_UPDATES=webhook mode
+#[post("/telegram/webhook", data = "<update>")]
+pub async fn telegram_webhook(
+    secret: TelegramSecretToken,
+    service: &State<TelegramBotService>,
+    update: Json<Update>,
+) -> Status {
+    match (TelegramBotService::webhook_secret(), secret.0) {
+        (Some(expected), Some(given)) if expected == given => {}
+        _ => return Status::Forbidden,
+    }
+
+    // Answer right away; Telegram resends updates that take too long
+    let service = service.inner().clone();
+    tokio::spawn(async move { service.handle_update(update.into_inner()).await });
+    Status::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobScheduler;
+    use crate::metrics::MetricsService;
+    use crate::telegram::TelegramBot;
+    use crate::test_support::{self, HttpStub};
+    use rocket::http::{ContentType, Header};
+    use rocket::local::asynchronous::Client;
+    use rocket::routes;
+
+    const SECRET: &str = "webhook-test-secret";
+
+    async fn client(api: &HttpStub) -> (tempfile::TempDir, Client) {
+        let (dir, pool) = test_support::sqlite_pool().await;
+        let bot = {
+            let _env = test_support::env_lock();
+            std::env::set_var("TELEGRAM_API_URL", &api.url);
+            std::env::set_var("TELEGRAM_WEBHOOK_SECRET", SECRET);
+            TelegramBot::new("TOKEN".to_string())
+        };
+        let metrics = MetricsService::init(pool.clone()).await;
+        let service = TelegramBotService::new(bot, 1001, pool.clone(), Some(JobScheduler::new(pool)), metrics);
+        let rocket = rocket::build().mount("/api", routes![telegram_webhook]).manage(service);
+        (dir, Client::tracked(rocket).await.unwrap())
+    }
+
+    const UPDATE: &str =
+        r#"{"update_id": 1, "message": {"message_id": 1, "from": {"id": 1001}, "chat": {"id": 1001}, "text": "/help"}}"#;
+
+    #[tokio::test]
+    async fn webhook_requires_the_secret_token() {
+        let api = HttpStub::start(|_| (200, r#"{"ok": true, "result": {"message_id": 2, "chat": {"id": 1001}}}"#.to_string())).await;
+        let (_dir, client) = client(&api).await;
+
+        let missing = client.post("/api/telegram/webhook").header(ContentType::JSON).body(UPDATE).dispatch().await;
+        assert_eq!(missing.status(), Status::Forbidden);
+
+        let wrong = client
+            .post("/api/telegram/webhook")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Telegram-Bot-Api-Secret-Token", "guess"))
+            .body(UPDATE)
+            .dispatch()
+            .await;
+        assert_eq!(wrong.status(), Status::Forbidden);
+
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        assert!(api.requests().is_empty());
+
+        let valid = client
+            .post("/api/telegram/webhook")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Telegram-Bot-Api-Secret-Token", SECRET))
+            .body(UPDATE)
+            .dispatch()
+            .await;
+        assert_eq!(valid.status(), Status::Ok);
+
+        // The update is handled after the answer
+        for _ in 0..50 {
+            if !api.hits("/botTOKEN/sendMessage").is_empty() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("/help from the webhook was never answered");
+    }
+}
@@ -1,5 +1,6 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use tracing::info;
 
 #[derive(Debug, Clone)]
 pub struct SteamClient {
@@ -167,35 +168,35 @@ impl SteamClient {
             self.api_key, self.steam_id, appid
         );
 
-        println!("============ STEAM WORKSHOP DEBUG ============");
-        println!("Fetching workshop items from: {}", url.replace(&self.api_key, "***"));
-        println!("Steam ID: {}", self.steam_id);
-        println!("App ID: {}", appid);
+        info!("============ STEAM WORKSHOP DEBUG ============");
+        info!("Fetching workshop items from: {}", url.replace(&self.api_key, "***"));
+        info!("Steam ID: {}", self.steam_id);
+        info!("App ID: {}", appid);
 
         let response = self.client.get(&url).send().await?;
 
-        println!("HTTP Status: {}", response.status());
+        info!("HTTP Status: {}", response.status());
 
         if !response.status().is_success() {
             let error_msg = format!("Steam API error: {}", response.status());
-            println!("ERROR: {}", error_msg);
+            info!("ERROR: {}", error_msg);
             return Err(error_msg.into());
         }
 
         let response_text = response.text().await?;
-        println!("Raw API Response: {}", response_text);
+        info!("Raw API Response: {}", response_text);
 
         let user_files: UserFilesResponse = match serde_json::from_str(&response_text) {
             Ok(data) => data,
             Err(e) => {
-                println!("ERROR parsing JSON: {}", e);
+                info!("ERROR parsing JSON: {}", e);
                 return Err(Box::new(e));
             }
         };
 
-        println!("Total files found: {}", user_files.response.total);
-        println!("Files in response: {}", user_files.response.publishedfiledetails.len());
-        println!("============================================");
+        info!("Total files found: {}", user_files.response.total);
+        info!("Files in response: {}", user_files.response.publishedfiledetails.len());
+        info!("============================================");
 
         if user_files.response.publishedfiledetails.is_empty() {
             return Ok(Vec::new());
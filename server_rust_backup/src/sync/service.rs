@@ -6,6 +6,7 @@ use sqlx::SqlitePool;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use uuid::Uuid;
+use tracing::info;
 
 const SYNC_STORAGE_DIR: &str = "sync_storage";
 
@@ -17,7 +18,7 @@ impl SyncService {
         let storage_dir = Self::get_storage_dir();
         std::fs::create_dir_all(&storage_dir)
             .map_err(|e| format!("Failed to create sync storage directory: {}", e))?;
-        println!("☁️  Sync storage initialized at: {:?}", storage_dir);
+        info!("Sync storage initialized at: {:?}", storage_dir);
         Ok(())
     }
 
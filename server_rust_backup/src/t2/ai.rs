@@ -1,3 +1,4 @@
+use crate::telemetry::RequestIdExt;
 use serde::{Deserialize, Serialize};
 use std::env;
 
@@ -68,6 +69,7 @@ async fn call_openrouter(messages: Vec<Message>, temperature: f32) -> Result<Str
         .header("HTTP-Referer", "https://bgalin.ru")
         .header("X-Title", "T2 Sales System")
         .json(&request)
+        .with_request_id()
         .send()
         .await
         .map_err(|e| format!("Request failed: {}", e))?;
@@ -1,3 +1,4 @@
+use crate::audit::{record_actor, AuditActor};
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 use sqlx::SqlitePool;
@@ -61,6 +62,16 @@ impl<'r> FromRequest<'r> for T2AuthGuard {
             Err(_) => return Outcome::Error((Status::InternalServerError, "Database error")),
         };
 
+        // Remember the employee for the audit log
+        record_actor(
+            request,
+            AuditActor {
+                label: format!("t2:{}", employee.id),
+                user_id: None,
+            },
+        )
+        .await;
+
         // Get all stores employee has access to
         let mut stores = Vec::new();
 
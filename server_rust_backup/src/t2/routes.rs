@@ -3,6 +3,7 @@ use rocket::{get, post, put, delete, State};
 use sqlx::SqlitePool;
 use sha2::{Sha256, Digest};
 use rand::Rng;
+use tracing::info;
 
 use super::models::*;
 use super::guards::{T2AuthGuard, T2AdminGuard};
@@ -51,7 +52,7 @@ fn generate_token() -> String {
 
 #[get("/t2/health")]
 pub async fn t2_health() -> Json<ApiResponse<String>> {
-    println!("T2 health check called");
+    info!("T2 health check called");
     ApiResponse::success("T2 API is running".to_string())
 }
 
@@ -62,7 +63,7 @@ pub async fn t2_login(
     pool: &State<SqlitePool>,
     request: Json<LoginRequest>,
 ) -> Json<ApiResponse<LoginResponse>> {
-    println!("T2 login attempt with code: {}", request.code);
+    info!("T2 login attempt with code: {}", request.code);
 
     // Find employee by code
     let employee = match sqlx::query_as::<_, T2Employee>(
@@ -0,0 +1,747 @@
+use crate::alice::handlers::send_wol;
+use crate::alice::service::PC_ONLINE_WINDOW_SECS;
+use crate::alice::{CommandDelivery, CommandQueueService, CommandTarget, PcCommandType};
+use crate::db::DbPool;
+use crate::files::FileService;
+use crate::jobs::{JobScheduler, APPROVAL_JOB_APPLICATION};
+use crate::metrics::MetricsService;
+use crate::notifications::{NotificationEvent, NotificationService};
+use crate::sync::SyncService;
+use crate::telegram::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, Message, TelegramBot, Update};
+use std::env;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+/// `getUpdates` long poll timeout
+const POLL_TIMEOUT_SECS: u64 = 50;
+/// Telegram refuses bigger uploads from bots
+const MAX_PHOTO_BYTES: i64 = 10 * 1024 * 1024;
+const MAX_DOCUMENT_BYTES: i64 = 50 * 1024 * 1024;
+/// How long `/screenshot` waits for the PC to capture and upload
+const SCREENSHOT_WAIT_SECS: u64 = 90;
+
+const HELP: &str = "<b>Команды</b>
+/status — сервер, ПК и уведомления
+/wake [ПК] — включить ПК по Wake-on-LAN
+/lock [ПК] — заблокировать ПК
+/screenshot [ПК] — снимок экрана ПК
+/jobs — автопоиск работы и отклики на подтверждение
+/links — короткие ссылки и переходы
+/sync — папки синхронизации
+/file &lt;id&gt; — прислать файл из файлового менеджера";
+
+/// How the bot receives updates: `TELEGRAM_UPDATES` = `polling` (default), `webhook` or `off`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UpdateMode {
+    Polling,
+    /// Telegram posts to `TELEGRAM_WEBHOOK_URL` (`/api/telegram/webhook`) with `TELEGRAM_WEBHOOK_SECRET`
+    Webhook { url: String, secret: String },
+    Off,
+}
+
+/// Two-way admin bot: commands, inline keyboard callbacks and approval requests.
+/// Only `ADMIN_TELEGRAM_ID` is served; everyone else is ignored.
+#[derive(Clone)]
+pub struct TelegramBotService {
+    bot: TelegramBot,
+    admin_id: i64,
+    pool: DbPool,
+    jobs: Option<JobScheduler>,
+    metrics: MetricsService,
+}
+
+impl TelegramBotService {
+    pub fn new(
+        bot: TelegramBot,
+        admin_id: i64,
+        pool: DbPool,
+        jobs: Option<JobScheduler>,
+        metrics: MetricsService,
+    ) -> Self {
+        Self {
+            bot,
+            admin_id,
+            pool,
+            jobs,
+            metrics,
+        }
+    }
+
+    fn update_mode() -> UpdateMode {
+        match env::var("TELEGRAM_UPDATES").unwrap_or_else(|_| "polling".to_string()).as_str() {
+            "off" => UpdateMode::Off,
+            "webhook" => match (env::var("TELEGRAM_WEBHOOK_URL"), Self::webhook_secret()) {
+                (Ok(url), Some(secret)) => UpdateMode::Webhook { url, secret },
+                _ => {
+                    warn!("TELEGRAM_UPDATES=webhook needs TELEGRAM_WEBHOOK_URL and TELEGRAM_WEBHOOK_SECRET; bot updates are off");
+                    UpdateMode::Off
+                }
+            },
+            _ => UpdateMode::Polling,
+        }
+    }
+
+    /// Expected `X-Telegram-Bot-Api-Secret-Token` of webhook requests
+    pub fn webhook_secret() -> Option<String> {
+        env::var("TELEGRAM_WEBHOOK_SECRET").ok().filter(|s| !s.is_empty())
+    }
+
+    /// Start receiving updates and forwarding approval requests; returns the update mode
+    pub fn start(self) -> &'static str {
+        let forwarder = self.clone();
+        tokio::spawn(async move { forwarder.forward_approvals().await });
+
+        match Self::update_mode() {
+            UpdateMode::Polling => {
+                tokio::spawn(async move { self.poll_updates().await });
+                "polling"
+            }
+            UpdateMode::Webhook { url, secret } => {
+                tokio::spawn(async move {
+                    match self.bot.set_webhook(&url, &secret).await {
+                        Ok(()) => info!("Telegram webhook set to {}", url),
+                        Err(e) => warn!("Failed to set Telegram webhook: {}", e),
+                    }
+                });
+                "webhook"
+            }
+            UpdateMode::Off => "off",
+        }
+    }
+
+    async fn poll_updates(self) {
+        // getUpdates is refused while a webhook is set
+        if let Err(e) = self.bot.delete_webhook().await {
+            warn!("Failed to delete Telegram webhook: {}", e);
+        }
+
+        let mut offset = 0;
+        loop {
+            match self.bot.get_updates(offset, POLL_TIMEOUT_SECS).await {
+                Ok(updates) => {
+                    for update in updates {
+                        offset = update.update_id + 1;
+                        // Applying to a vacancy takes a while; keep polling meanwhile
+                        let service = self.clone();
+                        tokio::spawn(async move { service.handle_update(update).await });
+                    }
+                }
+                Err(e) => {
+                    warn!("Telegram getUpdates failed: {}", e);
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                }
+            }
+        }
+    }
+
+    pub async fn handle_update(&self, update: Update) {
+        if let Some(query) = update.callback_query {
+            self.handle_callback(query).await;
+        } else if let Some(message) = update.message {
+            self.handle_message(message).await;
+        }
+    }
+
+    async fn handle_message(&self, message: Message) {
+        let from = message.from.as_ref().map(|u| u.id);
+        if from != Some(self.admin_id) {
+            debug!("Ignoring Telegram message from {:?}", from);
+            return;
+        }
+        let Some(text) = message.text.as_deref() else { return };
+
+        let (command, args) = text.trim().split_once(char::is_whitespace).unwrap_or((text.trim(), ""));
+        // "/status@my_bot" in group chats
+        let command = command.split('@').next().unwrap_or(command);
+        let args = args.trim();
+        let chat_id = message.chat.id;
+
+        let reply = match command {
+            "/start" | "/help" => HELP.to_string(),
+            "/status" => self.status().await,
+            "/wake" => self.wake(args).await,
+            "/lock" => self.lock(args).await,
+            "/screenshot" => self.screenshot(chat_id, args).await,
+            "/jobs" => self.jobs(chat_id).await,
+            "/links" => self.links().await,
+            "/sync" => self.sync_folders().await,
+            "/file" => match self.send_file(chat_id, args).await {
+                Ok(()) => return,
+                Err(e) => format!("❌ {}", escape(&e)),
+            },
+            _ => format!("Неизвестная команда.\n\n{}", HELP),
+        };
+
+        if let Err(e) = self.bot.send_message(chat_id, &reply).await {
+            warn!("Failed to answer Telegram command {}: {}", command, e);
+        }
+    }
+
+    async fn handle_callback(&self, query: CallbackQuery) {
+        if query.from.id != self.admin_id {
+            self.bot.answer_callback_query(&query.id, Some("Нет доступа")).await.ok();
+            return;
+        }
+        let data = query.data.as_deref().unwrap_or("");
+        let parsed = data
+            .strip_prefix("job:")
+            .and_then(|rest| rest.split_once(':'))
+            .and_then(|(action, id)| Some((action, id.parse::<i32>().ok()?)));
+        let (Some((action, vacancy_id)), Some(jobs)) = (parsed, self.jobs.as_ref()) else {
+            self.bot.answer_callback_query(&query.id, Some("Неизвестная кнопка")).await.ok();
+            return;
+        };
+
+        let outcome = match action {
+            "approve" => {
+                self.bot.answer_callback_query(&query.id, Some("Отправляю отклик…")).await.ok();
+                jobs.approve_application(vacancy_id)
+                    .await
+                    .map(|_| "✅ Отклик отправлен".to_string())
+            }
+            "skip" => {
+                self.bot.answer_callback_query(&query.id, None).await.ok();
+                jobs.skip_application(vacancy_id).await.map(|_| "⏭️ Пропущено".to_string())
+            }
+            _ => {
+                self.bot.answer_callback_query(&query.id, Some("Неизвестная кнопка")).await.ok();
+                return;
+            }
+        };
+        let outcome = outcome.unwrap_or_else(|e| format!("❌ {}", escape(&e)));
+
+        // Replace the buttons with the outcome so the request cannot be answered twice
+        if let Some(message) = query.message {
+            let text = format!("{}\n\n{}", escape(message.text.as_deref().unwrap_or("")), outcome);
+            if let Err(e) = self.bot.edit_message_text(message.chat.id, message.message_id, &text, None).await {
+                warn!("Failed to update Telegram approval message: {}", e);
+            }
+        }
+    }
+
+    /// Send approval requests posted by the job scheduler as messages with buttons
+    async fn forward_approvals(self) {
+        let mut rx = NotificationService::subscribe();
+        loop {
+            let notification = match rx.recv().await {
+                Ok(NotificationEvent::Created { notification }) => notification,
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let Some(data) = notification.data.as_ref() else { continue };
+            if data["approval"].as_str() != Some(APPROVAL_JOB_APPLICATION) {
+                continue;
+            }
+            let Some(vacancy_id) = data["vacancy_id"].as_i64() else { continue };
+
+            let text = format!("<b>{}</b>\n{}", escape(&notification.title), escape(&notification.message));
+            let keyboard = approval_keyboard(vacancy_id, data["url"].as_str());
+            if let Err(e) = self.bot.send_keyboard(self.admin_id, &text, &keyboard).await {
+                warn!("Failed to send approval request to Telegram: {}", e);
+            }
+        }
+    }
+
+    async fn status(&self) -> String {
+        let mut lines = vec!["<b>Статус</b>".to_string()];
+
+        lines.push(match CommandQueueService::online_clients(&self.pool, PC_ONLINE_WINDOW_SECS).await {
+            Ok(clients) if clients.is_empty() => "🖥 ПК: не в сети".to_string(),
+            Ok(clients) => format!(
+                "🖥 ПК в сети: {}",
+                clients.iter().map(|(_, name)| escape(name)).collect::<Vec<_>>().join(", ")
+            ),
+            Err(e) => format!("🖥 ПК: ошибка ({})", escape(&e.to_string())),
+        });
+
+        if let Some(snapshot) = self.metrics.latest() {
+            let metric = |name: &str| {
+                snapshot
+                    .values
+                    .get(name)
+                    .map(|v| format!("{:.0}%", v))
+                    .unwrap_or_else(|| "—".to_string())
+            };
+            lines.push(format!(
+                "📊 CPU {} · RAM {} · диск {}",
+                metric("cpu_percent"),
+                metric("memory_percent"),
+                metric("disk_percent")
+            ));
+        }
+
+        if let Ok(summary) = NotificationService::unread_summary(&self.pool).await {
+            lines.push(format!("🔔 Непрочитанных уведомлений: {}", summary.total));
+        }
+
+        if let Some(jobs) = &self.jobs {
+            lines.push(format!(
+                "💼 Автопоиск работы: {}",
+                if jobs.is_running() { "включён" } else { "остановлен" }
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Wake-on-LAN for every client with a MAC address, or the one named in `target`
+    async fn wake(&self, target: &str) -> String {
+        let clients = match CommandQueueService::get_clients(&self.pool).await {
+            Ok(clients) => clients,
+            Err(e) => return format!("❌ {}", escape(&e.to_string())),
+        };
+
+        let mut results = Vec::new();
+        for client in clients {
+            if !target.is_empty() && client.client_id != target && !client.client_name.eq_ignore_ascii_case(target) {
+                continue;
+            }
+            let Some(mac) = client.mac_address.as_deref().filter(|m| !m.is_empty()) else { continue };
+            results.push(match send_wol(mac) {
+                Ok(()) => format!("⚡ {}: пакет отправлен", escape(&client.client_name)),
+                Err(e) => format!("❌ {}: {}", escape(&client.client_name), escape(&e.to_string())),
+            });
+        }
+
+        if results.is_empty() {
+            "Нет ПК с MAC-адресом".to_string()
+        } else {
+            results.join("\n")
+        }
+    }
+
+    async fn lock(&self, target: &str) -> String {
+        let target = CommandTarget {
+            client_id: Some(target.to_string()).filter(|t| !t.is_empty()),
+            group: None,
+        };
+        match CommandQueueService::queue_command(&self.pool, &PcCommandType::Lock, 10, &target, &CommandDelivery::default())
+            .await
+        {
+            Ok(id) => format!("🔒 Команда блокировки отправлена (ID: {})", id),
+            Err(e) => format!("❌ {}", escape(&e)),
+        }
+    }
+
+    /// Queue a screenshot; it follows as a photo once the PC has uploaded it
+    async fn screenshot(&self, chat_id: i64, target: &str) -> String {
+        if !matches!(self.pool, DbPool::Sqlite(_)) {
+            return "Скриншоты недоступны (PostgreSQL)".to_string();
+        }
+        let target = CommandTarget {
+            client_id: Some(target.to_string()).filter(|t| !t.is_empty()),
+            group: None,
+        };
+        match CommandQueueService::queue_command(&self.pool, &PcCommandType::Screenshot, 10, &target, &CommandDelivery::default())
+            .await
+        {
+            Ok(id) => {
+                tokio::spawn(self.clone().deliver_screenshot(chat_id, id));
+                format!("📸 Скриншот запрошен (ID: {})", id)
+            }
+            Err(e) => format!("❌ {}", escape(&e)),
+        }
+    }
+
+    /// Wait for the screenshot command to finish, then send its artifact
+    async fn deliver_screenshot(self, chat_id: i64, command_id: i64) {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(SCREENSHOT_WAIT_SECS);
+        let outcome = loop {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            match CommandQueueService::get_command(&self.pool, command_id).await {
+                Ok(Some(command)) if command.status == "completed" => {
+                    break self.send_artifact_to(chat_id, command_id).await;
+                }
+                Ok(Some(command)) if matches!(command.status.as_str(), "failed" | "dead" | "cancelled") => {
+                    break Err(command.error.unwrap_or(command.status));
+                }
+                Ok(None) => break Err("Команда удалена".to_string()),
+                Ok(Some(_)) => {}
+                Err(e) => warn!("Failed to check screenshot command {}: {}", command_id, e),
+            }
+            if tokio::time::Instant::now() >= deadline {
+                break Err(format!("ПК не прислал скриншот за {} с", SCREENSHOT_WAIT_SECS));
+            }
+        };
+
+        if let Err(e) = outcome {
+            let text = format!("❌ Скриншот {}: {}", command_id, escape(&e));
+            if let Err(e) = self.bot.send_message(chat_id, &text).await {
+                warn!("Failed to report screenshot failure: {}", e);
+            }
+        }
+    }
+
+    /// Send the artifact of a PC command (e.g. a screenshot) to the admin
+    pub async fn send_artifact(&self, command_id: i64) -> Result<(), String> {
+        self.send_artifact_to(self.admin_id, command_id).await
+    }
+
+    async fn send_artifact_to(&self, chat_id: i64, command_id: i64) -> Result<(), String> {
+        let (file, data) = CommandQueueService::load_artifact(&self.pool, command_id)
+            .await?
+            .ok_or_else(|| "ПК не загрузил файл (старая версия клиента?)".to_string())?;
+        if file.size > MAX_DOCUMENT_BYTES {
+            return Err("Файл больше 50 МБ".to_string());
+        }
+
+        let caption = format!("📸 Команда {} · {}", command_id, escape(&file.name));
+        let sent = if file.mime_type.starts_with("image/") && file.size <= MAX_PHOTO_BYTES {
+            self.bot.send_photo(chat_id, data, &file.name, Some(&caption)).await
+        } else {
+            self.bot.send_document(chat_id, data, &file.name, Some(&caption)).await
+        };
+        sent.map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    /// Today's numbers; each pending approval follows as its own message with buttons
+    async fn jobs(&self, chat_id: i64) -> String {
+        let Some(jobs) = &self.jobs else {
+            return "Автопоиск работы недоступен".to_string();
+        };
+
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let counts: Result<(i64, i64), sqlx::Error> = match &self.pool {
+            DbPool::Sqlite(p) => {
+                sqlx::query_as(
+                    "SELECT (SELECT COUNT(*) FROM job_vacancies WHERE date(found_at) = ?),
+                            (SELECT COUNT(*) FROM job_vacancies WHERE date(applied_at) = ?)"
+                )
+                .bind(&today)
+                .bind(&today)
+                .fetch_one(p)
+                .await
+            }
+            DbPool::Postgres(p) => {
+                sqlx::query_as(
+                    "SELECT (SELECT COUNT(*) FROM job_vacancies WHERE found_at::date = $1::date),
+                            (SELECT COUNT(*) FROM job_vacancies WHERE applied_at::date = $1::date)"
+                )
+                .bind(&today)
+                .fetch_one(p)
+                .await
+            }
+        };
+        let pending_sql = "SELECT id, title, company, ai_score, url FROM job_vacancies WHERE status = 'pending_approval' ORDER BY id DESC LIMIT 10";
+        let pending: Vec<(i32, String, String, Option<i32>, String)> = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query_as(pending_sql).fetch_all(p).await,
+            DbPool::Postgres(p) => sqlx::query_as(pending_sql).fetch_all(p).await,
+        }
+        .unwrap_or_default();
+
+        let mut lines = vec![format!(
+            "<b>Автопоиск работы</b>: {}",
+            if jobs.is_running() { "включён" } else { "остановлен" }
+        )];
+        match counts {
+            Ok((found, applied)) => lines.push(format!("Сегодня найдено {}, откликов {}", found, applied)),
+            Err(e) => lines.push(format!("❌ {}", escape(&e.to_string()))),
+        }
+        lines.push(format!("Ждут подтверждения: {}", pending.len()));
+
+        let bot = self.bot.clone();
+        tokio::spawn(async move {
+            // After the summary, so the buttons come last
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            for (id, title, company, ai_score, url) in pending {
+                let text = format!(
+                    "<b>{}</b>\n{} (AI: {}%)",
+                    escape(&title),
+                    escape(&company),
+                    ai_score.unwrap_or(0)
+                );
+                if let Err(e) = bot.send_keyboard(chat_id, &text, &approval_keyboard(id as i64, Some(&url))).await {
+                    warn!("Failed to send pending vacancy to Telegram: {}", e);
+                }
+            }
+        });
+
+        lines.join("\n")
+    }
+
+    async fn links(&self) -> String {
+        let sql = "SELECT l.name, l.short_code, l.is_active, COUNT(c.id) AS clicks
+                   FROM short_links l LEFT JOIN link_clicks c ON c.link_id = l.id
+                   GROUP BY l.id, l.name, l.short_code, l.is_active
+                   ORDER BY clicks DESC, l.name LIMIT 15";
+        let links: Result<Vec<(String, String, bool, i64)>, sqlx::Error> = match &self.pool {
+            DbPool::Sqlite(p) => sqlx::query_as(sql).fetch_all(p).await,
+            DbPool::Postgres(p) => sqlx::query_as(sql).fetch_all(p).await,
+        };
+
+        match links {
+            Ok(links) if links.is_empty() => "Коротких ссылок нет".to_string(),
+            Ok(links) => {
+                let base_url = env::var("BASE_URL").unwrap_or_else(|_| "https://bgalin.ru".to_string());
+                let mut lines = vec!["<b>Короткие ссылки</b>".to_string()];
+                for (name, code, is_active, clicks) in links {
+                    lines.push(format!(
+                        "{} {} — {}/l/{} · {} переходов",
+                        if is_active { "🔗" } else { "⛔" },
+                        escape(&name),
+                        base_url,
+                        code,
+                        clicks
+                    ));
+                }
+                lines.join("\n")
+            }
+            Err(e) => format!("❌ {}", escape(&e.to_string())),
+        }
+    }
+
+    async fn sync_folders(&self) -> String {
+        let DbPool::Sqlite(pool) = &self.pool else {
+            return "Синхронизация недоступна (PostgreSQL)".to_string();
+        };
+        match SyncService::list_folders(pool).await {
+            Ok(folders) if folders.is_empty() => "Папок синхронизации нет".to_string(),
+            Ok(folders) => {
+                let mut lines = vec!["<b>Синхронизация</b>".to_string()];
+                for folder in folders {
+                    lines.push(format!(
+                        "📁 {} — {} файлов, {:.1} МБ, клиентов: {}",
+                        escape(&folder.name),
+                        folder.file_count,
+                        folder.total_size as f64 / 1_048_576.0,
+                        folder.client_count
+                    ));
+                }
+                lines.join("\n")
+            }
+            Err(e) => format!("❌ {}", escape(&e)),
+        }
+    }
+
+    /// Send a file from the file manager: images as photos, the rest as documents
+    async fn send_file(&self, chat_id: i64, file_id: &str) -> Result<(), String> {
+        if file_id.is_empty() {
+            return Err("Укажите ID файла: /file <id>".to_string());
+        }
+        let DbPool::Sqlite(pool) = &self.pool else {
+            return Err("Файловый менеджер недоступен (PostgreSQL)".to_string());
+        };
+        let file = FileService::get_file(pool, file_id)
+            .await?
+            .ok_or_else(|| "Файл не найден".to_string())?;
+        if file.size > MAX_DOCUMENT_BYTES {
+            return Err("Файл больше 50 МБ".to_string());
+        }
+
+        let data = FileService::get_file_data(&file.id).await?;
+        let sent = if file.mime_type.starts_with("image/") && file.size <= MAX_PHOTO_BYTES {
+            self.bot.send_photo(chat_id, data, &file.name, None).await
+        } else {
+            self.bot.send_document(chat_id, data, &file.name, None).await
+        };
+        sent.map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+fn approval_keyboard(vacancy_id: i64, url: Option<&str>) -> InlineKeyboardMarkup {
+    let keyboard = InlineKeyboardMarkup::default().row(vec![
+        InlineKeyboardButton::callback("✅ Откликнуться", format!("job:approve:{}", vacancy_id)),
+        InlineKeyboardButton::callback("⏭️ Пропустить", format!("job:skip:{}", vacancy_id)),
+    ]);
+    match url.filter(|u| !u.is_empty()) {
+        Some(url) => keyboard.row(vec![InlineKeyboardButton::url("Открыть вакансию", url)]),
+        None => keyboard,
+    }
+}
+
+/// Escape text for `parse_mode: HTML`
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, HttpStub};
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+
+    const ADMIN: i64 = 1001;
+
+    /// Fake Bot API answering every method the way Telegram does
+    async fn fake_api() -> HttpStub {
+        HttpStub::start(|request| {
+            let result = if request.path.ends_with("/sendMessage") {
+                json!({"message_id": 1, "chat": {"id": request.json()["chat_id"]}, "text": request.json()["text"]})
+            } else {
+                json!(true)
+            };
+            (200, json!({"ok": true, "result": result}).to_string())
+        })
+        .await
+    }
+
+    async fn service(api: &HttpStub) -> (TempDir, TelegramBotService) {
+        let (dir, pool) = test_support::sqlite_pool().await;
+        let bot = {
+            let _env = test_support::env_lock();
+            env::set_var("TELEGRAM_API_URL", &api.url);
+            TelegramBot::new("TOKEN".to_string())
+        };
+        let jobs = JobScheduler::new(pool.clone());
+        let metrics = MetricsService::init(pool.clone()).await;
+        (dir, TelegramBotService::new(bot, ADMIN, pool, Some(jobs), metrics))
+    }
+
+    fn message(from: i64, text: &str) -> Update {
+        serde_json::from_value(json!({
+            "update_id": 1,
+            "message": {"message_id": 10, "from": {"id": from}, "chat": {"id": from}, "text": text},
+        }))
+        .unwrap()
+    }
+
+    fn sent_texts(api: &HttpStub) -> Vec<Value> {
+        api.hits("/botTOKEN/sendMessage").iter().map(|r| r.json()).collect()
+    }
+
+    async fn insert_pending_vacancy(service: &TelegramBotService) -> i32 {
+        let pool = service.pool.as_sqlite().unwrap();
+        sqlx::query(
+            "INSERT INTO job_vacancies (hh_vacancy_id, title, company, url, status) VALUES ('42', 'Rust developer', 'Acme', 'https://hh.ru/vacancy/42', 'pending_approval')",
+        )
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_rowid() as i32
+    }
+
+    async fn vacancy_status(service: &TelegramBotService, id: i32) -> String {
+        sqlx::query_scalar("SELECT status FROM job_vacancies WHERE id = ?")
+            .bind(id)
+            .fetch_one(service.pool.as_sqlite().unwrap())
+            .await
+            .unwrap()
+    }
+
+    /// Press the button of `approval_keyboard` whose data starts with `prefix`
+    fn press(vacancy_id: i32, prefix: &str, from: i64) -> Update {
+        let keyboard = json!(approval_keyboard(vacancy_id as i64, Some("https://hh.ru/vacancy/42")));
+        let data = keyboard["inline_keyboard"][0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|b| b["callback_data"].as_str())
+            .find(|d| d.starts_with(prefix))
+            .unwrap()
+            .to_string();
+        serde_json::from_value(json!({
+            "update_id": 2,
+            "callback_query": {
+                "id": "cb",
+                "from": {"id": from},
+                "message": {"message_id": 20, "chat": {"id": ADMIN}, "text": "<Rust developer>"},
+                "data": data,
+            },
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn messages_from_others_are_ignored() {
+        let api = fake_api().await;
+        let (_dir, service) = service(&api).await;
+
+        service.handle_update(message(7, "/status")).await;
+        service.handle_update(message(7, "/lock")).await;
+
+        assert!(api.requests().is_empty());
+        let queued: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM alice_command_queue")
+            .fetch_one(service.pool.as_sqlite().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(queued, 0);
+    }
+
+    #[tokio::test]
+    async fn status_command_answers_with_status() {
+        let api = fake_api().await;
+        let (_dir, service) = service(&api).await;
+
+        service.handle_update(message(ADMIN, "/status@bgalin_bot")).await;
+
+        let sent = sent_texts(&api);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["chat_id"], ADMIN);
+        let text = sent[0]["text"].as_str().unwrap();
+        assert!(text.starts_with("<b>Статус</b>"), "{}", text);
+        assert!(text.contains("ПК: не в сети"), "{}", text);
+        assert!(text.contains("Автопоиск работы: остановлен"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn jobs_command_sends_summary_then_pending_vacancies_with_buttons() {
+        let api = fake_api().await;
+        let (_dir, service) = service(&api).await;
+        let vacancy_id = insert_pending_vacancy(&service).await;
+
+        service.handle_update(message(ADMIN, "/jobs")).await;
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let sent = sent_texts(&api);
+        assert_eq!(sent.len(), 2);
+        let summary = sent[0]["text"].as_str().unwrap();
+        assert!(summary.starts_with("<b>Автопоиск работы</b>"), "{}", summary);
+        assert!(summary.contains("Ждут подтверждения: 1"), "{}", summary);
+        assert!(sent[1]["text"].as_str().unwrap().contains("Rust developer"));
+        assert_eq!(
+            sent[1]["reply_markup"]["inline_keyboard"][0][0]["callback_data"],
+            format!("job:approve:{}", vacancy_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn skip_button_declines_the_vacancy_and_replaces_the_buttons() {
+        let api = fake_api().await;
+        let (_dir, service) = service(&api).await;
+        let vacancy_id = insert_pending_vacancy(&service).await;
+
+        service.handle_update(press(vacancy_id, "job:skip:", ADMIN)).await;
+
+        assert_eq!(vacancy_status(&service, vacancy_id).await, "ignored");
+        assert_eq!(api.hits("/answerCallbackQuery")[0].json()["callback_query_id"], "cb");
+        let edited = api.hits("/editMessageText");
+        assert_eq!(edited.len(), 1);
+        let edited = edited[0].json();
+        assert_eq!(edited["message_id"], 20);
+        assert_eq!(edited["text"], "&lt;Rust developer&gt;\n\n⏭️ Пропущено");
+        assert!(edited.get("reply_markup").is_none());
+
+        // A second press finds nothing left to decide
+        service.handle_update(press(vacancy_id, "job:skip:", ADMIN)).await;
+        assert!(api.hits("/editMessageText")[1].json()["text"].as_str().unwrap().contains("❌"));
+    }
+
+    #[tokio::test]
+    async fn approve_button_claims_the_vacancy_and_releases_it_when_applying_fails() {
+        let api = fake_api().await;
+        let (_dir, service) = service(&api).await;
+        let vacancy_id = insert_pending_vacancy(&service).await;
+
+        // No HH.ru account is linked, so the application itself fails
+        service.handle_update(press(vacancy_id, "job:approve:", ADMIN)).await;
+
+        assert_eq!(vacancy_status(&service, vacancy_id).await, "pending_approval");
+        assert_eq!(api.hits("/answerCallbackQuery")[0].json()["text"], "Отправляю отклик…");
+        assert!(api.hits("/editMessageText")[0].json()["text"].as_str().unwrap().contains("❌"));
+    }
+
+    #[tokio::test]
+    async fn buttons_pressed_by_others_change_nothing() {
+        let api = fake_api().await;
+        let (_dir, service) = service(&api).await;
+        let vacancy_id = insert_pending_vacancy(&service).await;
+
+        service.handle_update(press(vacancy_id, "job:skip:", 7)).await;
+
+        assert_eq!(vacancy_status(&service, vacancy_id).await, "pending_approval");
+        assert_eq!(api.hits("/answerCallbackQuery")[0].json()["text"], "Нет доступа");
+        assert!(api.hits("/editMessageText").is_empty());
+    }
+}
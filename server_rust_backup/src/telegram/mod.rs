@@ -1,50 +1,216 @@
+pub mod models;
+pub mod bot;
+
+pub use models::*;
+pub use bot::TelegramBotService;
+
+use crate::telemetry::RequestIdExt;
+use reqwest::multipart::{Form, Part};
 use reqwest::Client;
-use serde_json::json;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
 
+pub type TelegramResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Clone)]
 pub struct TelegramBot {
     bot_token: String,
+    /// `TELEGRAM_API_URL`, e.g. a local fake API server in tests
+    api_url: String,
     client: Client,
 }
 
 impl TelegramBot {
     pub fn new(bot_token: String) -> Self {
+        let api_url = std::env::var("TELEGRAM_API_URL")
+            .unwrap_or_else(|_| "https://api.telegram.org".to_string())
+            .trim_end_matches('/')
+            .to_string();
         Self {
             bot_token,
+            api_url,
             client: Client::new(),
         }
     }
 
-    pub async fn send_message(
-        &self,
-        chat_id: i64,
-        text: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+    fn method_url(&self, method: &str) -> String {
+        format!("{}/bot{}/{}", self.api_url, self.bot_token, method)
+    }
 
+    async fn parse_reply<T: DeserializeOwned>(response: reqwest::Response) -> TelegramResult<T> {
+        let status = response.status();
+        let body = response.text().await?;
+        let reply: ApiReply<T> = serde_json::from_str(&body)
+            .map_err(|_| format!("Telegram API error: {} {}", status, body))?;
+        match reply.result {
+            Some(result) if reply.ok => Ok(result),
+            _ => Err(format!(
+                "Telegram API error: {}",
+                reply.description.unwrap_or_else(|| status.to_string())
+            )
+            .into()),
+        }
+    }
+
+    /// Call a Bot API method with a JSON payload
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, payload: &Value) -> TelegramResult<T> {
         let response = self
             .client
-            .post(&url)
-            .json(&json!({
+            .post(self.method_url(method))
+            .json(payload)
+            .with_request_id()
+            .send()
+            .await?;
+        Self::parse_reply(response).await
+    }
+
+    pub async fn send_message(&self, chat_id: i64, text: &str) -> TelegramResult<()> {
+        self.call::<Message>(
+            "sendMessage",
+            &json!({
                 "chat_id": chat_id,
                 "text": text,
                 "parse_mode": "HTML"
-            }))
-            .send()
-            .await?;
+            }),
+        )
+        .await?;
+        Ok(())
+    }
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(format!("Telegram API error: {}", error_text).into());
+    pub async fn send_keyboard(
+        &self,
+        chat_id: i64,
+        text: &str,
+        keyboard: &InlineKeyboardMarkup,
+    ) -> TelegramResult<Message> {
+        self.call(
+            "sendMessage",
+            &json!({
+                "chat_id": chat_id,
+                "text": text,
+                "parse_mode": "HTML",
+                "reply_markup": keyboard
+            }),
+        )
+        .await
+    }
+
+    /// Replace the text of a sent message; the keyboard is removed unless given again
+    pub async fn edit_message_text(
+        &self,
+        chat_id: i64,
+        message_id: i64,
+        text: &str,
+        keyboard: Option<&InlineKeyboardMarkup>,
+    ) -> TelegramResult<()> {
+        let mut payload = json!({
+            "chat_id": chat_id,
+            "message_id": message_id,
+            "text": text,
+            "parse_mode": "HTML"
+        });
+        if let Some(keyboard) = keyboard {
+            payload["reply_markup"] = json!(keyboard);
         }
+        self.call::<Value>("editMessageText", &payload).await?;
+        Ok(())
+    }
+
+    /// Stop the spinner on a pressed button, optionally with a toast
+    pub async fn answer_callback_query(&self, callback_query_id: &str, text: Option<&str>) -> TelegramResult<()> {
+        self.call::<bool>(
+            "answerCallbackQuery",
+            &json!({
+                "callback_query_id": callback_query_id,
+                "text": text
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Long poll for updates after `offset`
+    pub async fn get_updates(&self, offset: i64, timeout_secs: u64) -> TelegramResult<Vec<Update>> {
+        self.call(
+            "getUpdates",
+            &json!({
+                "offset": offset,
+                "timeout": timeout_secs,
+                "allowed_updates": ["message", "callback_query"]
+            }),
+        )
+        .await
+    }
+
+    /// Register `url` for updates; Telegram sends `secret_token` in `X-Telegram-Bot-Api-Secret-Token`
+    pub async fn set_webhook(&self, url: &str, secret_token: &str) -> TelegramResult<()> {
+        self.call::<bool>(
+            "setWebhook",
+            &json!({
+                "url": url,
+                "secret_token": secret_token,
+                "allowed_updates": ["message", "callback_query"]
+            }),
+        )
+        .await?;
+        Ok(())
+    }
 
+    /// Required before long polling if a webhook was set earlier
+    pub async fn delete_webhook(&self) -> TelegramResult<()> {
+        self.call::<bool>("deleteWebhook", &json!({})).await?;
         Ok(())
     }
 
-    pub async fn send_otp_code(
+    async fn send_file(
         &self,
+        method: &str,
+        field: &str,
         chat_id: i64,
-        code: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        data: Vec<u8>,
+        file_name: &str,
+        caption: Option<&str>,
+    ) -> TelegramResult<Message> {
+        let mut form = Form::new()
+            .text("chat_id", chat_id.to_string())
+            .part(field.to_string(), Part::bytes(data).file_name(file_name.to_string()));
+        if let Some(caption) = caption {
+            form = form.text("caption", caption.to_string()).text("parse_mode", "HTML");
+        }
+
+        let response = self
+            .client
+            .post(self.method_url(method))
+            .multipart(form)
+            .with_request_id()
+            .send()
+            .await?;
+        Self::parse_reply(response).await
+    }
+
+    /// Send an image shown inline; Telegram accepts photos up to 10 MB
+    pub async fn send_photo(
+        &self,
+        chat_id: i64,
+        data: Vec<u8>,
+        file_name: &str,
+        caption: Option<&str>,
+    ) -> TelegramResult<Message> {
+        self.send_file("sendPhoto", "photo", chat_id, data, file_name, caption).await
+    }
+
+    /// Send any file as an attachment, up to 50 MB
+    pub async fn send_document(
+        &self,
+        chat_id: i64,
+        data: Vec<u8>,
+        file_name: &str,
+        caption: Option<&str>,
+    ) -> TelegramResult<Message> {
+        self.send_file("sendDocument", "document", chat_id, data, file_name, caption).await
+    }
+
+    pub async fn send_otp_code(&self, chat_id: i64, code: &str) -> TelegramResult<()> {
         let message = format!(
             "🔐 <b>Код для входа:</b>\n\n<code>{}</code>\n\nКод действителен 5 минут.",
             code
@@ -0,0 +1,5 @@
+pub mod request_id;
+pub mod subscriber;
+
+pub use request_id::*;
+pub use subscriber::init;
@@ -0,0 +1,140 @@
+use rocket::data::Data;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome as GuardOutcome};
+use rocket::route::{Handler, Outcome};
+use rocket::{Request, Response, Route};
+use std::future::Future;
+use std::time::Instant;
+use tracing::Instrument;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// Correlation ID of the current request: the client's `X-Request-Id` when it is sane, otherwise a new UUID
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn of(req: &Request<'_>) -> RequestId {
+        req.local_cache(|| {
+            let id = req
+                .headers()
+                .get_one(REQUEST_ID_HEADER)
+                .map(str::trim)
+                .filter(|id| {
+                    !id.is_empty()
+                        && id.len() <= 64
+                        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                })
+                .map(str::to_string)
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            RequestId(id)
+        })
+        .clone()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> GuardOutcome<Self, Self::Error> {
+        GuardOutcome::Success(RequestId::of(req))
+    }
+}
+
+/// Request ID of the task being run, set for route handlers and scheduler cycles
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Run `future` with `id` as the current request ID
+pub async fn with_request_id<F: Future>(id: String, future: F) -> F::Output {
+    CURRENT_REQUEST_ID.scope(id, future).await
+}
+
+/// Forward the current request ID to outgoing HTTP calls
+pub trait RequestIdExt {
+    fn with_request_id(self) -> Self;
+}
+
+impl RequestIdExt for reqwest::RequestBuilder {
+    fn with_request_id(self) -> Self {
+        match current_request_id() {
+            Some(id) => self.header(REQUEST_ID_HEADER, id),
+            None => self,
+        }
+    }
+}
+
+/// Route handler that runs inside a `request` span carrying the request ID
+#[derive(Clone)]
+struct TracedHandler(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for TracedHandler {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let id = RequestId::of(req).0;
+        let span = tracing::info_span!(
+            "request",
+            request_id = %id,
+            method = %req.method(),
+            route = %req.route().map(|r| r.uri.as_str()).unwrap_or_default(),
+        );
+        with_request_id(id, self.0.handle(req, data).instrument(span)).await
+    }
+}
+
+/// Wrap mounted routes so everything they log and call carries the request ID
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(TracedHandler(route.handler));
+            route
+        })
+        .collect()
+}
+
+/// When the request reached the server
+struct RequestStarted(Option<Instant>);
+
+/// Assigns request IDs, echoes them in `X-Request-Id` and writes one access log line per request
+pub struct RequestIdFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ID",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        RequestId::of(req);
+        req.local_cache(|| RequestStarted(Some(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let id = RequestId::of(req).0;
+        let elapsed_ms = req
+            .local_cache(|| RequestStarted(None))
+            .0
+            .map(|started| started.elapsed().as_millis() as u64)
+            .unwrap_or(0);
+        let status = res.status().code;
+
+        if status >= 500 {
+            tracing::error!(request_id = %id, method = %req.method(), path = %req.uri().path(), status, elapsed_ms, "request failed");
+        } else {
+            tracing::info!(request_id = %id, method = %req.method(), path = %req.uri().path(), status, elapsed_ms, "request completed");
+        }
+
+        res.set_header(Header::new(REQUEST_ID_HEADER, id));
+    }
+}
@@ -0,0 +1,93 @@
+use std::env;
+use std::path::Path;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+/// Base name of the rolling log files; the log viewer reads the same files back
+pub const DEFAULT_LOG_FILE: &str = "./logs/server.log";
+const DEFAULT_FILTER: &str = "info,rocket=warn,hyper=warn,sqlx=warn";
+
+/// Install the global subscriber.
+///
+/// `LOG_LEVEL` (or `RUST_LOG`) is an `EnvFilter` directive; `LOG_FORMAT=text|pretty|json` controls stdout.
+/// Files are written next to `LOG_FILE` as `server.<date>.log` in `LOG_FILE_FORMAT` (json by default),
+/// rotated by `LOG_ROTATION=daily|hourly|never` and limited to `LOG_MAX_FILES`.
+/// The returned guard flushes the file writer and must live until shutdown.
+pub fn init() -> Option<WorkerGuard> {
+    let filter = || {
+        env::var("LOG_LEVEL")
+            .or_else(|_| env::var("RUST_LOG"))
+            .ok()
+            .and_then(|directives| EnvFilter::try_new(directives).ok())
+            .unwrap_or_else(|| EnvFilter::new(DEFAULT_FILTER))
+    };
+
+    let stdout = match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => fmt::layer().json().flatten_event(true).with_current_span(true).boxed(),
+        Ok("pretty") => fmt::layer().pretty().boxed(),
+        _ => fmt::layer().with_target(false).boxed(),
+    };
+
+    let (file, guard) = match file_appender() {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let json_file = env::var("LOG_FILE_FORMAT").map(|f| f != "text").unwrap_or(true);
+            let layer = if json_file {
+                fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(true)
+                    .with_writer(writer)
+                    .boxed()
+            } else {
+                fmt::layer().with_ansi(false).with_writer(writer).boxed()
+            };
+            (Some(layer.with_filter(filter())), Some(guard))
+        }
+        Err(e) => {
+            eprintln!("File logging disabled: {}", e);
+            (None, None)
+        }
+    };
+
+    let installed = tracing_subscriber::registry()
+        .with(stdout.with_filter(filter()))
+        .with(file)
+        .try_init();
+    if let Err(e) = installed {
+        eprintln!("Tracing subscriber already installed: {}", e);
+    }
+
+    guard
+}
+
+fn file_appender() -> Result<RollingFileAppender, String> {
+    let path = env::var("LOG_FILE").unwrap_or_else(|_| DEFAULT_LOG_FILE.to_string());
+    let path = Path::new(&path);
+    let directory = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let prefix = path.file_stem().and_then(|s| s.to_str()).unwrap_or("server");
+    let suffix = path.extension().and_then(|s| s.to_str()).unwrap_or("log");
+
+    let rotation = match env::var("LOG_ROTATION").as_deref() {
+        Ok("hourly") => Rotation::HOURLY,
+        Ok("never") => Rotation::NEVER,
+        _ => Rotation::DAILY,
+    };
+    let max_files = env::var("LOG_MAX_FILES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(14)
+        .max(1);
+
+    std::fs::create_dir_all(directory).map_err(|e| format!("{}: {}", directory.display(), e))?;
+    RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(prefix)
+        .filename_suffix(suffix)
+        .max_log_files(max_files)
+        .build(directory)
+        .map_err(|e| e.to_string())
+}